@@ -6,11 +6,14 @@
 //! - Pipeline operations
 //! - Memory efficiency
 
+use aikv::command::{ClientContext, CommandExecutor};
 use aikv::protocol::parser::RespParser;
 use aikv::protocol::types::RespValue;
+use aikv::storage::StoredValue;
 use aikv::StorageEngine;
 use bytes::Bytes;
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::collections::VecDeque;
 use std::sync::Arc;
 
 /// Benchmark concurrent storage operations
@@ -110,6 +113,82 @@ fn bench_large_values(c: &mut Criterion) {
     group.finish();
 }
 
+/// Benchmark reading a small range from large collections, comparing the
+/// cloning `get_value` path with the borrowing `with_value` path
+fn bench_large_collection_reads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("large_collection_reads");
+
+    for size in [1_000, 100_000, 1_000_000].iter() {
+        let storage = StorageEngine::new_memory(16);
+        let list: VecDeque<Bytes> = (0..*size).map(|i| Bytes::from(i.to_string())).collect();
+        storage
+            .set_value(0, "large_list".to_string(), StoredValue::new_list(list))
+            .unwrap();
+
+        group.bench_with_input(BenchmarkId::new("get_value", size), size, |b, _| {
+            b.iter(|| {
+                let stored = storage.get_value(0, black_box("large_list")).unwrap();
+                let list = stored.unwrap();
+                let range: Vec<Bytes> = list.as_list().unwrap().iter().take(10).cloned().collect();
+                black_box(range)
+            });
+        });
+
+        group.bench_with_input(BenchmarkId::new("with_value", size), size, |b, _| {
+            b.iter(|| {
+                let range = storage
+                    .with_value(0, black_box("large_list"), |v| {
+                        Ok(v.as_list()?
+                            .iter()
+                            .take(10)
+                            .cloned()
+                            .collect::<Vec<Bytes>>())
+                    })
+                    .unwrap();
+                black_box(range)
+            });
+        });
+    }
+
+    group.finish();
+}
+
+/// Benchmark LSET, LINSERT and LREM on large lists, which edit the stored
+/// list in place rather than copying it on every write
+fn bench_large_list_writes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("large_list_writes");
+
+    for size in [1_000, 100_000].iter() {
+        let storage = StorageEngine::new_memory(16);
+        let list: VecDeque<Bytes> = (0..*size).map(|i| Bytes::from(i.to_string())).collect();
+        storage
+            .set_value(0, "large_list".to_string(), StoredValue::new_list(list))
+            .unwrap();
+        let executor = CommandExecutor::new(storage);
+        let mut client = ClientContext::new(0);
+        let args = |args: &[&str]| -> Vec<Bytes> {
+            args.iter().map(|a| Bytes::from(a.to_string())).collect()
+        };
+
+        let lset = args(&["large_list", "20", "value"]);
+        group.bench_with_input(BenchmarkId::new("lset", size), size, |b, _| {
+            b.iter(|| black_box(executor.execute("LSET", &lset, &mut client).unwrap()));
+        });
+
+        // Insert next to the head and remove again, so the list stays the same size
+        let linsert = args(&["large_list", "AFTER", "10", "inserted"]);
+        let lrem = args(&["large_list", "1", "inserted"]);
+        group.bench_with_input(BenchmarkId::new("linsert_lrem", size), size, |b, _| {
+            b.iter(|| {
+                black_box(executor.execute("LINSERT", &linsert, &mut client).unwrap());
+                black_box(executor.execute("LREM", &lrem, &mut client).unwrap())
+            });
+        });
+    }
+
+    group.finish();
+}
+
 /// Benchmark RESP protocol parsing with various message sizes
 fn bench_resp_parsing_sizes(c: &mut Criterion) {
     let mut group = c.benchmark_group("resp_parsing_sizes");
@@ -290,6 +369,8 @@ criterion_group!(
     benches,
     bench_concurrent_operations,
     bench_large_values,
+    bench_large_collection_reads,
+    bench_large_list_writes,
     bench_resp_parsing_sizes,
     bench_batch_sizes,
     bench_memory_patterns,
@@ -19,10 +19,13 @@
 //! The client should update its slot-to-node mapping and redirect future requests
 //! for that slot to the correct node.
 
+use crate::command::help::{help_reply, unknown_subcommand};
 use crate::error::{AikvError, Result};
 use crate::protocol::RespValue;
+use crate::storage::StorageEngine;
 use bytes::Bytes;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
 use tracing::{debug, info};
 
 #[cfg(feature = "cluster")]
@@ -37,6 +40,11 @@ use openraft::BasicNode;
 /// Redis Cluster has 16384 slots
 const TOTAL_SLOTS: u16 = 16384;
 
+/// How long a manual failover waits for the replica to catch up, and then
+/// for it to win the election, before giving up
+#[cfg(feature = "cluster")]
+const FAILOVER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
 /// Extract the hash tag from a key.
 ///
 /// Redis Cluster implements a concept called hash tags that makes it possible
@@ -69,6 +77,36 @@ pub fn key_to_slot_with_hash_tag(key: &[u8]) -> u16 {
     Router::key_to_slot(hash_part)
 }
 
+/// Keys of the local keyspace that hash to `slot`, in key order, at most `count`
+///
+/// Cluster mode only uses database 0, so that's the keyspace scanned.
+pub fn keys_in_slot(storage: &StorageEngine, slot: u16, count: usize) -> Result<Vec<String>> {
+    let mut keys: Vec<String> = storage
+        .get_all_keys_in_db(0)?
+        .into_iter()
+        .filter(|key| key_to_slot_with_hash_tag(key.as_bytes()) == slot)
+        .collect();
+    keys.sort();
+    keys.truncate(count);
+    Ok(keys)
+}
+
+/// Parse the slot argument of CLUSTER COUNTKEYSINSLOT/GETKEYSINSLOT
+pub fn parse_slot(arg: &[u8]) -> Result<u16> {
+    String::from_utf8_lossy(arg)
+        .parse::<u16>()
+        .ok()
+        .filter(|&slot| slot < TOTAL_SLOTS)
+        .ok_or_else(|| AikvError::Invalid("Invalid slot".to_string()))
+}
+
+/// Parse the count argument of CLUSTER GETKEYSINSLOT
+pub fn parse_slot_key_count(arg: &[u8]) -> Result<usize> {
+    String::from_utf8_lossy(arg)
+        .parse::<usize>()
+        .map_err(|_| AikvError::Invalid("Invalid number of keys".to_string()))
+}
+
 /// Failover mode for CLUSTER FAILOVER command
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FailoverMode {
@@ -80,6 +118,74 @@ pub enum FailoverMode {
     Takeover,
 }
 
+impl FailoverMode {
+    /// Parse the optional FORCE|TAKEOVER argument of CLUSTER FAILOVER
+    pub fn parse(args: &[Bytes]) -> Result<Self> {
+        match args {
+            [] => Ok(FailoverMode::Default),
+            [mode] => match String::from_utf8_lossy(mode).to_uppercase().as_str() {
+                "FORCE" => Ok(FailoverMode::Force),
+                "TAKEOVER" => Ok(FailoverMode::Takeover),
+                _ => Err(AikvError::Invalid("syntax error".to_string())),
+            },
+            _ => Err(AikvError::WrongArgCount("CLUSTER FAILOVER".to_string())),
+        }
+    }
+}
+
+/// A slot's migration state on this node, set with CLUSTER SETSLOT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotState {
+    /// Keys are moving out of the slot to the given node
+    Migrating(NodeId),
+    /// Keys are moving into the slot from the given node
+    Importing(NodeId),
+}
+
+/// What CLUSTER SETSLOT does to a slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetSlotAction {
+    /// IMPORTING <node-id>: this node is receiving the slot from the node
+    Importing(NodeId),
+    /// MIGRATING <node-id>: this node is handing the slot over to the node
+    Migrating(NodeId),
+    /// STABLE: clear any migration state
+    Stable,
+    /// NODE <node-id>: assign the slot to the node, ending the migration
+    Node(NodeId),
+}
+
+impl SetSlotAction {
+    /// Parse the arguments of CLUSTER SETSLOT that follow the slot
+    pub fn parse(args: &[Bytes]) -> Result<Self> {
+        let invalid = || {
+            AikvError::Invalid(
+                "Invalid CLUSTER SETSLOT action or number of arguments. Try CLUSTER HELP"
+                    .to_string(),
+            )
+        };
+        let action = args
+            .first()
+            .map(|a| String::from_utf8_lossy(a).to_uppercase())
+            .ok_or_else(invalid)?;
+        match (action.as_str(), &args[1..]) {
+            ("STABLE", []) => Ok(SetSlotAction::Stable),
+            ("IMPORTING", [node_id]) => Ok(SetSlotAction::Importing(parse_node_id(node_id)?)),
+            ("MIGRATING", [node_id]) => Ok(SetSlotAction::Migrating(parse_node_id(node_id)?)),
+            ("NODE", [node_id]) => Ok(SetSlotAction::Node(parse_node_id(node_id)?)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Parse a node ID, given in hex as CLUSTER NODES prints it or in decimal
+fn parse_node_id(arg: &[u8]) -> Result<NodeId> {
+    let node_id = String::from_utf8_lossy(arg);
+    u64::from_str_radix(&node_id, 16)
+        .or_else(|_| node_id.parse::<u64>())
+        .map_err(|_| AikvError::Invalid("Invalid node ID".to_string()))
+}
+
 /// Redirection type for cluster routing
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RedirectType {
@@ -151,6 +257,13 @@ pub struct ClusterCommands {
 
     /// Optional migration manager for slot migration
     migration_manager: Option<Arc<MigrationManager>>,
+
+    /// Local keyspace scanned by COUNTKEYSINSLOT/GETKEYSINSLOT
+    storage: Option<StorageEngine>,
+
+    /// Slots being migrated out of or imported into this node. As in Redis
+    /// this is each node's own view, not part of the cluster metadata
+    slot_states: RwLock<HashMap<u16, SlotState>>,
 }
 
 #[cfg(feature = "cluster")]
@@ -175,6 +288,8 @@ impl ClusterCommands {
             multi_raft,
             router,
             migration_manager: None,
+            storage: None,
+            slot_states: RwLock::new(HashMap::new()),
         }
     }
 
@@ -183,6 +298,11 @@ impl ClusterCommands {
         self.migration_manager = Some(manager);
     }
 
+    /// Set the local keyspace scanned by COUNTKEYSINSLOT/GETKEYSINSLOT
+    pub fn set_storage(&mut self, storage: StorageEngine) {
+        self.storage = Some(storage);
+    }
+
     /// Handle CLUSTER INFO command.
     ///
     /// Maps to: `meta_raft.get_cluster_meta()`
@@ -302,6 +422,22 @@ impl ClusterCommands {
                 }
             }
 
+            // Slots in migration are listed after our own ranges
+            if *node_id == self.node_id {
+                let slot_states = self
+                    .slot_states
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner);
+                let mut migrating: Vec<_> = slot_states.iter().collect();
+                migrating.sort_unstable_by_key(|(slot, _)| **slot);
+                for (slot, state) in migrating {
+                    slot_ranges.push(match state {
+                        SlotState::Migrating(target) => format!("[{}->-{:040x}]", slot, target),
+                        SlotState::Importing(source) => format!("[{}-<-{:040x}]", slot, source),
+                    });
+                }
+            }
+
             // Format address properly: ip:data_port@cluster_bus_port
             // node_info.addr is like "aikv1:50051" (raft address), we need to convert to data port
             let data_addr = Self::extract_data_address(&node_info.addr);
@@ -845,28 +981,36 @@ impl ClusterCommands {
 
     /// Handle CLUSTER GETKEYSINSLOT command.
     ///
-    /// Maps to: `state_machine.scan_slot_keys_sync(group, slot)`
-    ///
-    /// Note: This requires access to ShardedStateMachine which we'll need to add
-    pub fn cluster_getkeysinslot(&self, slot: u16, _count: usize) -> Result<RespValue> {
+    /// Scans the local keyspace, computing each key's slot.
+    pub fn cluster_getkeysinslot(&self, slot: u16, count: usize) -> Result<RespValue> {
         if slot >= TOTAL_SLOTS {
             return Err(AikvError::Invalid(format!("Invalid slot: {}", slot)));
         }
 
-        // TODO: Implement using ShardedStateMachine.scan_slot_keys_sync()
-        // For now, return empty array
-        Ok(RespValue::Array(Some(vec![])))
+        let keys = match &self.storage {
+            Some(storage) => keys_in_slot(storage, slot, count)?,
+            None => Vec::new(),
+        };
+        Ok(RespValue::Array(Some(
+            keys.into_iter()
+                .map(|key| RespValue::BulkString(Some(Bytes::from(key))))
+                .collect(),
+        )))
     }
 
     /// Handle CLUSTER COUNTKEYSINSLOT command.
+    ///
+    /// Scans the local keyspace, computing each key's slot.
     pub fn cluster_countkeysinslot(&self, slot: u16) -> Result<RespValue> {
         if slot >= TOTAL_SLOTS {
             return Err(AikvError::Invalid(format!("Invalid slot: {}", slot)));
         }
 
-        // TODO: Implement using ShardedStateMachine
-        // For now, return 0
-        Ok(RespValue::Integer(0))
+        let count = match &self.storage {
+            Some(storage) => keys_in_slot(storage, slot, usize::MAX)?.len(),
+            None => 0,
+        };
+        Ok(RespValue::Integer(count as i64))
     }
 
     /// Handle CLUSTER SHARDS command (Redis 7.0+).
@@ -874,125 +1018,100 @@ impl ClusterCommands {
     /// Returns the mapping of cluster slots to shards in Redis 7.0+ format.
     /// This command is used by modern Redis clients (like RedisInsight) to detect cluster mode.
     ///
+    /// Each Raft group is a shard: its leader is reported as the master and
+    /// the other group members as replicas. Shards and nodes are RESP3 maps,
+    /// which RESP2 clients receive as flat key/value arrays.
+    ///
     /// Maps to: `meta_raft.get_cluster_meta()`
     pub fn cluster_shards(&self) -> Result<RespValue> {
         let meta: ClusterMeta = self.meta_raft.get_cluster_meta();
-        let mut shards = Vec::new();
+        let mut group_ids: Vec<GroupId> = meta.groups.keys().copied().collect();
+        group_ids.sort();
 
-        // Build shard info for each group that has slots
-        for (group_id, group_meta) in &meta.groups {
-            // Find slots assigned to this group
-            let mut slot_ranges: Vec<(u16, u16)> = Vec::new();
-            let mut start: Option<u16> = None;
-            let mut end: Option<u16> = None;
+        let mut shards = Vec::new();
+        for group_id in group_ids {
+            let group_meta = &meta.groups[&group_id];
 
+            // Find slots assigned to this group, as flattened start/end pairs
+            let mut slots = Vec::new();
+            let mut range: Option<(u16, u16)> = None;
             for (slot_idx, &assigned_group) in meta.slots.iter().enumerate() {
-                if assigned_group == *group_id {
-                    if start.is_none() {
-                        start = Some(slot_idx as u16);
-                    }
-                    end = Some(slot_idx as u16);
-                } else if start.is_some() {
-                    slot_ranges.push((start.unwrap(), end.unwrap()));
-                    start = None;
-                    end = None;
+                let slot_idx = slot_idx as u16;
+                if assigned_group == group_id {
+                    range = Some(match range {
+                        Some((start, _)) => (start, slot_idx),
+                        None => (slot_idx, slot_idx),
+                    });
+                } else if let Some((start, end)) = range.take() {
+                    slots.push(RespValue::Integer(start as i64));
+                    slots.push(RespValue::Integer(end as i64));
                 }
             }
-            if let Some(s) = start {
-                slot_ranges.push((s, end.unwrap()));
-            }
-
-            // Skip groups without slots
-            if slot_ranges.is_empty() {
-                continue;
+            if let Some((start, end)) = range {
+                slots.push(RespValue::Integer(start as i64));
+                slots.push(RespValue::Integer(end as i64));
             }
 
-            // Build slots array for this shard
-            let mut slots_array = Vec::new();
-            for (range_start, range_end) in &slot_ranges {
-                slots_array.push(RespValue::Array(Some(vec![
-                    RespValue::Integer(*range_start as i64),
-                    RespValue::Integer(*range_end as i64),
-                ])));
-            }
-
-            // Build nodes array for this shard
-            let mut nodes_array = Vec::new();
-
-            // Add master node first (leader)
+            // Master (the group leader) first, then its replicas
+            let mut nodes = Vec::new();
             if let Some(leader_id) = group_meta.leader {
-                if let Some(node_info) = meta.nodes.get(&leader_id) {
-                    let data_addr = Self::extract_data_address(&node_info.addr);
-                    let (ip, port) = Self::parse_addr(&data_addr);
-                    let health = match node_info.status {
-                        NodeStatus::Online => "online",
-                        NodeStatus::Offline => "offline",
-                        _ => "loading",
-                    };
-
-                    nodes_array.push(RespValue::Array(Some(vec![
-                        RespValue::BulkString(Some(Bytes::from("id"))),
-                        RespValue::BulkString(Some(Bytes::from(format!("{:040x}", leader_id)))),
-                        RespValue::BulkString(Some(Bytes::from("port"))),
-                        RespValue::Integer(port),
-                        RespValue::BulkString(Some(Bytes::from("ip"))),
-                        RespValue::BulkString(Some(Bytes::from(ip.clone()))),
-                        RespValue::BulkString(Some(Bytes::from("endpoint"))),
-                        RespValue::BulkString(Some(Bytes::from(ip.clone()))),
-                        RespValue::BulkString(Some(Bytes::from("role"))),
-                        RespValue::BulkString(Some(Bytes::from("master"))),
-                        RespValue::BulkString(Some(Bytes::from("replication-offset"))),
-                        RespValue::Integer(0),
-                        RespValue::BulkString(Some(Bytes::from("health"))),
-                        RespValue::BulkString(Some(Bytes::from(health))),
-                    ])));
-                }
+                nodes.extend(Self::shard_node(&meta, leader_id, "master"));
             }
-
-            // Add replica nodes
             for &replica_id in &group_meta.replicas {
-                // Skip leader (already added as master)
-                if Some(replica_id) == group_meta.leader {
-                    continue;
-                }
-                if let Some(node_info) = meta.nodes.get(&replica_id) {
-                    let data_addr = Self::extract_data_address(&node_info.addr);
-                    let (ip, port) = Self::parse_addr(&data_addr);
-                    let health = match node_info.status {
-                        NodeStatus::Online => "online",
-                        NodeStatus::Offline => "offline",
-                        _ => "loading",
-                    };
-
-                    nodes_array.push(RespValue::Array(Some(vec![
-                        RespValue::BulkString(Some(Bytes::from("id"))),
-                        RespValue::BulkString(Some(Bytes::from(format!("{:040x}", replica_id)))),
-                        RespValue::BulkString(Some(Bytes::from("port"))),
-                        RespValue::Integer(port),
-                        RespValue::BulkString(Some(Bytes::from("ip"))),
-                        RespValue::BulkString(Some(Bytes::from(ip.clone()))),
-                        RespValue::BulkString(Some(Bytes::from("endpoint"))),
-                        RespValue::BulkString(Some(Bytes::from(ip.clone()))),
-                        RespValue::BulkString(Some(Bytes::from("role"))),
-                        RespValue::BulkString(Some(Bytes::from("replica"))),
-                        RespValue::BulkString(Some(Bytes::from("replication-offset"))),
-                        RespValue::Integer(0),
-                        RespValue::BulkString(Some(Bytes::from("health"))),
-                        RespValue::BulkString(Some(Bytes::from(health))),
-                    ])));
+                if Some(replica_id) != group_meta.leader {
+                    nodes.extend(Self::shard_node(&meta, replica_id, "replica"));
                 }
             }
 
-            // Build shard entry
-            shards.push(RespValue::Array(Some(vec![
-                RespValue::BulkString(Some(Bytes::from("slots"))),
-                RespValue::Array(Some(slots_array)),
-                RespValue::BulkString(Some(Bytes::from("nodes"))),
-                RespValue::Array(Some(nodes_array)),
-            ])));
+            // Skip groups that have neither slots nor known nodes
+            if slots.is_empty() && nodes.is_empty() {
+                continue;
+            }
+
+            shards.push(RespValue::map(vec![
+                (RespValue::bulk_string("slots"), RespValue::array(slots)),
+                (RespValue::bulk_string("nodes"), RespValue::array(nodes)),
+            ]));
         }
 
-        Ok(RespValue::Array(Some(shards)))
+        Ok(RespValue::array(shards))
+    }
+
+    /// Describe one node of a CLUSTER SHARDS entry, if the node is known
+    fn shard_node(meta: &ClusterMeta, node_id: NodeId, role: &'static str) -> Option<RespValue> {
+        let node_info = meta.nodes.get(&node_id)?;
+        let data_addr = Self::extract_data_address(&node_info.addr);
+        let (ip, port) = Self::parse_addr(&data_addr);
+        let health = match node_info.status {
+            NodeStatus::Online => "online",
+            NodeStatus::Offline => "fail",
+            _ => "loading",
+        };
+
+        Some(RespValue::map(vec![
+            (
+                RespValue::bulk_string("id"),
+                RespValue::bulk_string(format!("{:040x}", node_id)),
+            ),
+            (RespValue::bulk_string("port"), RespValue::Integer(port)),
+            (
+                RespValue::bulk_string("ip"),
+                RespValue::bulk_string(ip.clone()),
+            ),
+            (
+                RespValue::bulk_string("endpoint"),
+                RespValue::bulk_string(ip),
+            ),
+            (RespValue::bulk_string("role"), RespValue::bulk_string(role)),
+            (
+                RespValue::bulk_string("replication-offset"),
+                RespValue::Integer(0),
+            ),
+            (
+                RespValue::bulk_string("health"),
+                RespValue::bulk_string(health),
+            ),
+        ]))
     }
 
     /// Handle CLUSTER MYSHARDID command.
@@ -1096,7 +1215,17 @@ impl ClusterCommands {
 
     /// Handle CLUSTER FAILOVER command.
     ///
-    /// Triggers a manual failover (replica becomes master).
+    /// Triggers a manual failover (replica becomes master) by having this
+    /// node stand for election in the Raft group it replicates, and replies
+    /// once the failover has started, as Redis does:
+    ///
+    /// - by default the replica first applies every entry it has received,
+    ///   so it takes over with the master's latest writes
+    /// - FORCE stands for election straight away
+    /// - TAKEOVER also records this node as the group's master in the cluster
+    ///   metadata up front, without waiting for the election to be won
+    ///
+    /// Otherwise the metadata moves to this node once the group has elected it.
     pub async fn cluster_failover(&self, mode: FailoverMode) -> Result<RespValue> {
         let meta: ClusterMeta = self.meta_raft.get_cluster_meta();
 
@@ -1116,27 +1245,74 @@ impl ClusterCommands {
             }
         };
 
-        // Perform failover based on mode
-        match mode {
-            FailoverMode::Default | FailoverMode::Force => {
-                // Update group leader to this node
-                self.meta_raft
-                    .update_group_leader(group_id, self.node_id)
-                    .await
-                    .map_err(|e| {
-                        AikvError::Internal(format!("Failed to perform failover: {}", e))
-                    })?;
+        let raft = self.multi_raft.get_raft_group(group_id).ok_or_else(|| {
+            AikvError::Invalid(format!(
+                "Raft group {} is not running on this node",
+                group_id
+            ))
+        })?;
+
+        if mode == FailoverMode::Takeover {
+            self.meta_raft
+                .update_group_leader(group_id, self.node_id)
+                .await
+                .map_err(|e| AikvError::Internal(format!("Failed to perform takeover: {}", e)))?;
+        }
+
+        let node_id = self.node_id;
+        let meta_raft = Arc::clone(&self.meta_raft);
+        tokio::spawn(async move {
+            let deadline = tokio::time::Instant::now() + FAILOVER_TIMEOUT;
+            let poll = || tokio::time::sleep(std::time::Duration::from_millis(10));
+
+            if mode == FailoverMode::Default {
+                loop {
+                    let metrics = raft.metrics().borrow().clone();
+                    let applied = metrics.last_applied.map(|log| log.index);
+                    if applied >= metrics.last_log_index || tokio::time::Instant::now() >= deadline
+                    {
+                        break;
+                    }
+                    poll().await;
+                }
             }
-            FailoverMode::Takeover => {
-                // Force takeover without coordination
-                self.meta_raft
-                    .update_group_leader(group_id, self.node_id)
-                    .await
-                    .map_err(|e| {
-                        AikvError::Internal(format!("Failed to perform takeover: {}", e))
-                    })?;
+
+            if let Err(e) = raft.trigger().elect().await {
+                tracing::warn!(
+                    "Failover of group {} failed to start an election: {}",
+                    group_id,
+                    e
+                );
+                return;
             }
-        }
+            if mode == FailoverMode::Takeover {
+                return;
+            }
+
+            // Wait for the group to elect us before moving the metadata over
+            loop {
+                let leader = raft.metrics().borrow().current_leader;
+                if leader == Some(node_id) {
+                    break;
+                }
+                if tokio::time::Instant::now() >= deadline {
+                    tracing::warn!(
+                        "Failover of group {} timed out before this node was elected",
+                        group_id
+                    );
+                    return;
+                }
+                poll().await;
+            }
+            match meta_raft.update_group_leader(group_id, node_id).await {
+                Ok(_) => info!("Failover of group {} complete", group_id),
+                Err(e) => tracing::warn!(
+                    "Failover of group {} failed to update metadata: {}",
+                    group_id,
+                    e
+                ),
+            }
+        });
 
         Ok(RespValue::SimpleString("OK".to_string()))
     }
@@ -1240,16 +1416,100 @@ impl ClusterCommands {
         Ok(RespValue::SimpleString("OK".to_string()))
     }
 
-    /// Handle ASKING command.
+    /// Handle CLUSTER SETSLOT command.
     ///
-    /// Signals that the next command is for a key being migrated.
-    /// This is called on the target node after receiving -ASK redirect.
-    pub fn asking(&self) -> Result<RespValue> {
-        // In a full implementation, this would set a flag on the connection
-        // to allow the next command to operate on an importing slot
+    /// MIGRATING and IMPORTING only mark the slot on this node, as in Redis:
+    /// the source keeps serving the keys it still has and sends clients to
+    /// the target with -ASK for the rest, and the target serves the slot to
+    /// clients that sent ASKING first. NODE assigns the slot to the group the
+    /// node leads via `meta_raft.update_slots` and ends the migration; STABLE
+    /// just ends it.
+    pub async fn cluster_setslot(&self, slot: u16, action: SetSlotAction) -> Result<RespValue> {
+        let meta = self.meta_raft.get_cluster_meta();
+        let known = |node_id: NodeId| {
+            if meta.nodes.contains_key(&node_id) {
+                Ok(node_id)
+            } else {
+                Err(AikvError::Invalid(format!(
+                    "I don't know about node {:040x}",
+                    node_id
+                )))
+            }
+        };
+        let owner = meta
+            .slots
+            .get(slot as usize)
+            .and_then(|group_id| meta.groups.get(group_id))
+            .and_then(|group| group.leader);
+
+        match action {
+            SetSlotAction::Migrating(target) => {
+                known(target)?;
+                if owner != Some(self.node_id) {
+                    return Err(AikvError::Invalid(format!(
+                        "I'm not the owner of hash slot {}",
+                        slot
+                    )));
+                }
+                self.slot_states
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(slot, SlotState::Migrating(target));
+            }
+            SetSlotAction::Importing(source) => {
+                known(source)?;
+                if owner == Some(self.node_id) {
+                    return Err(AikvError::Invalid(format!(
+                        "I'm already the owner of hash slot {}",
+                        slot
+                    )));
+                }
+                self.slot_states
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(slot, SlotState::Importing(source));
+            }
+            SetSlotAction::Stable => {
+                self.slot_states
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .remove(&slot);
+            }
+            SetSlotAction::Node(node_id) => {
+                known(node_id)?;
+                let group_id = meta
+                    .groups
+                    .iter()
+                    .find(|(_, g)| g.leader == Some(node_id))
+                    .map(|(gid, _)| *gid)
+                    .ok_or_else(|| {
+                        AikvError::Invalid(format!("Target node {:040x} is not a master", node_id))
+                    })?;
+                self.meta_raft
+                    .update_slots(slot, slot + 1, group_id)
+                    .await
+                    .map_err(|e| {
+                        AikvError::Internal(format!("Failed to assign slot {}: {}", slot, e))
+                    })?;
+                self.slot_states
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .remove(&slot);
+            }
+        }
+
         Ok(RespValue::SimpleString("OK".to_string()))
     }
 
+    /// This node's migration state for a slot, if it has one
+    pub fn slot_state(&self, slot: u16) -> Option<SlotState> {
+        self.slot_states
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&slot)
+            .copied()
+    }
+
     /// Generate a unique node ID.
     /// This is a utility function for server initialization.
     pub fn generate_node_id() -> NodeId {
@@ -1277,6 +1537,66 @@ impl ClusterCommands {
         hasher.finish()
     }
 
+    /// CLUSTER HELP, also served before the cluster is initialized.
+    pub fn help() -> RespValue {
+        help_reply(
+            "CLUSTER",
+            &[
+                "ADDREPLICATION <replica-id> <master-id>",
+                "    Add a replica to a master's group (sent to the MetaRaft leader).",
+                "ADDSLOTS <slot> [<slot> ...]",
+                "    Assign slots to current node.",
+                "ADDSLOTSRANGE <start slot> <end slot> [<start slot> <end slot> ...]",
+                "    Assign slots which are between <start-slot> and <end-slot> to current node.",
+                "BUMPEPOCH",
+                "    Advance the cluster config epoch.",
+                "COUNT-FAILURE-REPORTS <node-id>",
+                "    Return number of failure reports for <node-id>.",
+                "COUNTKEYSINSLOT <slot>",
+                "    Return the number of keys in <slot>.",
+                "DELSLOTS <slot> [<slot> ...]",
+                "    Delete slots information from current node.",
+                "FAILOVER [FORCE|TAKEOVER]",
+                "    Promote current replica node to being a master.",
+                "FORGET <node-id>",
+                "    Remove a node from the cluster.",
+                "GETKEYSINSLOT <slot> <count>",
+                "    Return key names stored by current node in a slot.",
+                "INFO",
+                "    Return information about the cluster.",
+                "KEYSLOT <key>",
+                "    Return the hash slot for <key>.",
+                "MEET <ip> <port> [<node-id>]",
+                "    Connect nodes into a working cluster.",
+                "METARAFT <subcommand> [<arg> ...]",
+                "    Manage the MetaRaft membership of the cluster.",
+                "MYID",
+                "    Return the node id.",
+                "MYSHARDID",
+                "    Return the node's shard id.",
+                "NODES",
+                "    Return cluster configuration seen by node.",
+                "REPLICAS <node-id>",
+                "    Return <node-id> replicas.",
+                "REPLICATE <node-id>",
+                "    Configure current node as replica to <node-id>.",
+                "SAVECONFIG",
+                "    Force saving cluster configuration on disk.",
+                "SET-CONFIG-EPOCH <epoch>",
+                "    Set config epoch of current node.",
+                "SETSLOT <slot> (IMPORTING <node-id>|MIGRATING <node-id>|STABLE|NODE <node-id>)",
+                "    Set slot state.",
+                "SHARDS",
+                "    Return information about slot range mappings and the nodes associated with them.",
+                "SLAVES <node-id>",
+                "    Return <node-id> replicas.",
+                "SLOTS",
+                "    Return information about slots range mappings. Each range is made of:",
+                "    start, end, master and replicas IP addresses, ports and ids",
+            ],
+        )
+    }
+
     /// Execute a CLUSTER subcommand.
     ///
     /// This is the main dispatcher for CLUSTER commands.
@@ -1287,6 +1607,7 @@ impl ClusterCommands {
 
         let subcommand = String::from_utf8_lossy(&args[0]).to_uppercase();
         match subcommand.as_str() {
+            "HELP" => Ok(Self::help()),
             "INFO" => self.cluster_info(),
             "NODES" => self.cluster_nodes(),
             "SLOTS" => self.cluster_slots(),
@@ -1303,12 +1624,8 @@ impl ClusterCommands {
                         "CLUSTER GETKEYSINSLOT".to_string(),
                     ));
                 }
-                let slot = String::from_utf8_lossy(&args[1])
-                    .parse::<u16>()
-                    .map_err(|_| AikvError::Invalid("Invalid slot".to_string()))?;
-                let count = String::from_utf8_lossy(&args[2])
-                    .parse::<usize>()
-                    .map_err(|_| AikvError::Invalid("Invalid count".to_string()))?;
+                let slot = parse_slot(&args[1])?;
+                let count = parse_slot_key_count(&args[2])?;
                 self.cluster_getkeysinslot(slot, count)
             }
             "COUNTKEYSINSLOT" => {
@@ -1317,9 +1634,7 @@ impl ClusterCommands {
                         "CLUSTER COUNTKEYSINSLOT".to_string(),
                     ));
                 }
-                let slot = String::from_utf8_lossy(&args[1])
-                    .parse::<u16>()
-                    .map_err(|_| AikvError::Invalid("Invalid slot".to_string()))?;
+                let slot = parse_slot(&args[1])?;
                 self.cluster_countkeysinslot(slot)
             }
             "SHARDS" => self.cluster_shards(),
@@ -1370,30 +1685,13 @@ impl ClusterCommands {
                     .map_err(|_| AikvError::Invalid("Invalid node ID".to_string()))?;
                 self.cluster_count_failure_reports(node_id)
             }
-            _ => Err(AikvError::InvalidCommand(format!(
-                "Unknown CLUSTER subcommand: {}",
-                subcommand
-            ))),
+            _ => Err(unknown_subcommand(
+                "CLUSTER",
+                &String::from_utf8_lossy(&args[0]),
+            )),
         }
     }
 
-    /// Handle READONLY command.
-    ///
-    /// Sets connection to read-only mode for replica reads.
-    pub fn readonly(&self) -> Result<RespValue> {
-        // For now, just return OK
-        // In a full implementation, this would set a flag on the connection
-        Ok(RespValue::SimpleString("OK".to_string()))
-    }
-
-    /// Handle READWRITE command.
-    ///
-    /// Sets connection back to read-write mode (default).
-    pub fn readwrite(&self) -> Result<RespValue> {
-        // For now, just return OK
-        // In a full implementation, this would clear the read-only flag
-        Ok(RespValue::SimpleString("OK".to_string()))
-    }
     /// Handle CLUSTER METARAFT ADDLEARNER command.
     ///
     /// Adds a node as a learner to the MetaRaft cluster. This is the first step
@@ -1544,6 +1842,74 @@ impl ClusterCommands {
 
         Ok(RespValue::BulkString(Some(Bytes::from(info))))
     }
+
+    /// Handle WAIT command.
+    ///
+    /// Blocks until `numreplicas` replicas have acknowledged every log entry
+    /// this node had written as leader when WAIT was called, or until
+    /// `timeout_ms` elapses (0 blocks indefinitely). Returns the number of
+    /// replicas that acknowledged. When this node leads several groups, the
+    /// count is the minimum across them, since a write may have gone to any.
+    ///
+    /// Maps to: `multi_raft.get_raft_group(id).metrics().replication`
+    pub async fn wait(&self, numreplicas: usize, timeout_ms: u64) -> Result<RespValue> {
+        let meta = self.meta_raft.get_cluster_meta();
+        let rafts: Vec<_> = meta
+            .groups
+            .iter()
+            .filter(|(_, g)| g.leader == Some(self.node_id))
+            .filter_map(|(gid, _)| self.multi_raft.get_raft_group(*gid))
+            .collect();
+
+        if rafts.is_empty() {
+            return Ok(RespValue::Integer(0));
+        }
+
+        // Snapshot the log index each group must reach
+        let targets: Vec<Option<u64>> = rafts
+            .iter()
+            .map(|raft| raft.metrics().borrow().last_log_index)
+            .collect();
+
+        let acked = || -> usize {
+            rafts
+                .iter()
+                .zip(&targets)
+                .map(|(raft, target)| {
+                    let metrics = raft.metrics().borrow().clone();
+                    let target = target.unwrap_or(0);
+                    metrics
+                        .replication
+                        .map(|replication| {
+                            replication
+                                .iter()
+                                .filter(|(id, matched)| {
+                                    **id != self.node_id
+                                        && matched.as_ref().map_or(0, |log| log.index) >= target
+                                })
+                                .count()
+                        })
+                        .unwrap_or(0)
+                })
+                .min()
+                .unwrap_or(0)
+        };
+
+        let deadline = (timeout_ms > 0)
+            .then(|| tokio::time::Instant::now() + std::time::Duration::from_millis(timeout_ms));
+        loop {
+            let count = acked();
+            if count >= numreplicas {
+                return Ok(RespValue::Integer(count as i64));
+            }
+            if let Some(deadline) = deadline {
+                if tokio::time::Instant::now() >= deadline {
+                    return Ok(RespValue::Integer(count as i64));
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+    }
 }
 
 #[cfg(feature = "cluster")]
@@ -1625,8 +1991,8 @@ impl ClusterCommands {
                 return Ok(());
             }
 
-            // Check if this node is a replica (can handle READONLY requests)
-            // For now, we always redirect to the leader for write operations
+            // A replica redirects to the leader too; route_keys lets
+            // READONLY connections read here instead
             if group_meta.replicas.contains(&self.node_id) {
                 // This node is a replica, redirect to the leader
                 if let Some(leader_id) = group_meta.leader {
@@ -1693,6 +2059,65 @@ impl ClusterCommands {
         self.check_slot_ownership(first_slot)
     }
 
+    /// Check a command's keys the way the command dispatcher does, taking
+    /// slot migration into account.
+    ///
+    /// On top of [`check_keys_slot`](Self::check_keys_slot): while this node
+    /// is MIGRATING the slot, keys it no longer has in `db` are redirected to
+    /// the target with -ASK, and while it is IMPORTING the slot, a command
+    /// preceded by ASKING (`asking`) is served here instead of getting -MOVED.
+    /// Likewise, a read-only command on a READONLY connection (`readonly`)
+    /// is served from a slot this node replicates.
+    pub fn route_keys(
+        &self,
+        keys: &[&[u8]],
+        db: usize,
+        asking: bool,
+        readonly: bool,
+    ) -> Result<()> {
+        let Some(first) = keys.first() else {
+            return Ok(());
+        };
+        let slot = key_to_slot_with_hash_tag(first);
+        if keys[1..]
+            .iter()
+            .any(|key| key_to_slot_with_hash_tag(key) != slot)
+        {
+            return Err(AikvError::CrossSlot);
+        }
+
+        match (self.check_slot_ownership(slot), self.slot_state(slot)) {
+            (Ok(()), Some(SlotState::Migrating(target))) => {
+                let Some(storage) = &self.storage else {
+                    return Ok(());
+                };
+                for key in keys {
+                    if !storage.exists_in_db(db, &String::from_utf8_lossy(key))? {
+                        let meta = self.meta_raft.get_cluster_meta();
+                        let Some(target_info) = meta.nodes.get(&target) else {
+                            return Ok(());
+                        };
+                        let data_addr = Self::extract_data_address(&target_info.addr);
+                        return Err(Self::ask_error(slot, &data_addr));
+                    }
+                }
+                Ok(())
+            }
+            (Err(AikvError::Moved(..)), Some(SlotState::Importing(_))) if asking => Ok(()),
+            (Err(AikvError::Moved(..)), _) if readonly && self.replicates_slot(slot) => Ok(()),
+            (result, _) => result,
+        }
+    }
+
+    /// Whether this node is a replica in the group the slot is assigned to
+    fn replicates_slot(&self, slot: u16) -> bool {
+        let meta = self.meta_raft.get_cluster_meta();
+        meta.slots
+            .get(slot as usize)
+            .and_then(|group| meta.groups.get(group))
+            .is_some_and(|group_meta| group_meta.replicas.contains(&self.node_id))
+    }
+
     /// Get the slot number for a key.
     ///
     /// This uses hash tag extraction for Redis Cluster compatibility.
@@ -42,7 +42,8 @@ pub mod raft_service;
 
 // Export our implementations
 pub use commands::{
-    key_to_slot_with_hash_tag, ClusterCommands, FailoverMode, NodeInfo, RedirectType,
+    key_to_slot_with_hash_tag, keys_in_slot, parse_slot, parse_slot_key_count, ClusterCommands,
+    FailoverMode, NodeInfo, RedirectType, SetSlotAction, SlotState,
 };
 pub use node::{ClusterConfig, ClusterNode, GroupId, NodeId};
 
@@ -0,0 +1,498 @@
+//! ACL users and the rules limiting which commands and keys they may use
+//!
+//! Each user has a list of password hashes, the key patterns it may touch,
+//! the Pub/Sub channels it may use and an allow/deny list of commands. The
+//! `default` user, which connections start out as, may run everything on
+//! every key and channel.
+
+use super::config::glob_match_case_sensitive;
+use super::context::ClientContext;
+use super::help::{help_reply, unknown_subcommand};
+use super::server::{self, constant_time_eq};
+use crate::error::{AikvError, Result};
+use crate::protocol::RespValue;
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+/// The user connections are authenticated as until they AUTH
+pub const DEFAULT_USER: &str = "default";
+
+/// ACL users by name, shared by every connection
+pub type AclUsers = Arc<RwLock<HashMap<String, AclUser>>>;
+
+/// A fresh user table holding only the `default` user
+pub fn default_users() -> AclUsers {
+    let mut users = HashMap::new();
+    users.insert(DEFAULT_USER.to_string(), AclUser::unrestricted());
+    Arc::new(RwLock::new(users))
+}
+
+/// Check a username and password against the user table
+///
+/// Fails the same way whether the user is missing, disabled or the
+/// password is wrong, as AUTH doesn't tell them apart.
+pub fn authenticate(users: &AclUsers, username: &[u8], password: &[u8]) -> Result<()> {
+    let users = users
+        .read()
+        .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+    match users.get(String::from_utf8_lossy(username).as_ref()) {
+        Some(user) if user.enabled && user.check_password(password) => Ok(()),
+        _ => Err(AikvError::WrongPass),
+    }
+}
+
+/// SHA-256 of a password in hex, the form passwords are kept and listed in
+fn hash_password(password: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(password))
+}
+
+/// An ACL user
+#[derive(Debug, Clone, Default)]
+pub struct AclUser {
+    /// `on`: whether the user may authenticate
+    pub enabled: bool,
+    /// `nopass`: any password authenticates the user
+    pub nopass: bool,
+    /// SHA-256 hashes of the user's passwords
+    passwords: Vec<String>,
+    /// Glob patterns of the keys the user may access (`~pattern`)
+    key_patterns: Vec<String>,
+    /// Glob patterns of the Pub/Sub channels the user may use (`&pattern`)
+    channel_patterns: Vec<String>,
+    /// `+@all`: every command is allowed except those in `denied`
+    all_commands: bool,
+    /// Commands allowed on top of nothing (`+cmd`), in lowercase
+    allowed: BTreeSet<String>,
+    /// Commands taken away from `+@all` (`-cmd`), in lowercase
+    denied: BTreeSet<String>,
+}
+
+impl AclUser {
+    /// `on nopass ~* &* +@all`, what the default user starts as
+    fn unrestricted() -> Self {
+        Self {
+            enabled: true,
+            nopass: true,
+            key_patterns: vec!["*".to_string()],
+            channel_patterns: vec!["*".to_string()],
+            all_commands: true,
+            ..Self::default()
+        }
+    }
+
+    /// A user ACL SETUSER creates: disabled, with no keys or commands, but
+    /// every channel, as with Redis's `acl-pubsub-default allchannels`
+    fn created() -> Self {
+        Self {
+            channel_patterns: vec!["*".to_string()],
+            ..Self::default()
+        }
+    }
+
+    /// Apply one ACL SETUSER rule
+    fn apply_rule(&mut self, rule: &[u8]) -> Result<()> {
+        let rule = String::from_utf8_lossy(rule);
+        let lower = rule.to_lowercase();
+        match lower.as_str() {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "allkeys" => self.key_patterns = vec!["*".to_string()],
+            "resetkeys" => self.key_patterns.clear(),
+            "allchannels" => self.channel_patterns = vec!["*".to_string()],
+            "resetchannels" => self.channel_patterns.clear(),
+            "allcommands" | "+@all" => {
+                self.all_commands = true;
+                self.allowed.clear();
+                self.denied.clear();
+            }
+            "nocommands" | "-@all" => {
+                self.all_commands = false;
+                self.allowed.clear();
+                self.denied.clear();
+            }
+            "reset" => *self = Self::default(),
+            _ => {
+                if let Some(password) = rule.strip_prefix('>') {
+                    let hash = hash_password(password.as_bytes());
+                    self.nopass = false;
+                    if !self.passwords.contains(&hash) {
+                        self.passwords.push(hash);
+                    }
+                } else if let Some(password) = rule.strip_prefix('<') {
+                    let hash = hash_password(password.as_bytes());
+                    self.passwords.retain(|h| *h != hash);
+                } else if let Some(pattern) = rule.strip_prefix('~') {
+                    self.key_patterns.push(pattern.to_string());
+                } else if let Some(pattern) = rule.strip_prefix('&') {
+                    self.channel_patterns.push(pattern.to_string());
+                } else if let Some(command) = lower.strip_prefix('+') {
+                    Self::known_command(command, &rule)?;
+                    self.denied.remove(command);
+                    if !self.all_commands {
+                        self.allowed.insert(command.to_string());
+                    }
+                } else if let Some(command) = lower.strip_prefix('-') {
+                    Self::known_command(command, &rule)?;
+                    self.allowed.remove(command);
+                    if self.all_commands {
+                        self.denied.insert(command.to_string());
+                    }
+                } else {
+                    return Err(AikvError::InvalidArgument(format!(
+                        "ERR Error in ACL SETUSER modifier '{}': Syntax error",
+                        rule
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject `+cmd`/`-cmd` rules naming a command that doesn't exist
+    fn known_command(command: &str, rule: &str) -> Result<()> {
+        if server::lookup_command(&command.to_uppercase()).is_none() {
+            return Err(AikvError::InvalidArgument(format!(
+                "ERR Error in ACL SETUSER modifier '{}': Unknown command or category name in ACL",
+                rule
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether `password` authenticates the user
+    fn check_password(&self, password: &[u8]) -> bool {
+        if self.nopass {
+            return true;
+        }
+        let hash = hash_password(password);
+        self.passwords
+            .iter()
+            .any(|h| constant_time_eq(h.as_bytes(), hash.as_bytes()))
+    }
+
+    /// Whether the user may run `command`, given in uppercase
+    pub fn can_run(&self, command: &str) -> bool {
+        let command = command.to_lowercase();
+        if self.all_commands {
+            !self.denied.contains(&command)
+        } else {
+            self.allowed.contains(&command)
+        }
+    }
+
+    /// Whether the user may access `key`
+    pub fn can_access(&self, key: &[u8]) -> bool {
+        let key = String::from_utf8_lossy(key);
+        self.key_patterns
+            .iter()
+            .any(|pattern| glob_match_case_sensitive(pattern, &key))
+    }
+
+    /// Whether the user may use `channel`
+    ///
+    /// A PSUBSCRIBE pattern (`literal`) is only allowed if it is one of the
+    /// user's own patterns, not merely matched by one, as in Redis.
+    pub fn can_use_channel(&self, channel: &[u8], literal: bool) -> bool {
+        let channel = String::from_utf8_lossy(channel);
+        self.channel_patterns.iter().any(|pattern| {
+            pattern == "*"
+                || if literal {
+                    *pattern == channel
+                } else {
+                    glob_match_case_sensitive(pattern, &channel)
+                }
+        })
+    }
+
+    /// The command rules, as ACL LIST and ACL GETUSER show them
+    fn describe_commands(&self) -> String {
+        let (base, exceptions, sign) = if self.all_commands {
+            ("+@all", &self.denied, '-')
+        } else {
+            ("-@all", &self.allowed, '+')
+        };
+        std::iter::once(base.to_string())
+            .chain(exceptions.iter().map(|c| format!("{}{}", sign, c)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The key patterns, as ACL LIST and ACL GETUSER show them
+    fn describe_keys(&self) -> String {
+        self.key_patterns
+            .iter()
+            .map(|p| format!("~{}", p))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The channel patterns, as ACL GETUSER shows them
+    fn describe_channels(&self) -> String {
+        self.channel_patterns
+            .iter()
+            .map(|p| format!("&{}", p))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The user as a line of ACL LIST: `user <name> <rules...>`
+    ///
+    /// Channels are only listed when the user can't use all of them.
+    fn describe(&self, name: &str) -> String {
+        let mut rules = vec![
+            format!("user {}", name),
+            if self.enabled { "on" } else { "off" }.to_string(),
+        ];
+        if self.nopass {
+            rules.push("nopass".to_string());
+        }
+        rules.extend(self.passwords.iter().map(|h| format!("#{}", h)));
+        if !self.key_patterns.is_empty() {
+            rules.push(self.describe_keys());
+        }
+        if self.channel_patterns.is_empty() {
+            rules.push("resetchannels".to_string());
+        } else if !self.channel_patterns.iter().any(|p| p == "*") {
+            rules.push(self.describe_channels());
+        }
+        rules.push(self.describe_commands());
+        rules.join(" ")
+    }
+}
+
+/// ACL command handler
+pub struct AclCommands {
+    users: AclUsers,
+}
+
+impl AclCommands {
+    pub fn new() -> Self {
+        Self {
+            users: default_users(),
+        }
+    }
+
+    /// Share the user table with other connections
+    pub fn set_users(&mut self, users: AclUsers) {
+        self.users = users;
+    }
+
+    fn read_users(&self) -> Result<RwLockReadGuard<'_, HashMap<String, AclUser>>> {
+        self.users
+            .read()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))
+    }
+
+    /// Check that the connection's user may run `command` on the keys and
+    /// channels in `args`
+    pub fn check_permission(
+        &self,
+        client: &ClientContext,
+        command: &str,
+        args: &[Bytes],
+    ) -> Result<()> {
+        let users = self.read_users()?;
+        let name = &client.auth.user;
+        // A user deleted while connections were authenticated as it can't run anything
+        let user = match users.get(name) {
+            Some(user) if user.can_run(command) => user,
+            _ => {
+                return Err(AikvError::NoPerm(format!(
+                    "User {} has no permissions to run the '{}' command",
+                    name,
+                    command.to_lowercase()
+                )))
+            }
+        };
+        let (channels, literal): (&[Bytes], bool) = match command {
+            "PUBLISH" | "SPUBLISH" => (&args[..args.len().min(1)], false),
+            "SUBSCRIBE" | "SSUBSCRIBE" => (args, false),
+            "PSUBSCRIBE" => (args, true),
+            _ => (&[], false),
+        };
+        if channels
+            .iter()
+            .any(|channel| !user.can_use_channel(channel, literal))
+        {
+            return Err(AikvError::NoPerm(
+                "No permissions to access a channel".to_string(),
+            ));
+        }
+        if user.key_patterns.iter().any(|p| p == "*") {
+            return Ok(());
+        }
+        if let Some(info) = server::lookup_command(command) {
+            if info.keys(args).into_iter().any(|key| !user.can_access(key)) {
+                return Err(AikvError::NoPerm(
+                    "No permissions to access a key".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// ACL <subcommand> \[arg ...\]
+    pub fn acl(&self, args: &[Bytes], client: &ClientContext) -> Result<RespValue> {
+        let subcommand = String::from_utf8_lossy(&args[0]).to_uppercase();
+        match subcommand.as_str() {
+            "SETUSER" if args.len() >= 2 => self.setuser(&args[1], &args[2..]),
+            "GETUSER" if args.len() == 2 => self.getuser(&args[1]),
+            "DELUSER" if args.len() >= 2 => self.deluser(&args[1..]),
+            "LIST" if args.len() == 1 => self.list(),
+            "USERS" if args.len() == 1 => self.usernames(),
+            "WHOAMI" if args.len() == 1 => Ok(RespValue::bulk_string(client.auth.user.clone())),
+            "HELP" if args.len() == 1 => Ok(Self::help()),
+            "SETUSER" | "GETUSER" | "DELUSER" | "LIST" | "USERS" | "WHOAMI" | "HELP" => {
+                Err(AikvError::WrongArgCount(format!("ACL {}", subcommand)))
+            }
+            _ => Err(unknown_subcommand(
+                "ACL",
+                &String::from_utf8_lossy(&args[0]),
+            )),
+        }
+    }
+
+    /// ACL SETUSER username \[rule ...\]
+    /// Create the user if needed and apply the rules, all or none of them
+    fn setuser(&self, name: &[u8], rules: &[Bytes]) -> Result<RespValue> {
+        let name = String::from_utf8_lossy(name).to_string();
+        let mut users = self
+            .users
+            .write()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        let mut user = users.get(&name).cloned().unwrap_or_else(AclUser::created);
+        for rule in rules {
+            user.apply_rule(rule)?;
+        }
+        users.insert(name, user);
+        Ok(RespValue::ok())
+    }
+
+    /// ACL GETUSER username
+    fn getuser(&self, name: &[u8]) -> Result<RespValue> {
+        let users = self.read_users()?;
+        let Some(user) = users.get(String::from_utf8_lossy(name).as_ref()) else {
+            return Ok(RespValue::null_array());
+        };
+
+        let mut flags = vec![RespValue::bulk_string(if user.enabled {
+            "on"
+        } else {
+            "off"
+        })];
+        if user.nopass {
+            flags.push(RespValue::bulk_string("nopass"));
+        }
+        Ok(RespValue::map(vec![
+            (RespValue::bulk_string("flags"), RespValue::array(flags)),
+            (
+                RespValue::bulk_string("passwords"),
+                RespValue::array(
+                    user.passwords
+                        .iter()
+                        .map(|h| RespValue::bulk_string(h.clone()))
+                        .collect(),
+                ),
+            ),
+            (
+                RespValue::bulk_string("commands"),
+                RespValue::bulk_string(user.describe_commands()),
+            ),
+            (
+                RespValue::bulk_string("keys"),
+                RespValue::bulk_string(user.describe_keys()),
+            ),
+            (
+                RespValue::bulk_string("channels"),
+                RespValue::bulk_string(user.describe_channels()),
+            ),
+        ]))
+    }
+
+    /// ACL DELUSER username \[username ...\]
+    fn deluser(&self, names: &[Bytes]) -> Result<RespValue> {
+        let mut users = self
+            .users
+            .write()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        if names
+            .iter()
+            .any(|name| &name[..] == DEFAULT_USER.as_bytes())
+        {
+            return Err(AikvError::InvalidArgument(
+                "ERR The 'default' user cannot be removed".to_string(),
+            ));
+        }
+        let deleted = names
+            .iter()
+            .filter(|name| {
+                users
+                    .remove(String::from_utf8_lossy(name).as_ref())
+                    .is_some()
+            })
+            .count();
+        Ok(RespValue::integer(deleted as i64))
+    }
+
+    /// ACL LIST
+    fn list(&self) -> Result<RespValue> {
+        let users = self.read_users()?;
+        let mut names: Vec<&String> = users.keys().collect();
+        names.sort();
+        Ok(RespValue::array(
+            names
+                .into_iter()
+                .map(|name| RespValue::bulk_string(users[name].describe(name)))
+                .collect(),
+        ))
+    }
+
+    /// ACL USERS
+    fn usernames(&self) -> Result<RespValue> {
+        let users = self.read_users()?;
+        let mut names: Vec<&String> = users.keys().collect();
+        names.sort();
+        Ok(RespValue::array(
+            names
+                .into_iter()
+                .map(|name| RespValue::bulk_string(name.clone()))
+                .collect(),
+        ))
+    }
+
+    fn help() -> RespValue {
+        help_reply(
+            "ACL",
+            &[
+                "DELUSER <username> [<username> ...]",
+                "    Delete a list of users.",
+                "GETUSER <username>",
+                "    Get the user's details.",
+                "LIST",
+                "    Show users details in config file format.",
+                "SETUSER <username> <attribute> [<attribute> ...]",
+                "    Create or modify a user with the specified attributes.",
+                "USERS",
+                "    List all the registered usernames.",
+                "WHOAMI",
+                "    Return the current connection username.",
+            ],
+        )
+    }
+}
+
+impl Default for AclCommands {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,173 @@
+use crate::error::{AikvError, Result};
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Parse the timeout of a blocking command: seconds, possibly fractional,
+/// with 0 meaning no timeout
+pub(crate) fn parse_timeout(arg: &[u8]) -> Result<Option<Duration>> {
+    let out_of_range =
+        || AikvError::InvalidArgument("ERR timeout is not a float or out of range".to_string());
+    let secs = String::from_utf8_lossy(arg)
+        .parse::<f64>()
+        .map_err(|_| out_of_range())?;
+    if secs < 0.0 {
+        return Err(AikvError::InvalidArgument(
+            "ERR timeout is negative".to_string(),
+        ));
+    }
+    if secs == 0.0 {
+        return Ok(None);
+    }
+    Duration::try_from_secs_f64(secs)
+        .map(Some)
+        .map_err(|_| out_of_range())
+}
+
+/// Clients blocked on keys by BLMPOP and BZMPOP, shared by every connection
+/// so a write from one client wakes the clients blocked on its keys.
+///
+/// A woken client retries its pop, and blocks again if another client got
+/// to the elements first.
+#[derive(Debug, Default)]
+pub struct BlockedClients {
+    waiting: Mutex<HashMap<(usize, Bytes), Vec<Arc<Notify>>>>,
+    /// Number of clients blocked, so writes can skip the lock when it's 0
+    blocked: AtomicUsize,
+}
+
+impl BlockedClients {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Block on `keys` of database `db`
+    ///
+    /// The client stays blocked until the returned guard is dropped. Writes
+    /// to the keys from then on wake it, including any that happen before it
+    /// starts waiting.
+    pub fn block(&self, db: usize, keys: &[Bytes]) -> BlockedGuard<'_> {
+        let wakeup = Arc::new(Notify::new());
+        let mut waiting = self.lock();
+        for key in keys {
+            waiting
+                .entry((db, key.clone()))
+                .or_default()
+                .push(Arc::clone(&wakeup));
+        }
+        self.blocked.fetch_add(1, Ordering::SeqCst);
+        BlockedGuard {
+            clients: self,
+            db,
+            keys: keys.to_vec(),
+            wakeup,
+        }
+    }
+
+    /// Wake the clients blocked on any of `keys` of database `db`
+    pub fn wake(&self, db: usize, keys: &[&Bytes]) {
+        if self.blocked.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let waiting = self.lock();
+        for key in keys {
+            for wakeup in waiting.get(&(db, (*key).clone())).into_iter().flatten() {
+                wakeup.notify_one();
+            }
+        }
+    }
+
+    /// Wake every blocked client, after a write that may have touched any key
+    pub fn wake_all(&self) {
+        if self.blocked.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        for wakeup in self.lock().values().flatten() {
+            wakeup.notify_one();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<(usize, Bytes), Vec<Arc<Notify>>>> {
+        self.waiting.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// What [`BlockedClients::block`] returns; unblocks the client when dropped
+pub struct BlockedGuard<'a> {
+    clients: &'a BlockedClients,
+    db: usize,
+    keys: Vec<Bytes>,
+    wakeup: Arc<Notify>,
+}
+
+impl BlockedGuard<'_> {
+    /// Wait for a write to one of the keys since the guard was created, or
+    /// since the last wakeup
+    pub async fn woken(&self) {
+        self.wakeup.notified().await;
+    }
+}
+
+impl Drop for BlockedGuard<'_> {
+    fn drop(&mut self) {
+        let mut waiting = self.clients.lock();
+        for key in &self.keys {
+            let entry = (self.db, key.clone());
+            if let Some(wakeups) = waiting.get_mut(&entry) {
+                wakeups.retain(|wakeup| !Arc::ptr_eq(wakeup, &self.wakeup));
+                if wakeups.is_empty() {
+                    waiting.remove(&entry);
+                }
+            }
+        }
+        self.clients.blocked.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!(parse_timeout(b"0").unwrap(), None);
+        assert_eq!(
+            parse_timeout(b"1.5").unwrap(),
+            Some(Duration::from_millis(1500))
+        );
+        assert!(parse_timeout(b"-1").is_err());
+        assert!(parse_timeout(b"soon").is_err());
+        assert!(parse_timeout(b"1e300").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wake_reaches_only_clients_blocked_on_the_key() {
+        let clients = BlockedClients::new();
+        let key = Bytes::from("list");
+        let other = Bytes::from("other");
+        let blocked = clients.block(0, &[key.clone()]);
+
+        // Neither another key nor the same key in another database wakes it
+        clients.wake(0, &[&other]);
+        clients.wake(1, &[&key]);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), blocked.woken())
+                .await
+                .is_err()
+        );
+
+        // A write before the client starts waiting isn't missed
+        clients.wake(0, &[&key]);
+        tokio::time::timeout(Duration::from_secs(1), blocked.woken())
+            .await
+            .unwrap();
+
+        drop(blocked);
+        assert!(clients.lock().is_empty());
+        assert_eq!(clients.blocked.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,457 @@
+//! Runtime configuration exposed through CONFIG GET/SET
+//!
+//! One [`RuntimeConfig`] is shared by every connection, so a CONFIG SET on one
+//! connection is visible to CONFIG GET on all the others. The values here are
+//! what CONFIG GET reports; `ServerCommands::config_set` applies the side
+//! effects (slow log threshold, memory limit, AOF fsync policy, ...) before
+//! recording a new value.
+
+use super::server::AIKV_VERSION;
+use crate::error::{AikvError, Result};
+use crate::protocol::DEFAULT_MAX_BULK_LEN;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// Runtime configuration shared by every connection
+pub type SharedConfig = Arc<RwLock<RuntimeConfig>>;
+
+/// Parameters CONFIG SET may change while the server is running
+const MUTABLE_PARAMS: &[&str] = &[
+    "appendfsync",
+    "client-output-buffer-limit",
+    "loglevel",
+    "lua-time-limit",
+    "maxclients",
+    "maxmemory",
+    "maxmemory-policy",
+    "notify-keyspace-events",
+    "proto-max-bulk-len",
+    "rdbchecksum",
+    "slowlog-log-slower-than",
+    "slowlog-max-len",
+    "tcp-keepalive",
+    "timeout",
+];
+
+/// Server parameters visible through CONFIG GET, keyed by lower-case name
+#[derive(Debug, Clone)]
+pub struct RuntimeConfig {
+    params: BTreeMap<String, String>,
+}
+
+impl RuntimeConfig {
+    /// Create the configuration of a server listening on `port`, with every
+    /// other parameter at its default
+    pub fn new(port: u16) -> Self {
+        let defaults = [
+            ("server", "aikv".to_string()),
+            ("version", AIKV_VERSION.to_string()),
+            ("port", port.to_string()),
+            ("timeout", "0".to_string()),
+            ("tcp-keepalive", "300".to_string()),
+            ("maxclients", "10000".to_string()),
+            ("databases", "16".to_string()),
+            ("loglevel", "info".to_string()),
+            ("lua-time-limit", "5000".to_string()),
+            ("proto-max-bulk-len", DEFAULT_MAX_BULK_LEN.to_string()),
+            ("slowlog-log-slower-than", "10000".to_string()),
+            ("slowlog-max-len", "128".to_string()),
+            ("rdbchecksum", "yes".to_string()),
+            ("dbfilename", "dump.rdb".to_string()),
+            ("maxmemory", "0".to_string()),
+            ("maxmemory-policy", "noeviction".to_string()),
+            ("appendonly", "no".to_string()),
+            ("appendfsync", "everysec".to_string()),
+            ("notify-keyspace-events", String::new()),
+            (
+                "client-output-buffer-limit",
+                OutputBufferLimits::default().to_string(),
+            ),
+        ];
+
+        Self {
+            params: defaults
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        }
+    }
+
+    /// Get the value of a parameter
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params.get(&name.to_lowercase()).map(String::as_str)
+    }
+
+    /// Record the value of a parameter without any validation
+    ///
+    /// Used to keep the reported value in sync with the state it describes.
+    pub fn set(&mut self, name: &str, value: impl Into<String>) {
+        self.params.insert(name.to_lowercase(), value.into());
+    }
+
+    /// Check that CONFIG SET may change `name` while the server is running
+    pub fn check_settable(&self, name: &str) -> Result<()> {
+        let name = name.to_lowercase();
+        if MUTABLE_PARAMS.contains(&name.as_str()) {
+            Ok(())
+        } else if self.params.contains_key(&name) || name.starts_with("cluster-") {
+            Err(AikvError::InvalidArgument(format!(
+                "ERR CONFIG SET failed (possibly related to argument '{}') - can't set immutable config",
+                name
+            )))
+        } else {
+            Err(AikvError::InvalidArgument(format!(
+                "ERR Unknown option or number of arguments for CONFIG SET - '{}'",
+                name
+            )))
+        }
+    }
+
+    /// Parameters whose name matches the glob `pattern` in any case, in name
+    /// order
+    pub fn matching(&self, pattern: &str) -> Vec<(&str, &str)> {
+        let pattern = pattern.to_lowercase();
+        self.params
+            .iter()
+            .filter(|(name, _)| glob_match(&pattern, name))
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect()
+    }
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self::new(6379)
+    }
+}
+
+/// Parse a memory size such as `100mb` into bytes
+///
+/// Accepts a plain byte count or one with a `b`, `kb`, `mb` or `gb`
+/// (powers of 1000) or `k`, `m` or `g` (powers of 1024) suffix, in any case.
+pub fn parse_memory(value: &str) -> Option<u64> {
+    let value = value.trim().to_lowercase();
+    let units: [(&str, u64); 7] = [
+        ("kb", 1000),
+        ("mb", 1000 * 1000),
+        ("gb", 1000 * 1000 * 1000),
+        ("k", 1024),
+        ("m", 1024 * 1024),
+        ("g", 1024 * 1024 * 1024),
+        ("b", 1),
+    ];
+    let (digits, multiplier) = units
+        .iter()
+        .find_map(|(suffix, multiplier)| {
+            value
+                .strip_suffix(suffix)
+                .map(|digits| (digits, *multiplier))
+        })
+        .unwrap_or((value.as_str(), 1));
+
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// How much output may pile up for one class of clients before they are
+/// disconnected; a zero limit is disabled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutputBufferLimit {
+    /// Bytes at which the client is closed straight away
+    pub hard: u64,
+    /// Bytes the client may stay over for `soft_seconds` before it is closed
+    pub soft: u64,
+    pub soft_seconds: u64,
+}
+
+impl OutputBufferLimit {
+    /// Whether a client with `pending` bytes of output has overrun the limit
+    ///
+    /// `over_soft_since` is when the client went over the soft limit; it is
+    /// set and cleared here as the client crosses the limit.
+    pub fn exceeded(&self, pending: u64, over_soft_since: &mut Option<Instant>) -> bool {
+        if self.hard > 0 && pending >= self.hard {
+            return true;
+        }
+        if self.soft > 0 && pending >= self.soft {
+            let since = *over_soft_since.get_or_insert_with(Instant::now);
+            since.elapsed() >= Duration::from_secs(self.soft_seconds)
+        } else {
+            *over_soft_since = None;
+            false
+        }
+    }
+
+    /// When a client that went over the soft limit at `over_soft_since` has
+    /// been over it for too long, if it is over it at all
+    pub fn soft_deadline(&self, over_soft_since: Option<Instant>) -> Option<Instant> {
+        over_soft_since
+            .filter(|_| self.soft > 0)
+            .map(|since| since + Duration::from_secs(self.soft_seconds))
+    }
+}
+
+/// Output buffer limits by client class (`client-output-buffer-limit`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputBufferLimits {
+    pub normal: OutputBufferLimit,
+    pub replica: OutputBufferLimit,
+    pub pubsub: OutputBufferLimit,
+}
+
+impl Default for OutputBufferLimits {
+    /// The Redis defaults
+    fn default() -> Self {
+        Self {
+            normal: OutputBufferLimit::default(),
+            replica: OutputBufferLimit {
+                hard: 256 * 1024 * 1024,
+                soft: 64 * 1024 * 1024,
+                soft_seconds: 60,
+            },
+            pubsub: OutputBufferLimit {
+                hard: 32 * 1024 * 1024,
+                soft: 8 * 1024 * 1024,
+                soft_seconds: 60,
+            },
+        }
+    }
+}
+
+impl OutputBufferLimits {
+    /// Apply a `<class> <hard> <soft> <soft seconds>` list, as CONFIG SET
+    /// takes it; classes not mentioned keep their limits
+    pub fn update(&self, value: &str) -> Result<Self> {
+        let words: Vec<&str> = value.split_whitespace().collect();
+        if words.is_empty() || words.len() % 4 != 0 {
+            return Err(AikvError::InvalidArgument(
+                "ERR Wrong number of arguments in buffer limit configuration.".to_string(),
+            ));
+        }
+
+        let mut limits = *self;
+        for group in words.chunks(4) {
+            let limit = match group[0].to_lowercase().as_str() {
+                "normal" => &mut limits.normal,
+                "replica" | "slave" => &mut limits.replica,
+                "pubsub" => &mut limits.pubsub,
+                _ => {
+                    return Err(AikvError::InvalidArgument(
+                        "ERR Invalid client class specified in buffer limit configuration."
+                            .to_string(),
+                    ));
+                }
+            };
+            let (Some(hard), Some(soft), Ok(soft_seconds)) = (
+                parse_memory(group[1]),
+                parse_memory(group[2]),
+                group[3].parse::<u64>(),
+            ) else {
+                return Err(AikvError::InvalidArgument(
+                    "ERR Error in hard, soft or soft_seconds setting in buffer limit configuration."
+                        .to_string(),
+                ));
+            };
+            *limit = OutputBufferLimit {
+                hard,
+                soft,
+                soft_seconds,
+            };
+        }
+        Ok(limits)
+    }
+}
+
+impl fmt::Display for OutputBufferLimits {
+    /// The format CONFIG GET reports, with sizes in bytes
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let classes = [
+            ("normal", &self.normal),
+            ("slave", &self.replica),
+            ("pubsub", &self.pubsub),
+        ];
+        for (i, (class, limit)) in classes.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(
+                f,
+                "{} {} {} {}",
+                class, limit.hard, limit.soft, limit.soft_seconds
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Glob-style matching of a parameter name (`*`, `?`, `[...]` and `\`)
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    glob_match_recursive(&pattern, &name)
+}
+
+/// Glob matching that keeps the pattern's case, as channel patterns need
+pub fn glob_match_case_sensitive(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    glob_match_recursive(&pattern, &name)
+}
+
+fn glob_match_recursive(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|i| glob_match_recursive(&pattern[1..], &name[i..])),
+        Some('?') => !name.is_empty() && glob_match_recursive(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some((&c, rest)) = name.split_first() else {
+                return false;
+            };
+            let Some(end) = pattern.iter().skip(1).position(|&p| p == ']') else {
+                // An unterminated class matches a literal '['
+                return c == '[' && glob_match_recursive(&pattern[1..], rest);
+            };
+            let class = &pattern[1..end + 1];
+            let (negated, class) = match class.split_first() {
+                Some(('^', class)) => (true, class),
+                _ => (false, class),
+            };
+
+            let mut matched = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    matched |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    matched |= class[i] == c;
+                    i += 1;
+                }
+            }
+
+            matched != negated && glob_match_recursive(&pattern[end + 2..], rest)
+        }
+        Some('\\') if pattern.len() > 1 => {
+            name.first() == Some(&pattern[1]) && glob_match_recursive(&pattern[2..], &name[1..])
+        }
+        Some(&p) => name.first() == Some(&p) && glob_match_recursive(&pattern[1..], &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", "maxmemory"));
+        assert!(glob_match("max*", "maxmemory-policy"));
+        assert!(glob_match("slowlog-*-len", "slowlog-max-len"));
+        assert!(glob_match("port?", "ports"));
+        assert!(glob_match("[lm]*", "loglevel"));
+        assert!(glob_match("[^a-k]*", "maxmemory"));
+        assert!(!glob_match("[^a-m]*", "maxmemory"));
+        assert!(!glob_match("max*", "appendfsync"));
+        assert!(!glob_match("port?", "port"));
+    }
+
+    #[test]
+    fn test_parse_memory() {
+        assert_eq!(parse_memory("0"), Some(0));
+        assert_eq!(parse_memory("100mb"), Some(100_000_000));
+        assert_eq!(parse_memory("1G"), Some(1024 * 1024 * 1024));
+        assert_eq!(parse_memory("512b"), Some(512));
+        assert_eq!(parse_memory("mb"), None);
+        assert_eq!(parse_memory("-1"), None);
+        assert_eq!(parse_memory("10xb"), None);
+        assert_eq!(parse_memory("1gb"), Some(1_000_000_000));
+        assert_eq!(parse_memory("1GB"), Some(1_000_000_000));
+        assert_eq!(parse_memory("1gbi"), None);
+        assert_eq!(parse_memory("1.5gb"), None);
+    }
+
+    #[test]
+    fn test_matching_ignores_case() {
+        let config = RuntimeConfig::default();
+        let names: Vec<&str> = config
+            .matching("MAXMEMORY*")
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["maxmemory", "maxmemory-policy"]);
+    }
+
+    #[test]
+    fn test_output_buffer_limits() {
+        let defaults = OutputBufferLimits::default();
+        assert_eq!(
+            defaults.to_string(),
+            "normal 0 0 0 slave 268435456 67108864 60 pubsub 33554432 8388608 60"
+        );
+
+        let limits = defaults.update("pubsub 1000 512b 10").unwrap();
+        assert_eq!(limits.normal, defaults.normal);
+        assert_eq!(limits.replica, defaults.replica);
+        assert_eq!(
+            limits.pubsub,
+            OutputBufferLimit {
+                hard: 1000,
+                soft: 512,
+                soft_seconds: 10,
+            }
+        );
+        assert_eq!(defaults.update(&defaults.to_string()).unwrap(), defaults);
+
+        assert!(defaults.update("pubsub 1000 512").is_err());
+        assert!(defaults.update("monitor 0 0 0").is_err());
+        assert!(defaults.update("normal 1x 0 0").is_err());
+        assert!(defaults.update("").is_err());
+    }
+
+    #[test]
+    fn test_output_buffer_limit_exceeded() {
+        let limit = OutputBufferLimit {
+            hard: 100,
+            soft: 10,
+            soft_seconds: 60,
+        };
+        let mut since = None;
+        assert!(!limit.exceeded(5, &mut since));
+        assert_eq!(since, None);
+        // Over the soft limit starts the clock, but isn't fatal yet
+        assert!(!limit.exceeded(50, &mut since));
+        assert!(since.is_some());
+        assert!(limit.soft_deadline(since).is_some());
+        // Dropping back under resets it
+        assert!(!limit.exceeded(5, &mut since));
+        assert_eq!(since, None);
+        assert!(limit.exceeded(100, &mut since));
+
+        let immediate = OutputBufferLimit {
+            soft_seconds: 0,
+            ..limit
+        };
+        assert!(immediate.exceeded(50, &mut None));
+        assert!(!OutputBufferLimit::default().exceeded(u64::MAX, &mut None));
+    }
+
+    #[test]
+    fn test_check_settable() {
+        let config = RuntimeConfig::default();
+        assert!(config.check_settable("MAXMEMORY").is_ok());
+        assert!(config
+            .check_settable("port")
+            .unwrap_err()
+            .to_string()
+            .contains("immutable"));
+        assert!(config
+            .check_settable("no-such-option")
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown option"));
+    }
+}
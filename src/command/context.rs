@@ -0,0 +1,56 @@
+use super::server::AuthState;
+use crate::protocol::ProtocolVersion;
+use bytes::Bytes;
+use std::collections::HashSet;
+
+/// Per-connection state a command runs with, owned by the connection and
+/// handed to [`CommandExecutor::execute`](super::CommandExecutor::execute)
+///
+/// Commands that change connection state, such as SELECT, HELLO or CLIENT
+/// SETNAME, update it in place.
+#[derive(Debug, Clone, Default)]
+pub struct ClientContext {
+    /// The selected database (SELECT)
+    pub db: usize,
+    /// The connection's id, as CLIENT ID reports it
+    pub client_id: usize,
+    /// The protocol negotiated with HELLO
+    pub protocol: ProtocolVersion,
+    /// Whether the connection has authenticated (AUTH, HELLO ... AUTH)
+    pub auth: AuthState,
+    /// The name set with CLIENT SETNAME or HELLO ... SETNAME
+    pub name: Option<String>,
+    /// Channels the connection is subscribed to (SUBSCRIBE)
+    pub channels: HashSet<Bytes>,
+    /// Patterns the connection is subscribed to (PSUBSCRIBE)
+    pub patterns: HashSet<Bytes>,
+    /// CLIENT NO-TOUCH: the connection's commands don't update the access
+    /// time or frequency of the keys they read
+    pub no_touch: bool,
+    /// ASKING: the next command may use a slot this node is importing
+    pub asking: bool,
+    /// READONLY: read-only commands may use slots this node replicates,
+    /// until READWRITE
+    pub readonly: bool,
+}
+
+impl ClientContext {
+    /// The state of a fresh connection: database 0, RESP2, no name and
+    /// no subscriptions
+    pub fn new(client_id: usize) -> Self {
+        Self {
+            client_id,
+            ..Self::default()
+        }
+    }
+
+    /// Whether the connection is subscribed to any channel or pattern
+    pub fn is_subscribed(&self) -> bool {
+        !self.channels.is_empty() || !self.patterns.is_empty()
+    }
+
+    /// Channels and patterns subscribed to, as (UN)SUBSCRIBE replies count them
+    pub fn subscription_count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+}
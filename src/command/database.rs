@@ -3,15 +3,37 @@ use crate::protocol::RespValue;
 use crate::storage::StorageEngine;
 use bytes::Bytes;
 
+/// How FLUSHDB and FLUSHALL free the keys they remove
+enum FlushMode {
+    /// Before replying (the default)
+    Sync,
+    /// In the background, after replying
+    Async,
+}
+
+impl FlushMode {
+    fn parse(args: &[Bytes]) -> Result<Self> {
+        match args {
+            [] => Ok(FlushMode::Sync),
+            [mode] if mode.eq_ignore_ascii_case(b"SYNC") => Ok(FlushMode::Sync),
+            [mode] if mode.eq_ignore_ascii_case(b"ASYNC") => Ok(FlushMode::Async),
+            _ => Err(AikvError::InvalidArgument("ERR syntax error".to_string())),
+        }
+    }
+}
+
 /// Database command handler
 pub struct DatabaseCommands {
     storage: StorageEngine,
+    /// Number of databases the engine was configured with
+    db_count: usize,
 }
 
 impl DatabaseCommands {
-    pub fn new(storage: StorageEngine) -> Self {
+    pub fn new(storage: StorageEngine, db_count: usize) -> Self {
         Self {
             storage,
+            db_count,
         }
     }
 
@@ -21,17 +43,21 @@ impl DatabaseCommands {
             return Err(AikvError::WrongArgCount("SELECT".to_string()));
         }
 
-        let index_str = String::from_utf8_lossy(&args[0]);
-        let index = index_str
-            .parse::<usize>()
-            .map_err(|_| AikvError::InvalidArgument("ERR invalid DB index".to_string()))?;
-
-        if index >= 16 {
-            // Redis default is 16 databases
-            return Err(AikvError::InvalidArgument(
-                "ERR DB index is out of range".to_string(),
-            ));
-        }
+        let index = String::from_utf8_lossy(&args[0])
+            .parse::<i64>()
+            .map_err(|_| {
+                AikvError::InvalidArgument(
+                    "ERR value is not an integer or out of range".to_string(),
+                )
+            })?;
+
+        // Negative indices are out of range too, not malformed
+        let index = usize::try_from(index)
+            .ok()
+            .filter(|&index| index < self.db_count)
+            .ok_or_else(|| {
+                AikvError::InvalidArgument("ERR DB index is out of range".to_string())
+            })?;
 
         *current_db = index;
         Ok(RespValue::ok())
@@ -43,19 +69,31 @@ impl DatabaseCommands {
         Ok(RespValue::integer(size as i64))
     }
 
-    /// FLUSHDB - Clear current database
-    pub fn flushdb(&self, _args: &[Bytes], current_db: usize) -> Result<RespValue> {
-        self.storage.flush_db(current_db)?;
+    /// FLUSHDB [ASYNC | SYNC] - Clear current database
+    ///
+    /// Either way the database is empty when the reply is sent; ASYNC only
+    /// frees the removed keys in the background.
+    pub fn flushdb(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        match FlushMode::parse(args)? {
+            FlushMode::Sync => self.storage.flush_db(current_db)?,
+            FlushMode::Async => self.storage.flush_db_async(current_db)?,
+        }
         Ok(RespValue::ok())
     }
 
-    /// FLUSHALL - Clear all databases
-    pub fn flushall(&self, _args: &[Bytes]) -> Result<RespValue> {
-        self.storage.flush_all()?;
+    /// FLUSHALL [ASYNC | SYNC] - Clear all databases
+    pub fn flushall(&self, args: &[Bytes]) -> Result<RespValue> {
+        match FlushMode::parse(args)? {
+            FlushMode::Sync => self.storage.flush_all()?,
+            FlushMode::Async => self.storage.flush_all_async()?,
+        }
         Ok(RespValue::ok())
     }
 
     /// SWAPDB db1 db2 - Swap two databases
+    ///
+    /// Connections only hold the index they selected, so one that has
+    /// selected either database sees the other's keys from its next command.
     pub fn swapdb(&self, args: &[Bytes]) -> Result<RespValue> {
         if args.len() != 2 {
             return Err(AikvError::WrongArgCount("SWAPDB".to_string()));
@@ -71,7 +109,7 @@ impl DatabaseCommands {
             .parse::<usize>()
             .map_err(|_| AikvError::InvalidArgument("ERR invalid second DB index".to_string()))?;
 
-        if db1 >= 16 || db2 >= 16 {
+        if db1 >= self.db_count || db2 >= self.db_count {
             return Err(AikvError::InvalidArgument(
                 "ERR DB index is out of range".to_string(),
             ));
@@ -94,7 +132,7 @@ impl DatabaseCommands {
             .parse::<usize>()
             .map_err(|_| AikvError::InvalidArgument("ERR invalid DB index".to_string()))?;
 
-        if dest_db >= 16 {
+        if dest_db >= self.db_count {
             return Err(AikvError::InvalidArgument(
                 "ERR DB index is out of range".to_string(),
             ));
@@ -0,0 +1,738 @@
+//! Documentation of commands, as COMMAND DOCS reports it
+//!
+//! redis-cli uses these for its inline hints and completion. Commands
+//! without an entry here are still reported, with a generic summary and no
+//! arguments.
+
+use crate::protocol::RespValue;
+
+/// One argument of a command
+#[derive(Clone, Copy)]
+pub struct ArgDoc {
+    pub name: &'static str,
+    /// key, string, integer, double, pattern, unix-time, pure-token, oneof or block
+    pub kind: &'static str,
+    /// The literal word that introduces the argument, such as `EX` or `COUNT`
+    pub token: Option<&'static str>,
+    /// optional, multiple and multiple_token
+    pub flags: &'static [&'static str],
+    /// The alternatives of a `oneof`, or the parts of a `block`
+    pub arguments: &'static [ArgDoc],
+}
+
+impl ArgDoc {
+    const fn new(name: &'static str, kind: &'static str) -> Self {
+        Self {
+            name,
+            kind,
+            token: None,
+            flags: &[],
+            arguments: &[],
+        }
+    }
+
+    const fn token(self, token: &'static str) -> Self {
+        Self {
+            token: Some(token),
+            ..self
+        }
+    }
+
+    const fn flags(self, flags: &'static [&'static str]) -> Self {
+        Self {
+            flags,
+            ..self
+        }
+    }
+}
+
+/// Documentation of one command
+pub struct CommandDoc {
+    pub name: &'static str,
+    pub summary: &'static str,
+    /// The Redis version that introduced the command
+    pub since: &'static str,
+    pub group: &'static str,
+    pub complexity: &'static str,
+    pub arguments: &'static [ArgDoc],
+}
+
+const fn key(name: &'static str) -> ArgDoc {
+    ArgDoc::new(name, "key")
+}
+
+const fn string(name: &'static str) -> ArgDoc {
+    ArgDoc::new(name, "string")
+}
+
+const fn integer(name: &'static str) -> ArgDoc {
+    ArgDoc::new(name, "integer")
+}
+
+const fn double(name: &'static str) -> ArgDoc {
+    ArgDoc::new(name, "double")
+}
+
+/// A literal word on its own, such as `NX`
+const fn token(name: &'static str, token: &'static str) -> ArgDoc {
+    ArgDoc::new(name, "pure-token").token(token)
+}
+
+const fn oneof(name: &'static str, arguments: &'static [ArgDoc]) -> ArgDoc {
+    ArgDoc {
+        arguments,
+        ..ArgDoc::new(name, "oneof")
+    }
+}
+
+const fn block(name: &'static str, arguments: &'static [ArgDoc]) -> ArgDoc {
+    ArgDoc {
+        arguments,
+        ..ArgDoc::new(name, "block")
+    }
+}
+
+const OPTIONAL: &[&str] = &["optional"];
+const MULTIPLE: &[&str] = &["multiple"];
+
+/// `[COUNT count]`
+const COUNT: ArgDoc = integer("count").token("COUNT").flags(OPTIONAL);
+
+static COMMAND_DOCS: &[CommandDoc] = &[
+    // String commands
+    CommandDoc {
+        name: "GET",
+        summary: "Returns the string value of a key.",
+        since: "1.0.0",
+        group: "string",
+        complexity: "O(1)",
+        arguments: &[key("key")],
+    },
+    CommandDoc {
+        name: "SET",
+        summary: "Sets the string value of a key, ignoring its type. The key is created if it doesn't exist.",
+        since: "1.0.0",
+        group: "string",
+        complexity: "O(1)",
+        arguments: &[
+            key("key"),
+            string("value"),
+            oneof("condition", &[token("nx", "NX"), token("xx", "XX")]).flags(OPTIONAL),
+            token("get", "GET").flags(OPTIONAL),
+            oneof(
+                "expiration",
+                &[
+                    integer("seconds").token("EX"),
+                    integer("milliseconds").token("PX"),
+                    ArgDoc::new("unix-time-seconds", "unix-time").token("EXAT"),
+                    ArgDoc::new("unix-time-milliseconds", "unix-time").token("PXAT"),
+                    token("keepttl", "KEEPTTL"),
+                ],
+            )
+            .flags(OPTIONAL),
+        ],
+    },
+    CommandDoc {
+        name: "MGET",
+        summary: "Atomically returns the string values of one or more keys.",
+        since: "1.0.0",
+        group: "string",
+        complexity: "O(N) where N is the number of keys to retrieve.",
+        arguments: &[key("key").flags(MULTIPLE)],
+    },
+    CommandDoc {
+        name: "MSET",
+        summary: "Atomically creates or modifies the string values of one or more keys.",
+        since: "1.0.1",
+        group: "string",
+        complexity: "O(N) where N is the number of keys to set.",
+        arguments: &[block("data", &[key("key"), string("value")]).flags(MULTIPLE)],
+    },
+    CommandDoc {
+        name: "APPEND",
+        summary: "Appends a string to the value of a key. Creates the key if it doesn't exist.",
+        since: "2.0.0",
+        group: "string",
+        complexity: "O(1).",
+        arguments: &[key("key"), string("value")],
+    },
+    CommandDoc {
+        name: "STRLEN",
+        summary: "Returns the length of a string value.",
+        since: "2.2.0",
+        group: "string",
+        complexity: "O(1)",
+        arguments: &[key("key")],
+    },
+    CommandDoc {
+        name: "INCR",
+        summary: "Increments the integer value of a key by one. Uses 0 as initial value if the key doesn't exist.",
+        since: "1.0.0",
+        group: "string",
+        complexity: "O(1)",
+        arguments: &[key("key")],
+    },
+    CommandDoc {
+        name: "DECR",
+        summary: "Decrements the integer value of a key by one. Uses 0 as initial value if the key doesn't exist.",
+        since: "1.0.0",
+        group: "string",
+        complexity: "O(1)",
+        arguments: &[key("key")],
+    },
+    CommandDoc {
+        name: "INCRBY",
+        summary: "Increments the integer value of a key by a number. Uses 0 as initial value if the key doesn't exist.",
+        since: "1.0.0",
+        group: "string",
+        complexity: "O(1)",
+        arguments: &[key("key"), integer("increment")],
+    },
+    CommandDoc {
+        name: "DECRBY",
+        summary: "Decrements a number from the integer value of a key. Uses 0 as initial value if the key doesn't exist.",
+        since: "1.0.0",
+        group: "string",
+        complexity: "O(1)",
+        arguments: &[key("key"), integer("decrement")],
+    },
+    CommandDoc {
+        name: "GETRANGE",
+        summary: "Returns a substring of the string stored at a key.",
+        since: "2.4.0",
+        group: "string",
+        complexity: "O(N) where N is the length of the returned string.",
+        arguments: &[key("key"), integer("start"), integer("end")],
+    },
+    CommandDoc {
+        name: "SETRANGE",
+        summary: "Overwrites a part of a string value with another by an offset. Creates the key if it doesn't exist.",
+        since: "2.2.0",
+        group: "string",
+        complexity: "O(1), not counting the time taken to copy the new string in place.",
+        arguments: &[key("key"), integer("offset"), string("value")],
+    },
+    // Key commands
+    CommandDoc {
+        name: "DEL",
+        summary: "Deletes one or more keys.",
+        since: "1.0.0",
+        group: "generic",
+        complexity: "O(N) where N is the number of keys that will be removed.",
+        arguments: &[key("key").flags(MULTIPLE)],
+    },
+    CommandDoc {
+        name: "EXISTS",
+        summary: "Determines whether one or more keys exist.",
+        since: "1.0.0",
+        group: "generic",
+        complexity: "O(N) where N is the number of keys to check.",
+        arguments: &[key("key").flags(MULTIPLE)],
+    },
+    CommandDoc {
+        name: "TYPE",
+        summary: "Determines the type of value stored at a key.",
+        since: "1.0.0",
+        group: "generic",
+        complexity: "O(1)",
+        arguments: &[key("key")],
+    },
+    CommandDoc {
+        name: "KEYS",
+        summary: "Returns all key names that match a pattern.",
+        since: "1.0.0",
+        group: "generic",
+        complexity: "O(N) with N being the number of keys in the database.",
+        arguments: &[ArgDoc::new("pattern", "pattern")],
+    },
+    CommandDoc {
+        name: "SCAN",
+        summary: "Iterates over the key names in the database.",
+        since: "2.8.0",
+        group: "generic",
+        complexity: "O(1) for every call. O(N) for a complete iteration.",
+        arguments: &[
+            integer("cursor"),
+            ArgDoc::new("pattern", "pattern")
+                .token("MATCH")
+                .flags(OPTIONAL),
+            COUNT,
+            string("type").token("TYPE").flags(OPTIONAL),
+        ],
+    },
+    CommandDoc {
+        name: "RENAME",
+        summary: "Renames a key and overwrites the destination.",
+        since: "1.0.0",
+        group: "generic",
+        complexity: "O(1)",
+        arguments: &[key("key"), key("newkey")],
+    },
+    CommandDoc {
+        name: "EXPIRE",
+        summary: "Sets the expiration time of a key in seconds.",
+        since: "1.0.0",
+        group: "generic",
+        complexity: "O(1)",
+        arguments: &[
+            key("key"),
+            integer("seconds"),
+            oneof(
+                "condition",
+                &[
+                    token("nx", "NX"),
+                    token("xx", "XX"),
+                    token("gt", "GT"),
+                    token("lt", "LT"),
+                ],
+            )
+            .flags(OPTIONAL),
+        ],
+    },
+    CommandDoc {
+        name: "TTL",
+        summary: "Returns the expiration time in seconds of a key.",
+        since: "1.0.0",
+        group: "generic",
+        complexity: "O(1)",
+        arguments: &[key("key")],
+    },
+    CommandDoc {
+        name: "PTTL",
+        summary: "Returns the expiration time in milliseconds of a key.",
+        since: "2.6.0",
+        group: "generic",
+        complexity: "O(1)",
+        arguments: &[key("key")],
+    },
+    CommandDoc {
+        name: "PERSIST",
+        summary: "Removes the expiration time of a key.",
+        since: "2.2.0",
+        group: "generic",
+        complexity: "O(1)",
+        arguments: &[key("key")],
+    },
+    // List commands
+    CommandDoc {
+        name: "LPUSH",
+        summary: "Prepends one or more elements to a list. Creates the key if it doesn't exist.",
+        since: "1.0.0",
+        group: "list",
+        complexity: "O(1) for each element added.",
+        arguments: &[key("key"), string("element").flags(MULTIPLE)],
+    },
+    CommandDoc {
+        name: "RPUSH",
+        summary: "Appends one or more elements to a list. Creates the key if it doesn't exist.",
+        since: "1.0.0",
+        group: "list",
+        complexity: "O(1) for each element added.",
+        arguments: &[key("key"), string("element").flags(MULTIPLE)],
+    },
+    CommandDoc {
+        name: "LPOP",
+        summary: "Returns the first elements in a list after removing it. Deletes the list if the last element was popped.",
+        since: "1.0.0",
+        group: "list",
+        complexity: "O(N) where N is the number of elements returned",
+        arguments: &[key("key"), integer("count").flags(OPTIONAL)],
+    },
+    CommandDoc {
+        name: "RPOP",
+        summary: "Returns and removes the last elements of a list. Deletes the list if the last element was popped.",
+        since: "1.0.0",
+        group: "list",
+        complexity: "O(N) where N is the number of elements returned",
+        arguments: &[key("key"), integer("count").flags(OPTIONAL)],
+    },
+    CommandDoc {
+        name: "LLEN",
+        summary: "Returns the length of a list.",
+        since: "1.0.0",
+        group: "list",
+        complexity: "O(1)",
+        arguments: &[key("key")],
+    },
+    CommandDoc {
+        name: "LRANGE",
+        summary: "Returns a range of elements from a list.",
+        since: "1.0.0",
+        group: "list",
+        complexity: "O(S+N) where S is the distance of start offset from HEAD for small lists, from nearest end (HEAD or TAIL) for large lists; and N is the number of elements in the specified range.",
+        arguments: &[key("key"), integer("start"), integer("stop")],
+    },
+    // Hash commands
+    CommandDoc {
+        name: "HSET",
+        summary: "Creates or modifies the value of a field in a hash.",
+        since: "2.0.0",
+        group: "hash",
+        complexity: "O(1) for each field/value pair added.",
+        arguments: &[
+            key("key"),
+            block("data", &[string("field"), string("value")]).flags(MULTIPLE),
+        ],
+    },
+    CommandDoc {
+        name: "HGET",
+        summary: "Returns the value of a field in a hash.",
+        since: "2.0.0",
+        group: "hash",
+        complexity: "O(1)",
+        arguments: &[key("key"), string("field")],
+    },
+    CommandDoc {
+        name: "HDEL",
+        summary: "Deletes one or more fields and their values from a hash. Deletes the hash if no fields remain.",
+        since: "2.0.0",
+        group: "hash",
+        complexity: "O(N) where N is the number of fields to be removed.",
+        arguments: &[key("key"), string("field").flags(MULTIPLE)],
+    },
+    CommandDoc {
+        name: "HGETALL",
+        summary: "Returns all fields and values in a hash.",
+        since: "2.0.0",
+        group: "hash",
+        complexity: "O(N) where N is the size of the hash.",
+        arguments: &[key("key")],
+    },
+    // Set commands
+    CommandDoc {
+        name: "SADD",
+        summary: "Adds one or more members to a set. Creates the key if it doesn't exist.",
+        since: "1.0.0",
+        group: "set",
+        complexity: "O(1) for each element added.",
+        arguments: &[key("key"), string("member").flags(MULTIPLE)],
+    },
+    CommandDoc {
+        name: "SREM",
+        summary: "Removes one or more members from a set. Deletes the set if the last member was removed.",
+        since: "1.0.0",
+        group: "set",
+        complexity: "O(N) where N is the number of members to be removed.",
+        arguments: &[key("key"), string("member").flags(MULTIPLE)],
+    },
+    CommandDoc {
+        name: "SMEMBERS",
+        summary: "Returns all members of a set.",
+        since: "1.0.0",
+        group: "set",
+        complexity: "O(N) where N is the set cardinality.",
+        arguments: &[key("key")],
+    },
+    CommandDoc {
+        name: "SISMEMBER",
+        summary: "Determines whether a member belongs to a set.",
+        since: "1.0.0",
+        group: "set",
+        complexity: "O(1)",
+        arguments: &[key("key"), string("member")],
+    },
+    // Sorted set commands
+    CommandDoc {
+        name: "ZADD",
+        summary: "Adds one or more members to a sorted set, or updates their scores. Creates the key if it doesn't exist.",
+        since: "1.2.0",
+        group: "sorted-set",
+        complexity: "O(log(N)) for each item added, where N is the number of elements in the sorted set.",
+        arguments: &[
+            key("key"),
+            oneof("condition", &[token("nx", "NX"), token("xx", "XX")]).flags(OPTIONAL),
+            oneof("comparison", &[token("gt", "GT"), token("lt", "LT")]).flags(OPTIONAL),
+            token("change", "CH").flags(OPTIONAL),
+            token("increment", "INCR").flags(OPTIONAL),
+            block("data", &[double("score"), string("member")]).flags(MULTIPLE),
+        ],
+    },
+    CommandDoc {
+        name: "ZREM",
+        summary: "Removes one or more members from a sorted set. Deletes the sorted set if all members were removed.",
+        since: "1.2.0",
+        group: "sorted-set",
+        complexity: "O(M*log(N)) with N being the number of elements in the sorted set and M the number of elements to be removed.",
+        arguments: &[key("key"), string("member").flags(MULTIPLE)],
+    },
+    CommandDoc {
+        name: "ZSCORE",
+        summary: "Returns the score of a member in a sorted set.",
+        since: "1.2.0",
+        group: "sorted-set",
+        complexity: "O(1)",
+        arguments: &[key("key"), string("member")],
+    },
+    CommandDoc {
+        name: "ZRANGE",
+        summary: "Returns members in a sorted set within a range of indexes.",
+        since: "1.2.0",
+        group: "sorted-set",
+        complexity: "O(log(N)+M) with N being the number of elements in the sorted set and M the number of elements returned.",
+        arguments: &[
+            key("key"),
+            string("start"),
+            string("stop"),
+            oneof(
+                "sortby",
+                &[token("byscore", "BYSCORE"), token("bylex", "BYLEX")],
+            )
+            .flags(OPTIONAL),
+            token("rev", "REV").flags(OPTIONAL),
+            block("limit", &[integer("offset"), integer("count")])
+                .token("LIMIT")
+                .flags(OPTIONAL),
+            token("withscores", "WITHSCORES").flags(OPTIONAL),
+        ],
+    },
+    CommandDoc {
+        name: "ZCARD",
+        summary: "Returns the number of members in a sorted set.",
+        since: "1.2.0",
+        group: "sorted-set",
+        complexity: "O(1)",
+        arguments: &[key("key")],
+    },
+    // Stream commands
+    CommandDoc {
+        name: "XADD",
+        summary: "Appends a new message to a stream. Creates the key if it doesn't exist.",
+        since: "5.0.0",
+        group: "stream",
+        complexity: "O(1) when adding a new entry.",
+        arguments: &[
+            key("key"),
+            oneof("id-selector", &[token("auto-id", "*"), string("id")]),
+            block("data", &[string("field"), string("value")]).flags(MULTIPLE),
+        ],
+    },
+    CommandDoc {
+        name: "XLEN",
+        summary: "Return the number of messages in a stream.",
+        since: "5.0.0",
+        group: "stream",
+        complexity: "O(1)",
+        arguments: &[key("key")],
+    },
+    CommandDoc {
+        name: "XRANGE",
+        summary: "Returns the messages from a stream within a range of IDs.",
+        since: "5.0.0",
+        group: "stream",
+        complexity: "O(N) with N being the number of elements being returned.",
+        arguments: &[key("key"), string("start"), string("end"), COUNT],
+    },
+    CommandDoc {
+        name: "XREAD",
+        summary: "Returns messages from multiple streams with IDs greater than the ones requested.",
+        since: "5.0.0",
+        group: "stream",
+        complexity: "O(N) with N being the number of elements being returned.",
+        arguments: &[
+            COUNT,
+            block(
+                "streams",
+                &[key("key").flags(MULTIPLE), string("id").flags(MULTIPLE)],
+            )
+            .token("STREAMS"),
+        ],
+    },
+    // Connection and server commands
+    CommandDoc {
+        name: "PING",
+        summary: "Returns the server's liveliness response.",
+        since: "1.0.0",
+        group: "connection",
+        complexity: "O(1)",
+        arguments: &[string("message").flags(OPTIONAL)],
+    },
+    CommandDoc {
+        name: "ECHO",
+        summary: "Returns the given string.",
+        since: "1.0.0",
+        group: "connection",
+        complexity: "O(1)",
+        arguments: &[string("message")],
+    },
+    CommandDoc {
+        name: "SELECT",
+        summary: "Changes the selected database.",
+        since: "1.0.0",
+        group: "connection",
+        complexity: "O(1)",
+        arguments: &[integer("index")],
+    },
+    CommandDoc {
+        name: "AUTH",
+        summary: "Authenticates the connection.",
+        since: "1.0.0",
+        group: "connection",
+        complexity: "O(N) where N is the number of passwords defined for the user",
+        arguments: &[string("username").flags(OPTIONAL), string("password")],
+    },
+    CommandDoc {
+        name: "DBSIZE",
+        summary: "Returns the number of keys in the database.",
+        since: "1.0.0",
+        group: "server",
+        complexity: "O(1)",
+        arguments: &[],
+    },
+    CommandDoc {
+        name: "INFO",
+        summary: "Returns information and statistics about the server.",
+        since: "1.0.0",
+        group: "server",
+        complexity: "O(1)",
+        arguments: &[string("section").flags(&["optional", "multiple"])],
+    },
+    CommandDoc {
+        name: "FLUSHDB",
+        summary: "Remove all keys from the current database.",
+        since: "1.0.0",
+        group: "server",
+        complexity: "O(N) where N is the number of keys in the selected database",
+        arguments: &[oneof(
+            "flush-type",
+            &[token("async", "ASYNC"), token("sync", "SYNC")],
+        )
+        .flags(OPTIONAL)],
+    },
+    CommandDoc {
+        name: "FLUSHALL",
+        summary: "Removes all keys from all databases.",
+        since: "1.0.0",
+        group: "server",
+        complexity: "O(N) where N is the total number of keys in all databases",
+        arguments: &[oneof(
+            "flush-type",
+            &[token("async", "ASYNC"), token("sync", "SYNC")],
+        )
+        .flags(OPTIONAL)],
+    },
+    // Pub/Sub commands
+    CommandDoc {
+        name: "PUBLISH",
+        summary: "Posts a message to a channel.",
+        since: "2.0.0",
+        group: "pubsub",
+        complexity: "O(N+M) where N is the number of clients subscribed to the receiving channel and M is the total number of subscribed patterns (by any client).",
+        arguments: &[string("channel"), string("message")],
+    },
+    CommandDoc {
+        name: "SUBSCRIBE",
+        summary: "Listens for messages published to channels.",
+        since: "2.0.0",
+        group: "pubsub",
+        complexity: "O(N) where N is the number of channels to subscribe to.",
+        arguments: &[string("channel").flags(MULTIPLE)],
+    },
+];
+
+/// The documentation of `name` (upper case), if it has any
+pub fn lookup_doc(name: &str) -> Option<&'static CommandDoc> {
+    COMMAND_DOCS.iter().find(|doc| doc.name == name)
+}
+
+/// A command's documentation as COMMAND DOCS replies with it, under its
+/// lower-case name; `name` is used for commands without documentation
+pub fn docs_reply(name: &str) -> (RespValue, RespValue) {
+    let fields = match lookup_doc(name) {
+        Some(doc) => {
+            let mut key_spec_index = 0;
+            vec![
+                (
+                    RespValue::bulk_string("summary"),
+                    RespValue::bulk_string(doc.summary),
+                ),
+                (
+                    RespValue::bulk_string("since"),
+                    RespValue::bulk_string(doc.since),
+                ),
+                (
+                    RespValue::bulk_string("group"),
+                    RespValue::bulk_string(doc.group),
+                ),
+                (
+                    RespValue::bulk_string("complexity"),
+                    RespValue::bulk_string(doc.complexity),
+                ),
+                (
+                    RespValue::bulk_string("arguments"),
+                    arguments_reply(doc.arguments, &mut key_spec_index),
+                ),
+            ]
+        }
+        None => vec![
+            (
+                RespValue::bulk_string("summary"),
+                RespValue::bulk_string(format!("{} command", name)),
+            ),
+            (
+                RespValue::bulk_string("since"),
+                RespValue::bulk_string("1.0.0"),
+            ),
+            (
+                RespValue::bulk_string("group"),
+                RespValue::bulk_string("generic"),
+            ),
+        ],
+    };
+    (
+        RespValue::bulk_string(name.to_lowercase()),
+        RespValue::map(fields),
+    )
+}
+
+/// The argument specs of a command; key arguments are numbered in order,
+/// as the command's key specs are
+fn arguments_reply(arguments: &[ArgDoc], key_spec_index: &mut i64) -> RespValue {
+    RespValue::array(
+        arguments
+            .iter()
+            .map(|arg| {
+                let mut fields = vec![
+                    (
+                        RespValue::bulk_string("name"),
+                        RespValue::bulk_string(arg.name),
+                    ),
+                    (
+                        RespValue::bulk_string("type"),
+                        RespValue::bulk_string(arg.kind),
+                    ),
+                ];
+                if arg.kind == "key" {
+                    fields.push((
+                        RespValue::bulk_string("key_spec_index"),
+                        RespValue::integer(*key_spec_index),
+                    ));
+                    *key_spec_index += 1;
+                }
+                if let Some(token) = arg.token {
+                    fields.push((
+                        RespValue::bulk_string("token"),
+                        RespValue::bulk_string(token),
+                    ));
+                }
+                if !arg.flags.is_empty() {
+                    fields.push((
+                        RespValue::bulk_string("flags"),
+                        RespValue::set(
+                            arg.flags
+                                .iter()
+                                .map(|flag| RespValue::simple_string(*flag))
+                                .collect(),
+                        ),
+                    ));
+                }
+                if !arg.arguments.is_empty() {
+                    fields.push((
+                        RespValue::bulk_string("arguments"),
+                        arguments_reply(arg.arguments, key_spec_index),
+                    ));
+                }
+                RespValue::map(fields)
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,529 @@
+use super::string::{format_float, parse_float};
+use crate::error::{AikvError, Result};
+use crate::protocol::RespValue;
+use crate::storage::{StorageEngine, StoredValue};
+use bytes::Bytes;
+use std::collections::BTreeMap;
+
+/// Longitudes and latitudes a geohash can encode, as in Redis (EPSG:3857)
+const LONGITUDE_MIN: f64 = -180.0;
+const LONGITUDE_MAX: f64 = 180.0;
+const LATITUDE_MIN: f64 = -85.05112878;
+const LATITUDE_MAX: f64 = 85.05112878;
+
+/// Bits per coordinate; the interleaved hash is twice as long and so fits
+/// exactly in the 53-bit mantissa of a sorted set score
+const GEO_STEP: u32 = 26;
+
+/// Earth's radius as Redis uses it for distances
+const EARTH_RADIUS_IN_METERS: f64 = 6372797.560856;
+
+/// Geospatial command handler
+///
+/// Points live in a sorted set, each member scored with the 52-bit geohash
+/// of its position, so the ZSET commands work on them too.
+pub struct GeoCommands {
+    storage: StorageEngine,
+}
+
+/// Spread the low 32 bits of `v` over the even bits of a u64
+fn spread(v: u32) -> u64 {
+    let mut x = v as u64;
+    x = (x | (x << 16)) & 0x0000_FFFF_0000_FFFF;
+    x = (x | (x << 8)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x << 2)) & 0x3333_3333_3333_3333;
+    (x | (x << 1)) & 0x5555_5555_5555_5555
+}
+
+/// Gather the even bits of `x` back into a u32, undoing `spread`
+fn squash(x: u64) -> u32 {
+    let mut x = x & 0x5555_5555_5555_5555;
+    x = (x | (x >> 1)) & 0x3333_3333_3333_3333;
+    x = (x | (x >> 2)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x >> 4)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x >> 8)) & 0x0000_FFFF_0000_FFFF;
+    ((x | (x >> 16)) & 0x0000_0000_FFFF_FFFF) as u32
+}
+
+/// The geohash of a position: latitude bits on the even positions,
+/// longitude bits on the odd ones
+fn encode(longitude: f64, latitude: f64) -> u64 {
+    let cells = (1u64 << GEO_STEP) as f64;
+    let max_cell = (1u32 << GEO_STEP) - 1;
+    let cell = |value: f64, min: f64, max: f64| {
+        (((value - min) / (max - min) * cells) as u32).min(max_cell)
+    };
+    spread(cell(latitude, LATITUDE_MIN, LATITUDE_MAX))
+        | (spread(cell(longitude, LONGITUDE_MIN, LONGITUDE_MAX)) << 1)
+}
+
+/// The (longitude, latitude) at the center of a geohash's cell
+fn decode(hash: u64) -> (f64, f64) {
+    let cells = (1u64 << GEO_STEP) as f64;
+    let center = |cell: u32, min: f64, max: f64| {
+        let low = min + (cell as f64 / cells) * (max - min);
+        let high = min + ((cell as f64 + 1.0) / cells) * (max - min);
+        ((low + high) / 2.0).clamp(min, max)
+    };
+    (
+        center(squash(hash >> 1), LONGITUDE_MIN, LONGITUDE_MAX),
+        center(squash(hash), LATITUDE_MIN, LATITUDE_MAX),
+    )
+}
+
+/// Distance in meters between two points along a meridian
+fn latitude_distance(latitude1: f64, latitude2: f64) -> f64 {
+    EARTH_RADIUS_IN_METERS * (latitude2.to_radians() - latitude1.to_radians()).abs()
+}
+
+/// Great-circle distance in meters between two points (haversine formula)
+fn distance(longitude1: f64, latitude1: f64, longitude2: f64, latitude2: f64) -> f64 {
+    let v = ((longitude2.to_radians() - longitude1.to_radians()) / 2.0).sin();
+    // Same longitude: the cheaper meridian distance is exact
+    if v == 0.0 {
+        return latitude_distance(latitude1, latitude2);
+    }
+    let u = ((latitude2.to_radians() - latitude1.to_radians()) / 2.0).sin();
+    let a = u * u + latitude1.to_radians().cos() * latitude2.to_radians().cos() * v * v;
+    2.0 * EARTH_RADIUS_IN_METERS * a.sqrt().asin()
+}
+
+/// Meters in one of the distance units GEO commands accept
+fn parse_unit(unit: &[u8]) -> Result<f64> {
+    match String::from_utf8_lossy(unit).to_lowercase().as_str() {
+        "m" => Ok(1.0),
+        "km" => Ok(1000.0),
+        "ft" => Ok(0.3048),
+        "mi" => Ok(1609.34),
+        _ => Err(AikvError::InvalidArgument(
+            "ERR unsupported unit provided. please use M, KM, FT, MI".to_string(),
+        )),
+    }
+}
+
+fn parse_number(arg: &[u8]) -> Result<f64> {
+    parse_float(arg)
+        .ok_or_else(|| AikvError::InvalidArgument("ERR value is not a valid float".to_string()))
+}
+
+/// Parse a longitude and latitude, rejecting positions a geohash can't encode
+fn parse_position(longitude: &[u8], latitude: &[u8]) -> Result<(f64, f64)> {
+    let longitude = parse_number(longitude)?;
+    let latitude = parse_number(latitude)?;
+    if !(LONGITUDE_MIN..=LONGITUDE_MAX).contains(&longitude)
+        || !(LATITUDE_MIN..=LATITUDE_MAX).contains(&latitude)
+    {
+        return Err(AikvError::InvalidArgument(format!(
+            "ERR invalid longitude,latitude pair {:.6},{:.6}",
+            longitude, latitude
+        )));
+    }
+    Ok((longitude, latitude))
+}
+
+/// A distance as GEO commands reply with it
+fn format_distance(meters: f64, unit: f64) -> RespValue {
+    RespValue::bulk_string(format!("{:.4}", meters / unit))
+}
+
+fn format_position(longitude: f64, latitude: f64) -> RespValue {
+    RespValue::array(vec![
+        RespValue::bulk_string(format_float(longitude)),
+        RespValue::bulk_string(format_float(latitude)),
+    ])
+}
+
+/// Where a GEOSEARCH is centered
+enum Center {
+    Member(Bytes),
+    Position(f64, f64),
+}
+
+/// The area a GEOSEARCH covers, in meters
+enum Shape {
+    Radius(f64),
+    Box { width: f64, height: f64 },
+}
+
+/// Options of one GEOSEARCH call
+struct SearchOptions {
+    center: Center,
+    shape: Shape,
+    /// Meters per unit of the distances in the reply
+    unit: f64,
+    /// `Some(true)` for DESC, `Some(false)` for ASC
+    descending: Option<bool>,
+    /// How many points to return, and whether any that many will do (ANY)
+    count: Option<(usize, bool)>,
+    with_coord: bool,
+    with_dist: bool,
+    with_hash: bool,
+}
+
+impl SearchOptions {
+    fn parse(args: &[Bytes]) -> Result<Self> {
+        let syntax_error = || AikvError::InvalidArgument("ERR syntax error".to_string());
+        let mut center = None;
+        let mut centers = 0;
+        let mut shape = None;
+        let mut shapes = 0;
+        let mut unit = 1.0;
+        let mut descending = None;
+        let mut count = None;
+        let mut any = false;
+        let (mut with_coord, mut with_dist, mut with_hash) = (false, false, false);
+
+        let mut i = 0;
+        while i < args.len() {
+            let option = String::from_utf8_lossy(&args[i]).to_uppercase();
+            let remaining = args.len() - i - 1;
+            match option.as_str() {
+                "FROMMEMBER" if remaining >= 1 => {
+                    center = Some(Center::Member(args[i + 1].clone()));
+                    centers += 1;
+                    i += 1;
+                }
+                "FROMLONLAT" if remaining >= 2 => {
+                    let (longitude, latitude) = parse_position(&args[i + 1], &args[i + 2])?;
+                    center = Some(Center::Position(longitude, latitude));
+                    centers += 1;
+                    i += 2;
+                }
+                "BYRADIUS" if remaining >= 2 => {
+                    let radius = parse_number(&args[i + 1])?;
+                    if radius < 0.0 {
+                        return Err(AikvError::InvalidArgument(
+                            "ERR radius cannot be negative".to_string(),
+                        ));
+                    }
+                    unit = parse_unit(&args[i + 2])?;
+                    shape = Some(Shape::Radius(radius * unit));
+                    shapes += 1;
+                    i += 2;
+                }
+                "BYBOX" if remaining >= 3 => {
+                    let width = parse_number(&args[i + 1])?;
+                    let height = parse_number(&args[i + 2])?;
+                    if width < 0.0 || height < 0.0 {
+                        return Err(AikvError::InvalidArgument(
+                            "ERR height or width cannot be negative".to_string(),
+                        ));
+                    }
+                    unit = parse_unit(&args[i + 3])?;
+                    shape = Some(Shape::Box {
+                        width: width * unit,
+                        height: height * unit,
+                    });
+                    shapes += 1;
+                    i += 3;
+                }
+                "ASC" => descending = Some(false),
+                "DESC" => descending = Some(true),
+                "COUNT" if remaining >= 1 => {
+                    let n = String::from_utf8_lossy(&args[i + 1])
+                        .parse::<i64>()
+                        .map_err(|_| {
+                            AikvError::InvalidArgument(
+                                "ERR value is not an integer or out of range".to_string(),
+                            )
+                        })?;
+                    if n <= 0 {
+                        return Err(AikvError::InvalidArgument(
+                            "ERR COUNT must be > 0".to_string(),
+                        ));
+                    }
+                    count = Some(n as usize);
+                    i += 1;
+                }
+                "ANY" => any = true,
+                "WITHCOORD" => with_coord = true,
+                "WITHDIST" => with_dist = true,
+                "WITHHASH" => with_hash = true,
+                _ => return Err(syntax_error()),
+            }
+            i += 1;
+        }
+
+        let center =
+            match center {
+                Some(center) if centers == 1 => center,
+                _ => return Err(AikvError::InvalidArgument(
+                    "ERR exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH"
+                        .to_string(),
+                )),
+            };
+        let shape = match shape {
+            Some(shape) if shapes == 1 => shape,
+            _ => {
+                return Err(AikvError::InvalidArgument(
+                    "ERR exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH"
+                        .to_string(),
+                ))
+            }
+        };
+        if any && count.is_none() {
+            return Err(AikvError::InvalidArgument(
+                "ERR the ANY argument requires COUNT argument".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            center,
+            shape,
+            unit,
+            descending,
+            count: count.map(|n| (n, any)),
+            with_coord,
+            with_dist,
+            with_hash,
+        })
+    }
+}
+
+/// A point GEOSEARCH found
+struct GeoPoint {
+    member: Vec<u8>,
+    hash: u64,
+    longitude: f64,
+    latitude: f64,
+    /// Meters from the search center
+    distance: f64,
+}
+
+impl GeoCommands {
+    pub fn new(storage: StorageEngine) -> Self {
+        Self {
+            storage,
+        }
+    }
+
+    /// GEOADD key \[NX|XX\] \[CH\] longitude latitude member \[longitude latitude member ...\]
+    /// Add points to a geospatial index, returning how many were added
+    /// (or changed, with CH)
+    pub fn geoadd(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        if args.len() < 4 {
+            return Err(AikvError::WrongArgCount("GEOADD".to_string()));
+        }
+
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+        let (mut nx, mut xx, mut ch) = (false, false, false);
+        let mut i = 1;
+        while i < args.len() {
+            match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+                "NX" => nx = true,
+                "XX" => xx = true,
+                "CH" => ch = true,
+                _ => break,
+            }
+            i += 1;
+        }
+        let points = &args[i..];
+        if points.is_empty() || points.len() % 3 != 0 {
+            return Err(AikvError::InvalidArgument(
+                "ERR syntax error. Try GEOADD key [x1] [y1] [name1] [x2] [y2] [name2] ..."
+                    .to_string(),
+            ));
+        }
+        if nx && xx {
+            return Err(AikvError::InvalidArgument(
+                "ERR XX and NX options at the same time are not compatible".to_string(),
+            ));
+        }
+
+        let mut scored = Vec::with_capacity(points.len() / 3);
+        for point in points.chunks(3) {
+            let (longitude, latitude) = parse_position(&point[0], &point[1])?;
+            scored.push((point[2].to_vec(), encode(longitude, latitude) as f64));
+        }
+
+        // Update in place so the key keeps its TTL
+        let mut stored = match self.storage.get_value(db_index, &key)? {
+            Some(stored) => stored,
+            None => StoredValue::new_zset(BTreeMap::new()),
+        };
+        let zset = stored.as_zset_mut()?;
+        let mut added = 0;
+        let mut changed = 0;
+        for (member, score) in scored {
+            match zset.get_mut(&member) {
+                Some(current) if !nx => {
+                    if *current != score {
+                        *current = score;
+                        changed += 1;
+                    }
+                }
+                Some(_) => {}
+                None if !xx => {
+                    zset.insert(member, score);
+                    added += 1;
+                }
+                None => {}
+            }
+        }
+
+        if !zset.is_empty() {
+            self.storage.set_value(db_index, key, stored)?;
+        }
+        Ok(RespValue::integer(if ch { added + changed } else { added }))
+    }
+
+    /// GEOPOS key \[member ...\]
+    /// The position of each member, or nil for a member that isn't there
+    pub fn geopos(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        if args.is_empty() {
+            return Err(AikvError::WrongArgCount("GEOPOS".to_string()));
+        }
+
+        let zset = self.zset(&args[0], db_index)?;
+        let positions = args[1..]
+            .iter()
+            .map(|member| match zset.get(&member[..]) {
+                Some(&score) => {
+                    let (longitude, latitude) = decode(score as u64);
+                    format_position(longitude, latitude)
+                }
+                None => RespValue::null_array(),
+            })
+            .collect();
+        Ok(RespValue::array(positions))
+    }
+
+    /// GEODIST key member1 member2 \[M|KM|FT|MI\]
+    /// The distance between two members, or nil if either is missing
+    pub fn geodist(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        if args.len() != 3 && args.len() != 4 {
+            return Err(AikvError::WrongArgCount("GEODIST".to_string()));
+        }
+
+        let unit = match args.get(3) {
+            Some(unit) => parse_unit(unit)?,
+            None => 1.0,
+        };
+        let zset = self.zset(&args[0], db_index)?;
+        match (zset.get(&args[1][..]), zset.get(&args[2][..])) {
+            (Some(&score1), Some(&score2)) => {
+                let (longitude1, latitude1) = decode(score1 as u64);
+                let (longitude2, latitude2) = decode(score2 as u64);
+                Ok(format_distance(
+                    distance(longitude1, latitude1, longitude2, latitude2),
+                    unit,
+                ))
+            }
+            _ => Ok(RespValue::null_bulk_string()),
+        }
+    }
+
+    /// GEOSEARCH key FROMMEMBER member|FROMLONLAT longitude latitude
+    /// BYRADIUS radius unit|BYBOX width height unit \[ASC|DESC\]
+    /// \[COUNT count \[ANY\]\] \[WITHCOORD\] \[WITHDIST\] \[WITHHASH\]
+    /// The members within a circle or box around a member or position
+    ///
+    /// Every point of the index is checked, rather than only the geohash
+    /// cells around the center as Redis does; the results are the same.
+    pub fn geosearch(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        if args.len() < 6 {
+            return Err(AikvError::WrongArgCount("GEOSEARCH".to_string()));
+        }
+
+        let options = SearchOptions::parse(&args[1..])?;
+        let zset = self.zset(&args[0], db_index)?;
+        if zset.is_empty() {
+            return Ok(RespValue::array(Vec::new()));
+        }
+
+        let (center_longitude, center_latitude) = match &options.center {
+            Center::Position(longitude, latitude) => (*longitude, *latitude),
+            Center::Member(member) => match zset.get(&member[..]) {
+                Some(&score) => decode(score as u64),
+                None => {
+                    return Err(AikvError::InvalidArgument(
+                        "ERR could not decode requested zset member".to_string(),
+                    ))
+                }
+            },
+        };
+
+        let mut points = Vec::new();
+        for (member, &score) in &zset {
+            let hash = score as u64;
+            let (longitude, latitude) = decode(hash);
+            let within = match options.shape {
+                Shape::Radius(radius) => {
+                    let d = distance(center_longitude, center_latitude, longitude, latitude);
+                    (d <= radius).then_some(d)
+                }
+                // Latitude first: it is the cheaper distance to compute
+                Shape::Box {
+                    width,
+                    height,
+                } => (latitude_distance(latitude, center_latitude) <= height / 2.0
+                    && distance(longitude, latitude, center_longitude, latitude) <= width / 2.0)
+                    .then(|| distance(center_longitude, center_latitude, longitude, latitude)),
+            };
+            if let Some(distance) = within {
+                points.push(GeoPoint {
+                    member: member.clone(),
+                    hash,
+                    longitude,
+                    latitude,
+                    distance,
+                });
+                // ANY settles for the first points found
+                if options
+                    .count
+                    .is_some_and(|(n, any)| any && points.len() == n)
+                {
+                    break;
+                }
+            }
+        }
+
+        // A COUNT without ANY wants the nearest points
+        let descending = match (options.descending, options.count) {
+            (None, Some((_, false))) => Some(false),
+            (descending, _) => descending,
+        };
+        if let Some(descending) = descending {
+            points.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+            if descending {
+                points.reverse();
+            }
+        }
+        if let Some((n, _)) = options.count {
+            points.truncate(n);
+        }
+
+        let plain = !(options.with_coord || options.with_dist || options.with_hash);
+        let results = points
+            .into_iter()
+            .map(|point| {
+                let member = RespValue::bulk_string(Bytes::from(point.member));
+                if plain {
+                    return member;
+                }
+                let mut reply = vec![member];
+                if options.with_dist {
+                    reply.push(format_distance(point.distance, options.unit));
+                }
+                if options.with_hash {
+                    reply.push(RespValue::integer(point.hash as i64));
+                }
+                if options.with_coord {
+                    reply.push(format_position(point.longitude, point.latitude));
+                }
+                RespValue::array(reply)
+            })
+            .collect();
+        Ok(RespValue::array(results))
+    }
+
+    /// The sorted set at `key`, empty if there is none
+    fn zset(&self, key: &[u8], db_index: usize) -> Result<BTreeMap<Vec<u8>, f64>> {
+        let key = String::from_utf8_lossy(key);
+        match self.storage.get_value(db_index, &key)? {
+            Some(stored) => Ok(stored.as_zset()?.clone()),
+            None => Ok(BTreeMap::new()),
+        }
+    }
+}
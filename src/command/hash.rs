@@ -1,3 +1,4 @@
+use super::string::{checked_float_add, format_float, parse_float};
 use crate::error::{AikvError, Result};
 use crate::protocol::RespValue;
 use crate::storage::{StorageEngine, StoredValue};
@@ -265,24 +266,25 @@ impl HashCommands {
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
 
-        // Migrated: Logic moved from storage layer to command layer
-        let fields = if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            stored
-                .as_hash()?
-                .iter()
-                .map(|(k, v)| (k.clone(), v.clone()))
-                .collect()
-        } else {
-            Vec::new()
-        };
-
-        let mut result = Vec::new();
-        for (field, value) in fields {
-            result.push(RespValue::bulk_string(Bytes::from(field)));
-            result.push(RespValue::bulk_string(value));
-        }
-
-        Ok(RespValue::Array(Some(result)))
+        // Borrow the hash in place and copy out just the fields; a large
+        // reply is streamed from them rather than built up front
+        let fields = self
+            .storage
+            .with_value(db_index, &key, |stored| {
+                Ok(stored
+                    .as_hash()?
+                    .iter()
+                    .map(|(field, value)| (Bytes::from(field.clone()), value.clone()))
+                    .collect::<Vec<_>>())
+            })?
+            .unwrap_or_default();
+
+        Ok(RespValue::lazy_map(fields, |(field, value)| {
+            (
+                RespValue::bulk_string(field.clone()),
+                RespValue::bulk_string(value.clone()),
+            )
+        }))
     }
 
     /// HINCRBY key field increment
@@ -298,28 +300,30 @@ impl HashCommands {
             .parse::<i64>()
             .map_err(|_| AikvError::InvalidArgument("invalid increment".to_string()))?;
 
-        // Migrated: Logic moved from storage layer to command layer
-        let mut hash = if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            stored.as_hash()?.clone()
-        } else {
-            HashMap::new()
-        };
-
-        let current_value = if let Some(val_bytes) = hash.get(&field) {
-            String::from_utf8_lossy(val_bytes)
-                .parse::<i64>()
-                .map_err(|_| {
-                    AikvError::InvalidArgument("hash value is not an integer".to_string())
-                })?
-        } else {
-            0
-        };
-
-        let new_value = current_value + increment;
-        hash.insert(field, Bytes::from(new_value.to_string()));
-
-        self.storage
-            .set_value(db_index, key, StoredValue::new_hash(hash))?;
+        // Incrementing in place keeps the key's TTL, and concurrent
+        // increments of the same field, even of a new hash, can't lose one
+        // another's update
+        let mut new_value = increment;
+        self.storage.update_or_insert_value(
+            db_index,
+            &key,
+            || StoredValue::new_hash(HashMap::new()),
+            |stored| {
+                let hash = stored.as_hash_mut()?;
+                let current_value = if let Some(val_bytes) = hash.get(&field) {
+                    String::from_utf8_lossy(val_bytes)
+                        .parse::<i64>()
+                        .map_err(|_| {
+                            AikvError::InvalidArgument("hash value is not an integer".to_string())
+                        })?
+                } else {
+                    0
+                };
+                new_value = current_value + increment;
+                hash.insert(field.clone(), Bytes::from(new_value.to_string()));
+                Ok(())
+            },
+        )?;
         Ok(RespValue::Integer(new_value))
     }
 
@@ -332,31 +336,31 @@ impl HashCommands {
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
         let field = String::from_utf8_lossy(&args[1]).to_string();
-        let increment = String::from_utf8_lossy(&args[2])
-            .parse::<f64>()
-            .map_err(|_| AikvError::InvalidArgument("invalid increment".to_string()))?;
-
-        // Migrated: Logic moved from storage layer to command layer
-        let mut hash = if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            stored.as_hash()?.clone()
-        } else {
-            HashMap::new()
-        };
-
-        let current_value = if let Some(val_bytes) = hash.get(&field) {
-            String::from_utf8_lossy(val_bytes)
-                .parse::<f64>()
-                .map_err(|_| AikvError::InvalidArgument("hash value is not a float".to_string()))?
-        } else {
-            0.0
-        };
-
-        let new_value = current_value + increment;
-        hash.insert(field, Bytes::from(new_value.to_string()));
-
-        self.storage
-            .set_value(db_index, key, StoredValue::new_hash(hash))?;
-        Ok(RespValue::bulk_string(Bytes::from(new_value.to_string())))
+        let increment = parse_float(&args[2]).ok_or_else(|| {
+            AikvError::InvalidArgument("ERR value is not a valid float".to_string())
+        })?;
+
+        // Incremented in place, like HINCRBY
+        let mut formatted = Bytes::new();
+        self.storage.update_or_insert_value(
+            db_index,
+            &key,
+            || StoredValue::new_hash(HashMap::new()),
+            |stored| {
+                let hash = stored.as_hash_mut()?;
+                let current_value = if let Some(val_bytes) = hash.get(&field) {
+                    parse_float(val_bytes).ok_or_else(|| {
+                        AikvError::InvalidArgument("ERR hash value is not a float".to_string())
+                    })?
+                } else {
+                    0.0
+                };
+                formatted = Bytes::from(format_float(checked_float_add(current_value, increment)?));
+                hash.insert(field.clone(), formatted.clone());
+                Ok(())
+            },
+        )?;
+        Ok(RespValue::bulk_string(formatted))
     }
 
     /// HMSET key field value [field value ...]
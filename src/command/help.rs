@@ -0,0 +1,33 @@
+//! HELP replies for commands with subcommands
+//!
+//! CLIENT, CONFIG, OBJECT and the other container commands all answer
+//! `<command> HELP` in the layout Redis uses, which redis-cli and some
+//! clients rely on, and point at it when given a subcommand they don't have.
+
+use crate::error::AikvError;
+use crate::protocol::RespValue;
+
+/// The `<command> HELP` reply: a usage line, then `lines` describing each
+/// subcommand, then HELP itself, one simple string per line
+pub fn help_reply(command: &str, lines: &[&str]) -> RespValue {
+    let usage = format!(
+        "{} <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+        command
+    );
+    let help = ["HELP", "    Print this help."];
+    RespValue::array(
+        std::iter::once(usage.as_str())
+            .chain(lines.iter().copied())
+            .chain(help)
+            .map(RespValue::simple_string)
+            .collect(),
+    )
+}
+
+/// The error for a `subcommand` that `command` doesn't have
+pub fn unknown_subcommand(command: &str, subcommand: &str) -> AikvError {
+    AikvError::InvalidArgument(format!(
+        "ERR unknown subcommand '{}'. Try {} HELP.",
+        subcommand, command
+    ))
+}
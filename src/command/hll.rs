@@ -0,0 +1,322 @@
+use crate::error::{AikvError, Result};
+use crate::protocol::RespValue;
+use crate::storage::{StorageEngine, StoredValue, ValueType};
+use bytes::Bytes;
+
+/// Bits of the hash that pick a register
+const HLL_P: u32 = 14;
+
+/// Number of registers
+const HLL_REGISTERS: usize = 1 << HLL_P;
+
+/// Bits of the hash left over to count leading zeros in
+const HLL_Q: u32 = 64 - HLL_P;
+
+/// Bits per register
+const HLL_BITS: usize = 6;
+
+/// Largest value a register can hold
+const HLL_REGISTER_MAX: u8 = (1 << HLL_BITS) - 1;
+
+/// "HYLL", the encoding, three unused bytes and the cached cardinality
+const HLL_HDR_SIZE: usize = 16;
+
+/// Header plus 16384 packed 6-bit registers, as Redis lays out a dense HLL
+const HLL_DENSE_SIZE: usize = HLL_HDR_SIZE + (HLL_REGISTERS * HLL_BITS).div_ceil(8);
+
+/// Encoding byte of a dense HLL; the sparse encoding isn't supported
+const HLL_DENSE: u8 = 0;
+
+/// Set on the last cardinality byte when the cached value is stale
+const HLL_CARD_INVALID: u8 = 1 << 7;
+
+/// Seed Redis hashes HLL elements with
+const HLL_HASH_SEED: u64 = 0xadc83b19;
+
+/// α∞ of the Ertl estimator
+const HLL_ALPHA_INF: f64 = 0.721347520444481703680;
+
+/// HyperLogLog command handler
+///
+/// Each HLL is stored as an ordinary string value in the Redis dense format,
+/// so it can be copied with GET/SET and dumped like any other string.
+pub struct HllCommands {
+    storage: StorageEngine,
+}
+
+/// MurmurHash64A, the hash Redis uses for HLL elements
+fn murmurhash64a(data: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4_a793_5bd1_e995;
+    const R: u32 = 47;
+
+    let mut h = seed ^ (data.len() as u64).wrapping_mul(M);
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().expect("8-byte chunk"));
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, &byte) in tail.iter().enumerate() {
+            h ^= (byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+/// The register an element falls in, and the length of the run of zeros
+/// (plus one) in the rest of its hash
+fn pattern(element: &[u8]) -> (usize, u8) {
+    let hash = murmurhash64a(element, HLL_HASH_SEED);
+    let index = (hash & (HLL_REGISTERS as u64 - 1)) as usize;
+    // The sentinel bit caps the run at HLL_Q + 1
+    let rest = (hash >> HLL_P) | (1 << HLL_Q);
+    (index, rest.trailing_zeros() as u8 + 1)
+}
+
+/// σ(x) of the Ertl estimator
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+    let mut y = 1.0;
+    let mut z = x;
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if previous == z {
+            return z;
+        }
+    }
+}
+
+/// τ(x) of the Ertl estimator
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+    let mut y = 1.0;
+    let mut z = 1.0 - x;
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if previous == z {
+            return z / 3.0;
+        }
+    }
+}
+
+/// A dense HyperLogLog
+struct Hll {
+    bytes: Vec<u8>,
+}
+
+impl Hll {
+    fn new() -> Self {
+        let mut bytes = vec![0; HLL_DENSE_SIZE];
+        bytes[..4].copy_from_slice(b"HYLL");
+        bytes[4] = HLL_DENSE;
+        Self {
+            bytes,
+        }
+    }
+
+    /// Parse a stored value, which must be a string holding a dense HLL
+    fn from_value(value: &StoredValue) -> Result<Self> {
+        let bytes = value.as_string()?;
+        if bytes.len() != HLL_DENSE_SIZE || !bytes.starts_with(b"HYLL") || bytes[4] != HLL_DENSE {
+            return Err(AikvError::WrongType(
+                "Key is not a valid HyperLogLog string value.".to_string(),
+            ));
+        }
+        Ok(Self {
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    fn registers(&self) -> &[u8] {
+        &self.bytes[HLL_HDR_SIZE..]
+    }
+
+    fn get(&self, index: usize) -> u8 {
+        let registers = self.registers();
+        let byte = index * HLL_BITS / 8;
+        let shift = index * HLL_BITS % 8;
+        let low = registers[byte] as u16;
+        let high = registers.get(byte + 1).copied().unwrap_or(0) as u16;
+        (((low | (high << 8)) >> shift) as u8) & HLL_REGISTER_MAX
+    }
+
+    fn set(&mut self, index: usize, value: u8) {
+        let registers = &mut self.bytes[HLL_HDR_SIZE..];
+        let byte = index * HLL_BITS / 8;
+        let shift = index * HLL_BITS % 8;
+        let mask = (HLL_REGISTER_MAX as u16) << shift;
+        let value = (value as u16) << shift;
+        registers[byte] = (registers[byte] & !(mask as u8)) | value as u8;
+        if let Some(next) = registers.get_mut(byte + 1) {
+            *next = (*next & !((mask >> 8) as u8)) | (value >> 8) as u8;
+        }
+    }
+
+    /// Raise a register to `value`, returning whether it changed
+    fn raise(&mut self, index: usize, value: u8) -> bool {
+        if value > self.get(index) {
+            self.set(index, value);
+            self.invalidate();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Add an element, returning whether any register changed
+    fn add(&mut self, element: &[u8]) -> bool {
+        let (index, count) = pattern(element);
+        self.raise(index, count)
+    }
+
+    /// Fold another HLL in, register by register
+    fn merge(&mut self, other: &Hll) {
+        for index in 0..HLL_REGISTERS {
+            self.raise(index, other.get(index));
+        }
+    }
+
+    /// The cardinality is recomputed on every PFCOUNT, so the cached copy
+    /// in the header is only ever marked stale for other readers
+    fn invalidate(&mut self) {
+        self.bytes[HLL_HDR_SIZE - 1] |= HLL_CARD_INVALID;
+    }
+
+    /// Estimate the cardinality with Ertl's improved estimator, which
+    /// corrects the raw estimate's bias at both ends of the range
+    fn count(&self) -> u64 {
+        let mut histogram = [0u32; 64];
+        for index in 0..HLL_REGISTERS {
+            histogram[self.get(index) as usize] += 1;
+        }
+
+        let m = HLL_REGISTERS as f64;
+        let mut z = m * tau((m - histogram[HLL_Q as usize + 1] as f64) / m);
+        for j in (1..=HLL_Q as usize).rev() {
+            z += histogram[j] as f64;
+            z *= 0.5;
+        }
+        z += m * sigma(histogram[0] as f64 / m);
+        (HLL_ALPHA_INF * m * m / z).round() as u64
+    }
+}
+
+impl HllCommands {
+    pub fn new(storage: StorageEngine) -> Self {
+        Self {
+            storage,
+        }
+    }
+
+    /// PFADD key \[element ...\]
+    /// Add elements to a HyperLogLog, returning 1 if its estimate may have
+    /// changed and 0 otherwise
+    pub fn pfadd(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        if args.is_empty() {
+            return Err(AikvError::WrongArgCount("PFADD".to_string()));
+        }
+
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+        let existing = self.storage.get_value(db_index, &key)?;
+        let (mut hll, mut updated) = match &existing {
+            Some(stored) => (Hll::from_value(stored)?, false),
+            None => (Hll::new(), true),
+        };
+        for element in &args[1..] {
+            updated |= hll.add(element);
+        }
+
+        if updated {
+            self.store(db_index, key, existing, hll)?;
+        }
+        Ok(RespValue::integer(updated as i64))
+    }
+
+    /// PFCOUNT key \[key ...\]
+    /// The approximate number of distinct elements added to the union of
+    /// the given HyperLogLogs
+    pub fn pfcount(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        if args.is_empty() {
+            return Err(AikvError::WrongArgCount("PFCOUNT".to_string()));
+        }
+
+        let mut union = Hll::new();
+        for key in args {
+            if let Some(hll) = self.hll(key, db_index)? {
+                union.merge(&hll);
+            }
+        }
+        Ok(RespValue::integer(union.count() as i64))
+    }
+
+    /// PFMERGE destkey \[sourcekey ...\]
+    /// Merge the sources into the destination, creating it if needed;
+    /// an existing destination counts as one of the sources
+    pub fn pfmerge(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        if args.is_empty() {
+            return Err(AikvError::WrongArgCount("PFMERGE".to_string()));
+        }
+
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+        let existing = self.storage.get_value(db_index, &key)?;
+        let mut merged = match &existing {
+            Some(stored) => Hll::from_value(stored)?,
+            None => Hll::new(),
+        };
+        for source in &args[1..] {
+            if let Some(hll) = self.hll(source, db_index)? {
+                merged.merge(&hll);
+            }
+        }
+
+        self.store(db_index, key, existing, merged)?;
+        Ok(RespValue::ok())
+    }
+
+    /// The HLL stored at `key`, or None if the key doesn't exist
+    fn hll(&self, key: &[u8], db_index: usize) -> Result<Option<Hll>> {
+        let key = String::from_utf8_lossy(key);
+        self.storage
+            .get_value(db_index, &key)?
+            .map(|stored| Hll::from_value(&stored))
+            .transpose()
+    }
+
+    /// Write an HLL back, in place over `existing` so the key keeps its TTL
+    fn store(
+        &self,
+        db_index: usize,
+        key: String,
+        existing: Option<StoredValue>,
+        hll: Hll,
+    ) -> Result<()> {
+        let bytes = Bytes::from(hll.bytes);
+        let stored = match existing {
+            Some(mut stored) => {
+                *stored.value_mut() = ValueType::String(bytes);
+                stored
+            }
+            None => StoredValue::new_string(bytes),
+        };
+        self.storage.set_value(db_index, key, stored)
+    }
+}
@@ -1,6 +1,6 @@
 use crate::error::{AikvError, Result};
 use crate::protocol::RespValue;
-use crate::storage::StorageEngine;
+use crate::storage::{StorageEngine, ValueType};
 use bytes::Bytes;
 use serde_json::{json, Value as JsonValue};
 
@@ -16,28 +16,48 @@ impl JsonCommands {
         }
     }
 
-    /// JSON.GET key \[path\]
+    /// JSON.GET key \[path ...\]
+    ///
+    /// With several paths the reply is an object keyed by path, holding null
+    /// for paths that don't resolve.
     pub fn json_get(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
         if args.is_empty() {
             return Err(AikvError::WrongArgCount("JSON.GET".to_string()));
         }
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
-        let path = if args.len() > 1 {
-            String::from_utf8_lossy(&args[1]).to_string()
+        let paths: Vec<String> = if args.len() > 1 {
+            args[1..]
+                .iter()
+                .map(|path| String::from_utf8_lossy(path).to_string())
+                .collect()
         } else {
-            "$".to_string()
+            vec!["$".to_string()]
         };
+        let parsed = paths
+            .iter()
+            .map(|path| parse_path(path))
+            .collect::<Result<Vec<_>>>()?;
 
         match self.storage.get_from_db(current_db, &key)? {
             Some(value) => {
                 let json: JsonValue = serde_json::from_slice(&value)?;
 
-                let result = if path == "$" || path == "." {
-                    json
+                let result = if let [path] = parsed.as_slice() {
+                    match resolve(&json, path) {
+                        Some(result) => result.clone(),
+                        None => return Ok(RespValue::null_bulk_string()),
+                    }
                 } else {
-                    // Simple path extraction (full JSONPath would be more complex)
-                    self.extract_json_path(&json, &path)?
+                    let results = paths
+                        .into_iter()
+                        .zip(&parsed)
+                        .map(|(name, path)| {
+                            let value = resolve(&json, path).cloned().unwrap_or(JsonValue::Null);
+                            (name, value)
+                        })
+                        .collect();
+                    JsonValue::Object(results)
                 };
 
                 let json_string = serde_json::to_string(&result)?;
@@ -80,8 +100,9 @@ impl JsonCommands {
 
         // Parse the new value
         let new_value: JsonValue = serde_json::from_str(&value_str)?;
+        let path = parse_path(&path)?;
 
-        let result_json = if path == "$" || path == "." {
+        let result_json = if path.is_empty() {
             // Root path - replace entire value
             new_value
         } else {
@@ -91,8 +112,7 @@ impl JsonCommands {
                 None => json!({}),
             };
 
-            // Set value at path (simplified)
-            self.set_json_path(&mut json, &path, new_value)?;
+            set_path(&mut json, &path, new_value)?;
             json
         };
 
@@ -115,7 +135,8 @@ impl JsonCommands {
             "$".to_string()
         };
 
-        if path == "$" || path == "." {
+        let path = parse_path(&path)?;
+        if path.is_empty() {
             // Delete entire key
             if self.storage.delete_from_db(current_db, &key)? {
                 Ok(RespValue::integer(1))
@@ -128,7 +149,7 @@ impl JsonCommands {
                 Some(value) => {
                     let mut json: JsonValue = serde_json::from_slice(&value)?;
 
-                    if self.delete_json_path(&mut json, &path)? {
+                    if delete_path(&mut json, &path) {
                         let json_bytes = Bytes::from(serde_json::to_vec(&json)?);
                         self.storage.set_in_db(current_db, key, json_bytes)?;
                         Ok(RespValue::integer(1))
@@ -158,10 +179,8 @@ impl JsonCommands {
             Some(value) => {
                 let json: JsonValue = serde_json::from_slice(&value)?;
 
-                let target = if path == "$" || path == "." {
-                    &json
-                } else {
-                    &self.extract_json_path(&json, &path)?
+                let Some(target) = resolve(&json, &parse_path(&path)?) else {
+                    return Ok(RespValue::null_bulk_string());
                 };
 
                 let type_name = match target {
@@ -196,10 +215,8 @@ impl JsonCommands {
             Some(value) => {
                 let json: JsonValue = serde_json::from_slice(&value)?;
 
-                let target = if path == "$" || path == "." {
-                    &json
-                } else {
-                    &self.extract_json_path(&json, &path)?
+                let Some(target) = resolve(&json, &parse_path(&path)?) else {
+                    return Ok(RespValue::null_bulk_string());
                 };
 
                 if let JsonValue::String(s) = target {
@@ -229,10 +246,8 @@ impl JsonCommands {
             Some(value) => {
                 let json: JsonValue = serde_json::from_slice(&value)?;
 
-                let target = if path == "$" || path == "." {
-                    &json
-                } else {
-                    &self.extract_json_path(&json, &path)?
+                let Some(target) = resolve(&json, &parse_path(&path)?) else {
+                    return Ok(RespValue::null_bulk_string());
                 };
 
                 if let JsonValue::Array(arr) = target {
@@ -262,10 +277,8 @@ impl JsonCommands {
             Some(value) => {
                 let json: JsonValue = serde_json::from_slice(&value)?;
 
-                let target = if path == "$" || path == "." {
-                    &json
-                } else {
-                    &self.extract_json_path(&json, &path)?
+                let Some(target) = resolve(&json, &parse_path(&path)?) else {
+                    return Ok(RespValue::null_bulk_string());
                 };
 
                 if let JsonValue::Object(obj) = target {
@@ -278,106 +291,505 @@ impl JsonCommands {
         }
     }
 
-    // Helper methods for path operations (simplified JSONPath)
+    /// JSON.ARRAPPEND key path value \[value ...\]
+    pub fn json_arrappend(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.len() < 3 {
+            return Err(AikvError::WrongArgCount("JSON.ARRAPPEND".to_string()));
+        }
+
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+        let path = String::from_utf8_lossy(&args[1]).to_string();
+        let values = Self::parse_json_values(&args[2..])?;
 
-    fn extract_json_path(&self, json: &JsonValue, path: &str) -> Result<JsonValue> {
-        // Remove leading $ or .
-        let path = path.trim_start_matches('$').trim_start_matches('.');
+        let len = self.update_json_array(current_db, &key, &path, |arr| {
+            arr.extend(values);
+            Ok(arr.len())
+        })?;
+        Ok(RespValue::integer(len as i64))
+    }
 
-        if path.is_empty() {
-            return Ok(json.clone());
+    /// JSON.ARRINSERT key path index value \[value ...\]
+    ///
+    /// A negative index counts from the end of the array.
+    pub fn json_arrinsert(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.len() < 4 {
+            return Err(AikvError::WrongArgCount("JSON.ARRINSERT".to_string()));
+        }
+
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+        let path = String::from_utf8_lossy(&args[1]).to_string();
+        let index = Self::parse_index(&args[2])?;
+        let values = Self::parse_json_values(&args[3..])?;
+
+        let len = self.update_json_array(current_db, &key, &path, |arr| {
+            let len = arr.len() as i64;
+            let index = if index < 0 { index + len } else { index };
+            if !(0..=len).contains(&index) {
+                return Err(AikvError::InvalidArgument(
+                    "ERR index out of bounds".to_string(),
+                ));
+            }
+            let index = index as usize;
+            for (offset, value) in values.into_iter().enumerate() {
+                arr.insert(index + offset, value);
+            }
+            Ok(arr.len())
+        })?;
+        Ok(RespValue::integer(len as i64))
+    }
+
+    /// JSON.ARRPOP key \[path \[index\]\]
+    ///
+    /// Pops the last element by default; a negative index counts from the end
+    /// and an out-of-range index pops the first or last element.
+    pub fn json_arrpop(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.is_empty() || args.len() > 3 {
+            return Err(AikvError::WrongArgCount("JSON.ARRPOP".to_string()));
+        }
+
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+        let path = if args.len() > 1 {
+            String::from_utf8_lossy(&args[1]).to_string()
+        } else {
+            "$".to_string()
+        };
+        let index = match args.get(2) {
+            Some(index) => Self::parse_index(index)?,
+            None => -1,
+        };
+
+        let popped = self.update_json_array(current_db, &key, &path, |arr| {
+            if arr.is_empty() {
+                return Ok(None);
+            }
+            let len = arr.len() as i64;
+            let index = if index < 0 { index + len } else { index };
+            Ok(Some(arr.remove(index.clamp(0, len - 1) as usize)))
+        })?;
+
+        match popped {
+            Some(value) => Ok(RespValue::bulk_string(serde_json::to_string(&value)?)),
+            None => Ok(RespValue::null_bulk_string()),
         }
+    }
 
-        // Simple path like "name" or "user.name"
-        let parts: Vec<&str> = path.split('.').collect();
-        let mut current = json;
+    /// JSON.ARRTRIM key path start stop
+    ///
+    /// Keeps the elements from `start` to `stop` inclusive, like LTRIM.
+    pub fn json_arrtrim(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.len() != 4 {
+            return Err(AikvError::WrongArgCount("JSON.ARRTRIM".to_string()));
+        }
+
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+        let path = String::from_utf8_lossy(&args[1]).to_string();
+        let start = Self::parse_index(&args[2])?;
+        let stop = Self::parse_index(&args[3])?;
 
-        for part in parts {
-            if let JsonValue::Object(obj) = current {
-                current = obj.get(part).ok_or_else(|| {
-                    AikvError::InvalidArgument(format!("Path not found: {}", part))
-                })?;
+        let len = self.update_json_array(current_db, &key, &path, |arr| {
+            let len = arr.len() as i64;
+            let start = if start < 0 {
+                (start + len).max(0)
             } else {
-                return Err(AikvError::InvalidArgument(format!(
-                    "Cannot traverse non-object at: {}",
-                    part
-                )));
+                start
+            };
+            let stop = if stop < 0 {
+                stop + len
+            } else {
+                stop.min(len - 1)
+            };
+            if start > stop || start >= len {
+                arr.clear();
+            } else {
+                arr.truncate(stop as usize + 1);
+                arr.drain(..start as usize);
             }
+            Ok(arr.len())
+        })?;
+        Ok(RespValue::integer(len as i64))
+    }
+
+    /// JSON.NUMINCRBY key path number
+    pub fn json_numincrby(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        self.update_number(
+            args,
+            current_db,
+            "JSON.NUMINCRBY",
+            |a, b| a.checked_add(b),
+            |a, b| a + b,
+        )
+    }
+
+    /// JSON.NUMMULTBY key path number
+    pub fn json_nummultby(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        self.update_number(
+            args,
+            current_db,
+            "JSON.NUMMULTBY",
+            |a, b| a.checked_mul(b),
+            |a, b| a * b,
+        )
+    }
+
+    /// JSON.TOGGLE key path - Flip a boolean, returning its new value
+    pub fn json_toggle(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.len() != 2 {
+            return Err(AikvError::WrongArgCount("JSON.TOGGLE".to_string()));
         }
 
-        Ok(current.clone())
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+        let path = String::from_utf8_lossy(&args[1]).to_string();
+
+        let toggled = self.update_json_value(current_db, &key, &path, |target| {
+            let JsonValue::Bool(value) = target else {
+                return Err(AikvError::InvalidArgument(format!(
+                    "Path is not a boolean: {}",
+                    path
+                )));
+            };
+            *value = !*value;
+            Ok(*value)
+        })?;
+        Ok(RespValue::bulk_string(toggled.to_string()))
     }
 
-    fn set_json_path(&self, json: &mut JsonValue, path: &str, value: JsonValue) -> Result<()> {
-        // Remove leading $ or .
-        let path = path.trim_start_matches('$').trim_start_matches('.');
+    /// JSON.MGET key \[key ...\] path
+    ///
+    /// Returns the value at `path` of each key, or null where the key or the
+    /// path is missing.
+    pub fn json_mget(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.len() < 2 {
+            return Err(AikvError::WrongArgCount("JSON.MGET".to_string()));
+        }
 
-        if path.is_empty() {
-            *json = value;
-            return Ok(());
+        let (path, keys) = args.split_last().unwrap();
+        let path = parse_path(&String::from_utf8_lossy(path))?;
+
+        let mut results = Vec::with_capacity(keys.len());
+        for key in keys {
+            let key = String::from_utf8_lossy(key);
+            // A key that doesn't hold JSON is reported as missing, not an error
+            let value = self
+                .storage
+                .get_from_db(current_db, &key)
+                .ok()
+                .flatten()
+                .and_then(|value| serde_json::from_slice::<JsonValue>(&value).ok())
+                .and_then(|json| resolve(&json, &path).map(serde_json::to_string));
+            match value {
+                Some(json_string) => results.push(RespValue::bulk_string(json_string?)),
+                None => results.push(RespValue::null_bulk_string()),
+            }
         }
 
-        // Simple path like "name" or "user.name"
-        let parts: Vec<&str> = path.split('.').collect();
+        Ok(RespValue::array(results))
+    }
 
-        if !json.is_object() {
-            *json = json!({}); // Convert to object if not already
+    // Helper methods
+
+    /// Shared implementation of JSON.NUMINCRBY and JSON.NUMMULTBY
+    ///
+    /// Integers stay integers unless the result overflows; otherwise the
+    /// result is a float.
+    fn update_number(
+        &self,
+        args: &[Bytes],
+        current_db: usize,
+        command: &str,
+        int_op: impl FnOnce(i64, i64) -> Option<i64>,
+        float_op: impl FnOnce(f64, f64) -> f64,
+    ) -> Result<RespValue> {
+        if args.len() != 3 {
+            return Err(AikvError::WrongArgCount(command.to_string()));
         }
 
-        let mut current = json;
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+        let path = String::from_utf8_lossy(&args[1]).to_string();
+        let operand = match serde_json::from_slice::<JsonValue>(&args[2]) {
+            Ok(JsonValue::Number(operand)) => operand,
+            _ => {
+                return Err(AikvError::InvalidArgument(
+                    "ERR value is not a number".to_string(),
+                ))
+            }
+        };
 
-        for (i, part) in parts.iter().enumerate() {
-            if i == parts.len() - 1 {
-                // Last part - set the value
-                if let JsonValue::Object(obj) = current {
-                    obj.insert(part.to_string(), value);
-                    break; // Exit after inserting
-                }
-            } else {
-                // Intermediate part - ensure object exists
-                if let JsonValue::Object(obj) = current {
-                    current = obj.entry(part.to_string()).or_insert_with(|| json!({}));
+        let result = self.update_json_value(current_db, &key, &path, |target| {
+            let JsonValue::Number(current) = target else {
+                return Err(AikvError::InvalidArgument(format!(
+                    "Path is not a number: {}",
+                    path
+                )));
+            };
+
+            let integer = match (current.as_i64(), operand.as_i64()) {
+                (Some(a), Some(b)) => int_op(a, b),
+                _ => None,
+            };
+            let result = match integer {
+                Some(result) => serde_json::Number::from(result),
+                None => {
+                    let a = current.as_f64().unwrap_or_default();
+                    let b = operand.as_f64().unwrap_or_default();
+                    serde_json::Number::from_f64(float_op(a, b)).ok_or_else(|| {
+                        AikvError::InvalidArgument("ERR result is not a number".to_string())
+                    })?
                 }
-            }
+            };
+            *current = result.clone();
+            Ok(result)
+        })?;
+        Ok(RespValue::bulk_string(result.to_string()))
+    }
+
+    /// Apply `f` to the array at `path` of the document stored at `key`
+    fn update_json_array<R>(
+        &self,
+        current_db: usize,
+        key: &str,
+        path: &str,
+        f: impl FnOnce(&mut Vec<JsonValue>) -> Result<R>,
+    ) -> Result<R> {
+        self.update_json_value(current_db, key, path, |target| {
+            let JsonValue::Array(arr) = target else {
+                return Err(AikvError::InvalidArgument(format!(
+                    "Path is not an array: {}",
+                    path
+                )));
+            };
+            f(arr)
+        })
+    }
+
+    /// Apply `f` to the value at `path` of the document stored at `key`,
+    /// writing the document back in the same atomic update
+    fn update_json_value<R>(
+        &self,
+        current_db: usize,
+        key: &str,
+        path: &str,
+        f: impl FnOnce(&mut JsonValue) -> Result<R>,
+    ) -> Result<R> {
+        let mut result = None;
+        let updated = self.storage.update_value(current_db, key, |stored| {
+            let mut json: JsonValue = serde_json::from_slice(stored.as_string()?)?;
+            let target = resolve_mut(&mut json, &parse_path(path)?)
+                .ok_or_else(|| AikvError::InvalidArgument(format!("Path not found: {}", path)))?;
+            result = Some(f(target)?);
+            *stored.value_mut() = ValueType::String(Bytes::from(serde_json::to_vec(&json)?));
+            Ok(())
+        })?;
+
+        match result {
+            Some(result) if updated => Ok(result),
+            _ => Err(AikvError::InvalidArgument(
+                "ERR could not perform this operation on a key that doesn't exist".to_string(),
+            )),
         }
+    }
 
-        Ok(())
+    fn parse_json_values(args: &[Bytes]) -> Result<Vec<JsonValue>> {
+        args.iter()
+            .map(|arg| serde_json::from_slice(arg).map_err(AikvError::from))
+            .collect()
     }
 
-    fn delete_json_path(&self, json: &mut JsonValue, path: &str) -> Result<bool> {
-        // Remove leading $ or .
-        let path = path.trim_start_matches('$').trim_start_matches('.');
+    fn parse_index(arg: &Bytes) -> Result<i64> {
+        String::from_utf8_lossy(arg).parse().map_err(|_| {
+            AikvError::InvalidArgument("ERR value is not an integer or out of range".to_string())
+        })
+    }
+}
 
-        if path.is_empty() {
-            return Ok(false);
+/// One step of a JSONPath: an object member or an array index
+#[derive(Debug, Clone, PartialEq)]
+enum PathSegment {
+    Key(String),
+    /// A negative index counts from the end of the array
+    Index(i64),
+}
+
+impl std::fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathSegment::Key(key) => write!(f, "{}", key),
+            PathSegment::Index(index) => write!(f, "[{}]", index),
         }
+    }
+}
 
-        // Simple path like "name" or "user.name"
-        let parts: Vec<&str> = path.split('.').collect();
-        let mut current = json;
+/// Parse a JSONPath such as `$.users[0].name` or `$["key with space"]`
+///
+/// The root is `$` or `.`; the legacy forms `.a.b` and `a.b` are accepted too.
+fn parse_path(path: &str) -> Result<Vec<PathSegment>> {
+    let invalid = || AikvError::InvalidArgument(format!("Invalid JSON path: {}", path));
+    let chars: Vec<char> = path.chars().collect();
+    let mut segments = Vec::new();
+    let mut i = 0;
+
+    if chars.first() == Some(&'$') {
+        i = 1;
+    } else if path == "." {
+        return Ok(segments);
+    } else if !matches!(chars.first(), Some('.') | Some('[') | None) {
+        // Legacy path without a leading `$` or `.`
+        let end = find_key_end(&chars, 0);
+        segments.push(PathSegment::Key(chars[..end].iter().collect()));
+        i = end;
+    }
 
-        for (i, part) in parts.iter().enumerate() {
-            if i == parts.len() - 1 {
-                // Last part - delete the key
-                if let JsonValue::Object(obj) = current {
-                    return Ok(obj.remove(*part).is_some());
+    while i < chars.len() {
+        match chars[i] {
+            '.' => {
+                let end = find_key_end(&chars, i + 1);
+                if end == i + 1 {
+                    return Err(invalid());
                 }
-                return Ok(false);
-            } else {
-                // Intermediate part
-                if let JsonValue::Object(obj) = current {
-                    if let Some(next) = obj.get_mut(*part) {
-                        current = next;
-                    } else {
-                        return Ok(false);
+                segments.push(PathSegment::Key(chars[i + 1..end].iter().collect()));
+                i = end;
+            }
+            '[' => {
+                let quote = chars
+                    .get(i + 1)
+                    .copied()
+                    .filter(|c| *c == '"' || *c == '\'');
+                if let Some(quote) = quote {
+                    // Quoted member name, with backslash escapes
+                    let mut key = String::new();
+                    let mut j = i + 2;
+                    loop {
+                        match chars.get(j) {
+                            Some('\\') => {
+                                key.push(*chars.get(j + 1).ok_or_else(invalid)?);
+                                j += 2;
+                            }
+                            Some(c) if *c == quote => break,
+                            Some(c) => {
+                                key.push(*c);
+                                j += 1;
+                            }
+                            None => return Err(invalid()),
+                        }
+                    }
+                    if chars.get(j + 1) != Some(&']') {
+                        return Err(invalid());
                     }
+                    segments.push(PathSegment::Key(key));
+                    i = j + 2;
                 } else {
-                    return Ok(false);
+                    let close = chars[i..]
+                        .iter()
+                        .position(|c| *c == ']')
+                        .ok_or_else(invalid)?
+                        + i;
+                    let index: String = chars[i + 1..close].iter().collect();
+                    let index = index.trim().parse().map_err(|_| invalid())?;
+                    segments.push(PathSegment::Index(index));
+                    i = close + 1;
                 }
             }
+            _ => return Err(invalid()),
         }
+    }
+
+    Ok(segments)
+}
+
+/// End of an unquoted member name starting at `start`
+fn find_key_end(chars: &[char], start: usize) -> usize {
+    chars[start..]
+        .iter()
+        .position(|c| *c == '.' || *c == '[')
+        .map_or(chars.len(), |offset| start + offset)
+}
+
+/// Position of `index` in an array of `len` elements, if it is in range
+fn array_index(len: usize, index: i64) -> Option<usize> {
+    let index = if index < 0 { index + len as i64 } else { index };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+/// The value at `path`, or `None` if the path doesn't resolve
+fn resolve<'a>(json: &'a JsonValue, path: &[PathSegment]) -> Option<&'a JsonValue> {
+    path.iter()
+        .try_fold(json, |current, segment| match (segment, current) {
+            (PathSegment::Key(key), JsonValue::Object(obj)) => obj.get(key),
+            (PathSegment::Index(index), JsonValue::Array(arr)) => {
+                arr.get(array_index(arr.len(), *index)?)
+            }
+            _ => None,
+        })
+}
+
+fn resolve_mut<'a>(json: &'a mut JsonValue, path: &[PathSegment]) -> Option<&'a mut JsonValue> {
+    path.iter()
+        .try_fold(json, |current, segment| match (segment, current) {
+            (PathSegment::Key(key), JsonValue::Object(obj)) => obj.get_mut(key),
+            (PathSegment::Index(index), JsonValue::Array(arr)) => {
+                let index = array_index(arr.len(), *index)?;
+                arr.get_mut(index)
+            }
+            _ => None,
+        })
+}
+
+/// Set the value at `path`, creating missing intermediate objects
+///
+/// Fails if the path runs through a scalar or past the end of an array.
+fn set_path(json: &mut JsonValue, path: &[PathSegment], value: JsonValue) -> Result<()> {
+    let Some((last, parents)) = path.split_last() else {
+        *json = value;
+        return Ok(());
+    };
+    let not_creatable = |segment: &PathSegment| {
+        AikvError::InvalidArgument(format!("Cannot create path at: {}", segment))
+    };
+
+    let mut current = json;
+    for segment in parents {
+        current = match (segment, current) {
+            (PathSegment::Key(key), JsonValue::Object(obj)) => {
+                obj.entry(key.clone()).or_insert_with(|| json!({}))
+            }
+            (PathSegment::Index(index), JsonValue::Array(arr)) => {
+                let index = array_index(arr.len(), *index).ok_or_else(|| not_creatable(segment))?;
+                &mut arr[index]
+            }
+            _ => return Err(not_creatable(segment)),
+        };
+    }
+
+    match (last, current) {
+        (PathSegment::Key(key), JsonValue::Object(obj)) => {
+            obj.insert(key.clone(), value);
+        }
+        (PathSegment::Index(index), JsonValue::Array(arr)) => {
+            let index = array_index(arr.len(), *index).ok_or_else(|| not_creatable(last))?;
+            arr[index] = value;
+        }
+        _ => return Err(not_creatable(last)),
+    }
+    Ok(())
+}
 
-        Ok(false)
+/// Remove the value at `path`, returning whether there was one
+fn delete_path(json: &mut JsonValue, path: &[PathSegment]) -> bool {
+    let Some((last, parents)) = path.split_last() else {
+        return false;
+    };
+
+    match (last, resolve_mut(json, parents)) {
+        (PathSegment::Key(key), Some(JsonValue::Object(obj))) => obj.remove(key).is_some(),
+        (PathSegment::Index(index), Some(JsonValue::Array(arr))) => {
+            match array_index(arr.len(), *index) {
+                Some(index) => {
+                    arr.remove(index);
+                    true
+                }
+                None => false,
+            }
+        }
+        _ => false,
     }
 }
 
@@ -471,4 +883,398 @@ mod tests {
         let result = cmd.json_objlen(&[Bytes::from("user")], 0).unwrap();
         assert_eq!(result, RespValue::integer(2));
     }
+
+    fn get(cmd: &JsonCommands, key: &str) -> String {
+        match cmd.json_get(&[Bytes::from(key.to_string())], 0).unwrap() {
+            RespValue::BulkString(Some(data)) => String::from_utf8(data.to_vec()).unwrap(),
+            other => panic!("Expected bulk string, got {:?}", other),
+        }
+    }
+
+    fn set_doc(cmd: &JsonCommands) {
+        cmd.json_set(
+            &[
+                Bytes::from("doc"),
+                Bytes::from("$"),
+                Bytes::from(
+                    r#"{"users":[{"name":"Ann","tags":["a","b"]},{"name":"Bob"}],"key with space":1}"#,
+                ),
+            ],
+            0,
+        )
+        .unwrap();
+    }
+
+    fn get_path(cmd: &JsonCommands, paths: &[&str]) -> RespValue {
+        let mut args = vec![Bytes::from("doc")];
+        args.extend(paths.iter().map(|path| Bytes::from(path.to_string())));
+        cmd.json_get(&args, 0).unwrap()
+    }
+
+    #[test]
+    fn test_json_path_nested_and_array_access() {
+        let cmd = setup();
+        set_doc(&cmd);
+
+        assert_eq!(
+            get_path(&cmd, &["$.users[0].name"]),
+            RespValue::bulk_string(r#""Ann""#)
+        );
+        assert_eq!(
+            get_path(&cmd, &["$.users[-1].name"]),
+            RespValue::bulk_string(r#""Bob""#)
+        );
+        assert_eq!(
+            get_path(&cmd, &["$.users[0].tags[-1]"]),
+            RespValue::bulk_string(r#""b""#)
+        );
+        assert_eq!(
+            get_path(&cmd, &[r#"$["key with space"]"#]),
+            RespValue::bulk_string("1")
+        );
+        assert_eq!(
+            cmd.json_type(&[Bytes::from("doc"), Bytes::from("$.users")], 0)
+                .unwrap(),
+            RespValue::simple_string("array")
+        );
+        assert_eq!(
+            cmd.json_arrlen(&[Bytes::from("doc"), Bytes::from("$.users[0].tags")], 0)
+                .unwrap(),
+            RespValue::integer(2)
+        );
+
+        // Several paths give an object keyed by path
+        assert_eq!(
+            get_path(&cmd, &["$.users[1].name", "$.missing"]),
+            RespValue::bulk_string(r#"{"$.missing":null,"$.users[1].name":"Bob"}"#)
+        );
+
+        assert!(parse_path("$.users[").is_err());
+        assert!(parse_path("$..users").is_err());
+    }
+
+    #[test]
+    fn test_json_path_not_found() {
+        let cmd = setup();
+        set_doc(&cmd);
+
+        assert_eq!(
+            get_path(&cmd, &["$.users[5].name"]),
+            RespValue::null_bulk_string()
+        );
+        assert_eq!(
+            get_path(&cmd, &["$.nope.deeper"]),
+            RespValue::null_bulk_string()
+        );
+        assert_eq!(
+            cmd.json_type(&[Bytes::from("doc"), Bytes::from("$.nope")], 0)
+                .unwrap(),
+            RespValue::null_bulk_string()
+        );
+
+        // SET can't create a path through a scalar or past the end of an array
+        assert!(cmd
+            .json_set(
+                &[
+                    Bytes::from("doc"),
+                    Bytes::from("$.users[0].name.first"),
+                    Bytes::from("1")
+                ],
+                0
+            )
+            .is_err());
+        assert!(cmd
+            .json_set(
+                &[
+                    Bytes::from("doc"),
+                    Bytes::from("$.users[9].name"),
+                    Bytes::from("1")
+                ],
+                0
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_json_path_set_and_del() {
+        let cmd = setup();
+        set_doc(&cmd);
+
+        cmd.json_set(
+            &[
+                Bytes::from("doc"),
+                Bytes::from("$.users[1].name"),
+                Bytes::from(r#""Bea""#),
+            ],
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            get_path(&cmd, &["$.users[1].name"]),
+            RespValue::bulk_string(r#""Bea""#)
+        );
+
+        let result = cmd
+            .json_del(&[Bytes::from("doc"), Bytes::from("$.users[0]")], 0)
+            .unwrap();
+        assert_eq!(result, RespValue::integer(1));
+        assert_eq!(
+            get_path(&cmd, &["$.users"]),
+            RespValue::bulk_string(r#"[{"name":"Bea"}]"#)
+        );
+    }
+
+    #[test]
+    fn test_json_numincrby_nested_number() {
+        let cmd = setup();
+        cmd.json_set(
+            &[
+                Bytes::from("doc"),
+                Bytes::from("$"),
+                Bytes::from(r#"{"stats":{"visits":10,"ratio":1.5},"name":"x"}"#),
+            ],
+            0,
+        )
+        .unwrap();
+
+        let incr = |path: &str, by: &str| {
+            cmd.json_numincrby(
+                &[
+                    Bytes::from("doc"),
+                    Bytes::from(path.to_string()),
+                    Bytes::from(by.to_string()),
+                ],
+                0,
+            )
+        };
+        assert_eq!(
+            incr("$.stats.visits", "5").unwrap(),
+            RespValue::bulk_string("15")
+        );
+        assert_eq!(
+            incr("$.stats.visits", "0.5").unwrap(),
+            RespValue::bulk_string("15.5")
+        );
+        assert!(incr("$.name", "1").is_err());
+        assert!(incr("$.stats.visits", "abc").is_err());
+
+        let result = cmd
+            .json_nummultby(
+                &[
+                    Bytes::from("doc"),
+                    Bytes::from("$.stats.ratio"),
+                    Bytes::from("4"),
+                ],
+                0,
+            )
+            .unwrap();
+        assert_eq!(result, RespValue::bulk_string("6.0"));
+        assert_eq!(
+            get(&cmd, "doc"),
+            r#"{"name":"x","stats":{"ratio":6.0,"visits":15.5}}"#
+        );
+    }
+
+    #[test]
+    fn test_json_toggle() {
+        let cmd = setup();
+        cmd.json_set(
+            &[
+                Bytes::from("doc"),
+                Bytes::from("$"),
+                Bytes::from(r#"{"active":true,"count":1}"#),
+            ],
+            0,
+        )
+        .unwrap();
+
+        let toggle =
+            |path: &str| cmd.json_toggle(&[Bytes::from("doc"), Bytes::from(path.to_string())], 0);
+        assert_eq!(toggle("$.active").unwrap(), RespValue::bulk_string("false"));
+        assert_eq!(toggle("$.active").unwrap(), RespValue::bulk_string("true"));
+        assert!(toggle("$.count").is_err());
+    }
+
+    #[test]
+    fn test_json_mget_present_and_absent_keys() {
+        let cmd = setup();
+        cmd.json_set(
+            &[
+                Bytes::from("a"),
+                Bytes::from("$"),
+                Bytes::from(r#"{"name":"Ann"}"#),
+            ],
+            0,
+        )
+        .unwrap();
+        cmd.json_set(&[Bytes::from("b"), Bytes::from("$"), Bytes::from("{}")], 0)
+            .unwrap();
+
+        let result = cmd
+            .json_mget(
+                &[
+                    Bytes::from("a"),
+                    Bytes::from("missing"),
+                    Bytes::from("b"),
+                    Bytes::from("$.name"),
+                ],
+                0,
+            )
+            .unwrap();
+        assert_eq!(
+            result,
+            RespValue::array(vec![
+                RespValue::bulk_string(r#""Ann""#),
+                RespValue::null_bulk_string(),
+                RespValue::null_bulk_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_json_arrappend_root_and_nested() {
+        let cmd = setup();
+        cmd.json_set(
+            &[Bytes::from("arr"), Bytes::from("$"), Bytes::from("[1]")],
+            0,
+        )
+        .unwrap();
+
+        let result = cmd
+            .json_arrappend(
+                &[
+                    Bytes::from("arr"),
+                    Bytes::from("$"),
+                    Bytes::from("2"),
+                    Bytes::from(r#""three""#),
+                ],
+                0,
+            )
+            .unwrap();
+        assert_eq!(result, RespValue::integer(3));
+        assert_eq!(get(&cmd, "arr"), r#"[1,2,"three"]"#);
+
+        cmd.json_set(
+            &[
+                Bytes::from("doc"),
+                Bytes::from("$"),
+                Bytes::from(r#"{"user":{"tags":["a"]},"name":"x"}"#),
+            ],
+            0,
+        )
+        .unwrap();
+        let result = cmd
+            .json_arrappend(
+                &[
+                    Bytes::from("doc"),
+                    Bytes::from("$.user.tags"),
+                    Bytes::from(r#""b""#),
+                ],
+                0,
+            )
+            .unwrap();
+        assert_eq!(result, RespValue::integer(2));
+        assert_eq!(
+            get(&cmd, "doc"),
+            r#"{"name":"x","user":{"tags":["a","b"]}}"#
+        );
+
+        // Not an array, and a missing key
+        assert!(cmd
+            .json_arrappend(
+                &[Bytes::from("doc"), Bytes::from("$.name"), Bytes::from("1")],
+                0
+            )
+            .is_err());
+        assert!(cmd
+            .json_arrappend(
+                &[Bytes::from("missing"), Bytes::from("$"), Bytes::from("1")],
+                0
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_json_arrinsert_and_arrtrim() {
+        let cmd = setup();
+        cmd.json_set(
+            &[Bytes::from("arr"), Bytes::from("$"), Bytes::from("[1,2,3]")],
+            0,
+        )
+        .unwrap();
+
+        let result = cmd
+            .json_arrinsert(
+                &[
+                    Bytes::from("arr"),
+                    Bytes::from("$"),
+                    Bytes::from("-1"),
+                    Bytes::from("9"),
+                ],
+                0,
+            )
+            .unwrap();
+        assert_eq!(result, RespValue::integer(4));
+        assert_eq!(get(&cmd, "arr"), "[1,2,9,3]");
+        assert!(cmd
+            .json_arrinsert(
+                &[
+                    Bytes::from("arr"),
+                    Bytes::from("$"),
+                    Bytes::from("10"),
+                    Bytes::from("0"),
+                ],
+                0
+            )
+            .is_err());
+
+        let result = cmd
+            .json_arrtrim(
+                &[
+                    Bytes::from("arr"),
+                    Bytes::from("$"),
+                    Bytes::from("1"),
+                    Bytes::from("-2"),
+                ],
+                0,
+            )
+            .unwrap();
+        assert_eq!(result, RespValue::integer(2));
+        assert_eq!(get(&cmd, "arr"), "[2,9]");
+    }
+
+    #[test]
+    fn test_json_arrpop_negative_index() {
+        let cmd = setup();
+        cmd.json_set(
+            &[
+                Bytes::from("arr"),
+                Bytes::from("$"),
+                Bytes::from(r#"[1,"two",3,4]"#),
+            ],
+            0,
+        )
+        .unwrap();
+
+        let result = cmd
+            .json_arrpop(
+                &[Bytes::from("arr"), Bytes::from("$"), Bytes::from("-3")],
+                0,
+            )
+            .unwrap();
+        assert_eq!(result, RespValue::bulk_string(r#""two""#));
+        assert_eq!(get(&cmd, "arr"), "[1,3,4]");
+
+        // Defaults to the last element
+        let result = cmd.json_arrpop(&[Bytes::from("arr")], 0).unwrap();
+        assert_eq!(result, RespValue::bulk_string("4"));
+
+        cmd.json_set(
+            &[Bytes::from("empty"), Bytes::from("$"), Bytes::from("[]")],
+            0,
+        )
+        .unwrap();
+        let result = cmd.json_arrpop(&[Bytes::from("empty")], 0).unwrap();
+        assert_eq!(result, RespValue::null_bulk_string());
+    }
 }
@@ -1,11 +1,94 @@
 use crate::error::{AikvError, Result};
-use crate::protocol::RespValue;
+use crate::persistence::crc64;
+use crate::protocol::{RespParser, RespValue};
 use crate::storage::{SerializableStoredValue, StorageEngine, StoredValue};
 use bytes::Bytes;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Version of the DUMP payload format written by this build
+const DUMP_VERSION: u16 = 1;
+
+/// Length of the DUMP payload footer: 2-byte version + 8-byte CRC64
+const DUMP_FOOTER_LEN: usize = 10;
+
+/// Condition under which EXPIRE and friends replace a key's TTL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExpireCondition {
+    Always,
+    /// NX: only if the key has no TTL
+    Nx,
+    /// XX: only if the key already has a TTL
+    Xx,
+    /// GT: only if the new expiry is later than the current one
+    Gt,
+    /// LT: only if the new expiry is earlier than the current one
+    Lt,
+}
 
-/// Default number of databases (matching Redis default)
-const DEFAULT_DB_COUNT: usize = 16;
+impl ExpireCondition {
+    /// Parse the optional NX/XX/GT/LT flags following the time argument
+    fn parse(flags: &[Bytes]) -> Result<Self> {
+        let mut condition = ExpireCondition::Always;
+        for flag in flags {
+            let next = match String::from_utf8_lossy(flag).to_uppercase().as_str() {
+                "NX" => ExpireCondition::Nx,
+                "XX" => ExpireCondition::Xx,
+                "GT" => ExpireCondition::Gt,
+                "LT" => ExpireCondition::Lt,
+                other => {
+                    return Err(AikvError::InvalidArgument(format!(
+                        "ERR Unsupported option {}",
+                        other
+                    )))
+                }
+            };
+            condition = match (condition, next) {
+                (ExpireCondition::Always, next) => next,
+                (current, next) if current == next => current,
+                (ExpireCondition::Gt, ExpireCondition::Lt)
+                | (ExpireCondition::Lt, ExpireCondition::Gt) => {
+                    return Err(AikvError::InvalidArgument(
+                        "ERR GT and LT options at the same time are not compatible".to_string(),
+                    ))
+                }
+                _ => {
+                    return Err(AikvError::InvalidArgument(
+                        "ERR NX and XX, GT or LT options at the same time are not compatible"
+                            .to_string(),
+                    ))
+                }
+            };
+        }
+        Ok(condition)
+    }
+
+    /// Whether a key whose current expiry is `current` (`None` for no TTL)
+    /// may be given the expiry `new`
+    ///
+    /// A key without a TTL counts as never expiring, so GT never applies to it
+    /// and LT always does.
+    fn allows(self, current: Option<i64>, new: i64) -> bool {
+        match self {
+            ExpireCondition::Always => true,
+            ExpireCondition::Nx => current.is_none(),
+            ExpireCondition::Xx => current.is_some(),
+            ExpireCondition::Gt => current.is_some_and(|current| new > current),
+            ExpireCondition::Lt => current.map_or(true, |current| new < current),
+        }
+    }
+}
+
+/// Milliseconds to seconds, rounded to the nearest second as Redis reports
+/// TTL and EXPIRETIME; the -1 and -2 sentinels pass through unchanged
+fn round_to_seconds(ms: i64) -> i64 {
+    if ms > 0 {
+        (ms + 500) / 1000
+    } else {
+        ms
+    }
+}
 
 /// Key command handler
 pub struct KeyCommands {
@@ -87,8 +170,9 @@ impl KeyCommands {
         }
     }
 
-    /// SCAN cursor \[MATCH pattern\] \[COUNT count\]
-    /// Iterate keys using cursor-based iteration
+    /// SCAN cursor \[MATCH pattern\] \[COUNT count\] \[TYPE type\]
+    /// Iterate keys using cursor-based iteration, optionally only those
+    /// whose TYPE is `type`
     pub fn scan(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
         if args.is_empty() {
             return Err(AikvError::WrongArgCount("SCAN".to_string()));
@@ -103,6 +187,7 @@ impl KeyCommands {
         // Parse optional arguments
         let mut pattern = String::from("*");
         let mut count = 10_usize; // Default count
+        let mut type_name = None;
 
         let mut i = 1;
         while i < args.len() {
@@ -128,6 +213,13 @@ impl KeyCommands {
                         count = 1; // Minimum count is 1
                     }
                 }
+                "TYPE" => {
+                    if i + 1 >= args.len() {
+                        return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
+                    }
+                    i += 1;
+                    type_name = Some(String::from_utf8_lossy(&args[i]).to_lowercase());
+                }
                 _ => {
                     return Err(AikvError::InvalidArgument(format!(
                         "ERR unknown option '{}'",
@@ -140,7 +232,7 @@ impl KeyCommands {
 
         // Get all keys and filter by pattern
         let all_keys = self.storage.get_all_keys_in_db(current_db)?;
-        let matched_keys: Vec<String> = if pattern == "*" {
+        let mut matched_keys: Vec<String> = if pattern == "*" {
             all_keys
         } else {
             all_keys
@@ -148,6 +240,17 @@ impl KeyCommands {
                 .filter(|k| self.match_pattern(k, &pattern))
                 .collect()
         };
+        if let Some(type_name) = type_name {
+            let mut of_type = Vec::with_capacity(matched_keys.len());
+            for key in matched_keys {
+                if let Some(stored) = self.storage.get_value(current_db, &key)? {
+                    if stored.get_type_name() == type_name {
+                        of_type.push(key);
+                    }
+                }
+            }
+            matched_keys = of_type;
+        }
 
         // Calculate the range to return
         let total_keys = matched_keys.len();
@@ -215,6 +318,9 @@ impl KeyCommands {
     }
 
     /// TYPE key - Return the type of the value stored at key
+    ///
+    /// JSON documents are stored as plain strings, so they report `string`
+    /// rather than RedisJSON's `ReJSON-RL`.
     pub fn get_type(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
         if args.len() != 1 {
             return Err(AikvError::WrongArgCount("TYPE".to_string()));
@@ -257,6 +363,11 @@ impl KeyCommands {
                     dest_db = db_str.parse::<usize>().map_err(|_| {
                         AikvError::InvalidArgument("ERR invalid DB index".to_string())
                     })?;
+                    if dest_db >= self.storage.db_count() {
+                        return Err(AikvError::InvalidArgument(
+                            "ERR DB index is out of range".to_string(),
+                        ));
+                    }
                 }
                 "REPLACE" => {
                     replace = true;
@@ -268,106 +379,111 @@ impl KeyCommands {
             i += 1;
         }
 
+        if dest_db == current_db && src_key == dst_key {
+            return Err(AikvError::InvalidArgument(
+                "ERR source and destination objects are the same".to_string(),
+            ));
+        }
+
         let copied = self
             .storage
             .copy_in_db(current_db, dest_db, &src_key, &dst_key, replace)?;
         Ok(RespValue::integer(if copied { 1 } else { 0 }))
     }
 
-    /// EXPIRE key seconds - Set a key's time to live in seconds
+    /// EXPIRE key seconds \[NX|XX|GT|LT\] - Set a key's time to live in seconds
     pub fn expire(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
-        if args.len() != 2 {
-            return Err(AikvError::WrongArgCount("EXPIRE".to_string()));
-        }
-
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let seconds_str = String::from_utf8_lossy(&args[1]);
-        let seconds = seconds_str
-            .parse::<i64>()
-            .map_err(|_| AikvError::InvalidArgument("ERR value is not an integer".to_string()))?;
-
-        if seconds <= 0 {
-            // Delete the key immediately if seconds <= 0
-            let deleted = self.storage.delete_from_db(current_db, &key)?;
-            return Ok(RespValue::integer(if deleted { 1 } else { 0 }));
-        }
-
-        let expire_ms = (seconds as u64) * 1000;
-        let set = self.storage.set_expire_in_db(current_db, &key, expire_ms)?;
-        Ok(RespValue::integer(if set { 1 } else { 0 }))
+        self.expire_generic("EXPIRE", args, current_db, 1000, false)
     }
 
-    /// EXPIREAT key timestamp - Set expiration as UNIX timestamp in seconds
+    /// EXPIREAT key timestamp \[NX|XX|GT|LT\] - Set expiration as UNIX timestamp in seconds
     pub fn expireat(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
-        if args.len() != 2 {
-            return Err(AikvError::WrongArgCount("EXPIREAT".to_string()));
-        }
-
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let timestamp_str = String::from_utf8_lossy(&args[1]);
-        let timestamp = timestamp_str
-            .parse::<i64>()
-            .map_err(|_| AikvError::InvalidArgument("ERR value is not an integer".to_string()))?;
-
-        if timestamp <= 0 {
-            let deleted = self.storage.delete_from_db(current_db, &key)?;
-            return Ok(RespValue::integer(if deleted { 1 } else { 0 }));
-        }
-
-        let timestamp_ms = (timestamp as u64) * 1000;
-        let set = self
-            .storage
-            .set_expire_at_in_db(current_db, &key, timestamp_ms)?;
-        Ok(RespValue::integer(if set { 1 } else { 0 }))
+        self.expire_generic("EXPIREAT", args, current_db, 1000, true)
     }
 
-    /// PEXPIRE key milliseconds - Set expiration in milliseconds
+    /// PEXPIRE key milliseconds \[NX|XX|GT|LT\] - Set expiration in milliseconds
     pub fn pexpire(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
-        if args.len() != 2 {
-            return Err(AikvError::WrongArgCount("PEXPIRE".to_string()));
-        }
-
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let ms_str = String::from_utf8_lossy(&args[1]);
-        let milliseconds = ms_str
-            .parse::<i64>()
-            .map_err(|_| AikvError::InvalidArgument("ERR value is not an integer".to_string()))?;
-
-        if milliseconds <= 0 {
-            let deleted = self.storage.delete_from_db(current_db, &key)?;
-            return Ok(RespValue::integer(if deleted { 1 } else { 0 }));
-        }
-
-        let set = self
-            .storage
-            .set_expire_in_db(current_db, &key, milliseconds as u64)?;
-        Ok(RespValue::integer(if set { 1 } else { 0 }))
+        self.expire_generic("PEXPIRE", args, current_db, 1, false)
     }
 
-    /// PEXPIREAT key milliseconds-timestamp - Set expiration as UNIX timestamp in milliseconds
+    /// PEXPIREAT key milliseconds-timestamp \[NX|XX|GT|LT\] - Set expiration as UNIX
+    /// timestamp in milliseconds
     pub fn pexpireat(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
-        if args.len() != 2 {
-            return Err(AikvError::WrongArgCount("PEXPIREAT".to_string()));
+        self.expire_generic("PEXPIREAT", args, current_db, 1, true)
+    }
+
+    /// Shared implementation of EXPIRE, EXPIREAT, PEXPIRE and PEXPIREAT
+    ///
+    /// `unit_ms` is the length of one time unit of the argument, which is an
+    /// absolute Unix time if `absolute` is set and relative to now otherwise.
+    /// Replies 1 if the TTL was set (or the key deleted), 0 if the key doesn't
+    /// exist or the NX/XX/GT/LT condition wasn't met.
+    fn expire_generic(
+        &self,
+        command: &str,
+        args: &[Bytes],
+        current_db: usize,
+        unit_ms: i64,
+        absolute: bool,
+    ) -> Result<RespValue> {
+        if args.len() < 2 {
+            return Err(AikvError::WrongArgCount(command.to_string()));
         }
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
-        let timestamp_str = String::from_utf8_lossy(&args[1]);
-        let timestamp_ms = timestamp_str
+        let value = String::from_utf8_lossy(&args[1])
             .parse::<i64>()
             .map_err(|_| AikvError::InvalidArgument("ERR value is not an integer".to_string()))?;
+        let condition = ExpireCondition::parse(&args[2..])?;
+
+        // A time that has already passed deletes the key, as does any
+        // non-positive relative one; otherwise compute the absolute expiry
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        let expires_at = if value <= 0 && !absolute {
+            None
+        } else {
+            let at = value.checked_mul(unit_ms).and_then(|ms| {
+                if absolute {
+                    Some(ms)
+                } else {
+                    now_ms.checked_add(ms)
+                }
+            });
+            let at = at.ok_or_else(|| {
+                AikvError::InvalidArgument(format!(
+                    "ERR invalid expire time in '{}' command",
+                    command.to_lowercase()
+                ))
+            })?;
+            (at > now_ms).then_some(at)
+        };
 
-        if timestamp_ms <= 0 {
-            let deleted = self.storage.delete_from_db(current_db, &key)?;
-            return Ok(RespValue::integer(if deleted { 1 } else { 0 }));
+        // -2: no such key, -1: no TTL, otherwise the current absolute expiry
+        let current = self.storage.get_expire_time_in_db(current_db, &key)?;
+        if current == -2 {
+            return Ok(RespValue::integer(0));
+        }
+        let current = (current >= 0).then_some(current);
+        if !condition.allows(current, expires_at.unwrap_or(0)) {
+            return Ok(RespValue::integer(0));
         }
 
-        let set = self
-            .storage
-            .set_expire_at_in_db(current_db, &key, timestamp_ms as u64)?;
+        let set = match expires_at {
+            None => self.storage.delete_from_db(current_db, &key)?,
+            Some(at) => self
+                .storage
+                .set_expire_at_in_db(current_db, &key, at as u64)?,
+        };
         Ok(RespValue::integer(if set { 1 } else { 0 }))
     }
 
     /// TTL key - Get the time to live for a key in seconds
+    ///
+    /// Rounded to the nearest second, so a key with 1800ms left reports 2;
+    /// PTTL gives the exact milliseconds.
     pub fn ttl(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
         if args.len() != 1 {
             return Err(AikvError::WrongArgCount("TTL".to_string()));
@@ -376,9 +492,7 @@ impl KeyCommands {
         let key = String::from_utf8_lossy(&args[0]).to_string();
         let ttl_ms = self.storage.get_ttl_in_db(current_db, &key)?;
 
-        let ttl_seconds = if ttl_ms > 0 { ttl_ms / 1000 } else { ttl_ms };
-
-        Ok(RespValue::integer(ttl_seconds))
+        Ok(RespValue::integer(round_to_seconds(ttl_ms)))
     }
 
     /// PTTL key - Get the time to live for a key in milliseconds
@@ -405,7 +519,8 @@ impl KeyCommands {
         Ok(RespValue::integer(if persisted { 1 } else { 0 }))
     }
 
-    /// EXPIRETIME key - Get the expiration Unix timestamp in seconds
+    /// EXPIRETIME key - Get the expiration Unix timestamp in seconds,
+    /// rounded to the nearest second like TTL
     pub fn expiretime(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
         if args.len() != 1 {
             return Err(AikvError::WrongArgCount("EXPIRETIME".to_string()));
@@ -414,13 +529,7 @@ impl KeyCommands {
         let key = String::from_utf8_lossy(&args[0]).to_string();
         let expire_time_ms = self.storage.get_expire_time_in_db(current_db, &key)?;
 
-        let expire_time_seconds = if expire_time_ms > 0 {
-            expire_time_ms / 1000
-        } else {
-            expire_time_ms
-        };
-
-        Ok(RespValue::integer(expire_time_seconds))
+        Ok(RespValue::integer(round_to_seconds(expire_time_ms)))
     }
 
     /// PEXPIRETIME key - Get the expiration Unix timestamp in milliseconds
@@ -435,17 +544,17 @@ impl KeyCommands {
         Ok(RespValue::integer(expire_time_ms))
     }
 
-    /// DUMP key - Serialize the value stored at key in a Redis-specific format
+    /// DUMP key - Serialize the value stored at key
     ///
     /// Returns a serialized representation of the value that can be restored
-    /// using the RESTORE command. The serialization format is compatible with
-    /// Redis's RDB format structure (simplified version).
+    /// using the RESTORE command. The layout mirrors Redis's DUMP payload, but
+    /// the value encoding is AiKv-specific, so payloads only round-trip
+    /// between AiKv instances.
     ///
     /// Format:
-    /// - 1 byte: type
-    /// - variable: serialized value (bincode)
-    /// - 2 bytes: RDB version (0x0009)
-    /// - 8 bytes: CRC64 checksum
+    /// - variable: serialized value (bincode of `SerializableStoredValue`)
+    /// - 2 bytes: payload version (little-endian)
+    /// - 8 bytes: CRC64 of everything before it (little-endian)
     pub fn dump(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
         if args.len() != 1 {
             return Err(AikvError::WrongArgCount("DUMP".to_string()));
@@ -453,63 +562,54 @@ impl KeyCommands {
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
 
-        // Get the value
         match self.storage.get_value(current_db, &key)? {
-            Some(stored_value) => {
-                // Serialize the value
-                let serializable = stored_value.to_serializable();
-                let serialized = bincode::serialize(&serializable)
-                    .map_err(|e| AikvError::Storage(format!("Failed to serialize value: {}", e)))?;
-
-                // Build the dump format:
-                // - serialized value
-                // - 2 bytes RDB version (0x0009 = 9)
-                // - 8 bytes checksum (simplified additive checksum)
-                let mut dump_data = serialized;
-                dump_data.extend_from_slice(&[0x00, 0x09]); // RDB version 9
-
-                // Calculate a simple 64-bit additive checksum for data integrity
-                let checksum = Self::calculate_checksum(&dump_data);
-                dump_data.extend_from_slice(&checksum.to_le_bytes());
-
-                Ok(RespValue::bulk_string(Bytes::from(dump_data)))
-            }
+            Some(stored_value) => Ok(RespValue::bulk_string(Bytes::from(Self::encode_dump(
+                &stored_value,
+            )?))),
             None => Ok(RespValue::null_bulk_string()),
         }
     }
 
-    /// Calculate a simple 64-bit additive checksum for the data
-    fn calculate_checksum(data: &[u8]) -> u64 {
-        let mut checksum: u64 = 0;
-        for (i, byte) in data.iter().enumerate() {
-            checksum =
-                checksum.wrapping_add((*byte as u64).wrapping_mul((i as u64).wrapping_add(1)));
-        }
-        checksum
+    /// Encode a value into the DUMP payload format
+    pub(crate) fn encode_dump(stored_value: &StoredValue) -> Result<Vec<u8>> {
+        let mut payload = bincode::serialize(&stored_value.to_serializable())
+            .map_err(|e| AikvError::Storage(format!("Failed to serialize value: {}", e)))?;
+
+        payload.extend_from_slice(&DUMP_VERSION.to_le_bytes());
+        let checksum = crc64(0, &payload);
+        payload.extend_from_slice(&checksum.to_le_bytes());
+
+        Ok(payload)
     }
 
-    /// Verify the checksum in the dump data
-    fn verify_checksum(data: &[u8]) -> bool {
-        if data.len() < 10 {
-            return false;
+    /// Decode a DUMP payload, verifying its version and checksum
+    ///
+    /// The expiration stored in the payload is discarded; RESTORE sets it
+    /// from its own TTL argument.
+    pub(crate) fn decode_dump(payload: &[u8]) -> Result<StoredValue> {
+        if payload.len() < DUMP_FOOTER_LEN {
+            return Err(AikvError::BadDataFormat);
         }
 
-        // Extract checksum (last 8 bytes)
-        let stored_checksum = u64::from_le_bytes([
-            data[data.len() - 8],
-            data[data.len() - 7],
-            data[data.len() - 6],
-            data[data.len() - 5],
-            data[data.len() - 4],
-            data[data.len() - 3],
-            data[data.len() - 2],
-            data[data.len() - 1],
-        ]);
+        let (body, checksum) = payload.split_at(payload.len() - 8);
+        let checksum =
+            u64::from_le_bytes(checksum.try_into().map_err(|_| AikvError::BadDataFormat)?);
+        if crc64(0, body) != checksum {
+            return Err(AikvError::BadDataFormat);
+        }
 
-        // Calculate checksum on data without the checksum itself
-        let calculated_checksum = Self::calculate_checksum(&data[..data.len() - 8]);
+        let (data, version) = body.split_at(body.len() - 2);
+        let version = u16::from_le_bytes([version[0], version[1]]);
+        if version > DUMP_VERSION {
+            return Err(AikvError::BadDataFormat);
+        }
 
-        stored_checksum == calculated_checksum
+        let serializable: SerializableStoredValue =
+            bincode::deserialize(data).map_err(|_| AikvError::BadDataFormat)?;
+        let mut stored_value = StoredValue::from_serializable(serializable);
+        stored_value.set_expiration(None);
+
+        Ok(stored_value)
     }
 
     /// RESTORE key ttl serialized-value \[REPLACE\] \[ABSTTL\] \[IDLETIME seconds\] \[FREQ frequency\]
@@ -522,6 +622,8 @@ impl KeyCommands {
     /// - serialized-value: The serialized value from DUMP command
     /// - REPLACE: Replace existing key if present
     /// - ABSTTL: TTL is an absolute Unix timestamp in milliseconds
+    /// - IDLETIME: Set the idle time reported by OBJECT IDLETIME, in seconds
+    /// - FREQ: Set the access frequency reported by OBJECT FREQ (0-255)
     pub fn restore(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
         if args.len() < 3 {
             return Err(AikvError::WrongArgCount("RESTORE".to_string()));
@@ -532,12 +634,19 @@ impl KeyCommands {
         let ttl = ttl_str
             .parse::<i64>()
             .map_err(|_| AikvError::InvalidArgument("ERR invalid TTL value".to_string()))?;
+        if ttl < 0 {
+            return Err(AikvError::InvalidArgument(
+                "ERR invalid TTL value, must be >= 0".to_string(),
+            ));
+        }
 
         let serialized_value = &args[2];
 
         // Parse options
         let mut replace = false;
         let mut absttl = false;
+        let mut idle_secs: Option<u64> = None;
+        let mut frequency: Option<u8> = None;
 
         let mut i = 3;
         while i < args.len() {
@@ -549,12 +658,42 @@ impl KeyCommands {
                 "ABSTTL" => {
                     absttl = true;
                 }
-                "IDLETIME" | "FREQ" => {
-                    // These options are accepted but ignored (for Redis compatibility)
+                "IDLETIME" => {
+                    if i + 1 >= args.len() {
+                        return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
+                    }
+                    i += 1;
+                    let secs = String::from_utf8_lossy(&args[i])
+                        .parse::<i64>()
+                        .map_err(|_| {
+                            AikvError::InvalidArgument(
+                                "ERR value is not an integer or out of range".to_string(),
+                            )
+                        })?;
+                    if secs < 0 {
+                        return Err(AikvError::InvalidArgument(
+                            "ERR Invalid IDLETIME value, must be >= 0".to_string(),
+                        ));
+                    }
+                    idle_secs = Some(secs as u64);
+                }
+                "FREQ" => {
                     if i + 1 >= args.len() {
                         return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
                     }
-                    i += 1; // Skip the value
+                    i += 1;
+                    let freq = String::from_utf8_lossy(&args[i])
+                        .parse::<i64>()
+                        .map_err(|_| {
+                            AikvError::InvalidArgument(
+                                "ERR value is not an integer or out of range".to_string(),
+                            )
+                        })?;
+                    frequency = Some(u8::try_from(freq).map_err(|_| {
+                        AikvError::InvalidArgument(
+                            "ERR Invalid FREQ value, must be >= 0 and <= 255".to_string(),
+                        )
+                    })?);
                 }
                 _ => {
                     return Err(AikvError::InvalidArgument(format!(
@@ -568,64 +707,47 @@ impl KeyCommands {
 
         // Check if key already exists
         if !replace && self.storage.exists_in_db(current_db, &key)? {
-            return Err(AikvError::InvalidArgument(
-                "BUSYKEY Target key name already exists".to_string(),
-            ));
-        }
-
-        // Verify the serialized value format
-        if serialized_value.len() < 10 {
-            return Err(AikvError::InvalidArgument(
-                "ERR DUMP payload version or checksum are wrong".to_string(),
-            ));
-        }
-
-        // Verify checksum
-        if !Self::verify_checksum(serialized_value) {
-            return Err(AikvError::InvalidArgument(
-                "ERR DUMP payload version or checksum are wrong".to_string(),
-            ));
+            return Err(AikvError::BusyKey);
         }
 
-        // Extract the serialized data (without RDB version and checksum)
-        let data_len = serialized_value.len() - 10; // -2 for version, -8 for checksum
-        let data = &serialized_value[..data_len];
+        let mut stored_value = Self::decode_dump(serialized_value)?;
 
-        // Deserialize the value
-        let serializable: SerializableStoredValue = bincode::deserialize(data).map_err(|e| {
-            AikvError::InvalidArgument(format!(
-                "ERR DUMP payload version or checksum are wrong: {}",
-                e
-            ))
-        })?;
-
-        let mut stored_value = StoredValue::from_serializable(serializable);
-
-        // Set expiration if TTL is provided
         if ttl > 0 {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64;
             let expires_at = if absttl {
                 // TTL is an absolute timestamp
                 ttl as u64
             } else {
                 // TTL is relative (milliseconds from now)
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis() as u64;
                 now + (ttl as u64)
             };
+
+            // An already-expired TTL restores nothing, but REPLACE still
+            // removes the existing key
+            if expires_at <= now {
+                if replace {
+                    self.storage.delete_from_db(current_db, &key)?;
+                }
+                return Ok(RespValue::ok());
+            }
             stored_value.set_expiration(Some(expires_at));
-        } else if ttl == 0 {
-            // No expiration
-            stored_value.set_expiration(None);
-        } else {
-            // Negative TTL is an error
-            return Err(AikvError::InvalidArgument(
-                "ERR invalid TTL value, must be >= 0".to_string(),
-            ));
         }
 
-        // Store the value
+        if let Some(secs) = idle_secs {
+            stored_value.set_idle_time_ms(secs.saturating_mul(1000));
+        }
+        if let Some(frequency) = frequency {
+            stored_value.set_lfu_frequency(frequency);
+        }
+
+        // A replaced key is deleted first, so it doesn't pass its access
+        // frequency on to the restored one
+        if replace {
+            self.storage.delete_from_db(current_db, &key)?;
+        }
         self.storage.set_value(current_db, key, stored_value)?;
 
         Ok(RespValue::ok())
@@ -633,33 +755,38 @@ impl KeyCommands {
 
     /// MIGRATE host port key|"" destination-db timeout \[COPY\] \[REPLACE\] \[AUTH password\] \[AUTH2 username password\] \[KEYS key \[key ...\]\]
     ///
-    /// Atomically transfer a key from a source Redis instance to a destination Redis instance.
+    /// Transfer keys to another instance: each key is serialized in the DUMP
+    /// format and RESTOREd on the target, then deleted here unless COPY is
+    /// given. The command is run in three steps, so the blocking transfer can
+    /// happen off the async runtime: this parses the arguments and serializes
+    /// the keys that exist, [`Migration::transfer`] sends them, and
+    /// [`KeyCommands::finish_migrate`] removes the ones the target accepted.
     ///
-    /// Note: This is a simplified implementation that works within a single AiKv instance.
-    /// It simulates migration by moving/copying keys between databases.
-    ///
-    /// For true cross-instance migration, a network client would need to be implemented.
-    pub fn migrate(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+    /// Returns `None` if none of the keys exist, which MIGRATE replies NOKEY to.
+    pub fn prepare_migrate(&self, args: &[Bytes], current_db: usize) -> Result<Option<Migration>> {
         if args.len() < 5 {
             return Err(AikvError::WrongArgCount("MIGRATE".to_string()));
         }
 
-        let _host = String::from_utf8_lossy(&args[0]).to_string();
-        let _port = String::from_utf8_lossy(&args[1]);
-        let key_arg = String::from_utf8_lossy(&args[2]).to_string();
-        let dest_db_str = String::from_utf8_lossy(&args[3]);
-        let dest_db = dest_db_str
+        let host = String::from_utf8_lossy(&args[0]).to_string();
+        let port = String::from_utf8_lossy(&args[1])
+            .parse::<u16>()
+            .map_err(|_| AikvError::InvalidArgument("ERR Invalid port".to_string()))?;
+        let key_arg = args[2].clone();
+        let dest_db = String::from_utf8_lossy(&args[3])
             .parse::<usize>()
             .map_err(|_| AikvError::InvalidArgument("ERR invalid DB index".to_string()))?;
-        let _timeout_str = String::from_utf8_lossy(&args[4]);
-        let _timeout = _timeout_str
+        let timeout = String::from_utf8_lossy(&args[4])
             .parse::<i64>()
             .map_err(|_| AikvError::InvalidArgument("ERR timeout is not an integer".to_string()))?;
+        // Like Redis, a timeout that isn't positive means one second
+        let timeout = Duration::from_millis(if timeout <= 0 { 1000 } else { timeout as u64 });
 
         // Parse options
         let mut copy = false;
         let mut replace = false;
-        let mut keys: Vec<String> = Vec::new();
+        let mut auth: Option<Vec<Bytes>> = None;
+        let mut keys: Vec<Bytes> = Vec::new();
 
         let mut i = 5;
         while i < args.len() {
@@ -672,26 +799,27 @@ impl KeyCommands {
                     replace = true;
                 }
                 "AUTH" => {
-                    // Skip AUTH argument (password)
                     if i + 1 >= args.len() {
                         return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
                     }
+                    auth = Some(args[i + 1..i + 2].to_vec());
                     i += 1;
                 }
                 "AUTH2" => {
-                    // Skip AUTH2 arguments (username, password)
                     if i + 2 >= args.len() {
                         return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
                     }
+                    auth = Some(args[i + 1..i + 3].to_vec());
                     i += 2;
                 }
                 "KEYS" => {
-                    // Collect all remaining arguments as keys
-                    i += 1;
-                    while i < args.len() {
-                        keys.push(String::from_utf8_lossy(&args[i]).to_string());
-                        i += 1;
+                    if !key_arg.is_empty() {
+                        return Err(AikvError::InvalidArgument(
+                            "ERR When using MIGRATE KEYS option, the key argument must be set to the empty string".to_string(),
+                        ));
                     }
+                    // All remaining arguments are keys
+                    keys.extend_from_slice(&args[i + 1..]);
                     break;
                 }
                 _ => {
@@ -714,63 +842,170 @@ impl KeyCommands {
             keys.push(key_arg);
         }
 
-        // Validate destination database
-        if dest_db >= DEFAULT_DB_COUNT {
-            return Err(AikvError::InvalidArgument(
-                "ERR invalid DB index".to_string(),
-            ));
+        // Serialize the keys that exist, along with their absolute expiry
+        let mut payloads = Vec::new();
+        for key in keys {
+            if let Some(stored_value) = self
+                .storage
+                .get_value(current_db, &String::from_utf8_lossy(&key))?
+            {
+                let expires_at = stored_value.expires_at().unwrap_or(0);
+                payloads.push((
+                    key,
+                    expires_at,
+                    Bytes::from(Self::encode_dump(&stored_value)?),
+                ));
+            }
+        }
+        if payloads.is_empty() {
+            return Ok(None);
         }
 
-        // Process each key
-        let mut migrated_count = 0;
-        for key in &keys {
-            // Check if source key exists
-            if !self.storage.exists_in_db(current_db, key)? {
-                continue;
-            }
+        Ok(Some(Migration {
+            host,
+            port,
+            timeout,
+            auth,
+            dest_db,
+            copy,
+            replace,
+            payloads,
+        }))
+    }
 
-            // Check if destination key exists and REPLACE is not set
-            if self.storage.exists_in_db(dest_db, key)? && !replace {
-                return Err(AikvError::InvalidArgument(
-                    "BUSYKEY Target key name already exists".to_string(),
-                ));
+    /// Remove the keys the target of a migration accepted, unless it was a COPY
+    ///
+    /// Returns the keys removed, along with the error that stopped the
+    /// removal partway if one did.
+    pub fn finish_migrate(
+        &self,
+        migration: &Migration,
+        accepted: Vec<Bytes>,
+        current_db: usize,
+    ) -> (Vec<Bytes>, Result<()>) {
+        let mut removed = Vec::new();
+        if migration.copy {
+            return (removed, Ok(()));
+        }
+        for key in accepted {
+            if let Err(e) = self
+                .storage
+                .delete_from_db(current_db, &String::from_utf8_lossy(&key))
+            {
+                return (removed, Err(e));
             }
+            removed.push(key);
+        }
+        (removed, Ok(()))
+    }
+}
 
-            // Get the source value
-            if let Some(stored_value) = self.storage.get_value(current_db, key)? {
-                // Remember if destination had a value for rollback
-                let dest_had_value = self.storage.exists_in_db(dest_db, key)?;
-                let dest_old_value = if dest_had_value && replace {
-                    self.storage.get_value(dest_db, key)?
-                } else {
-                    None
-                };
+/// A MIGRATE that has been parsed and has its keys serialized, ready to send
+pub struct Migration {
+    host: String,
+    port: u16,
+    timeout: Duration,
+    auth: Option<Vec<Bytes>>,
+    dest_db: usize,
+    copy: bool,
+    replace: bool,
+    /// Each key with its absolute expiry (0 for none) and DUMP payload
+    payloads: Vec<(Bytes, u64, Bytes)>,
+}
 
-                // Copy to destination
-                self.storage
-                    .set_value(dest_db, key.clone(), stored_value.clone())?;
-
-                // Delete from source if not COPY mode
-                if !copy {
-                    if let Err(e) = self.storage.delete_from_db(current_db, key) {
-                        // Rollback: restore destination to previous state
-                        if let Some(old_val) = dest_old_value {
-                            let _ = self.storage.set_value(dest_db, key.clone(), old_val);
-                        } else if !dest_had_value {
-                            let _ = self.storage.delete_from_db(dest_db, key);
-                        }
-                        return Err(e);
-                    }
-                }
+impl Migration {
+    /// Send the keys to the target, blocking until it has replied to each, or
+    /// `timeout` milliseconds pass without progress
+    ///
+    /// Returns the keys the target accepted, in order, along with the error
+    /// that stopped the transfer if one did. Keys accepted before an error
+    /// still have to be removed by [`KeyCommands::finish_migrate`].
+    pub fn transfer(&self) -> (Vec<Bytes>, Result<()>) {
+        let mut accepted = Vec::new();
+        let result = self.send(&mut accepted);
+        (accepted, result)
+    }
 
-                migrated_count += 1;
+    fn send(&self, accepted: &mut Vec<Bytes>) -> Result<()> {
+        let mut target = MigrateTarget::connect(&self.host, self.port, self.timeout)?;
+        if let Some(auth) = &self.auth {
+            let mut command = vec![Bytes::from_static(b"AUTH")];
+            command.extend(auth.iter().cloned());
+            target.call(command)?;
+        }
+        target.call(vec![
+            Bytes::from_static(b"SELECT"),
+            Bytes::from(self.dest_db.to_string()),
+        ])?;
+
+        for (key, expires_at, payload) in &self.payloads {
+            let mut command = vec![
+                Bytes::from_static(b"RESTORE"),
+                key.clone(),
+                Bytes::from(expires_at.to_string()),
+                payload.clone(),
+                Bytes::from_static(b"ABSTTL"),
+            ];
+            if self.replace {
+                command.push(Bytes::from_static(b"REPLACE"));
             }
+            target.call(command)?;
+            accepted.push(key.clone());
         }
 
-        if migrated_count == 0 {
-            Ok(RespValue::simple_string("NOKEY"))
-        } else {
-            Ok(RespValue::ok())
+        Ok(())
+    }
+}
+
+/// The connection MIGRATE opens to its target instance
+struct MigrateTarget {
+    stream: TcpStream,
+    parser: RespParser,
+}
+
+impl MigrateTarget {
+    /// Connect, with `timeout` applying to the connect and to every read and write
+    fn connect(host: &str, port: u16, timeout: Duration) -> Result<Self> {
+        let connect_error =
+            || AikvError::IoErr("error or timeout connecting to the client".to_string());
+        let addr = (host, port)
+            .to_socket_addrs()
+            .map_err(|_| connect_error())?
+            .next()
+            .ok_or_else(connect_error)?;
+        let stream = TcpStream::connect_timeout(&addr, timeout).map_err(|_| connect_error())?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        Ok(Self {
+            stream,
+            parser: RespParser::new(4096),
+        })
+    }
+
+    /// Send a command and wait for its reply, turning an error reply into an error
+    fn call(&mut self, command: Vec<Bytes>) -> Result<RespValue> {
+        let command = RespValue::array(command.into_iter().map(RespValue::bulk_string).collect());
+        self.stream.write_all(&command.serialize()).map_err(|_| {
+            AikvError::IoErr("error or timeout writing to target instance".to_string())
+        })?;
+
+        let read_error =
+            || AikvError::IoErr("error or timeout reading from target instance".to_string());
+        let mut buf = [0u8; 4096];
+        loop {
+            if let Some(reply) = self.parser.parse()? {
+                return match reply {
+                    RespValue::Error(e) => Err(AikvError::InvalidArgument(format!(
+                        "ERR Target instance replied with error: {}",
+                        e
+                    ))),
+                    reply => Ok(reply),
+                };
+            }
+            match self.stream.read(&mut buf) {
+                Ok(0) | Err(_) => return Err(read_error()),
+                Ok(n) => self.parser.feed(&buf[..n]),
+            }
         }
     }
 }
@@ -4,6 +4,63 @@ use crate::storage::{StorageEngine, StoredValue};
 use bytes::Bytes;
 use std::collections::VecDeque;
 
+/// Parse the arguments of LMPOP and ZMPOP:
+/// `numkeys key [key ...] <where> [COUNT count]`
+///
+/// `wheres` are the two accepted directions. Returns the keys, whether the
+/// first direction was given and the count, which defaults to 1.
+pub(crate) fn parse_mpop_args<'a>(
+    command: &str,
+    args: &'a [Bytes],
+    wheres: [&str; 2],
+) -> Result<(&'a [Bytes], bool, usize)> {
+    if args.len() < 3 {
+        return Err(AikvError::WrongArgCount(command.to_string()));
+    }
+    let syntax_error = || AikvError::InvalidArgument("ERR syntax error".to_string());
+
+    let numkeys = String::from_utf8_lossy(&args[0])
+        .parse::<i64>()
+        .ok()
+        .filter(|&n| n > 0)
+        .ok_or_else(|| {
+            AikvError::InvalidArgument("ERR numkeys should be greater than 0".to_string())
+        })? as usize;
+    if numkeys > args.len() - 2 {
+        return Err(syntax_error());
+    }
+    let keys = &args[1..=numkeys];
+
+    let direction = &args[numkeys + 1];
+    let first = if direction.eq_ignore_ascii_case(wheres[0].as_bytes()) {
+        true
+    } else if direction.eq_ignore_ascii_case(wheres[1].as_bytes()) {
+        false
+    } else {
+        return Err(syntax_error());
+    };
+
+    let mut count = None;
+    let mut options = args[numkeys + 2..].iter();
+    while let Some(option) = options.next() {
+        match options.next() {
+            Some(value) if option.eq_ignore_ascii_case(b"COUNT") && count.is_none() => {
+                let value = String::from_utf8_lossy(value)
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| {
+                        AikvError::InvalidArgument("ERR count should be greater than 0".to_string())
+                    })?;
+                count = Some(value as usize);
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+
+    Ok((keys, first, count.unwrap_or(1)))
+}
+
 /// List command handler
 pub struct ListCommands {
     storage: StorageEngine,
@@ -87,99 +144,116 @@ impl ListCommands {
     /// LPOP key \[count\]
     /// Remove and return the first elements of the list stored at key
     pub fn lpop(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
-        if args.is_empty() {
-            return Err(AikvError::WrongArgCount("LPOP".to_string()));
-        }
-
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let count = if args.len() > 1 {
-            String::from_utf8_lossy(&args[1])
-                .parse::<usize>()
-                .map_err(|_| AikvError::InvalidArgument("invalid count".to_string()))?
-        } else {
-            1
-        };
+        self.pop(args, db_index, "LPOP", true)
+    }
 
-        // Migrated: Logic moved from storage layer to command layer
-        let mut values = Vec::new();
+    /// RPOP key \[count\]
+    /// Remove and return the last elements of the list stored at key
+    pub fn rpop(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        self.pop(args, db_index, "RPOP", false)
+    }
 
-        if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            let mut list = stored.as_list()?.clone();
+    /// LPOP and RPOP, popping from the front or the back
+    ///
+    /// Without a count the reply is a single element, or null; with one it
+    /// is an array, which is null rather than empty if the key is missing.
+    fn pop(
+        &self,
+        args: &[Bytes],
+        db_index: usize,
+        command: &str,
+        front: bool,
+    ) -> Result<RespValue> {
+        if args.is_empty() || args.len() > 2 {
+            return Err(AikvError::WrongArgCount(command.to_string()));
+        }
 
-            // Pop elements from the front
-            for _ in 0..count.min(list.len()) {
-                if let Some(value) = list.pop_front() {
-                    values.push(value);
-                }
-            }
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+        let count =
+            match args.get(1) {
+                Some(count) => Some(String::from_utf8_lossy(count).parse::<usize>().map_err(
+                    |_| {
+                        AikvError::InvalidArgument(
+                            "ERR value is out of range, must be positive".to_string(),
+                        )
+                    },
+                )?),
+                None => None,
+            };
 
-            // Update or delete the list
-            if list.is_empty() {
-                self.storage.delete_from_db(db_index, &key)?;
-            } else {
-                self.storage
-                    .set_value(db_index, key, StoredValue::new_list(list))?;
-            }
-        }
+        let Some(mut values) = self.pop_elements(db_index, key, count.unwrap_or(1), front)? else {
+            return Ok(match count {
+                Some(_) => RespValue::null_array(),
+                None => RespValue::Null,
+            });
+        };
 
-        if values.is_empty() {
-            Ok(RespValue::Null)
-        } else if count == 1 {
-            Ok(RespValue::bulk_string(values[0].clone()))
-        } else {
-            Ok(RespValue::Array(Some(
+        match count {
+            Some(_) => Ok(RespValue::Array(Some(
                 values.into_iter().map(RespValue::bulk_string).collect(),
-            )))
+            ))),
+            None => Ok(values
+                .pop()
+                .map(RespValue::bulk_string)
+                .unwrap_or(RespValue::Null)),
         }
     }
 
-    /// RPOP key \[count\]
-    /// Remove and return the last elements of the list stored at key
-    pub fn rpop(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
-        if args.is_empty() {
-            return Err(AikvError::WrongArgCount("RPOP".to_string()));
+    /// LMPOP numkeys key \[key ...\] LEFT|RIGHT \[COUNT count\]
+    /// Pop elements from the first non-empty list among the keys
+    ///
+    /// Replies with the key and the popped elements, or null if every list
+    /// is empty.
+    pub fn lmpop(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        let (keys, left, count) = parse_mpop_args("LMPOP", args, ["LEFT", "RIGHT"])?;
+
+        for key in keys {
+            let name = String::from_utf8_lossy(key).to_string();
+            if let Some(values) = self.pop_elements(db_index, name, count, left)? {
+                return Ok(RespValue::array(vec![
+                    RespValue::bulk_string(key.clone()),
+                    RespValue::array(values.into_iter().map(RespValue::bulk_string).collect()),
+                ]));
+            }
         }
+        Ok(RespValue::null_array())
+    }
 
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let count = if args.len() > 1 {
-            String::from_utf8_lossy(&args[1])
-                .parse::<usize>()
-                .map_err(|_| AikvError::InvalidArgument("invalid count".to_string()))?
-        } else {
-            1
+    /// Pop up to `count` elements from the front or the back of the list at
+    /// `key`, deleting the key once the list is empty
+    ///
+    /// Returns `None` if the key is missing.
+    fn pop_elements(
+        &self,
+        db_index: usize,
+        key: String,
+        count: usize,
+        front: bool,
+    ) -> Result<Option<Vec<Bytes>>> {
+        // Migrated: Logic moved from storage layer to command layer
+        let Some(stored) = self.storage.get_value(db_index, &key)? else {
+            return Ok(None);
         };
+        let mut list = stored.as_list()?.clone();
 
-        // Migrated: Logic moved from storage layer to command layer
         let mut values = Vec::new();
-
-        if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            let mut list = stored.as_list()?.clone();
-
-            // Pop elements from the back
-            for _ in 0..count.min(list.len()) {
-                if let Some(value) = list.pop_back() {
-                    values.push(value);
-                }
-            }
-
-            // Update or delete the list
-            if list.is_empty() {
-                self.storage.delete_from_db(db_index, &key)?;
+        for _ in 0..count.min(list.len()) {
+            let value = if front {
+                list.pop_front()
             } else {
-                self.storage
-                    .set_value(db_index, key, StoredValue::new_list(list))?;
-            }
+                list.pop_back()
+            };
+            values.extend(value);
         }
 
-        if values.is_empty() {
-            Ok(RespValue::Null)
-        } else if count == 1 {
-            Ok(RespValue::bulk_string(values[0].clone()))
-        } else {
-            Ok(RespValue::Array(Some(
-                values.into_iter().map(RespValue::bulk_string).collect(),
-            )))
+        // Update or delete the list
+        if list.is_empty() {
+            self.storage.delete_from_db(db_index, &key)?;
+        } else if !values.is_empty() {
+            self.storage
+                .set_value(db_index, key, StoredValue::new_list(list))?;
         }
+        Ok(Some(values))
     }
 
     /// LLEN key
@@ -216,14 +290,17 @@ impl ListCommands {
             .parse::<i64>()
             .map_err(|_| AikvError::InvalidArgument("invalid stop index".to_string()))?;
 
-        // Migrated: Logic moved from storage layer to command layer
-        let values = if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            let list = stored.as_list()?;
-            let len = list.len() as i64;
+        // Borrow the list in place so only the requested range is copied
+        let values = self
+            .storage
+            .with_value(db_index, &key, |stored| {
+                let list = stored.as_list()?;
+                let len = list.len() as i64;
+
+                if len == 0 {
+                    return Ok(Vec::new());
+                }
 
-            if len == 0 {
-                Vec::new()
-            } else {
                 // Normalize negative indices
                 let start_idx = if start < 0 {
                     (len + start).max(0) as usize
@@ -239,22 +316,21 @@ impl ListCommands {
 
                 // Extract range
                 if start_idx > stop_idx || start_idx >= len as usize {
-                    Vec::new()
+                    Ok(Vec::new())
                 } else {
-                    list.iter()
+                    Ok(list
+                        .iter()
                         .skip(start_idx)
                         .take(stop_idx - start_idx + 1)
                         .cloned()
-                        .collect()
+                        .collect())
                 }
-            }
-        } else {
-            Vec::new()
-        };
+            })?
+            .unwrap_or_default();
 
-        Ok(RespValue::Array(Some(
-            values.into_iter().map(RespValue::bulk_string).collect(),
-        )))
+        Ok(RespValue::lazy_array(values, |value| {
+            [RespValue::bulk_string(value.clone())]
+        }))
     }
 
     /// LINDEX key index
@@ -309,24 +385,25 @@ impl ListCommands {
             .map_err(|_| AikvError::InvalidArgument("invalid index".to_string()))?;
         let element = args[2].clone();
 
-        // Migrated: Logic moved from storage layer to command layer
-        if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            let mut list = stored.as_list()?.clone();
+        // Replace the element in place, without copying the list
+        let updated = self.storage.update_value(db_index, &key, |stored| {
+            let list = stored.as_list_mut()?;
             let len = list.len() as i64;
 
             // Normalize negative index
             let idx = if index < 0 { len + index } else { index };
 
-            if idx >= 0 && idx < len {
-                if let Some(elem) = list.get_mut(idx as usize) {
+            match usize::try_from(idx).ok().and_then(|idx| list.get_mut(idx)) {
+                Some(elem) => {
                     *elem = element;
+                    Ok(())
                 }
-                self.storage
-                    .set_value(db_index, key, StoredValue::new_list(list))?;
-                Ok(RespValue::simple_string("OK"))
-            } else {
-                Err(AikvError::InvalidArgument("index out of range".to_string()))
+                None => Err(AikvError::InvalidArgument("index out of range".to_string())),
             }
+        })?;
+
+        if updated {
+            Ok(RespValue::simple_string("OK"))
         } else {
             Err(AikvError::InvalidArgument("no such key".to_string()))
         }
@@ -343,65 +420,44 @@ impl ListCommands {
         let count = String::from_utf8_lossy(&args[1])
             .parse::<i64>()
             .map_err(|_| AikvError::InvalidArgument("invalid count".to_string()))?;
-        let element = args[2].clone();
-
-        // Migrated: Logic moved from storage layer to command layer
-        let removed = if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            let mut list = stored.as_list()?.clone();
-            let mut removed_count = 0;
-
-            if count == 0 {
-                // Remove all occurrences
-                list.retain(|e| {
-                    if e == &element {
-                        removed_count += 1;
-                        false
-                    } else {
-                        true
-                    }
-                });
-            } else if count > 0 {
-                // Remove first count occurrences from head
-                let mut to_remove = count as usize;
-                let mut new_list = VecDeque::new();
-                for elem in list {
-                    if to_remove > 0 && elem == element {
-                        to_remove -= 1;
-                        removed_count += 1;
-                    } else {
-                        new_list.push_back(elem);
-                    }
-                }
-                list = new_list;
-            } else {
-                // Remove first |count| occurrences from tail
-                let mut to_remove = (-count) as usize;
-                let mut new_list = VecDeque::new();
-                for elem in list.into_iter().rev() {
-                    if to_remove > 0 && elem == element {
-                        to_remove -= 1;
-                        removed_count += 1;
-                    } else {
-                        new_list.push_front(elem);
-                    }
-                }
-                list = new_list;
-            }
-
-            // Update or delete the list
-            if list.is_empty() {
-                self.storage.delete_from_db(db_index, &key)?;
-            } else {
-                self.storage
-                    .set_value(db_index, key, StoredValue::new_list(list))?;
-            }
-
-            removed_count
-        } else {
-            0
-        };
+        let element = &args[2];
+
+        // Remove the matches in place, in a single pass over the list
+        let mut removed = 0;
+        let mut emptied = false;
+        self.storage.update_value(db_index, &key, |stored| {
+            let list = stored.as_list_mut()?;
+            let matches = list
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| *e == element)
+                .map(|(i, _)| i);
+            let mut positions: Vec<usize> = match count {
+                0 => matches.collect(),
+                // The first count occurrences from the head
+                count if count > 0 => matches.take(count as usize).collect(),
+                // The first |count| occurrences from the tail
+                count => matches.rev().take(count.unsigned_abs() as usize).collect(),
+            };
+            positions.sort_unstable();
+
+            let mut positions = positions.into_iter().peekable();
+            let mut index = 0;
+            list.retain(|_| {
+                let remove = positions.next_if_eq(&index).is_some();
+                index += 1;
+                removed += remove as i64;
+                !remove
+            });
+            emptied = list.is_empty();
+            Ok(())
+        })?;
+
+        if emptied {
+            self.storage.delete_from_db(db_index, &key)?;
+        }
 
-        Ok(RespValue::Integer(removed as i64))
+        Ok(RespValue::Integer(removed))
     }
 
     /// LTRIM key start stop
@@ -480,32 +536,23 @@ impl ListCommands {
             _ => return Err(AikvError::InvalidArgument("ERR syntax error".to_string())),
         };
 
-        if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            let list = stored.as_list()?.clone();
-
-            // Find the pivot element
-            let pivot_idx = list.iter().position(|e| e == &pivot);
-
-            if let Some(idx) = pivot_idx {
-                let insert_idx = if before { idx } else { idx + 1 };
-                // VecDeque doesn't have insert, so we need to work around it
-                let mut new_list: VecDeque<Bytes> = list.iter().take(insert_idx).cloned().collect();
-                new_list.push_back(element);
-                for elem in list.iter().skip(insert_idx) {
-                    new_list.push_back(elem.clone());
-                }
-
-                let len = new_list.len();
-                self.storage
-                    .set_value(db_index, key, StoredValue::new_list(new_list))?;
-                Ok(RespValue::Integer(len as i64))
-            } else {
-                // Pivot not found
-                Ok(RespValue::Integer(-1))
+        // Insert in place; VecDeque::insert only shifts the shorter side
+        let mut len = None;
+        let updated = self.storage.update_value(db_index, &key, |stored| {
+            let list = stored.as_list_mut()?;
+            if let Some(idx) = list.iter().position(|e| e == &pivot) {
+                list.insert(if before { idx } else { idx + 1 }, element);
+                len = Some(list.len());
             }
-        } else {
+            Ok(())
+        })?;
+
+        match len {
+            Some(len) => Ok(RespValue::Integer(len as i64)),
+            // Pivot not found
+            None if updated => Ok(RespValue::Integer(-1)),
             // Key doesn't exist
-            Ok(RespValue::Integer(0))
+            None => Ok(RespValue::Integer(0)),
         }
     }
 
@@ -1,28 +1,133 @@
+pub mod acl;
+pub mod blocking;
+pub mod config;
+pub mod context;
 pub mod database;
+pub mod docs;
+pub mod geo;
 pub mod hash;
+pub mod help;
+pub mod hll;
 pub mod json;
 pub mod key;
 pub mod list;
+pub mod pubsub;
 pub mod script;
 pub mod server;
 pub mod set;
+pub mod sort;
+pub mod stream;
 pub mod string;
 pub mod zset;
 
+use self::acl::AclCommands;
+use self::blocking::BlockedClients;
+pub use self::context::ClientContext;
 use self::database::DatabaseCommands;
+use self::geo::GeoCommands;
 use self::hash::HashCommands;
+use self::help::unknown_subcommand;
+use self::hll::HllCommands;
 use self::json::JsonCommands;
 use self::key::KeyCommands;
 use self::list::ListCommands;
+use self::pubsub::{KeyspaceEvents, PubSub, PubSubCommands};
 use self::script::ScriptCommands;
 use self::server::ServerCommands;
 use self::set::SetCommands;
+use self::sort::SortCommands;
+use self::stream::StreamCommands;
 use self::string::StringCommands;
 use self::zset::ZSetCommands;
 use crate::error::{AikvError, Result};
+use crate::persistence::{AofReader, AofWriteGuard, AofWriter};
 use crate::protocol::RespValue;
-use crate::storage::StorageEngine;
+use crate::storage::{without_touching, StorageEngine};
 use bytes::Bytes;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, warn};
+
+/// Whether an LMPOP or ZMPOP popped from the left or the lowest scores
+fn mpop_takes_first(command: &str, args: &[Bytes]) -> bool {
+    let wheres = if command == "LMPOP" {
+        ["LEFT", "RIGHT"]
+    } else {
+        ["MIN", "MAX"]
+    };
+    list::parse_mpop_args(command, args, wheres).is_ok_and(|(_, first, _)| first)
+}
+
+/// Whether a command's integer reply counts the elements it changed, so 0
+/// means it did nothing
+fn is_count(command: &str) -> bool {
+    matches!(
+        command,
+        "SETNX"
+            | "EXPIRE"
+            | "PEXPIRE"
+            | "EXPIREAT"
+            | "PEXPIREAT"
+            | "PERSIST"
+            | "RENAMENX"
+            | "COPY"
+            | "MOVE"
+            | "LREM"
+            | "LINSERT"
+            | "HDEL"
+            | "HSETNX"
+            | "SADD"
+            | "SREM"
+            | "SMOVE"
+            | "SINTERSTORE"
+            | "SUNIONSTORE"
+            | "SDIFFSTORE"
+            | "ZREM"
+            | "PFADD"
+    )
+}
+
+/// The database a write command is confined to, which is what it takes the
+/// AOF write guard for and wakes blocked clients in; `None` for commands that
+/// can write to others
+fn write_scope(command: &str, db: usize) -> Option<usize> {
+    match command {
+        "FLUSHALL" | "SWAPDB" | "MOVE" | "COPY" | "EVAL" | "EVALSHA" => None,
+        _ => Some(db),
+    }
+}
+
+/// How much of an unknown command and its arguments the error echoes back
+const UNKNOWN_COMMAND_ECHO_LEN: usize = 128;
+
+/// The error for a command this server doesn't have, quoting the name and
+/// the first arguments as Redis does:
+/// `unknown command 'foo', with args beginning with: 'bar' 'baz' `
+fn unknown_command(name: &str, args: &[Bytes]) -> AikvError {
+    // Newlines would end the error line on the wire
+    let sanitize = |s: &str| s.replace(['\r', '\n'], " ");
+    let name: String = sanitize(name)
+        .chars()
+        .take(UNKNOWN_COMMAND_ECHO_LEN)
+        .collect();
+
+    let mut quoted = String::new();
+    for arg in args {
+        let room = UNKNOWN_COMMAND_ECHO_LEN.saturating_sub(quoted.len());
+        if room == 0 {
+            break;
+        }
+        let arg: String = sanitize(&String::from_utf8_lossy(arg))
+            .chars()
+            .take(room)
+            .collect();
+        quoted.push_str(&format!("'{}' ", arg));
+    }
+    AikvError::UnknownCommand(name, quoted)
+}
 
 /// Command executor with database context
 pub struct CommandExecutor {
@@ -36,6 +141,18 @@ pub struct CommandExecutor {
     hash_commands: HashCommands,
     set_commands: SetCommands,
     zset_commands: ZSetCommands,
+    geo_commands: GeoCommands,
+    stream_commands: StreamCommands,
+    hll_commands: HllCommands,
+    sort_commands: SortCommands,
+    pubsub_commands: PubSubCommands,
+    acl_commands: AclCommands,
+    /// Storage the command handlers share, for AOF rewrites
+    storage: StorageEngine,
+    /// Append-only log that successful write commands are recorded to
+    aof: Option<AofWriter>,
+    /// Clients blocked by BLMPOP and BZMPOP, woken by successful writes
+    blocked_clients: Arc<BlockedClients>,
     #[cfg(feature = "cluster")]
     cluster_commands: Option<crate::cluster::ClusterCommands>,
 }
@@ -55,7 +172,7 @@ impl CommandExecutor {
         Self {
             string_commands: StringCommands::new(storage.clone()),
             json_commands: JsonCommands::new(storage.clone()),
-            database_commands: DatabaseCommands::new(storage.clone()),
+            database_commands: DatabaseCommands::new(storage.clone(), storage.db_count()),
             key_commands: KeyCommands::new(storage.clone()),
             server_commands: ServerCommands::with_storage_port_and_cluster(
                 storage.clone(),
@@ -66,18 +183,68 @@ impl CommandExecutor {
             list_commands: ListCommands::new(storage.clone()),
             hash_commands: HashCommands::new(storage.clone()),
             set_commands: SetCommands::new(storage.clone()),
-            zset_commands: ZSetCommands::new(storage),
+            zset_commands: ZSetCommands::new(storage.clone()),
+            geo_commands: GeoCommands::new(storage.clone()),
+            stream_commands: StreamCommands::new(storage.clone()),
+            hll_commands: HllCommands::new(storage.clone()),
+            sort_commands: SortCommands::new(storage.clone()),
+            pubsub_commands: PubSubCommands::new(),
+            acl_commands: AclCommands::new(),
+            storage,
+            aof: None,
+            blocked_clients: Arc::new(BlockedClients::new()),
             #[cfg(feature = "cluster")]
             cluster_commands: None, // Will be set later when cluster is initialized
         }
     }
 
+    /// Log every successful write command executed from now on to `aof`
+    pub fn set_aof_writer(&mut self, aof: AofWriter) {
+        self.server_commands.set_aof_writer(aof.clone());
+        self.aof = Some(aof);
+    }
+
+    /// Replay an AOF file through this executor, returning the number of
+    /// commands applied.
+    ///
+    /// Commands that fail are skipped with a warning. A truncated or malformed
+    /// tail (e.g. from a crash mid-write) stops the replay with a warning, keeping
+    /// everything read up to that point.
+    pub fn replay_aof(&self, path: impl AsRef<Path>) -> Result<usize> {
+        let file = File::open(path.as_ref())
+            .map_err(|e| AikvError::Persistence(format!("Failed to open AOF file: {}", e)))?;
+        let mut reader = AofReader::new(BufReader::new(file));
+
+        let mut client = ClientContext::new(0);
+        let mut applied = 0;
+        loop {
+            let args = match reader.read_args() {
+                Ok(Some(args)) if !args.is_empty() => args,
+                Ok(Some(_)) => continue,
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("Stopping AOF replay after {} commands: {}", applied, e);
+                    break;
+                }
+            };
+
+            let command = String::from_utf8_lossy(&args[0]).to_string();
+            match self.execute(&command, &args[1..], &mut client) {
+                Ok(_) => applied += 1,
+                Err(e) => warn!("Skipping AOF command {}: {}", command, e),
+            }
+        }
+
+        Ok(applied)
+    }
+
     /// Set cluster commands after initialization.
     ///
     /// This allows setting the cluster commands after the CommandExecutor is created,
     /// once the cluster node components are fully initialized.
     #[cfg(feature = "cluster")]
-    pub fn set_cluster_commands(&mut self, cluster_commands: crate::cluster::ClusterCommands) {
+    pub fn set_cluster_commands(&mut self, mut cluster_commands: crate::cluster::ClusterCommands) {
+        cluster_commands.set_storage(self.storage.clone());
         self.cluster_commands = Some(cluster_commands);
     }
 
@@ -88,38 +255,52 @@ impl CommandExecutor {
     /// - Cluster is not initialized
     /// - The key belongs to this node
     ///
-    /// Returns `Err(AikvError::Moved(slot, addr))` if the key belongs to another node.
+    /// Returns `Err(AikvError::Moved(slot, addr))` if the key belongs to another node,
+    /// or `Err(AikvError::Ask(slot, addr))` if it has already been migrated away.
     #[cfg(feature = "cluster")]
-    fn check_key_routing(&self, key: &[u8]) -> Result<()> {
-        if let Some(ref cluster_commands) = self.cluster_commands {
-            cluster_commands.check_key_slot(key)
-        } else {
-            // Cluster not initialized, allow all operations locally
-            Ok(())
-        }
+    fn check_key_routing(&self, key: &[u8], client: &ClientContext) -> Result<()> {
+        self.check_keys_routing(&[key], client)
     }
 
     /// Check if multiple keys belong to this node in cluster mode.
     ///
     /// For multi-key commands (like MGET, MSET), all keys must be in the same slot.
     #[cfg(feature = "cluster")]
-    fn check_keys_routing(&self, keys: &[&[u8]]) -> Result<()> {
+    fn check_keys_routing(&self, keys: &[&[u8]], client: &ClientContext) -> Result<()> {
         if let Some(ref cluster_commands) = self.cluster_commands {
-            cluster_commands.check_keys_slot(keys)
+            cluster_commands.route_keys(keys, client.db, client.asking, client.readonly)
         } else {
+            // Cluster not initialized, allow all operations locally
             Ok(())
         }
     }
 
+    /// Check that the KEYS a script declares belong to this node in cluster mode.
+    ///
+    /// `args` are those of EVAL/EVALSHA: script or SHA1, numkeys, then the keys.
+    fn check_script_keys_routing(&self, args: &[Bytes], client: &ClientContext) -> Result<()> {
+        let numkeys = args
+            .get(1)
+            .and_then(|n| String::from_utf8_lossy(n).parse::<usize>().ok())
+            .unwrap_or(0);
+        let keys: Vec<&[u8]> = args
+            .iter()
+            .skip(2)
+            .take(numkeys)
+            .map(|k| k.as_ref())
+            .collect();
+        self.check_keys_routing(&keys, client)
+    }
+
     /// Placeholder for non-cluster builds
     #[cfg(not(feature = "cluster"))]
-    fn check_key_routing(&self, _key: &[u8]) -> Result<()> {
+    fn check_key_routing(&self, _key: &[u8], _client: &ClientContext) -> Result<()> {
         Ok(())
     }
 
     /// Placeholder for non-cluster builds
     #[cfg(not(feature = "cluster"))]
-    fn check_keys_routing(&self, _keys: &[&[u8]]) -> Result<()> {
+    fn check_keys_routing(&self, _keys: &[&[u8]], _client: &ClientContext) -> Result<()> {
         Ok(())
     }
 
@@ -127,299 +308,1010 @@ impl CommandExecutor {
         &self,
         command: &str,
         args: &[Bytes],
-        current_db: &mut usize,
-        client_id: usize,
+        client: &mut ClientContext,
+    ) -> Result<RespValue> {
+        let name = command;
+        let command = command.to_uppercase();
+
+        self.check_command(&command, args, client)?;
+
+        // Redis echoes an unknown command as it was sent, with its first arguments
+        match self.run(&command, args, client) {
+            Err(AikvError::UnknownCommand(..)) => Err(unknown_command(name, args)),
+            result => result,
+        }
+    }
+
+    /// Run a command that passed `check_command`, given its upper-case name
+    fn run(&self, command: &str, args: &[Bytes], client: &mut ClientContext) -> Result<RespValue> {
+        let db = client.db;
+        // Keep an AOF rewrite from snapshotting, and other writes to the same
+        // database from running, between this write and its log entry
+        let aof_guard = match &self.aof {
+            Some(aof) if server::is_write_command(command) => {
+                Some(aof.write_guard(write_scope(command, db)))
+            }
+            _ => None,
+        };
+
+        let notify = self.pubsub().keyspace_events() != KeyspaceEvents::none()
+            && server::is_write_command(command);
+        // DEL's reply doesn't say which of the keys existed
+        let deleted = if notify && command == "DEL" {
+            args.iter()
+                .filter(|key| {
+                    self.storage
+                        .exists_in_db(db, &String::from_utf8_lossy(key))
+                        .unwrap_or(false)
+                })
+                .cloned()
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        // READONLY only lets read-only commands use slots this node
+        // replicates; anything else is routed as if it weren't set
+        let masks_readonly = client.readonly
+            && !server::lookup_command(command).is_some_and(|info| info.has_flag("readonly"));
+        if masks_readonly {
+            client.readonly = false;
+        }
+        let result = if client.no_touch {
+            without_touching(|| self.dispatch(command, args, client))
+        } else {
+            self.dispatch(command, args, client)
+        };
+        // READWRITE clears the flag for good
+        if masks_readonly && command != "READWRITE" {
+            client.readonly = true;
+        }
+        // ASKING only covers the command right after it
+        if command != "ASKING" {
+            client.asking = false;
+        }
+
+        if let (true, Ok(reply)) = (notify, &result) {
+            for (class, event, key, db) in self.keyspace_events(command, args, reply, db, deleted) {
+                self.pubsub().notify_keyspace_event(class, event, &key, db);
+            }
+        }
+
+        if let (Some(aof_guard), Ok(reply)) = (&aof_guard, &result) {
+            self.log_write(aof_guard, command, args, reply, db);
+        }
+        drop(aof_guard);
+
+        // A null reply means nothing was written, and waking the clients
+        // blocked on an empty key would have them wake each other for good
+        let wrote = result.as_ref().is_ok_and(|reply| {
+            !matches!(
+                reply,
+                RespValue::Null | RespValue::BulkString(None) | RespValue::Array(None)
+            )
+        });
+        if wrote && server::is_write_command(command) {
+            match write_scope(command, db) {
+                Some(db) => {
+                    let keys = server::lookup_command(command)
+                        .map(|info| info.keys(args))
+                        .unwrap_or_default();
+                    self.blocked_clients.wake(db, &keys);
+                }
+                None => self.blocked_clients.wake_all(),
+            }
+        }
+
+        result
+    }
+
+    /// Validate arity and the user's ACL up front for every command in the command table
+    fn check_command(&self, command: &str, args: &[Bytes], client: &ClientContext) -> Result<()> {
+        if let Some(info) = server::lookup_command(command) {
+            if !info.accepts_arg_count(args.len() + 1) {
+                return Err(AikvError::WrongArgCount(command.to_string()));
+            }
+            self.acl_commands.check_permission(client, command, args)?;
+            if !info.has_flag("loading") && self.server_commands.is_loading() {
+                return Err(AikvError::Loading);
+            }
+        }
+        Ok(())
+    }
+
+    /// Append the AOF entries for a write that succeeded
+    fn log_write(
+        &self,
+        aof_guard: &AofWriteGuard<'_>,
+        command: &str,
+        args: &[Bytes],
+        reply: &RespValue,
+        db: usize,
+    ) {
+        for (command, args) in self.aof_entries(command, args, reply) {
+            // The write already happened, so a logging failure can't fail the command
+            if let Err(e) = aof_guard.append(db, &command, &args) {
+                error!("Failed to append {} to AOF: {}", command, e);
+            }
+        }
+    }
+
+    /// MIGRATE from a client connection, with the transfer to the target run
+    /// on a blocking thread so a slow target doesn't hold up a runtime worker
+    pub async fn execute_migrate(
+        &self,
+        args: &[Bytes],
+        client: &mut ClientContext,
+    ) -> Result<RespValue> {
+        self.check_command("MIGRATE", args, client)?;
+        // ASKING only covers the command right after it
+        client.asking = false;
+
+        let db = client.db;
+        let Some(migration) = self.key_commands.prepare_migrate(args, db)? else {
+            return Ok(RespValue::simple_string("NOKEY"));
+        };
+        let (migration, outcome) = tokio::task::spawn_blocking(move || {
+            let outcome = migration.transfer();
+            (migration, outcome)
+        })
+        .await
+        .map_err(|e| AikvError::Internal(format!("MIGRATE transfer failed: {}", e)))?;
+
+        let aof_guard = self.aof.as_ref().map(|aof| aof.write_guard(Some(db)));
+        let result = self.finish_migrate(aof_guard.as_ref(), &migration, outcome, db);
+        if let (Some(aof_guard), Ok(reply)) = (&aof_guard, &result) {
+            self.log_write(aof_guard, "MIGRATE", args, reply, db);
+        }
+        result
+    }
+
+    /// BLMPOP timeout numkeys key \[key ...\] LEFT|RIGHT \[COUNT count\], and
+    /// BZMPOP alike with MIN|MAX
+    ///
+    /// Runs LMPOP or ZMPOP, and while every key is empty waits for a write to
+    /// one of them before trying again, until `timeout` seconds pass (0 waits
+    /// for good). Replies null on timeout.
+    pub async fn execute_blocking_pop(
+        &self,
+        command: &str,
+        args: &[Bytes],
+        client: &mut ClientContext,
+    ) -> Result<RespValue> {
+        let command = command.to_uppercase();
+        self.check_command(&command, args, client)?;
+        let timeout = blocking::parse_timeout(&args[0])?;
+        let deadline = timeout.map(|timeout| tokio::time::Instant::now() + timeout);
+
+        let pop = &command[1..];
+        let pop_args = &args[1..];
+        let keys: Vec<Bytes> = server::lookup_command(pop)
+            .map(|info| info.keys(pop_args).into_iter().cloned().collect())
+            .unwrap_or_default();
+        // Blocked before the first try, so a write right after it isn't missed
+        let blocked = self.blocked_clients.block(client.db, &keys);
+        loop {
+            let reply = self.run(pop, pop_args, client)?;
+            if reply != RespValue::null_array() {
+                return Ok(reply);
+            }
+            match deadline {
+                Some(deadline) => {
+                    if tokio::time::timeout_at(deadline, blocked.woken())
+                        .await
+                        .is_err()
+                    {
+                        return Ok(RespValue::null_array());
+                    }
+                }
+                None => blocked.woken().await,
+            }
+        }
+    }
+
+    /// MIGRATE run in place, which blocks the calling thread for the transfer
+    fn migrate(&self, args: &[Bytes], db: usize) -> Result<RespValue> {
+        match self.key_commands.prepare_migrate(args, db)? {
+            Some(migration) => {
+                let outcome = migration.transfer();
+                self.finish_migrate(None, &migration, outcome, db)
+            }
+            None => Ok(RespValue::simple_string("NOKEY")),
+        }
+    }
+
+    /// Remove the keys a MIGRATE moved to its target
+    ///
+    /// A successful MIGRATE is logged as a DEL of its keys by `aof_entries`,
+    /// but an error reply isn't logged at all, so when the transfer or the
+    /// removal fails partway the keys already removed are logged here before
+    /// the error is returned, through `aof_guard` if the caller holds one.
+    fn finish_migrate(
+        &self,
+        aof_guard: Option<&AofWriteGuard<'_>>,
+        migration: &key::Migration,
+        (accepted, result): (Vec<Bytes>, Result<()>),
+        db: usize,
+    ) -> Result<RespValue> {
+        let (removed, removal) = self.key_commands.finish_migrate(migration, accepted, db);
+        if let Err(e) = result.and(removal) {
+            if let (Some(aof), false) = (&self.aof, removed.is_empty()) {
+                let logged = match aof_guard {
+                    Some(aof_guard) => aof_guard.append(db, "DEL", &removed),
+                    None => aof.append(db, "DEL", &removed),
+                };
+                if let Err(log_error) = logged {
+                    error!("Failed to append DEL to AOF: {}", log_error);
+                }
+            }
+            return Err(e);
+        }
+        Ok(RespValue::ok())
+    }
+
+    /// BGREWRITEAOF - Compact the AOF from a snapshot of the dataset in the background
+    fn bgrewriteaof(&self) -> Result<RespValue> {
+        let aof = self
+            .aof
+            .as_ref()
+            .ok_or_else(|| AikvError::Persistence("AOF is not enabled".to_string()))?;
+        aof.start_rewrite(&self.storage)?;
+        Ok(RespValue::simple_string(
+            "Background append only file rewriting started",
+        ))
+    }
+
+    /// Rewrite a successful write command into the AOF entries that reproduce it.
+    ///
+    /// Relative expiry times are turned into absolute PEXPIREAT timestamps so a
+    /// replay doesn't extend TTLs, EVALSHA is logged as EVAL since the
+    /// script cache isn't persisted, SPOP as an SREM of the members it
+    /// happened to pick, MIGRATE as a DEL of the keys it moved away and
+    /// XADD with the ID it gave the entry.
+    fn aof_entries(
+        &self,
+        command: &str,
+        args: &[Bytes],
+        reply: &RespValue,
+    ) -> Vec<(String, Vec<Bytes>)> {
+        // BLMPOP and BZMPOP only get here when they didn't block, as in
+        // scripts, and are logged as the LMPOP or ZMPOP they ran
+        if matches!(command, "BLMPOP" | "BZMPOP") {
+            return self.aof_entries(&command[1..], args.get(1..).unwrap_or_default(), reply);
+        }
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        // Absolute expiry in ms from a relative or absolute time argument
+        let expire_at = |unit_ms: i64, absolute: bool, value: &Bytes| -> Option<Bytes> {
+            let value = String::from_utf8_lossy(value).parse::<i64>().ok()?;
+            let ms = value.checked_mul(unit_ms)?;
+            let at = if absolute {
+                ms
+            } else {
+                now_ms.saturating_add(ms)
+            };
+            Some(Bytes::from(at.to_string()))
+        };
+        let pexpireat = |key: &Bytes, at: Bytes| ("PEXPIREAT".to_string(), vec![key.clone(), at]);
+        let unchanged = || vec![(command.to_string(), args.to_vec())];
+        // Conditional writes (SET NX, GETEX on a missing key) reply null when nothing happened
+        let applied = !matches!(reply, RespValue::Null | RespValue::BulkString(None));
+
+        match command {
+            "EXPIRE" | "PEXPIRE" | "EXPIREAT" => {
+                let unit_ms = if command == "PEXPIRE" { 1 } else { 1000 };
+                match expire_at(unit_ms, command == "EXPIREAT", &args[1]) {
+                    Some(at) => {
+                        let mut entry = pexpireat(&args[0], at);
+                        entry.1.extend_from_slice(&args[2..]);
+                        vec![entry]
+                    }
+                    None => unchanged(),
+                }
+            }
+            "SETEX" | "PSETEX" => {
+                let unit_ms = if command == "SETEX" { 1000 } else { 1 };
+                match expire_at(unit_ms, false, &args[1]) {
+                    Some(at) => vec![
+                        ("SET".to_string(), vec![args[0].clone(), args[2].clone()]),
+                        pexpireat(&args[0], at),
+                    ],
+                    None => unchanged(),
+                }
+            }
+            "SET" => {
+                let mut set_args = args[..2].to_vec();
+                let mut expiry = None;
+                let (mut nx, mut xx, mut get) = (false, false, false);
+                let mut i = 2;
+                while i < args.len() {
+                    let option = String::from_utf8_lossy(&args[i]).to_uppercase();
+                    match option.as_str() {
+                        "EX" | "PX" if i + 1 < args.len() => {
+                            let unit_ms = if option == "EX" { 1000 } else { 1 };
+                            expiry = expire_at(unit_ms, false, &args[i + 1]);
+                            i += 1;
+                        }
+                        _ => {
+                            nx |= option == "NX";
+                            xx |= option == "XX";
+                            get |= option == "GET";
+                            set_args.push(args[i].clone())
+                        }
+                    }
+                    i += 1;
+                }
+                // With GET the reply is the old value, so whether the write
+                // happened follows from the condition and whether the key existed
+                let existed = !matches!(reply, RespValue::Null | RespValue::BulkString(None));
+                let applied = match (get, nx, xx) {
+                    (false, _, _) => applied,
+                    (true, true, _) => !existed,
+                    (true, _, true) => existed,
+                    (true, false, false) => true,
+                };
+                match expiry {
+                    // A conditional SET that didn't happen mustn't set a TTL on replay
+                    Some(at) if applied => {
+                        vec![("SET".to_string(), set_args), pexpireat(&args[0], at)]
+                    }
+                    Some(_) => vec![("SET".to_string(), set_args)],
+                    None => unchanged(),
+                }
+            }
+            "GETEX" => {
+                let option = args
+                    .get(1)
+                    .map(|o| String::from_utf8_lossy(o).to_uppercase());
+                let at = match (option.as_deref(), args.get(2)) {
+                    (Some("EX"), Some(v)) => expire_at(1000, false, v),
+                    (Some("PX"), Some(v)) => expire_at(1, false, v),
+                    (Some("EXAT"), Some(v)) => expire_at(1000, true, v),
+                    (Some("PXAT"), Some(v)) => expire_at(1, true, v),
+                    (Some("PERSIST"), _) => {
+                        return vec![("PERSIST".to_string(), vec![args[0].clone()])]
+                    }
+                    // Plain GETEX only reads
+                    _ => return Vec::new(),
+                };
+                match at {
+                    Some(at) if applied => vec![pexpireat(&args[0], at)],
+                    _ => Vec::new(),
+                }
+            }
+            "SPOP" => {
+                let members: Vec<Bytes> = match reply {
+                    RespValue::BulkString(Some(member)) => vec![member.clone()],
+                    RespValue::Array(Some(items)) => items
+                        .iter()
+                        .filter_map(|item| match item {
+                            RespValue::BulkString(Some(member)) => Some(member.clone()),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                if members.is_empty() {
+                    return Vec::new();
+                }
+                let mut srem_args = vec![args[0].clone()];
+                srem_args.extend(members);
+                vec![("SREM".to_string(), srem_args)]
+            }
+            // Logged as a pop from the key it happened to pick
+            "LMPOP" | "ZMPOP" => {
+                let RespValue::Array(Some(items)) = reply else {
+                    return Vec::new();
+                };
+                let (Some(RespValue::BulkString(Some(key))), Some(RespValue::Array(Some(popped)))) =
+                    (items.first(), items.get(1))
+                else {
+                    return Vec::new();
+                };
+                let pop = match (command, mpop_takes_first(command, args)) {
+                    ("LMPOP", true) => "LPOP",
+                    ("LMPOP", false) => "RPOP",
+                    (_, true) => "ZPOPMIN",
+                    (_, false) => "ZPOPMAX",
+                };
+                vec![(
+                    pop.to_string(),
+                    vec![key.clone(), Bytes::from(popped.len().to_string())],
+                )]
+            }
+            "MIGRATE" => {
+                let copy = args[5..]
+                    .iter()
+                    .any(|arg| arg.eq_ignore_ascii_case(b"COPY"));
+                if copy || *reply != RespValue::ok() {
+                    return Vec::new();
+                }
+                let keys = server::lookup_command(command)
+                    .map(|info| info.keys(args).into_iter().cloned().collect())
+                    .unwrap_or_default();
+                vec![("DEL".to_string(), keys)]
+            }
+            "XADD" => match reply {
+                RespValue::BulkString(Some(id)) => {
+                    let mut xadd_args = args.to_vec();
+                    xadd_args[1] = id.clone();
+                    vec![(command.to_string(), xadd_args)]
+                }
+                _ => unchanged(),
+            },
+            "EVALSHA" => {
+                let sha1 = String::from_utf8_lossy(&args[0]);
+                match self.script_commands.cached_script(&sha1) {
+                    Some(script) => {
+                        let mut eval_args = vec![Bytes::from(script)];
+                        eval_args.extend_from_slice(&args[1..]);
+                        vec![("EVAL".to_string(), eval_args)]
+                    }
+                    None => unchanged(),
+                }
+            }
+            _ => unchanged(),
+        }
+    }
+
+    /// The keyspace events a successful write command caused, as (class,
+    /// event, key, database)
+    ///
+    /// `deleted` are the keys a DEL found. Commands that may empty a
+    /// collection also report a `del` when its key is gone afterwards.
+    fn keyspace_events(
+        &self,
+        command: &str,
+        args: &[Bytes],
+        reply: &RespValue,
+        db: usize,
+        deleted: Vec<Bytes>,
+    ) -> Vec<(KeyspaceEvents, &'static str, Bytes, usize)> {
+        use KeyspaceEvents as Class;
+
+        if matches!(command, "BLMPOP" | "BZMPOP") {
+            return self.keyspace_events(
+                &command[1..],
+                args.get(1..).unwrap_or_default(),
+                reply,
+                db,
+                deleted,
+            );
+        }
+        let key = |i: usize| args.get(i).cloned().unwrap_or_default();
+        let event = |class: Class, name: &'static str, i: usize| (class, name, key(i), db);
+        let changed = match reply {
+            RespValue::Null | RespValue::BulkString(None) | RespValue::Array(None) => false,
+            RespValue::Array(Some(items)) => !items.is_empty(),
+            RespValue::Integer(n) => *n > 0 || !is_count(command),
+            _ => true,
+        };
+        let has_option = |from: usize, options: &[&str]| {
+            args.iter().skip(from).any(|arg| {
+                let arg = String::from_utf8_lossy(arg).to_uppercase();
+                options.contains(&arg.as_str())
+            })
+        };
+        let emptied = |i: usize| {
+            !self
+                .storage
+                .exists_in_db(db, &String::from_utf8_lossy(&key(i)))
+                .unwrap_or(true)
+        };
+
+        if !changed {
+            return Vec::new();
+        }
+        let mut events = match command {
+            "SET" | "SETNX" | "SETEX" | "PSETEX" => {
+                let mut events = vec![event(Class::STRING, "set", 0)];
+                let expires = command == "SETEX"
+                    || command == "PSETEX"
+                    || (command == "SET" && has_option(2, &["EX", "PX", "EXAT", "PXAT"]));
+                if expires {
+                    events.push(event(Class::GENERIC, "expire", 0));
+                }
+                events
+            }
+            "MSET" => (0..args.len())
+                .step_by(2)
+                .map(|i| event(Class::STRING, "set", i))
+                .collect(),
+            "APPEND" => vec![event(Class::STRING, "append", 0)],
+            "INCR" | "DECR" | "INCRBY" | "DECRBY" => vec![event(Class::STRING, "incrby", 0)],
+            "INCRBYFLOAT" => vec![event(Class::STRING, "incrbyfloat", 0)],
+            "SETRANGE" => vec![event(Class::STRING, "setrange", 0)],
+            "SETBIT" => vec![event(Class::STRING, "setbit", 0)],
+            "GETDEL" => vec![event(Class::GENERIC, "del", 0)],
+            "GETEX" if has_option(1, &["PERSIST"]) => vec![event(Class::GENERIC, "persist", 0)],
+            "GETEX" if args.len() > 1 => vec![event(Class::GENERIC, "expire", 0)],
+            "DEL" => deleted
+                .into_iter()
+                .map(|key| (Class::GENERIC, "del", key, db))
+                .collect(),
+            // An expiry in the past deletes the key there and then
+            "EXPIRE" | "PEXPIRE" | "EXPIREAT" | "PEXPIREAT" if emptied(0) => {
+                vec![event(Class::GENERIC, "del", 0)]
+            }
+            "EXPIRE" | "PEXPIRE" | "EXPIREAT" | "PEXPIREAT" => {
+                vec![event(Class::GENERIC, "expire", 0)]
+            }
+            "PERSIST" => vec![event(Class::GENERIC, "persist", 0)],
+            "RENAME" | "RENAMENX" => vec![
+                event(Class::GENERIC, "rename_from", 0),
+                event(Class::GENERIC, "rename_to", 1),
+            ],
+            "COPY" => vec![event(Class::GENERIC, "copy_to", 1)],
+            "MOVE" => {
+                let to = String::from_utf8_lossy(&key(1)).parse().unwrap_or(db);
+                vec![
+                    event(Class::GENERIC, "move_from", 0),
+                    (Class::GENERIC, "move_to", key(0), to),
+                ]
+            }
+            "RESTORE" => vec![event(Class::GENERIC, "restore", 0)],
+            "SORT" => match args
+                .iter()
+                .position(|arg| arg.eq_ignore_ascii_case(b"STORE"))
+            {
+                Some(i) => vec![event(Class::LIST, "sortstore", i + 1)],
+                None => Vec::new(),
+            },
+            "LPUSH" => vec![event(Class::LIST, "lpush", 0)],
+            "RPUSH" => vec![event(Class::LIST, "rpush", 0)],
+            "LPOP" => vec![event(Class::LIST, "lpop", 0)],
+            "RPOP" => vec![event(Class::LIST, "rpop", 0)],
+            "LSET" => vec![event(Class::LIST, "lset", 0)],
+            "LTRIM" => vec![event(Class::LIST, "ltrim", 0)],
+            "LREM" => vec![event(Class::LIST, "lrem", 0)],
+            "LINSERT" => vec![event(Class::LIST, "linsert", 0)],
+            "LMOVE" => {
+                let side = |i: usize, left: &'static str, right: &'static str| {
+                    if key(i).eq_ignore_ascii_case(b"LEFT") {
+                        left
+                    } else {
+                        right
+                    }
+                };
+                vec![
+                    event(Class::LIST, side(2, "lpop", "rpop"), 0),
+                    event(Class::LIST, side(3, "lpush", "rpush"), 1),
+                ]
+            }
+            "HSET" | "HMSET" | "HSETNX" => vec![event(Class::HASH, "hset", 0)],
+            "HDEL" => vec![event(Class::HASH, "hdel", 0)],
+            "HINCRBY" => vec![event(Class::HASH, "hincrby", 0)],
+            "HINCRBYFLOAT" => vec![event(Class::HASH, "hincrbyfloat", 0)],
+            "SADD" => vec![event(Class::SET, "sadd", 0)],
+            "SREM" => vec![event(Class::SET, "srem", 0)],
+            "SPOP" => vec![event(Class::SET, "spop", 0)],
+            "SMOVE" => vec![event(Class::SET, "srem", 0), event(Class::SET, "sadd", 1)],
+            "SINTERSTORE" => vec![event(Class::SET, "sinterstore", 0)],
+            "SUNIONSTORE" => vec![event(Class::SET, "sunionstore", 0)],
+            "SDIFFSTORE" => vec![event(Class::SET, "sdiffstore", 0)],
+            "ZADD" if has_option(1, &["INCR"]) => vec![event(Class::ZSET, "zincr", 0)],
+            "ZADD" | "GEOADD" => vec![event(Class::ZSET, "zadd", 0)],
+            "ZINCRBY" => vec![event(Class::ZSET, "zincr", 0)],
+            "ZREM" => vec![event(Class::ZSET, "zrem", 0)],
+            "ZPOPMIN" => vec![event(Class::ZSET, "zpopmin", 0)],
+            "ZPOPMAX" => vec![event(Class::ZSET, "zpopmax", 0)],
+            // The reply names the key popped from
+            "LMPOP" | "ZMPOP" => match reply {
+                RespValue::Array(Some(items)) => match items.first() {
+                    Some(RespValue::BulkString(Some(popped))) => {
+                        let (class, name) = match (command, mpop_takes_first(command, args)) {
+                            ("LMPOP", true) => (Class::LIST, "lpop"),
+                            ("LMPOP", false) => (Class::LIST, "rpop"),
+                            (_, true) => (Class::ZSET, "zpopmin"),
+                            (_, false) => (Class::ZSET, "zpopmax"),
+                        };
+                        let mut events = vec![(class, name, popped.clone(), db)];
+                        let exists = self
+                            .storage
+                            .exists_in_db(db, &String::from_utf8_lossy(popped))
+                            .unwrap_or(true);
+                        if !exists {
+                            events.push((Class::GENERIC, "del", popped.clone(), db));
+                        }
+                        events
+                    }
+                    _ => Vec::new(),
+                },
+                _ => Vec::new(),
+            },
+            "PFADD" | "PFMERGE" => vec![event(Class::STRING, "pfadd", 0)],
+            "XADD" => vec![event(Class::STREAM, "xadd", 0)],
+            _ => Vec::new(),
+        };
+
+        let may_empty = matches!(
+            command,
+            "LPOP"
+                | "RPOP"
+                | "LTRIM"
+                | "LREM"
+                | "LMOVE"
+                | "HDEL"
+                | "SREM"
+                | "SPOP"
+                | "SMOVE"
+                | "ZREM"
+                | "ZPOPMIN"
+                | "ZPOPMAX"
+        );
+        if may_empty && emptied(0) {
+            events.push(event(Class::GENERIC, "del", 0));
+        }
+        events
+    }
+
+    fn dispatch(
+        &self,
+        command: &str,
+        args: &[Bytes],
+        client: &mut ClientContext,
     ) -> Result<RespValue> {
-        match command.to_uppercase().as_str() {
+        match command {
             // String commands - single key operations
             "GET" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.get(args, *current_db)
+                self.string_commands.get(args, client.db)
             }
             "SET" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.set(args, *current_db)
+                self.string_commands.set(args, client.db)
             }
             "DEL" => {
                 // DEL can take multiple keys, check all of them
                 if !args.is_empty() {
                     let keys: Vec<&[u8]> = args.iter().map(|b| b.as_ref()).collect();
-                    self.check_keys_routing(&keys)?;
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.string_commands.del(args, *current_db)
+                self.string_commands.del(args, client.db)
             }
             "EXISTS" => {
                 // EXISTS can take multiple keys
                 if !args.is_empty() {
                     let keys: Vec<&[u8]> = args.iter().map(|b| b.as_ref()).collect();
-                    self.check_keys_routing(&keys)?;
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.string_commands.exists(args, *current_db)
+                self.string_commands.exists(args, client.db)
             }
             "MGET" => {
                 // MGET takes multiple keys, all must be in the same slot
                 if !args.is_empty() {
                     let keys: Vec<&[u8]> = args.iter().map(|b| b.as_ref()).collect();
-                    self.check_keys_routing(&keys)?;
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.string_commands.mget(args, *current_db)
+                self.string_commands.mget(args, client.db)
             }
             "MSET" => {
                 // MSET takes key-value pairs, check all keys (every other arg starting at 0)
                 if args.len() >= 2 {
                     let keys: Vec<&[u8]> = args.iter().step_by(2).map(|b| b.as_ref()).collect();
-                    self.check_keys_routing(&keys)?;
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.string_commands.mset(args, *current_db)
+                self.string_commands.mset(args, client.db)
             }
             "STRLEN" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.strlen(args, *current_db)
+                self.string_commands.strlen(args, client.db)
             }
             "APPEND" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.append(args, *current_db)
+                self.string_commands.append(args, client.db)
             }
             "INCR" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.incr(args, *current_db)
+                self.string_commands.incr(args, client.db)
             }
             "DECR" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.decr(args, *current_db)
+                self.string_commands.decr(args, client.db)
             }
             "INCRBY" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.incrby(args, *current_db)
+                self.string_commands.incrby(args, client.db)
             }
             "DECRBY" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.decrby(args, *current_db)
+                self.string_commands.decrby(args, client.db)
             }
             "INCRBYFLOAT" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.incrbyfloat(args, *current_db)
+                self.string_commands.incrbyfloat(args, client.db)
             }
             "GETRANGE" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.getrange(args, *current_db)
+                self.string_commands.getrange(args, client.db)
             }
             "SETRANGE" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.setrange(args, *current_db)
+                self.string_commands.setrange(args, client.db)
             }
             "GETEX" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.getex(args, *current_db)
+                self.string_commands.getex(args, client.db)
             }
             "GETDEL" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.getdel(args, *current_db)
+                self.string_commands.getdel(args, client.db)
             }
             "SETNX" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.setnx(args, *current_db)
+                self.string_commands.setnx(args, client.db)
             }
             "SETEX" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.setex(args, *current_db)
+                self.string_commands.setex(args, client.db)
             }
             "PSETEX" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.string_commands.psetex(args, *current_db)
+                self.string_commands.psetex(args, client.db)
             }
             "SETBIT" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.string_commands.setbit(args, client.db)
+            }
+            "LCS" => {
+                // Both strings must be in the same slot
+                if args.len() >= 2 {
+                    let keys: Vec<&[u8]> = vec![args[0].as_ref(), args[1].as_ref()];
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.string_commands.setbit(args, *current_db)
+                self.string_commands.lcs(args, client.db)
             }
 
             // JSON commands - single key operations
             "JSON.GET" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.json_commands.json_get(args, *current_db)
+                self.json_commands.json_get(args, client.db)
             }
             "JSON.SET" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.json_commands.json_set(args, *current_db)
+                self.json_commands.json_set(args, client.db)
             }
             "JSON.DEL" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.json_commands.json_del(args, *current_db)
+                self.json_commands.json_del(args, client.db)
             }
             "JSON.TYPE" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.json_commands.json_type(args, *current_db)
+                self.json_commands.json_type(args, client.db)
             }
             "JSON.STRLEN" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.json_commands.json_strlen(args, *current_db)
+                self.json_commands.json_strlen(args, client.db)
             }
             "JSON.ARRLEN" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.json_commands.json_arrlen(args, *current_db)
+                self.json_commands.json_arrlen(args, client.db)
             }
             "JSON.OBJLEN" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.json_commands.json_objlen(args, client.db)
+            }
+            "JSON.ARRAPPEND" => {
+                if !args.is_empty() {
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.json_commands.json_arrappend(args, client.db)
+            }
+            "JSON.ARRINSERT" => {
+                if !args.is_empty() {
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.json_commands.json_arrinsert(args, client.db)
+            }
+            "JSON.ARRPOP" => {
+                if !args.is_empty() {
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.json_commands.json_arrpop(args, client.db)
+            }
+            "JSON.ARRTRIM" => {
+                if !args.is_empty() {
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.json_commands.json_arrtrim(args, client.db)
+            }
+            "JSON.NUMINCRBY" => {
+                if !args.is_empty() {
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.json_commands.json_numincrby(args, client.db)
+            }
+            "JSON.NUMMULTBY" => {
+                if !args.is_empty() {
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.json_commands.json_nummultby(args, client.db)
+            }
+            "JSON.TOGGLE" => {
+                if !args.is_empty() {
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.json_commands.json_toggle(args, client.db)
+            }
+            "JSON.MGET" => {
+                // JSON.MGET takes multiple keys followed by a path
+                if args.len() >= 2 {
+                    let keys: Vec<&[u8]> =
+                        args[..args.len() - 1].iter().map(|b| b.as_ref()).collect();
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.json_commands.json_objlen(args, *current_db)
+                self.json_commands.json_mget(args, client.db)
             }
 
             // Database commands - these are node-local, no routing needed
-            "SELECT" => self.database_commands.select(args, current_db),
-            "DBSIZE" => self.database_commands.dbsize(args, *current_db),
-            "FLUSHDB" => self.database_commands.flushdb(args, *current_db),
+            "SELECT" => self.database_commands.select(args, &mut client.db),
+            "DBSIZE" => self.database_commands.dbsize(args, client.db),
+            "FLUSHDB" => self.database_commands.flushdb(args, client.db),
             "FLUSHALL" => self.database_commands.flushall(args),
             "SWAPDB" => self.database_commands.swapdb(args),
-            "MOVE" => self.database_commands.move_key(args, *current_db),
+            "MOVE" => self.database_commands.move_key(args, client.db),
 
             // Key commands - most need routing checks
-            "KEYS" => self.key_commands.keys(args, *current_db), // Local scan, no routing
-            "SCAN" => self.key_commands.scan(args, *current_db), // Local scan, no routing
-            "RANDOMKEY" => self.key_commands.randomkey(args, *current_db), // Local, no routing
+            "KEYS" => self.key_commands.keys(args, client.db), // Local scan, no routing
+            "SCAN" => self.key_commands.scan(args, client.db), // Local scan, no routing
+            "RANDOMKEY" => self.key_commands.randomkey(args, client.db), // Local, no routing
             "RENAME" => {
                 // RENAME takes two keys, both must be in the same slot
                 if args.len() >= 2 {
                     let keys: Vec<&[u8]> = vec![args[0].as_ref(), args[1].as_ref()];
-                    self.check_keys_routing(&keys)?;
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.key_commands.rename(args, *current_db)
+                self.key_commands.rename(args, client.db)
             }
             "RENAMENX" => {
                 if args.len() >= 2 {
                     let keys: Vec<&[u8]> = vec![args[0].as_ref(), args[1].as_ref()];
-                    self.check_keys_routing(&keys)?;
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.key_commands.renamenx(args, *current_db)
+                self.key_commands.renamenx(args, client.db)
             }
             "TYPE" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.key_commands.get_type(args, *current_db)
+                self.key_commands.get_type(args, client.db)
             }
             "COPY" => {
                 // COPY takes source and destination keys
                 if args.len() >= 2 {
                     let keys: Vec<&[u8]> = vec![args[0].as_ref(), args[1].as_ref()];
-                    self.check_keys_routing(&keys)?;
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.key_commands.copy(args, *current_db)
+                self.key_commands.copy(args, client.db)
             }
             "DUMP" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.key_commands.dump(args, *current_db)
+                self.key_commands.dump(args, client.db)
             }
             "RESTORE" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.key_commands.restore(args, *current_db)
+                self.key_commands.restore(args, client.db)
+            }
+            "MIGRATE" => self.migrate(args, client.db), // MIGRATE handles routing internally
+            "SORT" => {
+                // The STORE destination has to be in the same slot as the source
+                let keys: Vec<&[u8]> = server::lookup_command("SORT")
+                    .map(|info| info.keys(args).into_iter().map(|k| k.as_ref()).collect())
+                    .unwrap_or_default();
+                if !keys.is_empty() {
+                    self.check_keys_routing(&keys, client)?;
+                }
+                self.sort_commands.sort(args, client.db)
             }
-            "MIGRATE" => self.key_commands.migrate(args, *current_db), // MIGRATE handles routing internally
 
             // Key expiration commands - single key operations
             "EXPIRE" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.key_commands.expire(args, *current_db)
+                self.key_commands.expire(args, client.db)
             }
             "EXPIREAT" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.key_commands.expireat(args, *current_db)
+                self.key_commands.expireat(args, client.db)
             }
             "PEXPIRE" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.key_commands.pexpire(args, *current_db)
+                self.key_commands.pexpire(args, client.db)
             }
             "PEXPIREAT" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.key_commands.pexpireat(args, *current_db)
+                self.key_commands.pexpireat(args, client.db)
             }
             "TTL" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.key_commands.ttl(args, *current_db)
+                self.key_commands.ttl(args, client.db)
             }
             "PTTL" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.key_commands.pttl(args, *current_db)
+                self.key_commands.pttl(args, client.db)
             }
             "PERSIST" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.key_commands.persist(args, *current_db)
+                self.key_commands.persist(args, client.db)
             }
             "EXPIRETIME" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.key_commands.expiretime(args, *current_db)
+                self.key_commands.expiretime(args, client.db)
             }
             "PEXPIRETIME" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.key_commands.pexpiretime(args, *current_db)
+                self.key_commands.pexpiretime(args, client.db)
             }
 
             // Server commands
@@ -433,10 +1325,11 @@ impl CommandExecutor {
                     "GET" => self.server_commands.config_get(&args[1..]),
                     "SET" => self.server_commands.config_set(&args[1..]),
                     "REWRITE" => self.server_commands.config_rewrite(&args[1..]),
-                    _ => Err(AikvError::InvalidCommand(format!(
-                        "Unknown CONFIG subcommand: {}",
-                        subcommand
-                    ))),
+                    "HELP" => self.server_commands.config_help(),
+                    _ => Err(unknown_subcommand(
+                        "CONFIG",
+                        &String::from_utf8_lossy(&args[0]),
+                    )),
                 }
             }
             "SLOWLOG" => self.server_commands.slowlog(args),
@@ -445,7 +1338,16 @@ impl CommandExecutor {
             "SAVE" => self.server_commands.save(args),
             "BGSAVE" => self.server_commands.bgsave(args),
             "LASTSAVE" => self.server_commands.lastsave(args),
+            "FSYNC" => self.server_commands.fsync(args),
+            "DEBUG" => self.server_commands.debug(args, client.db),
+            "OBJECT" => self.server_commands.object(args, client.db),
+            "MEMORY" => self.server_commands.memory(args, client.db),
+            "BGREWRITEAOF" => self.bgrewriteaof(),
+            "WAIT" => self.server_commands.wait(args),
+            "WAITAOF" => self.server_commands.waitaof(args),
             "SHUTDOWN" => self.server_commands.shutdown(args),
+            "PUBLISH" => self.pubsub_commands.publish(args), // Delivered on this node only
+            "ACL" => self.acl_commands.acl(args, client),
             "CLIENT" => {
                 if args.is_empty() {
                     return Err(AikvError::WrongArgCount("CLIENT".to_string()));
@@ -453,18 +1355,40 @@ impl CommandExecutor {
                 let subcommand = String::from_utf8_lossy(&args[0]).to_uppercase();
                 match subcommand.as_str() {
                     "LIST" => self.server_commands.client_list(&args[1..]),
-                    "SETNAME" => self.server_commands.client_setname(&args[1..], client_id),
-                    "GETNAME" => self.server_commands.client_getname(&args[1..], client_id),
-                    _ => Err(AikvError::InvalidCommand(format!(
-                        "Unknown CLIENT subcommand: {}",
-                        subcommand
-                    ))),
+                    "SETNAME" => self.server_commands.client_setname(&args[1..], client),
+                    "GETNAME" => self.server_commands.client_getname(&args[1..], client),
+                    "ID" => self.server_commands.client_id(&args[1..], client.client_id),
+                    "KILL" => self
+                        .server_commands
+                        .client_kill(&args[1..], client.client_id),
+                    "NO-TOUCH" => self.server_commands.client_no_touch(&args[1..], client),
+                    // Client eviction and pausing aren't implemented, so these are no-ops
+                    "NO-EVICT" | "UNPAUSE" => Ok(RespValue::ok()),
+                    "HELP" => self.server_commands.client_help(),
+                    _ => Err(unknown_subcommand(
+                        "CLIENT",
+                        &String::from_utf8_lossy(&args[0]),
+                    )),
                 }
             }
 
             // Script commands
-            "EVAL" => self.script_commands.eval(args, *current_db),
-            "EVALSHA" => self.script_commands.evalsha(args, *current_db),
+            "EVAL" => {
+                self.check_script_keys_routing(args, client)?;
+                self.script_commands.eval(args, client)
+            }
+            "EVALSHA" => {
+                self.check_script_keys_routing(args, client)?;
+                self.script_commands.evalsha(args, client)
+            }
+            "EVAL_RO" => {
+                self.check_script_keys_routing(args, client)?;
+                self.script_commands.eval_ro(args, client)
+            }
+            "EVALSHA_RO" => {
+                self.check_script_keys_routing(args, client)?;
+                self.script_commands.evalsha_ro(args, client)
+            }
             "SCRIPT" => {
                 if args.is_empty() {
                     return Err(AikvError::WrongArgCount("SCRIPT".to_string()));
@@ -475,391 +1399,506 @@ impl CommandExecutor {
                     "EXISTS" => self.script_commands.script_exists(&args[1..]),
                     "FLUSH" => self.script_commands.script_flush(&args[1..]),
                     "KILL" => self.script_commands.script_kill(&args[1..]),
-                    _ => Err(AikvError::InvalidCommand(format!(
-                        "Unknown SCRIPT subcommand: {}",
-                        subcommand
-                    ))),
+                    "HELP" => self.script_commands.script_help(),
+                    _ => Err(unknown_subcommand(
+                        "SCRIPT",
+                        &String::from_utf8_lossy(&args[0]),
+                    )),
                 }
             }
 
             // List commands - single key operations
             "LPUSH" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.list_commands.lpush(args, *current_db)
+                self.list_commands.lpush(args, client.db)
             }
             "RPUSH" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.list_commands.rpush(args, *current_db)
+                self.list_commands.rpush(args, client.db)
             }
             "LPOP" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.list_commands.lpop(args, *current_db)
+                self.list_commands.lpop(args, client.db)
             }
             "RPOP" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.list_commands.rpop(args, *current_db)
+                self.list_commands.rpop(args, client.db)
             }
             "LLEN" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.list_commands.llen(args, *current_db)
+                self.list_commands.llen(args, client.db)
             }
             "LRANGE" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.list_commands.lrange(args, *current_db)
+                self.list_commands.lrange(args, client.db)
             }
             "LINDEX" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.list_commands.lindex(args, *current_db)
+                self.list_commands.lindex(args, client.db)
             }
             "LSET" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.list_commands.lset(args, *current_db)
+                self.list_commands.lset(args, client.db)
             }
             "LREM" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.list_commands.lrem(args, *current_db)
+                self.list_commands.lrem(args, client.db)
             }
             "LTRIM" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.list_commands.ltrim(args, *current_db)
+                self.list_commands.ltrim(args, client.db)
             }
             "LINSERT" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.list_commands.linsert(args, *current_db)
+                self.list_commands.linsert(args, client.db)
             }
             "LMOVE" => {
                 // LMOVE takes source and destination keys
                 if args.len() >= 2 {
                     let keys: Vec<&[u8]> = vec![args[0].as_ref(), args[1].as_ref()];
-                    self.check_keys_routing(&keys)?;
+                    self.check_keys_routing(&keys, client)?;
+                }
+                self.list_commands.lmove(args, client.db)
+            }
+            "LMPOP" => {
+                let keys: Vec<&[u8]> = server::lookup_command("LMPOP")
+                    .map(|info| info.keys(args).into_iter().map(|k| k.as_ref()).collect())
+                    .unwrap_or_default();
+                if !keys.is_empty() {
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.list_commands.lmove(args, *current_db)
+                self.list_commands.lmpop(args, client.db)
+            }
+            // Without a connection to block, as in scripts, these pop or reply
+            // null right away
+            "BLMPOP" | "BZMPOP" => {
+                if args.is_empty() {
+                    return Err(AikvError::WrongArgCount(command.to_string()));
+                }
+                blocking::parse_timeout(&args[0])?;
+                self.dispatch(&command[1..], &args[1..], client)
             }
             "LPOS" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.list_commands.lpos(args, *current_db)
+                self.list_commands.lpos(args, client.db)
             }
 
             // Hash commands - single key operations
             "HSET" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.hash_commands.hset(args, *current_db)
+                self.hash_commands.hset(args, client.db)
             }
             "HSETNX" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.hash_commands.hsetnx(args, *current_db)
+                self.hash_commands.hsetnx(args, client.db)
             }
             "HGET" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.hash_commands.hget(args, *current_db)
+                self.hash_commands.hget(args, client.db)
             }
             "HMGET" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.hash_commands.hmget(args, *current_db)
+                self.hash_commands.hmget(args, client.db)
             }
             "HMSET" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.hash_commands.hmset(args, *current_db)
+                self.hash_commands.hmset(args, client.db)
             }
             "HDEL" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.hash_commands.hdel(args, *current_db)
+                self.hash_commands.hdel(args, client.db)
             }
             "HEXISTS" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.hash_commands.hexists(args, *current_db)
+                self.hash_commands.hexists(args, client.db)
             }
             "HLEN" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.hash_commands.hlen(args, *current_db)
+                self.hash_commands.hlen(args, client.db)
             }
             "HKEYS" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.hash_commands.hkeys(args, *current_db)
+                self.hash_commands.hkeys(args, client.db)
             }
             "HVALS" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.hash_commands.hvals(args, *current_db)
+                self.hash_commands.hvals(args, client.db)
             }
             "HGETALL" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.hash_commands.hgetall(args, *current_db)
+                self.hash_commands.hgetall(args, client.db)
             }
             "HINCRBY" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.hash_commands.hincrby(args, *current_db)
+                self.hash_commands.hincrby(args, client.db)
             }
             "HINCRBYFLOAT" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.hash_commands.hincrbyfloat(args, *current_db)
+                self.hash_commands.hincrbyfloat(args, client.db)
             }
             "HSCAN" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.hash_commands.hscan(args, *current_db)
+                self.hash_commands.hscan(args, client.db)
             }
 
             // Set commands - single key and multi-key operations
             "SADD" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.set_commands.sadd(args, *current_db)
+                self.set_commands.sadd(args, client.db)
             }
             "SREM" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.set_commands.srem(args, *current_db)
+                self.set_commands.srem(args, client.db)
             }
             "SISMEMBER" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.set_commands.sismember(args, *current_db)
+                self.set_commands.sismember(args, client.db)
             }
             "SMEMBERS" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.set_commands.smembers(args, *current_db)
+                self.set_commands.smembers(args, client.db)
             }
             "SCARD" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.set_commands.scard(args, *current_db)
+                self.set_commands.scard(args, client.db)
             }
             "SPOP" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.set_commands.spop(args, *current_db)
+                self.set_commands.spop(args, client.db)
             }
             "SRANDMEMBER" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.set_commands.srandmember(args, *current_db)
+                self.set_commands.srandmember(args, client.db)
             }
             "SUNION" => {
                 // SUNION takes multiple keys
                 if !args.is_empty() {
                     let keys: Vec<&[u8]> = args.iter().map(|b| b.as_ref()).collect();
-                    self.check_keys_routing(&keys)?;
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.set_commands.sunion(args, *current_db)
+                self.set_commands.sunion(args, client.db)
             }
             "SINTER" => {
                 if !args.is_empty() {
                     let keys: Vec<&[u8]> = args.iter().map(|b| b.as_ref()).collect();
-                    self.check_keys_routing(&keys)?;
+                    self.check_keys_routing(&keys, client)?;
+                }
+                self.set_commands.sinter(args, client.db)
+            }
+            "SINTERCARD" => {
+                let keys: Vec<&[u8]> = server::lookup_command("SINTERCARD")
+                    .map(|info| info.keys(args).into_iter().map(|k| k.as_ref()).collect())
+                    .unwrap_or_default();
+                if !keys.is_empty() {
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.set_commands.sinter(args, *current_db)
+                self.set_commands.sintercard(args, client.db)
             }
             "SDIFF" => {
                 if !args.is_empty() {
                     let keys: Vec<&[u8]> = args.iter().map(|b| b.as_ref()).collect();
-                    self.check_keys_routing(&keys)?;
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.set_commands.sdiff(args, *current_db)
+                self.set_commands.sdiff(args, client.db)
             }
             "SUNIONSTORE" => {
                 // First arg is destination, rest are source keys
                 if !args.is_empty() {
                     let keys: Vec<&[u8]> = args.iter().map(|b| b.as_ref()).collect();
-                    self.check_keys_routing(&keys)?;
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.set_commands.sunionstore(args, *current_db)
+                self.set_commands.sunionstore(args, client.db)
             }
             "SINTERSTORE" => {
                 if !args.is_empty() {
                     let keys: Vec<&[u8]> = args.iter().map(|b| b.as_ref()).collect();
-                    self.check_keys_routing(&keys)?;
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.set_commands.sinterstore(args, *current_db)
+                self.set_commands.sinterstore(args, client.db)
             }
             "SDIFFSTORE" => {
                 if !args.is_empty() {
                     let keys: Vec<&[u8]> = args.iter().map(|b| b.as_ref()).collect();
-                    self.check_keys_routing(&keys)?;
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.set_commands.sdiffstore(args, *current_db)
+                self.set_commands.sdiffstore(args, client.db)
             }
             "SSCAN" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.set_commands.sscan(args, *current_db)
+                self.set_commands.sscan(args, client.db)
             }
             "SMOVE" => {
                 // SMOVE takes source and destination keys
                 if args.len() >= 2 {
                     let keys: Vec<&[u8]> = vec![args[0].as_ref(), args[1].as_ref()];
-                    self.check_keys_routing(&keys)?;
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.set_commands.smove(args, *current_db)
+                self.set_commands.smove(args, client.db)
             }
 
             // Sorted Set commands - single key operations
             "ZADD" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zadd(args, *current_db)
+                self.zset_commands.zadd(args, client.db)
             }
             "ZREM" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zrem(args, *current_db)
+                self.zset_commands.zrem(args, client.db)
             }
             "ZSCORE" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zscore(args, *current_db)
+                self.zset_commands.zscore(args, client.db)
             }
             "ZRANK" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zrank(args, *current_db)
+                self.zset_commands.zrank(args, client.db)
             }
             "ZREVRANK" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zrevrank(args, *current_db)
+                self.zset_commands.zrevrank(args, client.db)
             }
             "ZRANGE" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zrange(args, *current_db)
+                self.zset_commands.zrange(args, client.db)
             }
             "ZREVRANGE" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zrevrange(args, *current_db)
+                self.zset_commands.zrevrange(args, client.db)
             }
             "ZRANGEBYSCORE" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zrangebyscore(args, *current_db)
+                self.zset_commands.zrangebyscore(args, client.db)
             }
             "ZREVRANGEBYSCORE" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zrevrangebyscore(args, *current_db)
+                self.zset_commands.zrevrangebyscore(args, client.db)
             }
             "ZCARD" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zcard(args, *current_db)
+                self.zset_commands.zcard(args, client.db)
             }
             "ZCOUNT" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zcount(args, *current_db)
+                self.zset_commands.zcount(args, client.db)
             }
             "ZINCRBY" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zincrby(args, *current_db)
+                self.zset_commands.zincrby(args, client.db)
             }
             "ZSCAN" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zscan(args, *current_db)
+                self.zset_commands.zscan(args, client.db)
             }
             "ZPOPMIN" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zpopmin(args, *current_db)
+                self.zset_commands.zpopmin(args, client.db)
             }
             "ZPOPMAX" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.zset_commands.zpopmax(args, client.db)
+            }
+            "ZMPOP" => {
+                let keys: Vec<&[u8]> = server::lookup_command("ZMPOP")
+                    .map(|info| info.keys(args).into_iter().map(|k| k.as_ref()).collect())
+                    .unwrap_or_default();
+                if !keys.is_empty() {
+                    self.check_keys_routing(&keys, client)?;
                 }
-                self.zset_commands.zpopmax(args, *current_db)
+                self.zset_commands.zmpop(args, client.db)
             }
             "ZRANGEBYLEX" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zrangebylex(args, *current_db)
+                self.zset_commands.zrangebylex(args, client.db)
             }
             "ZREVRANGEBYLEX" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zrevrangebylex(args, *current_db)
+                self.zset_commands.zrevrangebylex(args, client.db)
             }
             "ZLEXCOUNT" => {
                 if !args.is_empty() {
-                    self.check_key_routing(&args[0])?;
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.zset_commands.zlexcount(args, client.db)
+            }
+
+            // Geo commands
+            "GEOADD" => {
+                if !args.is_empty() {
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.geo_commands.geoadd(args, client.db)
+            }
+            "GEOPOS" => {
+                if !args.is_empty() {
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.geo_commands.geopos(args, client.db)
+            }
+            "GEODIST" => {
+                if !args.is_empty() {
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.geo_commands.geodist(args, client.db)
+            }
+            "GEOSEARCH" => {
+                if !args.is_empty() {
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.geo_commands.geosearch(args, client.db)
+            }
+
+            // HyperLogLog commands
+            "PFADD" => {
+                if !args.is_empty() {
+                    self.check_key_routing(&args[0], client)?;
                 }
-                self.zset_commands.zlexcount(args, *current_db)
+                self.hll_commands.pfadd(args, client.db)
+            }
+            "PFCOUNT" => {
+                // PFCOUNT merges every key, so all must be in the same slot
+                if !args.is_empty() {
+                    let keys: Vec<&[u8]> = args.iter().map(|b| b.as_ref()).collect();
+                    self.check_keys_routing(&keys, client)?;
+                }
+                self.hll_commands.pfcount(args, client.db)
+            }
+            "PFMERGE" => {
+                if !args.is_empty() {
+                    let keys: Vec<&[u8]> = args.iter().map(|b| b.as_ref()).collect();
+                    self.check_keys_routing(&keys, client)?;
+                }
+                self.hll_commands.pfmerge(args, client.db)
+            }
+
+            // Stream commands
+            "XADD" => {
+                if !args.is_empty() {
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.stream_commands.xadd(args, client.db)
+            }
+            "XLEN" => {
+                if !args.is_empty() {
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.stream_commands.xlen(args, client.db)
+            }
+            "XRANGE" => {
+                if !args.is_empty() {
+                    self.check_key_routing(&args[0], client)?;
+                }
+                self.stream_commands.xrange(args, client.db)
+            }
+            "XREAD" => {
+                let keys: Vec<&[u8]> = server::lookup_command("XREAD")
+                    .map(|info| info.keys(args).into_iter().map(|k| k.as_ref()).collect())
+                    .unwrap_or_default();
+                if !keys.is_empty() {
+                    self.check_keys_routing(&keys, client)?;
+                }
+                self.stream_commands.xread(args, client.db)
             }
 
             // Cluster commands (only available with cluster feature)
@@ -870,35 +1909,26 @@ impl CommandExecutor {
                 } else {
                     // Return fallback responses for read-only cluster commands when not initialized
                     // This helps Redis UI clients detect cluster mode properly
-                    Self::handle_cluster_fallback(args)
+                    self.handle_cluster_fallback(args)
                 }
             }
             #[cfg(feature = "cluster")]
             "READONLY" => {
-                if let Some(ref cluster_commands) = self.cluster_commands {
-                    cluster_commands.readonly()
-                } else {
-                    // READONLY is safe to acknowledge even without cluster
-                    Ok(RespValue::simple_string("OK"))
-                }
+                // Lets this connection read from slots this node replicates;
+                // safe to acknowledge even without cluster
+                client.readonly = true;
+                Ok(RespValue::simple_string("OK"))
             }
             #[cfg(feature = "cluster")]
             "READWRITE" => {
-                if let Some(ref cluster_commands) = self.cluster_commands {
-                    cluster_commands.readwrite()
-                } else {
-                    // READWRITE is safe to acknowledge even without cluster
-                    Ok(RespValue::simple_string("OK"))
-                }
+                client.readonly = false;
+                Ok(RespValue::simple_string("OK"))
             }
             #[cfg(feature = "cluster")]
             "ASKING" => {
-                if let Some(ref cluster_commands) = self.cluster_commands {
-                    cluster_commands.asking()
-                } else {
-                    // ASKING is safe to acknowledge even without cluster
-                    Ok(RespValue::simple_string("OK"))
-                }
+                // Lets the next command into a slot this node is importing
+                client.asking = true;
+                Ok(RespValue::simple_string("OK"))
             }
 
             // Utility commands
@@ -919,10 +1949,7 @@ impl CommandExecutor {
                 Ok(RespValue::bulk_string(args[0].clone()))
             }
 
-            _ => Err(AikvError::InvalidCommand(format!(
-                "Unknown command: {}",
-                command
-            ))),
+            _ => Err(unknown_command(command, args)),
         }
     }
 
@@ -930,6 +1957,94 @@ impl CommandExecutor {
         &self.server_commands
     }
 
+    /// The Pub/Sub hub PUBLISH and keyspace notifications deliver to
+    pub fn pubsub(&self) -> &Arc<PubSub> {
+        self.pubsub_commands.pubsub()
+    }
+
+    /// Share the server-wide Pub/Sub hub with this executor
+    pub fn set_pubsub(&mut self, pubsub: Arc<PubSub>) {
+        self.server_commands.set_pubsub(Arc::clone(&pubsub));
+        self.pubsub_commands.set_pubsub(pubsub);
+    }
+
+    /// Share the server-wide registry of blocked clients with this executor
+    pub fn set_blocked_clients(&mut self, blocked_clients: Arc<BlockedClients>) {
+        self.blocked_clients = blocked_clients;
+    }
+
+    /// Share the server-wide client registry with this executor
+    pub fn set_client_registry(&mut self, clients: server::ClientRegistry) {
+        self.server_commands.set_client_registry(clients);
+    }
+
+    /// Check that the client's ACL user may run `command` with `args`, for
+    /// the commands the connection runs itself instead of through `execute`
+    pub fn check_permission(
+        &self,
+        client: &ClientContext,
+        command: &str,
+        args: &[Bytes],
+    ) -> Result<()> {
+        self.acl_commands.check_permission(client, command, args)
+    }
+
+    /// Share the server-wide ACL user table with this executor
+    pub fn set_acl_users(&mut self, users: acl::AclUsers) {
+        self.acl_commands.set_users(Arc::clone(&users));
+        self.script_commands.set_acl_users(Arc::clone(&users));
+        self.server_commands.set_acl_users(users);
+    }
+
+    /// Share the server-wide runtime configuration (CONFIG GET/SET) with this
+    /// executor; call it before the other `set_*` methods
+    pub fn set_runtime_config(&mut self, config: config::SharedConfig) {
+        self.string_commands.set_runtime_config(Arc::clone(&config));
+        self.script_commands.set_runtime_config(Arc::clone(&config));
+        self.server_commands.set_runtime_config(config);
+    }
+
+    /// Share the server-wide registry of running scripts (SCRIPT KILL)
+    pub fn set_script_registry(&mut self, registry: Arc<script::ScriptRegistry>) {
+        self.script_commands.set_script_registry(registry);
+    }
+
+    /// Share the server-wide slow log with this executor
+    pub fn set_slow_query_log(&mut self, slow_query_log: Arc<crate::observability::SlowQueryLog>) {
+        self.server_commands.set_slow_query_log(slow_query_log);
+    }
+
+    /// Stop the server through `shutdown` on SHUTDOWN
+    pub fn set_shutdown_signal(&mut self, shutdown: server::ShutdownSignal) {
+        self.server_commands.set_shutdown_signal(shutdown);
+    }
+
+    /// Tell SHUTDOWN whether the server saves on shutdown by default
+    pub fn set_save_on_shutdown(&mut self, enabled: bool) {
+        self.server_commands.set_save_on_shutdown(enabled);
+    }
+
+    /// Share the server-wide switch of the expiration sweeper (DEBUG SET-ACTIVE-EXPIRE)
+    pub fn set_active_expire(&mut self, active_expire: Arc<std::sync::atomic::AtomicBool>) {
+        self.server_commands.set_active_expire(active_expire);
+    }
+
+    /// Share the server-wide flag raised while the dataset loads on startup;
+    /// commands not flagged `loading` fail with LOADING while it is set
+    pub fn set_loading(&mut self, loading: Arc<std::sync::atomic::AtomicBool>) {
+        self.server_commands.set_loading(loading);
+    }
+
+    /// Share the server-wide metrics with this executor (INFO commandstats)
+    pub fn set_metrics(&mut self, metrics: Arc<crate::observability::Metrics>) {
+        self.server_commands.set_metrics(metrics);
+    }
+
+    /// Share the server-wide RDB snapshot state with this executor
+    pub fn set_rdb_state(&mut self, rdb: Arc<server::RdbState>) {
+        self.server_commands.set_rdb_state(rdb);
+    }
+
     #[cfg(feature = "cluster")]
     pub fn cluster_commands(&self) -> Option<&crate::cluster::ClusterCommands> {
         self.cluster_commands.as_ref()
@@ -939,13 +2054,14 @@ impl CommandExecutor {
     /// Returns fallback responses for read-only commands to help Redis clients
     /// detect cluster mode properly.
     #[cfg(feature = "cluster")]
-    fn handle_cluster_fallback(args: &[Bytes]) -> Result<RespValue> {
+    fn handle_cluster_fallback(&self, args: &[Bytes]) -> Result<RespValue> {
         if args.is_empty() {
             return Err(AikvError::WrongArgCount("CLUSTER".to_string()));
         }
 
         let subcommand = String::from_utf8_lossy(&args[0]).to_uppercase();
         match subcommand.as_str() {
+            "HELP" => Ok(crate::cluster::ClusterCommands::help()),
             "INFO" => {
                 // Return minimal cluster info indicating cluster is not ready
                 let info = "cluster_state:fail\r\n\
@@ -997,8 +2113,31 @@ impl CommandExecutor {
             "BUMPEPOCH" => Ok(RespValue::BulkString(Some(Bytes::from("BUMPED 0")))),
             "SET-CONFIG-EPOCH" => Ok(RespValue::simple_string("OK")),
             "COUNT-FAILURE-REPORTS" => Ok(RespValue::Integer(0)),
-            "COUNTKEYSINSLOT" => Ok(RespValue::Integer(0)),
-            "GETKEYSINSLOT" => Ok(RespValue::Array(Some(vec![]))),
+            "COUNTKEYSINSLOT" => {
+                if args.len() != 2 {
+                    return Err(AikvError::WrongArgCount(
+                        "CLUSTER COUNTKEYSINSLOT".to_string(),
+                    ));
+                }
+                let slot = crate::cluster::parse_slot(&args[1])?;
+                let keys = crate::cluster::keys_in_slot(&self.storage, slot, usize::MAX)?;
+                Ok(RespValue::Integer(keys.len() as i64))
+            }
+            "GETKEYSINSLOT" => {
+                if args.len() != 3 {
+                    return Err(AikvError::WrongArgCount(
+                        "CLUSTER GETKEYSINSLOT".to_string(),
+                    ));
+                }
+                let slot = crate::cluster::parse_slot(&args[1])?;
+                let count = crate::cluster::parse_slot_key_count(&args[2])?;
+                let keys = crate::cluster::keys_in_slot(&self.storage, slot, count)?;
+                Ok(RespValue::Array(Some(
+                    keys.into_iter()
+                        .map(|key| RespValue::BulkString(Some(Bytes::from(key))))
+                        .collect(),
+                )))
+            }
             _ => Err(AikvError::Internal(
                 "Cluster not initialized. Please initialize cluster node first.".to_string(),
             )),
@@ -0,0 +1,475 @@
+//! Pub/Sub channels and keyspace notifications
+//!
+//! One [`PubSub`] hub is shared by every connection of a server. Connections
+//! register a [`Subscriber`] for the channels and patterns they subscribe
+//! to; PUBLISH and keyspace notifications deliver to every matching one.
+
+use super::config::glob_match_case_sensitive;
+use crate::error::{AikvError, Result};
+use crate::protocol::RespValue;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::sync::{mpsc, Notify};
+
+/// Bytes waiting in a connection's message queue, shared by both ends
+#[derive(Debug, Default)]
+struct QueuedBytes {
+    bytes: AtomicUsize,
+    /// Notified whenever a message is queued
+    grown: Notify,
+}
+
+/// Where a connection receives the messages published to its subscriptions
+#[derive(Clone, Debug)]
+pub struct Subscriber {
+    sender: mpsc::UnboundedSender<PubSubMessage>,
+    queued: Arc<QueuedBytes>,
+}
+
+impl Subscriber {
+    /// Create a subscriber and the queue its messages arrive on
+    pub fn channel() -> (Subscriber, MessageQueue) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let queued = Arc::new(QueuedBytes::default());
+        let queue = MessageQueue {
+            receiver,
+            queued: Arc::clone(&queued),
+        };
+        (
+            Subscriber {
+                sender,
+                queued,
+            },
+            queue,
+        )
+    }
+
+    /// Queue a message, returning false if the connection has gone away
+    fn send(&self, message: PubSubMessage) -> bool {
+        let size = message.size();
+        self.queued.bytes.fetch_add(size, Ordering::Relaxed);
+        if self.sender.send(message).is_err() {
+            self.queued.bytes.fetch_sub(size, Ordering::Relaxed);
+            return false;
+        }
+        self.queued.grown.notify_one();
+        true
+    }
+}
+
+/// The messages a connection has yet to write to its client
+#[derive(Debug)]
+pub struct MessageQueue {
+    receiver: mpsc::UnboundedReceiver<PubSubMessage>,
+    queued: Arc<QueuedBytes>,
+}
+
+impl MessageQueue {
+    /// Wait for the next message
+    pub async fn recv(&mut self) -> Option<PubSubMessage> {
+        let message = self.receiver.recv().await?;
+        self.queued
+            .bytes
+            .fetch_sub(message.size(), Ordering::Relaxed);
+        Some(message)
+    }
+
+    /// The next message, if one has already arrived
+    pub fn try_recv(&mut self) -> Option<PubSubMessage> {
+        let message = self.receiver.try_recv().ok()?;
+        self.queued
+            .bytes
+            .fetch_sub(message.size(), Ordering::Relaxed);
+        Some(message)
+    }
+
+    /// Drop every message waiting
+    pub fn clear(&mut self) {
+        while self.try_recv().is_some() {}
+    }
+
+    /// Bytes of the messages waiting, as counted by [`PubSubMessage::size`]
+    pub fn queued_bytes(&self) -> usize {
+        self.queued.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Wait until another message is queued
+    pub async fn grown(&self) {
+        self.queued.grown.notified().await
+    }
+}
+
+/// A message delivered to a subscriber
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PubSubMessage {
+    /// Published to a channel the subscriber subscribed to
+    Message { channel: Bytes, payload: Bytes },
+    /// Published to a channel matching a pattern the subscriber subscribed to
+    PMessage {
+        pattern: Bytes,
+        channel: Bytes,
+        payload: Bytes,
+    },
+}
+
+impl PubSubMessage {
+    /// Payload bytes the message holds, leaving out the RESP framing
+    pub fn size(&self) -> usize {
+        match self {
+            PubSubMessage::Message {
+                channel,
+                payload,
+            } => channel.len() + payload.len(),
+            PubSubMessage::PMessage {
+                pattern,
+                channel,
+                payload,
+            } => pattern.len() + channel.len() + payload.len(),
+        }
+    }
+
+    /// The push a subscribed client receives (an array under RESP2)
+    pub fn into_resp(self) -> RespValue {
+        match self {
+            PubSubMessage::Message {
+                channel,
+                payload,
+            } => RespValue::push(vec![
+                RespValue::bulk_string("message"),
+                RespValue::bulk_string(channel),
+                RespValue::bulk_string(payload),
+            ]),
+            PubSubMessage::PMessage {
+                pattern,
+                channel,
+                payload,
+            } => RespValue::push(vec![
+                RespValue::bulk_string("pmessage"),
+                RespValue::bulk_string(pattern),
+                RespValue::bulk_string(channel),
+                RespValue::bulk_string(payload),
+            ]),
+        }
+    }
+}
+
+/// Classes of keyspace events to publish (`notify-keyspace-events`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyspaceEvents(u32);
+
+impl KeyspaceEvents {
+    /// `K`: publish to `__keyspace@<db>__:<key>`
+    pub const KEYSPACE: Self = Self(1);
+    /// `E`: publish to `__keyevent@<db>__:<event>`
+    pub const KEYEVENT: Self = Self(1 << 1);
+    /// `g`: type-independent commands such as DEL, EXPIRE and RENAME
+    pub const GENERIC: Self = Self(1 << 2);
+    /// `$`: string commands
+    pub const STRING: Self = Self(1 << 3);
+    /// `l`: list commands
+    pub const LIST: Self = Self(1 << 4);
+    /// `s`: set commands
+    pub const SET: Self = Self(1 << 5);
+    /// `h`: hash commands
+    pub const HASH: Self = Self(1 << 6);
+    /// `z`: sorted set commands
+    pub const ZSET: Self = Self(1 << 7);
+    /// `x`: keys deleted because they expired
+    pub const EXPIRED: Self = Self(1 << 8);
+    /// `e`: keys evicted under `maxmemory`
+    pub const EVICTED: Self = Self(1 << 9);
+    /// `t`: stream commands
+    pub const STREAM: Self = Self(1 << 10);
+    /// `m`: key misses
+    pub const KEY_MISS: Self = Self(1 << 11);
+    /// `n`: new keys
+    pub const NEW: Self = Self(1 << 12);
+
+    /// Every class `A` stands for
+    const ALL: Self = Self(
+        Self::GENERIC.0
+            | Self::STRING.0
+            | Self::LIST.0
+            | Self::SET.0
+            | Self::HASH.0
+            | Self::ZSET.0
+            | Self::EXPIRED.0
+            | Self::EVICTED.0
+            | Self::STREAM.0,
+    );
+
+    /// The single-character flags, in the order Redis reports them
+    const FLAGS: &'static [(char, Self)] = &[
+        ('g', Self::GENERIC),
+        ('$', Self::STRING),
+        ('l', Self::LIST),
+        ('s', Self::SET),
+        ('h', Self::HASH),
+        ('z', Self::ZSET),
+        ('x', Self::EXPIRED),
+        ('e', Self::EVICTED),
+        ('t', Self::STREAM),
+        ('K', Self::KEYSPACE),
+        ('E', Self::KEYEVENT),
+        ('m', Self::KEY_MISS),
+        ('n', Self::NEW),
+    ];
+
+    /// No events
+    pub fn none() -> Self {
+        Self(0)
+    }
+
+    /// Parse a flag string such as `KEA` or `Kx`; `None` if it holds an
+    /// unknown flag
+    pub fn parse(flags: &str) -> Option<Self> {
+        flags.chars().try_fold(Self::none(), |events, flag| {
+            let class = match flag {
+                'A' => Self::ALL,
+                _ => Self::FLAGS.iter().find(|(c, _)| *c == flag)?.1,
+            };
+            Some(events | class)
+        })
+    }
+
+    /// Whether every class in `other` is enabled
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether these are the events of `class` that get published somewhere
+    fn publishes(self, class: Self) -> bool {
+        self.0 & (Self::KEYSPACE.0 | Self::KEYEVENT.0) != 0 && self.contains(class)
+    }
+}
+
+impl std::ops::BitOr for KeyspaceEvents {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl std::fmt::Display for KeyspaceEvents {
+    /// The canonical flag string CONFIG GET reports, `A` standing in for
+    /// all the type classes
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let all = self.contains(Self::ALL);
+        if all {
+            write!(f, "A")?;
+        }
+        for &(flag, class) in Self::FLAGS {
+            if (all && Self::ALL.contains(class)) || !self.contains(class) {
+                continue;
+            }
+            write!(f, "{}", flag)?;
+        }
+        Ok(())
+    }
+}
+
+/// Subscribers of one channel or pattern, by client id
+type Subscribers = HashMap<usize, Subscriber>;
+
+#[derive(Default)]
+struct Subscriptions {
+    channels: HashMap<Bytes, Subscribers>,
+    patterns: HashMap<Bytes, Subscribers>,
+}
+
+/// Channel and pattern subscriptions shared by every connection
+#[derive(Default)]
+pub struct PubSub {
+    subscriptions: RwLock<Subscriptions>,
+    /// Keyspace event classes currently published
+    keyspace_events: AtomicU32,
+}
+
+impl PubSub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Deliver messages published to `channel` to `subscriber`
+    pub fn subscribe(
+        &self,
+        client_id: usize,
+        channel: Bytes,
+        subscriber: &Subscriber,
+    ) -> Result<()> {
+        self.write()?
+            .channels
+            .entry(channel)
+            .or_default()
+            .insert(client_id, subscriber.clone());
+        Ok(())
+    }
+
+    /// Stop delivering messages published to `channel` to the client
+    pub fn unsubscribe(&self, client_id: usize, channel: &[u8]) -> Result<()> {
+        Self::remove(&mut self.write()?.channels, client_id, channel);
+        Ok(())
+    }
+
+    /// Deliver messages published to channels matching `pattern` to `subscriber`
+    pub fn psubscribe(
+        &self,
+        client_id: usize,
+        pattern: Bytes,
+        subscriber: &Subscriber,
+    ) -> Result<()> {
+        self.write()?
+            .patterns
+            .entry(pattern)
+            .or_default()
+            .insert(client_id, subscriber.clone());
+        Ok(())
+    }
+
+    /// Stop delivering messages matching `pattern` to the client
+    pub fn punsubscribe(&self, client_id: usize, pattern: &[u8]) -> Result<()> {
+        Self::remove(&mut self.write()?.patterns, client_id, pattern);
+        Ok(())
+    }
+
+    /// Publish `payload` to `channel`, returning how many subscribers got it
+    pub fn publish(&self, channel: &[u8], payload: &[u8]) -> Result<usize> {
+        let subscriptions = self
+            .subscriptions
+            .read()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        let channel = Bytes::copy_from_slice(channel);
+        let payload = Bytes::copy_from_slice(payload);
+
+        let mut receivers = 0;
+        if let Some(subscribers) = subscriptions.channels.get(&channel) {
+            for subscriber in subscribers.values() {
+                let message = PubSubMessage::Message {
+                    channel: channel.clone(),
+                    payload: payload.clone(),
+                };
+                // A closed receiver is a connection on its way out
+                if subscriber.send(message) {
+                    receivers += 1;
+                }
+            }
+        }
+
+        let channel_str = String::from_utf8_lossy(&channel);
+        for (pattern, subscribers) in &subscriptions.patterns {
+            if !glob_match_case_sensitive(&String::from_utf8_lossy(pattern), &channel_str) {
+                continue;
+            }
+            for subscriber in subscribers.values() {
+                let message = PubSubMessage::PMessage {
+                    pattern: pattern.clone(),
+                    channel: channel.clone(),
+                    payload: payload.clone(),
+                };
+                if subscriber.send(message) {
+                    receivers += 1;
+                }
+            }
+        }
+
+        Ok(receivers)
+    }
+
+    /// Keyspace event classes currently published
+    pub fn keyspace_events(&self) -> KeyspaceEvents {
+        KeyspaceEvents(self.keyspace_events.load(Ordering::Relaxed))
+    }
+
+    /// Publish keyspace events of these classes from now on
+    pub fn set_keyspace_events(&self, events: KeyspaceEvents) {
+        self.keyspace_events.store(events.0, Ordering::Relaxed);
+    }
+
+    /// Publish that `event` (of `class`) happened to `key` in database `db`,
+    /// if that class of events is enabled
+    pub fn notify_keyspace_event(&self, class: KeyspaceEvents, event: &str, key: &[u8], db: usize) {
+        let events = self.keyspace_events();
+        if !events.publishes(class) {
+            return;
+        }
+
+        // Notifications are best effort: a lock error only loses the event
+        if events.contains(KeyspaceEvents::KEYSPACE) {
+            let mut channel = format!("__keyspace@{}__:", db).into_bytes();
+            channel.extend_from_slice(key);
+            let _ = self.publish(&channel, event.as_bytes());
+        }
+        if events.contains(KeyspaceEvents::KEYEVENT) {
+            let channel = format!("__keyevent@{}__:{}", db, event);
+            let _ = self.publish(channel.as_bytes(), key);
+        }
+    }
+
+    /// Drop every subscription of a client that is going away
+    pub fn remove_client(&self, client_id: usize) -> Result<()> {
+        let mut subscriptions = self.write()?;
+        for map in [&mut subscriptions.channels, &mut subscriptions.patterns] {
+            map.retain(|_, subscribers| {
+                subscribers.remove(&client_id);
+                !subscribers.is_empty()
+            });
+        }
+        Ok(())
+    }
+
+    fn remove(map: &mut HashMap<Bytes, Subscribers>, client_id: usize, name: &[u8]) {
+        if let Some(subscribers) = map.get_mut(name) {
+            subscribers.remove(&client_id);
+            if subscribers.is_empty() {
+                map.remove(name);
+            }
+        }
+    }
+
+    fn write(&self) -> Result<std::sync::RwLockWriteGuard<'_, Subscriptions>> {
+        self.subscriptions
+            .write()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))
+    }
+}
+
+/// PUBLISH command handler
+pub struct PubSubCommands {
+    pubsub: Arc<PubSub>,
+}
+
+impl PubSubCommands {
+    pub fn new() -> Self {
+        Self {
+            pubsub: Arc::new(PubSub::new()),
+        }
+    }
+
+    /// The hub this handler publishes to
+    pub fn pubsub(&self) -> &Arc<PubSub> {
+        &self.pubsub
+    }
+
+    /// Share a server-wide hub with other connections
+    pub fn set_pubsub(&mut self, pubsub: Arc<PubSub>) {
+        self.pubsub = pubsub;
+    }
+
+    /// PUBLISH channel message - Post a message to a channel
+    pub fn publish(&self, args: &[Bytes]) -> Result<RespValue> {
+        if args.len() != 2 {
+            return Err(AikvError::WrongArgCount("PUBLISH".to_string()));
+        }
+        let receivers = self.pubsub.publish(&args[0], &args[1])?;
+        Ok(RespValue::integer(receivers as i64))
+    }
+}
+
+impl Default for PubSubCommands {
+    fn default() -> Self {
+        Self::new()
+    }
+}
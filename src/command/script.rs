@@ -1,12 +1,18 @@
+use super::acl::{self, AclUsers};
+use super::config::{RuntimeConfig, SharedConfig};
+use super::help::help_reply;
+use super::{server, sort, ClientContext, CommandExecutor};
 use crate::error::{AikvError, Result};
 use crate::protocol::RespValue;
-use crate::storage::{BatchOp, StorageEngine, StoredValue};
+use crate::storage::StorageEngine;
 use bytes::Bytes;
-use mlua::{Lua, LuaOptions, StdLib, Value as LuaValue};
+use mlua::{HookTriggers, Lua, LuaOptions, StdLib, Value as LuaValue, VmState};
 use sha1::{Digest, Sha1};
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
-use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock, PoisonError, RwLock};
 use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // KEY LOCK MANAGER - Key-level locking for parallel script execution
@@ -70,12 +76,19 @@ impl KeyLockManager {
     /// Returns a guard that releases the locks when dropped.
     /// Keys are sorted before locking to prevent deadlocks.
     pub fn lock_keys(&self, keys: &[String]) -> Result<KeyLockGuard<'_>> {
+        Ok(KeyLockGuard {
+            manager: self,
+            keys: self.acquire(keys)?,
+        })
+    }
+
+    /// Acquire locks for the specified keys, returning the keys locked
+    ///
+    /// The caller releases them with [`unlock_keys`](Self::unlock_keys).
+    fn acquire(&self, keys: &[String]) -> Result<Vec<String>> {
         if keys.is_empty() {
-            // No keys to lock, return empty guard
-            return Ok(KeyLockGuard {
-                manager: self,
-                keys: Vec::new(),
-            });
+            // No keys to lock
+            return Ok(Vec::new());
         }
 
         // Sort keys to prevent deadlock
@@ -106,10 +119,7 @@ impl KeyLockManager {
                     entry.holders += 1;
                 }
 
-                return Ok(KeyLockGuard {
-                    manager: self,
-                    keys: sorted_keys,
-                });
+                return Ok(sorted_keys);
             }
 
             // Check timeout
@@ -202,237 +212,349 @@ impl<'a> Drop for KeyLockGuard<'a> {
 }
 
 // ============================================================================
-// EXTENDED BATCH OPERATIONS - Support for complex types
+// SCRIPT REGISTRY - Running scripts, for SCRIPT KILL and lua-time-limit
 // ============================================================================
 
-/// Extended batch operation supporting all data types for transactions.
-#[derive(Debug, Clone)]
-pub enum ExtendedBatchOp {
-    /// Set a string value
-    SetString(Bytes),
-    /// Set a list value
-    SetList(VecDeque<Bytes>),
-    /// Set a hash value
-    SetHash(HashMap<String, Bytes>),
-    /// Set a set value
-    SetSet(HashSet<Vec<u8>>),
-    /// Set a sorted set value
-    SetZSet(BTreeMap<Vec<u8>, f64>),
-    /// Delete the key
-    Delete,
-}
+/// Number of Lua instructions between checks for SCRIPT KILL and lua-time-limit
+const SCRIPT_HOOK_INSTRUCTIONS: u32 = 1000;
 
-/// Script cache entry
-#[derive(Clone, Debug)]
-struct CachedScript {
-    script: String,
+/// A script being executed
+#[derive(Debug)]
+struct RunningScript {
+    /// When the script started
+    started: Instant,
+    /// Set by SCRIPT KILL; the script aborts at its next check
+    kill_requested: AtomicBool,
 }
 
-/// Transaction context for Lua script execution
-///
-/// This provides transactional semantics for Lua scripts by buffering all write
-/// operations and only committing them if the script completes successfully.
-/// If the script fails, the buffer is discarded, achieving automatic rollback.
-///
-/// When using AiDbStorageEngine, this leverages AiDb's WriteBatch for true
-/// atomic batch writes with WAL durability guarantees.
-///
-/// Extended to support complex types (List, Hash, Set, ZSet) for Lua scripts.
-#[derive(Debug)]
-struct ScriptTransaction {
-    /// Database index for this transaction
-    db_index: usize,
-    /// Extended write buffer: key -> operation (supports all types)
-    write_buffer: HashMap<String, ExtendedBatchOp>,
+/// Scripts currently executing, shared by every connection so SCRIPT KILL
+/// from one client can stop a script started by another.
+#[derive(Debug, Default)]
+pub struct ScriptRegistry {
+    running: Mutex<HashMap<u64, Arc<RunningScript>>>,
+    next_id: AtomicU64,
 }
 
-impl ScriptTransaction {
-    /// Create a new transaction context for a database
-    fn new(db_index: usize) -> Self {
-        Self {
-            db_index,
-            write_buffer: HashMap::new(),
-        }
+impl ScriptRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    /// Read a string value, checking buffer first, then storage
+    /// Register a script that is starting
     ///
-    /// This implements "read your own writes" semantics - if a key was set
-    /// or deleted in this transaction, return that state.
-    fn get(&self, storage: &StorageEngine, key: &str) -> Result<Option<Bytes>> {
-        // Check write buffer first
-        if let Some(op) = self.write_buffer.get(key) {
-            match op {
-                ExtendedBatchOp::SetString(value) => return Ok(Some(value.clone())),
-                ExtendedBatchOp::Delete => return Ok(None),
-                // For complex types, we return None for string GET
-                _ => {
-                    return Err(AikvError::WrongType(
-                        "Operation against a key holding the wrong kind of value".to_string(),
-                    ))
-                }
-            }
-        }
-
-        // Fall back to storage
-        storage.get_from_db(self.db_index, key)
+    /// The script stays registered until the returned guard is dropped.
+    fn start(&self) -> ScriptRunGuard<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let script = Arc::new(RunningScript {
+            started: Instant::now(),
+            kill_requested: AtomicBool::new(false),
+        });
+        self.running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, Arc::clone(&script));
+        ScriptRunGuard {
+            registry: self,
+            id,
+            script,
+        }
+    }
+
+    /// Ask every running script to abort, returning how many were running
+    pub fn kill_all(&self) -> usize {
+        let running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        for script in running.values() {
+            script.kill_requested.store(true, Ordering::Relaxed);
+        }
+        running.len()
     }
+}
 
-    /// Read a full StoredValue, checking buffer first, then storage
-    fn get_value(&self, storage: &StorageEngine, key: &str) -> Result<Option<StoredValue>> {
-        // Check write buffer first
-        if let Some(op) = self.write_buffer.get(key) {
-            match op {
-                ExtendedBatchOp::SetString(value) => {
-                    return Ok(Some(StoredValue::new_string(value.clone())))
-                }
-                ExtendedBatchOp::SetList(list) => {
-                    return Ok(Some(StoredValue::new_list(list.clone())))
-                }
-                ExtendedBatchOp::SetHash(hash) => {
-                    return Ok(Some(StoredValue::new_hash(hash.clone())))
-                }
-                ExtendedBatchOp::SetSet(set) => return Ok(Some(StoredValue::new_set(set.clone()))),
-                ExtendedBatchOp::SetZSet(zset) => {
-                    return Ok(Some(StoredValue::new_zset(zset.clone())))
-                }
-                ExtendedBatchOp::Delete => return Ok(None),
-            }
-        }
-
-        // Fall back to storage
-        storage.get_value(self.db_index, key)
-    }
+/// Guard that keeps a script registered while it runs.
+struct ScriptRunGuard<'a> {
+    registry: &'a ScriptRegistry,
+    id: u64,
+    script: Arc<RunningScript>,
+}
 
-    /// Write a string value to the buffer
-    fn set(&mut self, key: String, value: Bytes) {
-        self.write_buffer
-            .insert(key, ExtendedBatchOp::SetString(value));
+impl<'a> Drop for ScriptRunGuard<'a> {
+    fn drop(&mut self) {
+        self.registry
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.id);
     }
+}
 
-    /// Write a list value to the buffer
-    fn set_list(&mut self, key: String, list: VecDeque<Bytes>) {
-        self.write_buffer
-            .insert(key, ExtendedBatchOp::SetList(list));
-    }
+/// Script cache entry
+#[derive(Clone, Debug)]
+struct CachedScript {
+    script: String,
+}
 
-    /// Write a hash value to the buffer
-    fn set_hash(&mut self, key: String, hash: HashMap<String, Bytes>) {
-        self.write_buffer
-            .insert(key, ExtendedBatchOp::SetHash(hash));
-    }
+/// Log levels of redis.log, exposed to scripts as redis.LOG_*
+const LOG_DEBUG: i64 = 0;
+const LOG_VERBOSE: i64 = 1;
+const LOG_NOTICE: i64 = 2;
+const LOG_WARNING: i64 = 3;
+
+/// Sentinel standing in for JSON null in scripts, exposed as cjson.null
+const CJSON_NULL: LuaValue = LuaValue::LightUserData(mlua::LightUserData(std::ptr::null_mut()));
+
+/// Deepest table nesting cjson.encode accepts
+const CJSON_MAX_DEPTH: usize = 1000;
+
+/// Commands that work on a whole database rather than on named keys, which
+/// the scratch storage of a [`ScriptTransaction`] can't answer
+const KEYSPACE_COMMANDS: &[&str] = &[
+    "KEYS",
+    "SCAN",
+    "RANDOMKEY",
+    "DBSIZE",
+    "FLUSHDB",
+    "FLUSHALL",
+    "SWAPDB",
+    "MOVE",
+];
+
+/// Scratch storage a script's commands run against, and the executor over it
+///
+/// Scratch spaces are pooled and emptied between scripts, so a script
+/// doesn't build a storage engine and an executor of its own.
+struct Scratch {
+    storage: StorageEngine,
+    executor: CommandExecutor,
+}
 
-    /// Write a set value to the buffer
-    fn set_set(&mut self, key: String, set: HashSet<Vec<u8>>) {
-        self.write_buffer.insert(key, ExtendedBatchOp::SetSet(set));
+impl Scratch {
+    fn new(db_count: usize, acl_users: &AclUsers) -> Self {
+        let storage = StorageEngine::new_memory(db_count);
+        let mut executor = CommandExecutor::new(storage.clone());
+        executor.set_acl_users(Arc::clone(acl_users));
+        Self {
+            storage,
+            executor,
+        }
     }
+}
 
-    /// Write a sorted set value to the buffer
-    fn set_zset(&mut self, key: String, zset: BTreeMap<Vec<u8>, f64>) {
-        self.write_buffer
-            .insert(key, ExtendedBatchOp::SetZSet(zset));
-    }
+/// Scratch spaces not in use by a running script
+type ScratchPool = Arc<Mutex<Vec<Scratch>>>;
 
-    /// Mark a key for deletion in the buffer
-    fn delete(&mut self, key: String) {
-        self.write_buffer.insert(key, ExtendedBatchOp::Delete);
-    }
+/// Transaction context for Lua script execution
+///
+/// This provides transactional semantics for Lua scripts by running every
+/// command the script calls through a full [`CommandExecutor`] over a
+/// scratch storage. Before a command runs, the keys it names are locked
+/// against other scripts and copied from the live storage into the scratch
+/// storage, so the script reads its own writes. Each write is recorded as
+/// the AOF entries that reproduce it, and those are replayed against the
+/// live storage only if the script completes successfully, so writes other
+/// clients made to the same keys meanwhile are built on rather than
+/// overwritten. If the script fails, nothing is replayed, achieving
+/// automatic rollback.
+struct ScriptTransaction {
+    /// Live storage the transaction commits to
+    storage: StorageEngine,
+    /// Scratch space the script's commands run against; only taken to
+    /// return it to the pool
+    scratch: Option<Scratch>,
+    /// Pool the scratch space goes back to when the transaction ends
+    pool: ScratchPool,
+    /// Executor over the live storage that the writes are replayed through
+    live: Arc<OnceLock<CommandExecutor>>,
+    /// Script key locks, shared with the other scripts
+    key_locks: Arc<KeyLockManager>,
+    /// Keys whose script lock the script holds, declared or not
+    locked: HashSet<String>,
+    /// Keys the transaction locked itself, released when it ends
+    acquired: Vec<String>,
+    /// Connection state of the script's commands: the caller's user and the
+    /// database the script has selected
+    client: ClientContext,
+    /// Keys copied from the live storage into the scratch storage
+    loaded: HashSet<(usize, String)>,
+    /// Writes to replay on commit: database, command and arguments
+    writes: Vec<(usize, String, Vec<Bytes>)>,
+}
 
-    /// Check if a key exists, considering the buffer
-    fn exists(&self, storage: &StorageEngine, key: &str) -> Result<bool> {
-        // Check write buffer first
-        if let Some(op) = self.write_buffer.get(key) {
-            match op {
-                ExtendedBatchOp::Delete => return Ok(false),
-                _ => return Ok(true), // Any set operation means the key exists
-            }
+impl ScriptTransaction {
+    /// Create a new transaction context over the live storage of `commands`,
+    /// running as the calling client's user and starting in its database
+    ///
+    /// The script's declared `keys` must already be locked by the caller.
+    fn new(commands: &ScriptCommands, keys: &[String], caller: &ClientContext) -> Self {
+        let scratch = commands
+            .scratch_pool
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop()
+            .unwrap_or_else(|| Scratch::new(commands.storage.db_count(), &commands.acl_users));
+        Self {
+            storage: commands.storage.clone(),
+            scratch: Some(scratch),
+            pool: Arc::clone(&commands.scratch_pool),
+            live: Arc::clone(&commands.live_executor),
+            key_locks: Arc::clone(&commands.key_lock_manager),
+            locked: keys.iter().cloned().collect(),
+            acquired: Vec::new(),
+            client: ClientContext {
+                db: caller.db,
+                auth: caller.auth.clone(),
+                ..ClientContext::default()
+            },
+            loaded: HashSet::new(),
+            writes: Vec::new(),
         }
+    }
 
-        // Fall back to storage
-        storage.exists_in_db(self.db_index, key)
+    fn scratch(&self) -> &Scratch {
+        self.scratch
+            .as_ref()
+            .expect("scratch space is only taken when the transaction ends")
     }
 
-    /// Commit the transaction - apply all buffered operations to storage atomically
+    /// Lock keys the script didn't declare, until the transaction ends
     ///
-    /// This method handles both simple string operations (using write_batch) and
-    /// complex type operations (using set_value individually).
+    /// Two scripts each waiting for a key the other holds both fail once
+    /// the lock timeout runs out.
+    fn lock(&mut self, keys: &[String]) -> Result<()> {
+        let missing: Vec<String> = keys
+            .iter()
+            .filter(|key| !self.locked.contains(*key))
+            .cloned()
+            .collect();
+        let acquired = self.key_locks.acquire(&missing)?;
+        self.locked.extend(acquired.iter().cloned());
+        self.acquired.extend(acquired);
+        Ok(())
+    }
+
+    /// Copy a key from the live storage into the scratch storage
     ///
-    /// - For MemoryAdapter: In-memory atomicity within a single lock
-    /// - For AiDbStorageEngine: True atomic batch writes via AiDb's WriteBatch
-    ///   with WAL durability guarantees (all operations written to WAL first,
-    ///   single fsync, atomic recovery on crash)
-    fn commit(self, storage: &StorageEngine) -> Result<()> {
-        if self.write_buffer.is_empty() {
+    /// Only the first load of a key copies it, so later loads don't clobber
+    /// the script's own writes ("read your own writes").
+    fn load(&mut self, db_index: usize, key: &str) -> Result<()> {
+        if !self.loaded.insert((db_index, key.to_string())) {
             return Ok(());
         }
-
-        // Separate string operations (can use write_batch) from complex type operations
-        let mut string_ops: Vec<(String, BatchOp)> = Vec::new();
-        let mut complex_ops: Vec<(String, ExtendedBatchOp)> = Vec::new();
-
-        for (key, op) in self.write_buffer.into_iter() {
-            match op {
-                ExtendedBatchOp::SetString(value) => {
-                    string_ops.push((key, BatchOp::Set(value)));
-                }
-                ExtendedBatchOp::Delete => {
-                    string_ops.push((key, BatchOp::Delete));
-                }
-                _ => {
-                    complex_ops.push((key, op));
-                }
-            }
+        if let Some(value) = self.storage.get_value(db_index, key)? {
+            self.scratch()
+                .storage
+                .set_value(db_index, key.to_string(), value)?;
         }
+        Ok(())
+    }
 
-        // Commit string operations using write_batch for atomicity
-        if !string_ops.is_empty() {
-            storage.write_batch(self.db_index, string_ops)?;
+    /// Lock and load `keys` of the script's current database
+    fn prepare(&mut self, keys: &[String]) -> Result<()> {
+        self.lock(keys)?;
+        for key in keys {
+            self.load(self.client.db, key)?;
         }
+        Ok(())
+    }
 
-        // Commit complex type operations individually
-        // Note: Complex type operations are committed one by one because the current
-        // write_batch API only supports string operations. While individual commits
-        // are not strictly atomic across types, the key-level locking mechanism
-        // ensures that no other script can observe partial state during commit.
-        // A crash during commit could result in partial writes, but this is
-        // acceptable for the current use case. Future versions may implement
-        // a unified batch commit for all types.
-        for (key, op) in complex_ops {
-            match op {
-                ExtendedBatchOp::SetList(list) => {
-                    storage.set_value(self.db_index, key, StoredValue::new_list(list))?;
-                }
-                ExtendedBatchOp::SetHash(hash) => {
-                    storage.set_value(self.db_index, key, StoredValue::new_hash(hash))?;
-                }
-                ExtendedBatchOp::SetSet(set) => {
-                    storage.set_value(self.db_index, key, StoredValue::new_set(set))?;
-                }
-                ExtendedBatchOp::SetZSet(zset) => {
-                    storage.set_value(self.db_index, key, StoredValue::new_zset(zset))?;
-                }
-                // These are already handled above
-                ExtendedBatchOp::SetString(_) | ExtendedBatchOp::Delete => {}
-            }
+    /// Run a command called from the script against the scratch storage
+    fn call(&mut self, command: &str, args: &[Bytes]) -> Result<RespValue> {
+        let info = server::lookup_command(command);
+        if info.is_some_and(|info| info.has_flag("noscript"))
+            || KEYSPACE_COMMANDS.contains(&command)
+        {
+            return Err(AikvError::Script(format!(
+                "This command is not allowed from scripts: {}",
+                command
+            )));
+        }
+
+        let db_index = self.client.db;
+        let keys: Vec<String> = info
+            .map(|info| info.keys(args))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|key| String::from_utf8_lossy(key).to_string())
+            .collect();
+        self.prepare(&keys)?;
+
+        // SORT's BY and GET patterns read keys named by the sorted elements
+        if command == "SORT" || command == "SORT_RO" {
+            let keys = sort::pattern_keys(&self.scratch().storage, args, db_index)?;
+            self.prepare(&keys)?;
+        }
+
+        let scratch = self
+            .scratch
+            .as_ref()
+            .expect("scratch space is only taken when the transaction ends");
+        let reply = scratch.executor.execute(command, args, &mut self.client)?;
+
+        if server::is_write_command(command) {
+            let writes = scratch.executor.aof_entries(command, args, &reply);
+            self.writes.extend(
+                writes
+                    .into_iter()
+                    .map(|(command, args)| (db_index, command, args)),
+            );
         }
+        Ok(reply)
+    }
 
+    /// Commit the transaction - replay the script's writes against the live
+    /// storage, in the order the script made them
+    fn commit(self) -> Result<()> {
+        let live = self
+            .live
+            .get_or_init(|| CommandExecutor::new(self.storage.clone()));
+        let mut client = ClientContext::default();
+        for (db_index, command, args) in &self.writes {
+            client.db = *db_index;
+            live.execute(command, args, &mut client)?;
+        }
         Ok(())
     }
 
     // Note: rollback() is implicit - just drop the transaction without calling commit()
 }
 
+impl Drop for ScriptTransaction {
+    fn drop(&mut self) {
+        self.key_locks.unlock_keys(&self.acquired);
+        if let Some(scratch) = self.scratch.take() {
+            // A scratch space that can't be emptied isn't reused
+            if scratch.storage.flush_all().is_ok() {
+                self.pool
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(scratch);
+            }
+        }
+    }
+}
+
 /// Script command handler with key-level locking for parallel execution.
 ///
 /// This handler supports:
 /// - Key-level locking: Scripts operating on different keys can run in parallel
-/// - Extended command support: Beyond GET/SET/DEL/EXISTS, includes Hash, List, Set, ZSet commands
-/// - Transactional semantics: All writes are buffered and committed atomically
+/// - Full command support: redis.call runs any command through a `CommandExecutor`
+/// - Transactional semantics: All writes are buffered and only replayed if the script succeeds
 pub struct ScriptCommands {
     storage: StorageEngine,
     script_cache: Arc<RwLock<HashMap<String, CachedScript>>>,
     /// Key-level lock manager for parallel script execution
     key_lock_manager: Arc<KeyLockManager>,
+    /// Scripts running on any connection, for SCRIPT KILL
+    registry: Arc<ScriptRegistry>,
+    /// Runtime configuration, for lua-time-limit
+    config: SharedConfig,
+    /// ACL users, which the commands a script calls are checked against
+    acl_users: AclUsers,
+    /// Scratch spaces for scripts to run their commands against
+    scratch_pool: ScratchPool,
+    /// Executor over `storage` that scripts' writes are replayed through,
+    /// created by the first script to commit
+    live_executor: Arc<OnceLock<CommandExecutor>>,
 }
 
 impl ScriptCommands {
@@ -441,6 +563,11 @@ impl ScriptCommands {
             storage,
             script_cache: Arc::new(RwLock::new(HashMap::new())),
             key_lock_manager: Arc::new(KeyLockManager::default()),
+            registry: Arc::new(ScriptRegistry::new()),
+            config: Arc::new(RwLock::new(RuntimeConfig::default())),
+            acl_users: acl::default_users(),
+            scratch_pool: ScratchPool::default(),
+            live_executor: Arc::new(OnceLock::new()),
         }
     }
 
@@ -450,21 +577,82 @@ impl ScriptCommands {
             storage,
             script_cache: Arc::new(RwLock::new(HashMap::new())),
             key_lock_manager: Arc::new(KeyLockManager::new(lock_timeout)),
+            registry: Arc::new(ScriptRegistry::new()),
+            config: Arc::new(RwLock::new(RuntimeConfig::default())),
+            acl_users: acl::default_users(),
+            scratch_pool: ScratchPool::default(),
+            live_executor: Arc::new(OnceLock::new()),
         }
     }
 
+    /// Share the server-wide registry of running scripts
+    pub fn set_script_registry(&mut self, registry: Arc<ScriptRegistry>) {
+        self.registry = registry;
+    }
+
+    /// Share the server-wide runtime configuration
+    pub fn set_runtime_config(&mut self, config: SharedConfig) {
+        self.config = config;
+    }
+
+    /// Share the server's ACL users, so a script's commands are checked
+    /// against the rules of the user running it
+    pub fn set_acl_users(&mut self, users: AclUsers) {
+        self.acl_users = users;
+        // Pooled scratch executors check against the old users
+        self.scratch_pool = ScratchPool::default();
+    }
+
+    /// Longest a script may run before it's aborted, from lua-time-limit
+    ///
+    /// Zero or a negative limit lets scripts run forever.
+    fn time_limit(&self) -> Option<Duration> {
+        let config = self.config.read().ok()?;
+        let limit_ms = config.get("lua-time-limit")?.parse::<i64>().ok()?;
+        (limit_ms > 0).then(|| Duration::from_millis(limit_ms as u64))
+    }
+
     /// Calculate SHA1 hash of a script
-    fn calculate_sha1(script: &str) -> String {
+    fn calculate_sha1(data: impl AsRef<[u8]>) -> String {
         let mut hasher = Sha1::new();
-        hasher.update(script.as_bytes());
+        hasher.update(data.as_ref());
         format!("{:x}", hasher.finalize())
     }
 
     /// EVAL script numkeys [key [key ...]] [arg [arg ...]]
     /// Execute a Lua script
-    pub fn eval(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+    pub fn eval(&self, args: &[Bytes], client: &ClientContext) -> Result<RespValue> {
+        self.eval_script("EVAL", args, client, false)
+    }
+
+    /// EVAL_RO script numkeys [key [key ...]] [arg [arg ...]]
+    /// Execute a Lua script that may only call read-only commands
+    pub fn eval_ro(&self, args: &[Bytes], client: &ClientContext) -> Result<RespValue> {
+        self.eval_script("EVAL_RO", args, client, true)
+    }
+
+    /// EVALSHA sha1 numkeys [key [key ...]] [arg [arg ...]]
+    /// Execute a cached script by its SHA1 digest
+    pub fn evalsha(&self, args: &[Bytes], client: &ClientContext) -> Result<RespValue> {
+        self.evalsha_script("EVALSHA", args, client, false)
+    }
+
+    /// EVALSHA_RO sha1 numkeys [key [key ...]] [arg [arg ...]]
+    /// Execute a cached script that may only call read-only commands
+    pub fn evalsha_ro(&self, args: &[Bytes], client: &ClientContext) -> Result<RespValue> {
+        self.evalsha_script("EVALSHA_RO", args, client, true)
+    }
+
+    /// Shared implementation of EVAL and EVAL_RO
+    fn eval_script(
+        &self,
+        command: &str,
+        args: &[Bytes],
+        client: &ClientContext,
+        read_only: bool,
+    ) -> Result<RespValue> {
         if args.len() < 2 {
-            return Err(AikvError::WrongArgCount("EVAL".to_string()));
+            return Err(AikvError::WrongArgCount(command.to_string()));
         }
 
         let script = String::from_utf8_lossy(&args[0]).to_string();
@@ -488,14 +676,19 @@ impl ScriptCommands {
             .map(|b| String::from_utf8_lossy(b).to_string())
             .collect();
 
-        self.execute_script(&script, &keys, &argv, db_index)
+        self.execute_script(&script, &keys, &argv, client, read_only)
     }
 
-    /// EVALSHA sha1 numkeys [key [key ...]] [arg [arg ...]]
-    /// Execute a cached script by its SHA1 digest
-    pub fn evalsha(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+    /// Shared implementation of EVALSHA and EVALSHA_RO
+    fn evalsha_script(
+        &self,
+        command: &str,
+        args: &[Bytes],
+        client: &ClientContext,
+        read_only: bool,
+    ) -> Result<RespValue> {
         if args.len() < 2 {
-            return Err(AikvError::WrongArgCount("EVALSHA".to_string()));
+            return Err(AikvError::WrongArgCount(command.to_string()));
         }
 
         let sha1 = String::from_utf8_lossy(&args[0]).to_string();
@@ -532,7 +725,30 @@ impl ScriptCommands {
             .map(|b| String::from_utf8_lossy(b).to_string())
             .collect();
 
-        self.execute_script(&script, &keys, &argv, db_index)
+        self.execute_script(&script, &keys, &argv, client, read_only)
+    }
+
+    /// Get the body of a cached script by its SHA1 digest
+    pub fn cached_script(&self, sha1: &str) -> Option<String> {
+        let cache = self.script_cache.read().ok()?;
+        cache.get(sha1).map(|cached| cached.script.clone())
+    }
+
+    /// SCRIPT HELP
+    pub fn script_help(&self) -> Result<RespValue> {
+        Ok(help_reply(
+            "SCRIPT",
+            &[
+                "EXISTS <sha1> [<sha1> ...]",
+                "    Return information about the existence of the scripts in the script cache.",
+                "FLUSH [ASYNC|SYNC]",
+                "    Flush the Lua scripts cache.",
+                "KILL",
+                "    Kill the currently executing Lua script.",
+                "LOAD <script>",
+                "    Load a script into the scripts cache without executing it.",
+            ],
+        ))
     }
 
     /// SCRIPT LOAD script
@@ -597,13 +813,17 @@ impl ScriptCommands {
     }
 
     /// SCRIPT KILL
-    /// Kill the currently executing script (not implemented for now)
+    /// Kill the scripts currently executing on any connection
+    ///
+    /// A killed script's writes are still buffered in its transaction, so
+    /// aborting it simply discards them.
     pub fn script_kill(&self, _args: &[Bytes]) -> Result<RespValue> {
-        // In a single-threaded execution model, this is not really applicable
-        // Return NOTBUSY when no script is running
-        Err(AikvError::InvalidArgument(
-            "NOTBUSY No scripts in execution right now.".to_string(),
-        ))
+        if self.registry.kill_all() == 0 {
+            return Err(AikvError::InvalidArgument(
+                "NOTBUSY No scripts in execution right now.".to_string(),
+            ));
+        }
+        Ok(RespValue::simple_string("OK"))
     }
 
     /// Execute a Lua script with given keys and arguments
@@ -616,13 +836,24 @@ impl ScriptCommands {
         script: &str,
         keys: &[String],
         argv: &[String],
-        db_index: usize,
+        client: &ClientContext,
+        read_only: bool,
     ) -> Result<RespValue> {
         // Acquire key locks before execution (enables parallel execution for different keys)
         let _lock_guard = self.key_lock_manager.lock_keys(keys)?;
 
-        // Create transaction context for this script execution
-        let transaction = Arc::new(RwLock::new(ScriptTransaction::new(db_index)));
+        // Register the script so SCRIPT KILL can reach it; it's unregistered
+        // when the guard drops, however the script ends
+        let run_guard = self.registry.start();
+        let time_limit = self.time_limit();
+
+        // Create transaction context for this script execution, with the
+        // declared keys loaded up front
+        let mut transaction = ScriptTransaction::new(self, keys, client);
+        for key in keys {
+            transaction.load(client.db, key)?;
+        }
+        let transaction = Arc::new(Mutex::new(transaction));
 
         // Execute the script in a scope to ensure Lua is dropped before we commit
         let resp_result = {
@@ -633,6 +864,26 @@ impl ScriptCommands {
             )
             .map_err(|e| AikvError::Script(format!("Failed to create Lua instance: {}", e)))?;
 
+            // Abort the script when it's killed or runs past lua-time-limit
+            let run = Arc::clone(&run_guard.script);
+            lua.set_hook(
+                HookTriggers::new().every_nth_instruction(SCRIPT_HOOK_INSTRUCTIONS),
+                move |_, _| {
+                    if run.kill_requested.load(Ordering::Relaxed) {
+                        return Err(mlua::Error::RuntimeError(
+                            "ERR Script killed by user with SCRIPT KILL...".to_string(),
+                        ));
+                    }
+                    if let Some(limit) = time_limit.filter(|limit| run.started.elapsed() > *limit) {
+                        return Err(mlua::Error::RuntimeError(format!(
+                            "ERR Script exceeded lua-time-limit of {} ms",
+                            limit.as_millis()
+                        )));
+                    }
+                    Ok(VmState::Continue)
+                },
+            );
+
             // Set up KEYS and ARGV tables
             lua.globals()
                 .set("KEYS", lua.create_table().unwrap())
@@ -659,8 +910,6 @@ impl ScriptCommands {
             }
 
             // Set up redis.call and redis.pcall functions
-            let storage = self.storage.clone();
-
             lua.globals()
                 .set(
                     "redis",
@@ -673,11 +922,10 @@ impl ScriptCommands {
             let redis_table = lua.globals().get::<mlua::Table>("redis").unwrap();
 
             // redis.call - Execute Redis command (throws error on failure)
-            let storage_for_call = storage.clone();
             let txn_for_call = transaction.clone();
             let call_fn = lua
                 .create_function(move |lua_ctx, args: mlua::MultiValue| {
-                    Self::redis_call(&storage_for_call, &txn_for_call, lua_ctx, args, true)
+                    Self::redis_call(&txn_for_call, lua_ctx, args, true, read_only)
                 })
                 .map_err(|e| AikvError::Script(format!("Failed to create call function: {}", e)))?;
 
@@ -686,11 +934,10 @@ impl ScriptCommands {
                 .map_err(|e| AikvError::Script(format!("Failed to set redis.call: {}", e)))?;
 
             // redis.pcall - Protected call (returns error as result)
-            let storage_for_pcall = storage.clone();
             let txn_for_pcall = transaction.clone();
             let pcall_fn = lua
                 .create_function(move |lua_ctx, args: mlua::MultiValue| {
-                    Self::redis_call(&storage_for_pcall, &txn_for_pcall, lua_ctx, args, false)
+                    Self::redis_call(&txn_for_pcall, lua_ctx, args, false, read_only)
                 })
                 .map_err(|e| {
                     AikvError::Script(format!("Failed to create pcall function: {}", e))
@@ -700,6 +947,9 @@ impl ScriptCommands {
                 .set("pcall", pcall_fn)
                 .map_err(|e| AikvError::Script(format!("Failed to set redis.pcall: {}", e)))?;
 
+            Self::register_helpers(&lua, &redis_table)?;
+            Self::register_cjson(&lua)?;
+
             // Execute the script
             let result: LuaValue = lua
                 .load(script)
@@ -718,7 +968,7 @@ impl ScriptCommands {
             .into_inner()
             .map_err(|e| AikvError::Script(format!("Lock error on commit: {}", e)))?;
 
-        txn.commit(&self.storage)?;
+        txn.commit()?;
 
         // Return the converted result
         Ok(resp_result)
@@ -726,11 +976,11 @@ impl ScriptCommands {
 
     /// Execute a Redis command from Lua
     fn redis_call(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
+        transaction: &Arc<Mutex<ScriptTransaction>>,
         lua: &mlua::Lua,
         args: mlua::MultiValue,
         throw_error: bool,
+        read_only: bool,
     ) -> mlua::Result<LuaValue> {
         // Convert arguments to bytes
         let mut cmd_args: Vec<Bytes> = Vec::new();
@@ -777,71 +1027,15 @@ impl ScriptCommands {
             .to_string();
         let command_args = &cmd_args[1..];
 
-        // Execute commands - extended support for all data types
-        let result = match command.as_str() {
-            // String commands
-            "GET" => Self::execute_get(storage, transaction, command_args),
-            "SET" => Self::execute_set(storage, transaction, command_args),
-            "DEL" => Self::execute_del(storage, transaction, command_args),
-            "EXISTS" => Self::execute_exists(storage, transaction, command_args),
-            "INCR" => Self::execute_incr(storage, transaction, command_args),
-            "DECR" => Self::execute_decr(storage, transaction, command_args),
-            "INCRBY" => Self::execute_incrby(storage, transaction, command_args),
-            "DECRBY" => Self::execute_decrby(storage, transaction, command_args),
-            "INCRBYFLOAT" => Self::execute_incrbyfloat(storage, transaction, command_args),
-            "APPEND" => Self::execute_append(storage, transaction, command_args),
-            "STRLEN" => Self::execute_strlen(storage, transaction, command_args),
-
-            // Hash commands
-            "HGET" => Self::execute_hget(storage, transaction, command_args),
-            "HSET" => Self::execute_hset(storage, transaction, command_args),
-            "HDEL" => Self::execute_hdel(storage, transaction, command_args),
-            "HGETALL" => Self::execute_hgetall(storage, transaction, command_args),
-            "HMGET" => Self::execute_hmget(storage, transaction, command_args),
-            "HMSET" => Self::execute_hmset(storage, transaction, command_args),
-            "HINCRBY" => Self::execute_hincrby(storage, transaction, command_args),
-            "HEXISTS" => Self::execute_hexists(storage, transaction, command_args),
-            "HLEN" => Self::execute_hlen(storage, transaction, command_args),
-
-            // List commands
-            "LPUSH" => Self::execute_lpush(storage, transaction, command_args),
-            "RPUSH" => Self::execute_rpush(storage, transaction, command_args),
-            "LPOP" => Self::execute_lpop(storage, transaction, command_args),
-            "RPOP" => Self::execute_rpop(storage, transaction, command_args),
-            "LLEN" => Self::execute_llen(storage, transaction, command_args),
-            "LRANGE" => Self::execute_lrange(storage, transaction, command_args),
-            "LINDEX" => Self::execute_lindex(storage, transaction, command_args),
-
-            // Set commands
-            "SADD" => Self::execute_sadd(storage, transaction, command_args),
-            "SREM" => Self::execute_srem(storage, transaction, command_args),
-            "SMEMBERS" => Self::execute_smembers(storage, transaction, command_args),
-            "SISMEMBER" => Self::execute_sismember(storage, transaction, command_args),
-            "SCARD" => Self::execute_scard(storage, transaction, command_args),
-
-            // Sorted Set commands
-            "ZADD" => Self::execute_zadd(storage, transaction, command_args),
-            "ZREM" => Self::execute_zrem(storage, transaction, command_args),
-            "ZSCORE" => Self::execute_zscore(storage, transaction, command_args),
-            "ZRANK" => Self::execute_zrank(storage, transaction, command_args),
-            "ZRANGE" => Self::execute_zrange(storage, transaction, command_args),
-            "ZCARD" => Self::execute_zcard(storage, transaction, command_args),
-
-            // Set commands
-            "SSCAN" => Self::execute_sscan(storage, transaction, command_args),
-
-            // Key commands
-            "EXPIRE" => Self::execute_expire(storage, transaction, command_args),
-
-            _ => {
-                if throw_error {
-                    return Err(mlua::Error::RuntimeError(format!(
-                        "Command not supported in scripts: {}",
-                        command
-                    )));
-                } else {
-                    return Ok(LuaValue::Nil);
-                }
+        // Execute the command through the transaction, so writes stay buffered
+        let result = if read_only && server::is_write_command(&command) {
+            Err(AikvError::Script(
+                "Write commands are not allowed from read-only scripts".to_string(),
+            ))
+        } else {
+            match transaction.lock() {
+                Ok(mut txn) => txn.call(&command, command_args),
+                Err(e) => Err(AikvError::Script(format!("Lock error: {}", e))),
             }
         };
 
@@ -850,1333 +1044,98 @@ impl ScriptCommands {
             Err(e) => {
                 if throw_error {
                     Err(mlua::Error::RuntimeError(format!(
-                        "Command execution error: {}",
-                        e
-                    )))
-                } else {
-                    Ok(LuaValue::Nil)
-                }
-            }
-        }
-    }
-
-    /// Execute GET command
-    fn execute_get(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 1 {
-            return Err(AikvError::WrongArgCount("GET".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        match txn.get(storage, &key)? {
-            Some(value) => Ok(RespValue::bulk_string(value)),
-            None => Ok(RespValue::Null),
-        }
-    }
-
-    /// Execute SET command
-    fn execute_set(
-        _storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() < 2 {
-            return Err(AikvError::WrongArgCount("SET".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let value = args[1].clone();
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        txn.set(key, value);
-        Ok(RespValue::simple_string("OK"))
-    }
-
-    /// Execute DEL command
-    fn execute_del(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.is_empty() {
-            return Err(AikvError::WrongArgCount("DEL".to_string()));
-        }
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let mut count = 0;
-        for arg in args {
-            let key = String::from_utf8_lossy(arg).to_string();
-            // Check if key exists (in buffer or storage)
-            if txn.exists(storage, &key)? {
-                txn.delete(key);
-                count += 1;
-            }
-        }
-        Ok(RespValue::Integer(count))
-    }
-
-    /// Execute EXISTS command
-    fn execute_exists(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.is_empty() {
-            return Err(AikvError::WrongArgCount("EXISTS".to_string()));
-        }
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let mut count = 0;
-        for arg in args {
-            let key = String::from_utf8_lossy(arg).to_string();
-            if txn.exists(storage, &key)? {
-                count += 1;
-            }
-        }
-        Ok(RespValue::Integer(count))
-    }
-
-    // ========================================================================
-    // EXTENDED STRING COMMANDS
-    // ========================================================================
-
-    /// Execute INCR command
-    fn execute_incr(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 1 {
-            return Err(AikvError::WrongArgCount("INCR".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let current: i64 = match txn.get(storage, &key)? {
-            Some(v) => String::from_utf8_lossy(&v)
-                .parse()
-                .map_err(|_| AikvError::InvalidArgument("value is not an integer".to_string()))?,
-            None => 0,
-        };
-
-        let new_val = current + 1;
-        txn.set(key, Bytes::from(new_val.to_string()));
-        Ok(RespValue::Integer(new_val))
-    }
-
-    /// Execute DECR command
-    fn execute_decr(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 1 {
-            return Err(AikvError::WrongArgCount("DECR".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let current: i64 = match txn.get(storage, &key)? {
-            Some(v) => String::from_utf8_lossy(&v)
-                .parse()
-                .map_err(|_| AikvError::InvalidArgument("value is not an integer".to_string()))?,
-            None => 0,
-        };
-
-        let new_val = current - 1;
-        txn.set(key, Bytes::from(new_val.to_string()));
-        Ok(RespValue::Integer(new_val))
-    }
-
-    /// Execute INCRBY command
-    fn execute_incrby(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 2 {
-            return Err(AikvError::WrongArgCount("INCRBY".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let increment: i64 = String::from_utf8_lossy(&args[1])
-            .parse()
-            .map_err(|_| AikvError::InvalidArgument("increment is not an integer".to_string()))?;
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let current: i64 = match txn.get(storage, &key)? {
-            Some(v) => String::from_utf8_lossy(&v)
-                .parse()
-                .map_err(|_| AikvError::InvalidArgument("value is not an integer".to_string()))?,
-            None => 0,
-        };
-
-        let new_val = current + increment;
-        txn.set(key, Bytes::from(new_val.to_string()));
-        Ok(RespValue::Integer(new_val))
-    }
-
-    /// Execute DECRBY command
-    fn execute_decrby(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 2 {
-            return Err(AikvError::WrongArgCount("DECRBY".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let decrement: i64 = String::from_utf8_lossy(&args[1])
-            .parse()
-            .map_err(|_| AikvError::InvalidArgument("decrement is not an integer".to_string()))?;
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let current: i64 = match txn.get(storage, &key)? {
-            Some(v) => String::from_utf8_lossy(&v)
-                .parse()
-                .map_err(|_| AikvError::InvalidArgument("value is not an integer".to_string()))?,
-            None => 0,
-        };
-
-        let new_val = current - decrement;
-        txn.set(key, Bytes::from(new_val.to_string()));
-        Ok(RespValue::Integer(new_val))
-    }
-
-    /// Execute INCRBYFLOAT command
-    fn execute_incrbyfloat(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 2 {
-            return Err(AikvError::WrongArgCount("INCRBYFLOAT".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let increment: f64 = String::from_utf8_lossy(&args[1])
-            .parse()
-            .map_err(|_| AikvError::InvalidArgument("increment is not a float".to_string()))?;
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let current: f64 = match txn.get(storage, &key)? {
-            Some(v) => String::from_utf8_lossy(&v)
-                .parse()
-                .map_err(|_| AikvError::InvalidArgument("value is not a float".to_string()))?,
-            None => 0.0,
-        };
-
-        let new_val = current + increment;
-        let result_str = format!("{}", new_val);
-        txn.set(key, Bytes::from(result_str.clone()));
-        Ok(RespValue::bulk_string(Bytes::from(result_str)))
-    }
-
-    /// Execute APPEND command
-    fn execute_append(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 2 {
-            return Err(AikvError::WrongArgCount("APPEND".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let value = &args[1];
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let mut current = match txn.get(storage, &key)? {
-            Some(v) => v.to_vec(),
-            None => Vec::new(),
-        };
-
-        current.extend_from_slice(value);
-        let len = current.len() as i64;
-        txn.set(key, Bytes::from(current));
-        Ok(RespValue::Integer(len))
-    }
-
-    /// Execute STRLEN command
-    fn execute_strlen(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 1 {
-            return Err(AikvError::WrongArgCount("STRLEN".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let len = match txn.get(storage, &key)? {
-            Some(v) => v.len() as i64,
-            None => 0,
-        };
-        Ok(RespValue::Integer(len))
-    }
-
-    // ========================================================================
-    // HASH COMMANDS
-    // ========================================================================
-
-    /// Execute HGET command
-    fn execute_hget(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 2 {
-            return Err(AikvError::WrongArgCount("HGET".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let field = String::from_utf8_lossy(&args[1]).to_string();
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let hash = stored.as_hash()?;
-            match hash.get(&field) {
-                Some(value) => Ok(RespValue::bulk_string(value.clone())),
-                None => Ok(RespValue::Null),
-            }
-        } else {
-            Ok(RespValue::Null)
-        }
-    }
-
-    /// Execute HSET command
-    fn execute_hset(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() < 3 || args.len() % 2 == 0 {
-            return Err(AikvError::WrongArgCount("HSET".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let mut hash = if let Some(stored) = txn.get_value(storage, &key)? {
-            stored.as_hash()?.clone()
-        } else {
-            HashMap::new()
-        };
-
-        let mut count = 0;
-        for i in (1..args.len()).step_by(2) {
-            let field = String::from_utf8_lossy(&args[i]).to_string();
-            let value = args[i + 1].clone();
-            if hash.insert(field, value).is_none() {
-                count += 1;
-            }
-        }
-
-        txn.set_hash(key, hash);
-        Ok(RespValue::Integer(count))
-    }
-
-    /// Execute HDEL command
-    fn execute_hdel(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() < 2 {
-            return Err(AikvError::WrongArgCount("HDEL".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let mut hash = stored.as_hash()?.clone();
-            let mut count = 0;
-            for arg in &args[1..] {
-                let field = String::from_utf8_lossy(arg).to_string();
-                if hash.remove(&field).is_some() {
-                    count += 1;
-                }
-            }
-            if hash.is_empty() {
-                txn.delete(key);
-            } else {
-                txn.set_hash(key, hash);
-            }
-            Ok(RespValue::Integer(count))
-        } else {
-            Ok(RespValue::Integer(0))
-        }
-    }
-
-    /// Execute HGETALL command
-    fn execute_hgetall(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 1 {
-            return Err(AikvError::WrongArgCount("HGETALL".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let hash = stored.as_hash()?;
-            let mut result = Vec::new();
-            for (field, value) in hash {
-                result.push(RespValue::bulk_string(Bytes::from(field.clone())));
-                result.push(RespValue::bulk_string(value.clone()));
-            }
-            Ok(RespValue::Array(Some(result)))
-        } else {
-            Ok(RespValue::Array(Some(Vec::new())))
-        }
-    }
-
-    /// Execute HMGET command
-    fn execute_hmget(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() < 2 {
-            return Err(AikvError::WrongArgCount("HMGET".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let stored = txn.get_value(storage, &key)?;
-        let hash = match &stored {
-            Some(s) => Some(s.as_hash()?),
-            None => None,
-        };
-
-        let result: Vec<RespValue> = args[1..]
-            .iter()
-            .map(|arg| {
-                let field = String::from_utf8_lossy(arg).to_string();
-                match &hash {
-                    Some(h) => match h.get(&field) {
-                        Some(v) => RespValue::bulk_string(v.clone()),
-                        None => RespValue::Null,
-                    },
-                    None => RespValue::Null,
-                }
-            })
-            .collect();
-
-        Ok(RespValue::Array(Some(result)))
-    }
-
-    /// Execute HMSET command
-    fn execute_hmset(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() < 3 || args.len() % 2 == 0 {
-            return Err(AikvError::WrongArgCount("HMSET".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let mut hash = if let Some(stored) = txn.get_value(storage, &key)? {
-            stored.as_hash()?.clone()
-        } else {
-            HashMap::new()
-        };
-
-        for i in (1..args.len()).step_by(2) {
-            let field = String::from_utf8_lossy(&args[i]).to_string();
-            let value = args[i + 1].clone();
-            hash.insert(field, value);
-        }
-
-        txn.set_hash(key, hash);
-        Ok(RespValue::simple_string("OK"))
-    }
-
-    /// Execute HINCRBY command
-    fn execute_hincrby(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 3 {
-            return Err(AikvError::WrongArgCount("HINCRBY".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let field = String::from_utf8_lossy(&args[1]).to_string();
-        let increment: i64 = String::from_utf8_lossy(&args[2])
-            .parse()
-            .map_err(|_| AikvError::InvalidArgument("increment is not an integer".to_string()))?;
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let mut hash = if let Some(stored) = txn.get_value(storage, &key)? {
-            stored.as_hash()?.clone()
-        } else {
-            HashMap::new()
-        };
-
-        let current: i64 = match hash.get(&field) {
-            Some(v) => String::from_utf8_lossy(v).parse().map_err(|_| {
-                AikvError::InvalidArgument("field value is not an integer".to_string())
-            })?,
-            None => 0,
-        };
-
-        let new_val = current + increment;
-        hash.insert(field, Bytes::from(new_val.to_string()));
-        txn.set_hash(key, hash);
-        Ok(RespValue::Integer(new_val))
-    }
-
-    /// Execute HEXISTS command
-    fn execute_hexists(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 2 {
-            return Err(AikvError::WrongArgCount("HEXISTS".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let field = String::from_utf8_lossy(&args[1]).to_string();
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let hash = stored.as_hash()?;
-            Ok(RespValue::Integer(if hash.contains_key(&field) {
-                1
-            } else {
-                0
-            }))
-        } else {
-            Ok(RespValue::Integer(0))
-        }
-    }
-
-    /// Execute HLEN command
-    fn execute_hlen(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 1 {
-            return Err(AikvError::WrongArgCount("HLEN".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let hash = stored.as_hash()?;
-            Ok(RespValue::Integer(hash.len() as i64))
-        } else {
-            Ok(RespValue::Integer(0))
-        }
-    }
-
-    // ========================================================================
-    // LIST COMMANDS
-    // ========================================================================
-
-    /// Execute LPUSH command
-    fn execute_lpush(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() < 2 {
-            return Err(AikvError::WrongArgCount("LPUSH".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let mut list = if let Some(stored) = txn.get_value(storage, &key)? {
-            stored.as_list()?.clone()
-        } else {
-            VecDeque::new()
-        };
-
-        for i in (1..args.len()).rev() {
-            list.push_front(args[i].clone());
-        }
-
-        let len = list.len() as i64;
-        txn.set_list(key, list);
-        Ok(RespValue::Integer(len))
-    }
-
-    /// Execute RPUSH command
-    fn execute_rpush(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() < 2 {
-            return Err(AikvError::WrongArgCount("RPUSH".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let mut list = if let Some(stored) = txn.get_value(storage, &key)? {
-            stored.as_list()?.clone()
-        } else {
-            VecDeque::new()
-        };
-
-        for arg in &args[1..] {
-            list.push_back(arg.clone());
-        }
-
-        let len = list.len() as i64;
-        txn.set_list(key, list);
-        Ok(RespValue::Integer(len))
-    }
-
-    /// Execute LPOP command
-    fn execute_lpop(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.is_empty() {
-            return Err(AikvError::WrongArgCount("LPOP".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let count = if args.len() > 1 {
-            String::from_utf8_lossy(&args[1])
-                .parse::<usize>()
-                .map_err(|_| {
-                    AikvError::InvalidArgument("count is not a valid integer".to_string())
-                })?
-        } else {
-            1
-        };
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let mut list = stored.as_list()?.clone();
-
-            if count == 1 && args.len() == 1 {
-                // Single element, return as bulk string
-                if let Some(value) = list.pop_front() {
-                    if list.is_empty() {
-                        txn.delete(key);
-                    } else {
-                        txn.set_list(key, list);
-                    }
-                    return Ok(RespValue::bulk_string(value));
-                }
-            } else {
-                // Multiple elements, return as array
-                let mut result = Vec::new();
-                for _ in 0..count {
-                    if let Some(value) = list.pop_front() {
-                        result.push(RespValue::bulk_string(value));
-                    } else {
-                        break;
-                    }
-                }
-                if list.is_empty() {
-                    txn.delete(key);
-                } else {
-                    txn.set_list(key, list);
-                }
-                if result.is_empty() {
-                    return Ok(RespValue::Null);
-                }
-                return Ok(RespValue::Array(Some(result)));
-            }
-        }
-        Ok(RespValue::Null)
-    }
-
-    /// Execute RPOP command
-    fn execute_rpop(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.is_empty() {
-            return Err(AikvError::WrongArgCount("RPOP".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let count = if args.len() > 1 {
-            String::from_utf8_lossy(&args[1])
-                .parse::<usize>()
-                .map_err(|_| {
-                    AikvError::InvalidArgument("count is not a valid integer".to_string())
-                })?
-        } else {
-            1
-        };
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let mut list = stored.as_list()?.clone();
-
-            if count == 1 && args.len() == 1 {
-                // Single element, return as bulk string
-                if let Some(value) = list.pop_back() {
-                    if list.is_empty() {
-                        txn.delete(key);
-                    } else {
-                        txn.set_list(key, list);
-                    }
-                    return Ok(RespValue::bulk_string(value));
-                }
-            } else {
-                // Multiple elements, return as array
-                let mut result = Vec::new();
-                for _ in 0..count {
-                    if let Some(value) = list.pop_back() {
-                        result.push(RespValue::bulk_string(value));
-                    } else {
-                        break;
-                    }
-                }
-                if list.is_empty() {
-                    txn.delete(key);
-                } else {
-                    txn.set_list(key, list);
-                }
-                if result.is_empty() {
-                    return Ok(RespValue::Null);
-                }
-                return Ok(RespValue::Array(Some(result)));
-            }
-        }
-        Ok(RespValue::Null)
-    }
-
-    /// Execute LLEN command
-    fn execute_llen(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 1 {
-            return Err(AikvError::WrongArgCount("LLEN".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let list = stored.as_list()?;
-            Ok(RespValue::Integer(list.len() as i64))
-        } else {
-            Ok(RespValue::Integer(0))
-        }
-    }
-
-    /// Execute LRANGE command
-    fn execute_lrange(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 3 {
-            return Err(AikvError::WrongArgCount("LRANGE".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let start: i64 = String::from_utf8_lossy(&args[1])
-            .parse()
-            .map_err(|_| AikvError::InvalidArgument("start is not an integer".to_string()))?;
-        let stop: i64 = String::from_utf8_lossy(&args[2])
-            .parse()
-            .map_err(|_| AikvError::InvalidArgument("stop is not an integer".to_string()))?;
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let list = stored.as_list()?;
-            let len = list.len() as i64;
-
-            let start_idx = if start < 0 {
-                (len + start).max(0)
-            } else {
-                start.min(len)
-            } as usize;
-            let stop_idx = if stop < 0 {
-                (len + stop + 1).max(0)
-            } else {
-                (stop + 1).min(len)
-            } as usize;
-
-            if start_idx >= stop_idx {
-                return Ok(RespValue::Array(Some(Vec::new())));
-            }
-
-            let result: Vec<RespValue> = list
-                .iter()
-                .skip(start_idx)
-                .take(stop_idx - start_idx)
-                .map(|v| RespValue::bulk_string(v.clone()))
-                .collect();
-
-            Ok(RespValue::Array(Some(result)))
-        } else {
-            Ok(RespValue::Array(Some(Vec::new())))
-        }
-    }
-
-    /// Execute LINDEX command
-    fn execute_lindex(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 2 {
-            return Err(AikvError::WrongArgCount("LINDEX".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let index: i64 = String::from_utf8_lossy(&args[1])
-            .parse()
-            .map_err(|_| AikvError::InvalidArgument("index is not an integer".to_string()))?;
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let list = stored.as_list()?;
-            let len = list.len() as i64;
-
-            let actual_idx = if index < 0 { len + index } else { index };
-
-            if actual_idx < 0 || actual_idx >= len {
-                return Ok(RespValue::Null);
-            }
-
-            if let Some(value) = list.get(actual_idx as usize) {
-                return Ok(RespValue::bulk_string(value.clone()));
-            }
-        }
-        Ok(RespValue::Null)
-    }
-
-    // ========================================================================
-    // SET COMMANDS
-    // ========================================================================
-
-    /// Execute SADD command
-    fn execute_sadd(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() < 2 {
-            return Err(AikvError::WrongArgCount("SADD".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let mut set = if let Some(stored) = txn.get_value(storage, &key)? {
-            stored.as_set()?.clone()
-        } else {
-            HashSet::new()
-        };
-
-        let mut count = 0;
-        for arg in &args[1..] {
-            if set.insert(arg.to_vec()) {
-                count += 1;
-            }
-        }
-
-        txn.set_set(key, set);
-        Ok(RespValue::Integer(count))
-    }
-
-    /// Execute SREM command
-    fn execute_srem(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() < 2 {
-            return Err(AikvError::WrongArgCount("SREM".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let mut set = stored.as_set()?.clone();
-            let mut count = 0;
-            for arg in &args[1..] {
-                if set.remove(&arg.to_vec()) {
-                    count += 1;
-                }
-            }
-            if set.is_empty() {
-                txn.delete(key);
-            } else {
-                txn.set_set(key, set);
-            }
-            Ok(RespValue::Integer(count))
-        } else {
-            Ok(RespValue::Integer(0))
-        }
-    }
-
-    /// Execute SMEMBERS command
-    fn execute_smembers(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 1 {
-            return Err(AikvError::WrongArgCount("SMEMBERS".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let set = stored.as_set()?;
-            let result: Vec<RespValue> = set
-                .iter()
-                .map(|v| RespValue::bulk_string(Bytes::from(v.clone())))
-                .collect();
-            Ok(RespValue::Array(Some(result)))
-        } else {
-            Ok(RespValue::Array(Some(Vec::new())))
-        }
-    }
-
-    /// Execute SISMEMBER command
-    fn execute_sismember(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 2 {
-            return Err(AikvError::WrongArgCount("SISMEMBER".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let member = args[1].to_vec();
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let set = stored.as_set()?;
-            Ok(RespValue::Integer(if set.contains(&member) {
-                1
-            } else {
-                0
-            }))
-        } else {
-            Ok(RespValue::Integer(0))
-        }
-    }
-
-    /// Execute SCARD command
-    fn execute_scard(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 1 {
-            return Err(AikvError::WrongArgCount("SCARD".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let set = stored.as_set()?;
-            Ok(RespValue::Integer(set.len() as i64))
-        } else {
-            Ok(RespValue::Integer(0))
-        }
-    }
-
-    // ========================================================================
-    // SORTED SET COMMANDS
-    // ========================================================================
-
-    /// Execute ZADD command
-    fn execute_zadd(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() < 3 || (args.len() - 1) % 2 != 0 {
-            return Err(AikvError::WrongArgCount("ZADD".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        let mut zset = if let Some(stored) = txn.get_value(storage, &key)? {
-            stored.as_zset()?.clone()
-        } else {
-            BTreeMap::new()
-        };
-
-        let mut count = 0;
-        for i in (1..args.len()).step_by(2) {
-            let score: f64 = String::from_utf8_lossy(&args[i])
-                .parse()
-                .map_err(|_| AikvError::InvalidArgument("score is not a float".to_string()))?;
-            let member = args[i + 1].to_vec();
-            if zset.insert(member, score).is_none() {
-                count += 1;
-            }
-        }
-
-        txn.set_zset(key, zset);
-        Ok(RespValue::Integer(count))
-    }
-
-    /// Execute ZREM command
-    fn execute_zrem(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() < 2 {
-            return Err(AikvError::WrongArgCount("ZREM".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-
-        let mut txn = transaction
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let mut zset = stored.as_zset()?.clone();
-            let mut count = 0;
-            for arg in &args[1..] {
-                if zset.remove(&arg.to_vec()).is_some() {
-                    count += 1;
-                }
-            }
-            if zset.is_empty() {
-                txn.delete(key);
-            } else {
-                txn.set_zset(key, zset);
-            }
-            Ok(RespValue::Integer(count))
-        } else {
-            Ok(RespValue::Integer(0))
-        }
-    }
-
-    /// Execute ZSCORE command
-    fn execute_zscore(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 2 {
-            return Err(AikvError::WrongArgCount("ZSCORE".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let member = args[1].to_vec();
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let zset = stored.as_zset()?;
-            match zset.get(&member) {
-                Some(score) => Ok(RespValue::bulk_string(Bytes::from(score.to_string()))),
-                None => Ok(RespValue::Null),
-            }
-        } else {
-            Ok(RespValue::Null)
-        }
-    }
-
-    /// Execute ZRANK command
-    fn execute_zrank(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 2 {
-            return Err(AikvError::WrongArgCount("ZRANK".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let member = args[1].to_vec();
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let zset = stored.as_zset()?;
-            if !zset.contains_key(&member) {
-                return Ok(RespValue::Null);
-            }
-
-            // Sort by score and find rank
-            let mut sorted: Vec<_> = zset.iter().collect();
-            sorted.sort_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal));
-
-            for (rank, (m, _)) in sorted.iter().enumerate() {
-                if *m == &member {
-                    return Ok(RespValue::Integer(rank as i64));
-                }
-            }
-        }
-        Ok(RespValue::Null)
-    }
-
-    /// Execute ZRANGE command
-    fn execute_zrange(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() < 3 {
-            return Err(AikvError::WrongArgCount("ZRANGE".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let start: i64 = String::from_utf8_lossy(&args[1])
-            .parse()
-            .map_err(|_| AikvError::InvalidArgument("start is not an integer".to_string()))?;
-        let stop: i64 = String::from_utf8_lossy(&args[2])
-            .parse()
-            .map_err(|_| AikvError::InvalidArgument("stop is not an integer".to_string()))?;
-
-        let with_scores =
-            args.len() > 3 && String::from_utf8_lossy(&args[3]).to_uppercase() == "WITHSCORES";
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let zset = stored.as_zset()?;
-            let len = zset.len() as i64;
-
-            // Sort by score
-            let mut sorted: Vec<_> = zset.iter().collect();
-            sorted.sort_by(|a, b| {
-                a.1.partial_cmp(b.1)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-                    .then_with(|| a.0.cmp(b.0))
-            });
-
-            let start_idx = if start < 0 {
-                (len + start).max(0)
-            } else {
-                start.min(len)
-            } as usize;
-            let stop_idx = if stop < 0 {
-                (len + stop + 1).max(0)
-            } else {
-                (stop + 1).min(len)
-            } as usize;
-
-            if start_idx >= stop_idx {
-                return Ok(RespValue::Array(Some(Vec::new())));
-            }
-
-            let mut result = Vec::new();
-            for (member, score) in sorted.iter().skip(start_idx).take(stop_idx - start_idx) {
-                result.push(RespValue::bulk_string(Bytes::from((*member).clone())));
-                if with_scores {
-                    result.push(RespValue::bulk_string(Bytes::from(score.to_string())));
+                        "Command execution error: {}",
+                        e
+                    )))
+                } else {
+                    Ok(LuaValue::Nil)
                 }
             }
-            Ok(RespValue::Array(Some(result)))
-        } else {
-            Ok(RespValue::Array(Some(Vec::new())))
         }
     }
 
-    /// Execute ZCARD command
-    fn execute_zcard(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 1 {
-            return Err(AikvError::WrongArgCount("ZCARD".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
+    /// Register the helper functions and constants of the `redis` table:
+    /// sha1hex, error_reply, status_reply, log and the LOG_* levels
+    fn register_helpers(lua: &Lua, redis_table: &mlua::Table) -> Result<()> {
+        let set = |name: &str, value: LuaValue| {
+            redis_table
+                .set(name, value)
+                .map_err(|e| AikvError::Script(format!("Failed to set redis.{}: {}", name, e)))
+        };
+        let create_err = |name: &str, e: mlua::Error| {
+            AikvError::Script(format!("Failed to create {}: {}", name, e))
+        };
 
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        // redis.sha1hex(s) - Hex SHA1 digest of a string
+        let sha1hex = lua
+            .create_function(|_, data: mlua::String| Ok(Self::calculate_sha1(&*data.as_bytes())))
+            .map_err(|e| create_err("sha1hex", e))?;
+        set("sha1hex", LuaValue::Function(sha1hex))?;
+
+        // redis.error_reply(msg) / redis.status_reply(msg) - Tables that
+        // lua_to_resp turns into error and status replies
+        for (name, field) in [("error_reply", "err"), ("status_reply", "ok")] {
+            let reply_fn = lua
+                .create_function(move |lua_ctx, message: mlua::String| {
+                    let table = lua_ctx.create_table()?;
+                    table.set(field, message)?;
+                    Ok(table)
+                })
+                .map_err(|e| create_err(name, e))?;
+            set(name, LuaValue::Function(reply_fn))?;
+        }
+
+        // redis.log(level, msg) - Write to the server log
+        for (level, name) in [LOG_DEBUG, LOG_VERBOSE, LOG_NOTICE, LOG_WARNING]
+            .into_iter()
+            .zip(["LOG_DEBUG", "LOG_VERBOSE", "LOG_NOTICE", "LOG_WARNING"])
+        {
+            set(name, LuaValue::Integer(level))?;
+        }
+        let log_fn = lua
+            .create_function(|_, (level, message): (i64, mlua::String)| {
+                let message = message.to_string_lossy();
+                match level {
+                    LOG_DEBUG | LOG_VERBOSE => debug!(target: "aikv::script", "{}", message),
+                    LOG_NOTICE => info!(target: "aikv::script", "{}", message),
+                    LOG_WARNING => warn!(target: "aikv::script", "{}", message),
+                    _ => return Err(mlua::Error::RuntimeError("Invalid log level".to_string())),
+                }
+                Ok(())
+            })
+            .map_err(|e| create_err("log", e))?;
+        set("log", LuaValue::Function(log_fn))?;
 
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let zset = stored.as_zset()?;
-            Ok(RespValue::Integer(zset.len() as i64))
-        } else {
-            Ok(RespValue::Integer(0))
-        }
+        Ok(())
     }
 
-    /// Execute SSCAN command
-    fn execute_sscan(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() < 2 {
-            return Err(AikvError::WrongArgCount("SSCAN".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let cursor: u64 = String::from_utf8_lossy(&args[1])
-            .parse()
-            .map_err(|_| AikvError::InvalidArgument("cursor is not a valid integer".to_string()))?;
-
-        let txn = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(stored) = txn.get_value(storage, &key)? {
-            let set = stored.as_set()?;
-            let members: Vec<RespValue> = set
-                .iter()
-                .map(|m| RespValue::bulk_string(Bytes::from(m.clone())))
-                .collect();
-
-            // Simple cursor implementation - return all members for cursor 0, empty for others
-            let (new_cursor, result_members) = if cursor == 0 {
-                (1, members)
-            } else {
-                (0, Vec::new())
-            };
-
-            let result = vec![
-                RespValue::bulk_string(Bytes::from(new_cursor.to_string())),
-                RespValue::Array(Some(result_members)),
-            ];
-            Ok(RespValue::Array(Some(result)))
-        } else {
-            // Empty set
-            let result = vec![
-                RespValue::bulk_string(Bytes::from("0")),
-                RespValue::Array(Some(Vec::new())),
-            ];
-            Ok(RespValue::Array(Some(result)))
-        }
-    }
+    /// Register the `cjson` library: cjson.encode, cjson.decode and cjson.null
+    fn register_cjson(lua: &Lua) -> Result<()> {
+        let cjson = lua
+            .create_table()
+            .map_err(|e| AikvError::Script(format!("Failed to create cjson table: {}", e)))?;
 
-    /// Execute EXPIRE command
-    fn execute_expire(
-        storage: &StorageEngine,
-        transaction: &Arc<RwLock<ScriptTransaction>>,
-        args: &[Bytes],
-    ) -> Result<RespValue> {
-        if args.len() != 2 {
-            return Err(AikvError::WrongArgCount("EXPIRE".to_string()));
-        }
-        let key = String::from_utf8_lossy(&args[0]).to_string();
-        let seconds: i64 = String::from_utf8_lossy(&args[1]).parse().map_err(|_| {
-            AikvError::InvalidArgument("seconds is not a valid integer".to_string())
-        })?;
+        let encode_fn = lua
+            .create_function(|_, value: LuaValue| {
+                let json = lua_to_json(&value, 0)?;
+                serde_json::to_string(&json).map_err(mlua::Error::external)
+            })
+            .map_err(|e| AikvError::Script(format!("Failed to create cjson.encode: {}", e)))?;
 
-        let txn_guard = transaction
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        let decode_fn = lua
+            .create_function(|lua_ctx, text: mlua::String| {
+                let json: serde_json::Value =
+                    serde_json::from_slice(&text.as_bytes()).map_err(mlua::Error::external)?;
+                json_to_lua(lua_ctx, &json)
+            })
+            .map_err(|e| AikvError::Script(format!("Failed to create cjson.decode: {}", e)))?;
 
-        // Check if key exists
-        if txn_guard.exists(storage, &key)? {
-            // Convert seconds to milliseconds and set expiration in storage
-            let expire_ms = (seconds as u64).saturating_mul(1000);
-            storage.set_expire_in_db(txn_guard.db_index, &key, expire_ms)?;
-            Ok(RespValue::Integer(1))
-        } else {
-            Ok(RespValue::Integer(0))
-        }
+        cjson
+            .set("encode", encode_fn)
+            .and_then(|_| cjson.set("decode", decode_fn))
+            .and_then(|_| cjson.set("null", CJSON_NULL))
+            .and_then(|_| lua.globals().set("cjson", cjson))
+            .map_err(|e| AikvError::Script(format!("Failed to set cjson: {}", e)))
     }
 
     /// Convert Lua value to RESP value
@@ -2195,6 +1154,16 @@ impl ScriptCommands {
             }
             LuaValue::String(s) => Ok(RespValue::bulk_string(Bytes::from(s.as_bytes().to_vec()))),
             LuaValue::Table(t) => {
+                // {err=...} and {ok=...} tables are error and status replies
+                if let Ok(LuaValue::String(e)) = t.get::<LuaValue>("err") {
+                    return Ok(RespValue::Error(e.to_string_lossy().to_string()));
+                }
+                if let Ok(LuaValue::String(status)) = t.get::<LuaValue>("ok") {
+                    return Ok(RespValue::SimpleString(
+                        status.to_string_lossy().to_string(),
+                    ));
+                }
+
                 // Convert table to array
                 let mut results = Vec::new();
                 for i in 1..=t.len().unwrap_or(0) {
@@ -2246,7 +1215,109 @@ impl ScriptCommands {
                 }
                 None => Ok(LuaValue::Boolean(false)),
             },
-            _ => Ok(LuaValue::Nil),
+            // Scripts see RESP3-only replies the way a RESP2 client would
+            other => Self::resp_to_lua(lua, other.into_resp2()),
+        }
+    }
+}
+
+/// Convert a Lua value to JSON for cjson.encode
+///
+/// A table whose keys are exactly 1..n becomes an array; any other table
+/// (including an empty one) becomes an object. Nil and cjson.null become null.
+fn lua_to_json(value: &LuaValue, depth: usize) -> mlua::Result<serde_json::Value> {
+    use serde_json::Value as Json;
+
+    match value {
+        LuaValue::Nil => Ok(Json::Null),
+        LuaValue::LightUserData(ud) if ud.0.is_null() => Ok(Json::Null),
+        LuaValue::Boolean(b) => Ok(Json::Bool(*b)),
+        LuaValue::Integer(i) => Ok(Json::from(*i)),
+        LuaValue::Number(n) => serde_json::Number::from_f64(*n)
+            .map(Json::Number)
+            .ok_or_else(|| {
+                mlua::Error::RuntimeError(
+                    "Cannot serialise number: must not be NaN or Infinity".to_string(),
+                )
+            }),
+        LuaValue::String(s) => Ok(Json::String(s.to_string_lossy().to_string())),
+        LuaValue::Table(table) => {
+            if depth >= CJSON_MAX_DEPTH {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "Cannot serialise, excessive nesting ({})",
+                    depth + 1
+                )));
+            }
+
+            let entries = table
+                .clone()
+                .pairs::<LuaValue, LuaValue>()
+                .collect::<mlua::Result<Vec<_>>>()?;
+
+            let is_array = !entries.is_empty()
+                && entries.iter().all(|(key, _)| {
+                    matches!(key, LuaValue::Integer(i) if *i >= 1 && *i as usize <= entries.len())
+                });
+
+            if is_array {
+                let mut items = vec![Json::Null; entries.len()];
+                for (key, item) in &entries {
+                    if let LuaValue::Integer(i) = key {
+                        items[*i as usize - 1] = lua_to_json(item, depth + 1)?;
+                    }
+                }
+                return Ok(Json::Array(items));
+            }
+
+            let mut object = serde_json::Map::new();
+            for (key, item) in &entries {
+                let key = match key {
+                    LuaValue::String(s) => s.to_string_lossy().to_string(),
+                    LuaValue::Integer(i) => i.to_string(),
+                    LuaValue::Number(n) => n.to_string(),
+                    _ => {
+                        return Err(mlua::Error::RuntimeError(
+                            "Cannot serialise table: table key must be a number or string"
+                                .to_string(),
+                        ))
+                    }
+                };
+                object.insert(key, lua_to_json(item, depth + 1)?);
+            }
+            Ok(Json::Object(object))
+        }
+        other => Err(mlua::Error::RuntimeError(format!(
+            "Cannot serialise {}: type not supported",
+            other.type_name()
+        ))),
+    }
+}
+
+/// Convert JSON from cjson.decode to a Lua value, with null as cjson.null
+fn json_to_lua(lua: &Lua, json: &serde_json::Value) -> mlua::Result<LuaValue> {
+    use serde_json::Value as Json;
+
+    match json {
+        Json::Null => Ok(CJSON_NULL),
+        Json::Bool(b) => Ok(LuaValue::Boolean(*b)),
+        Json::Number(n) => Ok(match n.as_i64() {
+            Some(i) => LuaValue::Integer(i),
+            None => LuaValue::Number(n.as_f64().unwrap_or(f64::NAN)),
+        }),
+        Json::String(s) => Ok(LuaValue::String(lua.create_string(s)?)),
+        Json::Array(items) => {
+            let table = lua.create_table()?;
+            for (i, item) in items.iter().enumerate() {
+                table.raw_set(i + 1, json_to_lua(lua, item)?)?;
+            }
+            Ok(LuaValue::Table(table))
+        }
+        Json::Object(object) => {
+            let table = lua.create_table()?;
+            for (key, item) in object {
+                table.raw_set(key.as_str(), json_to_lua(lua, item)?)?;
+            }
+            Ok(LuaValue::Table(table))
         }
     }
 }
@@ -2254,7 +1325,7 @@ impl ScriptCommands {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::storage::StorageEngine;
+    use crate::storage::{StorageEngine, StoredValue};
 
     fn setup() -> ScriptCommands {
         let storage = StorageEngine::new_memory(16);
@@ -2339,7 +1410,7 @@ mod tests {
         let script = "return 42";
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0);
+        let result = script_commands.eval(&args, &ClientContext::default());
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), RespValue::Integer(42));
     }
@@ -2350,7 +1421,9 @@ mod tests {
         let script = "return KEYS[1]";
         let args = vec![Bytes::from(script), Bytes::from("1"), Bytes::from("mykey")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::BulkString(Some(value)) = result {
             assert_eq!(String::from_utf8_lossy(&value), "mykey");
         } else {
@@ -2364,7 +1437,9 @@ mod tests {
         let script = "return ARGV[1]";
         let args = vec![Bytes::from(script), Bytes::from("0"), Bytes::from("myarg")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::BulkString(Some(value)) = result {
             assert_eq!(String::from_utf8_lossy(&value), "myarg");
         } else {
@@ -2386,7 +1461,9 @@ mod tests {
             Bytes::from("myvalue"),
         ];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::BulkString(Some(value)) = result {
             assert_eq!(String::from_utf8_lossy(&value), "myvalue");
         } else {
@@ -2411,7 +1488,9 @@ mod tests {
 
         // Execute using EVALSHA
         let evalsha_args = vec![sha1, Bytes::from("0")];
-        let result = script_commands.evalsha(&evalsha_args, 0).unwrap();
+        let result = script_commands
+            .evalsha(&evalsha_args, &ClientContext::default())
+            .unwrap();
 
         if let RespValue::BulkString(Some(value)) = result {
             assert_eq!(String::from_utf8_lossy(&value), "hello from cache");
@@ -2426,7 +1505,7 @@ mod tests {
         let sha1 = "nonexistent_sha1";
         let args = vec![Bytes::from(sha1), Bytes::from("0")];
 
-        let result = script_commands.evalsha(&args, 0);
+        let result = script_commands.evalsha(&args, &ClientContext::default());
         assert!(result.is_err());
     }
 
@@ -2441,7 +1520,7 @@ mod tests {
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
         // Execute script
-        let result = script_commands.eval(&args, 0);
+        let result = script_commands.eval(&args, &ClientContext::default());
         assert!(result.is_ok());
 
         // Verify values are committed to storage
@@ -2463,7 +1542,7 @@ mod tests {
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
         // Execute script - should fail
-        let result = script_commands.eval(&args, 0);
+        let result = script_commands.eval(&args, &ClientContext::default());
         assert!(result.is_err());
 
         // Verify values are NOT in storage (rolled back)
@@ -2492,7 +1571,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             assert_eq!(arr.len(), 2);
             if let RespValue::BulkString(Some(v1)) = &arr[0] {
@@ -2526,7 +1607,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             assert_eq!(arr.len(), 3);
             assert_eq!(arr[0], RespValue::Integer(0)); // After DEL
@@ -2570,7 +1653,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         assert_eq!(result, RespValue::Integer(3));
 
         // Verify all are deleted
@@ -2597,6 +1682,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_transaction_sort_reads_pattern_keys() {
+        let script_commands = setup();
+        for (element, weight) in [("a", "3"), ("b", "1"), ("c", "2")] {
+            script_commands
+                .storage
+                .set_in_db(0, format!("w_{}", element), Bytes::from(weight))
+                .unwrap();
+        }
+
+        let script = r#"
+            redis.call('RPUSH', KEYS[1], 'a', 'b', 'c')
+            return redis.call('SORT', KEYS[1], 'BY', 'w_*', 'GET', '#', 'GET', 'w_*')
+        "#;
+        let args = vec![Bytes::from(script), Bytes::from("1"), Bytes::from("sorted")];
+
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
+        assert_eq!(
+            result,
+            RespValue::Array(Some(
+                ["b", "1", "c", "2", "a", "3"]
+                    .into_iter()
+                    .map(|s| RespValue::bulk_string(Bytes::from(s)))
+                    .collect()
+            ))
+        );
+    }
+
     #[test]
     fn test_transaction_exists_with_buffer() {
         let script_commands = setup();
@@ -2611,7 +1726,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             assert_eq!(arr[0], RespValue::Integer(0)); // Before SET
             assert_eq!(arr[1], RespValue::Integer(1)); // After SET
@@ -2633,7 +1750,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::BulkString(Some(val)) = result {
             assert_eq!(String::from_utf8_lossy(&val), "v3");
         } else {
@@ -2701,7 +1820,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             assert_eq!(arr[0], RespValue::Integer(11));
             assert_eq!(arr[1], RespValue::Integer(12));
@@ -2723,7 +1844,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             assert_eq!(arr[0], RespValue::Integer(15));
             assert_eq!(arr[1], RespValue::Integer(12));
@@ -2743,7 +1866,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::BulkString(Some(val)) = result {
             let f: f64 = String::from_utf8_lossy(&val).parse().unwrap();
             assert!((f - 10.6).abs() < 0.001);
@@ -2765,7 +1890,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             assert_eq!(arr[0], RespValue::Integer(5)); // "Hello"
             assert_eq!(arr[1], RespValue::Integer(11)); // "Hello World"
@@ -2794,7 +1921,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             if let RespValue::BulkString(Some(v1)) = &arr[0] {
                 assert_eq!(String::from_utf8_lossy(v1), "value1");
@@ -2821,7 +1950,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         assert_eq!(result, RespValue::Integer(15));
     }
 
@@ -2837,7 +1968,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             assert_eq!(arr[0], RespValue::Integer(2));
             assert_eq!(arr[1], RespValue::Integer(1));
@@ -2864,7 +1997,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             assert_eq!(arr[0], RespValue::Integer(4));
             if let RespValue::BulkString(Some(first)) = &arr[1] {
@@ -2891,7 +2026,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             if let RespValue::BulkString(Some(left)) = &arr[0] {
                 assert_eq!(String::from_utf8_lossy(left), "a");
@@ -2916,7 +2053,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             assert_eq!(arr.len(), 3);
             if let RespValue::BulkString(Some(v)) = &arr[0] {
@@ -2950,7 +2089,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             assert_eq!(arr[0], RespValue::Integer(3));
             assert_eq!(arr[1], RespValue::Integer(3));
@@ -2973,7 +2114,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             assert_eq!(arr[0], RespValue::Integer(2));
             assert_eq!(arr[1], RespValue::Integer(1));
@@ -2999,7 +2142,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             assert_eq!(arr[0], RespValue::Integer(3));
             assert_eq!(arr[1], RespValue::Integer(3));
@@ -3023,7 +2168,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             assert_eq!(arr.len(), 3);
             // Should be sorted by score
@@ -3053,7 +2200,9 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0).unwrap();
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
         if let RespValue::Array(Some(arr)) = result {
             assert_eq!(arr[0], RespValue::Integer(2));
             assert_eq!(arr[1], RespValue::Integer(1));
@@ -3079,7 +2228,7 @@ mod tests {
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
         // Script should fail
-        let result = script_commands.eval(&args, 0);
+        let result = script_commands.eval(&args, &ClientContext::default());
         assert!(result.is_err());
 
         // All values should be rolled back
@@ -3112,7 +2261,7 @@ mod tests {
         "#;
         let args = vec![Bytes::from(script), Bytes::from("0")];
 
-        let result = script_commands.eval(&args, 0);
+        let result = script_commands.eval(&args, &ClientContext::default());
         assert!(result.is_ok());
 
         // All values should be committed
@@ -3132,4 +2281,267 @@ mod tests {
             .unwrap()
             .is_some());
     }
+
+    #[test]
+    fn test_script_lpush_then_lrange() {
+        let script_commands = setup();
+        script_commands
+            .storage
+            .set_value(
+                0,
+                "script_list".to_string(),
+                StoredValue::new_list(vec![Bytes::from("a")].into()),
+            )
+            .unwrap();
+
+        let script = r#"
+            redis.call('LPUSH', KEYS[1], 'x')
+            return redis.call('LRANGE', KEYS[1], 0, -1)
+        "#;
+        let args = vec![
+            Bytes::from(script),
+            Bytes::from("1"),
+            Bytes::from("script_list"),
+        ];
+
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
+        assert_eq!(
+            result,
+            RespValue::array(vec![
+                RespValue::bulk_string("x"),
+                RespValue::bulk_string("a")
+            ])
+        );
+
+        let list = script_commands
+            .storage
+            .get_value(0, "script_list")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            list.as_list().unwrap(),
+            &vec![Bytes::from("x"), Bytes::from("a")]
+        );
+    }
+
+    #[test]
+    fn test_script_runs_any_command() {
+        let script_commands = setup();
+
+        // Commands without a dedicated script handler, including TTLs and SELECT
+        let script = r#"
+            redis.call('HINCRBYFLOAT', 'float_hash', 'f', '1.5')
+            redis.call('SETEX', 'ttl_key', 100, 'v')
+            redis.call('SELECT', 1)
+            redis.call('SET', 'db1_key', 'v')
+            return redis.call('TTL', 'ttl_key')
+        "#;
+        let args = vec![Bytes::from(script), Bytes::from("0")];
+
+        // SELECT only moved the script's own connection, so TTL saw db 1
+        let result = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap();
+        assert_eq!(result, RespValue::Integer(-2));
+
+        assert!(script_commands
+            .storage
+            .get_value(0, "float_hash")
+            .unwrap()
+            .is_some());
+        assert!(script_commands.storage.get_ttl_in_db(0, "ttl_key").unwrap() > 0);
+        assert_eq!(
+            script_commands.storage.get_from_db(1, "db1_key").unwrap(),
+            Some(Bytes::from("v"))
+        );
+        assert_eq!(
+            script_commands.storage.get_from_db(0, "db1_key").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_script_rejects_noscript_commands() {
+        let script_commands = setup();
+        let script = "return redis.call('EVAL', 'return 1', 0)";
+        let args = vec![Bytes::from(script), Bytes::from("0")];
+
+        let err = script_commands
+            .eval(&args, &ClientContext::default())
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("not allowed from scripts"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_script_error_and_status_reply() {
+        let script_commands = setup();
+
+        let args = vec![
+            Bytes::from("return redis.error_reply('boom')"),
+            Bytes::from("0"),
+        ];
+        assert_eq!(
+            script_commands
+                .eval(&args, &ClientContext::default())
+                .unwrap(),
+            RespValue::Error("boom".to_string())
+        );
+
+        let args = vec![
+            Bytes::from("return redis.status_reply('DONE')"),
+            Bytes::from("0"),
+        ];
+        assert_eq!(
+            script_commands
+                .eval(&args, &ClientContext::default())
+                .unwrap(),
+            RespValue::SimpleString("DONE".to_string())
+        );
+
+        // Plain tables with the same fields work too
+        let args = vec![Bytes::from("return {err='raw'}"), Bytes::from("0")];
+        assert_eq!(
+            script_commands
+                .eval(&args, &ClientContext::default())
+                .unwrap(),
+            RespValue::Error("raw".to_string())
+        );
+    }
+
+    #[test]
+    fn test_script_sha1hex_and_log() {
+        let script_commands = setup();
+
+        let args = vec![Bytes::from("return redis.sha1hex('')"), Bytes::from("0")];
+        assert_eq!(
+            script_commands
+                .eval(&args, &ClientContext::default())
+                .unwrap(),
+            RespValue::bulk_string("da39a3ee5e6b4b0d3255bfef95601890afd80709")
+        );
+
+        let script = r#"
+            redis.log(redis.LOG_WARNING, 'from a script')
+            return redis.LOG_NOTICE
+        "#;
+        let args = vec![Bytes::from(script), Bytes::from("0")];
+        assert_eq!(
+            script_commands
+                .eval(&args, &ClientContext::default())
+                .unwrap(),
+            RespValue::Integer(2)
+        );
+
+        let args = vec![Bytes::from("redis.log(42, 'x')"), Bytes::from("0")];
+        assert!(script_commands
+            .eval(&args, &ClientContext::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_script_cjson_round_trip() {
+        let script_commands = setup();
+
+        let script = r#"
+            local doc = {name = 'aikv', tags = {'kv', 'lua'}, meta = {stars = 42, extra = cjson.null}}
+            local decoded = cjson.decode(cjson.encode(doc))
+            return {decoded.name, decoded.tags[2], decoded.meta.stars, decoded.meta.extra == cjson.null}
+        "#;
+        let args = vec![Bytes::from(script), Bytes::from("0")];
+        assert_eq!(
+            script_commands
+                .eval(&args, &ClientContext::default())
+                .unwrap(),
+            RespValue::array(vec![
+                RespValue::bulk_string("aikv"),
+                RespValue::bulk_string("lua"),
+                RespValue::Integer(42),
+                RespValue::Integer(1),
+            ])
+        );
+
+        // Arrays and objects encode the way cjson does
+        let script = "return cjson.encode({1, 2, {a = true}, {}})";
+        let args = vec![Bytes::from(script), Bytes::from("0")];
+        assert_eq!(
+            script_commands
+                .eval(&args, &ClientContext::default())
+                .unwrap(),
+            RespValue::bulk_string(r#"[1,2,{"a":true},{}]"#)
+        );
+
+        let args = vec![Bytes::from("return cjson.decode('{bad')"), Bytes::from("0")];
+        assert!(script_commands
+            .eval(&args, &ClientContext::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_eval_ro_rejects_write_commands() {
+        let script_commands = setup();
+        script_commands
+            .storage
+            .set_in_db(0, "ro_key".to_string(), Bytes::from("value"))
+            .unwrap();
+
+        let read = vec![
+            Bytes::from("return redis.call('GET', KEYS[1])"),
+            Bytes::from("1"),
+            Bytes::from("ro_key"),
+        ];
+        assert_eq!(
+            script_commands.eval_ro(&read, 0).unwrap(),
+            RespValue::bulk_string("value")
+        );
+
+        let write = vec![
+            Bytes::from("return redis.call('SET', KEYS[1], 'changed')"),
+            Bytes::from("1"),
+            Bytes::from("ro_key"),
+        ];
+        let err = script_commands.eval_ro(&write, 0).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Write commands are not allowed from read-only scripts"),
+            "{}",
+            err
+        );
+        assert_eq!(
+            script_commands.storage.get_from_db(0, "ro_key").unwrap(),
+            Some(Bytes::from("value"))
+        );
+
+        // The same script works under plain EVAL
+        assert!(script_commands
+            .eval(&write, &ClientContext::default())
+            .is_ok());
+        assert_eq!(
+            script_commands.storage.get_from_db(0, "ro_key").unwrap(),
+            Some(Bytes::from("changed"))
+        );
+    }
+
+    #[test]
+    fn test_evalsha_ro() {
+        let script_commands = setup();
+        let sha1 = match script_commands
+            .script_load(&[Bytes::from("return redis.call('SET', 'k', 'v')")])
+            .unwrap()
+        {
+            RespValue::BulkString(Some(sha1)) => sha1,
+            other => panic!("Expected BulkString, got {:?}", other),
+        };
+
+        let args = vec![sha1, Bytes::from("0")];
+        assert!(script_commands.evalsha_ro(&args, 0).is_err());
+        assert!(script_commands
+            .evalsha(&args, &ClientContext::default())
+            .is_ok());
+    }
 }
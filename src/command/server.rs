@@ -1,16 +1,27 @@
+use super::acl::{self, AclUsers};
+use super::config::{glob_match, parse_memory, OutputBufferLimits, RuntimeConfig, SharedConfig};
+use super::context::ClientContext;
+use super::docs;
+use super::help::{help_reply, unknown_subcommand};
+use super::pubsub::{KeyspaceEvents, PubSub};
 use crate::error::{AikvError, Result};
-use crate::observability::{LogConfig, SlowQueryLog};
-use crate::protocol::RespValue;
-use crate::storage::StorageEngine;
+use crate::observability::{LogConfig, Metrics, SlowQueryLog};
+use crate::persistence::{AofSyncPolicy, AofWriter};
+use crate::protocol::{ProtocolVersion, RespValue, DEFAULT_MAX_BULK_LEN};
+use crate::storage::{
+    entry_size, EvictionPolicy, MemoryLimit, StorageEngine, StoredValue, ValueType,
+};
 use bytes::Bytes;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::{Arc, RwLock};
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
-use tracing::Level;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{watch, Notify};
+use tracing::{error, info, Level};
 
 /// AiKv version - the actual version of this server
-const AIKV_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub(crate) const AIKV_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// Redis-compatible version to report for client compatibility
 /// We report a modern Redis version to ensure clients like StackExchange.Redis work correctly
@@ -22,6 +33,304 @@ pub struct ClientInfo {
     pub id: usize,
     pub name: Option<String>,
     pub addr: String,
+    /// Notified to ask the connection to close (CLIENT KILL)
+    pub kill_signal: Arc<Notify>,
+}
+
+/// Registry of live connections, shared by every connection of a server
+/// so that CLIENT LIST/KILL see all clients
+pub type ClientRegistry = Arc<RwLock<HashMap<usize, ClientInfo>>>;
+
+/// RDB snapshot state, shared by every connection of a server so that SAVE,
+/// BGSAVE and LASTSAVE agree on the file and the last save time
+#[derive(Debug)]
+pub struct RdbState {
+    path: PathBuf,
+    /// Last successful save (Unix epoch in seconds)
+    last_save_time: AtomicU64,
+    bgsave_in_progress: AtomicBool,
+    /// Whether the last save attempt succeeded
+    last_save_ok: AtomicBool,
+    /// Whether snapshots carry a CRC64 trailer (`rdbchecksum`)
+    checksum: AtomicBool,
+}
+
+impl RdbState {
+    /// Create the state for snapshots written to `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            last_save_time: AtomicU64::new(unix_time_secs()),
+            bgsave_in_progress: AtomicBool::new(false),
+            last_save_ok: AtomicBool::new(true),
+            checksum: AtomicBool::new(true),
+        }
+    }
+
+    /// Path snapshots are written to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Unix timestamp (seconds) of the last successful save, or of startup
+    pub fn last_save_time(&self) -> u64 {
+        self.last_save_time.load(Ordering::SeqCst)
+    }
+
+    /// Whether a BGSAVE is currently running
+    pub fn bgsave_in_progress(&self) -> bool {
+        self.bgsave_in_progress.load(Ordering::SeqCst)
+    }
+
+    /// Whether the last SAVE or BGSAVE succeeded (`rdb_last_bgsave_status`)
+    pub fn last_save_ok(&self) -> bool {
+        self.last_save_ok.load(Ordering::SeqCst)
+    }
+
+    /// Whether snapshots are written with a CRC64 checksum
+    pub fn checksum(&self) -> bool {
+        self.checksum.load(Ordering::SeqCst)
+    }
+
+    /// Enable or disable the snapshot checksum; disabled writes zeros instead
+    pub fn set_checksum(&self, enabled: bool) {
+        self.checksum.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Snapshot every database of `storage` to the file (SAVE)
+    pub fn save(&self, storage: &StorageEngine) -> Result<()> {
+        let databases = storage.export_all_databases()?;
+        self.write(&databases)
+    }
+
+    /// Write `databases` to the snapshot file and record the save time
+    fn write(&self, databases: &[HashMap<String, StoredValue>]) -> Result<()> {
+        let result = crate::persistence::save_stored_value_rdb_atomic(
+            &self.path,
+            databases,
+            self.checksum(),
+        );
+        self.last_save_ok.store(result.is_ok(), Ordering::SeqCst);
+        result?;
+        self.last_save_time
+            .store(unix_time_secs(), Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl Default for RdbState {
+    fn default() -> Self {
+        Self::new("dump.rdb")
+    }
+}
+
+/// Whether a shutdown writes a final RDB snapshot
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShutdownSave {
+    /// As the server is configured to (`save_on_shutdown`)
+    Configured,
+    /// Always (SHUTDOWN SAVE)
+    Save,
+    /// Never (SHUTDOWN NOSAVE)
+    NoSave,
+}
+
+/// Graceful shutdown request, shared by a server and whoever may stop it
+/// (signal handlers, embedders, SHUTDOWN)
+///
+/// Triggering it is sticky: a server that starts waiting afterwards still
+/// sees the request.
+#[derive(Clone, Debug)]
+pub struct ShutdownSignal(Arc<watch::Sender<Option<ShutdownSave>>>);
+
+impl ShutdownSignal {
+    /// Create an untriggered signal
+    pub fn new() -> Self {
+        Self(Arc::new(watch::Sender::new(None)))
+    }
+
+    /// Ask the server to shut down, saving as configured
+    pub fn trigger(&self) {
+        self.request(ShutdownSave::Configured);
+    }
+
+    /// Ask the server to shut down, saving as `save` says
+    pub fn request(&self, save: ShutdownSave) {
+        self.0.send_replace(Some(save));
+    }
+
+    /// Whether a shutdown has been requested
+    pub fn is_triggered(&self) -> bool {
+        self.0.borrow().is_some()
+    }
+
+    /// Wait until a shutdown is requested, returning how it should save
+    pub async fn triggered(&self) -> ShutdownSave {
+        let mut receiver = self.0.subscribe();
+        // The sender lives in `self`, so the channel can't close under us
+        match receiver.wait_for(Option::is_some).await {
+            Ok(save) => (*save).unwrap_or(ShutdownSave::Configured),
+            Err(_) => ShutdownSave::Configured,
+        }
+    }
+}
+
+impl Default for ShutdownSignal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Encoding Redis would pick for a value of this size and shape
+fn value_encoding(value: &ValueType) -> &'static str {
+    match value {
+        ValueType::String(s) => {
+            let is_int =
+                s.len() <= 20 && std::str::from_utf8(s).is_ok_and(|s| s.parse::<i64>().is_ok());
+            if is_int {
+                "int"
+            } else if s.len() <= 44 {
+                "embstr"
+            } else {
+                "raw"
+            }
+        }
+        ValueType::List(_) => "quicklist",
+        ValueType::Hash(fields) => {
+            if fields.len() <= 128 && fields.iter().all(|(k, v)| k.len() <= 64 && v.len() <= 64) {
+                "listpack"
+            } else {
+                "hashtable"
+            }
+        }
+        ValueType::Set(members) => {
+            let all_ints = members
+                .iter()
+                .all(|m| std::str::from_utf8(m).is_ok_and(|m| m.parse::<i64>().is_ok()));
+            if all_ints && members.len() <= 512 {
+                "intset"
+            } else if members.len() <= 128 && members.iter().all(|m| m.len() <= 64) {
+                "listpack"
+            } else {
+                "hashtable"
+            }
+        }
+        ValueType::ZSet(members) => {
+            if members.len() <= 128 && members.keys().all(|m| m.len() <= 64) {
+                "listpack"
+            } else {
+                "skiplist"
+            }
+        }
+        ValueType::Stream(_) => "stream",
+    }
+}
+
+/// Sections returned by a plain INFO, in order
+const DEFAULT_INFO_SECTIONS: &[&str] = &[
+    "server",
+    "clients",
+    "memory",
+    "persistence",
+    "stats",
+    "replication",
+    "cpu",
+    "cluster",
+    "keyspace",
+];
+
+/// Sections returned by INFO all, in order
+const ALL_INFO_SECTIONS: &[&str] = &[
+    "server",
+    "clients",
+    "memory",
+    "persistence",
+    "stats",
+    "replication",
+    "cpu",
+    "modules",
+    "commandstats",
+    "errorstats",
+    "latencystats",
+    "cluster",
+    "keyspace",
+];
+
+/// Whose CPU time [`cpu_usage`] reports
+#[derive(Clone, Copy)]
+enum CpuScope {
+    Process,
+    Children,
+    Thread,
+}
+
+/// System and user CPU seconds used, from `getrusage`
+#[cfg(unix)]
+fn cpu_usage(scope: CpuScope) -> (f64, f64) {
+    let who = match scope {
+        CpuScope::Process => libc::RUSAGE_SELF,
+        CpuScope::Children => libc::RUSAGE_CHILDREN,
+        #[cfg(target_os = "linux")]
+        CpuScope::Thread => libc::RUSAGE_THREAD,
+        #[cfg(not(target_os = "linux"))]
+        CpuScope::Thread => libc::RUSAGE_SELF,
+    };
+    // SAFETY: getrusage only writes to the struct it's given
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(who, &mut usage) } != 0 {
+        return (0.0, 0.0);
+    }
+    let seconds = |tv: libc::timeval| tv.tv_sec as f64 + tv.tv_usec as f64 / 1_000_000.0;
+    (seconds(usage.ru_stime), seconds(usage.ru_utime))
+}
+
+/// CPU usage isn't tracked on this platform
+#[cfg(not(unix))]
+fn cpu_usage(_scope: CpuScope) -> (f64, f64) {
+    (0.0, 0.0)
+}
+
+fn unix_time_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Per-connection authentication state
+#[derive(Clone, Debug)]
+pub struct AuthState {
+    /// Password of the default user (`requirepass`); `None` disables auth
+    pub requirepass: Option<Arc<str>>,
+    /// Whether the connection may run commands other than AUTH/HELLO/PING
+    pub authenticated: bool,
+    /// ACL user the connection runs commands as
+    pub user: String,
+}
+
+impl AuthState {
+    /// Connections start authenticated only when no password is configured
+    pub fn new(requirepass: Option<Arc<str>>) -> Self {
+        Self {
+            authenticated: requirepass.is_none(),
+            requirepass,
+            user: acl::DEFAULT_USER.to_string(),
+        }
+    }
+}
+
+impl Default for AuthState {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// Compare two byte strings in time independent of where they differ
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
 }
 
 /// Command information structure for COMMAND command
@@ -41,903 +350,1649 @@ pub struct CommandInfo {
     pub step: i64,
 }
 
+impl CommandInfo {
+    /// Whether `argc` arguments (including the command name) satisfy the arity.
+    ///
+    /// A positive arity is an exact count; a negative arity `-n` means at least `n`.
+    pub fn accepts_arg_count(&self, argc: usize) -> bool {
+        let argc = argc as i64;
+        if self.arity >= 0 {
+            argc == self.arity
+        } else {
+            argc >= -self.arity
+        }
+    }
+
+    /// Whether the command carries `flag`
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    /// Positions of the keys in a command of `argc` arguments, counting the
+    /// command name as position 0
+    ///
+    /// A negative `last_key` counts back from the end of the arguments.
+    pub fn key_positions(&self, argc: usize) -> Vec<usize> {
+        if self.first_key <= 0 {
+            return Vec::new();
+        }
+        let last = if self.last_key < 0 {
+            argc as i64 + self.last_key
+        } else {
+            self.last_key.min(argc as i64 - 1)
+        };
+        (self.first_key..=last)
+            .step_by(self.step.max(1) as usize)
+            .map(|pos| pos as usize)
+            .collect()
+    }
+
+    /// The key arguments of a command, `args` excluding the command name
+    ///
+    /// Commands flagged `movablekeys` have keys the key spec can't describe,
+    /// so they are found by parsing the arguments instead.
+    pub fn keys<'a>(&self, args: &'a [Bytes]) -> Vec<&'a Bytes> {
+        match self.name {
+            // EVAL script numkeys key [key ...] arg [arg ...]
+            "EVAL" | "EVALSHA" | "EVAL_RO" | "EVALSHA_RO" => {
+                let numkeys = args
+                    .get(1)
+                    .and_then(|n| String::from_utf8_lossy(n).parse::<usize>().ok())
+                    .unwrap_or(0);
+                args.iter().skip(2).take(numkeys).collect()
+            }
+            // SORT key [BY pattern] [LIMIT offset count] [GET pattern ...] [STORE dest]
+            "SORT" => {
+                let mut keys: Vec<&Bytes> = args.iter().take(1).collect();
+                let mut i = 1;
+                while i < args.len() {
+                    let option = &args[i];
+                    if option.eq_ignore_ascii_case(b"LIMIT") {
+                        i += 3;
+                    } else if option.eq_ignore_ascii_case(b"BY")
+                        || option.eq_ignore_ascii_case(b"GET")
+                    {
+                        i += 2;
+                    } else if option.eq_ignore_ascii_case(b"STORE") {
+                        keys.extend(args.get(i + 1));
+                        i += 2;
+                    } else {
+                        i += 1;
+                    }
+                }
+                keys
+            }
+            // GEORADIUS key longitude latitude radius unit [... STORE dest]
+            "GEORADIUS" | "GEORADIUSBYMEMBER" => {
+                let options = if self.name == "GEORADIUS" { 5 } else { 4 };
+                let mut keys: Vec<&Bytes> = args.iter().take(1).collect();
+                let mut i = options;
+                while i < args.len() {
+                    if args[i].eq_ignore_ascii_case(b"STORE")
+                        || args[i].eq_ignore_ascii_case(b"STOREDIST")
+                    {
+                        keys.extend(args.get(i + 1));
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                keys
+            }
+            // MIGRATE host port key|"" db timeout [COPY] [REPLACE] [AUTH password]
+            //   [AUTH2 username password] [KEYS key [key ...]]
+            "MIGRATE" => {
+                let mut i = 5;
+                while i < args.len() {
+                    let option = &args[i];
+                    if option.eq_ignore_ascii_case(b"AUTH") {
+                        i += 2;
+                    } else if option.eq_ignore_ascii_case(b"AUTH2") {
+                        i += 3;
+                    } else if option.eq_ignore_ascii_case(b"KEYS") {
+                        // The single key argument must be empty to use KEYS
+                        return args[i + 1..].iter().collect();
+                    } else {
+                        i += 1;
+                    }
+                }
+                args.get(2).into_iter().collect()
+            }
+            // LMPOP numkeys key [key ...] LEFT|RIGHT [COUNT count], ZMPOP alike,
+            // and SINTERCARD numkeys key [key ...] [LIMIT limit]
+            "LMPOP" | "ZMPOP" | "SINTERCARD" => {
+                let numkeys = args
+                    .first()
+                    .and_then(|n| String::from_utf8_lossy(n).parse::<usize>().ok())
+                    .unwrap_or(0);
+                args.iter().skip(1).take(numkeys).collect()
+            }
+            // BLMPOP timeout numkeys key [key ...] LEFT|RIGHT [COUNT count], BZMPOP alike
+            "BLMPOP" | "BZMPOP" => {
+                let numkeys = args
+                    .get(1)
+                    .and_then(|n| String::from_utf8_lossy(n).parse::<usize>().ok())
+                    .unwrap_or(0);
+                args.iter().skip(2).take(numkeys).collect()
+            }
+            // XREAD [COUNT count] STREAMS key [key ...] id [id ...]
+            "XREAD" => match args.iter().position(|a| a.eq_ignore_ascii_case(b"STREAMS")) {
+                Some(i) => {
+                    let streams = &args[i + 1..];
+                    streams[..streams.len() / 2].iter().collect()
+                }
+                None => Vec::new(),
+            },
+            _ => self
+                .key_positions(args.len() + 1)
+                .into_iter()
+                .map(|pos| &args[pos - 1])
+                .collect(),
+        }
+    }
+}
+
+/// All commands in the command table
+pub fn command_table() -> &'static [CommandInfo] {
+    COMMAND_TABLE
+}
+
+/// Whether a command modifies the dataset (and so must be logged to the AOF)
+pub fn is_write_command(name: &str) -> bool {
+    lookup_command(name).is_some_and(|cmd| cmd.has_flag("write"))
+}
+
+/// Look up a command in the command table by its upper-case name
+pub fn lookup_command(name: &str) -> Option<&'static CommandInfo> {
+    static INDEX: OnceLock<HashMap<&'static str, &'static CommandInfo>> = OnceLock::new();
+    INDEX
+        .get_or_init(|| COMMAND_TABLE.iter().map(|cmd| (cmd.name, cmd)).collect())
+        .get(name)
+        .copied()
+}
+
 /// Server command handler
 pub struct ServerCommands {
     storage: StorageEngine,
-    clients: Arc<RwLock<HashMap<usize, ClientInfo>>>,
-    config: Arc<RwLock<HashMap<String, String>>>,
+    clients: ClientRegistry,
+    /// Parameters reported by CONFIG GET, shared by every connection
+    config: SharedConfig,
     start_time: Instant,
     run_id: String,
     tcp_port: u16,
     current_log_level: Arc<RwLock<Level>>,
     slow_query_log: Arc<SlowQueryLog>,
-    /// Last save timestamp (Unix epoch in seconds)
-    last_save_time: Arc<AtomicU64>,
-    /// Shutdown flag
-    shutdown_requested: Arc<AtomicBool>,
+    /// Server-wide metrics, for INFO commandstats/latencystats
+    metrics: Arc<Metrics>,
+    /// RDB snapshot file and last save time
+    rdb: Arc<RdbState>,
+    /// Append-only log, for CONFIG SET appendfsync
+    aof: Option<AofWriter>,
+    /// Stops the server (SHUTDOWN)
+    shutdown: ShutdownSignal,
+    /// Whether the server writes a final snapshot when shutting down
+    save_on_shutdown: bool,
+    /// Whether the background expiration sweeper runs (DEBUG SET-ACTIVE-EXPIRE)
+    active_expire: Arc<AtomicBool>,
+    /// Whether the dataset is still being loaded on startup (INFO loading)
+    loading: Arc<AtomicBool>,
+    /// Pub/Sub hub, for CONFIG SET notify-keyspace-events
+    pubsub: Arc<PubSub>,
+    /// ACL users, for AUTH and HELLO ... AUTH
+    acl_users: AclUsers,
     /// Whether cluster mode is enabled
     cluster_enabled: bool,
 }
 
 /// All supported commands with their metadata
-fn get_command_table() -> Vec<CommandInfo> {
-    vec![
-        // String commands
-        CommandInfo {
-            name: "GET",
-            arity: 2,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "SET",
-            arity: -3,
-            flags: &["write", "denyoom"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "DEL",
-            arity: -2,
-            flags: &["write"],
-            first_key: 1,
-            last_key: -1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "EXISTS",
-            arity: -2,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: -1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "MGET",
-            arity: -2,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: -1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "MSET",
-            arity: -3,
-            flags: &["write", "denyoom"],
-            first_key: 1,
-            last_key: -1,
-            step: 2,
-        },
-        CommandInfo {
-            name: "STRLEN",
-            arity: 2,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "APPEND",
-            arity: 3,
-            flags: &["write", "denyoom", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        // JSON commands
-        CommandInfo {
-            name: "JSON.GET",
-            arity: -2,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "JSON.SET",
-            arity: -4,
-            flags: &["write", "denyoom"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "JSON.DEL",
-            arity: -2,
-            flags: &["write"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "JSON.TYPE",
-            arity: -2,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "JSON.STRLEN",
-            arity: -2,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "JSON.ARRLEN",
-            arity: -2,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "JSON.OBJLEN",
-            arity: -2,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        // List commands
-        CommandInfo {
-            name: "LPUSH",
-            arity: -3,
-            flags: &["write", "denyoom", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "RPUSH",
-            arity: -3,
-            flags: &["write", "denyoom", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "LPOP",
-            arity: -2,
-            flags: &["write", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "RPOP",
-            arity: -2,
-            flags: &["write", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "LLEN",
-            arity: 2,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "LRANGE",
-            arity: 4,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "LINDEX",
-            arity: 3,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "LSET",
-            arity: 4,
-            flags: &["write", "denyoom"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "LREM",
-            arity: 4,
-            flags: &["write"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "LTRIM",
-            arity: 4,
-            flags: &["write"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "LINSERT",
-            arity: 5,
-            flags: &["write", "denyoom"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "LMOVE",
-            arity: 5,
-            flags: &["write", "denyoom"],
-            first_key: 1,
-            last_key: 2,
-            step: 1,
-        },
-        // Hash commands
-        CommandInfo {
-            name: "HSET",
-            arity: -4,
-            flags: &["write", "denyoom", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "HSETNX",
-            arity: 4,
-            flags: &["write", "denyoom", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "HGET",
-            arity: 3,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "HMGET",
-            arity: -3,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "HMSET",
-            arity: -4,
-            flags: &["write", "denyoom", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "HDEL",
-            arity: -3,
-            flags: &["write", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "HEXISTS",
-            arity: 3,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "HLEN",
-            arity: 2,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "HKEYS",
-            arity: 2,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "HVALS",
-            arity: 2,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "HGETALL",
-            arity: 2,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "HINCRBY",
-            arity: 4,
-            flags: &["write", "denyoom", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "HINCRBYFLOAT",
-            arity: 4,
-            flags: &["write", "denyoom", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "HSCAN",
-            arity: -3,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        // Set commands
-        CommandInfo {
-            name: "SADD",
-            arity: -3,
-            flags: &["write", "denyoom", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "SREM",
-            arity: -3,
-            flags: &["write", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "SISMEMBER",
-            arity: 3,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "SMEMBERS",
-            arity: 2,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "SCARD",
-            arity: 2,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "SPOP",
-            arity: -2,
-            flags: &["write", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "SRANDMEMBER",
-            arity: -2,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "SUNION",
-            arity: -2,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: -1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "SINTER",
-            arity: -2,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: -1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "SDIFF",
-            arity: -2,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: -1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "SUNIONSTORE",
-            arity: -3,
-            flags: &["write", "denyoom"],
-            first_key: 1,
-            last_key: -1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "SINTERSTORE",
-            arity: -3,
-            flags: &["write", "denyoom"],
-            first_key: 1,
-            last_key: -1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "SDIFFSTORE",
-            arity: -3,
-            flags: &["write", "denyoom"],
-            first_key: 1,
-            last_key: -1,
-            step: 1,
-        },
-        // Sorted Set commands
-        CommandInfo {
-            name: "ZADD",
-            arity: -4,
-            flags: &["write", "denyoom", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "ZREM",
-            arity: -3,
-            flags: &["write", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "ZSCORE",
-            arity: 3,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "ZRANK",
-            arity: 3,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "ZREVRANK",
-            arity: 3,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "ZRANGE",
-            arity: -4,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "ZREVRANGE",
-            arity: -4,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "ZRANGEBYSCORE",
-            arity: -4,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "ZREVRANGEBYSCORE",
-            arity: -4,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "ZCARD",
-            arity: 2,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "ZCOUNT",
-            arity: 4,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "ZINCRBY",
-            arity: 4,
-            flags: &["write", "denyoom", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        // Database commands
-        CommandInfo {
-            name: "SELECT",
-            arity: 2,
-            flags: &["fast"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "DBSIZE",
-            arity: 1,
-            flags: &["readonly", "fast"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "FLUSHDB",
-            arity: -1,
-            flags: &["write"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "FLUSHALL",
-            arity: -1,
-            flags: &["write"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "SWAPDB",
-            arity: 3,
-            flags: &["write", "fast"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "MOVE",
-            arity: 3,
-            flags: &["write", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        // Key commands
-        CommandInfo {
-            name: "KEYS",
-            arity: 2,
-            flags: &["readonly"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "SCAN",
-            arity: -2,
-            flags: &["readonly"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "RANDOMKEY",
-            arity: 1,
-            flags: &["readonly"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "RENAME",
-            arity: 3,
-            flags: &["write"],
-            first_key: 1,
-            last_key: 2,
-            step: 1,
-        },
-        CommandInfo {
-            name: "RENAMENX",
-            arity: 3,
-            flags: &["write", "fast"],
-            first_key: 1,
-            last_key: 2,
-            step: 1,
-        },
-        CommandInfo {
-            name: "TYPE",
-            arity: 2,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "COPY",
-            arity: -3,
-            flags: &["write", "denyoom"],
-            first_key: 1,
-            last_key: 2,
-            step: 1,
-        },
-        CommandInfo {
-            name: "DUMP",
-            arity: 2,
-            flags: &["readonly"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "RESTORE",
-            arity: -4,
-            flags: &["write", "denyoom"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "MIGRATE",
-            arity: -6,
-            flags: &["write"],
-            first_key: 3,
-            last_key: 3,
-            step: 1,
-        },
-        CommandInfo {
-            name: "EXPIRE",
-            arity: -3,
-            flags: &["write", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "EXPIREAT",
-            arity: -3,
-            flags: &["write", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "PEXPIRE",
-            arity: -3,
-            flags: &["write", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "PEXPIREAT",
-            arity: -3,
-            flags: &["write", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "TTL",
-            arity: 2,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "PTTL",
-            arity: 2,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "PERSIST",
-            arity: 2,
-            flags: &["write", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "EXPIRETIME",
-            arity: 2,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        CommandInfo {
-            name: "PEXPIRETIME",
-            arity: 2,
-            flags: &["readonly", "fast"],
-            first_key: 1,
-            last_key: 1,
-            step: 1,
-        },
-        // Server commands
-        CommandInfo {
-            name: "PING",
-            arity: -1,
-            flags: &["fast", "stale"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "ECHO",
-            arity: 2,
-            flags: &["fast"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "INFO",
-            arity: -1,
-            flags: &["stale", "fast"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "CONFIG",
-            arity: -2,
-            flags: &["admin", "stale"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "SLOWLOG",
-            arity: -2,
-            flags: &["admin", "stale"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "TIME",
-            arity: 1,
-            flags: &["fast", "stale"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "CLIENT",
-            arity: -2,
-            flags: &["admin", "stale"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "COMMAND",
-            arity: -1,
-            flags: &["stale"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "SAVE",
-            arity: 1,
-            flags: &["admin"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "BGSAVE",
-            arity: -1,
-            flags: &["admin"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "LASTSAVE",
-            arity: 1,
-            flags: &["fast", "stale"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "SHUTDOWN",
-            arity: -1,
-            flags: &["admin"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "MONITOR",
-            arity: 1,
-            flags: &["admin"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        // Script commands
-        CommandInfo {
-            name: "EVAL",
-            arity: -3,
-            flags: &["write", "denyoom"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "EVALSHA",
-            arity: -3,
-            flags: &["write", "denyoom"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        CommandInfo {
-            name: "SCRIPT",
-            arity: -2,
-            flags: &["admin"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-        // Connection commands
-        CommandInfo {
-            name: "HELLO",
-            arity: -1,
-            flags: &["fast", "stale"],
-            first_key: 0,
-            last_key: 0,
-            step: 0,
-        },
-    ]
+static COMMAND_TABLE: &[CommandInfo] = &[
+    // String commands
+    CommandInfo {
+        name: "GET",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SET",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "DEL",
+        arity: -2,
+        flags: &["write"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "EXISTS",
+        arity: -2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "MGET",
+        arity: -2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "MSET",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 2,
+    },
+    CommandInfo {
+        name: "STRLEN",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "APPEND",
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "INCR",
+        arity: 2,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "DECR",
+        arity: 2,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "INCRBY",
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "DECRBY",
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "INCRBYFLOAT",
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "GETRANGE",
+        arity: 4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SETRANGE",
+        arity: 4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "GETEX",
+        arity: -2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "GETDEL",
+        arity: 2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SETNX",
+        arity: 3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SETEX",
+        arity: 4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "PSETEX",
+        arity: 4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SETBIT",
+        arity: 4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "LCS",
+        arity: -3,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 2,
+        step: 1,
+    },
+    // JSON commands
+    CommandInfo {
+        name: "JSON.GET",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "JSON.SET",
+        arity: -4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "JSON.DEL",
+        arity: -2,
+        flags: &["write"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "JSON.TYPE",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "JSON.STRLEN",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "JSON.ARRLEN",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "JSON.OBJLEN",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "JSON.ARRAPPEND",
+        arity: -4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "JSON.ARRINSERT",
+        arity: -5,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "JSON.ARRPOP",
+        arity: -2,
+        flags: &["write"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "JSON.ARRTRIM",
+        arity: 5,
+        flags: &["write"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "JSON.NUMINCRBY",
+        arity: 4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "JSON.NUMMULTBY",
+        arity: 4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "JSON.TOGGLE",
+        arity: 3,
+        flags: &["write"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "JSON.MGET",
+        arity: -3,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: -2,
+        step: 1,
+    },
+    // List commands
+    CommandInfo {
+        name: "LPUSH",
+        arity: -3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "RPUSH",
+        arity: -3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "LPOP",
+        arity: -2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "RPOP",
+        arity: -2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "LLEN",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "LRANGE",
+        arity: 4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "LINDEX",
+        arity: 3,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "LSET",
+        arity: 4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "LREM",
+        arity: 4,
+        flags: &["write"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "LTRIM",
+        arity: 4,
+        flags: &["write"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "LINSERT",
+        arity: 5,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "LMOVE",
+        arity: 5,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 2,
+        step: 1,
+    },
+    CommandInfo {
+        name: "LMPOP",
+        arity: -4,
+        flags: &["write", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "BLMPOP",
+        arity: -5,
+        flags: &["write", "blocking", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "LPOS",
+        arity: -3,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    // Hash commands
+    CommandInfo {
+        name: "HSET",
+        arity: -4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "HSETNX",
+        arity: 4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "HGET",
+        arity: 3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "HMGET",
+        arity: -3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "HMSET",
+        arity: -4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "HDEL",
+        arity: -3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "HEXISTS",
+        arity: 3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "HLEN",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "HKEYS",
+        arity: 2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "HVALS",
+        arity: 2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "HGETALL",
+        arity: 2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "HINCRBY",
+        arity: 4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "HINCRBYFLOAT",
+        arity: 4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "HSCAN",
+        arity: -3,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    // Set commands
+    CommandInfo {
+        name: "SADD",
+        arity: -3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SREM",
+        arity: -3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SISMEMBER",
+        arity: 3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SMEMBERS",
+        arity: 2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SCARD",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SPOP",
+        arity: -2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SRANDMEMBER",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SUNION",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SINTER",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SINTERCARD",
+        arity: -3,
+        flags: &["readonly", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "SDIFF",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SUNIONSTORE",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SINTERSTORE",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SDIFFSTORE",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SMOVE",
+        arity: 4,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 2,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SSCAN",
+        arity: -3,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    // Sorted Set commands
+    CommandInfo {
+        name: "ZADD",
+        arity: -4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZREM",
+        arity: -3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZSCORE",
+        arity: 3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZRANK",
+        arity: 3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZREVRANK",
+        arity: 3,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZRANGE",
+        arity: -4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZREVRANGE",
+        arity: -4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZRANGEBYSCORE",
+        arity: -4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZREVRANGEBYSCORE",
+        arity: -4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZCARD",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZCOUNT",
+        arity: 4,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZINCRBY",
+        arity: 4,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZPOPMIN",
+        arity: -2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZPOPMAX",
+        arity: -2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZMPOP",
+        arity: -4,
+        flags: &["write", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "BZMPOP",
+        arity: -5,
+        flags: &["write", "blocking", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "ZRANGEBYLEX",
+        arity: -4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZREVRANGEBYLEX",
+        arity: -4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZLEXCOUNT",
+        arity: 4,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "ZSCAN",
+        arity: -3,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    // Geo commands
+    CommandInfo {
+        name: "GEOADD",
+        arity: -5,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "GEOPOS",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "GEODIST",
+        arity: -4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "GEOSEARCH",
+        arity: -7,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    // HyperLogLog commands
+    CommandInfo {
+        name: "PFADD",
+        arity: -2,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "PFCOUNT",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "PFMERGE",
+        arity: -2,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+    },
+    // Stream commands
+    CommandInfo {
+        name: "XADD",
+        arity: -5,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "XLEN",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "XRANGE",
+        arity: -4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "XREAD",
+        arity: -4,
+        flags: &["readonly", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    // Database commands
+    CommandInfo {
+        name: "SELECT",
+        arity: 2,
+        flags: &["fast", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "DBSIZE",
+        arity: 1,
+        flags: &["readonly", "fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "FLUSHDB",
+        arity: -1,
+        flags: &["write"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "FLUSHALL",
+        arity: -1,
+        flags: &["write"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "SWAPDB",
+        arity: 3,
+        flags: &["write", "fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "MOVE",
+        arity: 3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    // Key commands
+    CommandInfo {
+        name: "KEYS",
+        arity: 2,
+        flags: &["readonly"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "SCAN",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "RANDOMKEY",
+        arity: 1,
+        flags: &["readonly"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "RENAME",
+        arity: 3,
+        flags: &["write"],
+        first_key: 1,
+        last_key: 2,
+        step: 1,
+    },
+    CommandInfo {
+        name: "RENAMENX",
+        arity: 3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 2,
+        step: 1,
+    },
+    CommandInfo {
+        name: "TYPE",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "COPY",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 2,
+        step: 1,
+    },
+    CommandInfo {
+        name: "SORT",
+        arity: -2,
+        flags: &["write", "denyoom", "movablekeys"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "DUMP",
+        arity: 2,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "OBJECT",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 2,
+        last_key: 2,
+        step: 1,
+    },
+    CommandInfo {
+        name: "MEMORY",
+        arity: -2,
+        flags: &["readonly"],
+        first_key: 2,
+        last_key: 2,
+        step: 1,
+    },
+    CommandInfo {
+        name: "RESTORE",
+        arity: -4,
+        flags: &["write", "denyoom"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "MIGRATE",
+        arity: -6,
+        flags: &["write", "movablekeys"],
+        first_key: 3,
+        last_key: 3,
+        step: 1,
+    },
+    CommandInfo {
+        name: "EXPIRE",
+        arity: -3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "EXPIREAT",
+        arity: -3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "PEXPIRE",
+        arity: -3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "PEXPIREAT",
+        arity: -3,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "TTL",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "PTTL",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "PERSIST",
+        arity: 2,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "EXPIRETIME",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "PEXPIRETIME",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    // Server commands
+    CommandInfo {
+        name: "PING",
+        arity: -1,
+        flags: &["fast", "stale", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "ECHO",
+        arity: 2,
+        flags: &["fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "INFO",
+        arity: -1,
+        flags: &["stale", "fast", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "CONFIG",
+        arity: -2,
+        flags: &["admin", "stale", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "SLOWLOG",
+        arity: -2,
+        flags: &["admin", "stale", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "TIME",
+        arity: 1,
+        flags: &["fast", "stale", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "CLIENT",
+        arity: -2,
+        flags: &["admin", "stale", "noscript", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "COMMAND",
+        arity: -1,
+        flags: &["stale", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "SAVE",
+        arity: 1,
+        flags: &["admin", "noscript"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "BGSAVE",
+        arity: -1,
+        flags: &["admin", "noscript"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "BGREWRITEAOF",
+        arity: 1,
+        flags: &["admin", "noscript"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "DEBUG",
+        arity: -2,
+        flags: &["admin", "noscript", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "FSYNC",
+        arity: 1,
+        flags: &["admin", "noscript"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "LASTSAVE",
+        arity: 1,
+        flags: &["fast", "stale", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "WAIT",
+        arity: 3,
+        flags: &["noscript"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "WAITAOF",
+        arity: 4,
+        flags: &["noscript"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "SHUTDOWN",
+        arity: -1,
+        flags: &["admin", "noscript", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "MONITOR",
+        arity: 1,
+        flags: &["admin", "noscript", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    // Script commands
+    CommandInfo {
+        name: "EVAL",
+        arity: -3,
+        flags: &["write", "denyoom", "noscript", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "EVALSHA",
+        arity: -3,
+        flags: &["write", "denyoom", "noscript", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "EVAL_RO",
+        arity: -3,
+        flags: &["readonly", "noscript", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "EVALSHA_RO",
+        arity: -3,
+        flags: &["readonly", "noscript", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "SCRIPT",
+        arity: -2,
+        flags: &["admin", "noscript"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    // Connection commands
+    CommandInfo {
+        name: "HELLO",
+        arity: -1,
+        flags: &["fast", "stale", "noscript", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "ACL",
+        arity: -2,
+        flags: &["admin", "noscript", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "AUTH",
+        arity: -2,
+        flags: &["fast", "stale", "noscript", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "RESET",
+        arity: 1,
+        flags: &["fast", "stale", "noscript", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    // Pub/Sub commands
+    CommandInfo {
+        name: "PUBLISH",
+        arity: 3,
+        flags: &["pubsub", "fast", "stale", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "SUBSCRIBE",
+        arity: -2,
+        flags: &["pubsub", "noscript", "stale", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "UNSUBSCRIBE",
+        arity: -1,
+        flags: &["pubsub", "noscript", "stale", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "PSUBSCRIBE",
+        arity: -2,
+        flags: &["pubsub", "noscript", "stale", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "PUNSUBSCRIBE",
+        arity: -1,
+        flags: &["pubsub", "noscript", "stale", "loading"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    // Cluster commands
+    CommandInfo {
+        name: "CLUSTER",
+        arity: -2,
+        flags: &["stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "READONLY",
+        arity: 1,
+        flags: &["fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "READWRITE",
+        arity: 1,
+        flags: &["fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "ASKING",
+        arity: 1,
+        flags: &["fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+];
+
+/// Format a byte count the way Redis does in INFO (e.g. "1.50M")
+fn bytes_to_human(bytes: u64) -> String {
+    const UNITS: [(&str, u64); 4] = [
+        ("T", 1 << 40),
+        ("G", 1 << 30),
+        ("M", 1 << 20),
+        ("K", 1 << 10),
+    ];
+    for (suffix, size) in UNITS {
+        if bytes >= size {
+            return format!("{:.2}{}", bytes as f64 / size as f64, suffix);
+        }
+    }
+    format!("{}B", bytes)
 }
 
 /// Generate a random 40-character hex string for run_id (similar to Redis)
@@ -982,32 +2037,27 @@ impl ServerCommands {
         port: u16,
         cluster_enabled: bool,
     ) -> Self {
-        let mut default_config = HashMap::new();
-        default_config.insert("server".to_string(), "aikv".to_string());
-        default_config.insert("version".to_string(), AIKV_VERSION.to_string());
-        default_config.insert("port".to_string(), port.to_string());
-        default_config.insert("databases".to_string(), "16".to_string());
-        default_config.insert("loglevel".to_string(), "info".to_string());
-        default_config.insert("slowlog-log-slower-than".to_string(), "10000".to_string());
-        default_config.insert("slowlog-max-len".to_string(), "128".to_string());
-
-        // Initialize last_save_time to current time
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
+        let mut config = RuntimeConfig::new(port);
+        Self::sync_storage_config(&mut config, &storage);
 
         Self {
             storage,
             clients: Arc::new(RwLock::new(HashMap::new())),
-            config: Arc::new(RwLock::new(default_config)),
+            config: Arc::new(RwLock::new(config)),
             start_time: Instant::now(),
             run_id: generate_run_id(),
             tcp_port: port,
             current_log_level: Arc::new(RwLock::new(Level::INFO)),
             slow_query_log: Arc::new(SlowQueryLog::new()),
-            last_save_time: Arc::new(AtomicU64::new(now)),
-            shutdown_requested: Arc::new(AtomicBool::new(false)),
+            metrics: Arc::new(Metrics::new()),
+            rdb: Arc::new(RdbState::default()),
+            aof: None,
+            shutdown: ShutdownSignal::new(),
+            save_on_shutdown: false,
+            active_expire: Arc::new(AtomicBool::new(true)),
+            loading: Arc::new(AtomicBool::new(false)),
+            pubsub: Arc::new(PubSub::new()),
+            acl_users: acl::default_users(),
             cluster_enabled,
         }
     }
@@ -1025,6 +2075,38 @@ impl ServerCommands {
             .unwrap_or_else(|e| e.into_inner())
     }
 
+    /// How long a client may stay idle before its connection is closed
+    /// (`timeout`), or `None` if idle clients are never closed
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        let config = self.config.read().ok()?;
+        let seconds = config.get("timeout")?.parse::<u64>().ok()?;
+        (seconds > 0).then(|| Duration::from_secs(seconds))
+    }
+
+    /// Longest bulk string a client may send (`proto-max-bulk-len`)
+    pub fn proto_max_bulk_len(&self) -> usize {
+        self.config
+            .read()
+            .ok()
+            .and_then(|config| config.get("proto-max-bulk-len")?.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_BULK_LEN)
+    }
+
+    /// How much output each class of client may have pending before its
+    /// connection is closed (`client-output-buffer-limit`)
+    pub fn output_buffer_limits(&self) -> OutputBufferLimits {
+        let defaults = OutputBufferLimits::default();
+        self.config
+            .read()
+            .ok()
+            .and_then(|config| {
+                defaults
+                    .update(config.get("client-output-buffer-limit")?)
+                    .ok()
+            })
+            .unwrap_or(defaults)
+    }
+
     /// Get server uptime in seconds
     fn uptime_seconds(&self) -> u64 {
         self.start_time.elapsed().as_secs()
@@ -1091,7 +2173,7 @@ impl ServerCommands {
             "# Clients".to_string(),
             format!("connected_clients:{}", clients.len()),
             "cluster_connections:0".to_string(),
-            "maxclients:10000".to_string(),
+            format!("maxclients:{}", self.config_value("maxclients", "10000")),
             "client_recent_max_input_buffer:0".to_string(),
             "client_recent_max_output_buffer:0".to_string(),
             "blocked_clients:0".to_string(),
@@ -1101,11 +2183,15 @@ impl ServerCommands {
     }
 
     /// Build the Memory section info lines
-    fn build_memory_info(&self) -> Vec<String> {
-        vec![
+    fn build_memory_info(&self) -> Result<Vec<String>> {
+        let used_memory = self.storage.used_memory()? as u64;
+        let limit = self.storage.memory_limit()?;
+        let maxmemory = limit.maxmemory as u64;
+
+        Ok(vec![
             "# Memory".to_string(),
-            "used_memory:1024000".to_string(),
-            "used_memory_human:1000.00K".to_string(),
+            format!("used_memory:{}", used_memory),
+            format!("used_memory_human:{}", bytes_to_human(used_memory)),
             "used_memory_rss:2048000".to_string(),
             "used_memory_rss_human:2.00M".to_string(),
             "used_memory_peak:1024000".to_string(),
@@ -1113,7 +2199,7 @@ impl ServerCommands {
             "used_memory_peak_perc:100.00%".to_string(),
             "used_memory_overhead:1000000".to_string(),
             "used_memory_startup:1000000".to_string(),
-            "used_memory_dataset:24000".to_string(),
+            format!("used_memory_dataset:{}", used_memory),
             "used_memory_dataset_perc:0.00%".to_string(),
             "allocator_allocated:1024000".to_string(),
             "allocator_active:2048000".to_string(),
@@ -1124,9 +2210,9 @@ impl ServerCommands {
             "used_memory_lua_human:31.00K".to_string(),
             "used_memory_scripts:0".to_string(),
             "used_memory_scripts_human:0B".to_string(),
-            "maxmemory:0".to_string(),
-            "maxmemory_human:0B".to_string(),
-            "maxmemory_policy:noeviction".to_string(),
+            format!("maxmemory:{}", maxmemory),
+            format!("maxmemory_human:{}", bytes_to_human(maxmemory)),
+            format!("maxmemory_policy:{}", limit.policy.as_str()),
             "allocator_frag_ratio:1.00".to_string(),
             "allocator_frag_bytes:0".to_string(),
             "allocator_rss_ratio:1.00".to_string(),
@@ -1144,7 +2230,7 @@ impl ServerCommands {
             "active_defrag_running:0".to_string(),
             "lazyfree_pending_objects:0".to_string(),
             "lazyfreed_objects:0".to_string(),
-        ]
+        ])
     }
 
     /// Build the Stats section info lines
@@ -1158,11 +2244,14 @@ impl ServerCommands {
             "total_net_output_bytes:0".to_string(),
             "instantaneous_input_kbps:0.00".to_string(),
             "instantaneous_output_kbps:0.00".to_string(),
-            "rejected_connections:0".to_string(),
+            format!(
+                "rejected_connections:{}",
+                self.metrics.connections.rejected_connections()
+            ),
             "sync_full:0".to_string(),
             "sync_partial_ok:0".to_string(),
             "sync_partial_err:0".to_string(),
-            "expired_keys:0".to_string(),
+            format!("expired_keys:{}", self.metrics.memory.expired_keys.get()),
             "expired_stale_perc:0.00".to_string(),
             "expired_time_cap_reached_count:0".to_string(),
             "expire_cycle_cpu_milliseconds:0".to_string(),
@@ -1212,14 +2301,17 @@ impl ServerCommands {
 
     /// Build the CPU section info lines
     fn build_cpu_info(&self) -> Vec<String> {
+        let (sys, user) = cpu_usage(CpuScope::Process);
+        let (sys_children, user_children) = cpu_usage(CpuScope::Children);
+        let (sys_thread, user_thread) = cpu_usage(CpuScope::Thread);
         vec![
             "# CPU".to_string(),
-            "used_cpu_sys:0.000000".to_string(),
-            "used_cpu_user:0.000000".to_string(),
-            "used_cpu_sys_children:0.000000".to_string(),
-            "used_cpu_user_children:0.000000".to_string(),
-            "used_cpu_sys_main_thread:0.000000".to_string(),
-            "used_cpu_user_main_thread:0.000000".to_string(),
+            format!("used_cpu_sys:{:.6}", sys),
+            format!("used_cpu_user:{:.6}", user),
+            format!("used_cpu_sys_children:{:.6}", sys_children),
+            format!("used_cpu_user_children:{:.6}", user_children),
+            format!("used_cpu_sys_main_thread:{:.6}", sys_thread),
+            format!("used_cpu_user_main_thread:{:.6}", user_thread),
         ]
     }
 
@@ -1233,6 +2325,46 @@ impl ServerCommands {
         vec!["# Errorstats".to_string()]
     }
 
+    /// Build the Commandstats section info lines
+    fn build_commandstats_info(&self) -> Vec<String> {
+        let mut lines = vec!["# Commandstats".to_string()];
+        let errors = self.metrics.commands.errors_by_type();
+        if let Ok(latencies) = self.metrics.commands.latency_by_type.read() {
+            let mut commands: Vec<_> = latencies.iter().collect();
+            commands.sort_by(|a, b| a.0.cmp(b.0));
+            for (command, histogram) in commands {
+                lines.push(format!(
+                    "cmdstat_{}:calls={},usec={},usec_per_call={:.2},rejected_calls=0,failed_calls={}",
+                    command.to_lowercase(),
+                    histogram.count(),
+                    histogram.sum_us() as u64,
+                    histogram.avg_us(),
+                    errors.get(command).copied().unwrap_or(0)
+                ));
+            }
+        }
+        lines
+    }
+
+    /// Build the Latencystats section info lines
+    fn build_latencystats_info(&self) -> Vec<String> {
+        let mut lines = vec!["# Latencystats".to_string()];
+        if let Ok(latencies) = self.metrics.commands.latency_by_type.read() {
+            let mut commands: Vec<_> = latencies.iter().collect();
+            commands.sort_by(|a, b| a.0.cmp(b.0));
+            for (command, histogram) in commands {
+                lines.push(format!(
+                    "latency_percentiles_usec_{}:p50={:.3},p99={:.3},p99.9={:.3}",
+                    command.to_lowercase(),
+                    histogram.percentile_us(50.0) as f64,
+                    histogram.percentile_us(99.0) as f64,
+                    histogram.percentile_us(99.9) as f64
+                ));
+            }
+        }
+        lines
+    }
+
     /// Build the Cluster section info lines
     fn build_cluster_info(&self) -> Vec<String> {
         #[cfg(feature = "cluster")]
@@ -1246,29 +2378,64 @@ impl ServerCommands {
     }
 
     /// Build the Keyspace section info lines
-    fn build_keyspace_info(&self) -> Vec<String> {
-        vec!["# Keyspace".to_string()]
+    fn build_keyspace_info(&self) -> Result<Vec<String>> {
+        let mut lines = vec!["# Keyspace".to_string()];
+        for db in 0..self.storage.db_count() {
+            let keys = self.storage.dbsize_in_db(db)?;
+            if keys == 0 {
+                continue;
+            }
+
+            let mut expires = 0u64;
+            let mut ttl_sum = 0u64;
+            for key in self.storage.get_all_keys_in_db(db)? {
+                let ttl = self.storage.get_ttl_in_db(db, &key)?;
+                if ttl >= 0 {
+                    expires += 1;
+                    ttl_sum += ttl as u64;
+                }
+            }
+            let avg_ttl = if expires > 0 { ttl_sum / expires } else { 0 };
+            lines.push(format!(
+                "db{}:keys={},expires={},avg_ttl={}",
+                db, keys, expires, avg_ttl
+            ));
+        }
+        Ok(lines)
     }
 
     /// Build the Persistence section info lines
     fn build_persistence_info(&self) -> Vec<String> {
         vec![
             "# Persistence".to_string(),
-            "loading:0".to_string(),
+            format!("loading:{}", u8::from(self.is_loading())),
             "current_cow_size:0".to_string(),
             "current_cow_size_age:0".to_string(),
             "current_fork_perc:0.00".to_string(),
             "current_save_keys_processed:0".to_string(),
             "current_save_keys_total:0".to_string(),
             "rdb_changes_since_last_save:0".to_string(),
-            "rdb_bgsave_in_progress:0".to_string(),
-            "rdb_last_save_time:0".to_string(),
-            "rdb_last_bgsave_status:ok".to_string(),
+            format!(
+                "rdb_bgsave_in_progress:{}",
+                u8::from(self.rdb.bgsave_in_progress())
+            ),
+            format!("rdb_last_save_time:{}", self.rdb.last_save_time()),
+            format!(
+                "rdb_last_bgsave_status:{}",
+                if self.rdb.last_save_ok() { "ok" } else { "err" }
+            ),
             "rdb_last_bgsave_time_sec:-1".to_string(),
             "rdb_current_bgsave_time_sec:-1".to_string(),
             "rdb_last_cow_size:0".to_string(),
-            "aof_enabled:0".to_string(),
-            "aof_rewrite_in_progress:0".to_string(),
+            format!("aof_enabled:{}", u8::from(self.aof.is_some())),
+            format!(
+                "aof_rewrite_in_progress:{}",
+                u8::from(
+                    self.aof
+                        .as_ref()
+                        .is_some_and(|aof| aof.rewrite_in_progress())
+                )
+            ),
             "aof_rewrite_scheduled:0".to_string(),
             "aof_last_rewrite_time_sec:-1".to_string(),
             "aof_current_rewrite_time_sec:-1".to_string(),
@@ -1280,134 +2447,85 @@ impl ServerCommands {
         ]
     }
 
-    /// INFO \[section\] - Get server information
+    /// INFO \[section ...\] - Get server information
+    ///
+    /// With no argument the default sections are returned; `all` and
+    /// `everything` add the statistics sections, and any other names select
+    /// just those sections. Unknown sections are ignored, as in Redis.
     pub fn info(&self, args: &[Bytes]) -> Result<RespValue> {
-        let section = if args.is_empty() {
-            "default"
+        let mut sections: Vec<&str> = Vec::new();
+        let requested: Vec<String> = if args.is_empty() {
+            vec!["default".to_string()]
         } else {
-            &String::from_utf8_lossy(&args[0])
+            args.iter()
+                .map(|arg| String::from_utf8_lossy(arg).to_lowercase())
+                .collect()
         };
-
-        let mut info_lines = Vec::new();
-
-        match section.to_lowercase().as_str() {
-            // "default" returns the standard default sections (server, clients, memory,
-            // persistence, stats, replication, cpu, cluster, keyspace)
-            // This is required for redis-cli --cluster create to detect cluster_enabled:1
-            "default" => {
-                info_lines.extend(self.build_server_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_clients_info()?);
-                info_lines.push(String::new());
-                info_lines.extend(self.build_memory_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_persistence_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_stats_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_replication_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_cpu_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_cluster_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_keyspace_info());
-            }
-            "server" => {
-                info_lines.extend(self.build_server_info());
-            }
-            "clients" => {
-                info_lines.extend(self.build_clients_info()?);
-            }
-            "memory" => {
-                info_lines.extend(self.build_memory_info());
-            }
-            "stats" => {
-                info_lines.extend(self.build_stats_info());
-            }
-            "replication" => {
-                info_lines.extend(self.build_replication_info());
-            }
-            "cpu" => {
-                info_lines.extend(self.build_cpu_info());
-            }
-            "modules" => {
-                info_lines.extend(self.build_modules_info());
-            }
-            "errorstats" => {
-                info_lines.extend(self.build_errorstats_info());
-            }
-            "cluster" => {
-                info_lines.extend(self.build_cluster_info());
-            }
-            "keyspace" => {
-                info_lines.extend(self.build_keyspace_info());
-            }
-            "persistence" => {
-                info_lines.extend(self.build_persistence_info());
-            }
-            // "all" or "everything" returns all sections (including modules and errorstats)
-            "all" | "everything" => {
-                info_lines.extend(self.build_server_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_clients_info()?);
-                info_lines.push(String::new());
-                info_lines.extend(self.build_memory_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_persistence_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_stats_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_replication_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_cpu_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_modules_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_errorstats_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_cluster_info());
-                info_lines.push(String::new());
-                info_lines.extend(self.build_keyspace_info());
-            }
-            // For unknown sections, return empty result (Redis behavior)
-            // This is important for compatibility - Redis returns empty for unknown sections
-            _ => {
-                // Return empty bulk string for unknown sections to maintain compatibility
+        for name in &requested {
+            let names: &[&str] = match name.as_str() {
+                // This is required for redis-cli --cluster create to detect cluster_enabled:1
+                "default" => DEFAULT_INFO_SECTIONS,
+                "all" | "everything" => ALL_INFO_SECTIONS,
+                other => match ALL_INFO_SECTIONS.iter().find(|section| **section == other) {
+                    Some(section) => std::slice::from_ref(section),
+                    None => &[],
+                },
+            };
+            for section in names {
+                if !sections.contains(section) {
+                    sections.push(section);
+                }
             }
         }
 
-        let info_str = info_lines.join("\r\n");
-        Ok(RespValue::bulk_string(info_str))
+        let mut blocks = Vec::with_capacity(sections.len());
+        for section in sections {
+            blocks.push(self.build_info_section(section)?.join("\r\n"));
+        }
+        Ok(RespValue::bulk_string(blocks.join("\r\n\r\n")))
+    }
+
+    /// Lines of one INFO section
+    fn build_info_section(&self, section: &str) -> Result<Vec<String>> {
+        Ok(match section {
+            "server" => self.build_server_info(),
+            "clients" => self.build_clients_info()?,
+            "memory" => self.build_memory_info()?,
+            "persistence" => self.build_persistence_info(),
+            "stats" => self.build_stats_info(),
+            "replication" => self.build_replication_info(),
+            "cpu" => self.build_cpu_info(),
+            "modules" => self.build_modules_info(),
+            "commandstats" => self.build_commandstats_info(),
+            "errorstats" => self.build_errorstats_info(),
+            "latencystats" => self.build_latencystats_info(),
+            "cluster" => self.build_cluster_info(),
+            "keyspace" => self.build_keyspace_info()?,
+            _ => Vec::new(),
+        })
+    }
+
+    /// Value of a runtime configuration parameter, or `default` if unset
+    fn config_value(&self, name: &str, default: &str) -> String {
+        self.config
+            .read()
+            .ok()
+            .and_then(|config| config.get(name).map(str::to_string))
+            .unwrap_or_else(|| default.to_string())
     }
 
-    /// CONFIG GET parameter - Get configuration value
+    /// CONFIG GET pattern [pattern ...] - Get the parameters matching any of
+    /// the glob patterns
     pub fn config_get(&self, args: &[Bytes]) -> Result<RespValue> {
-        if args.len() != 1 {
+        if args.is_empty() {
             return Err(AikvError::WrongArgCount("CONFIG GET".to_string()));
         }
 
-        let parameter = String::from_utf8_lossy(&args[0]).to_lowercase();
         let config = self
             .config
             .read()
             .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
 
-        let mut results = Vec::new();
-
-        // Helper function to check if pattern matches key (supports * wildcard)
-        let matches_pattern = |pattern: &str, key: &str| -> bool {
-            if pattern == "*" {
-                return true;
-            }
-            // Simple glob matching for patterns like "cluster*"
-            if let Some(prefix) = pattern.strip_suffix('*') {
-                key.starts_with(prefix)
-            } else {
-                pattern == key
-            }
-        };
-
         // Built-in cluster configuration values (read-only, derived from runtime state)
         let builtin_configs: Vec<(&str, String)> = vec![
             (
@@ -1426,98 +2544,210 @@ impl ServerCommands {
             ),
         ];
 
-        // Support wildcard matching
-        if parameter == "*" {
-            // Add built-in configs first
-            for (key, value) in &builtin_configs {
-                results.push(RespValue::bulk_string(key.to_string()));
-                results.push(RespValue::bulk_string(value.clone()));
-            }
-            // Add user-defined configs
-            for (key, value) in config.iter() {
-                results.push(RespValue::bulk_string(key.clone()));
-                results.push(RespValue::bulk_string(value.clone()));
-            }
-        } else {
-            // Check built-in configs first
-            for (key, value) in &builtin_configs {
-                if matches_pattern(&parameter, key) {
-                    results.push(RespValue::bulk_string(key.to_string()));
-                    results.push(RespValue::bulk_string(value.clone()));
-                }
-            }
-            // Check user-defined configs
-            for (key, value) in config.iter() {
-                if matches_pattern(&parameter, key) {
-                    results.push(RespValue::bulk_string(key.clone()));
-                    results.push(RespValue::bulk_string(value.clone()));
+        let mut results = Vec::new();
+        let mut seen = HashSet::new();
+        for pattern in args {
+            let pattern = String::from_utf8_lossy(pattern).to_lowercase();
+            let builtins = builtin_configs
+                .iter()
+                .filter(|(key, _)| glob_match(&pattern, key))
+                .map(|(key, value)| (*key, value.as_str()));
+            for (key, value) in builtins.chain(config.matching(&pattern)) {
+                if seen.insert(key) {
+                    results.push((
+                        RespValue::bulk_string(key.to_string()),
+                        RespValue::bulk_string(value.to_string()),
+                    ));
                 }
             }
         }
 
-        Ok(RespValue::array(results))
+        Ok(RespValue::map(results))
     }
 
-    /// CONFIG SET parameter value - Set configuration value
+    /// CONFIG SET parameter value - Change a parameter of the running server
+    ///
+    /// Parameter names are matched in any case. A value that can't be parsed
+    /// is rejected with the Redis `CONFIG SET failed` error naming the
+    /// parameter, and leaves the configuration unchanged.
     pub fn config_set(&self, args: &[Bytes]) -> Result<RespValue> {
         if args.len() != 2 {
             return Err(AikvError::WrongArgCount("CONFIG SET".to_string()));
         }
 
-        let parameter = String::from_utf8_lossy(&args[0]).to_string();
+        let parameter = String::from_utf8_lossy(&args[0]).to_lowercase();
         let value = String::from_utf8_lossy(&args[1]).to_string();
 
-        // Handle special parameters with side effects (case-insensitive comparison)
-        let param_lower = parameter.to_lowercase();
-        if param_lower == "server" || param_lower == "version" || param_lower == "port" {
-            return Err(AikvError::InvalidArgument(
-                "ERR configuration parameter is read-only".to_string(),
-            ));
-        } else if param_lower == "loglevel" {
-            // Dynamic log level adjustment
-            if let Some(level) = LogConfig::parse_level(&value) {
-                if let Ok(mut current) = self.current_log_level.write() {
-                    *current = level;
+        self.config
+            .read()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?
+            .check_settable(&parameter)?;
+
+        // Apply the side effects first, so a rejected value leaves the
+        // configuration unchanged
+        let value = self.apply_config(&parameter, value).map_err(|e| match e {
+            AikvError::InvalidArgument(reason) => AikvError::InvalidArgument(format!(
+                "ERR CONFIG SET failed (possibly related to argument '{}') - {}",
+                parameter,
+                reason.strip_prefix("ERR ").unwrap_or(&reason)
+            )),
+            other => other,
+        })?;
+
+        self.config
+            .write()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?
+            .set(&parameter, value);
+        Ok(RespValue::ok())
+    }
+
+    /// Apply the side effects of setting `parameter` to `value`, returning the
+    /// value in the form CONFIG GET reports it
+    fn apply_config(&self, parameter: &str, mut value: String) -> Result<String> {
+        match parameter {
+            "loglevel" => {
+                // Dynamic log level adjustment
+                if let Some(level) = LogConfig::parse_level(&value) {
+                    if let Ok(mut current) = self.current_log_level.write() {
+                        *current = level;
+                    }
+                    value = value.to_lowercase();
+                } else {
+                    return Err(AikvError::InvalidArgument(format!(
+                        "ERR invalid log level: {}",
+                        value
+                    )));
                 }
-            } else {
-                return Err(AikvError::InvalidArgument(format!(
-                    "ERR invalid log level: {}",
-                    value
-                )));
             }
-        } else if param_lower == "slowlog-log-slower-than" {
-            // Update slow query threshold
-            match value.parse::<u64>() {
-                Ok(threshold) => {
-                    self.slow_query_log.set_threshold_us(threshold);
-                }
-                Err(_) => {
-                    return Err(AikvError::InvalidArgument(
-                        "ERR invalid slowlog threshold value".to_string(),
-                    ));
+            "slowlog-log-slower-than" => {
+                // Update slow query threshold; a negative value disables the log
+                match value.parse::<i64>() {
+                    Ok(threshold) if threshold < 0 => self.slow_query_log.disable(),
+                    Ok(threshold) => {
+                        self.slow_query_log.set_threshold_us(threshold as u64);
+                    }
+                    Err(_) => {
+                        return Err(AikvError::InvalidArgument(
+                            "ERR invalid slowlog threshold value".to_string(),
+                        ));
+                    }
                 }
             }
-        } else if param_lower == "slowlog-max-len" {
-            // Update slow query max length
-            match value.parse::<usize>() {
-                Ok(max_len) => {
-                    self.slow_query_log.set_max_len(max_len);
+            "lua-time-limit" => {
+                // Read by each script as it starts; zero or negative disables the limit
+                let limit = value.parse::<i64>().map_err(|_| {
+                    AikvError::InvalidArgument(
+                        "ERR argument couldn't be parsed into an integer".to_string(),
+                    )
+                })?;
+                value = limit.to_string();
+            }
+            "maxclients" => {
+                // Checked by the server on every accept
+                let maxclients = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| {
+                        AikvError::InvalidArgument(
+                            "ERR argument must be a positive integer".to_string(),
+                        )
+                    })?;
+                value = maxclients.to_string();
+            }
+            "proto-max-bulk-len" => {
+                // Read by each connection's parser and by the commands that
+                // grow a string; Redis won't go below 1mb either
+                let limit = parse_memory(&value)
+                    .filter(|&n| n >= 1024 * 1024)
+                    .ok_or_else(|| {
+                        AikvError::InvalidArgument(
+                            "ERR argument must be a memory value of at least 1048576 bytes"
+                                .to_string(),
+                        )
+                    })?;
+                value = limit.to_string();
+            }
+            "timeout" | "tcp-keepalive" => {
+                // Seconds; read by each connection, zero disables
+                let seconds = value.parse::<u64>().map_err(|_| {
+                    AikvError::InvalidArgument(
+                        "ERR argument couldn't be parsed into an integer".to_string(),
+                    )
+                })?;
+                value = seconds.to_string();
+            }
+            "client-output-buffer-limit" => {
+                // Only the classes given change; read by each connection as it writes
+                value = self.output_buffer_limits().update(&value)?.to_string();
+            }
+            "slowlog-max-len" => {
+                // Update slow query max length
+                match value.parse::<usize>() {
+                    Ok(max_len) => {
+                        self.slow_query_log.set_max_len(max_len);
+                    }
+                    Err(_) => {
+                        return Err(AikvError::InvalidArgument(
+                            "ERR invalid slowlog max length value".to_string(),
+                        ));
+                    }
                 }
-                Err(_) => {
+            }
+            "rdbchecksum" => match value.to_lowercase().as_str() {
+                "yes" => self.rdb.set_checksum(true),
+                "no" => self.rdb.set_checksum(false),
+                _ => {
                     return Err(AikvError::InvalidArgument(
-                        "ERR invalid slowlog max length value".to_string(),
+                        "ERR argument must be 'yes' or 'no'".to_string(),
                     ));
                 }
+            },
+            "maxmemory" => {
+                let maxmemory = parse_memory(&value).ok_or_else(|| {
+                    AikvError::InvalidArgument("ERR argument must be a memory value".to_string())
+                })?;
+                let limit = self.storage.memory_limit()?;
+                self.storage.set_memory_limit(MemoryLimit {
+                    maxmemory: maxmemory as usize,
+                    ..limit
+                })?;
+                value = maxmemory.to_string();
+            }
+            "maxmemory-policy" => {
+                let policy = EvictionPolicy::parse(&value).ok_or_else(|| {
+                    AikvError::InvalidArgument(format!("ERR invalid maxmemory policy: {}", value))
+                })?;
+                let limit = self.storage.memory_limit()?;
+                self.storage.set_memory_limit(MemoryLimit {
+                    policy,
+                    ..limit
+                })?;
+                value = policy.as_str().to_string();
+            }
+            "appendfsync" => {
+                let policy = value.parse::<AofSyncPolicy>().map_err(|_| {
+                    AikvError::InvalidArgument(
+                        "ERR argument must be one of: always, everysec, no".to_string(),
+                    )
+                })?;
+                if let Some(aof) = &self.aof {
+                    aof.set_sync_policy(policy)?;
+                }
+                value = policy.as_str().to_string();
+            }
+            "notify-keyspace-events" => {
+                let events = KeyspaceEvents::parse(&value).ok_or_else(|| {
+                    AikvError::InvalidArgument(
+                        "ERR Invalid event class character. Use 'Ag$lshzxeKEtmn'.".to_string(),
+                    )
+                })?;
+                self.pubsub.set_keyspace_events(events);
+                value = events.to_string();
             }
+            _ => {}
         }
-
-        let mut config = self
-            .config
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        config.insert(parameter, value);
-        Ok(RespValue::ok())
+        Ok(value)
     }
 
     /// SLOWLOG subcommand - Manage the slow query log
@@ -1530,13 +2760,18 @@ impl ServerCommands {
 
         match subcommand.as_str() {
             "GET" => {
-                // SLOWLOG GET [count]
-                let count = if args.len() > 1 {
-                    String::from_utf8_lossy(&args[1])
-                        .parse::<usize>()
-                        .unwrap_or(10)
-                } else {
-                    10
+                // SLOWLOG GET [count], where -1 returns every entry
+                let count = match args.get(1) {
+                    None => 10,
+                    Some(arg) => match String::from_utf8_lossy(arg).parse::<i64>() {
+                        Ok(-1) => usize::MAX,
+                        Ok(count) if count >= 0 => count as usize,
+                        _ => {
+                            return Err(AikvError::InvalidArgument(
+                                "ERR count should be greater than or equal to -1".to_string(),
+                            ))
+                        }
+                    },
                 };
 
                 let entries = self.slow_query_log.get(count);
@@ -1576,23 +2811,31 @@ impl ServerCommands {
                 self.slow_query_log.reset();
                 Ok(RespValue::ok())
             }
-            "HELP" => {
-                // SLOWLOG HELP
-                Ok(RespValue::array(vec![
-                    RespValue::bulk_string("SLOWLOG GET [count] - Get the slow log entries"),
-                    RespValue::bulk_string("SLOWLOG LEN - Get the slow log length"),
-                    RespValue::bulk_string("SLOWLOG RESET - Reset the slow log"),
-                    RespValue::bulk_string("SLOWLOG HELP - Show this help"),
-                ]))
-            }
-            _ => Err(AikvError::InvalidCommand(format!(
-                "Unknown SLOWLOG subcommand: {}",
-                subcommand
-            ))),
+            "HELP" => Ok(help_reply(
+                "SLOWLOG",
+                &[
+                    "GET [<count>]",
+                    "    Return top <count> entries from the slowlog (default: 10, -1 mean all).",
+                    "    Entries are made of:",
+                    "    id, timestamp, time in microseconds, arguments array, client IP and port,",
+                    "    client name",
+                    "LEN",
+                    "    Return the length of the slowlog.",
+                    "RESET",
+                    "    Reset the slowlog.",
+                ],
+            )),
+            _ => Err(unknown_subcommand(
+                "SLOWLOG",
+                &String::from_utf8_lossy(&args[0]),
+            )),
         }
     }
 
     /// TIME - Return the current server time
+    ///
+    /// As Redis does: unix seconds, then the microseconds elapsed within
+    /// that second, both as bulk strings.
     pub fn time(&self, _args: &[Bytes]) -> Result<RespValue> {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -1629,11 +2872,17 @@ impl ServerCommands {
     }
 
     /// CLIENT SETNAME name - Set client name
-    pub fn client_setname(&self, args: &[Bytes], client_id: usize) -> Result<RespValue> {
+    pub fn client_setname(&self, args: &[Bytes], client: &mut ClientContext) -> Result<RespValue> {
         if args.len() != 1 {
             return Err(AikvError::WrongArgCount("CLIENT SETNAME".to_string()));
         }
 
+        if args[0].iter().any(|&b| !(b'!'..=b'~').contains(&b)) {
+            return Err(AikvError::InvalidArgument(
+                "ERR Client names cannot contain spaces, newlines or special characters."
+                    .to_string(),
+            ));
+        }
         let name = String::from_utf8_lossy(&args[0]).to_string();
 
         let mut clients = self
@@ -1641,45 +2890,309 @@ impl ServerCommands {
             .write()
             .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
 
-        if let Some(client) = clients.get_mut(&client_id) {
-            client.name = Some(name);
+        // The registry's copy is the one CLIENT LIST shows
+        if let Some(info) = clients.get_mut(&client.client_id) {
+            info.name = Some(name.clone());
         }
+        client.name = Some(name);
 
         Ok(RespValue::ok())
     }
 
     /// CLIENT GETNAME - Get client name
-    pub fn client_getname(&self, _args: &[Bytes], client_id: usize) -> Result<RespValue> {
+    pub fn client_getname(&self, _args: &[Bytes], client: &ClientContext) -> Result<RespValue> {
+        match &client.name {
+            Some(name) => Ok(RespValue::bulk_string(name.clone())),
+            None => Ok(RespValue::null_bulk_string()),
+        }
+    }
+
+    /// AUTH \[username\] password - Authenticate the connection
+    ///
+    /// Without a username the password is checked for the `default` user.
+    pub fn auth(&self, args: &[Bytes], client: &mut ClientContext) -> Result<RespValue> {
+        match args {
+            [password] => {
+                if client.auth.requirepass.is_none() {
+                    return Err(AikvError::InvalidArgument(
+                        "ERR AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?".to_string(),
+                    ));
+                }
+                self.login(client, acl::DEFAULT_USER.as_bytes(), password)?;
+            }
+            [username, password] => self.login(client, username, password)?,
+            _ => return Err(AikvError::WrongArgCount("AUTH".to_string())),
+        }
+        Ok(RespValue::ok())
+    }
+
+    /// Authenticate the connection as `username`
+    ///
+    /// The `default` user's password is `requirepass` when one is
+    /// configured; every other user is checked against the ACL table.
+    fn login(&self, client: &mut ClientContext, username: &[u8], password: &[u8]) -> Result<()> {
+        match &client.auth.requirepass {
+            Some(expected) if username == acl::DEFAULT_USER.as_bytes() => {
+                if !constant_time_eq(expected.as_bytes(), password) {
+                    return Err(AikvError::WrongPass);
+                }
+            }
+            _ => acl::authenticate(&self.acl_users, username, password)?,
+        }
+        client.auth.user = String::from_utf8_lossy(username).to_string();
+        client.auth.authenticated = true;
+        Ok(())
+    }
+
+    /// RESET
+    ///
+    /// Put the connection back to RESP2 and database 0, clear its name and
+    /// NO-TOUCH mode and, when a password is required, its authentication.
+    /// Subscriptions are dropped by the connection, which owns their delivery.
+    pub fn reset(&self, client: &mut ClientContext) -> Result<RespValue> {
+        let mut clients = self
+            .clients
+            .write()
+            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        if let Some(info) = clients.get_mut(&client.client_id) {
+            info.name = None;
+        }
+
+        client.protocol = ProtocolVersion::Resp2;
+        client.db = 0;
+        client.name = None;
+        client.no_touch = false;
+        client.readonly = false;
+        client.auth.authenticated = client.auth.requirepass.is_none();
+        client.auth.user = acl::DEFAULT_USER.to_string();
+        Ok(RespValue::simple_string("RESET"))
+    }
+
+    /// HELLO \[protover \[AUTH username password\] \[SETNAME clientname\]\]
+    ///
+    /// Negotiate the protocol version for a connection and return the server
+    /// properties. Without arguments the current version is kept. The
+    /// client's protocol is switched only once the whole command succeeded;
+    /// the reply is a map (flattened to an array under RESP2 by the serializer).
+    /// An unauthenticated connection must pass AUTH to use HELLO.
+    pub fn hello(&self, args: &[Bytes], client: &mut ClientContext) -> Result<RespValue> {
+        let mut version = client.protocol;
+
+        if !args.is_empty() {
+            version = match String::from_utf8_lossy(&args[0]).parse::<i64>() {
+                Ok(2) => ProtocolVersion::Resp2,
+                Ok(3) => ProtocolVersion::Resp3,
+                Ok(_) => return Err(AikvError::NoProto),
+                Err(_) => {
+                    return Err(AikvError::InvalidArgument(
+                        "ERR Protocol version is not an integer or out of range".to_string(),
+                    ))
+                }
+            };
+        }
+
+        let mut name = None;
+        let mut i = 1;
+        while i < args.len() {
+            let option = String::from_utf8_lossy(&args[i]).to_uppercase();
+            match option.as_str() {
+                "AUTH" if i + 2 < args.len() => {
+                    self.login(client, &args[i + 1], &args[i + 2])?;
+                    i += 3;
+                }
+                "SETNAME" if i + 1 < args.len() => {
+                    name = Some(args[i + 1].clone());
+                    i += 2;
+                }
+                _ => {
+                    return Err(AikvError::InvalidArgument(format!(
+                        "ERR Syntax error in HELLO option '{}'",
+                        String::from_utf8_lossy(&args[i])
+                    )))
+                }
+            }
+        }
+
+        if !client.auth.authenticated {
+            return Err(AikvError::NoAuth(
+                "HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and select the RESP protocol version at the same time".to_string(),
+            ));
+        }
+
+        if let Some(name) = name {
+            self.client_setname(&[name], client)?;
+        }
+
+        let mode = if self.cluster_enabled {
+            "cluster"
+        } else {
+            "standalone"
+        };
+        let proto = match version {
+            ProtocolVersion::Resp2 => 2,
+            ProtocolVersion::Resp3 => 3,
+        };
+
+        let reply = RespValue::map(vec![
+            (
+                RespValue::bulk_string("server"),
+                RespValue::bulk_string("aikv"),
+            ),
+            (
+                RespValue::bulk_string("version"),
+                RespValue::bulk_string(REDIS_COMPAT_VERSION),
+            ),
+            (RespValue::bulk_string("proto"), RespValue::integer(proto)),
+            (
+                RespValue::bulk_string("id"),
+                RespValue::integer(client.client_id as i64),
+            ),
+            (RespValue::bulk_string("mode"), RespValue::bulk_string(mode)),
+            (
+                RespValue::bulk_string("role"),
+                RespValue::bulk_string("master"),
+            ),
+            (
+                RespValue::bulk_string("modules"),
+                RespValue::array(Vec::new()),
+            ),
+        ]);
+
+        client.protocol = version;
+        Ok(reply)
+    }
+
+    /// CLIENT ID - Get the current connection's id
+    pub fn client_id(&self, args: &[Bytes], client_id: usize) -> Result<RespValue> {
+        if !args.is_empty() {
+            return Err(AikvError::WrongArgCount("CLIENT ID".to_string()));
+        }
+        Ok(RespValue::integer(client_id as i64))
+    }
+
+    /// CLIENT NO-TOUCH ON|OFF - Stop (or resume) the connection's commands
+    /// from updating the access time and frequency of the keys they read
+    pub fn client_no_touch(&self, args: &[Bytes], client: &mut ClientContext) -> Result<RespValue> {
+        let [flag] = args else {
+            return Err(AikvError::WrongArgCount("CLIENT NO-TOUCH".to_string()));
+        };
+        client.no_touch = match String::from_utf8_lossy(flag).to_uppercase().as_str() {
+            "ON" => true,
+            "OFF" => false,
+            _ => return Err(AikvError::InvalidArgument("ERR syntax error".to_string())),
+        };
+        Ok(RespValue::ok())
+    }
+
+    /// CLIENT KILL - Close client connections
+    ///
+    /// Supports the legacy `CLIENT KILL addr:port` form, which replies OK, and
+    /// the filter form `CLIENT KILL [ID id] [ADDR addr:port] [SKIPME yes|no]`,
+    /// which replies with the number of clients killed. The caller is skipped
+    /// unless `SKIPME no` is given.
+    pub fn client_kill(&self, args: &[Bytes], client_id: usize) -> Result<RespValue> {
+        if args.is_empty() {
+            return Err(AikvError::WrongArgCount("CLIENT KILL".to_string()));
+        }
+
         let clients = self
             .clients
             .read()
             .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
 
-        if let Some(client) = clients.get(&client_id) {
-            if let Some(name) = &client.name {
-                return Ok(RespValue::bulk_string(name.clone()));
+        // Legacy form: CLIENT KILL addr:port
+        if args.len() == 1 {
+            let addr = String::from_utf8_lossy(&args[0]);
+            return match clients.values().find(|c| c.addr == addr) {
+                Some(client) => {
+                    client.kill_signal.notify_one();
+                    Ok(RespValue::ok())
+                }
+                None => Err(AikvError::InvalidArgument("ERR No such client".to_string())),
+            };
+        }
+
+        if args.len() % 2 != 0 {
+            return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
+        }
+
+        let mut id_filter = None;
+        let mut addr_filter = None;
+        let mut skip_me = true;
+        for pair in args.chunks(2) {
+            let option = String::from_utf8_lossy(&pair[0]).to_uppercase();
+            let value = String::from_utf8_lossy(&pair[1]).to_string();
+            match option.as_str() {
+                "ID" => {
+                    id_filter = Some(value.parse::<usize>().map_err(|_| {
+                        AikvError::InvalidArgument(
+                            "ERR client-id should be greater than 0".to_string(),
+                        )
+                    })?);
+                }
+                "ADDR" => addr_filter = Some(value),
+                "SKIPME" => {
+                    skip_me = match value.to_lowercase().as_str() {
+                        "yes" => true,
+                        "no" => false,
+                        _ => {
+                            return Err(AikvError::InvalidArgument("ERR syntax error".to_string()))
+                        }
+                    };
+                }
+                _ => return Err(AikvError::InvalidArgument("ERR syntax error".to_string())),
             }
         }
 
-        Ok(RespValue::null_bulk_string())
+        let mut killed = 0;
+        for client in clients.values() {
+            if skip_me && client.id == client_id {
+                continue;
+            }
+            if id_filter.is_some_and(|id| id != client.id) {
+                continue;
+            }
+            if addr_filter
+                .as_ref()
+                .is_some_and(|addr| *addr != client.addr)
+            {
+                continue;
+            }
+            client.kill_signal.notify_one();
+            killed += 1;
+        }
+
+        Ok(RespValue::integer(killed))
     }
 
-    /// Register a client
-    pub fn register_client(&self, id: usize, addr: String) -> Result<()> {
+    /// Register a client, returning the signal that asks its connection to close
+    pub fn register_client(&self, id: usize, addr: String) -> Result<Arc<Notify>> {
         let mut clients = self
             .clients
             .write()
             .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
 
+        let kill_signal = Arc::new(Notify::new());
         clients.insert(
             id,
             ClientInfo {
                 id,
                 name: None,
                 addr,
+                kill_signal: Arc::clone(&kill_signal),
             },
         );
-        Ok(())
+        Ok(kill_signal)
+    }
+
+    /// Share a client registry with other connections
+    pub fn set_client_registry(&mut self, clients: ClientRegistry) {
+        self.clients = clients;
+    }
+
+    /// Share the ACL user table with other connections
+    pub fn set_acl_users(&mut self, users: AclUsers) {
+        self.acl_users = users;
     }
 
     /// Unregister a client
@@ -1697,8 +3210,7 @@ impl ServerCommands {
     pub fn command(&self, args: &[Bytes]) -> Result<RespValue> {
         if args.is_empty() {
             // COMMAND with no args returns all commands
-            let commands = get_command_table();
-            let result: Vec<RespValue> = commands
+            let result: Vec<RespValue> = COMMAND_TABLE
                 .iter()
                 .map(|cmd| self.format_command_info(cmd))
                 .collect();
@@ -1712,10 +3224,10 @@ impl ServerCommands {
             "DOCS" => self.command_docs(&args[1..]),
             "GETKEYS" => self.command_getkeys(&args[1..]),
             "HELP" => self.command_help(),
-            _ => Err(AikvError::InvalidCommand(format!(
-                "Unknown COMMAND subcommand: {}",
-                subcommand
-            ))),
+            _ => Err(unknown_subcommand(
+                "COMMAND",
+                &String::from_utf8_lossy(&args[0]),
+            )),
         }
     }
 
@@ -1739,7 +3251,7 @@ impl ServerCommands {
 
     /// COMMAND COUNT - Get total number of commands
     fn command_count(&self) -> Result<RespValue> {
-        let count = get_command_table().len();
+        let count = COMMAND_TABLE.len();
         Ok(RespValue::integer(count as i64))
     }
 
@@ -1749,15 +3261,11 @@ impl ServerCommands {
             return Err(AikvError::WrongArgCount("COMMAND INFO".to_string()));
         }
 
-        let commands = get_command_table();
-        let command_map: HashMap<&str, &CommandInfo> =
-            commands.iter().map(|c| (c.name, c)).collect();
-
         let result: Vec<RespValue> = args
             .iter()
             .map(|arg| {
                 let name = String::from_utf8_lossy(arg).to_uppercase();
-                match command_map.get(name.as_str()) {
+                match lookup_command(&name) {
                     Some(cmd) => self.format_command_info(cmd),
                     None => RespValue::null_bulk_string(),
                 }
@@ -1767,68 +3275,23 @@ impl ServerCommands {
         Ok(RespValue::array(result))
     }
 
-    /// COMMAND DOCS - Get command documentation (simplified)
+    /// COMMAND DOCS [command ...] - Get the documentation of commands, or of
+    /// every command without arguments; unknown commands are left out
     fn command_docs(&self, args: &[Bytes]) -> Result<RespValue> {
-        if args.is_empty() {
-            // Return docs for all commands (simplified)
-            let commands = get_command_table();
-            let result: Vec<(RespValue, RespValue)> = commands
+        let docs = if args.is_empty() {
+            COMMAND_TABLE
                 .iter()
-                .map(|cmd| {
-                    (
-                        RespValue::bulk_string(cmd.name.to_lowercase()),
-                        RespValue::map(vec![
-                            (
-                                RespValue::bulk_string("summary"),
-                                RespValue::bulk_string(format!("{} command", cmd.name)),
-                            ),
-                            (
-                                RespValue::bulk_string("since"),
-                                RespValue::bulk_string("1.0.0"),
-                            ),
-                            (
-                                RespValue::bulk_string("group"),
-                                RespValue::bulk_string("generic"),
-                            ),
-                        ]),
-                    )
-                })
-                .collect();
-            return Ok(RespValue::map(result));
-        }
-
-        // Return docs for specific commands
-        let commands = get_command_table();
-        let command_map: HashMap<&str, &CommandInfo> =
-            commands.iter().map(|c| (c.name, c)).collect();
-
-        let result: Vec<(RespValue, RespValue)> = args
-            .iter()
-            .filter_map(|arg| {
-                let name = String::from_utf8_lossy(arg).to_uppercase();
-                command_map.get(name.as_str()).map(|cmd| {
-                    (
-                        RespValue::bulk_string(cmd.name.to_lowercase()),
-                        RespValue::map(vec![
-                            (
-                                RespValue::bulk_string("summary"),
-                                RespValue::bulk_string(format!("{} command", cmd.name)),
-                            ),
-                            (
-                                RespValue::bulk_string("since"),
-                                RespValue::bulk_string("1.0.0"),
-                            ),
-                            (
-                                RespValue::bulk_string("group"),
-                                RespValue::bulk_string("generic"),
-                            ),
-                        ]),
-                    )
+                .map(|cmd| docs::docs_reply(cmd.name))
+                .collect()
+        } else {
+            args.iter()
+                .filter_map(|arg| {
+                    let name = String::from_utf8_lossy(arg).to_uppercase();
+                    lookup_command(&name).map(|cmd| docs::docs_reply(cmd.name))
                 })
-            })
-            .collect();
-
-        Ok(RespValue::map(result))
+                .collect()
+        };
+        Ok(RespValue::map(docs))
     }
 
     /// COMMAND GETKEYS command [arg ...] - Extract keys from a command
@@ -1838,64 +3301,99 @@ impl ServerCommands {
         }
 
         let cmd_name = String::from_utf8_lossy(&args[0]).to_uppercase();
-        let commands = get_command_table();
-
-        let cmd = commands.iter().find(|c| c.name == cmd_name.as_str());
-
-        match cmd {
-            Some(cmd_info) => {
-                if cmd_info.first_key == 0 {
-                    return Ok(RespValue::array(vec![]));
-                }
-
-                let cmd_args = &args[1..];
-                let mut keys = Vec::new();
 
-                if cmd_info.last_key == -1 {
-                    // All remaining args from first_key are keys
-                    let start = (cmd_info.first_key - 1) as usize;
-                    for (i, arg) in cmd_args.iter().enumerate().skip(start) {
-                        if cmd_info.step == 1 || (i - start) % cmd_info.step as usize == 0 {
-                            keys.push(RespValue::bulk_string(arg.clone()));
-                        }
-                    }
-                } else if cmd_info.last_key >= cmd_info.first_key {
-                    let start = (cmd_info.first_key - 1) as usize;
-                    let end = (cmd_info.last_key - 1) as usize;
-                    for i in (start..=end.min(cmd_args.len().saturating_sub(1)))
-                        .step_by(cmd_info.step.max(1) as usize)
-                    {
-                        if i < cmd_args.len() {
-                            keys.push(RespValue::bulk_string(cmd_args[i].clone()));
-                        }
-                    }
-                }
+        let cmd_info = lookup_command(&cmd_name).ok_or_else(|| {
+            AikvError::InvalidCommand(format!("Invalid command specified: {}", cmd_name))
+        })?;
+        if !cmd_info.accepts_arg_count(args.len()) {
+            return Err(AikvError::InvalidArgument(
+                "ERR Invalid number of arguments specified for command".to_string(),
+            ));
+        }
 
-                Ok(RespValue::array(keys))
-            }
-            None => Err(AikvError::InvalidCommand(format!(
-                "Invalid command specified: {}",
-                cmd_name
-            ))),
+        let keys = cmd_info.keys(&args[1..]);
+        if keys.is_empty() {
+            let message = if cmd_info.first_key > 0 || cmd_info.has_flag("movablekeys") {
+                "ERR Invalid arguments specified for command"
+            } else {
+                "ERR The command has no key arguments"
+            };
+            return Err(AikvError::InvalidArgument(message.to_string()));
         }
+        Ok(RespValue::array(
+            keys.into_iter()
+                .map(|key| RespValue::bulk_string(key.clone()))
+                .collect(),
+        ))
     }
 
     /// COMMAND HELP - Show help for COMMAND subcommands
     fn command_help(&self) -> Result<RespValue> {
-        Ok(RespValue::array(vec![
-            RespValue::bulk_string("COMMAND - Return information about all commands"),
-            RespValue::bulk_string("COMMAND COUNT - Return the total number of commands"),
-            RespValue::bulk_string(
-                "COMMAND INFO <command-name> [<command-name> ...] - Return details about command(s)",
-            ),
-            RespValue::bulk_string(
-                "COMMAND DOCS [<command-name> ...] - Return documentation for command(s)",
-            ),
-            RespValue::bulk_string(
-                "COMMAND GETKEYS <command> [<arg> ...] - Extract keys from a full command",
-            ),
-            RespValue::bulk_string("COMMAND HELP - Show this help"),
-        ]))
+        Ok(help_reply(
+            "COMMAND",
+            &[
+                "(no subcommand)",
+                "    Return details about all commands.",
+                "COUNT",
+                "    Return the total number of commands in this server.",
+                "DOCS [<command-name> ...]",
+                "    Return documentation details about multiple commands.",
+                "    If no command names are given, documentation details for all",
+                "    commands are returned.",
+                "GETKEYS <full-command>",
+                "    Return the keys from a full command.",
+                "INFO [<command-name> ...]",
+                "    Return details about multiple commands.",
+                "    If no command names are given, documentation details for all",
+                "    commands are returned.",
+            ],
+        ))
+    }
+
+    /// CONFIG HELP - Show help for CONFIG subcommands
+    pub fn config_help(&self) -> Result<RespValue> {
+        Ok(help_reply(
+            "CONFIG",
+            &[
+                "GET <pattern>",
+                "    Return parameters matching the glob-like <pattern> and their values.",
+                "SET <directive> <value>",
+                "    Set the configuration <directive> to <value>.",
+                "REWRITE",
+                "    Rewrite the configuration file.",
+            ],
+        ))
+    }
+
+    /// CLIENT HELP - Show help for CLIENT subcommands
+    pub fn client_help(&self) -> Result<RespValue> {
+        Ok(help_reply(
+            "CLIENT",
+            &[
+                "GETNAME",
+                "    Return the name of the current connection.",
+                "ID",
+                "    Return the ID of the current connection.",
+                "KILL <ip:port>",
+                "    Kill connection made from <ip:port>.",
+                "KILL <option> <value> [<option> <value> [...]]",
+                "    Kill connections. Options are:",
+                "    * ADDR (<ip:port>|<unixsocket>:0)",
+                "      Kill connections made from the specified address",
+                "    * ID <client-id>",
+                "      Kill connections by client id.",
+                "LIST",
+                "    Return information about client connections.",
+                "NO-EVICT (ON|OFF)",
+                "    Protect current client connection from eviction.",
+                "NO-TOUCH (ON|OFF)",
+                "    Will not touch LRU/LFU stats when this mode is on.",
+                "SETNAME <name>",
+                "    Assign the name <name> to the current connection.",
+                "UNPAUSE",
+                "    Stop the current client pause, resuming traffic.",
+            ],
+        ))
     }
 
     /// CONFIG REWRITE - Rewrite the configuration file
@@ -1911,67 +3409,504 @@ impl ServerCommands {
         if !args.is_empty() {
             return Err(AikvError::WrongArgCount("SAVE".to_string()));
         }
+        if self.rdb.bgsave_in_progress() {
+            return Err(AikvError::Persistence(
+                "Background save already in progress".to_string(),
+            ));
+        }
 
-        // Export all databases from storage
-        let databases = self.storage.export_all_databases()?;
-
-        // Create a temporary file for the RDB dump
-        let temp_file = tempfile::NamedTempFile::new()
-            .map_err(|e| AikvError::Persistence(format!("Failed to create temp file: {}", e)))?;
-        let temp_path = temp_file.path();
-
-        // Save to RDB format
-        crate::persistence::save_stored_value_rdb(temp_path, &databases)?;
+        self.rdb.save(&self.storage)?;
 
-        // Update last save time
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        self.last_save_time.store(now, Ordering::SeqCst);
-
-        // For now, we save to a temporary file and don't persist it permanently
-        // In a real implementation, this would save to a configured RDB file path
         Ok(RespValue::ok())
     }
 
     /// BGSAVE - Asynchronously save the dataset to disk
+    ///
+    /// The databases are cloned under their read locks, then serialized and
+    /// written on a background thread so the caller doesn't wait for the disk.
     pub fn bgsave(&self, args: &[Bytes]) -> Result<RespValue> {
-        if !args.is_empty() {
-            return Err(AikvError::WrongArgCount("BGSAVE".to_string()));
+        if args.len() > 1
+            || args
+                .first()
+                .is_some_and(|arg| !arg.eq_ignore_ascii_case(b"SCHEDULE"))
+        {
+            return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
+        }
+        if self.rdb.bgsave_in_progress.swap(true, Ordering::SeqCst) {
+            return Err(AikvError::Persistence(
+                "Background save already in progress".to_string(),
+            ));
         }
 
-        // For now, perform synchronous save (background save would require threading)
-        // In a real implementation, this would spawn a background thread
-        self.save(args)?;
+        let databases = match self.storage.export_all_databases() {
+            Ok(databases) => databases,
+            Err(e) => {
+                self.rdb.bgsave_in_progress.store(false, Ordering::SeqCst);
+                return Err(e);
+            }
+        };
+
+        let rdb = Arc::clone(&self.rdb);
+        std::thread::spawn(move || {
+            match rdb.write(&databases) {
+                Ok(()) => info!("Background saving terminated with success"),
+                Err(e) => error!("Background saving failed: {}", e),
+            }
+            rdb.bgsave_in_progress.store(false, Ordering::SeqCst);
+        });
 
         Ok(RespValue::simple_string("Background saving started"))
     }
 
     /// LASTSAVE - Get the Unix timestamp of the last successful save
     pub fn lastsave(&self, _args: &[Bytes]) -> Result<RespValue> {
-        let last_save = self.last_save_time.load(Ordering::SeqCst);
-        Ok(RespValue::integer(last_save as i64))
+        Ok(RespValue::integer(self.rdb.last_save_time() as i64))
+    }
+
+    /// FSYNC - Flush the storage engine and the AOF to disk, replying once
+    /// every write acknowledged so far is durable
+    pub fn fsync(&self, args: &[Bytes]) -> Result<RespValue> {
+        if !args.is_empty() {
+            return Err(AikvError::WrongArgCount("FSYNC".to_string()));
+        }
+
+        self.storage.sync()?;
+        if let Some(aof) = &self.aof {
+            aof.fsync()?;
+        }
+        Ok(RespValue::ok())
+    }
+
+    /// DEBUG subcommand \[arg ...\] - Introspection and testing helpers
+    ///
+    /// Supports OBJECT, SLEEP, SET-ACTIVE-EXPIRE, JMAP (a no-op) and
+    /// DBSIZE-EXACT, which counts the keys with a full scan rather than
+    /// trusting a maintained count.
+    pub fn debug(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        let subcommand = String::from_utf8_lossy(&args[0]).to_uppercase();
+        match (subcommand.as_str(), &args[1..]) {
+            ("OBJECT", [key]) => self.debug_object(&String::from_utf8_lossy(key), current_db),
+            ("SLEEP", [seconds]) => {
+                let seconds = String::from_utf8_lossy(seconds)
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|s| s.is_finite() && *s >= 0.0)
+                    .ok_or_else(|| {
+                        AikvError::InvalidArgument("ERR value is not a valid float".to_string())
+                    })?;
+                // Like Redis, this blocks the caller for the whole duration
+                std::thread::sleep(Duration::from_secs_f64(seconds));
+                Ok(RespValue::ok())
+            }
+            ("SET-ACTIVE-EXPIRE", [flag]) => {
+                let enabled = match flag.as_ref() {
+                    b"0" => false,
+                    b"1" => true,
+                    _ => {
+                        return Err(AikvError::InvalidArgument(
+                            "ERR value is out of range, must be 0 or 1".to_string(),
+                        ))
+                    }
+                };
+                self.active_expire.store(enabled, Ordering::SeqCst);
+                Ok(RespValue::ok())
+            }
+            ("HELP", []) => Ok(help_reply(
+                "DEBUG",
+                &[
+                    "DBSIZE-EXACT",
+                    "    Count the keys of the current database with a full scan.",
+                    "JMAP",
+                    "    Does nothing; accepted for compatibility.",
+                    "OBJECT <key>",
+                    "    Show low level info about the <key> and associated value.",
+                    "SET-ACTIVE-EXPIRE <0|1>",
+                    "    Setting it to 0 disables expiring keys in background when they are not",
+                    "    accessed (otherwise the Redis behavior). Setting it to 1 reenables back the",
+                    "    default.",
+                    "SLEEP <seconds>",
+                    "    Stop the server for <seconds>. Decimals allowed.",
+                ],
+            )),
+            ("JMAP", []) => Ok(RespValue::ok()),
+            ("DBSIZE-EXACT", []) => Ok(RespValue::integer(
+                self.storage.exact_dbsize_in_db(current_db)? as i64,
+            )),
+            ("OBJECT" | "SLEEP" | "SET-ACTIVE-EXPIRE" | "JMAP" | "DBSIZE-EXACT", _) => Err(
+                AikvError::WrongArgCount(format!("DEBUG {}", subcommand)),
+            ),
+            _ => Err(unknown_subcommand(
+                "DEBUG",
+                &String::from_utf8_lossy(&args[0]),
+            )),
+        }
     }
 
-    /// SHUTDOWN - Shut down the server
-    /// Note: This sets a shutdown flag but doesn't actually terminate the process
-    /// The actual shutdown should be handled by the server loop
+    /// OBJECT subcommand \[key\] - Inspect the internals of the value stored at key
+    ///
+    /// Supports ENCODING, FREQ, IDLETIME, REFCOUNT and HELP. Looking a key up
+    /// this way doesn't count as an access. FREQ is only tracked meaningfully
+    /// under an LFU policy and IDLETIME under any other, so each errors under
+    /// the policy it doesn't apply to, as in Redis.
+    pub fn object(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        let subcommand = args
+            .first()
+            .map(|arg| String::from_utf8_lossy(arg).to_uppercase())
+            .ok_or_else(|| AikvError::WrongArgCount("OBJECT".to_string()))?;
+
+        let key = match (subcommand.as_str(), &args[1..]) {
+            ("HELP", []) => {
+                return Ok(help_reply(
+                    "OBJECT",
+                    &[
+                        "ENCODING <key>",
+                        "    Return the kind of internal representation used in order to store the value",
+                        "    associated with a <key>.",
+                        "FREQ <key>",
+                        "    Return the access frequency index of the <key>. The returned integer is",
+                        "    proportional to the logarithm of the recent access frequency of the key.",
+                        "IDLETIME <key>",
+                        "    Return the idle time of the <key>, that is the approximated number of",
+                        "    seconds elapsed since the last access to the key.",
+                        "REFCOUNT <key>",
+                        "    Return the number of references of the value associated with the specified",
+                        "    <key>.",
+                    ],
+                ));
+            }
+            ("ENCODING" | "FREQ" | "IDLETIME" | "REFCOUNT", [key]) => String::from_utf8_lossy(key),
+            _ => {
+                return Err(AikvError::InvalidArgument(format!(
+                "ERR unknown subcommand or wrong number of arguments for '{}'. Try OBJECT HELP.",
+                subcommand
+            )))
+            }
+        };
+
+        let Some(value) = self.storage.peek_value(current_db, &key)? else {
+            return Ok(RespValue::null_bulk_string());
+        };
+        let lfu = self.storage.memory_limit()?.policy.is_lfu();
+
+        match subcommand.as_str() {
+            "ENCODING" => Ok(RespValue::bulk_string(value_encoding(value.value()))),
+            "REFCOUNT" => Ok(RespValue::integer(1)),
+            "FREQ" if !lfu => Err(AikvError::InvalidArgument(
+                "ERR An LFU maxmemory policy is not selected, access frequency not tracked. Please note that when switching between policies at runtime LRU and LFU data will take some time to adjust.".to_string(),
+            )),
+            "FREQ" => Ok(RespValue::integer(i64::from(value.lfu_frequency()))),
+            _ if lfu => Err(AikvError::InvalidArgument(
+                "ERR An LRU maxmemory policy is not selected, access time not tracked. Please note that when switching between policies at runtime LRU and LFU data will take some time to adjust.".to_string(),
+            )),
+            _ => Ok(RespValue::integer((value.idle_time_ms() / 1000) as i64)),
+        }
+    }
+
+    /// MEMORY subcommand \[arg ...\] - Memory usage reports
+    ///
+    /// USAGE estimates a key the same way maxmemory accounts for it: key and
+    /// value bytes plus a fixed overhead per key and per element. The estimate
+    /// is exact for that model, so SAMPLES is accepted but not needed.
+    pub fn memory(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        let subcommand = args
+            .first()
+            .map(|arg| String::from_utf8_lossy(arg).to_uppercase())
+            .ok_or_else(|| AikvError::WrongArgCount("MEMORY".to_string()))?;
+
+        match (subcommand.as_str(), &args[1..]) {
+            ("HELP", []) => Ok(help_reply(
+                "MEMORY",
+                &[
+                    "DOCTOR",
+                    "    Return memory problems reports.",
+                    "MALLOC-STATS",
+                    "    Return internal statistics report from the memory allocator.",
+                    "PURGE",
+                    "    Attempt to purge dirty pages for reclamation by the allocator.",
+                    "STATS",
+                    "    Return information about the memory usage of the server.",
+                    "USAGE <key> [SAMPLES <count>]",
+                    "    Return memory in bytes used by <key> and its value. Nested values are",
+                    "    sampled up to <count> times (default: 5, 0 means sample all).",
+                ],
+            )),
+            ("USAGE", [key, options @ ..]) => {
+                match options {
+                    [] => {}
+                    [option, count] if option.eq_ignore_ascii_case(b"SAMPLES") => {
+                        String::from_utf8_lossy(count).parse::<u64>().map_err(|_| {
+                            AikvError::InvalidArgument(
+                                "ERR value is not an integer or out of range".to_string(),
+                            )
+                        })?;
+                    }
+                    _ => return Err(AikvError::InvalidArgument("ERR syntax error".to_string())),
+                }
+                let key = String::from_utf8_lossy(key);
+                match self.storage.peek_value(current_db, &key)? {
+                    Some(value) => Ok(RespValue::integer(entry_size(&key, &value) as i64)),
+                    None => Ok(RespValue::null_bulk_string()),
+                }
+            }
+            ("STATS", []) => {
+                let used_memory = self.storage.used_memory()?;
+                let mut keys = 0;
+                for db in 0..self.storage.db_count() {
+                    keys += self.storage.dbsize_in_db(db)?;
+                }
+                let bytes_per_key = if keys > 0 { used_memory / keys } else { 0 };
+                let stat = |name: &str, value: usize| {
+                    (
+                        RespValue::bulk_string(name.to_string()),
+                        RespValue::integer(value as i64),
+                    )
+                };
+                Ok(RespValue::map(vec![
+                    stat("total.allocated", used_memory),
+                    stat("dataset.bytes", used_memory),
+                    stat("keys.count", keys),
+                    stat("keys.bytes-per-key", bytes_per_key),
+                ]))
+            }
+            ("DOCTOR", []) => {
+                let report = if self.storage.used_memory()? < 5 * 1024 * 1024 {
+                    "Hi Sam, this instance is empty or is using very little memory, my issues detector can't be used in these conditions. Please, leave for your mission on Earth and fill it with some data. The new Sam and I will be back to our programming as soon as I finished rebooting."
+                } else {
+                    "Hi Sam, I can't find any memory issue in your instance. I can only account for what occurs on this base."
+                };
+                Ok(RespValue::bulk_string(report))
+            }
+            ("MALLOC-STATS", []) => Ok(RespValue::bulk_string(
+                "Stats not supported for the current allocator",
+            )),
+            ("PURGE", []) => Ok(RespValue::ok()),
+            _ => Err(AikvError::InvalidArgument(format!(
+                "ERR unknown subcommand or wrong number of arguments for '{}'. Try MEMORY HELP.",
+                subcommand
+            ))),
+        }
+    }
+
+    /// DEBUG OBJECT key - Low-level details of the value stored at key
+    fn debug_object(&self, key: &str, current_db: usize) -> Result<RespValue> {
+        let value = self
+            .storage
+            .peek_value(current_db, key)?
+            .ok_or_else(|| AikvError::InvalidArgument("ERR no such key".to_string()))?;
+        let serialized_length = super::key::KeyCommands::encode_dump(&value)?.len();
+        let idle_seconds = value.idle_time_ms() / 1000;
+        let lru = (unix_time_secs().saturating_sub(idle_seconds)) & 0xFF_FFFF;
+
+        let mut reply = format!(
+            "Value at:0x0 refcount:1 encoding:{} serializedlength:{} lru:{} lru_seconds_idle:{}",
+            value_encoding(value.value()),
+            serialized_length,
+            lru,
+            idle_seconds
+        );
+        if let ValueType::List(items) = value.value() {
+            let size: usize = items.iter().map(|item| item.len()).sum();
+            reply.push_str(&format!(
+                " ql_nodes:1 ql_avg_node:{:.2} ql_listpack_max:-2 ql_compressed:0 ql_uncompressed_size:{}",
+                items.len() as f64,
+                size
+            ));
+        }
+        Ok(RespValue::simple_string(reply))
+    }
+
+    /// Share the server-wide runtime configuration with this handler
+    ///
+    /// Call this before the other `set_*` methods, which record the state
+    /// they share in the configuration.
+    pub fn set_runtime_config(&mut self, config: SharedConfig) {
+        if let Ok(mut config) = config.write() {
+            Self::sync_storage_config(&mut config, &self.storage);
+        }
+        self.config = config;
+    }
+
+    /// Record the database count and memory limit of `storage` in `config`
+    fn sync_storage_config(config: &mut RuntimeConfig, storage: &StorageEngine) {
+        config.set("databases", storage.db_count().to_string());
+        if let Ok(limit) = storage.memory_limit() {
+            config.set("maxmemory", limit.maxmemory.to_string());
+            config.set("maxmemory-policy", limit.policy.as_str());
+        }
+    }
+
+    /// Share the server-wide slow log with this handler
+    pub fn set_slow_query_log(&mut self, slow_query_log: Arc<SlowQueryLog>) {
+        if let Ok(mut config) = self.config.write() {
+            let threshold = match slow_query_log.threshold_us() {
+                u64::MAX => "-1".to_string(),
+                threshold => threshold.to_string(),
+            };
+            config.set("slowlog-log-slower-than", threshold);
+            config.set("slowlog-max-len", slow_query_log.max_len().to_string());
+        }
+        self.slow_query_log = slow_query_log;
+    }
+
+    /// Share the server's append-only log with this handler
+    pub fn set_aof_writer(&mut self, aof: AofWriter) {
+        if let Ok(mut config) = self.config.write() {
+            config.set("appendonly", "yes");
+            config.set("appendfsync", aof.sync_policy().as_str());
+        }
+        self.aof = Some(aof);
+    }
+
+    /// Stop the server through `shutdown` on SHUTDOWN
+    pub fn set_shutdown_signal(&mut self, shutdown: ShutdownSignal) {
+        self.shutdown = shutdown;
+    }
+
+    /// Tell SHUTDOWN whether the server saves on shutdown by default
+    pub fn set_save_on_shutdown(&mut self, enabled: bool) {
+        self.save_on_shutdown = enabled;
+    }
+
+    /// Share the server-wide switch of the expiration sweeper with this handler
+    pub fn set_active_expire(&mut self, active_expire: Arc<AtomicBool>) {
+        self.active_expire = active_expire;
+    }
+
+    /// Share the server-wide flag raised while the dataset loads with this handler
+    pub fn set_loading(&mut self, loading: Arc<AtomicBool>) {
+        self.loading = loading;
+    }
+
+    /// Whether the dataset is still being loaded, so only commands flagged
+    /// `loading` may run
+    pub fn is_loading(&self) -> bool {
+        self.loading.load(Ordering::SeqCst)
+    }
+
+    /// Share the server-wide Pub/Sub hub with this handler
+    pub fn set_pubsub(&mut self, pubsub: Arc<PubSub>) {
+        if let Ok(mut config) = self.config.write() {
+            config.set(
+                "notify-keyspace-events",
+                pubsub.keyspace_events().to_string(),
+            );
+        }
+        self.pubsub = pubsub;
+    }
+
+    /// Share the server-wide metrics with this handler
+    pub fn set_metrics(&mut self, metrics: Arc<Metrics>) {
+        self.metrics = metrics;
+    }
+
+    /// Share the server-wide RDB snapshot state with this handler
+    pub fn set_rdb_state(&mut self, rdb: Arc<RdbState>) {
+        if let Ok(mut config) = self.config.write() {
+            let checksum = if rdb.checksum() { "yes" } else { "no" };
+            config.set("rdbchecksum", checksum);
+            if let Some(name) = rdb.path().file_name() {
+                config.set("dbfilename", name.to_string_lossy());
+            }
+        }
+        self.rdb = rdb;
+    }
+
+    /// WAIT numreplicas timeout - Wait for replicas to acknowledge prior writes
+    ///
+    /// A standalone server has no replicas, so this returns 0 immediately.
+    /// In cluster mode the connection routes WAIT to `ClusterCommands::wait`.
+    pub fn wait(&self, args: &[Bytes]) -> Result<RespValue> {
+        Self::parse_wait_args(args)?;
+        Ok(RespValue::integer(0))
+    }
+
+    /// WAITAOF numlocal numreplicas timeout - Wait for prior writes to reach
+    /// the AOF on disk
+    ///
+    /// Returns how many of the local AOF and the replicas have fsynced every
+    /// write acknowledged so far. The local AOF is fsynced on the spot,
+    /// whatever the appendfsync policy, so there is nothing to wait for; a
+    /// standalone server has no replicas to count.
+    pub fn waitaof(&self, args: &[Bytes]) -> Result<RespValue> {
+        if args.len() != 3 {
+            return Err(AikvError::WrongArgCount("WAITAOF".to_string()));
+        }
+
+        let numlocal = String::from_utf8_lossy(&args[0])
+            .parse::<i64>()
+            .map_err(|_| {
+                AikvError::InvalidArgument(
+                    "ERR value is not an integer or out of range".to_string(),
+                )
+            })?;
+        Self::parse_wait_args(&args[1..])?;
+
+        let local = match &self.aof {
+            Some(aof) => {
+                aof.fsync()?;
+                1
+            }
+            None if numlocal > 0 => {
+                return Err(AikvError::InvalidArgument(
+                    "ERR WAITAOF cannot be used when numlocal is set but appendonly is disabled."
+                        .to_string(),
+                ));
+            }
+            None => 0,
+        };
+        Ok(RespValue::array(vec![
+            RespValue::integer(local),
+            RespValue::integer(0),
+        ]))
+    }
+
+    /// Parse WAIT arguments into (numreplicas, timeout in milliseconds)
+    pub fn parse_wait_args(args: &[Bytes]) -> Result<(usize, u64)> {
+        if args.len() != 2 {
+            return Err(AikvError::WrongArgCount("WAIT".to_string()));
+        }
+
+        let numreplicas = String::from_utf8_lossy(&args[0])
+            .parse::<i64>()
+            .map_err(|_| {
+                AikvError::InvalidArgument(
+                    "ERR value is not an integer or out of range".to_string(),
+                )
+            })?;
+        let timeout = String::from_utf8_lossy(&args[1])
+            .parse::<i64>()
+            .map_err(|_| {
+                AikvError::InvalidArgument(
+                    "ERR timeout is not an integer or out of range".to_string(),
+                )
+            })?;
+        if timeout < 0 {
+            return Err(AikvError::InvalidArgument(
+                "ERR timeout is negative".to_string(),
+            ));
+        }
+
+        Ok((numreplicas.max(0) as usize, timeout as u64))
+    }
+
+    /// SHUTDOWN \[NOSAVE|SAVE\] \[NOW\] \[FORCE\] \[ABORT\] - Shut down the server
+    ///
+    /// Signals the server to stop gracefully; the final snapshot follows the
+    /// configured policy unless SAVE or NOSAVE overrides it. NOW and FORCE
+    /// are accepted for compatibility: there are no replicas to wait for.
     pub fn shutdown(&self, args: &[Bytes]) -> Result<RespValue> {
-        // Parse optional arguments: NOSAVE, SAVE, NOW, FORCE, ABORT
-        let mut _nosave = false;
-        let mut _save = false;
-        let mut _now = false;
-        let mut _force = false;
+        let mut save = ShutdownSave::Configured;
         let mut abort = false;
 
         for arg in args {
             let arg_str = String::from_utf8_lossy(arg).to_uppercase();
             match arg_str.as_str() {
-                "NOSAVE" => _nosave = true,
-                "SAVE" => _save = true,
-                "NOW" => _now = true,
-                "FORCE" => _force = true,
+                "NOSAVE" | "SAVE" if save != ShutdownSave::Configured => {
+                    return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
+                }
+                "NOSAVE" => save = ShutdownSave::NoSave,
+                "SAVE" => save = ShutdownSave::Save,
+                "NOW" | "FORCE" => {}
                 "ABORT" => abort = true,
                 _ => {
                     return Err(AikvError::InvalidArgument(format!(
@@ -1983,23 +3918,26 @@ impl ServerCommands {
         }
 
         if abort {
-            // Abort a pending shutdown
-            self.shutdown_requested.store(false, Ordering::SeqCst);
+            // Shutdowns start right away, so there is never one to abort
             return Ok(RespValue::ok());
         }
 
-        // Set shutdown flag
-        self.shutdown_requested.store(true, Ordering::SeqCst);
+        if save == ShutdownSave::Save && !self.save_on_shutdown && self.aof.is_none() {
+            return Err(AikvError::InvalidArgument(
+                "ERR SHUTDOWN SAVE requested, but persistence is disabled".to_string(),
+            ));
+        }
+
+        info!("User requested shutdown...");
+        self.shutdown.request(save);
 
-        // In a real implementation, the server would check this flag and exit gracefully
-        // For now, we just return an error indicating shutdown was requested
-        // The connection will be closed, and the client should reconnect
+        // The connection closes once this reply is written
         Err(AikvError::Storage("Server is shutting down".to_string()))
     }
 
     /// Check if shutdown has been requested
     pub fn is_shutdown_requested(&self) -> bool {
-        self.shutdown_requested.load(Ordering::SeqCst)
+        self.shutdown.is_triggered()
     }
 }
 
@@ -2,17 +2,30 @@ use crate::error::{AikvError, Result};
 use crate::protocol::RespValue;
 use crate::storage::{StorageEngine, StoredValue};
 use bytes::Bytes;
+use rand::seq::{IteratorRandom, SliceRandom};
+use rand::Rng;
 use std::collections::HashSet;
 
 /// Set command handler
 pub struct SetCommands {
     storage: StorageEngine,
+    /// Largest result a set operation may build, unlimited when `None`
+    result_limit: Option<usize>,
+}
+
+/// The set algebra shared by SUNION, SINTER, SDIFF and their STORE variants
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SetOp {
+    Union,
+    Inter,
+    Diff,
 }
 
 impl SetCommands {
     pub fn new(storage: StorageEngine) -> Self {
         Self {
             storage,
+            result_limit: None,
         }
     }
 
@@ -115,16 +128,16 @@ impl SetCommands {
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
 
-        let members = if let Some(stored) = self.storage.get_value(db_index, &key)? {
+        let members: Vec<Bytes> = if let Some(stored) = self.storage.get_value(db_index, &key)? {
             let set = stored.as_set()?;
             set.iter().map(|v| Bytes::from(v.clone())).collect()
         } else {
             Vec::new()
         };
 
-        Ok(RespValue::Array(Some(
-            members.into_iter().map(RespValue::bulk_string).collect(),
-        )))
+        Ok(RespValue::lazy_set(members, |member| {
+            RespValue::bulk_string(member.clone())
+        }))
     }
 
     /// SCARD key
@@ -148,87 +161,134 @@ impl SetCommands {
 
     /// SPOP key \[count\]
     /// Remove and return one or multiple random members from the set value stored at key
+    ///
+    /// Without a count, replies a single member (or null); with one, an
+    /// array of up to `count` members. The members are taken out in a single
+    /// storage update, so concurrent SPOPs never return the same member.
     pub fn spop(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
-        if args.is_empty() {
+        if args.is_empty() || args.len() > 2 {
             return Err(AikvError::WrongArgCount("SPOP".to_string()));
         }
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
-        let count = if args.len() > 1 {
-            String::from_utf8_lossy(&args[1])
-                .parse::<usize>()
-                .map_err(|_| AikvError::InvalidArgument("invalid count".to_string()))?
-        } else {
-            1
-        };
+        let count =
+            match args.get(1) {
+                Some(count) => Some(String::from_utf8_lossy(count).parse::<usize>().map_err(
+                    |_| {
+                        AikvError::InvalidArgument(
+                            "ERR value is out of range, must be positive".to_string(),
+                        )
+                    },
+                )?),
+                None => None,
+            };
+        let wanted = count.unwrap_or(1);
 
-        // Migrated: Logic moved from storage layer to command layer
         let mut members = Vec::new();
-
-        if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            let mut set = stored.as_set()?.clone();
-
-            let to_remove: Vec<Vec<u8>> = set.iter().take(count).cloned().collect();
-            for member in to_remove {
-                set.remove(&member);
-                members.push(Bytes::from(member));
-            }
-
-            // Update or delete the set
-            if set.is_empty() {
-                self.storage.delete_from_db(db_index, &key)?;
-            } else {
-                self.storage
-                    .set_value(db_index, key, StoredValue::new_set(set))?;
+        let mut takes_all = false;
+        if wanted > 0 {
+            self.storage.update_value(db_index, &key, |stored| {
+                let set = stored.as_set_mut()?;
+                if wanted >= set.len() {
+                    takes_all = true;
+                    return Ok(());
+                }
+                let mut rng = rand::thread_rng();
+                let picked: Vec<Vec<u8>> = set.iter().cloned().choose_multiple(&mut rng, wanted);
+                for member in picked {
+                    set.remove(&member);
+                    members.push(Bytes::from(member));
+                }
+                members.shuffle(&mut rng);
+                Ok(())
+            })?;
+        }
+        if takes_all {
+            // Taking every member removes the key in one step, rather than
+            // leaving an empty set behind to delete separately
+            if let Some(stored) = self.storage.delete_and_get(db_index, &key)? {
+                members = stored.as_set()?.iter().cloned().map(Bytes::from).collect();
+                members.shuffle(&mut rand::thread_rng());
             }
         }
 
-        if members.is_empty() {
-            Ok(RespValue::Null)
-        } else if count == 1 {
-            Ok(RespValue::bulk_string(members[0].clone()))
-        } else {
-            Ok(RespValue::Array(Some(
+        match count {
+            None => Ok(match members.pop() {
+                Some(member) => RespValue::bulk_string(member),
+                None => RespValue::Null,
+            }),
+            Some(_) => Ok(RespValue::Array(Some(
                 members.into_iter().map(RespValue::bulk_string).collect(),
-            )))
+            ))),
         }
     }
 
     /// SRANDMEMBER key \[count\]
     /// Return one or multiple random members from the set value stored at key
+    ///
+    /// Without a count, replies a single member (or null). A positive count
+    /// replies up to that many distinct members; a negative one replies
+    /// exactly `-count` members, which may repeat.
     pub fn srandmember(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
-        if args.is_empty() {
+        if args.is_empty() || args.len() > 2 {
             return Err(AikvError::WrongArgCount("SRANDMEMBER".to_string()));
         }
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
-        let count = if args.len() > 1 {
-            String::from_utf8_lossy(&args[1])
-                .parse::<i64>()
-                .map_err(|_| AikvError::InvalidArgument("invalid count".to_string()))?
-        } else {
-            1
+        let count = match args.get(1) {
+            Some(count) => Some(String::from_utf8_lossy(count).parse::<i64>().map_err(|_| {
+                AikvError::InvalidArgument(
+                    "ERR value is not an integer or out of range".to_string(),
+                )
+            })?),
+            None => None,
         };
 
-        let members = if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            let set = stored.as_set()?;
-            set.iter()
-                .take(count.unsigned_abs() as usize)
-                .map(|v| Bytes::from(v.clone()))
-                .collect()
-        } else {
-            Vec::new()
+        let stored = self.storage.get_value(db_index, &key)?;
+        let empty = HashSet::new();
+        let set = match &stored {
+            Some(stored) => stored.as_set()?,
+            None => &empty,
+        };
+        let mut rng = rand::thread_rng();
+
+        let members: Vec<&Vec<u8>> = match count {
+            None => {
+                return Ok(match set.iter().choose(&mut rng) {
+                    Some(member) => RespValue::bulk_string(Bytes::from(member.clone())),
+                    None => RespValue::Null,
+                });
+            }
+            Some(count) if count >= 0 => {
+                let mut members = set.iter().choose_multiple(&mut rng, count as usize);
+                // choose_multiple keeps iteration order
+                members.shuffle(&mut rng);
+                members
+            }
+            Some(count) => {
+                let all: Vec<&Vec<u8>> = set.iter().collect();
+                if all.is_empty() {
+                    Vec::new()
+                } else {
+                    (0..count.unsigned_abs())
+                        .map(|_| all[rng.gen_range(0..all.len())])
+                        .collect()
+                }
+            }
         };
 
-        if members.is_empty() && args.len() == 1 {
-            Ok(RespValue::Null)
-        } else if count == 1 && args.len() == 1 {
-            Ok(RespValue::bulk_string(members[0].clone()))
-        } else {
-            Ok(RespValue::Array(Some(
-                members.into_iter().map(RespValue::bulk_string).collect(),
-            )))
-        }
+        Ok(RespValue::Array(Some(
+            members
+                .into_iter()
+                .map(|member| RespValue::bulk_string(Bytes::from(member.clone())))
+                .collect(),
+        )))
+    }
+
+    /// Cap the number of members a SUNION/SINTER/SDIFF result may hold, so a
+    /// set operation over huge sets fails instead of materializing it
+    pub fn set_result_limit(&mut self, limit: Option<usize>) {
+        self.result_limit = limit;
     }
 
     /// SUNION key [key ...]
@@ -237,26 +297,8 @@ impl SetCommands {
         if args.is_empty() {
             return Err(AikvError::WrongArgCount("SUNION".to_string()));
         }
-
-        let keys: Vec<String> = args
-            .iter()
-            .map(|b| String::from_utf8_lossy(b).to_string())
-            .collect();
-
-        let mut result = HashSet::new();
-        for key in keys {
-            if let Some(stored) = self.storage.get_value(db_index, &key)? {
-                let set = stored.as_set()?;
-                result.extend(set.iter().cloned());
-            }
-        }
-
-        Ok(RespValue::Array(Some(
-            result
-                .into_iter()
-                .map(|v| RespValue::bulk_string(Bytes::from(v)))
-                .collect(),
-        )))
+        let result = self.combine(SetOp::Union, args, db_index)?;
+        Ok(Self::set_reply(result))
     }
 
     /// SINTER key [key ...]
@@ -265,35 +307,72 @@ impl SetCommands {
         if args.is_empty() {
             return Err(AikvError::WrongArgCount("SINTER".to_string()));
         }
+        let result = self.combine(SetOp::Inter, args, db_index)?;
+        Ok(Self::set_reply(result))
+    }
 
-        let keys: Vec<String> = args
-            .iter()
-            .map(|b| String::from_utf8_lossy(b).to_string())
-            .collect();
+    /// SINTERCARD numkeys key [key ...] \[LIMIT limit\]
+    /// Returns the number of members in the intersection of the given sets
+    ///
+    /// Counting stops once it reaches a non-zero limit.
+    pub fn sintercard(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        if args.len() < 2 {
+            return Err(AikvError::WrongArgCount("SINTERCARD".to_string()));
+        }
 
-        let mut result: Option<HashSet<Vec<u8>>> = None;
+        let numkeys = String::from_utf8_lossy(&args[0])
+            .parse::<i64>()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or_else(|| {
+                AikvError::InvalidArgument("ERR numkeys should be greater than 0".to_string())
+            })? as usize;
+        if numkeys > args.len() - 1 {
+            return Err(AikvError::InvalidArgument(
+                "ERR Number of keys can't be greater than number of args".to_string(),
+            ));
+        }
+        let keys = &args[1..=numkeys];
+
+        let limit = match &args[numkeys + 1..] {
+            [] => 0,
+            [option, limit] if option.eq_ignore_ascii_case(b"LIMIT") => {
+                String::from_utf8_lossy(limit)
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|&n| n >= 0)
+                    .ok_or_else(|| {
+                        AikvError::InvalidArgument("ERR LIMIT can't be negative".to_string())
+                    })? as usize
+            }
+            _ => return Err(AikvError::InvalidArgument("ERR syntax error".to_string())),
+        };
 
+        let mut sets = Vec::with_capacity(keys.len());
         for key in keys {
-            if let Some(stored) = self.storage.get_value(db_index, &key)? {
-                let set = stored.as_set()?;
-                if let Some(res) = &mut result {
-                    *res = res.intersection(set).cloned().collect();
-                } else {
-                    result = Some(set.clone());
-                }
-            } else {
-                // If any key doesn't exist, intersection is empty
-                return Ok(RespValue::Array(Some(Vec::new())));
+            let key = String::from_utf8_lossy(key);
+            match self.storage.get_value(db_index, &key)? {
+                Some(stored) => sets.push(stored.as_set()?.clone()),
+                // Nothing intersects an empty set, so don't load the rest
+                None => return Ok(RespValue::Integer(0)),
             }
         }
 
-        Ok(RespValue::Array(Some(
-            result
-                .unwrap_or_default()
-                .into_iter()
-                .map(|v| RespValue::bulk_string(Bytes::from(v)))
-                .collect(),
-        )))
+        // Count the members of the smallest set that are in all the others
+        sets.sort_by_key(HashSet::len);
+        let Some((smallest, others)) = sets.split_first() else {
+            return Ok(RespValue::Integer(0));
+        };
+        let mut count = 0;
+        for member in smallest {
+            if others.iter().all(|set| set.contains(member)) {
+                count += 1;
+                if count == limit {
+                    break;
+                }
+            }
+        }
+        Ok(RespValue::Integer(count as i64))
     }
 
     /// SDIFF key [key ...]
@@ -302,34 +381,8 @@ impl SetCommands {
         if args.is_empty() {
             return Err(AikvError::WrongArgCount("SDIFF".to_string()));
         }
-
-        let keys: Vec<String> = args
-            .iter()
-            .map(|b| String::from_utf8_lossy(b).to_string())
-            .collect();
-
-        let mut result: HashSet<Vec<u8>> = HashSet::new();
-
-        // Start with the first set
-        if let Some(stored) = self.storage.get_value(db_index, &keys[0])? {
-            let set = stored.as_set()?;
-            result = set.clone();
-        }
-
-        // Subtract all other sets
-        for key in &keys[1..] {
-            if let Some(stored) = self.storage.get_value(db_index, key)? {
-                let set = stored.as_set()?;
-                result = result.difference(set).cloned().collect();
-            }
-        }
-
-        Ok(RespValue::Array(Some(
-            result
-                .into_iter()
-                .map(|v| RespValue::bulk_string(Bytes::from(v)))
-                .collect(),
-        )))
+        let result = self.combine(SetOp::Diff, args, db_index)?;
+        Ok(Self::set_reply(result))
     }
 
     /// SUNIONSTORE destination key [key ...]
@@ -338,26 +391,7 @@ impl SetCommands {
         if args.len() < 2 {
             return Err(AikvError::WrongArgCount("SUNIONSTORE".to_string()));
         }
-
-        let dest = String::from_utf8_lossy(&args[0]).to_string();
-        let keys: Vec<String> = args[1..]
-            .iter()
-            .map(|b| String::from_utf8_lossy(b).to_string())
-            .collect();
-
-        let mut result = HashSet::new();
-        for key in keys {
-            if let Some(stored) = self.storage.get_value(db_index, &key)? {
-                let set = stored.as_set()?;
-                result.extend(set.iter().cloned());
-            }
-        }
-
-        let count = result.len();
-        self.storage
-            .set_value(db_index, dest, StoredValue::new_set(result))?;
-
-        Ok(RespValue::Integer(count as i64))
+        self.combine_and_store(SetOp::Union, args, db_index)
     }
 
     /// SINTERSTORE destination key [key ...]
@@ -366,36 +400,7 @@ impl SetCommands {
         if args.len() < 2 {
             return Err(AikvError::WrongArgCount("SINTERSTORE".to_string()));
         }
-
-        let dest = String::from_utf8_lossy(&args[0]).to_string();
-        let keys: Vec<String> = args[1..]
-            .iter()
-            .map(|b| String::from_utf8_lossy(b).to_string())
-            .collect();
-
-        let mut result: Option<HashSet<Vec<u8>>> = None;
-
-        for key in keys {
-            if let Some(stored) = self.storage.get_value(db_index, &key)? {
-                let set = stored.as_set()?;
-                if let Some(res) = &mut result {
-                    *res = res.intersection(set).cloned().collect();
-                } else {
-                    result = Some(set.clone());
-                }
-            } else {
-                // If any key doesn't exist, intersection is empty
-                result = Some(HashSet::new());
-                break;
-            }
-        }
-
-        let final_set = result.unwrap_or_default();
-        let count = final_set.len();
-        self.storage
-            .set_value(db_index, dest, StoredValue::new_set(final_set))?;
-
-        Ok(RespValue::Integer(count as i64))
+        self.combine_and_store(SetOp::Inter, args, db_index)
     }
 
     /// SDIFFSTORE destination key [key ...]
@@ -404,36 +409,96 @@ impl SetCommands {
         if args.len() < 2 {
             return Err(AikvError::WrongArgCount("SDIFFSTORE".to_string()));
         }
+        self.combine_and_store(SetOp::Diff, args, db_index)
+    }
 
-        let dest = String::from_utf8_lossy(&args[0]).to_string();
-        let keys: Vec<String> = args[1..]
-            .iter()
-            .map(|b| String::from_utf8_lossy(b).to_string())
-            .collect();
-
-        let mut result: HashSet<Vec<u8>> = HashSet::new();
-
-        // Start with the first set
-        if let Some(stored) = self.storage.get_value(db_index, &keys[0])? {
-            let set = stored.as_set()?;
-            result = set.clone();
+    /// Compute `op` over the sets stored at `keys`; a missing key is an empty set
+    fn combine(&self, op: SetOp, keys: &[Bytes], db_index: usize) -> Result<HashSet<Vec<u8>>> {
+        let mut sets = Vec::with_capacity(keys.len());
+        for key in keys {
+            let key = String::from_utf8_lossy(key);
+            match self.storage.get_value(db_index, &key)? {
+                Some(stored) => sets.push(stored.as_set()?.clone()),
+                // Nothing intersects an empty set, so don't load the rest
+                None if op == SetOp::Inter => return Ok(HashSet::new()),
+                None => sets.push(HashSet::new()),
+            }
         }
 
-        // Subtract all other sets
-        for key in &keys[1..] {
-            if let Some(stored) = self.storage.get_value(db_index, key)? {
-                let set = stored.as_set()?;
-                result = result.difference(set).cloned().collect();
+        let result = match op {
+            SetOp::Union => {
+                let mut result = HashSet::new();
+                for set in sets {
+                    result.extend(set);
+                    self.check_result_len(result.len())?;
+                }
+                result
             }
-        }
+            SetOp::Inter => {
+                // Start from the smallest set: the result can only shrink
+                sets.sort_by_key(HashSet::len);
+                let mut sets = sets.into_iter();
+                let mut result = sets.next().unwrap_or_default();
+                for set in sets {
+                    if result.is_empty() {
+                        break;
+                    }
+                    result.retain(|member| set.contains(member));
+                }
+                result
+            }
+            SetOp::Diff => {
+                let mut sets = sets.into_iter();
+                let mut result = sets.next().unwrap_or_default();
+                for set in sets {
+                    if result.is_empty() {
+                        break;
+                    }
+                    result.retain(|member| !set.contains(member));
+                }
+                result
+            }
+        };
+        self.check_result_len(result.len())?;
+        Ok(result)
+    }
+
+    /// Store the result of `op` over `args[1..]` at `args[0]`; an empty result
+    /// deletes the destination instead of storing an empty set
+    fn combine_and_store(&self, op: SetOp, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        let dest = String::from_utf8_lossy(&args[0]).to_string();
+        let result = self.combine(op, &args[1..], db_index)?;
 
         let count = result.len();
-        self.storage
-            .set_value(db_index, dest, StoredValue::new_set(result))?;
+        if result.is_empty() {
+            self.storage.delete_from_db(db_index, &dest)?;
+        } else {
+            self.storage
+                .set_value(db_index, dest, StoredValue::new_set(result))?;
+        }
 
         Ok(RespValue::Integer(count as i64))
     }
 
+    fn check_result_len(&self, len: usize) -> Result<()> {
+        match self.result_limit {
+            Some(limit) if len > limit => Err(AikvError::InvalidArgument(format!(
+                "ERR set operation result exceeds the limit of {} members",
+                limit
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    fn set_reply(result: HashSet<Vec<u8>>) -> RespValue {
+        RespValue::set(
+            result
+                .into_iter()
+                .map(|v| RespValue::bulk_string(Bytes::from(v)))
+                .collect(),
+        )
+    }
+
     /// SSCAN key cursor [MATCH pattern] [COUNT count]
     /// Incrementally iterates over the members of a set
     pub fn sscan(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
@@ -0,0 +1,314 @@
+use super::string::parse_float;
+use crate::error::{AikvError, Result};
+use crate::protocol::RespValue;
+use crate::storage::{StorageEngine, StoredValue, ValueType};
+use bytes::Bytes;
+use std::collections::VecDeque;
+
+/// SORT command handler
+pub struct SortCommands {
+    storage: StorageEngine,
+}
+
+/// Options of one SORT call
+#[derive(Debug, Default)]
+struct SortOptions {
+    /// Pattern of the external keys to sort by
+    by: Option<Bytes>,
+    /// Offset and count of the elements to return
+    limit: Option<(i64, i64)>,
+    /// Patterns of the external keys to return instead of the elements
+    get: Vec<Bytes>,
+    desc: bool,
+    alpha: bool,
+    /// List to store the result in instead of returning it
+    store: Option<String>,
+}
+
+impl SortOptions {
+    fn parse(args: &[Bytes]) -> Result<Self> {
+        let mut options = Self::default();
+        let mut i = 0;
+        while i < args.len() {
+            let option = String::from_utf8_lossy(&args[i]).to_uppercase();
+            let remaining = args.len() - i - 1;
+            match option.as_str() {
+                "ASC" => options.desc = false,
+                "DESC" => options.desc = true,
+                "ALPHA" => options.alpha = true,
+                "BY" if remaining >= 1 => {
+                    options.by = Some(args[i + 1].clone());
+                    i += 1;
+                }
+                "GET" if remaining >= 1 => {
+                    options.get.push(args[i + 1].clone());
+                    i += 1;
+                }
+                "STORE" if remaining >= 1 => {
+                    options.store = Some(String::from_utf8_lossy(&args[i + 1]).to_string());
+                    i += 1;
+                }
+                "LIMIT" if remaining >= 2 => {
+                    options.limit =
+                        Some((parse_integer(&args[i + 1])?, parse_integer(&args[i + 2])?));
+                    i += 2;
+                }
+                _ => return Err(AikvError::InvalidArgument("ERR syntax error".to_string())),
+            }
+            i += 1;
+        }
+        Ok(options)
+    }
+
+    /// A `BY` pattern without `*` names no per-element key, so the elements
+    /// keep their stored order
+    fn dont_sort(&self) -> bool {
+        self.by
+            .as_ref()
+            .is_some_and(|pattern| !pattern.contains(&b'*'))
+    }
+}
+
+fn parse_integer(arg: &[u8]) -> Result<i64> {
+    String::from_utf8_lossy(arg).parse::<i64>().map_err(|_| {
+        AikvError::InvalidArgument("ERR value is not an integer or out of range".to_string())
+    })
+}
+
+/// An element being sorted and the weight it's sorted by
+struct SortItem {
+    element: Bytes,
+    /// Weight compared under ALPHA
+    text: Option<Bytes>,
+    /// Weight compared numerically
+    score: f64,
+}
+
+impl SortCommands {
+    pub fn new(storage: StorageEngine) -> Self {
+        Self {
+            storage,
+        }
+    }
+
+    /// SORT key \[BY pattern\] \[LIMIT offset count\] \[GET pattern ...\] \[ASC|DESC\] \[ALPHA\] \[STORE destination\]
+    /// Sort the elements of a list, set or sorted set
+    ///
+    /// Elements sort numerically unless ALPHA is given. BY and GET patterns
+    /// name other keys by replacing their first `*` with the element, and
+    /// read a hash field with a `key->field` suffix; GET `#` is the element
+    /// itself.
+    pub fn sort(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        if args.is_empty() {
+            return Err(AikvError::WrongArgCount("SORT".to_string()));
+        }
+
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+        let options = SortOptions::parse(&args[1..])?;
+
+        let elements = match self.storage.get_value(db_index, &key)? {
+            Some(stored) => Self::elements(&stored)?,
+            None => Vec::new(),
+        };
+
+        let mut elements = if options.dont_sort() {
+            elements
+        } else {
+            self.sorted(elements, &options, db_index)?
+        };
+
+        if let Some((offset, count)) = options.limit {
+            let offset = offset.max(0) as usize;
+            let count = if count < 0 {
+                elements.len()
+            } else {
+                count as usize
+            };
+            elements = elements.into_iter().skip(offset).take(count).collect();
+        }
+
+        // Each element, or what each GET pattern finds for it
+        let mut results = Vec::new();
+        for element in elements {
+            if options.get.is_empty() {
+                results.push(Some(element));
+                continue;
+            }
+            for pattern in &options.get {
+                results.push(self.lookup(db_index, pattern, &element)?);
+            }
+        }
+
+        match options.store {
+            Some(destination) => {
+                let len = results.len();
+                if results.is_empty() {
+                    self.storage.delete_from_db(db_index, &destination)?;
+                } else {
+                    // Missing values are stored as empty strings
+                    let list: VecDeque<Bytes> =
+                        results.into_iter().map(Option::unwrap_or_default).collect();
+                    self.storage
+                        .set_value(db_index, destination, StoredValue::new_list(list))?;
+                }
+                Ok(RespValue::integer(len as i64))
+            }
+            None => Ok(RespValue::array(
+                results
+                    .into_iter()
+                    .map(|result| match result {
+                        Some(value) => RespValue::bulk_string(value),
+                        None => RespValue::null_bulk_string(),
+                    })
+                    .collect(),
+            )),
+        }
+    }
+
+    /// The elements of a sortable value, in their stored order
+    fn elements(stored: &StoredValue) -> Result<Vec<Bytes>> {
+        match stored.value() {
+            ValueType::List(list) => Ok(list.iter().cloned().collect()),
+            ValueType::Set(set) => Ok(set.iter().map(|m| Bytes::from(m.clone())).collect()),
+            ValueType::ZSet(zset) => {
+                let mut members: Vec<(&Vec<u8>, f64)> = zset
+                    .iter()
+                    .map(|(member, score)| (member, *score))
+                    .collect();
+                members.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(b.0)));
+                Ok(members
+                    .into_iter()
+                    .map(|(member, _)| Bytes::from(member.clone()))
+                    .collect())
+            }
+            _ => Err(AikvError::WrongType(
+                "Operation against a key holding the wrong kind of value".to_string(),
+            )),
+        }
+    }
+
+    /// Sort `elements` by their weights, breaking ties by the elements
+    /// themselves so the order is deterministic
+    fn sorted(
+        &self,
+        elements: Vec<Bytes>,
+        options: &SortOptions,
+        db_index: usize,
+    ) -> Result<Vec<Bytes>> {
+        let mut items = Vec::with_capacity(elements.len());
+        for element in elements {
+            let weight = match &options.by {
+                Some(pattern) => self.lookup(db_index, pattern, &element)?,
+                None => Some(element.clone()),
+            };
+            let score = match (&weight, options.alpha) {
+                (Some(weight), false) => parse_float(weight).ok_or_else(|| {
+                    AikvError::InvalidArgument(
+                        "ERR One or more scores can't be converted into double".to_string(),
+                    )
+                })?,
+                // Missing weights count as 0
+                _ => 0.0,
+            };
+            items.push(SortItem {
+                element,
+                text: weight,
+                score,
+            });
+        }
+
+        items.sort_by(|a, b| {
+            let order = if options.alpha {
+                // Missing weights sort first
+                a.text.cmp(&b.text)
+            } else {
+                a.score.total_cmp(&b.score)
+            };
+            let order = order.then_with(|| a.element.cmp(&b.element));
+            if options.desc {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+
+        Ok(items.into_iter().map(|item| item.element).collect())
+    }
+
+    /// The value `pattern` names for `element`, if there is one
+    fn lookup(&self, db_index: usize, pattern: &[u8], element: &[u8]) -> Result<Option<Bytes>> {
+        if pattern == b"#" {
+            return Ok(Some(Bytes::copy_from_slice(element)));
+        }
+        let Some((key, field)) = pattern_key(pattern, element) else {
+            return Ok(None);
+        };
+        let Some(stored) = self.storage.get_value(db_index, &key)? else {
+            return Ok(None);
+        };
+
+        // Keys of the wrong type read as missing
+        Ok(match (stored.value(), field) {
+            (ValueType::String(value), None) => Some(value.clone()),
+            (ValueType::Hash(hash), Some(field)) => {
+                hash.get(String::from_utf8_lossy(field).as_ref()).cloned()
+            }
+            _ => None,
+        })
+    }
+}
+
+/// The key `pattern` names for `element`, and the hash field to read from
+/// it if the pattern has one
+fn pattern_key<'a>(pattern: &'a [u8], element: &[u8]) -> Option<(String, Option<&'a [u8]>)> {
+    let star = pattern.iter().position(|&b| b == b'*')?;
+
+    // A `->field` after the `*` reads a hash field instead of a string
+    let arrow = pattern[star..]
+        .windows(2)
+        .position(|w| w == b"->")
+        .map(|pos| star + pos)
+        .filter(|&pos| pos + 2 < pattern.len());
+    let (key_pattern, field) = match arrow {
+        Some(pos) => (&pattern[..pos], Some(&pattern[pos + 2..])),
+        None => (pattern, None),
+    };
+
+    let key = [&key_pattern[..star], element, &key_pattern[star + 1..]].concat();
+    Some((String::from_utf8_lossy(&key).to_string(), field))
+}
+
+/// The keys the BY and GET patterns of a SORT call read, for callers that
+/// have to prepare them before the sort runs
+///
+/// Malformed arguments name no keys; SORT itself reports them.
+pub(super) fn pattern_keys(
+    storage: &StorageEngine,
+    args: &[Bytes],
+    db_index: usize,
+) -> Result<Vec<String>> {
+    let Some((key, options)) = args.split_first() else {
+        return Ok(Vec::new());
+    };
+    let Ok(options) = SortOptions::parse(options) else {
+        return Ok(Vec::new());
+    };
+    let patterns: Vec<&Bytes> = options.by.iter().chain(&options.get).collect();
+    if patterns.is_empty() {
+        return Ok(Vec::new());
+    }
+    let elements = match storage.get_value(db_index, &String::from_utf8_lossy(key))? {
+        Some(stored) => SortCommands::elements(&stored).unwrap_or_default(),
+        None => Vec::new(),
+    };
+
+    Ok(patterns
+        .iter()
+        .flat_map(|pattern| {
+            elements
+                .iter()
+                .filter_map(|element| pattern_key(pattern, element))
+                .map(|(key, _)| key)
+        })
+        .collect())
+}
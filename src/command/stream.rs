@@ -0,0 +1,325 @@
+use crate::error::{AikvError, Result};
+use crate::protocol::RespValue;
+use crate::storage::{StorageEngine, StoredValue, StreamId};
+use bytes::Bytes;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The ID XADD gives its entry, as written in the command
+enum NewId {
+    /// `*`: the current time, or right after the last entry if the clock is behind
+    Auto,
+    /// `<ms>-*`: the next sequence number within `ms`
+    AutoSeq(u64),
+    /// `<ms>-<seq>` or `<ms>`
+    Explicit(StreamId),
+}
+
+fn invalid_id() -> AikvError {
+    AikvError::InvalidArgument(
+        "ERR Invalid stream ID specified as stream command argument".to_string(),
+    )
+}
+
+fn not_increasing() -> AikvError {
+    AikvError::InvalidArgument(
+        "ERR The ID specified in XADD is equal or smaller than the target stream top item"
+            .to_string(),
+    )
+}
+
+/// Parse `<ms>-<seq>`, or `<ms>` with `missing_seq` as its sequence number
+fn parse_id(arg: &[u8], missing_seq: u64) -> Result<StreamId> {
+    let arg = std::str::from_utf8(arg).map_err(|_| invalid_id())?;
+    let (ms, seq) = match arg.split_once('-') {
+        Some((ms, seq)) => (ms, seq.parse::<u64>().map_err(|_| invalid_id())?),
+        None => (arg, missing_seq),
+    };
+    Ok((ms.parse::<u64>().map_err(|_| invalid_id())?, seq))
+}
+
+fn parse_new_id(arg: &[u8]) -> Result<NewId> {
+    if arg == b"*" {
+        return Ok(NewId::Auto);
+    }
+    if let Some(ms) = arg.strip_suffix(b"-*") {
+        let ms = std::str::from_utf8(ms)
+            .ok()
+            .and_then(|ms| ms.parse::<u64>().ok())
+            .ok_or_else(invalid_id)?;
+        return Ok(NewId::AutoSeq(ms));
+    }
+    Ok(NewId::Explicit(parse_id(arg, 0)?))
+}
+
+/// The ID right after `id`, if there is one
+fn next_id((ms, seq): StreamId) -> Option<StreamId> {
+    match seq.checked_add(1) {
+        Some(seq) => Some((ms, seq)),
+        None => ms.checked_add(1).map(|ms| (ms, 0)),
+    }
+}
+
+/// The ID right before `id`, if there is one
+fn previous_id((ms, seq): StreamId) -> Option<StreamId> {
+    match seq.checked_sub(1) {
+        Some(seq) => Some((ms, seq)),
+        None => ms.checked_sub(1).map(|ms| (ms, u64::MAX)),
+    }
+}
+
+fn format_id((ms, seq): StreamId) -> String {
+    format!("{}-{}", ms, seq)
+}
+
+/// An entry as XRANGE and XREAD reply with it: its ID and its fields and values
+fn entry_reply(id: StreamId, fields: &[(Bytes, Bytes)]) -> RespValue {
+    RespValue::array(vec![
+        RespValue::bulk_string(format_id(id)),
+        RespValue::array(
+            fields
+                .iter()
+                .flat_map(|(field, value)| {
+                    [
+                        RespValue::bulk_string(field.clone()),
+                        RespValue::bulk_string(value.clone()),
+                    ]
+                })
+                .collect(),
+        ),
+    ])
+}
+
+fn parse_count(arg: &[u8]) -> Result<usize> {
+    let count = String::from_utf8_lossy(arg).parse::<i64>().map_err(|_| {
+        AikvError::InvalidArgument("ERR value is not an integer or out of range".to_string())
+    })?;
+    Ok(count.max(0) as usize)
+}
+
+/// Stream command handler
+///
+/// Entries are kept in ID order, and the last entry's ID is the one new IDs
+/// must be greater than; entries are never removed, so it is also the
+/// largest ID the stream has handed out.
+pub struct StreamCommands {
+    storage: StorageEngine,
+}
+
+impl StreamCommands {
+    pub fn new(storage: StorageEngine) -> Self {
+        Self {
+            storage,
+        }
+    }
+
+    /// Pick the ID of a new entry, given the ID of the stream's last one
+    fn assign_id(last: Option<StreamId>, id: &NewId) -> Result<StreamId> {
+        match *id {
+            NewId::Auto => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0);
+                match last {
+                    // The clock went backwards, or several entries in the same millisecond
+                    Some(last) if last.0 >= now => next_id(last).ok_or_else(|| {
+                        AikvError::InvalidArgument(
+                            "ERR The stream has exhausted the last possible ID, unable to add more items"
+                                .to_string(),
+                        )
+                    }),
+                    _ => Ok((now, 0)),
+                }
+            }
+            NewId::AutoSeq(ms) => match last {
+                Some((last_ms, last_seq)) if last_ms == ms => last_seq
+                    .checked_add(1)
+                    .map(|seq| (ms, seq))
+                    .ok_or_else(not_increasing),
+                Some((last_ms, _)) if last_ms > ms => Err(not_increasing()),
+                // 0-0 is never a valid entry ID
+                _ => Ok((ms, u64::from(ms == 0))),
+            },
+            NewId::Explicit(id) => {
+                if id == (0, 0) {
+                    return Err(AikvError::InvalidArgument(
+                        "ERR The ID specified in XADD must be greater than 0-0".to_string(),
+                    ));
+                }
+                if last.is_some_and(|last| id <= last) {
+                    return Err(not_increasing());
+                }
+                Ok(id)
+            }
+        }
+    }
+
+    /// XADD key <* | id> field value \[field value ...\]
+    /// Append an entry to a stream, creating it if needed, and return its ID
+    pub fn xadd(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        if args.len() < 4 || args.len() % 2 != 0 {
+            return Err(AikvError::WrongArgCount("XADD".to_string()));
+        }
+
+        let key = String::from_utf8_lossy(&args[0]).to_string();
+        let new_id = parse_new_id(&args[1])?;
+        let fields: Vec<(Bytes, Bytes)> = args[2..]
+            .chunks(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+
+        let mut assigned = None;
+        let updated = self.storage.update_value(db_index, &key, |stored| {
+            let entries = stored.as_stream_mut()?;
+            let id = Self::assign_id(entries.keys().next_back().copied(), &new_id)?;
+            entries.insert(id, fields.clone());
+            assigned = Some(id);
+            Ok(())
+        })?;
+
+        let id = match assigned {
+            Some(id) if updated => id,
+            _ => {
+                let id = Self::assign_id(None, &new_id)?;
+                let mut entries = BTreeMap::new();
+                entries.insert(id, fields);
+                self.storage
+                    .set_value(db_index, key, StoredValue::new_stream(entries))?;
+                id
+            }
+        };
+        Ok(RespValue::bulk_string(format_id(id)))
+    }
+
+    /// XLEN key
+    /// The number of entries in a stream, 0 if it doesn't exist
+    pub fn xlen(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        if args.len() != 1 {
+            return Err(AikvError::WrongArgCount("XLEN".to_string()));
+        }
+
+        let key = String::from_utf8_lossy(&args[0]);
+        let len = self
+            .storage
+            .with_value(db_index, &key, |stored| Ok(stored.as_stream()?.len()))?
+            .unwrap_or(0);
+        Ok(RespValue::integer(len as i64))
+    }
+
+    /// XRANGE key start end \[COUNT count\]
+    /// The entries with IDs between start and end, inclusive unless prefixed
+    /// with `(`; `-` and `+` are the smallest and largest IDs, and an ID
+    /// without a sequence number covers the whole millisecond
+    pub fn xrange(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        if args.len() != 3 && args.len() != 5 {
+            return Err(AikvError::WrongArgCount("XRANGE".to_string()));
+        }
+
+        let key = String::from_utf8_lossy(&args[0]);
+        let start = match args[1].as_ref() {
+            b"-" => Some((0, 0)),
+            arg => match arg.strip_prefix(b"(") {
+                Some(id) => next_id(parse_id(id, 0)?),
+                None => Some(parse_id(arg, 0)?),
+            },
+        };
+        let end = match args[2].as_ref() {
+            b"+" => Some((u64::MAX, u64::MAX)),
+            arg => match arg.strip_prefix(b"(") {
+                Some(id) => previous_id(parse_id(id, u64::MAX)?),
+                None => Some(parse_id(arg, u64::MAX)?),
+            },
+        };
+        let count = match args.get(3..) {
+            Some([option, count]) if option.eq_ignore_ascii_case(b"COUNT") => parse_count(count)?,
+            Some([]) => usize::MAX,
+            _ => return Err(AikvError::InvalidArgument("ERR syntax error".to_string())),
+        };
+
+        // An exclusive bound past the ends of the ID space leaves nothing in between
+        let (Some(start), Some(end)) = (start, end) else {
+            return Ok(RespValue::array(Vec::new()));
+        };
+        if start > end || count == 0 {
+            return Ok(RespValue::array(Vec::new()));
+        }
+
+        let entries = self
+            .storage
+            .with_value(db_index, &key, |stored| {
+                Ok(stored
+                    .as_stream()?
+                    .range(start..=end)
+                    .take(count)
+                    .map(|(id, fields)| entry_reply(*id, fields))
+                    .collect())
+            })?
+            .unwrap_or_default();
+        Ok(RespValue::array(entries))
+    }
+
+    /// XREAD \[COUNT count\] STREAMS key \[key ...\] id \[id ...\]
+    /// The entries after each ID, or after the stream's last entry for `$`,
+    /// for every stream with any; a null reply if none has. Never blocks.
+    pub fn xread(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        let mut count = usize::MAX;
+        let mut i = 0;
+        while i < args.len() && !args[i].eq_ignore_ascii_case(b"STREAMS") {
+            match args.get(i + 1) {
+                Some(value) if args[i].eq_ignore_ascii_case(b"COUNT") => {
+                    count = parse_count(value)?;
+                    i += 2;
+                }
+                _ => return Err(AikvError::InvalidArgument("ERR syntax error".to_string())),
+            }
+        }
+
+        let streams = args.get(i + 1..).unwrap_or_default();
+        if streams.is_empty() || streams.len() % 2 != 0 {
+            return Err(AikvError::InvalidArgument(
+                "ERR Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified."
+                    .to_string(),
+            ));
+        }
+        let (keys, ids) = streams.split_at(streams.len() / 2);
+
+        let mut replies = Vec::new();
+        for (key, id) in keys.iter().zip(ids) {
+            let key = String::from_utf8_lossy(key);
+            let after = if id.as_ref() == b"$" {
+                None
+            } else {
+                Some(parse_id(id, 0)?)
+            };
+            let entries: Vec<RespValue> = self
+                .storage
+                .with_value(db_index, &key, |stored| {
+                    let entries = stored.as_stream()?;
+                    // `$` only ever sees entries added after the call, so never any here
+                    let Some(after) = after else {
+                        return Ok(Vec::new());
+                    };
+                    Ok(entries
+                        .range((Bound::Excluded(after), Bound::Unbounded))
+                        .take(count)
+                        .map(|(id, fields)| entry_reply(*id, fields))
+                        .collect())
+                })?
+                .unwrap_or_default();
+            if !entries.is_empty() {
+                replies.push(RespValue::array(vec![
+                    RespValue::bulk_string(key.to_string()),
+                    RespValue::array(entries),
+                ]));
+            }
+        }
+
+        if replies.is_empty() {
+            Ok(RespValue::null_array())
+        } else {
+            Ok(RespValue::array(replies))
+        }
+    }
+}
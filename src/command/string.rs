@@ -1,17 +1,45 @@
+use super::config::{RuntimeConfig, SharedConfig};
 use crate::error::{AikvError, Result};
-use crate::protocol::RespValue;
-use crate::storage::StorageEngine;
+use crate::protocol::{RespValue, DEFAULT_MAX_BULK_LEN};
+use crate::storage::{StorageEngine, ValueType};
 use bytes::Bytes;
+use std::sync::{Arc, RwLock};
 
 /// String command handler
 pub struct StringCommands {
     storage: StorageEngine,
+    /// Runtime configuration, for proto-max-bulk-len
+    config: SharedConfig,
 }
 
 impl StringCommands {
     pub fn new(storage: StorageEngine) -> Self {
         Self {
             storage,
+            config: Arc::new(RwLock::new(RuntimeConfig::default())),
+        }
+    }
+
+    /// Share the server-wide runtime configuration
+    pub fn set_runtime_config(&mut self, config: SharedConfig) {
+        self.config = config;
+    }
+
+    /// Check that a string grown to `len` bytes stays within proto-max-bulk-len
+    ///
+    /// `None` stands for a length that overflowed while being computed.
+    fn check_string_len(&self, len: Option<usize>) -> Result<()> {
+        let limit = self
+            .config
+            .read()
+            .ok()
+            .and_then(|config| config.get("proto-max-bulk-len")?.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_BULK_LEN);
+        match len {
+            Some(len) if len <= limit => Ok(()),
+            _ => Err(AikvError::InvalidArgument(
+                "ERR string exceeds maximum allowed size (proto-max-bulk-len)".to_string(),
+            )),
         }
     }
 
@@ -29,7 +57,10 @@ impl StringCommands {
         }
     }
 
-    /// SET key value \[EX seconds\] \[PX milliseconds\] \[NX|XX\]
+    /// SET key value \[EX seconds\] \[PX milliseconds\] \[NX|XX\] \[KEEPTTL\] \[GET\]
+    ///
+    /// With GET the reply is the old value, whether or not the condition let
+    /// the write happen.
     pub fn set(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
         if args.len() < 2 {
             return Err(AikvError::WrongArgCount("SET".to_string()));
@@ -42,6 +73,8 @@ impl StringCommands {
         let mut i = 2;
         let mut nx = false;
         let mut xx = false;
+        let mut keepttl = false;
+        let mut get = false;
         let mut expire_ms: Option<u64> = None;
 
         while i < args.len() {
@@ -49,6 +82,8 @@ impl StringCommands {
             match option.as_str() {
                 "NX" => nx = true,
                 "XX" => xx = true,
+                "KEEPTTL" => keepttl = true,
+                "GET" => get = true,
                 "EX" => {
                     // Set expiration in seconds
                     if i + 1 >= args.len() {
@@ -78,30 +113,62 @@ impl StringCommands {
             i += 1;
         }
 
+        if keepttl && expire_ms.is_some() {
+            return Err(AikvError::InvalidArgument("ERR syntax error".to_string()));
+        }
+
+        let reply = if get {
+            match self.storage.get_from_db(current_db, &key)? {
+                Some(old) => RespValue::bulk_string(old),
+                None => RespValue::null_bulk_string(),
+            }
+        } else {
+            RespValue::ok()
+        };
+
         // Check conditions
         if nx && self.storage.exists_in_db(current_db, &key)? {
-            return Ok(RespValue::null_bulk_string());
+            return Ok(if get {
+                reply
+            } else {
+                RespValue::null_bulk_string()
+            });
         }
 
         if xx && !self.storage.exists_in_db(current_db, &key)? {
-            return Ok(RespValue::null_bulk_string());
+            return Ok(if get {
+                reply
+            } else {
+                RespValue::null_bulk_string()
+            });
         }
 
+        // An overwrite drops the old TTL unless KEEPTTL asks to keep it
+        let expire_at = match expire_ms {
+            Some(ms) => {
+                use std::time::{SystemTime, UNIX_EPOCH};
+                let now_ms = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis() as u64;
+                Some(now_ms + ms)
+            }
+            None if keepttl => self
+                .storage
+                .peek_value(current_db, &key)?
+                .and_then(|stored| stored.expires_at()),
+            None => None,
+        };
+
         // Set with or without expiration
-        if let Some(ms) = expire_ms {
-            use std::time::{SystemTime, UNIX_EPOCH};
-            let now_ms = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_millis() as u64;
-            let expire_at = now_ms + ms;
+        if let Some(expire_at) = expire_at {
             self.storage
                 .set_with_expiration_in_db(current_db, key, value, expire_at)?;
         } else {
             self.storage.set_in_db(current_db, key, value)?;
         }
 
-        Ok(RespValue::ok())
+        Ok(reply)
     }
 
     /// DEL key \[key ...\]
@@ -144,15 +211,23 @@ impl StringCommands {
             return Err(AikvError::WrongArgCount("MGET".to_string()));
         }
 
-        // Migrated: Logic moved from storage layer to command layer
-        let mut result = Vec::with_capacity(args.len());
-        for arg in args {
-            let key = String::from_utf8_lossy(arg).to_string();
-            match self.storage.get_from_db(current_db, &key)? {
-                Some(bytes) => result.push(RespValue::bulk_string(bytes)),
-                None => result.push(RespValue::null_bulk_string()),
-            }
-        }
+        let keys: Vec<String> = args
+            .iter()
+            .map(|arg| String::from_utf8_lossy(arg).to_string())
+            .collect();
+
+        // Keys holding other types read as nil rather than failing the command
+        let result = self
+            .storage
+            .read_batch(current_db, &keys)?
+            .into_iter()
+            .map(
+                |stored| match stored.as_ref().map(|stored| stored.as_string()) {
+                    Some(Ok(bytes)) => RespValue::bulk_string(bytes.clone()),
+                    _ => RespValue::null_bulk_string(),
+                },
+            )
+            .collect();
 
         Ok(RespValue::array(result))
     }
@@ -196,19 +271,25 @@ impl StringCommands {
         let key = String::from_utf8_lossy(&args[0]).to_string();
         let append_value = &args[1];
 
-        let new_value = match self.storage.get_from_db(current_db, &key)? {
-            Some(existing) => {
-                let mut combined = existing.to_vec();
-                combined.extend_from_slice(append_value);
-                Bytes::from(combined)
-            }
-            None => append_value.clone(),
-        };
+        // Appending in place keeps the key's TTL
+        let mut len = append_value.len();
+        let updated = self.storage.update_value(current_db, &key, |stored| {
+            let current = stored.as_string()?;
+            self.check_string_len(current.len().checked_add(append_value.len()))?;
+            let mut combined = current.to_vec();
+            combined.extend_from_slice(append_value);
+            len = combined.len();
+            *stored.value_mut() = ValueType::String(Bytes::from(combined));
+            Ok(())
+        })?;
 
-        let len = new_value.len() as i64;
-        self.storage.set_in_db(current_db, key, new_value)?;
+        if !updated {
+            self.check_string_len(Some(append_value.len()))?;
+            self.storage
+                .set_in_db(current_db, key, append_value.clone())?;
+        }
 
-        Ok(RespValue::integer(len))
+        Ok(RespValue::integer(len as i64))
     }
 
     /// INCR key
@@ -305,36 +386,21 @@ impl StringCommands {
         }
 
         let key = String::from_utf8_lossy(&args[0]).to_string();
-        let increment = String::from_utf8_lossy(&args[1])
-            .parse::<f64>()
-            .map_err(|_| {
-                AikvError::InvalidArgument("ERR value is not a valid float".to_string())
-            })?;
+        let increment = parse_float(&args[1]).ok_or_else(|| {
+            AikvError::InvalidArgument("ERR value is not a valid float".to_string())
+        })?;
 
         let current_value = match self.storage.get_from_db(current_db, &key)? {
-            Some(value) => {
-                let value_str = String::from_utf8_lossy(&value);
-                value_str.parse::<f64>().map_err(|_| {
-                    AikvError::InvalidArgument("ERR value is not a valid float".to_string())
-                })?
-            }
+            Some(value) => parse_float(&value).ok_or_else(|| {
+                AikvError::InvalidArgument("ERR value is not a valid float".to_string())
+            })?,
             None => 0.0,
         };
 
-        let new_value = current_value + increment;
-
-        // Check for infinity or NaN
-        if new_value.is_infinite() || new_value.is_nan() {
-            return Err(AikvError::InvalidArgument(
-                "ERR increment would produce NaN or Infinity".to_string(),
-            ));
-        }
-
-        // Format the float as Redis does (remove trailing zeros)
-        let formatted = format!("{}", new_value);
+        let new_value = checked_float_add(current_value, increment)?;
         self.storage
-            .set_in_db(current_db, key, Bytes::from(formatted.clone()))?;
-        Ok(RespValue::bulk_string(Bytes::from(formatted)))
+            .set_in_db(current_db, key, Bytes::from(format_float(new_value)))?;
+        Ok(RespValue::double(new_value))
     }
 
     /// GETRANGE key start end
@@ -409,7 +475,13 @@ impl StringCommands {
             None => Vec::new(),
         };
 
+        // Writing nothing leaves the string, or the missing key, as it is
+        if value.is_empty() {
+            return Ok(RespValue::integer(current.len() as i64));
+        }
+
         // Extend with null bytes if necessary
+        self.check_string_len(offset.checked_add(value.len()))?;
         let required_len = offset + value.len();
         if required_len > current.len() {
             current.resize(required_len, 0);
@@ -661,16 +733,17 @@ impl StringCommands {
             ));
         }
 
+        // Calculate byte and bit positions
+        let byte_index = offset / 8;
+        let bit_index = offset % 8;
+        self.check_string_len(Some(byte_index + 1))?;
+
         // Get current value or create empty string
         let mut current = match self.storage.get_from_db(current_db, &key)? {
             Some(v) => v.to_vec(),
             None => Vec::new(),
         };
 
-        // Calculate byte and bit positions
-        let byte_index = offset / 8;
-        let bit_index = offset % 8;
-
         // Extend the string if necessary
         if byte_index >= current.len() {
             current.resize(byte_index + 1, 0);
@@ -690,6 +763,214 @@ impl StringCommands {
             .set_in_db(current_db, key, Bytes::from(current))?;
         Ok(RespValue::integer(old_bit))
     }
+
+    /// LCS key1 key2 \[LEN\] \[IDX\] \[MINMATCHLEN len\] \[WITHMATCHLEN\]
+    /// Find the longest common subsequence of two strings
+    ///
+    /// A missing key counts as an empty string.
+    pub fn lcs(&self, args: &[Bytes], current_db: usize) -> Result<RespValue> {
+        if args.len() < 2 {
+            return Err(AikvError::WrongArgCount("LCS".to_string()));
+        }
+
+        let mut len_only = false;
+        let mut idx = false;
+        let mut min_match_len = 0;
+        let mut with_match_len = false;
+        let mut i = 2;
+        while i < args.len() {
+            let option = String::from_utf8_lossy(&args[i]).to_uppercase();
+            match option.as_str() {
+                "LEN" => len_only = true,
+                "IDX" => idx = true,
+                "WITHMATCHLEN" => with_match_len = true,
+                "MINMATCHLEN" if i + 1 < args.len() => {
+                    i += 1;
+                    let len = String::from_utf8_lossy(&args[i])
+                        .parse::<i64>()
+                        .map_err(|_| {
+                            AikvError::InvalidArgument(
+                                "ERR value is not an integer or out of range".to_string(),
+                            )
+                        })?;
+                    min_match_len = len.max(0) as usize;
+                }
+                _ => return Err(AikvError::InvalidArgument("ERR syntax error".to_string())),
+            }
+            i += 1;
+        }
+        if len_only && idx {
+            return Err(AikvError::InvalidArgument(
+                "ERR If you want both the length and indexes, please just use IDX.".to_string(),
+            ));
+        }
+
+        let a = self.string_or_empty(&args[0], current_db)?;
+        let b = self.string_or_empty(&args[1], current_db)?;
+        let lcs = Lcs::new(&a, &b);
+
+        if len_only {
+            return Ok(RespValue::integer(lcs.len() as i64));
+        }
+        if !idx {
+            return Ok(RespValue::bulk_string(lcs.subsequence()));
+        }
+
+        let range = |start: usize, end: usize| {
+            RespValue::array(vec![
+                RespValue::integer(start as i64),
+                RespValue::integer(end as i64),
+            ])
+        };
+        let matches = lcs
+            .matches()
+            .into_iter()
+            .filter(|m| m.len() >= min_match_len)
+            .map(|m| {
+                let mut reply = vec![range(m.a.0, m.a.1), range(m.b.0, m.b.1)];
+                if with_match_len {
+                    reply.push(RespValue::integer(m.len() as i64));
+                }
+                RespValue::array(reply)
+            })
+            .collect();
+        Ok(RespValue::map(vec![
+            (RespValue::bulk_string("matches"), RespValue::array(matches)),
+            (
+                RespValue::bulk_string("len"),
+                RespValue::integer(lcs.len() as i64),
+            ),
+        ]))
+    }
+
+    /// The string stored at `key`, or an empty one if there is none
+    fn string_or_empty(&self, key: &[u8], current_db: usize) -> Result<Bytes> {
+        let key = String::from_utf8_lossy(key);
+        match self.storage.get_value(current_db, &key)? {
+            Some(value) => value.as_string().cloned(),
+            None => Ok(Bytes::new()),
+        }
+    }
+}
+
+/// The longest common subsequence table of two strings
+struct Lcs<'a> {
+    a: &'a [u8],
+    b: &'a [u8],
+    /// `table[i * (b.len() + 1) + j]` is the LCS length of `a[..i]` and `b[..j]`
+    table: Vec<u32>,
+}
+
+/// A run of bytes common to both strings, as inclusive ranges in each
+struct LcsMatch {
+    a: (usize, usize),
+    b: (usize, usize),
+}
+
+impl LcsMatch {
+    fn len(&self) -> usize {
+        self.a.1 - self.a.0 + 1
+    }
+}
+
+impl<'a> Lcs<'a> {
+    fn new(a: &'a [u8], b: &'a [u8]) -> Self {
+        let width = b.len() + 1;
+        let mut table = vec![0u32; (a.len() + 1) * width];
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                table[i * width + j] = if a[i - 1] == b[j - 1] {
+                    table[(i - 1) * width + j - 1] + 1
+                } else {
+                    table[(i - 1) * width + j].max(table[i * width + j - 1])
+                };
+            }
+        }
+        Self {
+            a,
+            b,
+            table,
+        }
+    }
+
+    fn at(&self, i: usize, j: usize) -> u32 {
+        self.table[i * (self.b.len() + 1) + j]
+    }
+
+    fn len(&self) -> usize {
+        self.at(self.a.len(), self.b.len()) as usize
+    }
+
+    /// Walk the table back from the end, calling `matched` with the
+    /// positions of each common byte, last one first
+    fn backtrack(&self, mut matched: impl FnMut(usize, usize)) {
+        let (mut i, mut j) = (self.a.len(), self.b.len());
+        while i > 0 && j > 0 {
+            if self.a[i - 1] == self.b[j - 1] {
+                matched(i - 1, j - 1);
+                i -= 1;
+                j -= 1;
+            } else if self.at(i - 1, j) > self.at(i, j - 1) {
+                i -= 1;
+            } else {
+                j -= 1;
+            }
+        }
+    }
+
+    /// The common subsequence itself
+    fn subsequence(&self) -> Bytes {
+        let mut bytes = Vec::with_capacity(self.len());
+        self.backtrack(|i, _| bytes.push(self.a[i]));
+        bytes.reverse();
+        Bytes::from(bytes)
+    }
+
+    /// The runs the subsequence is made of, last one first as Redis reports them
+    fn matches(&self) -> Vec<LcsMatch> {
+        let mut matches: Vec<LcsMatch> = Vec::new();
+        self.backtrack(|i, j| match matches.last_mut() {
+            // Backtracking extends the current run towards the start
+            Some(run) if run.a.0 == i + 1 && run.b.0 == j + 1 => {
+                run.a.0 = i;
+                run.b.0 = j;
+            }
+            _ => matches.push(LcsMatch {
+                a: (i, i),
+                b: (j, j),
+            }),
+        });
+        matches
+    }
+}
+
+/// Parse a float argument or stored value, rejecting `nan`
+pub(crate) fn parse_float(bytes: &[u8]) -> Option<f64> {
+    std::str::from_utf8(bytes)
+        .ok()?
+        .parse::<f64>()
+        .ok()
+        .filter(|value| !value.is_nan())
+}
+
+/// Add an increment the way INCRBYFLOAT and HINCRBYFLOAT do, refusing to
+/// produce a NaN or infinite result
+pub(crate) fn checked_float_add(current: f64, increment: f64) -> Result<f64> {
+    let value = current + increment;
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(AikvError::InvalidArgument(
+            "ERR increment would produce NaN or Infinity".to_string(),
+        ))
+    }
+}
+
+/// Format a float as Redis stores it: the shortest decimal that round-trips,
+/// without trailing zeros or an exponent (`3000`, not `3.0e3`)
+pub(crate) fn format_float(value: f64) -> String {
+    // f64's Display never switches to scientific notation
+    value.to_string()
 }
 
 #[cfg(test)]
@@ -697,6 +978,17 @@ mod tests {
     use super::*;
     use crate::storage::StorageEngine;
 
+    #[test]
+    fn test_format_float() {
+        assert_eq!(format_float(3.0), "3");
+        assert_eq!(format_float(3.140), "3.14");
+        assert_eq!(format_float(parse_float(b"3.0e3").unwrap()), "3000");
+        assert_eq!(format_float(1e21), "1000000000000000000000");
+        assert_eq!(format_float(0.0001), "0.0001");
+        assert_eq!(parse_float(b"nan"), None);
+        assert_eq!(parse_float(b"1.5x"), None);
+    }
+
     fn setup() -> StringCommands {
         StringCommands::new(StorageEngine::new_memory(16))
     }
@@ -815,4 +1107,70 @@ mod tests {
         let result = cmd.get(&[Bytes::from("key1")], 0).unwrap();
         assert_eq!(result, RespValue::bulk_string("Hello World"));
     }
+
+    #[test]
+    fn test_lcs() {
+        let cmd = setup();
+        cmd.mset(
+            &[
+                Bytes::from("key1"),
+                Bytes::from("ohmytext"),
+                Bytes::from("key2"),
+                Bytes::from("mynewtext"),
+            ],
+            0,
+        )
+        .unwrap();
+        let lcs = |options: &[&str]| {
+            let mut args = vec![Bytes::from("key1"), Bytes::from("key2")];
+            args.extend(options.iter().map(|o| Bytes::from(o.to_string())));
+            cmd.lcs(&args, 0)
+        };
+        let range =
+            |start, end| RespValue::array(vec![RespValue::integer(start), RespValue::integer(end)]);
+        let idx_reply = |matches: Vec<RespValue>| {
+            RespValue::map(vec![
+                (RespValue::bulk_string("matches"), RespValue::array(matches)),
+                (RespValue::bulk_string("len"), RespValue::integer(6)),
+            ])
+        };
+
+        assert_eq!(lcs(&[]).unwrap(), RespValue::bulk_string("mytext"));
+        assert_eq!(lcs(&["LEN"]).unwrap(), RespValue::integer(6));
+        assert_eq!(
+            lcs(&["IDX"]).unwrap(),
+            idx_reply(vec![
+                RespValue::array(vec![range(4, 7), range(5, 8)]),
+                RespValue::array(vec![range(2, 3), range(0, 1)]),
+            ])
+        );
+        assert_eq!(
+            lcs(&["IDX", "MINMATCHLEN", "4", "WITHMATCHLEN"]).unwrap(),
+            idx_reply(vec![RespValue::array(vec![
+                range(4, 7),
+                range(5, 8),
+                RespValue::integer(4)
+            ])])
+        );
+        assert!(lcs(&["LEN", "IDX"]).is_err());
+        assert!(lcs(&["BOGUS"]).is_err());
+
+        // A missing key is an empty string; any other type is an error
+        assert_eq!(
+            cmd.lcs(&[Bytes::from("key1"), Bytes::from("missing")], 0)
+                .unwrap(),
+            RespValue::bulk_string("")
+        );
+        cmd.storage
+            .set_value(
+                0,
+                "list".to_string(),
+                crate::storage::StoredValue::new_list(vec![Bytes::from("a")].into()),
+            )
+            .unwrap();
+        assert!(matches!(
+            cmd.lcs(&[Bytes::from("key1"), Bytes::from("list")], 0),
+            Err(AikvError::WrongType(_))
+        ));
+    }
 }
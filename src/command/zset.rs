@@ -1,3 +1,4 @@
+use super::list::parse_mpop_args;
 use crate::error::{AikvError, Result};
 use crate::protocol::RespValue;
 use crate::storage::{StorageEngine, StoredValue};
@@ -113,7 +114,7 @@ impl ZSetCommands {
         };
 
         match score {
-            Some(score) => Ok(RespValue::bulk_string(Bytes::from(score.to_string()))),
+            Some(score) => Ok(RespValue::double(score)),
             None => Ok(RespValue::Null),
         }
     }
@@ -204,47 +205,51 @@ impl ZSetCommands {
         let with_scores =
             args.len() > 3 && String::from_utf8_lossy(&args[3]).to_uppercase() == "WITHSCORES";
 
-        // Migrated: Logic moved from storage layer to command layer
-        let members = if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            let zset = stored.as_zset()?;
-            let mut sorted: Vec<_> = zset.iter().collect();
-            sorted.sort_by(|a, b| a.1.partial_cmp(b.1).unwrap());
-
-            let len = sorted.len() as i64;
-            let start_idx = if start < 0 {
-                (len + start).max(0)
-            } else {
-                start.min(len)
-            } as usize;
-            let stop_idx = if stop < 0 {
-                (len + stop).max(-1) + 1
-            } else {
-                (stop + 1).min(len)
-            } as usize;
+        // Borrow the sorted set in place so only the requested range is copied
+        let members = self
+            .storage
+            .with_value(db_index, &key, |stored| {
+                let zset = stored.as_zset()?;
+                let mut sorted: Vec<_> = zset.iter().collect();
+                sorted.sort_by(|a, b| a.1.partial_cmp(b.1).unwrap());
 
-            if start_idx >= stop_idx {
-                Vec::new()
-            } else {
-                sorted
-                    .iter()
-                    .skip(start_idx)
-                    .take(stop_idx - start_idx)
-                    .map(|(m, s)| (Bytes::from(m.to_vec()), **s))
-                    .collect()
-            }
+                let len = sorted.len() as i64;
+                let start_idx = if start < 0 {
+                    (len + start).max(0)
+                } else {
+                    start.min(len)
+                } as usize;
+                let stop_idx = if stop < 0 {
+                    (len + stop).max(-1) + 1
+                } else {
+                    (stop + 1).min(len)
+                } as usize;
+
+                if start_idx >= stop_idx {
+                    Ok(Vec::new())
+                } else {
+                    Ok(sorted
+                        .iter()
+                        .skip(start_idx)
+                        .take(stop_idx - start_idx)
+                        .map(|(m, s)| (Bytes::from(m.to_vec()), **s))
+                        .collect())
+                }
+            })?
+            .unwrap_or_default();
+
+        if with_scores {
+            Ok(RespValue::lazy_array(members, |(member, score)| {
+                [
+                    RespValue::bulk_string(member.clone()),
+                    RespValue::bulk_string(Bytes::from(score.to_string())),
+                ]
+            }))
         } else {
-            Vec::new()
-        };
-
-        let mut result = Vec::new();
-        for (member, score) in members {
-            result.push(RespValue::bulk_string(member));
-            if with_scores {
-                result.push(RespValue::bulk_string(Bytes::from(score.to_string())));
-            }
+            Ok(RespValue::lazy_array(members, |(member, _)| {
+                [RespValue::bulk_string(member.clone())]
+            }))
         }
-
-        Ok(RespValue::Array(Some(result)))
     }
 
     /// ZREVRANGE key start stop \[WITHSCORES\]
@@ -554,7 +559,7 @@ impl ZSetCommands {
 
         self.storage
             .set_value(db_index, key, StoredValue::new_zset(zset.1))?;
-        Ok(RespValue::bulk_string(Bytes::from(zset.0.to_string())))
+        Ok(RespValue::double(zset.0))
     }
 
     /// ZSCAN key cursor [MATCH pattern] [COUNT count]
@@ -669,40 +674,17 @@ impl ZSetCommands {
             1
         };
 
-        if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            let mut zset = stored.as_zset()?.clone();
-
-            // Sort by score (ascending)
-            let mut sorted: Vec<(Vec<u8>, f64)> =
-                zset.iter().map(|(k, v)| (k.clone(), *v)).collect();
-            sorted.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
-
-            let to_pop = count.min(sorted.len());
-            let popped: Vec<(Vec<u8>, f64)> = sorted.into_iter().take(to_pop).collect();
-
-            // Remove popped elements from zset
-            for (member, _) in &popped {
-                zset.remove(member);
-            }
-
-            // Update or delete the zset
-            if zset.is_empty() {
-                self.storage.delete_from_db(db_index, &key)?;
-            } else {
-                self.storage
-                    .set_value(db_index, key, StoredValue::new_zset(zset))?;
-            }
+        let popped = self
+            .pop_by_score(db_index, key, count, false)?
+            .unwrap_or_default();
 
-            // Build response
-            let mut result = Vec::with_capacity(popped.len() * 2);
-            for (member, score) in popped {
-                result.push(RespValue::bulk_string(Bytes::from(member)));
-                result.push(RespValue::bulk_string(Bytes::from(score.to_string())));
-            }
-            Ok(RespValue::Array(Some(result)))
-        } else {
-            Ok(RespValue::Array(Some(vec![])))
+        // Build response
+        let mut result = Vec::with_capacity(popped.len() * 2);
+        for (member, score) in popped {
+            result.push(RespValue::bulk_string(Bytes::from(member)));
+            result.push(RespValue::bulk_string(Bytes::from(score.to_string())));
         }
+        Ok(RespValue::Array(Some(result)))
     }
 
     /// ZPOPMAX key \[count\]
@@ -725,40 +707,92 @@ impl ZSetCommands {
             1
         };
 
-        if let Some(stored) = self.storage.get_value(db_index, &key)? {
-            let mut zset = stored.as_zset()?.clone();
-
-            // Sort by score (descending)
-            let mut sorted: Vec<(Vec<u8>, f64)> =
-                zset.iter().map(|(k, v)| (k.clone(), *v)).collect();
-            sorted.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        let popped = self
+            .pop_by_score(db_index, key, count, true)?
+            .unwrap_or_default();
 
-            let to_pop = count.min(sorted.len());
-            let popped: Vec<(Vec<u8>, f64)> = sorted.into_iter().take(to_pop).collect();
+        // Build response
+        let mut result = Vec::with_capacity(popped.len() * 2);
+        for (member, score) in popped {
+            result.push(RespValue::bulk_string(Bytes::from(member)));
+            result.push(RespValue::bulk_string(Bytes::from(score.to_string())));
+        }
+        Ok(RespValue::Array(Some(result)))
+    }
 
-            // Remove popped elements from zset
-            for (member, _) in &popped {
-                zset.remove(member);
+    /// ZMPOP numkeys key \[key ...\] MIN|MAX \[COUNT count\]
+    /// Pop the lowest or highest scored members from the first non-empty
+    /// sorted set among the keys
+    ///
+    /// Replies with the key and a \[member, score\] pair per popped member,
+    /// or null if every sorted set is empty.
+    pub fn zmpop(&self, args: &[Bytes], db_index: usize) -> Result<RespValue> {
+        let (keys, min, count) = parse_mpop_args("ZMPOP", args, ["MIN", "MAX"])?;
+
+        for key in keys {
+            let name = String::from_utf8_lossy(key).to_string();
+            if let Some(popped) = self.pop_by_score(db_index, name, count, !min)? {
+                let members = popped
+                    .into_iter()
+                    .map(|(member, score)| {
+                        RespValue::array(vec![
+                            RespValue::bulk_string(Bytes::from(member)),
+                            RespValue::bulk_string(Bytes::from(score.to_string())),
+                        ])
+                    })
+                    .collect();
+                return Ok(RespValue::array(vec![
+                    RespValue::bulk_string(key.clone()),
+                    RespValue::array(members),
+                ]));
             }
+        }
+        Ok(RespValue::null_array())
+    }
 
-            // Update or delete the zset
-            if zset.is_empty() {
-                self.storage.delete_from_db(db_index, &key)?;
+    /// Pop up to `count` of the lowest (or, with `max`, highest) scored
+    /// members of the sorted set at `key`, deleting the key once it's empty
+    ///
+    /// Returns `None` if the key is missing.
+    fn pop_by_score(
+        &self,
+        db_index: usize,
+        key: String,
+        count: usize,
+        max: bool,
+    ) -> Result<Option<Vec<(Vec<u8>, f64)>>> {
+        let Some(stored) = self.storage.get_value(db_index, &key)? else {
+            return Ok(None);
+        };
+        let mut zset = stored.as_zset()?.clone();
+
+        // Sort by score, ascending for the lowest and descending for the highest
+        let mut sorted: Vec<(Vec<u8>, f64)> = zset.iter().map(|(k, v)| (k.clone(), *v)).collect();
+        sorted.sort_by(|a, b| {
+            let order = a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal);
+            if max {
+                order.reverse()
             } else {
-                self.storage
-                    .set_value(db_index, key, StoredValue::new_zset(zset))?;
+                order
             }
+        });
 
-            // Build response
-            let mut result = Vec::with_capacity(popped.len() * 2);
-            for (member, score) in popped {
-                result.push(RespValue::bulk_string(Bytes::from(member)));
-                result.push(RespValue::bulk_string(Bytes::from(score.to_string())));
-            }
-            Ok(RespValue::Array(Some(result)))
+        let to_pop = count.min(sorted.len());
+        let popped: Vec<(Vec<u8>, f64)> = sorted.into_iter().take(to_pop).collect();
+
+        // Remove popped elements from zset
+        for (member, _) in &popped {
+            zset.remove(member);
+        }
+
+        // Update or delete the zset
+        if zset.is_empty() {
+            self.storage.delete_from_db(db_index, &key)?;
         } else {
-            Ok(RespValue::Array(Some(vec![])))
+            self.storage
+                .set_value(db_index, key, StoredValue::new_zset(zset))?;
         }
+        Ok(Some(popped))
     }
 
     /// ZRANGEBYLEX key min max [LIMIT offset count]
@@ -8,10 +8,18 @@ pub enum AikvError {
     #[error("Protocol error: {0}")]
     Protocol(String),
 
+    /// Malformed input that more data can't fix; the connection should be closed
+    #[error("Protocol error: {0}")]
+    InvalidProtocol(String),
+
     #[error("Invalid command: {0}")]
     InvalidCommand(String),
 
-    #[error("Wrong number of arguments for '{0}' command")]
+    /// A command this server doesn't know, and its first arguments quoted
+    #[error("unknown command '{0}', with args beginning with: {1}")]
+    UnknownCommand(String, String),
+
+    #[error("wrong number of arguments for '{}' command", .0.to_lowercase())]
     WrongArgCount(String),
 
     #[error("Invalid argument: {0}")]
@@ -20,7 +28,7 @@ pub enum AikvError {
     #[error("Key not found")]
     KeyNotFound,
 
-    #[error("Wrong type: {0}")]
+    #[error("WRONGTYPE {0}")]
     WrongType(String),
 
     #[error("Storage error: {0}")]
@@ -53,6 +61,36 @@ pub enum AikvError {
     #[error("Cluster support is not enabled")]
     ClusterDisabled,
 
+    #[error("OOM command not allowed when used memory > 'maxmemory'.")]
+    OutOfMemory,
+
+    #[error("BUSYKEY Target key name already exists.")]
+    BusyKey,
+
+    /// A MIGRATE target that couldn't be reached or didn't answer in time
+    #[error("IOERR {0}")]
+    IoErr(String),
+
+    /// A data command sent while the dataset is still being loaded on startup
+    #[error("LOADING AiKv is loading the dataset in memory")]
+    Loading,
+
+    #[error("Bad data format")]
+    BadDataFormat,
+
+    #[error("NOPROTO unsupported protocol version")]
+    NoProto,
+
+    #[error("WRONGPASS invalid username-password pair or user is disabled.")]
+    WrongPass,
+
+    #[error("NOAUTH {0}")]
+    NoAuth(String),
+
+    /// The connection's ACL user may not run the command or touch its keys
+    #[error("NOPERM {0}")]
+    NoPerm(String),
+
     #[error("Unknown error: {0}")]
     Unknown(String),
 }
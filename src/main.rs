@@ -1,6 +1,11 @@
+use aikv::command::CommandExecutor;
+use aikv::persistence::{load_rdb_into, AofSyncPolicy, AofWriter};
+use aikv::server::{ShutdownSignal, TlsConfig};
+use aikv::storage::{EvictionPolicy, MemoryLimit};
 use aikv::{Server, StorageEngine};
 use serde::Deserialize;
 use std::fs;
+use std::sync::atomic::Ordering;
 use tracing::{info, warn};
 use tracing_subscriber::{self, filter::LevelFilter, EnvFilter};
 
@@ -20,6 +25,22 @@ struct ServerConfig {
     host: String,
     #[serde(default = "default_port")]
     port: u16,
+    /// Password clients must AUTH with (unset or empty = no authentication)
+    #[serde(default)]
+    requirepass: Option<String>,
+    /// Close connections idle for this many seconds (0 = never)
+    #[serde(default)]
+    timeout: u64,
+    /// TCP keepalive interval of client sockets in seconds (unset = 300, 0 = off)
+    #[serde(default)]
+    tcp_keepalive: Option<u64>,
+    /// Maximum number of simultaneous clients (unset = 10000)
+    #[serde(default)]
+    maxclients: Option<u64>,
+    /// When clients can connect: "loaded" (after the RDB/AOF is loaded) or
+    /// "listening" (right away, getting -LOADING until it is)
+    #[serde(default = "default_ready_when")]
+    ready_when: String,
 }
 
 fn default_host() -> String {
@@ -30,6 +51,10 @@ fn default_port() -> u16 {
     6379
 }
 
+fn default_ready_when() -> String {
+    "loaded".to_string()
+}
+
 /// Storage section of the configuration file
 #[derive(Deserialize, Default)]
 struct StorageConfig {
@@ -56,6 +81,31 @@ fn default_databases() -> usize {
     16
 }
 
+/// Memory section of the configuration file
+#[derive(Deserialize)]
+struct MemoryConfig {
+    /// Maximum dataset size in bytes (0 = unlimited)
+    #[serde(default)]
+    maxmemory: u64,
+    /// Eviction policy: noeviction, allkeys-lru, allkeys-random, volatile-lru, volatile-ttl,
+    /// allkeys-lfu, volatile-lfu
+    #[serde(default = "default_maxmemory_policy")]
+    maxmemory_policy: String,
+}
+
+fn default_maxmemory_policy() -> String {
+    "noeviction".to_string()
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            maxmemory: 0,
+            maxmemory_policy: default_maxmemory_policy(),
+        }
+    }
+}
+
 /// Logging section of the configuration file
 #[derive(Deserialize, Default)]
 struct LoggingConfig {
@@ -68,6 +118,170 @@ fn default_log_level() -> String {
     "info".to_string()
 }
 
+/// Slow log section of the configuration file
+#[derive(Deserialize)]
+struct SlowlogSection {
+    /// Log commands slower than this many microseconds (negative disables)
+    #[serde(
+        rename = "log-slower-than",
+        default = "default_slowlog_log_slower_than"
+    )]
+    log_slower_than: i64,
+    /// Maximum number of slow log entries kept
+    #[serde(rename = "max-len", default = "default_slowlog_max_len")]
+    max_len: usize,
+}
+
+fn default_slowlog_log_slower_than() -> i64 {
+    10_000
+}
+
+fn default_slowlog_max_len() -> usize {
+    128
+}
+
+impl Default for SlowlogSection {
+    fn default() -> Self {
+        Self {
+            log_slower_than: default_slowlog_log_slower_than(),
+            max_len: default_slowlog_max_len(),
+        }
+    }
+}
+
+/// Metrics section of the configuration file
+#[derive(Deserialize)]
+struct MetricsSection {
+    /// Serve Prometheus metrics over HTTP
+    #[serde(default)]
+    enabled: bool,
+    /// Port of the metrics endpoint, on the same host as the server
+    #[serde(default = "default_metrics_port")]
+    port: u16,
+}
+
+fn default_metrics_port() -> u16 {
+    9121
+}
+
+impl Default for MetricsSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: default_metrics_port(),
+        }
+    }
+}
+
+/// TLS section of the configuration file
+#[derive(Deserialize)]
+struct TlsSection {
+    /// Accept TLS connections on a separate port
+    #[serde(default)]
+    enabled: bool,
+    /// Port of the TLS listener, on the same host as the server
+    #[serde(default = "default_tls_port", alias = "tls_port")]
+    port: u16,
+    /// PEM certificate chain presented to clients
+    #[serde(default)]
+    cert_file: String,
+    /// PEM private key of the certificate
+    #[serde(default)]
+    key_file: String,
+    /// PEM CA certificates that client certificates must be signed by
+    #[serde(default)]
+    ca_file: Option<String>,
+}
+
+fn default_tls_port() -> u16 {
+    6380
+}
+
+impl Default for TlsSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: default_tls_port(),
+            cert_file: String::new(),
+            key_file: String::new(),
+            ca_file: None,
+        }
+    }
+}
+
+/// Persistence section of the configuration file
+#[derive(Deserialize)]
+struct PersistenceSection {
+    /// Log every write command to the append-only file
+    #[serde(default)]
+    appendonly: bool,
+    /// Path of the append-only file
+    #[serde(default = "default_aof_path")]
+    aof_path: String,
+    /// AOF fsync policy: always, everysec, no
+    #[serde(default = "default_aof_sync")]
+    aof_sync: String,
+    /// Rewrite the AOF once it grows by this percentage since the last
+    /// rewrite (0 disables automatic rewrites)
+    #[serde(default = "default_auto_aof_rewrite_percentage")]
+    auto_aof_rewrite_percentage: u64,
+    /// Minimum AOF size in bytes before an automatic rewrite
+    #[serde(default = "default_auto_aof_rewrite_min_size")]
+    auto_aof_rewrite_min_size: u64,
+    /// Path of the RDB snapshot written by SAVE/BGSAVE and loaded on startup
+    #[serde(default = "default_rdb_path")]
+    rdb_path: String,
+    /// Write a CRC64 checksum at the end of RDB snapshots
+    #[serde(default = "default_rdbchecksum")]
+    rdbchecksum: bool,
+    /// Write a final RDB snapshot when shutting down
+    #[serde(default = "default_save_on_shutdown")]
+    save_on_shutdown: bool,
+}
+
+fn default_aof_path() -> String {
+    "appendonly.aof".to_string()
+}
+
+fn default_aof_sync() -> String {
+    "everysec".to_string()
+}
+
+fn default_auto_aof_rewrite_percentage() -> u64 {
+    100
+}
+
+fn default_auto_aof_rewrite_min_size() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_rdb_path() -> String {
+    "dump.rdb".to_string()
+}
+
+fn default_rdbchecksum() -> bool {
+    true
+}
+
+fn default_save_on_shutdown() -> bool {
+    true
+}
+
+impl Default for PersistenceSection {
+    fn default() -> Self {
+        Self {
+            appendonly: false,
+            aof_path: default_aof_path(),
+            aof_sync: default_aof_sync(),
+            auto_aof_rewrite_percentage: default_auto_aof_rewrite_percentage(),
+            auto_aof_rewrite_min_size: default_auto_aof_rewrite_min_size(),
+            rdb_path: default_rdb_path(),
+            rdbchecksum: default_rdbchecksum(),
+            save_on_shutdown: default_save_on_shutdown(),
+        }
+    }
+}
+
 /// Cluster section of the configuration file
 #[cfg(feature = "cluster")]
 #[derive(Deserialize, Default)]
@@ -100,7 +314,17 @@ struct Config {
     #[serde(default)]
     storage: StorageConfig,
     #[serde(default)]
+    memory: MemoryConfig,
+    #[serde(default)]
     logging: LoggingConfig,
+    #[serde(default)]
+    persistence: PersistenceSection,
+    #[serde(default)]
+    metrics: MetricsSection,
+    #[serde(default)]
+    slowlog: SlowlogSection,
+    #[serde(default)]
+    tls: TlsSection,
     #[cfg(feature = "cluster")]
     #[serde(default)]
     cluster: ClusterConfigSection,
@@ -111,6 +335,7 @@ struct CliArgs {
     config_path: Option<String>,
     host: Option<String>,
     port: Option<u16>,
+    requirepass: Option<String>,
     show_help: bool,
     show_version: bool,
 }
@@ -130,6 +355,7 @@ fn print_help() {
     println!("    -c, --config <FILE>    Path to configuration file (TOML format)");
     println!("    -H, --host <HOST>      Bind address (default: 127.0.0.1)");
     println!("    -p, --port <PORT>      Bind port (default: 6379)");
+    println!("    --requirepass <PASS>   Require clients to AUTH with this password");
     println!("    -h, --help             Print help information");
     println!("    -v, --version          Print version information");
     println!();
@@ -155,15 +381,48 @@ fn print_help() {
     println!("    [server]");
     println!("    host = \"127.0.0.1\"");
     println!("    port = 6379");
+    println!("    requirepass = \"secret\"  # optional, enables AUTH");
+    println!("    timeout = 0          # close idle connections after N seconds, 0 = never");
+    println!("    tcp_keepalive = 300  # seconds, 0 disables");
+    println!("    maxclients = 10000   # further connections are rejected");
     println!();
     println!("    [storage]");
     println!("    engine = \"memory\"    # or \"aidb\"");
     println!("    data_dir = \"./data\"  # for aidb engine");
     println!("    databases = 16");
     println!();
+    println!("    [memory]");
+    println!("    maxmemory = 0        # bytes, 0 = unlimited (memory engine)");
+    println!("    maxmemory_policy = \"noeviction\"");
+    println!();
     println!("    [logging]");
     println!("    level = \"info\"       # trace, debug, info, warn, error");
     println!();
+    println!("    [slowlog]");
+    println!("    log-slower-than = 10000  # microseconds, negative disables");
+    println!("    max-len = 128");
+    println!();
+    println!("    [metrics]");
+    println!("    enabled = false      # serve Prometheus metrics at /metrics");
+    println!("    port = 9121");
+    println!();
+    println!("    [tls]");
+    println!("    enabled = false      # accept TLS connections on a separate port");
+    println!("    port = 6380");
+    println!("    cert_file = \"tls/server.crt\"");
+    println!("    key_file = \"tls/server.key\"");
+    println!("    ca_file = \"tls/ca.crt\"  # optional, requires client certificates");
+    println!();
+    println!("    [persistence]");
+    println!("    appendonly = false   # log writes to an append-only file");
+    println!("    aof_path = \"appendonly.aof\"");
+    println!("    aof_sync = \"everysec\"  # always, everysec, no");
+    println!("    auto_aof_rewrite_percentage = 100  # 0 disables automatic rewrites");
+    println!("    auto_aof_rewrite_min_size = 67108864");
+    println!("    rdb_path = \"dump.rdb\"  # loaded on startup unless AOF is enabled");
+    println!("    rdbchecksum = true   # CRC64 trailer on RDB snapshots");
+    println!("    save_on_shutdown = true  # final RDB snapshot on SIGTERM/SIGINT");
+    println!();
     println!("For more information, visit: https://github.com/Genuineh/AiKv");
 }
 
@@ -178,6 +437,7 @@ fn parse_args() -> CliArgs {
         config_path: None,
         host: None,
         port: None,
+        requirepass: None,
         show_help: false,
         show_version: false,
     };
@@ -226,6 +486,15 @@ fn parse_args() -> CliArgs {
                     std::process::exit(1);
                 }
             }
+            "--requirepass" => {
+                if i + 1 < args.len() {
+                    cli.requirepass = Some(args[i + 1].clone());
+                    i += 1;
+                } else {
+                    eprintln!("Error: {} requires a password argument", args[i]);
+                    std::process::exit(1);
+                }
+            }
             arg => {
                 // Legacy mode: first argument is the address
                 if cli.config_path.is_none() && cli.host.is_none() && cli.port.is_none() {
@@ -270,16 +539,7 @@ fn parse_args() -> CliArgs {
 }
 
 /// Load configuration from file and merge with CLI arguments
-#[cfg(feature = "cluster")]
-fn load_config(
-    cli: &CliArgs,
-) -> (
-    String,
-    u16,
-    StorageConfig,
-    LoggingConfig,
-    ClusterConfigSection,
-) {
+fn load_config(cli: &CliArgs) -> Config {
     let mut config = Config::default();
 
     // Load from config file if specified
@@ -300,44 +560,30 @@ fn load_config(
     }
 
     // CLI arguments override config file
-    let host = cli.host.clone().unwrap_or(config.server.host);
-    let port = cli.port.unwrap_or(config.server.port);
-
-    (host, port, config.storage, config.logging, config.cluster)
-}
-
-/// Load configuration from file and merge with CLI arguments
-#[cfg(not(feature = "cluster"))]
-fn load_config(cli: &CliArgs) -> (String, u16, StorageConfig, LoggingConfig) {
-    let mut config = Config::default();
-
-    // Load from config file if specified
-    if let Some(ref path) = cli.config_path {
-        match fs::read_to_string(path) {
-            Ok(content) => match toml::from_str::<Config>(&content) {
-                Ok(cfg) => config = cfg,
-                Err(e) => {
-                    eprintln!("Failed to parse config file '{}': {}", path, e);
-                    std::process::exit(1);
-                }
-            },
-            Err(e) => {
-                eprintln!("Failed to read config file '{}': {}", path, e);
-                std::process::exit(1);
-            }
-        }
+    if let Some(ref host) = cli.host {
+        config.server.host = host.clone();
+    }
+    if let Some(port) = cli.port {
+        config.server.port = port;
+    }
+    if let Some(ref password) = cli.requirepass {
+        config.server.requirepass = Some(password.clone());
     }
 
-    // CLI arguments override config file
-    let host = cli.host.clone().unwrap_or(config.server.host);
-    let port = cli.port.unwrap_or(config.server.port);
-
-    (host, port, config.storage, config.logging)
+    config
 }
 
 /// Create storage engine based on configuration
-fn create_storage_engine(storage_config: &StorageConfig) -> StorageEngine {
-    match storage_config.engine.to_lowercase().as_str() {
+fn create_storage_engine(
+    storage_config: &StorageConfig,
+    memory_config: &MemoryConfig,
+) -> StorageEngine {
+    if storage_config.databases == 0 {
+        eprintln!("Error: databases must be at least 1");
+        std::process::exit(1);
+    }
+
+    let engine = match storage_config.engine.to_lowercase().as_str() {
         "aidb" => {
             info!(
                 "Using AiDb storage engine with data directory: {}",
@@ -362,6 +608,104 @@ fn create_storage_engine(storage_config: &StorageConfig) -> StorageEngine {
             warn!("Unknown storage engine '{}', falling back to memory", other);
             StorageEngine::new_memory(storage_config.databases)
         }
+    };
+
+    let policy = EvictionPolicy::parse(&memory_config.maxmemory_policy).unwrap_or_else(|| {
+        warn!(
+            "Unknown maxmemory policy '{}', using 'noeviction'",
+            memory_config.maxmemory_policy
+        );
+        EvictionPolicy::NoEviction
+    });
+    if memory_config.maxmemory > 0 {
+        info!(
+            "Memory limit: {} bytes, policy: {}",
+            memory_config.maxmemory,
+            policy.as_str()
+        );
+    }
+    let limit = MemoryLimit {
+        maxmemory: memory_config.maxmemory as usize,
+        policy,
+    };
+    if let Err(e) = engine.set_memory_limit(limit) {
+        eprintln!("Failed to apply memory limit: {}", e);
+        std::process::exit(1);
+    }
+
+    engine
+}
+
+/// Load the RDB snapshot into `storage`, unless the AOF is the source of truth
+///
+/// A missing or unreadable snapshot is not fatal: the server starts empty.
+fn load_rdb(persistence: &PersistenceSection, storage: &StorageEngine) {
+    if persistence.appendonly || !std::path::Path::new(&persistence.rdb_path).exists() {
+        return;
+    }
+
+    match load_rdb_into(&persistence.rdb_path, storage) {
+        Ok(count) => info!("Loaded {} keys from RDB: {}", count, persistence.rdb_path),
+        Err(e) => warn!(
+            "Failed to load RDB '{}', starting empty: {}",
+            persistence.rdb_path, e
+        ),
+    }
+}
+
+/// Replay the AOF into `storage`, if AOF is enabled and the file exists
+fn replay_aof(persistence: &PersistenceSection, storage: &StorageEngine) {
+    if !persistence.appendonly || !std::path::Path::new(&persistence.aof_path).exists() {
+        return;
+    }
+
+    match CommandExecutor::new(storage.clone()).replay_aof(&persistence.aof_path) {
+        Ok(count) => info!(
+            "Loaded {} commands from AOF: {}",
+            count, persistence.aof_path
+        ),
+        Err(e) => {
+            eprintln!("Failed to load AOF '{}': {}", persistence.aof_path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Open the AOF for appending, if AOF is enabled
+///
+/// Nothing is appended until clients run writes, so the log can be replayed
+/// (see [`replay_aof`]) before or after opening it, as long as no writes run
+/// in between.
+fn open_aof(persistence: &PersistenceSection, storage: &StorageEngine) -> Option<AofWriter> {
+    if !persistence.appendonly {
+        return None;
+    }
+
+    let sync_policy = persistence
+        .aof_sync
+        .parse::<AofSyncPolicy>()
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+
+    match AofWriter::new(&persistence.aof_path, sync_policy) {
+        Ok(writer) => {
+            info!(
+                "AOF enabled: {} (sync: {})",
+                persistence.aof_path, persistence.aof_sync
+            );
+            writer.enable_auto_rewrite(
+                storage.clone(),
+                persistence.auto_aof_rewrite_percentage,
+                persistence.auto_aof_rewrite_min_size,
+            );
+            Some(writer)
+        }
+        Err(e) => {
+            eprintln!("Failed to open AOF '{}': {}", persistence.aof_path, e);
+            std::process::exit(1);
+        }
     }
 }
 
@@ -381,10 +725,9 @@ async fn main() {
     }
 
     // Load configuration
-    #[cfg(feature = "cluster")]
-    let (host, port, storage_config, logging_config, cluster_config) = load_config(&cli);
-    #[cfg(not(feature = "cluster"))]
-    let (host, port, storage_config, logging_config) = load_config(&cli);
+    let config = load_config(&cli);
+    let logging_config = &config.logging;
+    let storage_config = &config.storage;
 
     // Initialize logging with configured level
     let log_level = logging_config.level.to_lowercase();
@@ -404,7 +747,7 @@ async fn main() {
         .with_env_filter(filter)
         .init();
 
-    let addr = format!("{}:{}", host, port);
+    let addr = format!("{}:{}", config.server.host, config.server.port);
 
     // Print startup banner
     println!("{}", LOGO);
@@ -415,13 +758,74 @@ async fn main() {
     println!();
 
     // Create storage engine based on configuration
-    let storage = create_storage_engine(&storage_config);
+    let storage = create_storage_engine(storage_config, &config.memory);
+    let load_while_listening = match config.server.ready_when.as_str() {
+        "loaded" => false,
+        "listening" => true,
+        other => {
+            eprintln!(
+                "Invalid ready_when '{}': expected 'loaded' or 'listening'",
+                other
+            );
+            std::process::exit(1);
+        }
+    };
+    if !load_while_listening {
+        load_rdb(&config.persistence, &storage);
+        replay_aof(&config.persistence, &storage);
+    }
+    let aof = open_aof(&config.persistence, &storage);
 
     // Create and run server
-    let mut server = Server::new(addr, storage);
+    let mut server = Server::new(addr, storage.clone());
+    server.set_rdb_path(&config.persistence.rdb_path);
+    server.set_rdb_checksum(config.persistence.rdbchecksum);
+    server.set_save_on_shutdown(config.persistence.save_on_shutdown);
+    let slow_query_log = server.slow_query_log();
+    if config.slowlog.log_slower_than < 0 {
+        slow_query_log.disable();
+    } else {
+        slow_query_log.set_threshold_us(config.slowlog.log_slower_than as u64);
+    }
+    slow_query_log.set_max_len(config.slowlog.max_len);
+    if config.metrics.enabled {
+        server.set_metrics_addr(format!("{}:{}", config.server.host, config.metrics.port));
+    }
+    if let Some(aof) = aof {
+        server.set_aof_writer(aof);
+    }
+    if config.tls.enabled {
+        let mut tls_config = TlsConfig::new(&config.tls.cert_file, &config.tls.key_file);
+        if let Some(ca_file) = &config.tls.ca_file {
+            tls_config = tls_config.with_ca_file(ca_file);
+        }
+        let tls_addr = format!("{}:{}", config.server.host, config.tls.port);
+        if let Err(e) = server.set_tls(tls_addr, &tls_config) {
+            eprintln!("Failed to set up TLS: {}", e);
+            std::process::exit(1);
+        }
+    }
+    if config
+        .server
+        .requirepass
+        .as_deref()
+        .is_some_and(|p| !p.is_empty())
+    {
+        info!("Password authentication enabled (requirepass)");
+    }
+    server.set_requirepass(config.server.requirepass.clone());
+    server.set_idle_timeout(config.server.timeout);
+    if let Some(tcp_keepalive) = config.server.tcp_keepalive {
+        server.set_tcp_keepalive(tcp_keepalive);
+    }
+    if let Some(maxclients) = config.server.maxclients {
+        server.set_maxclients(maxclients);
+    }
 
     // Initialize cluster if enabled
     #[cfg(feature = "cluster")]
+    let cluster_config = &config.cluster;
+    #[cfg(feature = "cluster")]
     if cluster_config.enabled {
         info!("Cluster mode enabled in configuration");
         if let Err(e) = server
@@ -438,8 +842,54 @@ async fn main() {
         }
     }
 
+    // Clients connecting before the dataset is loaded get -LOADING
+    if load_while_listening {
+        let loading = server.loading_flag();
+        loading.store(true, Ordering::SeqCst);
+        let persistence = config.persistence;
+        tokio::task::spawn_blocking(move || {
+            load_rdb(&persistence, &storage);
+            replay_aof(&persistence, &storage);
+            loading.store(false, Ordering::SeqCst);
+            info!("Dataset loaded, accepting commands");
+        });
+    }
+
+    tokio::spawn(shutdown_on_signal(server.shutdown_signal()));
     if let Err(e) = server.run().await {
         eprintln!("Server error: {}", e);
         std::process::exit(1);
     }
 }
+
+/// Shut the server down gracefully on SIGINT (Ctrl-C) or SIGTERM (`docker stop`)
+async fn shutdown_on_signal(shutdown: ShutdownSignal) {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Failed to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => info!("Received SIGINT, shutting down"),
+        _ = terminate => info!("Received SIGTERM, shutting down"),
+    }
+    shutdown.trigger();
+}
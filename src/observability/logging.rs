@@ -18,6 +18,24 @@ const DEFAULT_SLOWLOG_MAX_LEN: usize = 128;
 /// Default slow query threshold in microseconds (10ms)
 const DEFAULT_SLOWLOG_THRESHOLD_US: u64 = 10_000;
 
+/// Maximum number of arguments (including the command) kept per slow log entry
+pub const SLOWLOG_ENTRY_MAX_ARGC: usize = 32;
+
+/// Maximum number of bytes kept per slow log argument
+pub const SLOWLOG_ENTRY_MAX_STRING: usize = 128;
+
+/// Shorten an argument to [`SLOWLOG_ENTRY_MAX_STRING`] bytes, noting how much was cut
+fn truncate_slowlog_arg(arg: &str) -> String {
+    if arg.len() <= SLOWLOG_ENTRY_MAX_STRING {
+        return arg.to_string();
+    }
+    let mut end = SLOWLOG_ENTRY_MAX_STRING;
+    while !arg.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}... ({} more bytes)", &arg[..end], arg.len() - end)
+}
+
 /// Log format type
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum LogFormat {
@@ -190,7 +208,21 @@ impl SlowQueryLog {
         }
     }
 
+    /// Stop logging slow queries (a negative `slowlog-log-slower-than`)
+    pub fn disable(&self) {
+        self.set_threshold_us(u64::MAX);
+    }
+
+    /// Whether a command taking `duration` would be logged
+    pub fn is_slow(&self, duration: Duration) -> bool {
+        let threshold = self.threshold_us.load(Ordering::Relaxed);
+        threshold != u64::MAX && duration.as_micros() as u64 >= threshold
+    }
+
     /// Record a slow query if it exceeds the threshold
+    ///
+    /// Like Redis, only the first [`SLOWLOG_ENTRY_MAX_ARGC`] arguments and the
+    /// first [`SLOWLOG_ENTRY_MAX_STRING`] bytes of each are kept.
     pub fn record(
         &self,
         command: &str,
@@ -198,10 +230,27 @@ impl SlowQueryLog {
         duration: Duration,
         client_addr: Option<String>,
     ) {
-        let duration_us = duration.as_micros() as u64;
-        if duration_us < self.threshold_us.load(Ordering::Relaxed) {
+        if !self.is_slow(duration) {
             return;
         }
+        let duration_us = duration.as_micros() as u64;
+
+        // The command name counts towards the argument limit, and an elided
+        // tail takes the last slot
+        let max_args = SLOWLOG_ENTRY_MAX_ARGC - 1;
+        let args: Vec<String> = if args.len() > max_args {
+            let kept = max_args - 1;
+            args[..kept]
+                .iter()
+                .map(|arg| truncate_slowlog_arg(arg))
+                .chain(std::iter::once(format!(
+                    "... ({} more arguments)",
+                    args.len() - kept
+                )))
+                .collect()
+        } else {
+            args.iter().map(|arg| truncate_slowlog_arg(arg)).collect()
+        };
 
         let entry = SlowQueryEntry {
             id: self.next_id.fetch_add(1, Ordering::SeqCst),
@@ -211,7 +260,7 @@ impl SlowQueryLog {
                 .as_secs(),
             duration_us,
             command: command.to_string(),
-            args: args.to_vec(),
+            args,
             client_addr,
         };
 
@@ -412,4 +461,30 @@ mod tests {
         manager.set_level(Level::DEBUG);
         assert_eq!(manager.level(), Level::DEBUG);
     }
+
+    #[test]
+    fn test_slow_query_log_truncates_args() {
+        let log = SlowQueryLog::with_settings(10, 0);
+        let args: Vec<String> = (0..40).map(|i| i.to_string()).collect();
+        log.record("DEL", &args, Duration::from_millis(1), None);
+        log.record(
+            "SET",
+            &["k".to_string(), "x".repeat(200)],
+            Duration::from_millis(1),
+            None,
+        );
+
+        let entries = log.get(10);
+        assert_eq!(
+            entries[0].args[1],
+            format!("{}... (72 more bytes)", "x".repeat(128))
+        );
+        assert_eq!(entries[1].args.len(), 31);
+        assert_eq!(entries[1].args[29], "29");
+        assert_eq!(entries[1].args[30], "... (10 more arguments)");
+
+        log.disable();
+        log.record("GET", &[], Duration::from_secs(10), None);
+        assert_eq!(log.len(), 2);
+    }
 }
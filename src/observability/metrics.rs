@@ -81,6 +81,103 @@ impl Gauge {
     }
 }
 
+/// Number of latency histogram buckets; bucket `i` counts durations of at
+/// most `2^i` microseconds (the last one up to ~16.8s), plus an overflow bucket
+pub const LATENCY_BUCKETS: usize = 25;
+
+/// Latency histogram with power-of-two microsecond buckets
+#[derive(Debug)]
+pub struct LatencyHistogram {
+    /// Non-cumulative counts; the extra last slot holds durations above every bound
+    buckets: [AtomicU64; LATENCY_BUCKETS + 1],
+    count: AtomicU64,
+    /// Kept in nanoseconds so sub-microsecond commands still add up
+    sum_ns: AtomicU64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyHistogram {
+    /// Create an empty histogram
+    pub fn new() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            count: AtomicU64::new(0),
+            sum_ns: AtomicU64::new(0),
+        }
+    }
+
+    /// Upper bound of bucket `index` in microseconds
+    pub fn bucket_bound_us(index: usize) -> u64 {
+        1 << index
+    }
+
+    /// Record one observation
+    pub fn record(&self, duration: Duration) {
+        let us = duration.as_micros().min(u64::MAX as u128) as u64;
+        // Smallest i with us <= 2^i
+        let index = if us <= 1 {
+            0
+        } else {
+            (64 - (us - 1).leading_zeros()) as usize
+        };
+        self.buckets[index.min(LATENCY_BUCKETS)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_ns.fetch_add(
+            duration.as_nanos().min(u64::MAX as u128) as u64,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Number of observations
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Sum of all observations in microseconds
+    pub fn sum_us(&self) -> f64 {
+        self.sum_ns.load(Ordering::Relaxed) as f64 / 1000.0
+    }
+
+    /// Average observation in microseconds, or 0 if there are none
+    pub fn avg_us(&self) -> f64 {
+        match self.count() {
+            0 => 0.0,
+            count => self.sum_us() / count as f64,
+        }
+    }
+
+    /// Cumulative count for each bucket bound, as Prometheus `le` buckets
+    pub fn cumulative_buckets(&self) -> Vec<(u64, u64)> {
+        let mut total = 0;
+        (0..LATENCY_BUCKETS)
+            .map(|i| {
+                total += self.buckets[i].load(Ordering::Relaxed);
+                (Self::bucket_bound_us(i), total)
+            })
+            .collect()
+    }
+
+    /// Smallest bucket bound (in microseconds) covering `percentile` (0-100)
+    /// of the observations, or 0 if there are none
+    pub fn percentile_us(&self, percentile: f64) -> u64 {
+        let count = self.count();
+        if count == 0 {
+            return 0;
+        }
+        let target = ((percentile / 100.0) * count as f64).ceil().max(1.0) as u64;
+        self.cumulative_buckets()
+            .into_iter()
+            .find(|&(_, cumulative)| cumulative >= target)
+            .map(|(bound, _)| bound)
+            .unwrap_or_else(|| Self::bucket_bound_us(LATENCY_BUCKETS))
+    }
+}
+
 /// Command execution metrics
 #[derive(Debug)]
 pub struct CommandMetrics {
@@ -92,6 +189,8 @@ pub struct CommandMetrics {
     pub total_errors: Counter,
     /// Errors per command type
     pub errors_by_type: RwLock<HashMap<String, Counter>>,
+    /// Latency distribution per command type
+    pub latency_by_type: RwLock<HashMap<String, LatencyHistogram>>,
     /// Total command execution time in microseconds
     pub total_duration_us: AtomicU64,
     /// Commands per second (calculated)
@@ -116,6 +215,7 @@ impl CommandMetrics {
             commands_by_type: RwLock::new(HashMap::new()),
             total_errors: Counter::new(),
             errors_by_type: RwLock::new(HashMap::new()),
+            latency_by_type: RwLock::new(HashMap::new()),
             total_duration_us: AtomicU64::new(0),
             ops_per_sec: RwLock::new(0.0),
             last_ops_calc: RwLock::new(Instant::now()),
@@ -130,6 +230,12 @@ impl CommandMetrics {
             .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
 
         let command_upper = command.to_uppercase();
+        if let Ok(mut latencies) = self.latency_by_type.write() {
+            latencies
+                .entry(command_upper.clone())
+                .or_insert_with(LatencyHistogram::new)
+                .record(duration);
+        }
         if let Ok(mut commands) = self.commands_by_type.write() {
             commands
                 .entry(command_upper)
@@ -170,6 +276,15 @@ impl CommandMetrics {
         self.total_duration_us.load(Ordering::Relaxed) as f64 / total as f64
     }
 
+    /// Get errors by type
+    pub fn errors_by_type(&self) -> HashMap<String, u64> {
+        if let Ok(errors) = self.errors_by_type.read() {
+            errors.iter().map(|(k, v)| (k.clone(), v.get())).collect()
+        } else {
+            HashMap::new()
+        }
+    }
+
     /// Get commands by type
     pub fn commands_by_type(&self) -> HashMap<String, u64> {
         if let Ok(commands) = self.commands_by_type.read() {
@@ -211,6 +326,9 @@ impl CommandMetrics {
         if let Ok(mut errors) = self.errors_by_type.write() {
             errors.clear();
         }
+        if let Ok(mut latencies) = self.latency_by_type.write() {
+            latencies.clear();
+        }
     }
 }
 
@@ -557,6 +675,39 @@ impl Metrics {
             ));
         }
 
+        // Latency per command type
+        output.push_str(
+            "# HELP aikv_command_duration_seconds Command execution time by command type\n",
+        );
+        output.push_str("# TYPE aikv_command_duration_seconds histogram\n");
+        if let Ok(latencies) = self.commands.latency_by_type.read() {
+            for (cmd, histogram) in latencies.iter() {
+                for (bound_us, cumulative) in histogram.cumulative_buckets() {
+                    output.push_str(&format!(
+                        "aikv_command_duration_seconds_bucket{{command=\"{}\",le=\"{}\"}} {}\n",
+                        cmd,
+                        bound_us as f64 / 1_000_000.0,
+                        cumulative
+                    ));
+                }
+                output.push_str(&format!(
+                    "aikv_command_duration_seconds_bucket{{command=\"{}\",le=\"+Inf\"}} {}\n",
+                    cmd,
+                    histogram.count()
+                ));
+                output.push_str(&format!(
+                    "aikv_command_duration_seconds_sum{{command=\"{}\"}} {}\n",
+                    cmd,
+                    histogram.sum_us() / 1_000_000.0
+                ));
+                output.push_str(&format!(
+                    "aikv_command_duration_seconds_count{{command=\"{}\"}} {}\n",
+                    cmd,
+                    histogram.count()
+                ));
+            }
+        }
+
         output
     }
 
@@ -714,4 +865,24 @@ mod tests {
         assert!(output.contains("aikv_commands_total 1"));
         assert!(output.contains("aikv_connected_clients 1"));
     }
+
+    #[test]
+    fn test_latency_histogram() {
+        let histogram = LatencyHistogram::new();
+        histogram.record(Duration::from_micros(1));
+        histogram.record(Duration::from_micros(3));
+        histogram.record(Duration::from_micros(4));
+        histogram.record(Duration::from_micros(1000));
+
+        assert_eq!(histogram.count(), 4);
+        assert_eq!(histogram.sum_us(), 1008.0);
+        assert_eq!(histogram.avg_us(), 252.0);
+        let buckets = histogram.cumulative_buckets();
+        assert_eq!(buckets[0], (1, 1));
+        assert_eq!(buckets[1], (2, 1));
+        assert_eq!(buckets[2], (4, 3));
+        assert_eq!(buckets[10], (1024, 4));
+        assert_eq!(histogram.percentile_us(50.0), 4);
+        assert_eq!(histogram.percentile_us(99.0), 1024);
+    }
 }
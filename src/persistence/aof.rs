@@ -1,101 +1,576 @@
 use crate::error::{AikvError, Result};
 use crate::persistence::config::AofSyncPolicy;
+use crate::storage::{StorageEngine, StoredValue, ValueType};
+use bytes::Bytes;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 use std::io::{BufRead, BufReader, BufWriter, Write};
-use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard};
+use std::thread;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Interval between fsyncs under [`AofSyncPolicy::EverySecond`]
+const FSYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Interval between checks of the auto-rewrite thresholds
+const AUTO_REWRITE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Maximum number of elements per command when a rewrite emits a collection
+const REWRITE_ITEMS_PER_COMMAND: usize = 64;
+
+/// Number of locks writes are ordered by; databases share them modulo this
+const ORDER_STRIPES: usize = 16;
+
+/// Open AOF file plus the database the log last switched to
+struct AofFile {
+    writer: BufWriter<File>,
+    /// A second handle on the log, so it can be fsynced without holding the lock
+    handle: Arc<File>,
+    /// Database selected by the last SELECT written to the log
+    selected_db: Option<usize>,
+    /// Current size of the log in bytes
+    size: u64,
+    /// Size of the log after the last rewrite (or at open), for auto-rewrite
+    base_size: u64,
+    /// Entries logged while a rewrite is running, appended to the new log
+    /// before it replaces the old one
+    rewrite_buffer: Option<Vec<u8>>,
+    /// When logged entries are fsynced; CONFIG SET appendfsync changes it
+    sync_policy: AofSyncPolicy,
+}
+
+impl AofFile {
+    /// Write one command to the log, and to the rewrite buffer if a rewrite
+    /// is running
+    fn write_command<'a>(&mut self, args: impl IntoIterator<Item = &'a [u8]>) -> Result<()> {
+        let buf = encode_command(args);
+        self.writer
+            .write_all(&buf)
+            .map_err(|e| AikvError::Persistence(format!("Failed to write command: {}", e)))?;
+        self.size += buf.len() as u64;
+        if let Some(rewrite_buffer) = &mut self.rewrite_buffer {
+            rewrite_buffer.extend_from_slice(&buf);
+        }
+        Ok(())
+    }
+}
 
 /// AOF writer for logging commands
 pub struct AofWriter {
-    writer: Arc<Mutex<BufWriter<File>>>,
-    sync_policy: AofSyncPolicy,
+    path: Arc<PathBuf>,
+    file: Arc<Mutex<AofFile>>,
+    /// Held shared by each write command from execution through logging, and
+    /// exclusively while a rewrite snapshots the dataset
+    gate: Arc<RwLock<()>>,
+    /// One of these is held by each write command from execution through
+    /// logging, picked by the database it writes to, so writes to a
+    /// database are logged in the order they were applied
+    order: Arc<Vec<Mutex<()>>>,
+}
+
+/// What [`AofWriter::write_guard`] returns
+///
+/// Dropping it releases its locks, then fsyncs the log if an entry logged
+/// through it needs that.
+pub struct AofWriteGuard<'a> {
+    writer: &'a AofWriter,
+    gate: Option<RwLockReadGuard<'a, ()>>,
+    order: Vec<MutexGuard<'a, ()>>,
+    /// The log file, once an entry logged through the guard is waiting for an fsync
+    unsynced: RefCell<Option<Arc<File>>>,
+}
+
+impl AofWriteGuard<'_> {
+    /// Log a write like [`AofWriter::append`], but leave the fsync the policy
+    /// calls for until the guard's locks are released
+    pub fn append(&self, db: usize, command: &str, args: &[Bytes]) -> Result<()> {
+        if let Some(handle) = self.writer.write_entry(db, command, args)? {
+            *self.unsynced.borrow_mut() = Some(handle);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for AofWriteGuard<'_> {
+    fn drop(&mut self) {
+        // Writes waiting for the locks don't have to wait for the disk too
+        self.order.clear();
+        self.gate = None;
+        if let Some(handle) = self.unsynced.get_mut().take() {
+            if let Err(e) = sync_file(&handle) {
+                error!("Failed to fsync AOF file: {}", e);
+            }
+        }
+    }
 }
 
 impl AofWriter {
     /// Create a new AOF writer
+    ///
+    /// A background thread fsyncs the file once a second while the policy is
+    /// [`AofSyncPolicy::EverySecond`], until the last clone of the writer is
+    /// dropped.
     pub fn new<P: AsRef<Path>>(path: P, sync_policy: AofSyncPolicy) -> Result<Self> {
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(path)
-            .map_err(|e| AikvError::Persistence(format!("Failed to open AOF file: {}", e)))?;
+        let path = path.as_ref().to_path_buf();
+        let file = open_append(&path)?;
+        let size = file
+            .metadata()
+            .map_err(|e| AikvError::Persistence(format!("Failed to stat AOF file: {}", e)))?
+            .len();
+
+        let handle = Arc::new(
+            file.try_clone()
+                .map_err(|e| AikvError::Persistence(format!("Failed to open AOF file: {}", e)))?,
+        );
+        let file = Arc::new(Mutex::new(AofFile {
+            writer: BufWriter::new(file),
+            handle,
+            selected_db: None,
+            size,
+            base_size: size,
+            rewrite_buffer: None,
+            sync_policy,
+        }));
+
+        let weak = Arc::downgrade(&file);
+        thread::spawn(move || loop {
+            thread::sleep(FSYNC_INTERVAL);
+            let Some(file) = weak.upgrade() else {
+                break;
+            };
+            let handle = match file.lock() {
+                Ok(file) if file.sync_policy == AofSyncPolicy::EverySecond => {
+                    Arc::clone(&file.handle)
+                }
+                Ok(_) => continue,
+                Err(_) => break,
+            };
+            drop(file);
+            if let Err(e) = handle.sync_data() {
+                warn!("Failed to fsync AOF file: {}", e);
+            }
+        });
 
         Ok(Self {
-            writer: Arc::new(Mutex::new(BufWriter::new(file))),
-            sync_policy,
+            path: Arc::new(path),
+            file,
+            gate: Arc::new(RwLock::new(())),
+            order: Arc::new((0..ORDER_STRIPES).map(|_| Mutex::new(())).collect()),
         })
     }
 
+    /// Current fsync policy
+    pub fn sync_policy(&self) -> AofSyncPolicy {
+        self.lock()
+            .map(|file| file.sync_policy)
+            .unwrap_or(AofSyncPolicy::EverySecond)
+    }
+
+    /// Change the fsync policy for entries logged from now on
+    pub fn set_sync_policy(&self, sync_policy: AofSyncPolicy) -> Result<()> {
+        let mut file = self.lock()?;
+        file.sync_policy = sync_policy;
+        Ok(())
+    }
+
     /// Log a command in RESP format
     pub fn log_command(&self, command: &[String]) -> Result<()> {
-        let mut writer = self
-            .writer
-            .lock()
-            .map_err(|e| AikvError::Persistence(format!("Failed to lock writer: {}", e)))?;
+        let unsynced = {
+            let mut file = self.lock()?;
+            file.write_command(command.iter().map(|arg| arg.as_bytes()))?;
+            self.flush_entries(&mut file)?
+        };
+        match unsynced {
+            Some(handle) => sync_file(&handle),
+            None => Ok(()),
+        }
+    }
 
-        // Write command in RESP array format
-        write!(writer, "*{}\r\n", command.len()).map_err(|e| {
-            AikvError::Persistence(format!("Failed to write command length: {}", e))
-        })?;
+    /// Log a write command executed against database `db`
+    ///
+    /// A SELECT is logged first whenever `db` differs from the database of the
+    /// previous entry, so replaying the log applies every command to the right
+    /// database.
+    ///
+    /// Write commands log through their [`AofWriteGuard`] instead, so the
+    /// fsync happens after the guard's locks are released.
+    pub fn append(&self, db: usize, command: &str, args: &[Bytes]) -> Result<()> {
+        match self.write_entry(db, command, args)? {
+            Some(handle) => sync_file(&handle),
+            None => Ok(()),
+        }
+    }
 
-        for arg in command {
-            let arg_bytes = arg.as_bytes();
-            write!(writer, "${}\r\n", arg_bytes.len()).map_err(|e| {
-                AikvError::Persistence(format!("Failed to write arg length: {}", e))
-            })?;
-            writer
-                .write_all(arg_bytes)
-                .map_err(|e| AikvError::Persistence(format!("Failed to write arg: {}", e)))?;
-            writer
-                .write_all(b"\r\n")
-                .map_err(|e| AikvError::Persistence(format!("Failed to write CRLF: {}", e)))?;
+    /// Write an entry and hand it to the OS, returning the file to fsync if
+    /// the policy calls for it
+    fn write_entry(&self, db: usize, command: &str, args: &[Bytes]) -> Result<Option<Arc<File>>> {
+        let mut file = self.lock()?;
+        if file.selected_db != Some(db) {
+            let db = db.to_string();
+            file.write_command([&b"SELECT"[..], db.as_bytes()])?;
+        }
+        file.selected_db = Some(db);
+
+        file.write_command(
+            std::iter::once(command.as_bytes()).chain(args.iter().map(|arg| arg.as_ref())),
+        )?;
+        self.flush_entries(&mut file)
+    }
+
+    /// Flush the writer
+    pub fn flush(&self) -> Result<()> {
+        let mut file = self.lock()?;
+
+        file.writer
+            .flush()
+            .map_err(|e| AikvError::Persistence(format!("Failed to flush: {}", e)))?;
+        Ok(())
+    }
+
+    /// Flush the writer and fsync the file, whatever the sync policy
+    pub fn fsync(&self) -> Result<()> {
+        let mut file = self.lock()?;
+
+        file.writer
+            .flush()
+            .map_err(|e| AikvError::Persistence(format!("Failed to flush: {}", e)))?;
+        file.writer
+            .get_ref()
+            .sync_data()
+            .map_err(|e| AikvError::Persistence(format!("Failed to fsync: {}", e)))?;
+        Ok(())
+    }
+
+    /// Keep a rewrite from snapshotting the dataset, and other writes to
+    /// database `db` from running, until the guard is dropped
+    ///
+    /// Write commands hold this from before they change the dataset until
+    /// after they are logged, so every write lands in exactly one of the
+    /// rewrite snapshot and the rewrite buffer, and two writes to the same
+    /// key can't be logged in the opposite order to the one they were applied in.
+    /// A `db` of `None` is for writes that may touch any database, and keeps
+    /// out writes to all of them.
+    pub fn write_guard(&self, db: Option<usize>) -> AofWriteGuard<'_> {
+        fn lock(stripe: &Mutex<()>) -> MutexGuard<'_, ()> {
+            stripe.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        let gate = self.gate.read().unwrap_or_else(PoisonError::into_inner);
+        // Taking every stripe in ascending order can't deadlock with writes
+        // holding one of them
+        let order = match db {
+            Some(db) => vec![lock(&self.order[db % ORDER_STRIPES])],
+            None => self.order.iter().map(lock).collect(),
+        };
+        AofWriteGuard {
+            writer: self,
+            gate: Some(gate),
+            order,
+            unsynced: RefCell::new(None),
+        }
+    }
+
+    /// Whether a rewrite is currently running
+    pub fn rewrite_in_progress(&self) -> bool {
+        self.lock()
+            .map(|file| file.rewrite_buffer.is_some())
+            .unwrap_or(false)
+    }
+
+    /// Rewrite the log from the current contents of `storage`, returning the
+    /// size of the new log
+    ///
+    /// The new log holds one command per live key (batched for large
+    /// collections) and replaces the old one atomically.
+    pub fn rewrite(&self, storage: &StorageEngine) -> Result<u64> {
+        let databases = self.begin_rewrite(storage)?;
+        self.finish_rewrite(&databases)
+    }
+
+    /// Like [`rewrite`](Self::rewrite), but writes the new log on a background
+    /// thread once the dataset has been snapshotted
+    ///
+    /// Writes logged while the rewrite runs are buffered and appended to the
+    /// new log before it is swapped in.
+    pub fn start_rewrite(&self, storage: &StorageEngine) -> Result<()> {
+        let databases = self.begin_rewrite(storage)?;
+        let writer = self.clone();
+        thread::spawn(move || match writer.finish_rewrite(&databases) {
+            Ok(size) => info!("Background AOF rewrite finished ({} bytes)", size),
+            Err(e) => error!("Background AOF rewrite failed: {}", e),
+        });
+        Ok(())
+    }
+
+    /// Rewrite the log in the background whenever it has grown by
+    /// `percentage` percent since the last rewrite and is at least `min_size`
+    /// bytes (Redis `auto-aof-rewrite-percentage` / `auto-aof-rewrite-min-size`)
+    ///
+    /// A `percentage` of 0 disables automatic rewrites.
+    pub fn enable_auto_rewrite(&self, storage: StorageEngine, percentage: u64, min_size: u64) {
+        if percentage == 0 {
+            return;
         }
 
-        // Sync according to policy
-        match self.sync_policy {
-            AofSyncPolicy::Always => {
-                writer
-                    .flush()
-                    .map_err(|e| AikvError::Persistence(format!("Failed to flush: {}", e)))?;
-                writer
-                    .get_ref()
-                    .sync_all()
-                    .map_err(|e| AikvError::Persistence(format!("Failed to sync: {}", e)))?;
+        let weak = Arc::downgrade(&self.file);
+        let path = Arc::clone(&self.path);
+        let gate = Arc::clone(&self.gate);
+        let order = Arc::clone(&self.order);
+        thread::spawn(move || loop {
+            thread::sleep(AUTO_REWRITE_CHECK_INTERVAL);
+            let Some(file) = weak.upgrade() else {
+                break;
+            };
+            let writer = AofWriter {
+                path: Arc::clone(&path),
+                file,
+                gate: Arc::clone(&gate),
+                order: Arc::clone(&order),
+            };
+
+            let due = match writer.lock() {
+                Ok(file) => {
+                    let base = file.base_size.max(1);
+                    file.rewrite_buffer.is_none()
+                        && file.size >= min_size
+                        && file.size.saturating_mul(100) / base >= 100 + percentage
+                }
+                Err(_) => break,
+            };
+            if due {
+                info!("Starting automatic AOF rewrite");
+                if let Err(e) = writer.start_rewrite(&storage) {
+                    warn!("Failed to start automatic AOF rewrite: {}", e);
+                }
             }
-            AofSyncPolicy::EverySecond | AofSyncPolicy::No => {
-                // For EverySecond, we would need a background thread to sync periodically
-                // For No, we let the OS handle it
-                // For now, just flush the buffer
-                writer
-                    .flush()
-                    .map_err(|e| AikvError::Persistence(format!("Failed to flush: {}", e)))?;
+        });
+    }
+
+    /// Start buffering logged writes and snapshot the dataset
+    fn begin_rewrite(&self, storage: &StorageEngine) -> Result<Vec<HashMap<String, StoredValue>>> {
+        let _gate = self.gate.write().unwrap_or_else(PoisonError::into_inner);
+        {
+            let mut file = self.lock()?;
+            if file.rewrite_buffer.is_some() {
+                return Err(AikvError::Persistence(
+                    "Background append only file rewriting already in progress".to_string(),
+                ));
             }
+            file.rewrite_buffer = Some(Vec::new());
+            // Make the first buffered entry start with a SELECT
+            file.selected_db = None;
         }
 
-        Ok(())
+        storage.export_all_databases().inspect_err(|_| {
+            self.abort_rewrite();
+        })
     }
 
-    /// Flush the writer
-    pub fn flush(&self) -> Result<()> {
-        let mut writer = self
-            .writer
+    /// Write the snapshot plus the buffered writes to a new log and swap it in
+    fn finish_rewrite(&self, databases: &[HashMap<String, StoredValue>]) -> Result<u64> {
+        let result = self.write_rewritten_log(databases);
+        if result.is_err() {
+            self.abort_rewrite();
+        }
+        result
+    }
+
+    fn write_rewritten_log(&self, databases: &[HashMap<String, StoredValue>]) -> Result<u64> {
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let temp_file = tempfile::NamedTempFile::new_in(dir)
+            .map_err(|e| AikvError::Persistence(format!("Failed to create temp file: {}", e)))?;
+        let mut out = BufWriter::new(temp_file);
+
+        for (db, values) in databases.iter().enumerate() {
+            if values.is_empty() {
+                continue;
+            }
+            let db = db.to_string();
+            write_command(&mut out, [&b"SELECT"[..], db.as_bytes()])?;
+            for (key, value) in values {
+                if !value.is_expired() {
+                    write_value(&mut out, key, value)?;
+                }
+            }
+        }
+
+        // Swap under the lock so nothing is logged between draining the
+        // buffer and reopening the new file
+        let mut file = self.lock()?;
+        let buffer = file.rewrite_buffer.take().unwrap_or_default();
+        out.write_all(&buffer)
+            .map_err(|e| AikvError::Persistence(format!("Failed to write command: {}", e)))?;
+        let temp_file = out
+            .into_inner()
+            .map_err(|e| AikvError::Persistence(format!("Failed to flush: {}", e)))?;
+        temp_file
+            .as_file()
+            .sync_all()
+            .map_err(|e| AikvError::Persistence(format!("Failed to sync: {}", e)))?;
+        temp_file
+            .persist(self.path.as_path())
+            .map_err(|e| AikvError::Persistence(format!("Failed to rename AOF file: {}", e)))?;
+
+        if let Err(e) = file.writer.flush() {
+            warn!("Failed to flush replaced AOF file: {}", e);
+        }
+        let new_file = open_append(&self.path)?;
+        let size = new_file
+            .metadata()
+            .map_err(|e| AikvError::Persistence(format!("Failed to stat AOF file: {}", e)))?
+            .len();
+        file.handle = Arc::new(
+            new_file
+                .try_clone()
+                .map_err(|e| AikvError::Persistence(format!("Failed to open AOF file: {}", e)))?,
+        );
+        file.writer = BufWriter::new(new_file);
+        file.size = size;
+        file.base_size = size;
+        Ok(size)
+    }
+
+    /// Stop buffering writes for a rewrite that failed
+    fn abort_rewrite(&self) {
+        if let Ok(mut file) = self.lock() {
+            file.rewrite_buffer = None;
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, AofFile>> {
+        self.file
             .lock()
-            .map_err(|e| AikvError::Persistence(format!("Failed to lock writer: {}", e)))?;
+            .map_err(|e| AikvError::Persistence(format!("Failed to lock writer: {}", e)))
+    }
 
-        writer
+    /// Flush buffered entries, returning the file to fsync if the policy is
+    /// Always
+    ///
+    /// Every policy hands the data to the OS; only Always waits for the disk,
+    /// which the caller does once it has released the lock.
+    fn flush_entries(&self, file: &mut AofFile) -> Result<Option<Arc<File>>> {
+        file.writer
             .flush()
             .map_err(|e| AikvError::Persistence(format!("Failed to flush: {}", e)))?;
-        Ok(())
+        Ok((file.sync_policy == AofSyncPolicy::Always).then(|| Arc::clone(&file.handle)))
     }
 }
 
+/// Wait for entries handed to the OS to reach the disk
+fn sync_file(file: &File) -> Result<()> {
+    file.sync_all()
+        .map_err(|e| AikvError::Persistence(format!("Failed to sync: {}", e)))
+}
+
 impl Clone for AofWriter {
     fn clone(&self) -> Self {
         Self {
-            writer: Arc::clone(&self.writer),
-            sync_policy: self.sync_policy,
+            path: Arc::clone(&self.path),
+            file: Arc::clone(&self.file),
+            gate: Arc::clone(&self.gate),
+            order: Arc::clone(&self.order),
+        }
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| AikvError::Persistence(format!("Failed to open AOF file: {}", e)))
+}
+
+/// Encode one command as a RESP array of bulk strings
+fn encode_command<'a>(args: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+    let args: Vec<&[u8]> = args.into_iter().collect();
+    let mut buf = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        buf.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        buf.extend_from_slice(arg);
+        buf.extend_from_slice(b"\r\n");
+    }
+    buf
+}
+
+/// Write one command as a RESP array of bulk strings
+fn write_command<'a>(
+    writer: &mut impl Write,
+    args: impl IntoIterator<Item = &'a [u8]>,
+) -> Result<()> {
+    // One write per command keeps entries whole in the buffer
+    writer
+        .write_all(&encode_command(args))
+        .map_err(|e| AikvError::Persistence(format!("Failed to write command: {}", e)))
+}
+
+/// Write the commands that recreate `key` with its current value and expiry
+fn write_value(writer: &mut impl Write, key: &str, value: &StoredValue) -> Result<()> {
+    let key = key.as_bytes();
+    // Emit `command key item...` in batches of REWRITE_ITEMS_PER_COMMAND items
+    let mut write_batched = |command: &[u8], items: Vec<Vec<u8>>, per_item: usize| {
+        for chunk in items.chunks(REWRITE_ITEMS_PER_COMMAND * per_item) {
+            write_command(
+                writer,
+                [command, key]
+                    .into_iter()
+                    .chain(chunk.iter().map(|item| item.as_slice())),
+            )?;
+        }
+        Ok::<_, AikvError>(())
+    };
+
+    match &value.value {
+        ValueType::String(data) => write_batched(b"SET", vec![data.to_vec()], 1)?,
+        ValueType::List(items) => write_batched(
+            b"RPUSH",
+            items.iter().map(|item| item.to_vec()).collect(),
+            1,
+        )?,
+        ValueType::Hash(fields) => write_batched(
+            b"HSET",
+            fields
+                .iter()
+                .flat_map(|(field, value)| [field.as_bytes().to_vec(), value.to_vec()])
+                .collect(),
+            2,
+        )?,
+        ValueType::Set(members) => write_batched(b"SADD", members.iter().cloned().collect(), 1)?,
+        ValueType::ZSet(members) => write_batched(
+            b"ZADD",
+            members
+                .iter()
+                .flat_map(|(member, score)| [score.to_string().into_bytes(), member.clone()])
+                .collect(),
+            2,
+        )?,
+        // One XADD per entry, each with its own ID
+        ValueType::Stream(entries) => {
+            for ((ms, seq), fields) in entries {
+                let items: Vec<Vec<u8>> = std::iter::once(format!("{}-{}", ms, seq).into_bytes())
+                    .chain(
+                        fields
+                            .iter()
+                            .flat_map(|(field, value)| [field.to_vec(), value.to_vec()]),
+                    )
+                    .collect();
+                let per_command = items.len();
+                write_batched(b"XADD", items, per_command)?;
+            }
         }
     }
+
+    if let Some(expires_at) = value.expires_at() {
+        let expires_at = expires_at.to_string();
+        write_command(writer, [&b"PEXPIREAT"[..], key, expires_at.as_bytes()])?;
+    }
+    Ok(())
 }
 
 /// AOF reader for replaying commands
@@ -112,6 +587,21 @@ impl<R: BufRead> AofReader<R> {
 
     /// Read next command from AOF
     pub fn read_command(&mut self) -> Result<Option<Vec<String>>> {
+        let Some(args) = self.read_args()? else {
+            return Ok(None);
+        };
+
+        args.into_iter()
+            .map(|arg| {
+                String::from_utf8(arg.to_vec())
+                    .map_err(|e| AikvError::Persistence(format!("Invalid UTF-8: {}", e)))
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(Some)
+    }
+
+    /// Read next command from AOF as raw (binary-safe) arguments
+    pub fn read_args(&mut self) -> Result<Option<Vec<Bytes>>> {
         let mut line = String::new();
 
         // Read array length
@@ -157,20 +647,14 @@ impl<R: BufRead> AofReader<R> {
                 AikvError::Persistence(format!("Invalid bulk string length: {}", e))
             })?;
 
-            let mut buf = vec![0u8; len];
+            // Read the data plus its trailing CRLF
+            let mut buf = vec![0u8; len + 2];
             self.reader.read_exact(&mut buf).map_err(|e| {
                 AikvError::Persistence(format!("Failed to read bulk string: {}", e))
             })?;
+            buf.truncate(len);
 
-            // Read CRLF
-            line.clear();
-            self.reader
-                .read_line(&mut line)
-                .map_err(|e| AikvError::Persistence(format!("Failed to read CRLF: {}", e)))?;
-
-            let arg = String::from_utf8(buf)
-                .map_err(|e| AikvError::Persistence(format!("Invalid UTF-8: {}", e)))?;
-            command.push(arg);
+            command.push(Bytes::from(buf));
         }
 
         Ok(Some(command))
@@ -267,4 +751,104 @@ mod tests {
         assert_eq!(commands[0], vec!["SET", "key1", "value1"]);
         assert_eq!(commands[1], vec!["GET", "key1"]);
     }
+
+    #[test]
+    fn test_aof_append_selects_db_and_is_binary_safe() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path();
+
+        let writer = AofWriter::new(path, AofSyncPolicy::No).unwrap();
+        let binary = Bytes::from_static(&[0, 0xff, b'\r', b'\n']);
+        writer
+            .append(0, "SET", &[Bytes::from("a"), binary.clone()])
+            .unwrap();
+        writer.append(0, "DEL", &[Bytes::from("b")]).unwrap();
+        writer.append(2, "DEL", &[Bytes::from("c")]).unwrap();
+        drop(writer);
+
+        let file = File::open(path).unwrap();
+        let mut reader = AofReader::new(BufReader::new(file));
+        let mut commands = Vec::new();
+        while let Some(args) = reader.read_args().unwrap() {
+            commands.push(args);
+        }
+
+        let expected: Vec<Vec<Bytes>> = vec![
+            vec!["SELECT".into(), "0".into()],
+            vec!["SET".into(), "a".into(), binary],
+            vec!["DEL".into(), "b".into()],
+            vec!["SELECT".into(), "2".into()],
+            vec!["DEL".into(), "c".into()],
+        ];
+        assert_eq!(commands, expected);
+    }
+
+    #[test]
+    fn test_write_guards_only_hold_up_writes_to_their_database() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path();
+
+        let writer = AofWriter::new(path, AofSyncPolicy::Always).unwrap();
+        let guard = writer.write_guard(Some(0));
+        guard.append(0, "SET", &["a".into(), "1".into()]).unwrap();
+        // Would deadlock if database 1 waited for the guard on database 0
+        thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let guard = writer.write_guard(Some(1));
+                    guard.append(1, "SET", &["b".into(), "2".into()]).unwrap();
+                })
+                .join()
+                .unwrap();
+        });
+        drop(guard);
+        drop(writer);
+
+        let commands = load_aof(path).unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                vec!["SELECT", "0"],
+                vec!["SET", "a", "1"],
+                vec!["SELECT", "1"],
+                vec!["SET", "b", "2"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rewrite_keeps_writes_logged_during_rewrite() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path();
+        let storage = StorageEngine::new_memory(16);
+        storage
+            .set_value(
+                0,
+                "a".to_string(),
+                StoredValue::new_string(Bytes::from("1")),
+            )
+            .unwrap();
+
+        let writer = AofWriter::new(path, AofSyncPolicy::No).unwrap();
+        writer.append(0, "SET", &["a".into(), "0".into()]).unwrap();
+        let databases = writer.begin_rewrite(&storage).unwrap();
+        assert!(writer.begin_rewrite(&storage).is_err());
+        writer.append(0, "SET", &["b".into(), "2".into()]).unwrap();
+        writer.finish_rewrite(&databases).unwrap();
+        assert!(!writer.rewrite_in_progress());
+        writer.append(0, "DEL", &["a".into()]).unwrap();
+        drop(writer);
+
+        let commands = load_aof(path).unwrap();
+        assert_eq!(
+            commands,
+            vec![
+                vec!["SELECT", "0"],
+                vec!["SET", "a", "1"],
+                vec!["SELECT", "0"],
+                vec!["SET", "b", "2"],
+                vec!["DEL", "a"],
+            ]
+        );
+    }
 }
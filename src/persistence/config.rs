@@ -1,5 +1,7 @@
+use crate::error::AikvError;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::str::FromStr;
 
 /// Persistence configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +32,34 @@ pub enum AofSyncPolicy {
     No,
 }
 
+impl AofSyncPolicy {
+    /// Redis `appendfsync` spelling of the policy
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AofSyncPolicy::Always => "always",
+            AofSyncPolicy::EverySecond => "everysec",
+            AofSyncPolicy::No => "no",
+        }
+    }
+}
+
+impl FromStr for AofSyncPolicy {
+    type Err = AikvError;
+
+    /// Parse the Redis `appendfsync` spelling: `always`, `everysec` or `no`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "always" => Ok(AofSyncPolicy::Always),
+            "everysec" => Ok(AofSyncPolicy::EverySecond),
+            "no" => Ok(AofSyncPolicy::No),
+            _ => Err(AikvError::InvalidArgument(format!(
+                "invalid AOF sync policy '{}', expected always, everysec or no",
+                s
+            ))),
+        }
+    }
+}
+
 impl Default for PersistenceConfig {
     fn default() -> Self {
         Self {
@@ -1,10 +1,12 @@
 pub mod aof;
 pub mod config;
+pub mod crc64;
 pub mod rdb;
 
-pub use aof::{load_aof, AofReader, AofWriter};
+pub use aof::{load_aof, AofReader, AofWriteGuard, AofWriter};
 pub use config::{AofSyncPolicy, PersistenceConfig};
+pub use crc64::crc64;
 pub use rdb::{
-    load_rdb, load_stored_value_rdb, save_rdb, save_stored_value_rdb, DatabaseData, RdbReader,
-    RdbWriter,
+    load_rdb, load_rdb_into, load_stored_value_rdb, save_rdb, save_rdb_with_checksum,
+    save_stored_value_rdb, save_stored_value_rdb_atomic, DatabaseData, RdbReader, RdbWriter,
 };
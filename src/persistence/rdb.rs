@@ -1,9 +1,10 @@
 use crate::error::{AikvError, Result};
-use crate::storage::{SerializableStoredValue, StoredValue};
+use crate::persistence::crc64::crc64;
+use crate::storage::{SerializableStoredValue, StorageEngine, StoredValue};
 use bytes::Bytes;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Write};
+use std::io::{self, BufReader, BufWriter, Read, Write};
 use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -19,15 +20,58 @@ const OPCODE_SELECTDB: u8 = 0xFE;
 const OPCODE_EXPIRETIME_MS: u8 = 0xFC;
 const OPCODE_AUX: u8 = 0xFA;
 
+/// Writer that keeps a running CRC64 of everything written through it
+struct Crc64Writer<W: Write> {
+    inner: W,
+    crc: u64,
+}
+
+impl<W: Write> Write for Crc64Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.crc = crc64(self.crc, &buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reader that keeps a running CRC64 of everything read through it
+struct Crc64Reader<R: Read> {
+    inner: R,
+    crc: u64,
+}
+
+impl<R: Read> Read for Crc64Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.crc = crc64(self.crc, &buf[..n]);
+        Ok(n)
+    }
+}
+
 /// RDB writer for creating database snapshots
 pub struct RdbWriter<W: Write> {
-    writer: BufWriter<W>,
+    writer: BufWriter<Crc64Writer<W>>,
+    /// Whether to write a CRC64 trailer (zeros otherwise)
+    checksum: bool,
 }
 
 impl<W: Write> RdbWriter<W> {
     pub fn new(writer: W) -> Self {
+        Self::with_checksum(writer, true)
+    }
+
+    /// Create a writer, optionally skipping the CRC64 trailer (Redis `rdbchecksum no`)
+    pub fn with_checksum(writer: W, checksum: bool) -> Self {
         Self {
-            writer: BufWriter::new(writer),
+            writer: BufWriter::new(Crc64Writer {
+                inner: writer,
+                crc: 0,
+            }),
+            checksum,
         }
     }
 
@@ -147,9 +191,17 @@ impl<W: Write> RdbWriter<W> {
             .write_all(&[OPCODE_EOF])
             .map_err(|e| AikvError::Persistence(format!("Failed to write EOF: {}", e)))?;
 
-        // Write 8-byte checksum (simplified - just write zeros for now)
+        // 8-byte CRC64 of everything before it, or zeros when disabled
         self.writer
-            .write_all(&[0u8; 8])
+            .flush()
+            .map_err(|e| AikvError::Persistence(format!("Failed to flush: {}", e)))?;
+        let checksum = if self.checksum {
+            self.writer.get_ref().crc
+        } else {
+            0
+        };
+        self.writer
+            .write_all(&checksum.to_le_bytes())
             .map_err(|e| AikvError::Persistence(format!("Failed to write checksum: {}", e)))?;
 
         self.writer
@@ -162,13 +214,16 @@ impl<W: Write> RdbWriter<W> {
 
 /// RDB reader for loading database snapshots
 pub struct RdbReader<R: Read> {
-    reader: BufReader<R>,
+    reader: Crc64Reader<BufReader<R>>,
 }
 
 impl<R: Read> RdbReader<R> {
     pub fn new(reader: R) -> Self {
         Self {
-            reader: BufReader::new(reader),
+            reader: Crc64Reader {
+                inner: BufReader::new(reader),
+                crc: 0,
+            },
         }
     }
 
@@ -265,9 +320,17 @@ impl<R: Read> RdbReader<R> {
 
             match opcode[0] {
                 OPCODE_EOF => {
-                    // Read checksum
-                    let mut _checksum = [0u8; 8];
-                    let _ = self.reader.read_exact(&mut _checksum);
+                    let expected = self.reader.crc;
+                    let mut checksum = [0u8; 8];
+                    self.reader.read_exact(&mut checksum).map_err(|e| {
+                        AikvError::Persistence(format!("Failed to read checksum: {}", e))
+                    })?;
+
+                    // A zero checksum means the file was written without one
+                    let checksum = u64::from_le_bytes(checksum);
+                    if checksum != 0 && checksum != expected {
+                        return Err(AikvError::Persistence("RDB checksum mismatch".to_string()));
+                    }
                     break;
                 }
                 OPCODE_SELECTDB => {
@@ -312,10 +375,19 @@ impl<R: Read> RdbReader<R> {
 
 /// Save database to RDB file
 pub fn save_rdb<P: AsRef<Path>>(path: P, databases: &[DatabaseData]) -> Result<()> {
+    save_rdb_with_checksum(path, databases, true)
+}
+
+/// Save database to RDB file, optionally without the CRC64 trailer
+pub fn save_rdb_with_checksum<P: AsRef<Path>>(
+    path: P,
+    databases: &[DatabaseData],
+    checksum: bool,
+) -> Result<()> {
     let file = File::create(path)
         .map_err(|e| AikvError::Persistence(format!("Failed to create RDB file: {}", e)))?;
 
-    let mut writer = RdbWriter::new(file);
+    let mut writer = RdbWriter::with_checksum(file, checksum);
     writer.write_header()?;
 
     // Write metadata
@@ -341,8 +413,12 @@ pub fn save_stored_value_rdb<P: AsRef<Path>>(
     path: P,
     databases: &[HashMap<String, StoredValue>],
 ) -> Result<()> {
-    // Convert StoredValue databases to DatabaseData format
-    let rdb_databases: Result<Vec<DatabaseData>> = databases
+    save_rdb(path, &to_rdb_databases(databases)?)
+}
+
+/// Convert StoredValue databases to DatabaseData format, dropping expired keys
+fn to_rdb_databases(databases: &[HashMap<String, StoredValue>]) -> Result<Vec<DatabaseData>> {
+    databases
         .iter()
         .map(|db| {
             let mut rdb_db = HashMap::new();
@@ -362,10 +438,33 @@ pub fn save_stored_value_rdb<P: AsRef<Path>>(
             }
             Ok(rdb_db)
         })
-        .collect();
+        .collect()
+}
 
-    let rdb_databases = rdb_databases?;
-    save_rdb(path, &rdb_databases)
+/// Save StoredValue database to an RDB file, replacing it atomically
+///
+/// The snapshot is written to a temporary file in the same directory and
+/// renamed over `path`, so a crash mid-save never leaves a truncated file.
+/// With `checksum` false the CRC64 trailer is written as zeros.
+pub fn save_stored_value_rdb_atomic<P: AsRef<Path>>(
+    path: P,
+    databases: &[HashMap<String, StoredValue>],
+    checksum: bool,
+) -> Result<()> {
+    let path = path.as_ref();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let temp_file = tempfile::NamedTempFile::new_in(dir)
+        .map_err(|e| AikvError::Persistence(format!("Failed to create temp file: {}", e)))?;
+
+    save_rdb_with_checksum(temp_file.path(), &to_rdb_databases(databases)?, checksum)?;
+
+    temp_file
+        .persist(path)
+        .map_err(|e| AikvError::Persistence(format!("Failed to rename RDB file: {}", e)))?;
+    Ok(())
 }
 
 /// Load database from RDB file
@@ -410,6 +509,24 @@ pub fn load_stored_value_rdb<P: AsRef<Path>>(path: P) -> Result<Vec<HashMap<Stri
     Ok(stored_databases)
 }
 
+/// Load an RDB file into `storage`, returning the number of keys restored
+///
+/// Keys that expired since the snapshot was taken are skipped.
+pub fn load_rdb_into<P: AsRef<Path>>(path: P, storage: &StorageEngine) -> Result<usize> {
+    let databases = load_stored_value_rdb(path)?;
+
+    let mut count = 0;
+    for (db_index, db) in databases.into_iter().enumerate() {
+        for (key, value) in db {
+            if !value.is_expired() {
+                storage.set_value(db_index, key, value)?;
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -537,4 +654,38 @@ mod tests {
         assert_eq!(zset_data.get(&b"member1".to_vec()).unwrap(), &1.0);
         assert_eq!(zset_data.get(&b"member2".to_vec()).unwrap(), &2.5);
     }
+
+    #[test]
+    fn test_rdb_checksum_detects_corruption() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path();
+
+        let mut db = HashMap::new();
+        db.insert("key".to_string(), (Bytes::from("some value"), None));
+        save_rdb(path, &[db.clone()]).unwrap();
+        assert_eq!(load_rdb(path).unwrap()[0], db);
+
+        // Flip a byte inside the value: the file still parses but fails the checksum
+        let mut data = std::fs::read(path).unwrap();
+        let pos = data.windows(4).position(|w| w == b"some").unwrap();
+        data[pos] ^= 0x20;
+        std::fs::write(path, &data).unwrap();
+
+        let err = load_rdb(path).unwrap_err();
+        assert!(err.to_string().contains("RDB checksum mismatch"), "{}", err);
+    }
+
+    #[test]
+    fn test_rdb_without_checksum_writes_zeros() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path();
+
+        let mut db = HashMap::new();
+        db.insert("key".to_string(), (Bytes::from("value"), Some(42)));
+        save_rdb_with_checksum(path, &[db.clone()], false).unwrap();
+
+        let data = std::fs::read(path).unwrap();
+        assert_eq!(&data[data.len() - 8..], &[0u8; 8]);
+        assert_eq!(load_rdb(path).unwrap()[0], db);
+    }
 }
@@ -1,5 +1,5 @@
 pub mod parser;
 pub mod types;
 
-pub use parser::RespParser;
-pub use types::RespValue;
+pub use parser::{RespParser, DEFAULT_MAX_BULK_LEN, DEFAULT_MAX_MULTIBULK_LEN};
+pub use types::{ProtocolVersion, RespValue, StreamingReply, STREAMING_THRESHOLD};
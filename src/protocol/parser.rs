@@ -2,19 +2,44 @@ use super::types::RespValue;
 use crate::error::{AikvError, Result};
 use bytes::{Buf, Bytes, BytesMut};
 
+/// Default maximum length of a bulk string (Redis `proto-max-bulk-len`, 512MB)
+pub const DEFAULT_MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
+/// Default maximum number of elements in an array
+pub const DEFAULT_MAX_MULTIBULK_LEN: usize = 1024 * 1024;
+
+/// Longest inline command accepted, line ending included (Redis
+/// `PROTO_INLINE_MAX_SIZE`)
+pub const MAX_INLINE_LEN: usize = 64 * 1024;
+
 /// RESP protocol parser
 pub struct RespParser {
     buffer: BytesMut,
+    max_bulk_len: usize,
+    max_multibulk_len: usize,
 }
 
 impl RespParser {
-    /// Create a new parser with a given capacity
+    /// Create a new parser with a given capacity and the default limits
     pub fn new(capacity: usize) -> Self {
+        Self::with_limits(capacity, DEFAULT_MAX_BULK_LEN, DEFAULT_MAX_MULTIBULK_LEN)
+    }
+
+    /// Create a new parser that rejects bulk strings longer than `max_bulk_len`
+    /// bytes and arrays with more than `max_multibulk_len` elements
+    pub fn with_limits(capacity: usize, max_bulk_len: usize, max_multibulk_len: usize) -> Self {
         Self {
             buffer: BytesMut::with_capacity(capacity),
+            max_bulk_len,
+            max_multibulk_len,
         }
     }
 
+    /// Change the longest bulk string accepted from now on
+    pub fn set_max_bulk_len(&mut self, max_bulk_len: usize) {
+        self.max_bulk_len = max_bulk_len;
+    }
+
     /// Add data to the parser buffer
     pub fn feed(&mut self, data: &[u8]) {
         self.buffer.extend_from_slice(data);
@@ -26,9 +51,40 @@ impl RespParser {
     }
 
     /// Try to parse a complete RESP value from the buffer
+    ///
+    /// Input that doesn't start with a RESP type marker is parsed as an
+    /// inline command (e.g. `PING\r\n` typed into telnet) and returned as an
+    /// array of bulk strings.
     pub fn parse(&mut self) -> Result<Option<RespValue>> {
-        if self.buffer.is_empty() {
-            return Ok(None);
+        loop {
+            if self.buffer.is_empty() {
+                return Ok(None);
+            }
+            if is_type_marker(self.buffer[0]) {
+                break;
+            }
+
+            // Inline command: wait for the whole line, but only so long
+            let window = &self.buffer[..self.buffer.len().min(MAX_INLINE_LEN)];
+            let Some(newline) = window.iter().position(|&b| b == b'\n') else {
+                if self.buffer.len() >= MAX_INLINE_LEN {
+                    return Err(AikvError::InvalidProtocol(
+                        "too big inline request".to_string(),
+                    ));
+                }
+                return Ok(None);
+            };
+            let line = self.buffer.split_to(newline + 1);
+            let line = line.strip_suffix(b"\n").unwrap_or(&line[..]);
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            let args = split_inline_args(line)?;
+
+            // Empty lines are ignored, like in Redis
+            if !args.is_empty() {
+                return Ok(Some(RespValue::Array(Some(
+                    args.into_iter().map(RespValue::bulk_string).collect(),
+                ))));
+            }
         }
 
         let mut cursor = std::io::Cursor::new(&self.buffer[..]);
@@ -105,20 +161,14 @@ impl RespParser {
 
         let len = line
             .parse::<i64>()
-            .map_err(|_| AikvError::Protocol(format!("Invalid bulk string length: {}", line)))?;
+            .map_err(|_| AikvError::InvalidProtocol("invalid bulk length".to_string()))?;
 
         if len == -1 {
             return Ok(RespValue::BulkString(None));
         }
 
-        if len < 0 {
-            return Err(AikvError::Protocol(format!(
-                "Invalid bulk string length: {}",
-                len
-            )));
-        }
-
-        let len = len as usize;
+        // Reject bad lengths up front instead of buffering toward them
+        let len = self.checked_bulk_len(len)?;
         let pos = cursor.position() as usize;
         let data = cursor.get_ref();
 
@@ -136,20 +186,16 @@ impl RespParser {
         let line = self.read_line(cursor)?;
         let len = line
             .parse::<i64>()
-            .map_err(|_| AikvError::Protocol(format!("Invalid array length: {}", line)))?;
+            .map_err(|_| AikvError::InvalidProtocol("invalid multibulk length".to_string()))?;
 
         if len == -1 {
             return Ok(RespValue::Array(None));
         }
 
-        if len < 0 {
-            return Err(AikvError::Protocol(format!(
-                "Invalid array length: {}",
-                len
-            )));
-        }
+        let len = self.checked_multibulk_len(len)?;
 
-        let mut array = Vec::with_capacity(len as usize);
+        // Elements may not have arrived yet, so don't trust the length for the allocation
+        let mut array = Vec::with_capacity(len.min(1024));
         for _ in 0..len {
             let value = self.parse_value(cursor)?;
             array.push(value);
@@ -158,6 +204,26 @@ impl RespParser {
         Ok(RespValue::Array(Some(array)))
     }
 
+    /// Validates a length-prefixed payload size against `max_bulk_len`.
+    fn checked_bulk_len(&self, len: i64) -> Result<usize> {
+        if len < 0 || len as u64 > self.max_bulk_len as u64 {
+            return Err(AikvError::InvalidProtocol(
+                "invalid bulk length".to_string(),
+            ));
+        }
+        Ok(len as usize)
+    }
+
+    /// Validates an aggregate element count against `max_multibulk_len`.
+    fn checked_multibulk_len(&self, len: i64) -> Result<usize> {
+        if len < 0 || len as u64 > self.max_multibulk_len as u64 {
+            return Err(AikvError::InvalidProtocol(
+                "invalid multibulk length".to_string(),
+            ));
+        }
+        Ok(len as usize)
+    }
+
     fn read_line(&self, cursor: &mut std::io::Cursor<&[u8]>) -> Result<String> {
         let start = cursor.position() as usize;
         let data = cursor.get_ref();
@@ -212,14 +278,7 @@ impl RespParser {
             .parse::<i64>()
             .map_err(|_| AikvError::Protocol(format!("Invalid bulk error length: {}", line)))?;
 
-        if len < 0 {
-            return Err(AikvError::Protocol(format!(
-                "Invalid bulk error length: {}",
-                len
-            )));
-        }
-
-        let len = len as usize;
+        let len = self.checked_bulk_len(len)?;
         let pos = cursor.position() as usize;
         let data = cursor.get_ref();
 
@@ -239,14 +298,7 @@ impl RespParser {
             AikvError::Protocol(format!("Invalid verbatim string length: {}", line))
         })?;
 
-        if len < 0 {
-            return Err(AikvError::Protocol(format!(
-                "Invalid verbatim string length: {}",
-                len
-            )));
-        }
-
-        let len = len as usize;
+        let len = self.checked_bulk_len(len)?;
         let pos = cursor.position() as usize;
         let data = cursor.get_ref();
 
@@ -280,11 +332,8 @@ impl RespParser {
             .parse::<i64>()
             .map_err(|_| AikvError::Protocol(format!("Invalid map length: {}", line)))?;
 
-        if len < 0 {
-            return Err(AikvError::Protocol(format!("Invalid map length: {}", len)));
-        }
-
-        let mut pairs = Vec::with_capacity(len as usize);
+        let len = self.checked_multibulk_len(len)?;
+        let mut pairs = Vec::with_capacity(len.min(1024));
         for _ in 0..len {
             let key = self.parse_value(cursor)?;
             let value = self.parse_value(cursor)?;
@@ -300,11 +349,8 @@ impl RespParser {
             .parse::<i64>()
             .map_err(|_| AikvError::Protocol(format!("Invalid set length: {}", line)))?;
 
-        if len < 0 {
-            return Err(AikvError::Protocol(format!("Invalid set length: {}", len)));
-        }
-
-        let mut items = Vec::with_capacity(len as usize);
+        let len = self.checked_multibulk_len(len)?;
+        let mut items = Vec::with_capacity(len.min(1024));
         for _ in 0..len {
             let value = self.parse_value(cursor)?;
             items.push(value);
@@ -319,11 +365,8 @@ impl RespParser {
             .parse::<i64>()
             .map_err(|_| AikvError::Protocol(format!("Invalid push length: {}", line)))?;
 
-        if len < 0 {
-            return Err(AikvError::Protocol(format!("Invalid push length: {}", len)));
-        }
-
-        let mut items = Vec::with_capacity(len as usize);
+        let len = self.checked_multibulk_len(len)?;
+        let mut items = Vec::with_capacity(len.min(1024));
         for _ in 0..len {
             let value = self.parse_value(cursor)?;
             items.push(value);
@@ -338,14 +381,8 @@ impl RespParser {
             .parse::<i64>()
             .map_err(|_| AikvError::Protocol(format!("Invalid attribute length: {}", line)))?;
 
-        if len < 0 {
-            return Err(AikvError::Protocol(format!(
-                "Invalid attribute length: {}",
-                len
-            )));
-        }
-
-        let mut attributes = Vec::with_capacity(len as usize);
+        let len = self.checked_multibulk_len(len)?;
+        let mut attributes = Vec::with_capacity(len.min(1024));
         for _ in 0..len {
             let key = self.parse_value(cursor)?;
             let value = self.parse_value(cursor)?;
@@ -363,6 +400,7 @@ impl RespParser {
 
     fn parse_streamed_string_body(&self, cursor: &mut std::io::Cursor<&[u8]>) -> Result<RespValue> {
         let mut chunks = Vec::new();
+        let mut total = 0usize;
 
         loop {
             // Expect ';' marker for each chunk
@@ -391,6 +429,14 @@ impl RespParser {
                 break;
             }
 
+            // The chunks together form one bulk string, so they share its limit
+            total = total.saturating_add(len);
+            if total > self.max_bulk_len {
+                return Err(AikvError::InvalidProtocol(
+                    "invalid bulk length".to_string(),
+                ));
+            }
+
             let pos = cursor.position() as usize;
             let data = cursor.get_ref();
 
@@ -414,6 +460,117 @@ impl RespParser {
     }
 }
 
+/// Whether `byte` starts a RESP2 or RESP3 value
+fn is_type_marker(byte: u8) -> bool {
+    matches!(
+        byte,
+        b'+' | b'-'
+            | b':'
+            | b'$'
+            | b'*'
+            | b'_'
+            | b'#'
+            | b','
+            | b'('
+            | b'!'
+            | b'='
+            | b'%'
+            | b'~'
+            | b'>'
+            | b'|'
+            | b';'
+    )
+}
+
+/// Split an inline command line into arguments.
+///
+/// Arguments are separated by whitespace. Double-quoted arguments support
+/// `\n`, `\r`, `\t`, `\b`, `\a`, `\xHH` and backslash-escaped characters;
+/// single-quoted arguments only support `\'`. A closing quote must be
+/// followed by whitespace or the end of the line.
+fn split_inline_args(line: &[u8]) -> Result<Vec<Bytes>> {
+    let unbalanced = || AikvError::InvalidProtocol("unbalanced quotes in request".to_string());
+
+    let mut args = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < line.len() && line[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i >= line.len() {
+            return Ok(args);
+        }
+
+        let mut arg = Vec::new();
+        match line[i] {
+            b'"' => {
+                i += 1;
+                loop {
+                    match line.get(i) {
+                        None => return Err(unbalanced()),
+                        Some(b'"') => break,
+                        Some(b'\\') if i + 1 < line.len() => {
+                            let hex = line
+                                .get(i + 2..i + 4)
+                                .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+                                .and_then(|h| std::str::from_utf8(h).ok())
+                                .and_then(|h| u8::from_str_radix(h, 16).ok());
+                            match (line[i + 1], hex) {
+                                (b'x', Some(byte)) => {
+                                    arg.push(byte);
+                                    i += 2;
+                                }
+                                (b'n', _) => arg.push(b'\n'),
+                                (b'r', _) => arg.push(b'\r'),
+                                (b't', _) => arg.push(b'\t'),
+                                (b'b', _) => arg.push(0x08),
+                                (b'a', _) => arg.push(0x07),
+                                (c, _) => arg.push(c),
+                            }
+                            i += 2;
+                        }
+                        Some(&c) => {
+                            arg.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+            }
+            b'\'' => {
+                i += 1;
+                loop {
+                    match line.get(i) {
+                        None => return Err(unbalanced()),
+                        Some(b'\'') => break,
+                        Some(b'\\') if line.get(i + 1) == Some(&b'\'') => {
+                            arg.push(b'\'');
+                            i += 2;
+                        }
+                        Some(&c) => {
+                            arg.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+            }
+            _ => {
+                while i < line.len() && !line[i].is_ascii_whitespace() {
+                    arg.push(line[i]);
+                    i += 1;
+                }
+            }
+        }
+
+        // A closing quote must end the argument
+        if i < line.len() && !line[i].is_ascii_whitespace() {
+            return Err(unbalanced());
+        }
+        args.push(Bytes::from(arg));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -689,4 +846,168 @@ mod tests {
             })
         );
     }
+
+    fn inline(args: &[&str]) -> Option<RespValue> {
+        Some(RespValue::Array(Some(
+            args.iter()
+                .map(|a| RespValue::BulkString(Some(Bytes::copy_from_slice(a.as_bytes()))))
+                .collect(),
+        )))
+    }
+
+    #[test]
+    fn test_parse_inline_command() {
+        let mut parser = RespParser::new(128);
+        parser.feed(b"PING\r\n");
+        assert_eq!(parser.parse().unwrap(), inline(&["PING"]));
+
+        // Bare \n line endings and empty lines are accepted too
+        parser.feed(b"\r\n  GET   foo \n");
+        assert_eq!(parser.parse().unwrap(), inline(&["GET", "foo"]));
+        assert_eq!(parser.parse().unwrap(), None);
+
+        // Incomplete lines wait for more data
+        parser.feed(b"ECHO hel");
+        assert_eq!(parser.parse().unwrap(), None);
+        parser.feed(b"lo\r\n*1\r\n$4\r\nPING\r\n");
+        assert_eq!(parser.parse().unwrap(), inline(&["ECHO", "hello"]));
+        assert_eq!(parser.parse().unwrap(), inline(&["PING"]));
+    }
+
+    #[test]
+    fn test_parse_inline_command_too_big() {
+        let mut parser = RespParser::new(128);
+        let mut line = vec![b'a'; MAX_INLINE_LEN - 2];
+        line.extend_from_slice(b"\r\n");
+        parser.feed(&line);
+        assert!(parser.parse().unwrap().is_some());
+
+        // Without a line ending in sight, the request is refused once it
+        // reaches the limit rather than buffered without end
+        parser.feed(&vec![b'a'; MAX_INLINE_LEN - 1]);
+        assert_eq!(parser.parse().unwrap(), None);
+        parser.feed(b"a\r\n");
+        assert!(
+            matches!(parser.parse(), Err(AikvError::InvalidProtocol(ref e)) if e == "too big inline request")
+        );
+    }
+
+    #[test]
+    fn test_parse_inline_quoted_arguments() {
+        let mut parser = RespParser::new(128);
+        parser.feed(b"SET foo \"bar baz\"\r\n");
+        assert_eq!(parser.parse().unwrap(), inline(&["SET", "foo", "bar baz"]));
+
+        parser.feed(b"SET 'it\\'s' \"a\\tb\\x41\\\"\" ''\r\n");
+        assert_eq!(
+            parser.parse().unwrap(),
+            inline(&["SET", "it's", "a\tbA\"", ""])
+        );
+    }
+
+    #[test]
+    fn test_parse_inline_unbalanced_quotes() {
+        for line in [
+            &b"SET foo \"bar\r\n"[..],
+            b"SET foo 'bar\r\n",
+            b"SET \"foo\"bar\r\n",
+        ] {
+            let mut parser = RespParser::new(128);
+            parser.feed(line);
+            assert!(
+                matches!(parser.parse(), Err(AikvError::InvalidProtocol(_))),
+                "{:?}",
+                String::from_utf8_lossy(line)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_bulk_length_limit() {
+        let mut parser = RespParser::with_limits(128, 16, DEFAULT_MAX_MULTIBULK_LEN);
+        parser.feed(b"$16\r\n0123456789abcdef\r\n");
+        assert_eq!(
+            parser.parse().unwrap(),
+            Some(RespValue::BulkString(Some(Bytes::from("0123456789abcdef"))))
+        );
+
+        // Over-limit lengths fail as soon as the header arrives
+        parser.feed(b"*1\r\n$17\r\n");
+        assert!(matches!(parser.parse(), Err(AikvError::InvalidProtocol(_))));
+
+        let mut parser = RespParser::new(128);
+        parser.feed(b"$999999999999\r\n");
+        assert!(matches!(parser.parse(), Err(AikvError::InvalidProtocol(_))));
+    }
+
+    #[test]
+    fn test_parse_multibulk_length_limit() {
+        let mut parser = RespParser::with_limits(128, DEFAULT_MAX_BULK_LEN, 2);
+        parser.feed(b"*2\r\n:1\r\n:2\r\n");
+        assert_eq!(
+            parser.parse().unwrap(),
+            Some(RespValue::Array(Some(vec![
+                RespValue::Integer(1),
+                RespValue::Integer(2)
+            ])))
+        );
+
+        parser.feed(b"*3\r\n");
+        assert!(matches!(parser.parse(), Err(AikvError::InvalidProtocol(_))));
+    }
+
+    #[test]
+    fn test_parse_resp3_length_limits() {
+        // Aggregate headers are checked before anything is allocated for them
+        for input in [
+            &b"%99999999999\r\n"[..],
+            b"~99999999999\r\n",
+            b">99999999999\r\n",
+            b"|99999999999\r\n",
+            b"%3\r\n",
+            b"~-2\r\n",
+        ] {
+            let mut parser = RespParser::with_limits(128, DEFAULT_MAX_BULK_LEN, 2);
+            parser.feed(input);
+            assert!(
+                matches!(parser.parse(), Err(AikvError::InvalidProtocol(_))),
+                "{:?}",
+                String::from_utf8_lossy(input)
+            );
+        }
+
+        // So are the payloads of every length-prefixed type
+        for input in [
+            &b"!17\r\n"[..],
+            b"=17\r\n",
+            b"$?\r\n;10\r\n0123456789\r\n;7\r\n",
+        ] {
+            let mut parser = RespParser::with_limits(128, 16, DEFAULT_MAX_MULTIBULK_LEN);
+            parser.feed(input);
+            assert!(
+                matches!(parser.parse(), Err(AikvError::InvalidProtocol(_))),
+                "{:?}",
+                String::from_utf8_lossy(input)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_negative_lengths() {
+        for input in [&b"*-2\r\n"[..], b"$-5\r\n", b"*abc\r\n"] {
+            let mut parser = RespParser::new(128);
+            parser.feed(input);
+            assert!(
+                matches!(parser.parse(), Err(AikvError::InvalidProtocol(_))),
+                "{:?}",
+                String::from_utf8_lossy(input)
+            );
+        }
+
+        // -1 is the null array / bulk string
+        let mut parser = RespParser::new(128);
+        parser.feed(b"*-1\r\n$-1\r\n");
+        assert_eq!(parser.parse().unwrap(), Some(RespValue::Array(None)));
+        assert_eq!(parser.parse().unwrap(), Some(RespValue::BulkString(None)));
+    }
 }
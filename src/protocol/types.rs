@@ -1,4 +1,20 @@
 use bytes::Bytes;
+use std::fmt;
+use std::sync::Arc;
+
+/// Aggregates built with [`RespValue::lazy_array`] and friends that have more
+/// items than this become [`RespValue::Streaming`]; smaller ones are built
+/// right away
+pub const STREAMING_THRESHOLD: usize = 1024;
+
+/// RESP protocol version negotiated by a connection via HELLO
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProtocolVersion {
+    /// RESP2, the default until a client sends `HELLO 3`
+    #[default]
+    Resp2,
+    Resp3,
+}
 
 /// RESP (REdis Serialization Protocol) value types
 /// Supports both RESP2 and RESP3 protocol versions
@@ -58,6 +74,10 @@ pub enum RespValue {
     /// Streamed String: $?\r\n;4\r\nHell\r\n;5\r\no wor\r\n;1\r\nd\r\n;0\r\n
     /// For streaming large bulk strings in chunks (RESP3)
     StreamedString(Vec<Bytes>),
+
+    /// A large array, set or map whose elements are only serialized while
+    /// the connection writes them out, see [`StreamingReply`]
+    Streaming(StreamingReply),
 }
 
 impl RespValue {
@@ -164,6 +184,102 @@ impl RespValue {
         RespValue::StreamedString(chunks)
     }
 
+    /// Create an array reply from `items`, each giving `N` elements, which
+    /// is streamed if there are more than [`STREAMING_THRESHOLD`] items
+    pub fn lazy_array<T, F, const N: usize>(items: Vec<T>, element: F) -> Self
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&T) -> [RespValue; N] + Send + Sync + 'static,
+    {
+        StreamingReply::new(AggregateKind::Array, items, element).into_value()
+    }
+
+    /// Create a set reply from `items`, streamed like [`RespValue::lazy_array`]
+    pub fn lazy_set<T, F>(items: Vec<T>, element: F) -> Self
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&T) -> RespValue + Send + Sync + 'static,
+    {
+        StreamingReply::new(AggregateKind::Set, items, move |item| [element(item)]).into_value()
+    }
+
+    /// Create a map reply from `items`, each giving a key and a value,
+    /// streamed like [`RespValue::lazy_array`]
+    pub fn lazy_map<T, F>(items: Vec<T>, pair: F) -> Self
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&T) -> (RespValue, RespValue) + Send + Sync + 'static,
+    {
+        StreamingReply::new(AggregateKind::Map, items, move |item| {
+            let (key, value) = pair(item);
+            [key, value]
+        })
+        .into_value()
+    }
+
+    /// Serialize for a connection speaking the given protocol version
+    ///
+    /// RESP3 connections get the value as-is. RESP2 connections get RESP3-only
+    /// types downgraded the way Redis does it (see [`RespValue::into_resp2`]),
+    /// so values built only from RESP2 types serialize identically either way.
+    pub fn serialize_for(self, protocol: ProtocolVersion) -> Bytes {
+        if let RespValue::Streaming(reply) = &self {
+            return Bytes::from(reply.serialize_for(protocol));
+        }
+        match protocol {
+            ProtocolVersion::Resp3 => self.serialize(),
+            ProtocolVersion::Resp2 => self.into_resp2().serialize(),
+        }
+    }
+
+    /// Convert RESP3-only types into their RESP2 equivalents
+    ///
+    /// - Map → flat array of alternating keys and values
+    /// - Set, Push → array
+    /// - Null → null bulk string
+    /// - Boolean → integer 1 or 0
+    /// - Double, BigNumber, VerbatimString → bulk string
+    /// - BulkError → error
+    /// - Attribute → the attached data (attributes are dropped)
+    /// - StreamedString → the chunks joined into one bulk string
+    /// - Streaming → built in full, then converted like any other aggregate
+    pub fn into_resp2(self) -> RespValue {
+        match self {
+            RespValue::Array(Some(items)) => {
+                RespValue::Array(Some(items.into_iter().map(RespValue::into_resp2).collect()))
+            }
+            RespValue::Null => RespValue::BulkString(None),
+            RespValue::Boolean(b) => RespValue::Integer(if b { 1 } else { 0 }),
+            RespValue::Double(d) => RespValue::BulkString(Some(Bytes::from(format_double(d)))),
+            RespValue::BigNumber(s) => RespValue::BulkString(Some(Bytes::from(s))),
+            RespValue::BulkError(e) => RespValue::Error(e),
+            RespValue::VerbatimString {
+                data,
+                ..
+            } => RespValue::BulkString(Some(data)),
+            RespValue::Map(pairs) => {
+                let mut items = Vec::with_capacity(pairs.len() * 2);
+                for (key, value) in pairs {
+                    items.push(key.into_resp2());
+                    items.push(value.into_resp2());
+                }
+                RespValue::Array(Some(items))
+            }
+            RespValue::Set(items) | RespValue::Push(items) => {
+                RespValue::Array(Some(items.into_iter().map(RespValue::into_resp2).collect()))
+            }
+            RespValue::Attribute {
+                data,
+                ..
+            } => data.into_resp2(),
+            RespValue::StreamedString(chunks) => {
+                RespValue::BulkString(Some(Bytes::from(chunks.concat())))
+            }
+            RespValue::Streaming(reply) => reply.materialize().into_resp2(),
+            other => other,
+        }
+    }
+
     /// Serialize to RESP format bytes
     /// Supports both RESP2 and RESP3 formats
     pub fn serialize(&self) -> Bytes {
@@ -199,17 +315,7 @@ impl RespValue {
                     Bytes::from("#f\r\n")
                 }
             }
-            RespValue::Double(d) => {
-                if d.is_infinite() {
-                    if d.is_sign_positive() {
-                        Bytes::from(",inf\r\n")
-                    } else {
-                        Bytes::from(",-inf\r\n")
-                    }
-                } else {
-                    Bytes::from(format!(",{}\r\n", d))
-                }
-            }
+            RespValue::Double(d) => Bytes::from(format!(",{}\r\n", format_double(*d))),
             RespValue::BigNumber(s) => Bytes::from(format!("({}\r\n", s)),
             RespValue::BulkError(e) => {
                 let bytes = e.as_bytes();
@@ -275,10 +381,186 @@ impl RespValue {
                 result.extend_from_slice(b";0\r\n");
                 Bytes::from(result)
             }
+            RespValue::Streaming(reply) => Bytes::from(reply.serialize_for(ProtocolVersion::Resp3)),
+        }
+    }
+}
+
+/// Which aggregate a [`StreamingReply`] stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AggregateKind {
+    Array,
+    Set,
+    Map,
+}
+
+/// A large aggregate reply kept as the items it is made of
+///
+/// Building a reply of a million elements as `RespValue`s, then serializing
+/// all of it, holds the reply in memory two or three times over. A streaming
+/// reply keeps only the items it was built from; the connection writes the
+/// header, then serializes the items one at a time, flushing the socket as
+/// it goes. The bytes are the same as for the aggregate built in full.
+#[derive(Clone)]
+pub struct StreamingReply {
+    kind: AggregateKind,
+    items: Arc<dyn ReplyItems>,
+}
+
+/// The items of a streaming reply, each turning into a fixed number of
+/// elements (two, a key and a value, for a map)
+trait ReplyItems: Send + Sync {
+    fn len(&self) -> usize;
+
+    /// Elements each item turns into
+    fn width(&self) -> usize;
+
+    /// Build the elements of item `index`, handing them to `f` in order
+    fn for_each_element(&self, index: usize, f: &mut dyn FnMut(RespValue));
+}
+
+struct Items<T, F> {
+    items: Vec<T>,
+    element: F,
+}
+
+impl<T, F, const N: usize> ReplyItems for Items<T, F>
+where
+    T: Send + Sync,
+    F: Fn(&T) -> [RespValue; N] + Send + Sync,
+{
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    fn width(&self) -> usize {
+        N
+    }
+
+    fn for_each_element(&self, index: usize, f: &mut dyn FnMut(RespValue)) {
+        for element in (self.element)(&self.items[index]) {
+            f(element);
         }
     }
 }
 
+impl StreamingReply {
+    fn new<T, F, const N: usize>(kind: AggregateKind, items: Vec<T>, element: F) -> Self
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&T) -> [RespValue; N] + Send + Sync + 'static,
+    {
+        Self {
+            kind,
+            items: Arc::new(Items {
+                items,
+                element,
+            }),
+        }
+    }
+
+    /// Stream the reply if it is large enough to be worth it
+    fn into_value(self) -> RespValue {
+        if self.len() > STREAMING_THRESHOLD {
+            RespValue::Streaming(self)
+        } else {
+            self.materialize()
+        }
+    }
+
+    /// Number of items: elements of an array or set, pairs of a map
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Whether the reply has no items
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The aggregate header, as the given protocol spells it
+    ///
+    /// RESP2 has neither maps nor sets, so both are sent as flat arrays.
+    pub fn header(&self, protocol: ProtocolVersion) -> Bytes {
+        let elements = self.items.len() * self.items.width();
+        let header = match (self.kind, protocol) {
+            (AggregateKind::Map, ProtocolVersion::Resp3) => format!("%{}\r\n", self.items.len()),
+            (AggregateKind::Set, ProtocolVersion::Resp3) => format!("~{}\r\n", elements),
+            _ => format!("*{}\r\n", elements),
+        };
+        Bytes::from(header)
+    }
+
+    /// Append the serialized elements of item `index` to `out`
+    pub fn write_item(&self, index: usize, protocol: ProtocolVersion, out: &mut Vec<u8>) {
+        self.items.for_each_element(index, &mut |element| {
+            out.extend_from_slice(&element.serialize_for(protocol));
+        });
+    }
+
+    /// Serialize the whole reply in one go
+    pub fn serialize_for(&self, protocol: ProtocolVersion) -> Vec<u8> {
+        let mut result = self.header(protocol).to_vec();
+        for index in 0..self.len() {
+            self.write_item(index, protocol, &mut result);
+        }
+        result
+    }
+
+    /// Build the aggregate this reply stands for
+    pub fn materialize(&self) -> RespValue {
+        let mut elements = Vec::with_capacity(self.items.len() * self.items.width());
+        for index in 0..self.len() {
+            self.items
+                .for_each_element(index, &mut |element| elements.push(element));
+        }
+        match self.kind {
+            AggregateKind::Array => RespValue::Array(Some(elements)),
+            AggregateKind::Set => RespValue::Set(elements),
+            AggregateKind::Map => {
+                let mut pairs = Vec::with_capacity(elements.len() / 2);
+                let mut elements = elements.into_iter();
+                while let (Some(key), Some(value)) = (elements.next(), elements.next()) {
+                    pairs.push((key, value));
+                }
+                RespValue::Map(pairs)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for StreamingReply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamingReply")
+            .field("kind", &self.kind)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// Two streaming replies are equal when the aggregates they stand for are
+impl PartialEq for StreamingReply {
+    fn eq(&self, other: &Self) -> bool {
+        self.materialize() == other.materialize()
+    }
+}
+
+/// Format a double the way RESP3 spells it: `inf`, `-inf`, `nan`, or the
+/// shortest decimal representation
+fn format_double(d: f64) -> String {
+    if d.is_infinite() {
+        if d.is_sign_positive() {
+            "inf".to_string()
+        } else {
+            "-inf".to_string()
+        }
+    } else if d.is_nan() {
+        "nan".to_string()
+    } else {
+        d.to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -518,4 +800,97 @@ mod tests {
         assert_eq!(&serialized[16..19], &[0x80, 0x81, 0x82]);
         assert_eq!(&serialized[19..21], b"\r\n");
     }
+
+    #[test]
+    fn test_serialize_for_resp2_downgrades_resp3_types() {
+        let val = RespValue::map(vec![
+            (RespValue::bulk_string("a"), RespValue::double(1.5)),
+            (RespValue::bulk_string("b"), RespValue::boolean(true)),
+            (RespValue::bulk_string("c"), RespValue::null()),
+            (
+                RespValue::bulk_string("d"),
+                RespValue::set(vec![RespValue::integer(1)]),
+            ),
+        ]);
+
+        assert_eq!(
+            val.clone().serialize_for(ProtocolVersion::Resp2),
+            Bytes::from("*8\r\n$1\r\na\r\n$3\r\n1.5\r\n$1\r\nb\r\n:1\r\n$1\r\nc\r\n$-1\r\n$1\r\nd\r\n*1\r\n:1\r\n")
+        );
+        assert_eq!(
+            val.clone().serialize_for(ProtocolVersion::Resp3),
+            val.serialize()
+        );
+    }
+
+    #[test]
+    fn test_serialize_for_double() {
+        assert_eq!(
+            RespValue::double(1.5).serialize_for(ProtocolVersion::Resp2),
+            Bytes::from("$3\r\n1.5\r\n")
+        );
+        assert_eq!(
+            RespValue::double(1.5).serialize_for(ProtocolVersion::Resp3),
+            Bytes::from(",1.5\r\n")
+        );
+        // Whole numbers and infinities are spelled the same way as before
+        assert_eq!(
+            RespValue::double(3.0).serialize_for(ProtocolVersion::Resp2),
+            Bytes::from("$1\r\n3\r\n")
+        );
+        assert_eq!(
+            RespValue::double(f64::NEG_INFINITY).serialize_for(ProtocolVersion::Resp2),
+            Bytes::from("$4\r\n-inf\r\n")
+        );
+        assert_eq!(
+            RespValue::double(f64::NEG_INFINITY).serialize_for(ProtocolVersion::Resp3),
+            Bytes::from(",-inf\r\n")
+        );
+    }
+
+    #[test]
+    fn test_serialize_for_keeps_resp2_types_identical() {
+        let val = RespValue::array(vec![
+            RespValue::simple_string("OK"),
+            RespValue::integer(42),
+            RespValue::bulk_string("foo"),
+            RespValue::null_bulk_string(),
+            RespValue::null_array(),
+        ]);
+
+        assert_eq!(
+            val.clone().serialize_for(ProtocolVersion::Resp2),
+            val.serialize()
+        );
+        assert_eq!(
+            val.clone().serialize_for(ProtocolVersion::Resp3),
+            val.serialize()
+        );
+    }
+
+    #[test]
+    fn test_lazy_aggregates_stream_only_when_large() {
+        let small = RespValue::lazy_map(vec![("a", 1)], |(key, value)| {
+            (RespValue::bulk_string(*key), RespValue::integer(*value))
+        });
+        assert_eq!(
+            small,
+            RespValue::map(vec![(RespValue::bulk_string("a"), RespValue::integer(1))])
+        );
+
+        let items: Vec<i64> = (0..=STREAMING_THRESHOLD as i64).collect();
+        let large = RespValue::lazy_set(items.clone(), |i| RespValue::integer(*i));
+        let RespValue::Streaming(reply) = &large else {
+            panic!("expected a streaming reply, got {:?}", large);
+        };
+        let built = RespValue::set(items.into_iter().map(RespValue::integer).collect());
+        assert_eq!(reply.materialize(), built);
+        assert_eq!(large.clone().into_resp2(), built.clone().into_resp2());
+        for protocol in [ProtocolVersion::Resp2, ProtocolVersion::Resp3] {
+            assert_eq!(
+                large.clone().serialize_for(protocol),
+                built.clone().serialize_for(protocol)
+            );
+        }
+    }
 }
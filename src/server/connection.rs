@@ -1,15 +1,20 @@
-use crate::command::CommandExecutor;
-use crate::error::Result;
+use crate::command::config::OutputBufferLimit;
+use crate::command::pubsub::{MessageQueue, PubSubMessage, Subscriber};
+use crate::command::server::AuthState;
+use crate::command::{ClientContext, CommandExecutor};
+use crate::error::{AikvError, Result};
 use crate::observability::Metrics;
-use crate::protocol::{RespParser, RespValue};
-use crate::server::monitor::MonitorBroadcaster;
+use crate::protocol::{RespParser, RespValue, StreamingReply, DEFAULT_MAX_MULTIBULK_LEN};
+use crate::server::monitor::{MonitorBroadcaster, MonitorMessage};
 use bytes::Bytes;
+use std::future::Future;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::select;
+use tokio::sync::{broadcast, Notify};
 use tracing::{debug, warn};
 
 static CLIENT_ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
@@ -18,12 +23,11 @@ static CLIENT_ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
 /// These are typically internal, debugging, or replication commands.
 const MONITOR_EXCLUDED_COMMANDS: &[&str] = &["MONITOR", "DEBUG", "SYNC", "PSYNC"];
 
-/// Protocol version
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum ProtocolVersion {
-    Resp2,
-    Resp3,
-}
+/// Pending pipeline replies are written out once they reach this many bytes,
+/// so a long pipeline or a streaming reply isn't buffered in full
+const REPLY_FLUSH_THRESHOLD: usize = 64 * 1024;
+
+pub use crate::protocol::ProtocolVersion;
 
 /// Connection mode
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -32,21 +36,41 @@ enum ConnectionMode {
     Monitor,
 }
 
+/// What woke up a connection in normal mode
+enum Wakeup {
+    /// The client sent this many bytes (0 on disconnect)
+    Read(usize),
+    /// A message arrived for one of the client's subscriptions
+    Message(PubSubMessage),
+}
+
 /// Connection handler for a single client
-pub struct Connection {
-    stream: TcpStream,
+///
+/// Generic over the client stream, so the same handler serves plain TCP and
+/// TLS connections.
+pub struct Connection<S = TcpStream> {
+    stream: S,
     parser: RespParser,
     executor: CommandExecutor,
-    protocol_version: ProtocolVersion,
-    current_db: usize,
-    client_id: usize,
+    /// Per-connection state handed to every command
+    client: ClientContext,
     metrics: Option<Arc<Metrics>>,
     client_addr: String,
     monitor_broadcaster: Option<Arc<MonitorBroadcaster>>,
     mode: ConnectionMode,
+    /// Commands run by other clients, once this one has sent MONITOR
+    monitor_feed: Option<broadcast::Receiver<MonitorMessage>>,
+    /// Notified by CLIENT KILL from another connection
+    kill_signal: Arc<Notify>,
+    /// Handed to the Pub/Sub hub to deliver messages to this client
+    subscriber: Subscriber,
+    /// Messages delivered for this client's subscriptions
+    messages: MessageQueue,
+    /// When the pending output went over the soft output buffer limit
+    over_soft_limit_since: Option<Instant>,
 }
 
-impl Connection {
+impl Connection<TcpStream> {
     /// Create a new connection handler.
     ///
     /// # Arguments
@@ -62,34 +86,69 @@ impl Connection {
         metrics: Option<Arc<Metrics>>,
         monitor_broadcaster: Option<Arc<MonitorBroadcaster>>,
     ) -> Self {
-        let client_id = CLIENT_ID_COUNTER.fetch_add(1, Ordering::SeqCst);
         let peer_addr = stream
             .peer_addr()
             .map(|addr| addr.to_string())
             .unwrap_or_else(|_| "unknown".to_string());
+        Self::with_peer_addr(stream, peer_addr, executor, metrics, monitor_broadcaster)
+    }
+}
+
+impl<S> Connection<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    /// Create a connection handler over any stream, such as a TLS stream,
+    /// for a client at `peer_addr`.
+    ///
+    /// The other arguments are as for [`Connection::new`].
+    pub fn with_peer_addr(
+        stream: S,
+        peer_addr: String,
+        executor: CommandExecutor,
+        metrics: Option<Arc<Metrics>>,
+        monitor_broadcaster: Option<Arc<MonitorBroadcaster>>,
+    ) -> Self {
+        let client_id = CLIENT_ID_COUNTER.fetch_add(1, Ordering::SeqCst);
 
         // Register client
-        if let Err(e) = executor
+        let kill_signal = executor
             .server_commands()
             .register_client(client_id, peer_addr.clone())
-        {
-            warn!("Failed to register client: {}", e);
-        }
+            .unwrap_or_else(|e| {
+                warn!("Failed to register client: {}", e);
+                Arc::new(Notify::new())
+            });
+        let (subscriber, messages) = Subscriber::channel();
 
         Self {
             stream,
-            parser: RespParser::new(8192),
+            parser: RespParser::with_limits(
+                8192,
+                executor.server_commands().proto_max_bulk_len(),
+                DEFAULT_MAX_MULTIBULK_LEN,
+            ),
             executor,
-            protocol_version: ProtocolVersion::Resp2, // Default to RESP2
-            current_db: 0,                            // Default to database 0
-            client_id,
+            client: ClientContext::new(client_id),
             metrics,
             client_addr: peer_addr,
             monitor_broadcaster,
             mode: ConnectionMode::Normal,
+            monitor_feed: None,
+            kill_signal,
+            subscriber,
+            messages,
+            over_soft_limit_since: None,
         }
     }
 
+    /// Require clients to authenticate with `password` before running commands.
+    ///
+    /// Must be called before `handle`; `None` leaves authentication disabled.
+    pub fn set_requirepass(&mut self, password: Option<Arc<str>>) {
+        self.client.auth = AuthState::new(password);
+    }
+
     /// Handle the connection using a state machine
     pub async fn handle(&mut self) -> Result<()> {
         loop {
@@ -113,8 +172,49 @@ impl Connection {
 
     /// Handle normal command mode. Returns false if connection should close.
     async fn handle_normal_mode(&mut self) -> Result<bool> {
-        // Read data from the client
-        let n = self.stream.read_buf(self.parser.buffer_mut()).await?;
+        // Read data from the client, unless another client kills us first or
+        // the client stays idle past the `timeout`; subscribed clients also
+        // wait for messages, and are never idle
+        let kill_signal = Arc::clone(&self.kill_signal);
+        let subscribed = self.client.is_subscribed();
+        let idle_timeout = if subscribed {
+            None
+        } else {
+            self.executor.server_commands().idle_timeout()
+        };
+        // A CONFIG SET proto-max-bulk-len applies to open connections too
+        self.parser
+            .set_max_bulk_len(self.executor.server_commands().proto_max_bulk_len());
+        let wakeup = select! {
+            result = Self::within(idle_timeout, self.stream.read_buf(self.parser.buffer_mut())) => {
+                match result {
+                    Some(result) => Wakeup::Read(result?),
+                    None => {
+                        debug!("Client {} idle for over {:?}, closing", self.client.client_id, idle_timeout);
+                        return Ok(false);
+                    }
+                }
+            }
+            Some(message) = self.messages.recv(), if subscribed => Wakeup::Message(message),
+            _ = kill_signal.notified() => {
+                debug!("Client {} killed", self.client.client_id);
+                return Ok(false);
+            }
+        };
+
+        let n = match wakeup {
+            Wakeup::Read(n) => n,
+            Wakeup::Message(message) => {
+                // Send whatever else has arrived along with it
+                let mut data = message.into_resp().serialize_for(self.client.protocol);
+                while let Some(message) = self.messages.try_recv() {
+                    data.extend_from_slice(
+                        &message.into_resp().serialize_for(self.client.protocol),
+                    );
+                }
+                return self.write_bytes(&data).await;
+            }
+        };
 
         if n == 0 {
             // Connection closed
@@ -126,18 +226,59 @@ impl Connection {
             metrics.connections.record_bytes_received(n as u64);
         }
 
-        // Parse and process commands
-        while let Some(value) = self.parser.parse()? {
-            let response = self.process_command(value).await;
-            self.write_response(response).await?;
+        // Run every command the read brought in; the replies of a pipeline
+        // are written together once the buffer is drained
+        let mut replies = Vec::new();
+        loop {
+            let value = match self.parser.parse() {
+                Ok(Some(value)) => value,
+                Ok(None) => break,
+                Err(e) => {
+                    // Like Redis, report the protocol error and close the connection
+                    replies.extend_from_slice(
+                        &Self::format_error_response(e).serialize_for(self.client.protocol),
+                    );
+                    self.write_bytes(&replies).await?;
+                    return Ok(false);
+                }
+            };
+            // (UN)SUBSCRIBE commands reply once per channel
+            let responses = match self.process_subscription(&value) {
+                Some(responses) => responses,
+                None => vec![self.process_command(value).await],
+            };
+            // Serialize right away: HELLO may switch protocols mid-pipeline
+            for response in responses {
+                if let RespValue::Streaming(reply) = response {
+                    if !self.write_streaming(&mut replies, &reply).await? {
+                        return Ok(false);
+                    }
+                } else {
+                    replies.extend_from_slice(&response.serialize_for(self.client.protocol));
+                }
+            }
 
             // Check if mode changed to monitor
             if self.mode == ConnectionMode::Monitor {
-                return Ok(true);
+                break;
+            }
+            if replies.len() >= REPLY_FLUSH_THRESHOLD {
+                if !self.write_bytes(&replies).await? {
+                    return Ok(false);
+                }
+                replies.clear();
             }
         }
 
-        Ok(true)
+        self.write_bytes(&replies).await
+    }
+
+    /// Wait for `future`, giving up with `None` after `limit`, if there is one
+    async fn within<F: Future>(limit: Option<Duration>, future: F) -> Option<F::Output> {
+        match limit {
+            Some(limit) => tokio::time::timeout(limit, future).await.ok(),
+            None => Some(future.await),
+        }
     }
 
     /// Handle monitor mode - stream all commands to this client.
@@ -152,10 +293,18 @@ impl Connection {
             }
         };
 
-        let mut receiver = broadcaster.subscribe();
+        let mut receiver = match self.monitor_feed.take() {
+            Some(receiver) => receiver,
+            None => broadcaster.subscribe(),
+        };
+        let kill_signal = Arc::clone(&self.kill_signal);
 
         loop {
             select! {
+                _ = kill_signal.notified() => {
+                    debug!("Monitor client {} killed", self.client.client_id);
+                    return Ok(false);
+                }
                 // Receive monitor messages
                 msg = receiver.recv() => {
                     match msg {
@@ -163,14 +312,18 @@ impl Connection {
                             // Format and send the monitor message
                             let formatted = monitor_msg.format();
                             let response = RespValue::simple_string(formatted);
-                            if let Err(e) = self.write_response(response).await {
-                                debug!("Monitor client write error: {}", e);
-                                return Ok(false);
+                            match self.write_response(response).await {
+                                Ok(true) => {}
+                                Ok(false) => return Ok(false),
+                                Err(e) => {
+                                    debug!("Monitor client write error: {}", e);
+                                    return Ok(false);
+                                }
                             }
                         }
                         Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
                             // We missed some messages due to slow reading
-                            debug!("Monitor client {} lagged behind by {} messages", self.client_id, n);
+                            debug!("Monitor client {} lagged behind by {} messages", self.client.client_id, n);
                         }
                         Err(tokio::sync::broadcast::error::RecvError::Closed) => {
                             // Broadcaster closed
@@ -183,7 +336,7 @@ impl Connection {
                     match result {
                         Ok(0) => {
                             // Client disconnected
-                            broadcaster.unregister_monitor(self.client_id).await;
+                            broadcaster.unregister_monitor(self.client.client_id).await;
                             return Ok(false);
                         }
                         Ok(_) => {
@@ -194,14 +347,13 @@ impl Connection {
                                         if let RespValue::BulkString(Some(cmd)) = &arr[0] {
                                             let command = String::from_utf8_lossy(cmd).to_uppercase();
                                             if command == "QUIT" {
-                                                broadcaster.unregister_monitor(self.client_id).await;
+                                                broadcaster.unregister_monitor(self.client.client_id).await;
                                                 self.write_response(RespValue::ok()).await?;
                                                 return Ok(false);
                                             } else if command == "RESET" {
-                                                broadcaster.unregister_monitor(self.client_id).await;
+                                                broadcaster.unregister_monitor(self.client.client_id).await;
                                                 self.mode = ConnectionMode::Normal;
-                                                self.write_response(RespValue::simple_string("RESET")).await?;
-                                                return Ok(true);
+                                                return self.write_response(RespValue::simple_string("RESET")).await;
                                             }
                                         }
                                     }
@@ -210,7 +362,7 @@ impl Connection {
                         }
                         Err(e) => {
                             debug!("Monitor client read error: {}", e);
-                            broadcaster.unregister_monitor(self.client_id).await;
+                            broadcaster.unregister_monitor(self.client.client_id).await;
                             return Ok(false);
                         }
                     }
@@ -225,15 +377,19 @@ impl Connection {
         if let Err(e) = self
             .executor
             .server_commands()
-            .unregister_client(self.client_id)
+            .unregister_client(self.client.client_id)
         {
             warn!("Failed to unregister client: {}", e);
         }
 
+        if let Err(e) = self.executor.pubsub().remove_client(self.client.client_id) {
+            warn!("Failed to drop client subscriptions: {}", e);
+        }
+
         // Unregister from monitor if in monitor mode
         if self.mode == ConnectionMode::Monitor {
             if let Some(ref broadcaster) = self.monitor_broadcaster {
-                broadcaster.unregister_monitor(self.client_id).await;
+                broadcaster.unregister_monitor(self.client.client_id).await;
             }
         }
     }
@@ -253,14 +409,29 @@ impl Connection {
 
                 let command_upper = command.to_uppercase();
 
-                // Handle HELLO command for protocol version negotiation
-                if command_upper == "HELLO" {
-                    return self.handle_hello(&arr[1..]);
-                }
-
-                // Handle MONITOR command
-                if command_upper == "MONITOR" {
-                    return self.handle_monitor().await;
+                // A RESP2 connection in subscribe mode shares its replies with
+                // pushed messages, so only commands that fit that shape run
+                if self.client.is_subscribed() && self.client.protocol == ProtocolVersion::Resp2 {
+                    match command_upper.as_str() {
+                        "PING" => {
+                            let message = match arr.get(1) {
+                                Some(RespValue::BulkString(Some(message))) => message.clone(),
+                                _ => Bytes::new(),
+                            };
+                            return RespValue::push(vec![
+                                RespValue::bulk_string("pong"),
+                                RespValue::bulk_string(message),
+                            ]);
+                        }
+                        "QUIT" | "RESET" => {}
+                        _ => {
+                            return RespValue::error(format!(
+                                "ERR Can't execute '{}': only (P|S)SUBSCRIBE / \
+                                 (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+                                command.to_lowercase()
+                            ));
+                        }
+                    }
                 }
 
                 let args: Vec<Bytes> = arr[1..]
@@ -271,6 +442,53 @@ impl Connection {
                     })
                     .collect();
 
+                // AUTH, HELLO and RESET change connection state, so they're handled here
+                if command_upper == "RESET" {
+                    return self.reset();
+                }
+
+                if command_upper == "AUTH" {
+                    return match self
+                        .executor
+                        .server_commands()
+                        .auth(&args, &mut self.client)
+                    {
+                        Ok(resp) => resp,
+                        Err(e) => Self::format_error_response(e),
+                    };
+                }
+
+                if !self.client.auth.authenticated
+                    && !matches!(command_upper.as_str(), "HELLO" | "PING")
+                {
+                    return Self::format_error_response(AikvError::NoAuth(
+                        "Authentication required.".to_string(),
+                    ));
+                }
+
+                if command_upper == "HELLO" {
+                    return match self
+                        .executor
+                        .server_commands()
+                        .hello(&args, &mut self.client)
+                    {
+                        Ok(resp) => resp,
+                        Err(e) => Self::format_error_response(e),
+                    };
+                }
+
+                // MONITOR sees every command, AUTH passwords included, so it
+                // is only handled once the client has authenticated
+                if command_upper == "MONITOR" {
+                    if let Err(e) = self
+                        .executor
+                        .check_permission(&self.client, "MONITOR", &args)
+                    {
+                        return Self::format_error_response(e);
+                    }
+                    return self.handle_monitor().await;
+                }
+
                 // Broadcast to monitors (except excluded internal/debugging commands)
                 if !MONITOR_EXCLUDED_COMMANDS.contains(&command_upper.as_str()) {
                     self.broadcast_to_monitors(&command_upper, &args);
@@ -288,10 +506,18 @@ impl Connection {
                             | "ADDSLOTS"
                             | "ADDSLOTSRANGE"
                             | "DELSLOTS"
+                            | "FAILOVER"
+                            | "SETSLOT"
                             | "REPLICATE"
                             | "ADDREPLICATION"
                             | "METARAFT"
                     ) {
+                        if let Err(e) =
+                            self.executor
+                                .check_permission(&self.client, "CLUSTER", &args)
+                        {
+                            return Self::format_error_response(e);
+                        }
                         if let Some(cluster_cmds) = self.executor.cluster_commands() {
                             let result = self
                                 .handle_async_cluster_command(cluster_cmds, &subcommand, &args[1..])
@@ -310,7 +536,7 @@ impl Connection {
                                             command = %format!("CLUSTER {}", subcommand),
                                             duration_us = duration.as_micros(),
                                             client = %self.client_addr,
-                                            db = self.current_db,
+                                            db = self.client.db,
                                             "Async cluster command executed"
                                         );
                                     }
@@ -332,9 +558,42 @@ impl Connection {
                     }
                 }
 
-                let result =
-                    self.executor
-                        .execute(&command, &args, &mut self.current_db, self.client_id);
+                // WAIT blocks on replica acknowledgements, so in cluster mode it
+                // runs asynchronously against the Raft groups
+                #[cfg(feature = "cluster")]
+                let result = match self.executor.cluster_commands() {
+                    Some(cluster_cmds) if command_upper == "WAIT" => {
+                        match self
+                            .executor
+                            .check_permission(&self.client, "WAIT", &args)
+                            .and_then(|()| {
+                                crate::command::server::ServerCommands::parse_wait_args(&args)
+                            }) {
+                            Ok((numreplicas, timeout)) => {
+                                cluster_cmds.wait(numreplicas, timeout).await
+                            }
+                            Err(e) => Err(e),
+                        }
+                    }
+                    _ if command_upper == "MIGRATE" => {
+                        self.executor.execute_migrate(&args, &mut self.client).await
+                    }
+                    _ if command_upper == "BLMPOP" || command_upper == "BZMPOP" => {
+                        self.block_on_pop(&command, &args).await
+                    }
+                    _ => self.executor.execute(&command, &args, &mut self.client),
+                };
+
+                // MIGRATE transfers its keys on a blocking thread, and the
+                // blocking pops wait for writes from other clients
+                #[cfg(not(feature = "cluster"))]
+                let result = if command_upper == "MIGRATE" {
+                    self.executor.execute_migrate(&args, &mut self.client).await
+                } else if command_upper == "BLMPOP" || command_upper == "BZMPOP" {
+                    self.block_on_pop(&command, &args).await
+                } else {
+                    self.executor.execute(&command, &args, &mut self.client)
+                };
 
                 // Record metrics
                 if let Some(ref metrics) = self.metrics {
@@ -346,7 +605,7 @@ impl Connection {
                                 command = %command,
                                 duration_us = duration.as_micros(),
                                 client = %self.client_addr,
-                                db = self.current_db,
+                                db = self.client.db,
                                 "Command executed"
                             );
                         }
@@ -356,6 +615,21 @@ impl Connection {
                     }
                 }
 
+                let duration = start.elapsed();
+                let slow_query_log = self.executor.server_commands().slow_query_log();
+                if slow_query_log.is_slow(duration) {
+                    let args: Vec<String> = args
+                        .iter()
+                        .map(|arg| String::from_utf8_lossy(arg).to_string())
+                        .collect();
+                    slow_query_log.record(
+                        &command,
+                        &args,
+                        duration,
+                        Some(self.client_addr.clone()),
+                    );
+                }
+
                 match result {
                     Ok(resp) => resp,
                     Err(e) => Self::format_error_response(e),
@@ -365,12 +639,49 @@ impl Connection {
         }
     }
 
+    /// Run BLMPOP or BZMPOP, which may wait for another client's write
+    ///
+    /// Commands the client pipelines meanwhile are buffered for after the
+    /// reply. The pop is given up if the client disconnects or is killed, so
+    /// it can't take elements nobody would receive.
+    async fn block_on_pop(&mut self, command: &str, args: &[Bytes]) -> Result<RespValue> {
+        let kill_signal = Arc::clone(&self.kill_signal);
+        let pop = self
+            .executor
+            .execute_blocking_pop(command, args, &mut self.client);
+        tokio::pin!(pop);
+        loop {
+            select! {
+                result = &mut pop => return result,
+                read = self.stream.read_buf(self.parser.buffer_mut()) => match read {
+                    Ok(n) if n > 0 => {
+                        if let Some(ref metrics) = self.metrics {
+                            metrics.connections.record_bytes_received(n as u64);
+                        }
+                    }
+                    Ok(_) => {
+                        return Err(AikvError::Internal(
+                            "client disconnected while blocked".to_string(),
+                        ))
+                    }
+                    Err(e) => return Err(e.into()),
+                },
+                _ = kill_signal.notified() => {
+                    // Leave the kill for the read loop to act on
+                    kill_signal.notify_one();
+                    return Err(AikvError::Internal(
+                        "client killed while blocked".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+
     /// Format an error into a RESP error response.
     ///
     /// Cluster-specific errors (MOVED, ASK, CROSSSLOT) have special formats
     /// that Redis clients expect.
-    fn format_error_response(e: crate::error::AikvError) -> RespValue {
-        use crate::error::AikvError;
+    fn format_error_response(e: AikvError) -> RespValue {
         match e {
             // Cluster redirection errors - format without "ERR " prefix
             AikvError::Moved(slot, addr) => RespValue::error(format!("MOVED {} {}", slot, addr)),
@@ -378,6 +689,16 @@ impl Connection {
             AikvError::CrossSlot => {
                 RespValue::error("CROSSSLOT Keys in request don't hash to the same slot")
             }
+            // These carry their own error codes in Redis
+            AikvError::OutOfMemory
+            | AikvError::WrongType(_)
+            | AikvError::BusyKey
+            | AikvError::IoErr(_)
+            | AikvError::Loading
+            | AikvError::NoProto
+            | AikvError::WrongPass
+            | AikvError::NoAuth(_)
+            | AikvError::NoPerm(_) => RespValue::error(e.to_string()),
             // All other errors use the standard "ERR " prefix
             _ => RespValue::error(format!("ERR {}", e)),
         }
@@ -392,7 +713,7 @@ impl Connection {
                     .map(|b| String::from_utf8_lossy(b).to_string())
                     .collect();
                 broadcaster.broadcast_command(
-                    self.current_db,
+                    self.client.db,
                     &self.client_addr,
                     command,
                     &args_str,
@@ -409,8 +730,6 @@ impl Connection {
         subcommand: &str,
         args: &[Bytes],
     ) -> Result<RespValue> {
-        use crate::error::AikvError;
-
         match subcommand {
             "MEET" => {
                 // CLUSTER MEET ip port [node-id]
@@ -537,6 +856,20 @@ impl Connection {
 
                 cluster_cmds.cluster_replicate(master_id).await
             }
+            "FAILOVER" => {
+                // CLUSTER FAILOVER [FORCE|TAKEOVER]
+                let mode = crate::cluster::FailoverMode::parse(args)?;
+                cluster_cmds.cluster_failover(mode).await
+            }
+            "SETSLOT" => {
+                // CLUSTER SETSLOT slot IMPORTING|MIGRATING|NODE node_id, or STABLE
+                if args.is_empty() {
+                    return Err(AikvError::WrongArgCount("CLUSTER SETSLOT".to_string()));
+                }
+                let slot = crate::cluster::parse_slot(&args[0])?;
+                let action = crate::cluster::SetSlotAction::parse(&args[1..])?;
+                cluster_cmds.cluster_setslot(slot, action).await
+            }
             "ADDREPLICATION" => {
                 // CLUSTER ADDREPLICATION replica_node_id master_node_id
                 // This command is sent to the leader to add a replica to a master's group
@@ -652,11 +985,135 @@ impl Connection {
         }
     }
 
+    /// Handle SUBSCRIBE, UNSUBSCRIBE, PSUBSCRIBE and PUNSUBSCRIBE, which
+    /// change the connection's subscriptions and reply once per channel or
+    /// pattern; `None` for any other command
+    fn process_subscription(&mut self, value: &RespValue) -> Option<Vec<RespValue>> {
+        let RespValue::Array(Some(arr)) = value else {
+            return None;
+        };
+        let Some(RespValue::BulkString(Some(command))) = arr.first() else {
+            return None;
+        };
+        let command = String::from_utf8_lossy(command).to_uppercase();
+        let pattern = match command.as_str() {
+            "SUBSCRIBE" | "UNSUBSCRIBE" => false,
+            "PSUBSCRIBE" | "PUNSUBSCRIBE" => true,
+            _ => return None,
+        };
+        // Let process_command refuse unauthenticated clients
+        if !self.client.auth.authenticated {
+            return None;
+        }
+
+        let mut names: Vec<Bytes> = arr[1..]
+            .iter()
+            .filter_map(|v| match v {
+                RespValue::BulkString(Some(b)) => Some(b.clone()),
+                _ => None,
+            })
+            .collect();
+        let subscribe = matches!(command.as_str(), "SUBSCRIBE" | "PSUBSCRIBE");
+        if subscribe && names.is_empty() {
+            return Some(vec![Self::format_error_response(AikvError::WrongArgCount(
+                command,
+            ))]);
+        }
+        if let Err(e) = self
+            .executor
+            .check_permission(&self.client, &command, &names)
+        {
+            return Some(vec![Self::format_error_response(e)]);
+        }
+        self.broadcast_to_monitors(&command, &names);
+
+        let pubsub = Arc::clone(self.executor.pubsub());
+        let subscriptions = if pattern {
+            &mut self.client.patterns
+        } else {
+            &mut self.client.channels
+        };
+        // Without arguments, unsubscribing drops every subscription of its kind
+        if names.is_empty() {
+            names = subscriptions.iter().cloned().collect();
+            names.sort();
+        }
+
+        let mut replies = Vec::new();
+        let kind = command.to_lowercase();
+        for name in names {
+            let result = match (subscribe, pattern) {
+                (true, false) => {
+                    pubsub.subscribe(self.client.client_id, name.clone(), &self.subscriber)
+                }
+                (true, true) => {
+                    pubsub.psubscribe(self.client.client_id, name.clone(), &self.subscriber)
+                }
+                (false, false) => pubsub.unsubscribe(self.client.client_id, &name),
+                (false, true) => pubsub.punsubscribe(self.client.client_id, &name),
+            };
+            if let Err(e) = result {
+                replies.push(Self::format_error_response(e));
+                continue;
+            }
+            let subscriptions = if pattern {
+                &mut self.client.patterns
+            } else {
+                &mut self.client.channels
+            };
+            if subscribe {
+                subscriptions.insert(name.clone());
+            } else {
+                subscriptions.remove(&name);
+            }
+            let count = self.client.subscription_count();
+            replies.push(RespValue::push(vec![
+                RespValue::bulk_string(kind.clone()),
+                RespValue::bulk_string(name),
+                RespValue::integer(count as i64),
+            ]));
+        }
+
+        // Messages still queued for dropped subscriptions are stale
+        if !self.client.is_subscribed() {
+            self.messages.clear();
+        }
+
+        // Unsubscribing from nothing still gets a reply
+        if replies.is_empty() {
+            let count = self.client.subscription_count();
+            replies.push(RespValue::push(vec![
+                RespValue::bulk_string(kind),
+                RespValue::null_bulk_string(),
+                RespValue::integer(count as i64),
+            ]));
+        }
+        Some(replies)
+    }
+
+    /// Handle RESET: put the connection back the way it was when it connected
+    fn reset(&mut self) -> RespValue {
+        if let Err(e) = self.executor.pubsub().remove_client(self.client.client_id) {
+            warn!("Failed to drop client subscriptions: {}", e);
+        }
+        self.client.channels.clear();
+        self.client.patterns.clear();
+        self.messages.clear();
+        match self.executor.server_commands().reset(&mut self.client) {
+            Ok(resp) => resp,
+            Err(e) => Self::format_error_response(e),
+        }
+    }
+
     /// Handle MONITOR command
+    ///
+    /// The feed is subscribed to before replying, so no command another
+    /// client runs after seeing our +OK can slip past the monitor.
     async fn handle_monitor(&mut self) -> RespValue {
         if let Some(ref broadcaster) = self.monitor_broadcaster {
+            self.monitor_feed = Some(broadcaster.subscribe());
             broadcaster
-                .register_monitor(self.client_id, self.client_addr.clone())
+                .register_monitor(self.client.client_id, self.client_addr.clone())
                 .await;
             self.mode = ConnectionMode::Monitor;
             RespValue::ok()
@@ -665,65 +1122,99 @@ impl Connection {
         }
     }
 
-    fn handle_hello(&mut self, args: &[RespValue]) -> RespValue {
-        if args.is_empty() {
-            return RespValue::error("ERR wrong number of arguments for 'hello' command");
+    /// Serialize a streaming reply onto `replies` one item at a time,
+    /// writing out what has built up whenever it passes the flush threshold,
+    /// so the whole reply is never held serialized
+    async fn write_streaming(
+        &mut self,
+        replies: &mut Vec<u8>,
+        reply: &StreamingReply,
+    ) -> Result<bool> {
+        let protocol = self.client.protocol;
+        replies.extend_from_slice(&reply.header(protocol));
+        for index in 0..reply.len() {
+            reply.write_item(index, protocol, replies);
+            if replies.len() >= REPLY_FLUSH_THRESHOLD {
+                if !self.write_bytes(replies).await? {
+                    return Ok(false);
+                }
+                replies.clear();
+            }
         }
+        Ok(true)
+    }
 
-        // Parse protocol version
-        let version_str = match &args[0] {
-            RespValue::BulkString(Some(v)) => String::from_utf8_lossy(v).to_string(),
-            _ => return RespValue::error("ERR invalid protocol version"),
-        };
-
-        let version = match version_str.as_str() {
-            "2" => ProtocolVersion::Resp2,
-            "3" => ProtocolVersion::Resp3,
-            _ => return RespValue::error("NOPROTO unsupported protocol version"),
-        };
+    async fn write_response(&mut self, response: RespValue) -> Result<bool> {
+        let data = response.serialize_for(self.client.protocol);
+        self.write_bytes(&data).await
+    }
 
-        self.protocol_version = version;
-
-        // Build response based on protocol version
-        match self.protocol_version {
-            ProtocolVersion::Resp2 => {
-                // RESP2 response: array
-                RespValue::array(vec![
-                    RespValue::bulk_string("server"),
-                    RespValue::bulk_string("aikv"),
-                    RespValue::bulk_string("version"),
-                    RespValue::bulk_string("0.1.0"),
-                    RespValue::bulk_string("proto"),
-                    RespValue::integer(2),
-                ])
-            }
-            ProtocolVersion::Resp3 => {
-                // RESP3 response: map
-                RespValue::map(vec![
-                    (
-                        RespValue::simple_string("server"),
-                        RespValue::simple_string("aikv"),
-                    ),
-                    (
-                        RespValue::simple_string("version"),
-                        RespValue::simple_string("0.1.0"),
-                    ),
-                    (RespValue::simple_string("proto"), RespValue::integer(3)),
-                ])
-            }
+    /// The output buffer limit of this client's class
+    ///
+    /// There are no replica connections, and MONITOR clients count as
+    /// normal clients, as they do in Redis.
+    fn output_buffer_limit(&self) -> OutputBufferLimit {
+        let limits = self.executor.server_commands().output_buffer_limits();
+        if self.client.is_subscribed() {
+            limits.pubsub
+        } else {
+            limits.normal
         }
     }
 
-    async fn write_response(&mut self, response: RespValue) -> Result<()> {
-        let data = response.serialize();
+    /// Write already serialized replies and flush them
+    ///
+    /// Returns false if the client's pending output (the data being written
+    /// plus any queued messages) overran its `client-output-buffer-limit`,
+    /// in which case the connection should be closed. The limit is checked
+    /// again whenever a message is queued while the write is stuck on a
+    /// client that isn't reading.
+    async fn write_bytes(&mut self, data: &[u8]) -> Result<bool> {
+        if data.is_empty() {
+            return Ok(true);
+        }
 
         // Record bytes sent
         if let Some(ref metrics) = self.metrics {
             metrics.connections.record_bytes_sent(data.len() as u64);
         }
 
-        self.stream.write_all(&data).await?;
-        self.stream.flush().await?;
-        Ok(())
+        let limit = self.output_buffer_limit();
+        let queue = &self.messages;
+        let over_soft_since = &mut self.over_soft_limit_since;
+        let pending = |queue: &MessageQueue| (data.len() + queue.queued_bytes()) as u64;
+
+        if !limit.exceeded(pending(queue), over_soft_since) {
+            let stream = &mut self.stream;
+            let write = async {
+                stream.write_all(data).await?;
+                stream.flush().await
+            };
+            tokio::pin!(write);
+            loop {
+                let deadline = limit.soft_deadline(*over_soft_since);
+                select! {
+                    result = &mut write => {
+                        result?;
+                        // Whatever was queued meanwhile is still pending
+                        if !limit.exceeded(queue.queued_bytes() as u64, over_soft_since) {
+                            return Ok(true);
+                        }
+                        break;
+                    }
+                    _ = queue.grown() => {}
+                    _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now).into()), if deadline.is_some() => {}
+                }
+                if limit.exceeded(pending(queue), over_soft_since) {
+                    break;
+                }
+            }
+        }
+
+        warn!(
+            "Client {} ({}) overran its output buffer limit, closing",
+            self.client.client_id, self.client_addr
+        );
+        Ok(false)
     }
 }
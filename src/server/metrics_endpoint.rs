@@ -0,0 +1,111 @@
+//! HTTP endpoint serving metrics to Prometheus
+//!
+//! A deliberately small HTTP/1.1 responder: it answers `GET /metrics` with the
+//! text exposition format and closes the connection after every response.
+
+use crate::observability::Metrics;
+use crate::storage::StorageEngine;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+use tracing::{debug, warn};
+
+/// Largest request head accepted before the connection is dropped
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+/// Time a client gets to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Accept scrape requests on `listener` until the task is dropped
+pub async fn serve(listener: TcpListener, metrics: Arc<Metrics>, storage: StorageEngine) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, addr)) => {
+                let metrics = Arc::clone(&metrics);
+                let storage = storage.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle(stream, &metrics, &storage).await {
+                        debug!("Metrics request from {} failed: {}", addr, e);
+                    }
+                });
+            }
+            Err(e) => warn!("Failed to accept metrics connection: {}", e),
+        }
+    }
+}
+
+/// Render the server metrics plus memory usage and per-database key counts
+pub fn render(metrics: &Metrics, storage: &StorageEngine) -> String {
+    if let Ok(used) = storage.used_memory() {
+        metrics.memory.set_used_memory(used as u64);
+    }
+    let mut output = metrics.export_prometheus();
+
+    output.push_str("# HELP aikv_db_keys Number of keys per database\n");
+    output.push_str("# TYPE aikv_db_keys gauge\n");
+    for db in 0..storage.db_count() {
+        match storage.dbsize_in_db(db) {
+            Ok(0) | Err(_) => {}
+            Ok(keys) => output.push_str(&format!("aikv_db_keys{{db=\"{}\"}} {}\n", db, keys)),
+        }
+    }
+
+    output
+}
+
+async fn handle(
+    mut stream: TcpStream,
+    metrics: &Metrics,
+    storage: &StorageEngine,
+) -> std::io::Result<()> {
+    let Some(head) = timeout(REQUEST_TIMEOUT, read_head(&mut stream))
+        .await
+        .unwrap_or(Ok(None))?
+    else {
+        return Ok(());
+    };
+
+    let request_line = head.lines().next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    // Ignore any query string
+    let path = path.split('?').next().unwrap_or_default();
+
+    let (status, body) = match (method, path) {
+        ("GET", "/metrics") => ("200 OK", render(metrics, storage)),
+        ("GET", _) => ("404 Not Found", "Not Found\n".to_string()),
+        _ => ("405 Method Not Allowed", "Method Not Allowed\n".to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Read up to the blank line ending the request head, or `None` if the client
+/// hung up or sent too much
+async fn read_head(stream: &mut TcpStream) -> std::io::Result<Option<String>> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if buf.windows(4).any(|w| w == b"\r\n\r\n") {
+            return Ok(Some(String::from_utf8_lossy(&buf).into_owned()));
+        }
+        if buf.len() > MAX_REQUEST_HEAD {
+            return Ok(None);
+        }
+    }
+}
@@ -1,22 +1,56 @@
 pub mod connection;
+pub mod metrics_endpoint;
 pub mod monitor;
+pub mod tls;
 
+pub use crate::command::server::{ShutdownSave, ShutdownSignal};
 pub use monitor::{MonitorBroadcaster, MonitorMessage};
+pub use tls::TlsConfig;
 
 use self::connection::Connection;
+use crate::command::acl::{self, AclUsers};
+use crate::command::blocking::BlockedClients;
+use crate::command::config::{RuntimeConfig, SharedConfig};
+use crate::command::pubsub::{KeyspaceEvents, PubSub};
+use crate::command::script::ScriptRegistry;
+use crate::command::server::{ClientRegistry, RdbState, ShutdownSave};
 use crate::command::CommandExecutor;
 use crate::error::Result;
-use crate::observability::Metrics;
+use crate::observability::{Metrics, SlowQueryLog};
+use crate::persistence::AofWriter;
 use crate::storage::StorageEngine;
+use socket2::{SockRef, TcpKeepalive};
 use std::net::SocketAddr;
-use std::sync::Arc;
-use tokio::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+use tokio_rustls::TlsAcceptor;
 use tracing::warn;
 use tracing::{error, info};
 
 #[cfg(feature = "cluster")]
 use crate::cluster::{ClusterCommands, MetaRaftNode, MultiRaftNode, Router};
 
+/// How often the background sweeper deletes expired keys (Redis `hz` 10)
+const ACTIVE_EXPIRE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a shutdown waits for connections to finish their commands
+/// (Redis `shutdown-timeout`)
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Keeps a client counted in `connected_clients` until dropped
+struct ConnectedClient(Arc<Metrics>);
+
+impl Drop for ConnectedClient {
+    fn drop(&mut self) {
+        self.0.connections.record_disconnection();
+    }
+}
+
 /// AiKv server
 pub struct Server {
     addr: String,
@@ -24,6 +58,38 @@ pub struct Server {
     storage: StorageEngine,
     metrics: Arc<Metrics>,
     monitor_broadcaster: Arc<MonitorBroadcaster>,
+    /// Slow log shared by every connection
+    slow_query_log: Arc<SlowQueryLog>,
+    /// Runtime configuration shared by every connection (CONFIG GET/SET)
+    config: SharedConfig,
+    /// Password clients must AUTH with, if any
+    requirepass: Option<Arc<str>>,
+    /// Connected clients, shared by every connection for CLIENT LIST/KILL
+    clients: ClientRegistry,
+    /// ACL users, shared by every connection
+    acl_users: AclUsers,
+    /// Append-only log shared by every connection, if AOF is enabled
+    aof: Option<AofWriter>,
+    /// RDB snapshot file and last save time, shared by every connection
+    rdb: Arc<RdbState>,
+    /// Whether a shutdown writes a final RDB snapshot
+    save_on_shutdown: bool,
+    /// Stops the accept loop and makes `run` return
+    shutdown: ShutdownSignal,
+    /// Scripts running on any connection, for SCRIPT KILL
+    scripts: Arc<ScriptRegistry>,
+    /// Whether the expiration sweeper runs, toggled by DEBUG SET-ACTIVE-EXPIRE
+    active_expire: Arc<AtomicBool>,
+    /// Raised while the dataset is loaded on startup; see [`Server::loading_flag`]
+    loading: Arc<AtomicBool>,
+    /// Pub/Sub channels and keyspace notifications shared by every connection
+    pubsub: Arc<PubSub>,
+    /// Clients blocked by BLMPOP and BZMPOP, woken by writes from any connection
+    blocked_clients: Arc<BlockedClients>,
+    /// Address of the Prometheus `/metrics` endpoint, if enabled
+    metrics_addr: Option<String>,
+    /// Address and acceptor of the TLS listener, if enabled
+    tls: Option<(String, TlsAcceptor)>,
     #[cfg(feature = "cluster")]
     node_id: u64,
     #[cfg(feature = "cluster")]
@@ -64,6 +130,22 @@ impl Server {
             storage,
             metrics: Arc::new(Metrics::new()),
             monitor_broadcaster: Arc::new(MonitorBroadcaster::new()),
+            slow_query_log: Arc::new(SlowQueryLog::new()),
+            config: Arc::new(RwLock::new(RuntimeConfig::new(port))),
+            requirepass: None,
+            clients: ClientRegistry::default(),
+            acl_users: acl::default_users(),
+            aof: None,
+            rdb: Arc::new(RdbState::default()),
+            save_on_shutdown: false,
+            shutdown: ShutdownSignal::new(),
+            scripts: Arc::new(ScriptRegistry::new()),
+            active_expire: Arc::new(AtomicBool::new(true)),
+            loading: Arc::new(AtomicBool::new(false)),
+            pubsub: Arc::new(PubSub::new()),
+            blocked_clients: Arc::new(BlockedClients::new()),
+            metrics_addr: None,
+            tls: None,
             #[cfg(feature = "cluster")]
             node_id,
             #[cfg(feature = "cluster")]
@@ -250,6 +332,84 @@ impl Server {
         Ok(())
     }
 
+    /// Log every write command from every connection to `aof`.
+    ///
+    /// Replay any existing log (see [`CommandExecutor::replay_aof`]) before calling
+    /// this, so the replayed commands aren't appended a second time.
+    pub fn set_aof_writer(&mut self, aof: AofWriter) {
+        self.aof = Some(aof);
+    }
+
+    /// Write SAVE/BGSAVE snapshots to `path` (default `dump.rdb`)
+    pub fn set_rdb_path(&mut self, path: impl Into<PathBuf>) {
+        self.rdb = Arc::new(RdbState::new(path));
+    }
+
+    /// Write a final snapshot when the server shuts down (off by default)
+    pub fn set_save_on_shutdown(&mut self, enabled: bool) {
+        self.save_on_shutdown = enabled;
+    }
+
+    /// Write snapshots with (default) or without a CRC64 checksum
+    pub fn set_rdb_checksum(&mut self, enabled: bool) {
+        self.rdb.set_checksum(enabled);
+    }
+
+    /// Serve Prometheus metrics over HTTP at `addr` (`GET /metrics`)
+    pub fn set_metrics_addr(&mut self, addr: impl Into<String>) {
+        self.metrics_addr = Some(addr.into());
+    }
+
+    /// Accept at most `maxclients` simultaneous clients (Redis `maxclients`,
+    /// default 10000); clients beyond that are rejected
+    pub fn set_maxclients(&mut self, maxclients: u64) {
+        if let Ok(mut config) = self.config.write() {
+            config.set("maxclients", maxclients.to_string());
+        }
+    }
+
+    /// Close connections idle for more than `seconds` (Redis `timeout`);
+    /// 0 keeps idle connections open
+    pub fn set_idle_timeout(&mut self, seconds: u64) {
+        if let Ok(mut config) = self.config.write() {
+            config.set("timeout", seconds.to_string());
+        }
+    }
+
+    /// Send TCP keepalive probes every `seconds` on client sockets (Redis
+    /// `tcp-keepalive`, default 300); 0 disables them
+    pub fn set_tcp_keepalive(&mut self, seconds: u64) {
+        if let Ok(mut config) = self.config.write() {
+            config.set("tcp-keepalive", seconds.to_string());
+        }
+    }
+
+    /// Also accept TLS connections at `addr` (Redis `tls-port`), using the
+    /// certificates in `config`
+    ///
+    /// Fails if the certificate files can't be loaded.
+    pub fn set_tls(&mut self, addr: impl Into<String>, config: &TlsConfig) -> Result<()> {
+        self.tls = Some((addr.into(), config.acceptor()?));
+        Ok(())
+    }
+
+    /// Require clients to authenticate with `password` (Redis `requirepass`).
+    ///
+    /// `None` or an empty password disables authentication.
+    pub fn set_requirepass(&mut self, password: Option<String>) {
+        self.requirepass = password.filter(|p| !p.is_empty()).map(Arc::from);
+    }
+
+    /// Get the slow log shared by every connection (SLOWLOG)
+    pub fn slow_query_log(&self) -> Arc<SlowQueryLog> {
+        Arc::clone(&self.slow_query_log)
+    }
+
+    /// Get the runtime configuration shared by every connection (CONFIG GET/SET)
+    pub fn config(&self) -> SharedConfig {
+        Arc::clone(&self.config)
+    }
+
     /// Get server metrics
     pub fn metrics(&self) -> Arc<Metrics> {
         Arc::clone(&self.metrics)
@@ -260,21 +420,126 @@ impl Server {
         Arc::clone(&self.monitor_broadcaster)
     }
 
-    /// Run the server
+    /// Get the flag that marks the dataset as still loading, for loading it
+    /// while `run` already accepts clients
+    ///
+    /// While it is set, commands not flagged `loading` (everything but PING,
+    /// INFO and the like) are answered with `-LOADING`, so clients don't see a
+    /// half-loaded dataset; clear it once loading is done.
+    pub fn loading_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.loading)
+    }
+
+    /// Get the signal that shuts the server down, for triggering it while
+    /// `run` owns the server
+    pub fn shutdown_signal(&self) -> ShutdownSignal {
+        self.shutdown.clone()
+    }
+
+    /// Ask the server to shut down gracefully (see [`Server::run`]), saving
+    /// as configured
+    pub fn shutdown(&self) {
+        self.shutdown.trigger();
+    }
+
+    /// Run the server until it is shut down
+    ///
+    /// Once the shutdown signal fires, no new connections are accepted, open
+    /// connections finish the commands they're running and close (waiting at
+    /// most a grace period), the AOF is flushed and, if enabled, a final
+    /// snapshot is written before returning.
     pub async fn run(&self) -> Result<()> {
+        let tls_listener = match &self.tls {
+            Some((tls_addr, acceptor)) => {
+                let tls_listener = TcpListener::bind(tls_addr).await?;
+                info!("AiKv server listening for TLS on {}", tls_addr);
+                Some((tls_listener, acceptor.clone()))
+            }
+            None => None,
+        };
+
         let listener = TcpListener::bind(&self.addr).await?;
         info!("AiKv server listening on {}", self.addr);
 
-        loop {
-            match listener.accept().await {
-                Ok((stream, addr)) => {
+        // Tasks that live as long as the server; dropping the set stops them
+        let mut background = JoinSet::new();
+        if let Some(metrics_addr) = &self.metrics_addr {
+            let metrics_listener = TcpListener::bind(metrics_addr).await?;
+            info!(
+                "Metrics endpoint listening on http://{}/metrics",
+                metrics_addr
+            );
+            background.spawn(metrics_endpoint::serve(
+                metrics_listener,
+                Arc::clone(&self.metrics),
+                self.storage.clone(),
+            ));
+        }
+
+        background.spawn(Self::sweep_expired(
+            self.storage.clone(),
+            Arc::clone(&self.active_expire),
+            Arc::clone(&self.metrics),
+            Arc::clone(&self.pubsub),
+        ));
+
+        let mut connections = JoinSet::new();
+        let save = loop {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted.map(|(stream, addr)| (stream, addr, None)),
+                accepted = Self::accept_tls(&tls_listener) => accepted,
+                // Reap finished connections so the set doesn't grow forever
+                Some(_) = connections.join_next() => continue,
+                save = self.shutdown.triggered() => break save,
+            };
+
+            match accepted {
+                Ok((stream, addr, tls_acceptor)) => {
+                    if self.at_maxclients() {
+                        warn!(
+                            "Rejecting connection from {}: max number of clients reached",
+                            addr
+                        );
+                        self.metrics.connections.record_rejected();
+                        tokio::spawn(async move {
+                            match tls_acceptor {
+                                None => Self::reject(stream).await,
+                                Some(acceptor) => {
+                                    if let Ok(stream) = acceptor.accept(stream).await {
+                                        Self::reject(stream).await;
+                                    }
+                                }
+                            }
+                        });
+                        continue;
+                    }
+
                     info!("New connection from: {}", addr);
+                    self.set_keepalive(&stream);
 
-                    // Record connection metrics
+                    // Record connection metrics; the guard records the
+                    // disconnection however the connection task ends
                     self.metrics.connections.record_connection();
+                    let connected = ConnectedClient(Arc::clone(&self.metrics));
 
                     // Create executor with or without cluster commands
                     let mut executor = CommandExecutor::with_port(self.storage.clone(), self.port);
+                    executor.set_runtime_config(Arc::clone(&self.config));
+                    executor.set_client_registry(Arc::clone(&self.clients));
+                    executor.set_acl_users(Arc::clone(&self.acl_users));
+                    executor.set_rdb_state(Arc::clone(&self.rdb));
+                    executor.set_metrics(Arc::clone(&self.metrics));
+                    executor.set_slow_query_log(Arc::clone(&self.slow_query_log));
+                    executor.set_script_registry(Arc::clone(&self.scripts));
+                    executor.set_active_expire(Arc::clone(&self.active_expire));
+                    executor.set_loading(Arc::clone(&self.loading));
+                    executor.set_shutdown_signal(self.shutdown.clone());
+                    executor.set_save_on_shutdown(self.save_on_shutdown);
+                    executor.set_pubsub(Arc::clone(&self.pubsub));
+                    executor.set_blocked_clients(Arc::clone(&self.blocked_clients));
+                    if let Some(aof) = &self.aof {
+                        executor.set_aof_writer(aof.clone());
+                    }
 
                     #[cfg(feature = "cluster")]
                     if let (Some(meta_raft), Some(multi_raft), Some(router)) =
@@ -292,21 +557,35 @@ impl Server {
 
                     let metrics = Arc::clone(&self.metrics);
                     let monitor_broadcaster = Arc::clone(&self.monitor_broadcaster);
-
-                    tokio::spawn(async move {
-                        let mut conn = Connection::new(
-                            stream,
-                            executor,
-                            Some(metrics.clone()),
-                            Some(monitor_broadcaster),
-                        );
-
-                        if let Err(e) = conn.handle().await {
-                            error!("Connection error: {}", e);
+                    let requirepass = self.requirepass.clone();
+
+                    connections.spawn(async move {
+                        let _connected = connected;
+                        match tls_acceptor {
+                            None => {
+                                let conn = Connection::new(
+                                    stream,
+                                    executor,
+                                    Some(metrics.clone()),
+                                    Some(monitor_broadcaster),
+                                );
+                                Self::serve(conn, requirepass).await;
+                            }
+                            Some(acceptor) => match acceptor.accept(stream).await {
+                                Ok(stream) => {
+                                    let conn = Connection::with_peer_addr(
+                                        stream,
+                                        addr.to_string(),
+                                        executor,
+                                        Some(metrics.clone()),
+                                        Some(monitor_broadcaster),
+                                    );
+                                    Self::serve(conn, requirepass).await;
+                                }
+                                Err(e) => warn!("TLS handshake with {} failed: {}", addr, e),
+                            },
                         }
 
-                        // Record disconnection
-                        metrics.connections.record_disconnection();
                         info!("Connection closed: {}", addr);
                     });
                 }
@@ -314,6 +593,156 @@ impl Server {
                     error!("Failed to accept connection: {}", e);
                 }
             }
+        };
+
+        info!("Shutting down: no longer accepting connections");
+        drop(listener);
+        drop(tls_listener);
+        self.finish_connections(connections).await;
+        background.shutdown().await;
+        self.final_save(save)
+    }
+
+    /// Close every connection once it's done with the commands it has read,
+    /// waiting at most `SHUTDOWN_GRACE_PERIOD` before dropping the rest
+    async fn finish_connections(&self, mut connections: JoinSet<()>) {
+        // Connections only look at their kill signal between reads, so
+        // commands already underway run to completion and get their replies
+        if let Ok(clients) = self.clients.read() {
+            for client in clients.values() {
+                client.kill_signal.notify_one();
+            }
+        }
+
+        let drained = tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, async {
+            while connections.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            warn!(
+                "{} connections still open after {:?}, closing them",
+                connections.len(),
+                SHUTDOWN_GRACE_PERIOD
+            );
+            connections.shutdown().await;
+        }
+    }
+
+    /// Persist what's in memory before `run` returns
+    fn final_save(&self, save: ShutdownSave) -> Result<()> {
+        if let Some(aof) = &self.aof {
+            aof.fsync()?;
+        }
+        let save = match save {
+            ShutdownSave::Configured => self.save_on_shutdown,
+            ShutdownSave::Save => true,
+            ShutdownSave::NoSave => false,
+        };
+        if save {
+            info!("Saving the final RDB snapshot before exiting");
+            self.rdb.save(&self.storage)?;
+            info!("DB saved on disk");
+        }
+        info!("AiKv is now ready to exit, bye bye...");
+        Ok(())
+    }
+
+    /// Delete expired keys in the background, so keys nobody reads again
+    /// don't linger; paused while `active_expire` is off
+    ///
+    /// Each key removed is published as an `expired` keyspace event.
+    async fn sweep_expired(
+        storage: StorageEngine,
+        active_expire: Arc<AtomicBool>,
+        metrics: Arc<Metrics>,
+        pubsub: Arc<PubSub>,
+    ) {
+        let mut interval = tokio::time::interval(ACTIVE_EXPIRE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if !active_expire.load(Ordering::SeqCst) {
+                continue;
+            }
+            for db in 0..storage.db_count() {
+                match storage.remove_expired_in_db(db) {
+                    Ok(removed) => {
+                        metrics.memory.expired_keys.inc_by(removed.len() as u64);
+                        for key in removed {
+                            pubsub.notify_keyspace_event(
+                                KeyspaceEvents::EXPIRED,
+                                "expired",
+                                key.as_bytes(),
+                                db,
+                            );
+                        }
+                    }
+                    Err(e) => warn!("Failed to remove expired keys from db {}: {}", db, e),
+                }
+            }
+        }
+    }
+
+    /// Whether as many clients as `maxclients` allows are already connected
+    fn at_maxclients(&self) -> bool {
+        let maxclients = self
+            .config
+            .read()
+            .ok()
+            .and_then(|config| config.get("maxclients")?.parse::<u64>().ok())
+            .unwrap_or(u64::MAX);
+        self.metrics.connections.connected_clients() >= maxclients
+    }
+
+    /// Tell a client over the `maxclients` limit why it's being disconnected
+    async fn reject<S: AsyncWrite + Unpin>(mut stream: S) {
+        let _ = stream
+            .write_all(b"-ERR max number of clients reached\r\n")
+            .await;
+        let _ = stream.shutdown().await;
+    }
+
+    /// Enable TCP keepalive on an accepted socket, every `tcp-keepalive` seconds
+    fn set_keepalive(&self, stream: &TcpStream) {
+        let seconds = self
+            .config
+            .read()
+            .ok()
+            .and_then(|config| config.get("tcp-keepalive")?.parse::<u64>().ok())
+            .unwrap_or(0);
+        if seconds == 0 {
+            return;
+        }
+
+        let keepalive = TcpKeepalive::new()
+            .with_time(Duration::from_secs(seconds))
+            .with_interval(Duration::from_secs(seconds));
+        if let Err(e) = SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+            warn!("Failed to enable TCP keepalive: {}", e);
+        }
+    }
+
+    /// Accept the next connection on the TLS listener, if there is one
+    async fn accept_tls(
+        tls_listener: &Option<(TcpListener, TlsAcceptor)>,
+    ) -> std::io::Result<(TcpStream, SocketAddr, Option<TlsAcceptor>)> {
+        match tls_listener {
+            Some((listener, acceptor)) => {
+                let (stream, addr) = listener.accept().await?;
+                Ok((stream, addr, Some(acceptor.clone())))
+            }
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Serve one client until it disconnects
+    async fn serve<S>(mut conn: Connection<S>, requirepass: Option<Arc<str>>)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        conn.set_requirepass(requirepass);
+
+        if let Err(e) = conn.handle().await {
+            error!("Connection error: {}", e);
         }
     }
 }
@@ -0,0 +1,90 @@
+//! TLS for client connections
+//!
+//! When TLS is enabled the server listens on a second port and wraps every
+//! connection accepted there in a [`TlsAcceptor`] before handing the
+//! encrypted stream to a [`Connection`](super::connection::Connection).
+
+use crate::error::{AikvError, Result};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+/// Certificate files of the TLS listener
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    /// PEM certificate chain presented to clients
+    pub cert_file: PathBuf,
+    /// PEM private key of the certificate
+    pub key_file: PathBuf,
+    /// PEM CA certificates; when set, clients must present a certificate
+    /// signed by one of them
+    pub ca_file: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    /// TLS with the given certificate and key, without client certificates
+    pub fn new(cert_file: impl Into<PathBuf>, key_file: impl Into<PathBuf>) -> Self {
+        Self {
+            cert_file: cert_file.into(),
+            key_file: key_file.into(),
+            ca_file: None,
+        }
+    }
+
+    /// Require client certificates signed by a CA in `ca_file`
+    pub fn with_ca_file(mut self, ca_file: impl Into<PathBuf>) -> Self {
+        self.ca_file = Some(ca_file.into());
+        self
+    }
+
+    /// Load the certificate files and build an acceptor for TLS connections
+    pub fn acceptor(&self) -> Result<TlsAcceptor> {
+        let certs = load_certs(&self.cert_file)?;
+        let key = load_key(&self.key_file)?;
+
+        let builder = ServerConfig::builder();
+        let builder = match &self.ca_file {
+            Some(ca_file) => {
+                let mut roots = RootCertStore::empty();
+                for cert in load_certs(ca_file)? {
+                    roots.add(cert).map_err(|e| invalid(ca_file, e))?;
+                }
+                let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                    .build()
+                    .map_err(|e| invalid(ca_file, e))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        let config = builder
+            .with_single_cert(certs, key)
+            .map_err(|e| invalid(&self.cert_file, e))?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+fn invalid(path: &Path, error: impl std::fmt::Display) -> AikvError {
+    AikvError::Invalid(format!("TLS file '{}': {}", path.display(), error))
+}
+
+/// Read every certificate in a PEM file
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader).collect::<std::io::Result<Vec<_>>>()?;
+    if certs.is_empty() {
+        return Err(invalid(path, "no certificates found"));
+    }
+    Ok(certs)
+}
+
+/// Read the first private key in a PEM file
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    rustls_pemfile::private_key(&mut reader)?.ok_or_else(|| invalid(path, "no private key found"))
+}
@@ -42,9 +42,13 @@ use crate::error::{AikvError, Result};
 use crate::storage::{SerializableStoredValue, StoredValue};
 use aidb::{Options, WriteBatch, DB};
 use bytes::Bytes;
+use rand::Rng;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
-use std::path::Path;
-use std::sync::Arc;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 // Re-export BatchOp from memory_adapter for consistency
@@ -63,13 +67,45 @@ pub use crate::storage::memory_adapter::BatchOp;
 /// - **All Data Types**: Supports String, List, Hash, Set, and ZSet through serialization
 /// - **Expiration**: Built-in support for key expiration with automatic cleanup
 /// - **Thread-Safe**: Uses Arc for safe sharing across threads
+///
+/// # Consistency
+///
+/// AiDb has no transactions, so every write of a key serializes on a
+/// striped per-key lock. An update therefore never interleaves with another
+/// write of the same key, and concurrent read-modify-writes (e.g. two
+/// HINCRBY on one field) don't lose each other's changes. Writes of two keys
+/// (RENAME, COPY, MOVE) and batches take the locks of all their keys in
+/// stripe order. Reads don't take the lock.
 #[derive(Clone)]
 pub struct AiDbStorageAdapter {
-    /// Multiple databases (default: 16 databases like Redis)
-    /// Each database is a separate AiDb instance with its own directory
-    databases: Arc<Vec<Arc<DB>>>,
+    /// Multiple databases (default: 16 databases like Redis), indexed by
+    /// logical database. Each database is a separate AiDb instance with its
+    /// own directory; SWAPDB reorders them.
+    databases: Arc<RwLock<Vec<OpenDb>>>,
+    /// Where the logical database to directory mapping is kept once SWAPDB
+    /// has changed it
+    layout_path: PathBuf,
+    /// Locks serializing the writes of a key, picked by hashing the key and
+    /// its logical database
+    key_locks: Arc<Vec<Mutex<()>>>,
+}
+
+/// An open AiDb instance and the `db{N}` directory it lives in
+struct OpenDb {
+    dir: usize,
+    db: Arc<DB>,
+    /// Keys stored in the instance, expired or not, so DBSIZE doesn't scan.
+    /// Kept up to date by the writes and reconciled by the expiration sweeper.
+    keys: AtomicUsize,
 }
 
+/// File under the base directory listing, for each logical database in
+/// order, the number of the `db{N}` directory holding it
+const LAYOUT_FILE: &str = "db_layout";
+
+/// Number of key locks; keys hashing to the same one merely wait on each other
+const KEY_LOCK_STRIPES: usize = 256;
+
 impl AiDbStorageAdapter {
     /// Create a new AiDb storage adapter with the given path and database count.
     ///
@@ -89,9 +125,14 @@ impl AiDbStorageAdapter {
                 .map_err(|e| AikvError::Storage(format!("Failed to create directory: {}", e)))?;
         }
 
+        let layout_path = base_path.join(LAYOUT_FILE);
+        let layout = Self::read_layout(&layout_path)?;
+
         let mut databases = Vec::with_capacity(db_count);
         for i in 0..db_count {
-            let db_path = base_path.join(format!("db{}", i));
+            // Databases past the end of the layout were never swapped
+            let dir = layout.get(i).copied().unwrap_or(i);
+            let db_path = base_path.join(format!("db{}", dir));
             // Use sync_wal(false) for better write performance.
             // The default Options::default() has sync_wal: true, which causes
             // synchronous disk writes for every put operation, resulting in
@@ -101,14 +142,163 @@ impl AiDbStorageAdapter {
             let options = Options::default().sync_wal(false);
             let db = DB::open(&db_path, options)
                 .map_err(|e| AikvError::Storage(format!("Failed to open database {}: {}", i, e)))?;
-            databases.push(Arc::new(db));
+            let keys = AtomicUsize::new(Self::count_keys(&db));
+            databases.push(OpenDb {
+                dir,
+                db: Arc::new(db),
+                keys,
+            });
         }
 
         Ok(Self {
-            databases: Arc::new(databases),
+            databases: Arc::new(RwLock::new(databases)),
+            layout_path,
+            key_locks: Arc::new((0..KEY_LOCK_STRIPES).map(|_| Mutex::new(())).collect()),
         })
     }
 
+    /// Read the layout file, if SWAPDB ever wrote one
+    ///
+    /// The layout must be a permutation of `0..len`, so logical databases past
+    /// its end can keep their own directories.
+    fn read_layout(path: &Path) -> Result<Vec<usize>> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(AikvError::Storage(format!(
+                    "Failed to read database layout: {}",
+                    e
+                )))
+            }
+        };
+
+        let layout: Vec<usize> = contents
+            .split_whitespace()
+            .map(|dir| dir.parse::<usize>())
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|e| AikvError::Storage(format!("Invalid database layout: {}", e)))?;
+
+        let mut seen = vec![false; layout.len()];
+        for &dir in &layout {
+            if dir >= layout.len() || std::mem::replace(&mut seen[dir], true) {
+                return Err(AikvError::Storage(format!(
+                    "Invalid database layout in {}",
+                    path.display()
+                )));
+            }
+        }
+        Ok(layout)
+    }
+
+    /// Replace the layout file, going through a temporary file so a crash
+    /// never leaves half a layout behind
+    fn write_layout(path: &Path, layout: &[usize]) -> Result<()> {
+        let contents: Vec<String> = layout.iter().map(usize::to_string).collect();
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, contents.join(" ") + "\n")
+            .and_then(|_| std::fs::rename(&tmp_path, path))
+            .map_err(|e| AikvError::Storage(format!("Failed to write database layout: {}", e)))
+    }
+
+    /// The open instances, indexed by logical database
+    ///
+    /// The list is only ever reordered, so a lock poisoned by a panicking
+    /// command still guards a valid list and is recovered.
+    fn open_databases(&self) -> RwLockReadGuard<'_, Vec<OpenDb>> {
+        self.databases
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Take the lock serializing writes of `key` in logical database `db_index`
+    ///
+    /// Like the database list, the lock guards no data of its own, so it is
+    /// recovered if an update closure panicked while holding it.
+    fn lock_key(&self, db_index: usize, key: &str) -> MutexGuard<'_, ()> {
+        self.key_locks[self.stripe(db_index, key)]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Take the locks of several keys, given as (logical database, key)
+    ///
+    /// Stripes are locked in index order, and a stripe shared by several of
+    /// the keys only once, so two multi-key writes can't deadlock.
+    fn lock_keys(&self, keys: &[(usize, &str)]) -> Vec<MutexGuard<'_, ()>> {
+        let mut stripes: Vec<usize> = keys
+            .iter()
+            .map(|&(db_index, key)| self.stripe(db_index, key))
+            .collect();
+        stripes.sort_unstable();
+        stripes.dedup();
+        stripes
+            .into_iter()
+            .map(|stripe| {
+                self.key_locks[stripe]
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+            })
+            .collect()
+    }
+
+    /// Index of the lock of `key` in logical database `db_index`
+    fn stripe(&self, db_index: usize, key: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        (db_index, key).hash(&mut hasher);
+        (hasher.finish() % self.key_locks.len() as u64) as usize
+    }
+
+    /// The AiDb instance currently holding logical database `db_index`
+    fn db(&self, db_index: usize) -> Result<Arc<DB>> {
+        self.open_databases()
+            .get(db_index)
+            .map(|open| Arc::clone(&open.db))
+            .ok_or_else(|| AikvError::Storage(format!("Invalid database index: {}", db_index)))
+    }
+
+    /// Count the keys of an instance, skipping expiration metadata
+    fn count_keys(db: &DB) -> usize {
+        let mut keys = 0;
+        let mut iter = db.iter();
+        while iter.valid() {
+            if !iter.key().starts_with(b"__exp__:") {
+                keys += 1;
+            }
+            iter.next();
+        }
+        keys
+    }
+
+    /// Add `delta` to the key count of logical database `db_index`
+    fn adjust_key_count(&self, db_index: usize, delta: isize) {
+        if delta == 0 {
+            return;
+        }
+        if let Some(open) = self.open_databases().get(db_index) {
+            let _ = open
+                .keys
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |keys| {
+                    Some(keys.saturating_add_signed(delta))
+                });
+        }
+    }
+
+    /// Replace the key count of logical database `db_index`
+    fn set_key_count(&self, db_index: usize, keys: usize) {
+        if let Some(open) = self.open_databases().get(db_index) {
+            open.keys.store(keys, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether `key` is stored at all, expired or not
+    fn is_stored(db: &DB, key: &[u8]) -> Result<bool> {
+        Ok(db
+            .get(key)
+            .map_err(|e| AikvError::Storage(format!("Failed to check key existence: {}", e)))?
+            .is_some())
+    }
+
     /// Get current time in milliseconds
     fn current_time_ms() -> u64 {
         SystemTime::now()
@@ -119,27 +309,20 @@ impl AiDbStorageAdapter {
 
     /// Check if a key is expired based on its stored expiration metadata
     fn is_expired(&self, db: &DB, key: &[u8]) -> Result<bool> {
+        Ok(self
+            .expiration(db, key)?
+            .is_some_and(|expire_at| Self::current_time_ms() >= expire_at))
+    }
+
+    /// The expiration timestamp (ms) recorded for a key, if it has one
+    fn expiration(&self, db: &DB, key: &[u8]) -> Result<Option<u64>> {
         let expire_key = Self::expiration_key(key);
-        if let Some(expire_bytes) = db
+        let expire_bytes = db
             .get(&expire_key)
-            .map_err(|e| AikvError::Storage(format!("Failed to get expiration: {}", e)))?
-        {
-            if expire_bytes.len() == 8 {
-                let expire_at = u64::from_le_bytes([
-                    expire_bytes[0],
-                    expire_bytes[1],
-                    expire_bytes[2],
-                    expire_bytes[3],
-                    expire_bytes[4],
-                    expire_bytes[5],
-                    expire_bytes[6],
-                    expire_bytes[7],
-                ]);
-                let now = Self::current_time_ms();
-                return Ok(now >= expire_at);
-            }
-        }
-        Ok(false)
+            .map_err(|e| AikvError::Storage(format!("Failed to get expiration: {}", e)))?;
+        Ok(expire_bytes
+            .and_then(|bytes| <[u8; 8]>::try_from(&bytes[..]).ok())
+            .map(u64::from_le_bytes))
     }
 
     /// Generate expiration metadata key for a given key
@@ -184,14 +367,18 @@ impl AiDbStorageAdapter {
     /// }
     /// ```
     pub fn get_value(&self, db_index: usize, key: &str) -> Result<Option<StoredValue>> {
-        if db_index >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {}",
-                db_index
-            )));
-        }
+        self.read_value(db_index, key, false)
+    }
 
-        let db = &self.databases[db_index];
+    /// `get_value` for callers already holding the key's lock
+    fn get_value_locked(&self, db_index: usize, key: &str) -> Result<Option<StoredValue>> {
+        self.read_value(db_index, key, true)
+    }
+
+    /// Read a value, cleaning it up if it has expired; the key's lock is
+    /// taken for the cleanup unless `locked` says the caller holds it
+    fn read_value(&self, db_index: usize, key: &str, locked: bool) -> Result<Option<StoredValue>> {
+        let db = &self.db(db_index)?;
         let key_bytes = key.as_bytes();
 
         // Try to read main key first, only check expiration when key exists
@@ -202,15 +389,12 @@ impl AiDbStorageAdapter {
         {
             Some(serialized) => {
                 // Main key exists, check if expired
-                if self.is_expired(db, key_bytes)? {
-                    // Clean up expired key
-                    db.delete(key_bytes).map_err(|e| {
-                        AikvError::Storage(format!("Failed to delete expired key: {}", e))
-                    })?;
-                    let expire_key = Self::expiration_key(key_bytes);
-                    db.delete(&expire_key).map_err(|e| {
-                        AikvError::Storage(format!("Failed to delete expiration: {}", e))
-                    })?;
+                let expires_at = self.expiration(db, key_bytes)?;
+                if expires_at.is_some_and(|expire_at| Self::current_time_ms() >= expire_at) {
+                    // Clean up expired key under its lock, so a write that
+                    // lands meanwhile isn't deleted or counted twice
+                    let _guard = (!locked).then(|| self.lock_key(db_index, key));
+                    self.remove_if_expired(db_index, key)?;
                     return Ok(None);
                 }
                 // Deserialize and return
@@ -218,12 +402,67 @@ impl AiDbStorageAdapter {
                     .map_err(|e| {
                         AikvError::Storage(format!("Failed to deserialize value: {}", e))
                     })?;
-                Ok(Some(StoredValue::from_serializable(serializable)))
+                // The expiration key is authoritative: EXPIRE and PERSIST
+                // don't rewrite the serialized value
+                let mut value = StoredValue::from_serializable(serializable);
+                value.set_expiration(expires_at);
+                Ok(Some(value))
             }
             None => Ok(None),
         }
     }
 
+    /// Delete `key` if it is stored and has expired, returning whether it was
+    ///
+    /// The caller must hold the key's lock, so the check and the delete
+    /// can't be split by another write and the key count only drops for a
+    /// key that was actually removed.
+    fn remove_if_expired(&self, db_index: usize, key: &str) -> Result<bool> {
+        let db = &self.db(db_index)?;
+        let key_bytes = key.as_bytes();
+        if !Self::is_stored(db, key_bytes)? || !self.is_expired(db, key_bytes)? {
+            return Ok(false);
+        }
+        db.delete(key_bytes)
+            .map_err(|e| AikvError::Storage(format!("Failed to delete expired key: {}", e)))?;
+        db.delete(&Self::expiration_key(key_bytes))
+            .map_err(|e| AikvError::Storage(format!("Failed to delete expiration: {}", e)))?;
+        self.adjust_key_count(db_index, -1);
+        Ok(true)
+    }
+
+    /// Get a stored value without counting it as an access.
+    ///
+    /// AiDb doesn't track accesses, so this is the same as `get_value()`.
+    pub fn peek_value(&self, db_index: usize, key: &str) -> Result<Option<StoredValue>> {
+        self.get_value(db_index, key)
+    }
+
+    /// Run a read-only closure against a stored value.
+    ///
+    /// Values are deserialized from disk on every read, so unlike the memory
+    /// adapter this can't avoid materializing the value; it exists so callers
+    /// can use the same borrowing API for both backends.
+    pub fn with_value<F, R>(&self, db_index: usize, key: &str, f: F) -> Result<Option<R>>
+    where
+        F: FnOnce(&StoredValue) -> Result<R>,
+    {
+        match self.get_value(db_index, key)? {
+            Some(stored) => f(&stored).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the stored values of several keys, in key order.
+    ///
+    /// AiDb has no multi-get, so this reads the keys one by one; it exists so
+    /// callers can use the same batch API for both backends.
+    pub fn read_batch(&self, db_index: usize, keys: &[String]) -> Result<Vec<Option<StoredValue>>> {
+        keys.iter()
+            .map(|key| self.get_value(db_index, key))
+            .collect()
+    }
+
     /// Set a value for a key in a specific database.
     ///
     /// This method supports all data types (String, List, Hash, Set, ZSet) through
@@ -245,14 +484,13 @@ impl AiDbStorageAdapter {
     /// storage.set_value(0, "mykey".to_string(), value)?;
     /// ```
     pub fn set_value(&self, db_index: usize, key: String, value: StoredValue) -> Result<()> {
-        if db_index >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {}",
-                db_index
-            )));
-        }
+        let _guard = self.lock_key(db_index, &key);
+        self.put_value(db_index, &key, value)
+    }
 
-        let db = &self.databases[db_index];
+    /// Store a value without taking its key lock, for callers already holding it
+    fn put_value(&self, db_index: usize, key: &str, value: StoredValue) -> Result<()> {
+        let db = &self.db(db_index)?;
         let key_bytes = key.as_bytes();
 
         // Serialize using bincode
@@ -261,14 +499,23 @@ impl AiDbStorageAdapter {
             .map_err(|e| AikvError::Storage(format!("Failed to serialize value: {}", e)))?;
 
         // Store the serialized value
+        let is_new = !Self::is_stored(db, key_bytes)?;
         db.put(key_bytes, &serialized)
             .map_err(|e| AikvError::Storage(format!("Failed to put value: {}", e)))?;
+        if is_new {
+            self.adjust_key_count(db_index, 1);
+        }
 
-        // Handle expiration if set
-        if let Some(expires_at) = value.expires_at() {
-            let expire_key = Self::expiration_key(key_bytes);
-            db.put(&expire_key, &expires_at.to_le_bytes())
-                .map_err(|e| AikvError::Storage(format!("Failed to set expiration: {}", e)))?;
+        // The value's expiration replaces whatever the key had before, so an
+        // overwrite without one doesn't inherit a stale TTL
+        let expire_key = Self::expiration_key(key_bytes);
+        match value.expires_at() {
+            Some(expires_at) => db
+                .put(&expire_key, &expires_at.to_le_bytes())
+                .map_err(|e| AikvError::Storage(format!("Failed to set expiration: {}", e)))?,
+            None => db
+                .delete(&expire_key)
+                .map_err(|e| AikvError::Storage(format!("Failed to clear expiration: {}", e)))?,
         }
 
         Ok(())
@@ -278,7 +525,8 @@ impl AiDbStorageAdapter {
     ///
     /// This method provides atomic read-modify-write semantics for updating values.
     /// It's useful for implementing commands that need to modify data structures
-    /// in-place (e.g., LPUSH, HSET, SADD).
+    /// in-place (e.g., LPUSH, HSET, SADD). The key lock is held from the read
+    /// to the write, so concurrent updates of one key apply one after another.
     ///
     /// # Arguments
     /// * `db_index` - The database index (0-15 by default)
@@ -302,15 +550,17 @@ impl AiDbStorageAdapter {
     where
         F: FnOnce(&mut StoredValue) -> Result<()>,
     {
-        if db_index >= self.databases.len() {
+        if db_index >= self.db_count() {
             return Err(AikvError::Storage(format!(
                 "Invalid database index: {}",
                 db_index
             )));
         }
 
+        let _guard = self.lock_key(db_index, key);
+
         // Get the current value
-        let mut value = match self.get_value(db_index, key)? {
+        let mut value = match self.get_value_locked(db_index, key)? {
             Some(v) => v,
             None => return Ok(false),
         };
@@ -319,11 +569,52 @@ impl AiDbStorageAdapter {
         f(&mut value)?;
 
         // Store the updated value
-        self.set_value(db_index, key.to_string(), value)?;
+        self.put_value(db_index, key, value)?;
 
         Ok(true)
     }
 
+    /// Atomically update a value using a closure, starting from `default()`
+    /// when the key doesn't exist.
+    ///
+    /// Like `update_value`, but a missing key is created under the same key
+    /// lock, so concurrent upserts of a new key can't overwrite each other
+    /// (e.g. HINCRBY of a field of a hash that doesn't exist yet).
+    ///
+    /// # Example
+    /// ```ignore
+    /// storage.update_or_insert_value(0, "myhash", || StoredValue::new_hash(HashMap::new()), |v| {
+    ///     v.as_hash_mut()?.insert("field".to_string(), Bytes::from("1"));
+    ///     Ok(())
+    /// })?;
+    /// ```
+    pub fn update_or_insert_value<D, F>(
+        &self,
+        db_index: usize,
+        key: &str,
+        default: D,
+        f: F,
+    ) -> Result<()>
+    where
+        D: FnOnce() -> StoredValue,
+        F: FnOnce(&mut StoredValue) -> Result<()>,
+    {
+        if db_index >= self.db_count() {
+            return Err(AikvError::Storage(format!(
+                "Invalid database index: {}",
+                db_index
+            )));
+        }
+
+        let _guard = self.lock_key(db_index, key);
+        let mut value = match self.get_value_locked(db_index, key)? {
+            Some(v) => v,
+            None => default(),
+        };
+        f(&mut value)?;
+        self.put_value(db_index, key, value)
+    }
+
     /// Atomically delete a key and return its value.
     ///
     /// This method provides atomic delete-and-get semantics, useful for implementing
@@ -346,18 +637,12 @@ impl AiDbStorageAdapter {
     /// }
     /// ```
     pub fn delete_and_get(&self, db_index: usize, key: &str) -> Result<Option<StoredValue>> {
-        if db_index >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {}",
-                db_index
-            )));
-        }
-
-        let db = &self.databases[db_index];
+        let db = &self.db(db_index)?;
         let key_bytes = key.as_bytes();
+        let _guard = self.lock_key(db_index, key);
 
         // Get the value before deleting
-        let value = self.get_value(db_index, key)?;
+        let value = self.get_value_locked(db_index, key)?;
 
         if value.is_some() {
             // Delete the key
@@ -367,6 +652,7 @@ impl AiDbStorageAdapter {
             // Delete expiration metadata if exists
             let expire_key = Self::expiration_key(key_bytes);
             let _ = db.delete(&expire_key);
+            self.adjust_key_count(db_index, -1);
         }
 
         Ok(value)
@@ -382,7 +668,7 @@ impl AiDbStorageAdapter {
     ///
     /// # Arguments
     /// * `db_index` - The database index (0-15 by default)
-    /// * `operations` - Vector of (key, operation) pairs where operation is either Set(value) or Delete
+    /// * `operations` - Vector of (key, operation) pairs where operation is Set(value), SetValue(value) or Delete
     ///
     /// # Returns
     /// * `Ok(())` - If all operations succeeded
@@ -400,7 +686,7 @@ impl AiDbStorageAdapter {
     /// storage.write_batch(0, ops)?;
     /// ```
     pub fn write_batch(&self, db_index: usize, operations: Vec<(String, BatchOp)>) -> Result<()> {
-        if db_index >= self.databases.len() {
+        if db_index >= self.db_count() {
             return Err(AikvError::Storage(format!(
                 "Invalid database index: {}",
                 db_index
@@ -411,21 +697,32 @@ impl AiDbStorageAdapter {
             return Ok(());
         }
 
-        let db = &self.databases[db_index];
+        let db = &self.db(db_index)?;
+        let keys: Vec<(usize, &str)> = operations
+            .iter()
+            .map(|(key, _)| (db_index, key.as_str()))
+            .collect();
+        let _guards = self.lock_keys(&keys);
         let mut batch = WriteBatch::new();
+        // Whether each key touched is stored once the batch is applied
+        let mut stored: HashMap<String, (bool, bool)> = HashMap::new();
 
         for (key, op) in operations {
             let key_bytes = key.as_bytes();
+            let now_stored = !matches!(op, BatchOp::Delete);
+            match stored.get_mut(&key) {
+                Some((_, after)) => *after = now_stored,
+                None => {
+                    let before = Self::is_stored(db, key_bytes)?;
+                    stored.insert(key.clone(), (before, now_stored));
+                }
+            }
             match op {
+                // A plain set has no TTL, whatever the key had before
                 BatchOp::Set(value) => {
-                    // Serialize StoredValue into bincode format before putting into AiDb
-                    let stored = StoredValue::new_string(value);
-                    let serializable = stored.to_serializable();
-                    let serialized = bincode::serialize(&serializable).map_err(|e| {
-                        AikvError::Storage(format!("Failed to serialize value: {}", e))
-                    })?;
-                    batch.put(key_bytes, &serialized);
+                    Self::batch_put(&mut batch, key_bytes, &StoredValue::new_string(value))?
                 }
+                BatchOp::SetValue(value) => Self::batch_put(&mut batch, key_bytes, &value)?,
                 BatchOp::Delete => {
                     batch.delete(key_bytes);
                     // Also delete expiration metadata
@@ -439,6 +736,30 @@ impl AiDbStorageAdapter {
         db.write(batch)
             .map_err(|e| AikvError::Storage(format!("Failed to write batch: {}", e)))?;
 
+        let delta: isize = stored
+            .values()
+            .map(|&(before, after)| after as isize - before as isize)
+            .sum();
+        self.adjust_key_count(db_index, delta);
+
+        Ok(())
+    }
+
+    /// Add the writes storing `value` under `key` to `batch`
+    ///
+    /// The expiration metadata goes in the same batch as the value, so a
+    /// crash can't leave one without the other.
+    fn batch_put(batch: &mut WriteBatch, key: &[u8], value: &StoredValue) -> Result<()> {
+        // Serialize StoredValue into bincode format before putting into AiDb
+        let serialized = bincode::serialize(&value.to_serializable())
+            .map_err(|e| AikvError::Storage(format!("Failed to serialize value: {}", e)))?;
+        batch.put(key, &serialized);
+
+        let expire_key = Self::expiration_key(key);
+        match value.expires_at() {
+            Some(expires_at) => batch.put(&expire_key, &expires_at.to_le_bytes()),
+            None => batch.delete(&expire_key),
+        }
         Ok(())
     }
 
@@ -448,17 +769,13 @@ impl AiDbStorageAdapter {
 
     /// Get a value by key from a specific database
     ///
-    /// Uses get_value internally and extracts string bytes if the stored value is a string.
+    /// Uses get_value internally and extracts string bytes if the stored value
+    /// is a string. Any other type is a WRONGTYPE error, as with the memory
+    /// adapter.
     pub fn get_from_db(&self, db_index: usize, key: &str) -> Result<Option<Bytes>> {
         // Use get_value which properly deserializes bincode data
         match self.get_value(db_index, key)? {
-            Some(stored_value) => {
-                // Extract string bytes from StoredValue
-                match stored_value.as_string() {
-                    Ok(bytes) => Ok(Some(bytes.clone())),
-                    Err(_) => Ok(None), // Non-string types return None for legacy compatibility
-                }
-            }
+            Some(stored_value) => Ok(Some(stored_value.as_string()?.clone())),
             None => Ok(None),
         }
     }
@@ -499,15 +816,9 @@ impl AiDbStorageAdapter {
 
     /// Set expiration for a key in milliseconds
     pub fn set_expire_in_db(&self, db_index: usize, key: &str, expire_ms: u64) -> Result<bool> {
-        if db_index >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {}",
-                db_index
-            )));
-        }
-
-        let db = &self.databases[db_index];
+        let db = &self.db(db_index)?;
         let key_bytes = key.as_bytes();
+        let _guard = self.lock_key(db_index, key);
 
         // Check if key exists and is not expired
         if self.is_expired(db, key_bytes)? {
@@ -538,15 +849,9 @@ impl AiDbStorageAdapter {
         key: &str,
         timestamp_ms: u64,
     ) -> Result<bool> {
-        if db_index >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {}",
-                db_index
-            )));
-        }
-
-        let db = &self.databases[db_index];
+        let db = &self.db(db_index)?;
         let key_bytes = key.as_bytes();
+        let _guard = self.lock_key(db_index, key);
 
         // Check if key exists and is not expired
         if self.is_expired(db, key_bytes)? {
@@ -571,14 +876,7 @@ impl AiDbStorageAdapter {
 
     /// Get TTL in milliseconds
     pub fn get_ttl_in_db(&self, db_index: usize, key: &str) -> Result<i64> {
-        if db_index >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {}",
-                db_index
-            )));
-        }
-
-        let db = &self.databases[db_index];
+        let db = &self.db(db_index)?;
         let key_bytes = key.as_bytes();
 
         // Check if key exists
@@ -626,14 +924,7 @@ impl AiDbStorageAdapter {
 
     /// Get expiration timestamp in milliseconds
     pub fn get_expire_time_in_db(&self, db_index: usize, key: &str) -> Result<i64> {
-        if db_index >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {}",
-                db_index
-            )));
-        }
-
-        let db = &self.databases[db_index];
+        let db = &self.db(db_index)?;
         let key_bytes = key.as_bytes();
 
         // Check if key exists
@@ -676,15 +967,9 @@ impl AiDbStorageAdapter {
 
     /// Remove expiration from a key
     pub fn persist_in_db(&self, db_index: usize, key: &str) -> Result<bool> {
-        if db_index >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {}",
-                db_index
-            )));
-        }
-
-        let db = &self.databases[db_index];
+        let db = &self.db(db_index)?;
         let key_bytes = key.as_bytes();
+        let _guard = self.lock_key(db_index, key);
 
         // Check if key exists
         if db
@@ -718,15 +1003,9 @@ impl AiDbStorageAdapter {
 
     /// Delete a key from a specific database
     pub fn delete_from_db(&self, db_index: usize, key: &str) -> Result<bool> {
-        if db_index >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {}",
-                db_index
-            )));
-        }
-
-        let db = &self.databases[db_index];
+        let db = &self.db(db_index)?;
         let key_bytes = key.as_bytes();
+        let _guard = self.lock_key(db_index, key);
 
         // Check if key exists
         let exists = db
@@ -735,6 +1014,9 @@ impl AiDbStorageAdapter {
             .is_some();
 
         if exists {
+            // An expired key is dropped, but it didn't exist as far as DEL is concerned
+            let expired = self.is_expired(db, key_bytes)?;
+
             // Delete the key
             db.delete(key_bytes)
                 .map_err(|e| AikvError::Storage(format!("Failed to delete key: {}", e)))?;
@@ -742,8 +1024,9 @@ impl AiDbStorageAdapter {
             // Delete expiration metadata if exists
             let expire_key = Self::expiration_key(key_bytes);
             let _ = db.delete(&expire_key);
+            self.adjust_key_count(db_index, -1);
 
-            Ok(true)
+            Ok(!expired)
         } else {
             Ok(false)
         }
@@ -756,14 +1039,7 @@ impl AiDbStorageAdapter {
 
     /// Check if a key exists in a specific database
     pub fn exists_in_db(&self, db_index: usize, key: &str) -> Result<bool> {
-        if db_index >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {}",
-                db_index
-            )));
-        }
-
-        let db = &self.databases[db_index];
+        let db = &self.db(db_index)?;
         let key_bytes = key.as_bytes();
 
         // Check if expired
@@ -786,14 +1062,7 @@ impl AiDbStorageAdapter {
     /// Get all keys in a database
     /// Note: This is an expensive operation for large databases
     pub fn get_all_keys_in_db(&self, db_index: usize) -> Result<Vec<String>> {
-        if db_index >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {}",
-                db_index
-            )));
-        }
-
-        let db = &self.databases[db_index];
+        let db = &self.db(db_index)?;
         let mut keys = Vec::new();
 
         // Create an iterator to scan all keys
@@ -825,21 +1094,61 @@ impl AiDbStorageAdapter {
         Ok(keys)
     }
 
+    /// Delete the expired keys of a database, returning the keys removed
+    ///
+    /// Used by the active expiration sweeper; reads already skip expired keys.
+    pub fn remove_expired_in_db(&self, db_index: usize) -> Result<Vec<String>> {
+        let db = &self.db(db_index)?;
+        let mut expired = Vec::new();
+        let mut iter = db.iter();
+        while iter.valid() {
+            let key = iter.key();
+            if !key.starts_with(b"__exp__:") && self.is_expired(db, key)? {
+                expired.push(String::from_utf8_lossy(key).into_owned());
+            }
+            iter.next();
+        }
+        drop(iter);
+
+        // A key rewritten since the scan is left alone, and only the keys
+        // actually removed come off the count
+        let mut removed = Vec::with_capacity(expired.len());
+        for key in expired {
+            let _guard = self.lock_key(db_index, &key);
+            if self.remove_if_expired(db_index, &key)? {
+                removed.push(key);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Number of logical databases
+    pub fn db_count(&self) -> usize {
+        self.open_databases().len()
+    }
+
     /// Get database size (number of keys)
+    ///
+    /// Reads the maintained key count rather than scanning, so keys that
+    /// expired but haven't been removed yet still count until a read or the
+    /// sweeper drops them. `exact_dbsize_in_db` scans instead.
     pub fn dbsize_in_db(&self, db_index: usize) -> Result<usize> {
+        self.open_databases()
+            .get(db_index)
+            .map(|open| open.keys.load(Ordering::Relaxed))
+            .ok_or_else(|| AikvError::Storage(format!("Invalid database index: {}", db_index)))
+    }
+
+    /// Count the live keys of a database with a full scan
+    ///
+    /// Note: This is an expensive operation for large databases
+    pub fn exact_dbsize_in_db(&self, db_index: usize) -> Result<usize> {
         Ok(self.get_all_keys_in_db(db_index)?.len())
     }
 
     /// Clear a specific database
     pub fn flush_db(&self, db_index: usize) -> Result<()> {
-        if db_index >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {}",
-                db_index
-            )));
-        }
-
-        let db = &self.databases[db_index];
+        let db = &self.db(db_index)?;
 
         // Get all keys and delete them
         let mut iter = db.iter();
@@ -851,38 +1160,73 @@ impl AiDbStorageAdapter {
             db.delete(&key)
                 .map_err(|e| AikvError::Storage(format!("Failed to delete key: {}", e)))?;
         }
+        self.set_key_count(db_index, 0);
 
         Ok(())
     }
 
     /// Clear all databases
     pub fn flush_all(&self) -> Result<()> {
-        for i in 0..self.databases.len() {
+        for i in 0..self.db_count() {
             self.flush_db(i)?;
         }
         Ok(())
     }
 
-    /// Swap two databases
-    /// Note: This is not efficiently implementable with AiDb, so we return an error
-    pub fn swap_db(&self, _db1: usize, _db2: usize) -> Result<()> {
-        Err(AikvError::Storage(
-            "SWAPDB is not supported with AiDb storage backend".to_string(),
-        ))
+    /// Flush every database's memtable and WAL to disk, returning once
+    /// what has been written so far would survive a crash
+    ///
+    /// Writes skip the per-write WAL fsync (see `new`), so this is how a
+    /// client gets a durability point.
+    pub fn sync(&self) -> Result<()> {
+        for (i, open) in self.open_databases().iter().enumerate() {
+            open.db.flush().map_err(|e| {
+                AikvError::Storage(format!("Failed to flush database {}: {}", i, e))
+            })?;
+        }
+        Ok(())
     }
 
-    /// Move a key from one database to another
-    pub fn move_key(&self, src_db: usize, dst_db: usize, key: &str) -> Result<bool> {
-        if src_db >= self.databases.len() || dst_db >= self.databases.len() {
+    /// Swap two databases
+    ///
+    /// Only the instances backing the two logical databases are swapped; no
+    /// data moves on disk. The new layout is written to the layout file so a
+    /// restart maps each logical database to the same directory again.
+    pub fn swap_db(&self, db1: usize, db2: usize) -> Result<()> {
+        let mut databases = self
+            .databases
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if db1 >= databases.len() || db2 >= databases.len() {
             return Err(AikvError::Storage(format!(
                 "Invalid database index: {} or {}",
-                src_db, dst_db
+                db1, db2
             )));
         }
+        if db1 == db2 {
+            return Ok(());
+        }
+
+        // Keep the directories of databases beyond the configured count
+        let previous = Self::read_layout(&self.layout_path)?;
 
-        let src = &self.databases[src_db];
-        let dst = &self.databases[dst_db];
+        databases.swap(db1, db2);
+        let mut layout: Vec<usize> = databases.iter().map(|open| open.dir).collect();
+        layout.extend(previous.into_iter().skip(databases.len()));
+        if let Err(e) = Self::write_layout(&self.layout_path, &layout) {
+            // Keep memory and disk in agreement
+            databases.swap(db1, db2);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Move a key from one database to another
+    pub fn move_key(&self, src_db: usize, dst_db: usize, key: &str) -> Result<bool> {
+        let src = &self.db(src_db)?;
+        let dst = &self.db(dst_db)?;
         let key_bytes = key.as_bytes();
+        let _guards = self.lock_keys(&[(src_db, key), (dst_db, key)]);
 
         // Check if key exists in source and is not expired
         if self.is_expired(src, key_bytes)? {
@@ -924,22 +1268,33 @@ impl AiDbStorageAdapter {
         src.delete(key_bytes)
             .map_err(|e| AikvError::Storage(format!("Failed to delete from source: {}", e)))?;
         let _ = src.delete(&expire_key);
+        self.adjust_key_count(dst_db, 1);
+        self.adjust_key_count(src_db, -1);
 
         Ok(true)
     }
 
     /// Rename a key
     pub fn rename_in_db(&self, db_index: usize, old_key: &str, new_key: &str) -> Result<bool> {
-        if db_index >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {}",
-                db_index
-            )));
-        }
+        self.rename(db_index, old_key, new_key, false)
+    }
 
-        let db = &self.databases[db_index];
+    /// Rename a key only if new key doesn't exist
+    pub fn rename_nx_in_db(&self, db_index: usize, old_key: &str, new_key: &str) -> Result<bool> {
+        self.rename(db_index, old_key, new_key, true)
+    }
+
+    /// Rename a key, unless `nx` is set and the new key exists
+    fn rename(&self, db_index: usize, old_key: &str, new_key: &str, nx: bool) -> Result<bool> {
+        let db = &self.db(db_index)?;
         let old_key_bytes = old_key.as_bytes();
         let new_key_bytes = new_key.as_bytes();
+        let _guards = self.lock_keys(&[(db_index, old_key), (db_index, new_key)]);
+
+        // Check if new key exists
+        if nx && Self::is_stored(db, new_key_bytes)? {
+            return Ok(false);
+        }
 
         // Check if old key exists and is not expired
         if self.is_expired(db, old_key_bytes)? {
@@ -954,7 +1309,13 @@ impl AiDbStorageAdapter {
             None => return Ok(false),
         };
 
+        // Writing the key over itself and then deleting the old name would lose it
+        if old_key == new_key {
+            return Ok(true);
+        }
+
         // Set new key
+        let replaced = Self::is_stored(db, new_key_bytes)?;
         db.put(new_key_bytes, &value)
             .map_err(|e| AikvError::Storage(format!("Failed to put value: {}", e)))?;
 
@@ -973,32 +1334,11 @@ impl AiDbStorageAdapter {
         db.delete(old_key_bytes)
             .map_err(|e| AikvError::Storage(format!("Failed to delete old key: {}", e)))?;
         let _ = db.delete(&old_expire_key);
-
-        Ok(true)
-    }
-
-    /// Rename a key only if new key doesn't exist
-    pub fn rename_nx_in_db(&self, db_index: usize, old_key: &str, new_key: &str) -> Result<bool> {
-        if db_index >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {}",
-                db_index
-            )));
-        }
-
-        let db = &self.databases[db_index];
-        let new_key_bytes = new_key.as_bytes();
-
-        // Check if new key exists
-        if db
-            .get(new_key_bytes)
-            .map_err(|e| AikvError::Storage(format!("Failed to check new key: {}", e)))?
-            .is_some()
-        {
-            return Ok(false);
+        if replaced {
+            self.adjust_key_count(db_index, -1);
         }
 
-        self.rename_in_db(db_index, old_key, new_key)
+        Ok(true)
     }
 
     /// Copy a key
@@ -1010,17 +1350,11 @@ impl AiDbStorageAdapter {
         dst_key: &str,
         replace: bool,
     ) -> Result<bool> {
-        if src_db >= self.databases.len() || dst_db >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {} or {}",
-                src_db, dst_db
-            )));
-        }
-
-        let src = &self.databases[src_db];
-        let dst = &self.databases[dst_db];
+        let src = &self.db(src_db)?;
+        let dst = &self.db(dst_db)?;
         let src_key_bytes = src_key.as_bytes();
         let dst_key_bytes = dst_key.as_bytes();
+        let _guards = self.lock_keys(&[(src_db, src_key), (dst_db, dst_key)]);
 
         // Check if source key exists and is not expired
         if self.is_expired(src, src_key_bytes)? {
@@ -1035,11 +1369,9 @@ impl AiDbStorageAdapter {
             None => return Ok(false),
         };
 
-        // Check if destination key exists
-        let dst_exists = dst
-            .get(dst_key_bytes)
-            .map_err(|e| AikvError::Storage(format!("Failed to check destination: {}", e)))?
-            .is_some();
+        // Check if destination key exists (an expired key counts as absent)
+        let dst_stored = Self::is_stored(dst, dst_key_bytes)?;
+        let dst_exists = dst_stored && !self.is_expired(dst, dst_key_bytes)?;
 
         if dst_exists && !replace {
             return Ok(false);
@@ -1058,88 +1390,59 @@ impl AiDbStorageAdapter {
         {
             dst.put(&dst_expire_key, &expire_bytes)
                 .map_err(|e| AikvError::Storage(format!("Failed to put expiration: {}", e)))?;
+        } else {
+            // Don't let a replaced key's TTL carry over to the copy
+            dst.delete(&dst_expire_key)
+                .map_err(|e| AikvError::Storage(format!("Failed to delete expiration: {}", e)))?;
+        }
+        if !dst_stored {
+            self.adjust_key_count(dst_db, 1);
         }
 
         Ok(true)
     }
 
     /// Get a random key from a database
+    ///
+    /// Reservoir-samples one pass over the keys, so every live key is
+    /// equally likely without collecting them first.
     pub fn random_key_in_db(&self, db_index: usize) -> Result<Option<String>> {
-        if db_index >= self.databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {}",
-                db_index
-            )));
-        }
-
-        let db = &self.databases[db_index];
+        let db = &self.db(db_index)?;
+        let mut rng = rand::thread_rng();
+        let mut chosen = None;
+        let mut seen = 0u64;
 
-        // Create an iterator and get the first valid key
         let mut iter = db.iter();
-
         while iter.valid() {
             let key = iter.key();
 
-            // Skip expiration metadata keys
-            if key.starts_with(b"__exp__:") {
-                iter.next();
-                continue;
-            }
-
-            // Check if expired
-            if self.is_expired(db, key)? {
+            // Skip expiration metadata and expired keys
+            if key.starts_with(b"__exp__:") || self.is_expired(db, key)? {
                 iter.next();
                 continue;
             }
 
-            if let Ok(key_str) = String::from_utf8(key.to_vec()) {
-                // Use current time as a simple random selection mechanism
-                // In a production system, this would use a proper random number generator
-                let now_ns = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_nanos() as u64;
-                if now_ns % 5 == 0 {
-                    return Ok(Some(key_str));
+            if let Ok(key_str) = std::str::from_utf8(key) {
+                // Keep the n-th key with probability 1/n
+                seen += 1;
+                if rng.gen_range(0..seen) == 0 {
+                    chosen = Some(key_str.to_string());
                 }
             }
 
             iter.next();
         }
 
-        // If we didn't find a key through random selection, return the first valid key
-        let mut iter = db.iter();
-
-        while iter.valid() {
-            let key = iter.key();
-
-            if key.starts_with(b"__exp__:") {
-                iter.next();
-                continue;
-            }
-
-            if self.is_expired(db, key)? {
-                iter.next();
-                continue;
-            }
-
-            if let Ok(key_str) = String::from_utf8(key.to_vec()) {
-                return Ok(Some(key_str));
-            }
-
-            iter.next();
-        }
-
-        Ok(None)
+        Ok(chosen)
     }
 
     /// Export all databases as StoredValue maps (for persistence)
     pub fn export_all_databases(&self) -> Result<Vec<HashMap<String, StoredValue>>> {
-        let mut result = Vec::with_capacity(self.databases.len());
+        let mut result = Vec::with_capacity(self.db_count());
 
-        for db_index in 0..self.databases.len() {
+        for db_index in 0..self.db_count() {
             let mut db_map = HashMap::new();
-            let db = &self.databases[db_index];
+            let db = &self.db(db_index)?;
 
             let mut iter = db.iter();
             while iter.valid() {
@@ -1306,6 +1609,49 @@ mod tests {
         assert_eq!(storage.get("key1").unwrap(), None);
     }
 
+    #[test]
+    fn test_plain_set_drops_old_expiration() {
+        let (_dir, storage) = create_temp_storage();
+        storage
+            .set_with_expiration_in_db(
+                0,
+                "key1".to_string(),
+                Bytes::from("old"),
+                AiDbStorageAdapter::current_time_ms() + 300,
+            )
+            .unwrap();
+        storage
+            .set_with_expiration_in_db(
+                0,
+                "key2".to_string(),
+                Bytes::from("old"),
+                AiDbStorageAdapter::current_time_ms() + 300,
+            )
+            .unwrap();
+
+        // Overwrite both shortly before they'd expire, through both set paths
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        storage
+            .set_in_db(0, "key1".to_string(), Bytes::from("new"))
+            .unwrap();
+        storage
+            .write_batch(
+                0,
+                vec![("key2".to_string(), BatchOp::Set(Bytes::from("new")))],
+            )
+            .unwrap();
+
+        // Past the old expiration time they're still there, without a TTL
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        for key in ["key1", "key2"] {
+            assert_eq!(
+                storage.get_from_db(0, key).unwrap(),
+                Some(Bytes::from("new"))
+            );
+            assert_eq!(storage.get_ttl_in_db(0, key).unwrap(), -1);
+        }
+    }
+
     // ========================================================================
     // Tests for new serialization-based storage (all data types)
     // ========================================================================
@@ -1429,6 +1775,124 @@ mod tests {
         assert_eq!(retrieved_list[1], Bytes::from("item2"));
     }
 
+    #[test]
+    fn test_concurrent_updates_are_not_lost() {
+        let (_dir, storage) = create_temp_storage();
+        let mut hash = HashMap::new();
+        hash.insert("count".to_string(), Bytes::from("0"));
+        storage
+            .set_value(0, "counter".to_string(), StoredValue::new_hash(hash))
+            .unwrap();
+
+        const THREADS: usize = 8;
+        const INCREMENTS: usize = 100;
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let storage = storage.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..INCREMENTS {
+                        let updated = storage
+                            .update_value(0, "counter", |v| {
+                                let hash = v.as_hash_mut()?;
+                                let count: usize =
+                                    String::from_utf8_lossy(&hash["count"]).parse().unwrap();
+                                hash.insert(
+                                    "count".to_string(),
+                                    Bytes::from((count + 1).to_string()),
+                                );
+                                Ok(())
+                            })
+                            .unwrap();
+                        assert!(updated);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let stored = storage.get_value(0, "counter").unwrap().unwrap();
+        assert_eq!(
+            stored.as_hash().unwrap()["count"],
+            Bytes::from((THREADS * INCREMENTS).to_string())
+        );
+    }
+
+    #[test]
+    fn test_concurrent_upserts_of_a_missing_key_are_not_lost() {
+        let (_dir, storage) = create_temp_storage();
+
+        const THREADS: usize = 8;
+        const INCREMENTS: usize = 100;
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let storage = storage.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..INCREMENTS {
+                        storage
+                            .update_or_insert_value(
+                                0,
+                                "counter",
+                                || StoredValue::new_hash(HashMap::new()),
+                                |v| {
+                                    let hash = v.as_hash_mut()?;
+                                    let count: usize = hash
+                                        .get("count")
+                                        .map(|c| String::from_utf8_lossy(c).parse().unwrap())
+                                        .unwrap_or(0);
+                                    hash.insert(
+                                        "count".to_string(),
+                                        Bytes::from((count + 1).to_string()),
+                                    );
+                                    Ok(())
+                                },
+                            )
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let stored = storage.get_value(0, "counter").unwrap().unwrap();
+        assert_eq!(
+            stored.as_hash().unwrap()["count"],
+            Bytes::from((THREADS * INCREMENTS).to_string())
+        );
+        assert_eq!(storage.dbsize_in_db(0).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_concurrent_renames_never_lose_or_duplicate_a_key() {
+        let (_dir, storage) = create_temp_storage();
+        storage
+            .set_in_db(0, "a".to_string(), Bytes::from("value"))
+            .unwrap();
+
+        let handles: Vec<_> = [("a", "b"), ("b", "a")]
+            .into_iter()
+            .map(|(from, to)| {
+                let storage = storage.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..200 {
+                        storage.rename_in_db(0, from, to).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let a = storage.exists_in_db(0, "a").unwrap();
+        let b = storage.exists_in_db(0, "b").unwrap();
+        assert!(a != b, "a: {}, b: {}", a, b);
+        assert_eq!(storage.dbsize_in_db(0).unwrap(), 1);
+    }
+
     #[test]
     fn test_delete_and_get() {
         let (_dir, storage) = create_temp_storage();
@@ -1491,6 +1955,176 @@ mod tests {
         assert_eq!(v1.as_string().unwrap(), &Bytes::from("db1 value"));
     }
 
+    #[test]
+    fn test_random_key_is_uniform() {
+        let (_dir, storage) = create_temp_storage();
+        assert_eq!(storage.random_key_in_db(0).unwrap(), None);
+        for i in 0..4 {
+            storage
+                .set_in_db(0, format!("key:{}", i), Bytes::from("v"))
+                .unwrap();
+        }
+
+        let mut hits = HashMap::new();
+        for _ in 0..2000 {
+            let key = storage.random_key_in_db(0).unwrap().unwrap();
+            *hits.entry(key).or_insert(0usize) += 1;
+        }
+
+        // 500 expected per key, give or take about 20
+        assert_eq!(hits.len(), 4);
+        assert!(
+            hits.values().all(|&count| (350..=650).contains(&count)),
+            "{:?}",
+            hits
+        );
+    }
+
+    #[test]
+    fn test_swap_db() {
+        let (dir, storage) = create_temp_storage();
+        storage
+            .set_in_db(0, "in0".to_string(), Bytes::from("zero"))
+            .unwrap();
+        storage
+            .set_in_db(1, "in1".to_string(), Bytes::from("one"))
+            .unwrap();
+
+        storage.swap_db(0, 1).unwrap();
+        assert_eq!(
+            storage.get_from_db(0, "in1").unwrap(),
+            Some(Bytes::from("one"))
+        );
+        assert_eq!(
+            storage.get_from_db(1, "in0").unwrap(),
+            Some(Bytes::from("zero"))
+        );
+        assert!(!storage.exists_in_db(0, "in0").unwrap());
+        assert!(storage.swap_db(0, 2).is_err());
+
+        // The swap survives a restart
+        drop(storage);
+        let storage = AiDbStorageAdapter::new(dir.path(), 2).unwrap();
+        assert_eq!(
+            storage.get_from_db(0, "in1").unwrap(),
+            Some(Bytes::from("one"))
+        );
+        assert_eq!(
+            storage.get_from_db(1, "in0").unwrap(),
+            Some(Bytes::from("zero"))
+        );
+
+        // Swapping back restores the original layout
+        storage.swap_db(1, 0).unwrap();
+        assert_eq!(
+            storage.get_from_db(0, "in0").unwrap(),
+            Some(Bytes::from("zero"))
+        );
+    }
+
+    #[test]
+    fn test_dbsize_tracks_writes_and_expirations() {
+        let (dir, storage) = create_temp_storage();
+        let dbsize = |db| storage.dbsize_in_db(db).unwrap();
+
+        storage
+            .set_in_db(0, "a".to_string(), Bytes::from("1"))
+            .unwrap();
+        storage
+            .set_in_db(0, "b".to_string(), Bytes::from("2"))
+            .unwrap();
+        // An overwrite isn't a new key
+        storage
+            .set_in_db(0, "a".to_string(), Bytes::from("3"))
+            .unwrap();
+        assert_eq!(dbsize(0), 2);
+
+        storage
+            .write_batch(
+                0,
+                vec![
+                    ("c".to_string(), BatchOp::Set(Bytes::from("4"))),
+                    ("b".to_string(), BatchOp::Delete),
+                    ("missing".to_string(), BatchOp::Delete),
+                ],
+            )
+            .unwrap();
+        assert_eq!(dbsize(0), 2);
+
+        assert!(storage.delete_from_db(0, "a").unwrap());
+        assert!(!storage.delete_from_db(0, "a").unwrap());
+        assert_eq!(dbsize(0), 1);
+
+        assert!(storage.rename_in_db(0, "c", "c").unwrap());
+        assert!(storage.rename_in_db(0, "c", "d").unwrap());
+        assert!(storage.copy_in_db(0, 0, "d", "e", false).unwrap());
+        assert!(storage.move_key(0, 1, "e").unwrap());
+        assert_eq!((dbsize(0), dbsize(1)), (1, 1));
+
+        // Lazy expiration: the read that finds the key expired drops it
+        let soon = AiDbStorageAdapter::current_time_ms() + 100;
+        storage
+            .set_with_expiration_in_db(0, "x".to_string(), Bytes::from("v"), soon)
+            .unwrap();
+        storage
+            .set_with_expiration_in_db(0, "y".to_string(), Bytes::from("v"), soon)
+            .unwrap();
+        assert_eq!(dbsize(0), 3);
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert!(storage.get_value(0, "x").unwrap().is_none());
+        assert_eq!(dbsize(0), 2);
+        assert_eq!(storage.exact_dbsize_in_db(0).unwrap(), 1);
+
+        // The sweeper removes the rest
+        assert_eq!(storage.remove_expired_in_db(0).unwrap(), vec!["y"]);
+        assert_eq!(dbsize(0), 1);
+
+        // Counts follow their database through SWAPDB and survive a reopen
+        storage.swap_db(0, 1).unwrap();
+        storage
+            .set_in_db(1, "f".to_string(), Bytes::from("5"))
+            .unwrap();
+        assert_eq!((dbsize(0), dbsize(1)), (1, 2));
+        drop(storage);
+        let storage = AiDbStorageAdapter::new(dir.path(), 2).unwrap();
+        assert_eq!(storage.dbsize_in_db(1).unwrap(), 2);
+
+        storage.flush_db(1).unwrap();
+        assert_eq!(storage.dbsize_in_db(1).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_concurrent_expirations_count_a_key_once() {
+        let (_dir, storage) = create_temp_storage();
+        storage
+            .set_in_db(0, "live".to_string(), Bytes::from("v"))
+            .unwrap();
+        let soon = AiDbStorageAdapter::current_time_ms() + 50;
+        storage
+            .set_with_expiration_in_db(0, "x".to_string(), Bytes::from("v"), soon)
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        // Readers and the sweeper all find "x" expired, but only one removes it
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let storage = storage.clone();
+                std::thread::spawn(move || {
+                    if i == 0 {
+                        storage.remove_expired_in_db(0).unwrap();
+                    } else {
+                        assert!(storage.get_value(0, "x").unwrap().is_none());
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(storage.dbsize_in_db(0).unwrap(), 1);
+    }
+
     #[test]
     fn test_complex_list_operations() {
         let (_dir, storage) = create_temp_storage();
@@ -37,20 +37,49 @@
 
 use crate::error::{AikvError, Result};
 use bytes::Bytes;
+use rand::seq::IteratorRandom;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+thread_local! {
+    /// Set while a CLIENT NO-TOUCH connection's command runs on this thread
+    static NO_TOUCH: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f` without the keys it looks up counting as accessed, so their
+/// idle time and access frequency are left as they were (CLIENT NO-TOUCH)
+pub fn without_touching<R>(f: impl FnOnce() -> R) -> R {
+    /// Restores the previous setting, even if `f` panics
+    struct Restore(bool);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            NO_TOUCH.with(|no_touch| no_touch.set(self.0));
+        }
+    }
+
+    let _restore = Restore(NO_TOUCH.with(|no_touch| no_touch.replace(true)));
+    f()
+}
+
 /// Batch operation for atomic writes
 #[derive(Debug, Clone)]
 pub enum BatchOp {
-    /// Set a key to a value
+    /// Set a key to a string value, dropping any TTL it had
     Set(Bytes),
+    /// Set a key to a value of any type, with the value's own expiration
+    SetValue(StoredValue),
     /// Delete a key
     Delete,
 }
 
+/// ID of a stream entry: milliseconds and a sequence number within them
+pub type StreamId = (u64, u64);
+
 /// Different value types supported by the storage.
 ///
 /// These types correspond to Redis data types and are used by the storage layer
@@ -68,6 +97,8 @@ pub enum ValueType {
     Set(HashSet<Vec<u8>>), // Using Vec<u8> instead of Bytes for HashSet compatibility
     /// Sorted Set type - ordered collection with scores (Redis ZSET)
     ZSet(BTreeMap<Vec<u8>, f64>), // member -> score mapping
+    /// Stream type - entries of field-value pairs in ID order (Redis STREAM)
+    Stream(BTreeMap<StreamId, Vec<(Bytes, Bytes)>>),
 }
 
 /// Value with optional expiration time.
@@ -79,8 +110,118 @@ pub struct StoredValue {
     pub(crate) value: ValueType,
     /// Expiration time in milliseconds since UNIX epoch
     pub(crate) expires_at: Option<u64>,
+    /// Last time the key was accessed, used by LRU eviction
+    pub(crate) last_access: LastAccess,
+    /// Logarithmic access counter, used by LFU eviction
+    pub(crate) frequency: AccessFrequency,
+}
+
+/// Last access time in milliseconds since UNIX epoch.
+///
+/// Stored atomically so that reads holding only a shared lock can still
+/// touch the key.
+#[derive(Debug)]
+pub(crate) struct LastAccess(AtomicU64);
+
+impl LastAccess {
+    fn now() -> Self {
+        Self(AtomicU64::new(current_time_ms()))
+    }
+
+    fn touch(&self) {
+        self.0.store(current_time_ms(), Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn set(&self, ms: u64) {
+        self.0.store(ms, Ordering::Relaxed);
+    }
+}
+
+impl Clone for LastAccess {
+    fn clone(&self) -> Self {
+        Self(AtomicU64::new(self.get()))
+    }
+}
+
+/// Counter given to new keys, so they aren't the first to be evicted
+const LFU_INIT_VAL: u64 = 5;
+
+/// How fast the counter saturates; Redis' default `lfu-log-factor`
+const LFU_LOG_FACTOR: f64 = 10.0;
+
+/// Minutes without access for the counter to drop by one; Redis' default
+/// `lfu-decay-time`
+const LFU_DECAY_MINUTES: u64 = 1;
+
+/// Access frequency as Redis' LFU keeps it: an 8-bit counter incremented
+/// with a probability that falls as it grows (so it approximates the
+/// logarithm of the access count) and decayed as time passes without access.
+///
+/// The counter and the minute it was last updated are packed into one atomic
+/// so that reads holding only a shared lock can still count an access.
+#[derive(Debug)]
+pub(crate) struct AccessFrequency(AtomicU64);
+
+impl AccessFrequency {
+    fn new() -> Self {
+        Self(AtomicU64::new(Self::pack(LFU_INIT_VAL)))
+    }
+
+    fn pack(counter: u64) -> u64 {
+        (current_time_ms() / 60_000) << 8 | counter
+    }
+
+    /// Current counter, after the decay owed since the last update
+    /// (Redis' `LFUDecrAndReturn`)
+    fn get(&self) -> u8 {
+        let packed = self.0.load(Ordering::Relaxed);
+        let elapsed = (current_time_ms() / 60_000).saturating_sub(packed >> 8);
+        (packed & 0xFF).saturating_sub(elapsed / LFU_DECAY_MINUTES) as u8
+    }
+
+    /// Count an access (Redis' `LFULogIncr` applied to the decayed counter)
+    fn touch(&self) {
+        let mut counter = u64::from(self.get());
+        if counter < 255 {
+            let base = counter.saturating_sub(LFU_INIT_VAL) as f64;
+            if rand::random::<f64>() < 1.0 / (base * LFU_LOG_FACTOR + 1.0) {
+                counter += 1;
+            }
+        }
+        self.0.store(Self::pack(counter), Ordering::Relaxed);
+    }
+
+    fn set(&self, counter: u8) {
+        self.0
+            .store(Self::pack(u64::from(counter)), Ordering::Relaxed);
+    }
+}
+
+impl Clone for AccessFrequency {
+    fn clone(&self) -> Self {
+        Self(AtomicU64::new(self.0.load(Ordering::Relaxed)))
+    }
+}
+
+/// Get current time in milliseconds since UNIX epoch
+fn current_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
 }
 
+/// Approximate bookkeeping overhead per key (hash table slot, key header,
+/// expiration and access metadata)
+const ENTRY_OVERHEAD: usize = 64;
+
+/// Approximate bookkeeping overhead per element of a collection value
+const ELEMENT_OVERHEAD: usize = 16;
+
 // Serializable versions for storage (optimized for bincode)
 #[derive(Serialize, Deserialize)]
 enum SerializableValueType {
@@ -89,6 +230,7 @@ enum SerializableValueType {
     Hash(Vec<(String, Vec<u8>)>),
     Set(Vec<Vec<u8>>),
     ZSet(Vec<(Vec<u8>, f64)>),
+    Stream(Vec<(StreamId, Vec<(Vec<u8>, Vec<u8>)>)>),
 }
 
 /// Serializable representation of StoredValue for persistence.
@@ -118,6 +260,18 @@ impl StoredValue {
             ValueType::ZSet(zset) => {
                 SerializableValueType::ZSet(zset.iter().map(|(k, v)| (k.clone(), *v)).collect())
             }
+            ValueType::Stream(entries) => SerializableValueType::Stream(
+                entries
+                    .iter()
+                    .map(|(id, fields)| {
+                        let fields = fields
+                            .iter()
+                            .map(|(f, v)| (f.to_vec(), v.to_vec()))
+                            .collect();
+                        (*id, fields)
+                    })
+                    .collect(),
+            ),
         };
         SerializableStoredValue {
             value,
@@ -142,10 +296,24 @@ impl StoredValue {
             SerializableValueType::ZSet(vec_zset) => {
                 ValueType::ZSet(vec_zset.into_iter().collect())
             }
+            SerializableValueType::Stream(vec_entries) => ValueType::Stream(
+                vec_entries
+                    .into_iter()
+                    .map(|(id, fields)| {
+                        let fields = fields
+                            .into_iter()
+                            .map(|(f, v)| (Bytes::from(f), Bytes::from(v)))
+                            .collect();
+                        (id, fields)
+                    })
+                    .collect(),
+            ),
         };
         Self {
             value,
             expires_at: serializable.expires_at,
+            last_access: LastAccess::now(),
+            frequency: AccessFrequency::new(),
         }
     }
 }
@@ -155,6 +323,8 @@ impl StoredValue {
         Self {
             value: ValueType::String(data),
             expires_at: None,
+            last_access: LastAccess::now(),
+            frequency: AccessFrequency::new(),
         }
     }
 
@@ -162,6 +332,8 @@ impl StoredValue {
         Self {
             value: ValueType::List(list),
             expires_at: None,
+            last_access: LastAccess::now(),
+            frequency: AccessFrequency::new(),
         }
     }
 
@@ -169,6 +341,8 @@ impl StoredValue {
         Self {
             value: ValueType::Hash(hash),
             expires_at: None,
+            last_access: LastAccess::now(),
+            frequency: AccessFrequency::new(),
         }
     }
 
@@ -176,6 +350,8 @@ impl StoredValue {
         Self {
             value: ValueType::Set(set),
             expires_at: None,
+            last_access: LastAccess::now(),
+            frequency: AccessFrequency::new(),
         }
     }
 
@@ -183,6 +359,17 @@ impl StoredValue {
         Self {
             value: ValueType::ZSet(zset),
             expires_at: None,
+            last_access: LastAccess::now(),
+            frequency: AccessFrequency::new(),
+        }
+    }
+
+    pub fn new_stream(entries: BTreeMap<StreamId, Vec<(Bytes, Bytes)>>) -> Self {
+        Self {
+            value: ValueType::Stream(entries),
+            expires_at: None,
+            last_access: LastAccess::now(),
+            frequency: AccessFrequency::new(),
         }
     }
 
@@ -190,6 +377,8 @@ impl StoredValue {
         Self {
             value,
             expires_at: Some(expires_at),
+            last_access: LastAccess::now(),
+            frequency: AccessFrequency::new(),
         }
     }
 
@@ -212,6 +401,7 @@ impl StoredValue {
             ValueType::Hash(_) => "hash",
             ValueType::Set(_) => "set",
             ValueType::ZSet(_) => "zset",
+            ValueType::Stream(_) => "stream",
         }
     }
 
@@ -315,6 +505,26 @@ impl StoredValue {
         }
     }
 
+    /// Check if value is of Stream type and return reference to it
+    pub fn as_stream(&self) -> Result<&BTreeMap<StreamId, Vec<(Bytes, Bytes)>>> {
+        match &self.value {
+            ValueType::Stream(entries) => Ok(entries),
+            _ => Err(AikvError::WrongType(
+                "Operation against a key holding the wrong kind of value".to_string(),
+            )),
+        }
+    }
+
+    /// Check if value is of Stream type and return mutable reference to it
+    pub fn as_stream_mut(&mut self) -> Result<&mut BTreeMap<StreamId, Vec<(Bytes, Bytes)>>> {
+        match &mut self.value {
+            ValueType::Stream(entries) => Ok(entries),
+            _ => Err(AikvError::WrongType(
+                "Operation against a key holding the wrong kind of value".to_string(),
+            )),
+        }
+    }
+
     /// Get expiration time in milliseconds since UNIX epoch
     pub fn expires_at(&self) -> Option<u64> {
         self.expires_at
@@ -324,17 +534,421 @@ impl StoredValue {
     pub fn set_expiration(&mut self, expires_at: Option<u64>) {
         self.expires_at = expires_at;
     }
+
+    /// Record an access to this value, unless running under [`without_touching`]
+    pub fn touch(&self) {
+        if NO_TOUCH.with(Cell::get) {
+            return;
+        }
+        self.last_access.touch();
+        self.frequency.touch();
+    }
+
+    /// Logarithmic access frequency reported by OBJECT FREQ
+    pub fn lfu_frequency(&self) -> u8 {
+        self.frequency.get()
+    }
+
+    /// Set the access frequency (RESTORE's FREQ option)
+    pub fn set_lfu_frequency(&self, counter: u8) {
+        self.frequency.set(counter);
+    }
+
+    /// Milliseconds elapsed since this value was last accessed
+    pub fn idle_time_ms(&self) -> u64 {
+        current_time_ms().saturating_sub(self.last_access.get())
+    }
+
+    /// Clone this value as a new, independent key (as COPY does)
+    ///
+    /// The data and expiration are cloned; access tracking starts fresh.
+    pub fn to_copy(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            expires_at: self.expires_at,
+            last_access: LastAccess::now(),
+            frequency: AccessFrequency::new(),
+        }
+    }
+
+    /// Backdate the last access time so the value reports `idle_ms` of idle time
+    pub fn set_idle_time_ms(&self, idle_ms: u64) {
+        self.last_access
+            .set(current_time_ms().saturating_sub(idle_ms));
+    }
+
+    /// Approximate number of bytes held by the value payload
+    pub fn approximate_size(&self) -> usize {
+        match &self.value {
+            ValueType::String(data) => data.len(),
+            ValueType::List(list) => list.iter().map(|b| b.len() + ELEMENT_OVERHEAD).sum(),
+            ValueType::Hash(hash) => hash
+                .iter()
+                .map(|(k, v)| k.len() + v.len() + ELEMENT_OVERHEAD)
+                .sum(),
+            ValueType::Set(set) => set.iter().map(|m| m.len() + ELEMENT_OVERHEAD).sum(),
+            ValueType::ZSet(zset) => zset
+                .keys()
+                .map(|m| m.len() + std::mem::size_of::<f64>() + ELEMENT_OVERHEAD)
+                .sum(),
+            ValueType::Stream(entries) => entries
+                .values()
+                .map(|fields| {
+                    std::mem::size_of::<StreamId>()
+                        + fields
+                            .iter()
+                            .map(|(f, v)| f.len() + v.len() + ELEMENT_OVERHEAD)
+                            .sum::<usize>()
+                })
+                .sum(),
+        }
+    }
+}
+
+/// Approximate number of bytes accounted for a key and its value
+///
+/// This is what maxmemory counts against the limit, and what MEMORY USAGE
+/// reports.
+pub fn entry_size(key: &str, value: &StoredValue) -> usize {
+    ENTRY_OVERHEAD + key.len() + value.approximate_size()
+}
+
+/// Database containing key-value pairs.
+///
+/// Keeps an approximate byte count of its contents up to date on every
+/// insert and removal so that memory usage can be checked without a scan.
+#[derive(Default)]
+struct Database {
+    entries: HashMap<String, StoredValue>,
+    used_memory: usize,
+}
+
+impl Database {
+    fn get(&self, key: &str) -> Option<&StoredValue> {
+        self.entries.get(key)
+    }
+
+    /// Mutable access for changes that don't affect the value's size (e.g. TTL).
+    /// Use `update` for anything that may grow or shrink the value.
+    fn get_mut(&mut self, key: &str) -> Option<&mut StoredValue> {
+        self.entries.get_mut(key)
+    }
+
+    fn contains_key(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    fn contains_live_key(&self, key: &str) -> bool {
+        matches!(self.entries.get(key), Some(v) if !v.is_expired())
+    }
+
+    fn iter(&self) -> std::collections::hash_map::Iter<'_, String, StoredValue> {
+        self.entries.iter()
+    }
+
+    fn insert(&mut self, key: String, value: StoredValue) -> Option<StoredValue> {
+        // Like Redis, overwriting a key keeps its access frequency
+        if let Some(old_value) = self.entries.get(&key).filter(|v| !v.is_expired()) {
+            value.frequency.set(old_value.frequency.get());
+        }
+        let key_len = key.len();
+        self.used_memory += ENTRY_OVERHEAD + key_len + value.approximate_size();
+        let old = self.entries.insert(key, value);
+        if let Some(ref old_value) = old {
+            self.release(ENTRY_OVERHEAD + key_len + old_value.approximate_size());
+        }
+        old
+    }
+
+    fn remove(&mut self, key: &str) -> Option<StoredValue> {
+        let old = self.entries.remove(key);
+        if let Some(ref old_value) = old {
+            self.release(entry_size(key, old_value));
+        }
+        old
+    }
+
+    /// Apply `f` to a live value, keeping the memory accounting in sync.
+    /// Expired values are removed and reported as missing.
+    fn update<F>(&mut self, key: &str, f: F) -> Result<bool>
+    where
+        F: FnOnce(&mut StoredValue) -> Result<()>,
+    {
+        let stored = match self.entries.get_mut(key) {
+            Some(stored) => stored,
+            None => return Ok(false),
+        };
+        if stored.is_expired() {
+            self.remove(key);
+            return Ok(false);
+        }
+
+        let before = stored.approximate_size();
+        stored.touch();
+        let result = f(stored);
+        let after = stored.approximate_size();
+        self.used_memory = (self.used_memory + after).saturating_sub(before);
+        result.map(|_| true)
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.used_memory = 0;
+    }
+
+    /// Empty the database and hand back what it held, so the caller decides
+    /// where the entries are freed
+    fn take(&mut self) -> HashMap<String, StoredValue> {
+        self.used_memory = 0;
+        std::mem::take(&mut self.entries)
+    }
+
+    fn remove_expired(&mut self) -> Vec<String> {
+        let expired: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, v)| v.is_expired())
+            .map(|(k, _)| k.clone())
+            .collect();
+        for key in &expired {
+            self.remove(key);
+        }
+        expired
+    }
+
+    /// Number of bytes a write of `value` under `key` would add
+    fn growth(&self, key: &str, value: &StoredValue) -> usize {
+        let existing = self.get(key).map(|v| entry_size(key, v)).unwrap_or(0);
+        entry_size(key, value).saturating_sub(existing)
+    }
+
+    fn release(&mut self, bytes: usize) {
+        self.used_memory = self.used_memory.saturating_sub(bytes);
+    }
+
+    /// Pick the key this database would evict under `policy`, together with a
+    /// score where lower means "evict first", never picking `protected`
+    ///
+    /// Like Redis, only [`EVICTION_SAMPLES`] eligible keys, from a random
+    /// point of the table on, are compared rather than every key.
+    fn eviction_candidate(
+        &self,
+        policy: EvictionPolicy,
+        protected: Option<&str>,
+    ) -> Option<(u64, String)> {
+        if policy == EvictionPolicy::NoEviction || self.entries.is_empty() {
+            return None;
+        }
+        let volatile = matches!(
+            policy,
+            EvictionPolicy::VolatileLru | EvictionPolicy::VolatileTtl | EvictionPolicy::VolatileLfu
+        );
+        let start = rand::thread_rng().gen_range(0..self.entries.len());
+        self.entries
+            .iter()
+            .skip(start)
+            .chain(self.entries.iter().take(start))
+            .filter(|(k, v)| Some(k.as_str()) != protected && (!volatile || v.expires_at.is_some()))
+            .take(EVICTION_SAMPLES)
+            .map(|(k, v)| {
+                let score = match policy {
+                    EvictionPolicy::AllKeysLru | EvictionPolicy::VolatileLru => v.last_access.get(),
+                    EvictionPolicy::VolatileTtl => v.expires_at.unwrap_or(u64::MAX),
+                    EvictionPolicy::AllKeysLfu | EvictionPolicy::VolatileLfu => {
+                        u64::from(v.frequency.get())
+                    }
+                    EvictionPolicy::AllKeysRandom | EvictionPolicy::NoEviction => {
+                        rand::random::<u64>()
+                    }
+                };
+                (score, k)
+            })
+            .min_by_key(|(score, _)| *score)
+            .map(|(score, k)| (score, k.clone()))
+    }
+}
+
+/// Number of keys compared per database when picking one to evict, as
+/// Redis's default `maxmemory-samples`
+const EVICTION_SAMPLES: usize = 5;
+
+/// Acquire the read lock of a single database
+///
+/// A command panicking while it held the lock poisons it, but the database
+/// behind it is still valid, so the lock is recovered rather than every later
+/// command failing.
+fn lock_for_read(db: &RwLock<Database>) -> RwLockReadGuard<'_, Database> {
+    db.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Acquire the write lock of a single database, recovering it if poisoned
+fn lock_for_write(db: &RwLock<Database>) -> RwLockWriteGuard<'_, Database> {
+    db.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Free the contents of flushed databases on a background thread, so
+/// dropping a large dataset doesn't hold up the command that flushed it
+fn free_in_background(contents: Vec<HashMap<String, StoredValue>>) {
+    if contents.iter().all(HashMap::is_empty) {
+        return;
+    }
+    std::thread::spawn(move || drop(contents));
+}
+
+/// Pick the next key to evict under `policy` across all databases
+fn eviction_candidate(
+    dbs: &[RwLockWriteGuard<'_, Database>],
+    policy: EvictionPolicy,
+    protected: Option<(usize, &str)>,
+) -> Option<(usize, String)> {
+    let mut best: Option<(u64, usize, String)> = None;
+    for (db_index, db) in dbs.iter().enumerate() {
+        let protected = protected
+            .filter(|(protected_db, _)| *protected_db == db_index)
+            .map(|(_, key)| key);
+        if let Some((score, key)) = db.eviction_candidate(policy, protected) {
+            let better = match &best {
+                Some((best_score, _, _)) => score < *best_score,
+                None => true,
+            };
+            if better {
+                best = Some((score, db_index, key));
+            }
+        }
+    }
+    best.map(|(_, db_index, key)| (db_index, key))
+}
+
+/// Evict keys other than `protected` from the locked databases until
+/// `incoming` more bytes fit under the limit
+///
+/// Returns `AikvError::OutOfMemory` when the policy is `noeviction` or no
+/// key is eligible for eviction.
+fn evict(
+    dbs: &mut [RwLockWriteGuard<'_, Database>],
+    limit: MemoryLimit,
+    incoming: usize,
+    protected: Option<(usize, &str)>,
+) -> Result<()> {
+    let used = |dbs: &[RwLockWriteGuard<'_, Database>]| -> usize {
+        dbs.iter().map(|db| db.used_memory).sum()
+    };
+    if used(dbs) + incoming <= limit.maxmemory {
+        return Ok(());
+    }
+    if limit.policy == EvictionPolicy::NoEviction {
+        return Err(AikvError::OutOfMemory);
+    }
+
+    // Expired keys are free to reclaim before touching live data
+    for db in dbs.iter_mut() {
+        db.remove_expired();
+    }
+
+    while used(dbs) + incoming > limit.maxmemory {
+        match eviction_candidate(dbs, limit.policy, protected) {
+            Some((db_index, key)) => {
+                dbs[db_index].remove(&key);
+            }
+            None => return Err(AikvError::OutOfMemory),
+        }
+    }
+    Ok(())
+}
+
+/// Policy applied when a write would push memory usage past `maxmemory`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvictionPolicy {
+    /// Reject writes with an OOM error
+    #[default]
+    NoEviction,
+    /// Evict the least recently used key among all keys
+    AllKeysLru,
+    /// Evict a random key among all keys
+    AllKeysRandom,
+    /// Evict the least recently used key among keys with a TTL
+    VolatileLru,
+    /// Evict the key with the nearest expiration time
+    VolatileTtl,
+    /// Evict the least frequently used key among all keys
+    AllKeysLfu,
+    /// Evict the least frequently used key among keys with a TTL
+    VolatileLfu,
+}
+
+impl EvictionPolicy {
+    /// Parse an eviction policy from its Redis name
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "noeviction" => Some(Self::NoEviction),
+            "allkeys-lru" => Some(Self::AllKeysLru),
+            "allkeys-random" => Some(Self::AllKeysRandom),
+            "volatile-lru" => Some(Self::VolatileLru),
+            "volatile-ttl" => Some(Self::VolatileTtl),
+            "allkeys-lfu" => Some(Self::AllKeysLfu),
+            "volatile-lfu" => Some(Self::VolatileLfu),
+            _ => None,
+        }
+    }
+
+    /// Redis name of the policy
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NoEviction => "noeviction",
+            Self::AllKeysLru => "allkeys-lru",
+            Self::AllKeysRandom => "allkeys-random",
+            Self::VolatileLru => "volatile-lru",
+            Self::VolatileTtl => "volatile-ttl",
+            Self::AllKeysLfu => "allkeys-lfu",
+            Self::VolatileLfu => "volatile-lfu",
+        }
+    }
+
+    /// Whether the policy tracks access frequency rather than recency
+    pub fn is_lfu(&self) -> bool {
+        matches!(self, Self::AllKeysLfu | Self::VolatileLfu)
+    }
+}
+
+/// Memory limit configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemoryLimit {
+    /// Maximum number of bytes for the dataset (0 means unlimited)
+    pub maxmemory: usize,
+    /// What to do when the limit is reached
+    pub policy: EvictionPolicy,
+}
+
+/// Bytes set aside for a write between making room for it and applying it,
+/// so concurrent writes can't both claim the same headroom
+///
+/// Released on drop, which must come after the write is accounted in its
+/// database.
+struct Reservation<'a> {
+    reserved: &'a Mutex<usize>,
+    bytes: usize,
 }
 
-/// Database containing key-value pairs
-type Database = HashMap<String, StoredValue>;
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if self.bytes > 0 {
+            let mut reserved = self.reserved.lock().unwrap_or_else(PoisonError::into_inner);
+            *reserved = reserved.saturating_sub(self.bytes);
+        }
+    }
+}
 
 /// Simple in-memory storage adapter
 /// This will be replaced with AiDb integration in the future
 #[derive(Clone)]
 pub struct StorageAdapter {
-    /// Multiple databases (default: 16 databases like Redis)
-    databases: Arc<RwLock<Vec<Database>>>,
+    /// Multiple databases (default: 16 databases like Redis), each behind
+    /// its own lock so writes to different databases don't contend
+    databases: Arc<Vec<RwLock<Database>>>,
+    /// Memory limit and eviction policy
+    memory_limit: Arc<RwLock<MemoryLimit>>,
+    /// Bytes reserved by writes that are about to be applied
+    reserved: Arc<Mutex<usize>>,
 }
 
 impl StorageAdapter {
@@ -345,34 +959,175 @@ impl StorageAdapter {
     pub fn with_db_count(count: usize) -> Self {
         let mut databases = Vec::with_capacity(count);
         for _ in 0..count {
-            databases.push(HashMap::new());
+            databases.push(RwLock::new(Database::default()));
         }
         Self {
-            databases: Arc::new(RwLock::new(databases)),
+            databases: Arc::new(databases),
+            memory_limit: Arc::new(RwLock::new(MemoryLimit::default())),
+            reserved: Arc::new(Mutex::new(0)),
         }
     }
 
-    /// Get current time in milliseconds
-    fn current_time_ms() -> u64 {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64
+    /// Set the memory limit and eviction policy
+    pub fn set_memory_limit(&self, limit: MemoryLimit) -> Result<()> {
+        let mut memory_limit = self
+            .memory_limit
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        *memory_limit = limit;
+        Ok(())
     }
 
-    /// Clean up expired keys in a database
-    /// Reserved for future background cleanup task
-    #[allow(dead_code)]
-    fn cleanup_expired(&self, db_index: usize) -> Result<()> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+    /// Get the memory limit and eviction policy
+    pub fn memory_limit(&self) -> Result<MemoryLimit> {
+        let memory_limit = self
+            .memory_limit
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        Ok(*memory_limit)
+    }
 
-        if let Some(db) = databases.get_mut(db_index) {
-            db.retain(|_, v| !v.is_expired());
+    /// Number of logical databases
+    pub fn db_count(&self) -> usize {
+        self.databases.len()
+    }
+
+    /// Approximate number of bytes used by all databases
+    pub fn used_memory(&self) -> Result<usize> {
+        let mut total = 0;
+        for db in self.databases.iter() {
+            let db = lock_for_read(db);
+            total += db.used_memory;
+        }
+        Ok(total)
+    }
+
+    /// Acquire the read lock of a database, or `None` for an invalid index
+    fn read_db(&self, db_index: usize) -> Result<Option<RwLockReadGuard<'_, Database>>> {
+        Ok(self.databases.get(db_index).map(lock_for_read))
+    }
+
+    /// Acquire the write lock of a database, or `None` for an invalid index
+    fn write_db(&self, db_index: usize) -> Result<Option<RwLockWriteGuard<'_, Database>>> {
+        Ok(self.databases.get(db_index).map(lock_for_write))
+    }
+
+    /// Acquire the write locks of every database, in index order like
+    /// `write_pair`, so that nothing changes while keys are evicted
+    fn write_all(&self) -> Vec<RwLockWriteGuard<'_, Database>> {
+        self.databases.iter().map(lock_for_write).collect()
+    }
+
+    /// Acquire the write locks of two distinct databases.
+    ///
+    /// Locks are always taken in ascending index order so that concurrent
+    /// cross-database operations (SWAPDB, MOVE, COPY) can't deadlock. The
+    /// guards are returned in argument order.
+    fn write_pair(
+        &self,
+        db1: usize,
+        db2: usize,
+    ) -> Result<(
+        RwLockWriteGuard<'_, Database>,
+        RwLockWriteGuard<'_, Database>,
+    )> {
+        if db1 == db2 || db1 >= self.databases.len() || db2 >= self.databases.len() {
+            return Err(AikvError::Storage(format!(
+                "Invalid database index: {} or {}",
+                db1, db2
+            )));
+        }
+
+        if db1 < db2 {
+            let first = lock_for_write(&self.databases[db1]);
+            let second = lock_for_write(&self.databases[db2]);
+            Ok((first, second))
+        } else {
+            let second = lock_for_write(&self.databases[db2]);
+            let first = lock_for_write(&self.databases[db1]);
+            Ok((first, second))
+        }
+    }
+
+    /// Make room for a write of `value` under `key` in `db_index`, evicting keys
+    /// according to the configured policy, and reserve it until the returned
+    /// reservation is dropped.
+    ///
+    /// Must be called without holding any database lock, since eviction may
+    /// visit every database. Returns `AikvError::OutOfMemory` when the policy is
+    /// `noeviction` or no key is eligible for eviction.
+    fn reserve(&self, db_index: usize, key: &str, value: &StoredValue) -> Result<Reservation<'_>> {
+        if self.memory_limit()?.maxmemory == 0 {
+            return self.reserve_bytes(0, None);
+        }
+        let incoming = match self.read_db(db_index)? {
+            Some(db) => db.growth(key, value),
+            None => 0,
+        };
+        self.reserve_bytes(incoming, Some((db_index, key)))
+    }
+
+    /// Make room for `incoming` more bytes, evicting keys other than
+    /// `protected`, and reserve them until the returned reservation is dropped
+    ///
+    /// Must be called without holding any database lock.
+    fn reserve_bytes(
+        &self,
+        incoming: usize,
+        protected: Option<(usize, &str)>,
+    ) -> Result<Reservation<'_>> {
+        let mut reservation = Reservation {
+            reserved: &self.reserved,
+            bytes: 0,
+        };
+        let limit = self.memory_limit()?;
+        if limit.maxmemory == 0 {
+            return Ok(reservation);
+        }
+        loop {
+            {
+                // Checked and reserved under one lock, so two writes can't
+                // both fit into the same headroom
+                let mut reserved = self.reserved.lock().unwrap_or_else(PoisonError::into_inner);
+                if *reserved + self.used_memory()? + incoming <= limit.maxmemory {
+                    *reserved += incoming;
+                    reservation.bytes = incoming;
+                    return Ok(reservation);
+                }
+            }
+            self.make_room(incoming, protected)?;
+        }
+    }
+
+    /// Evict keys other than `protected` until `incoming` more bytes, and
+    /// whatever other writes have reserved, fit under `maxmemory`.
+    ///
+    /// Must be called without holding any database lock.
+    fn make_room(&self, incoming: usize, protected: Option<(usize, &str)>) -> Result<()> {
+        let limit = self.memory_limit()?;
+        let incoming = incoming + *self.reserved.lock().unwrap_or_else(PoisonError::into_inner);
+        if limit.maxmemory == 0 || self.used_memory()? + incoming <= limit.maxmemory {
+            return Ok(());
+        }
+        evict(&mut self.write_all(), limit, incoming, protected)
+    }
+
+    /// Delete the expired keys of a database, returning the keys removed
+    ///
+    /// Used by the active expiration sweeper. The database is only write
+    /// locked when it actually holds expired keys.
+    pub fn remove_expired_in_db(&self, db_index: usize) -> Result<Vec<String>> {
+        let has_expired = match self.read_db(db_index)? {
+            Some(db) => db.iter().any(|(_, v)| v.is_expired()),
+            None => false,
+        };
+        if !has_expired {
+            return Ok(Vec::new());
+        }
+        match self.write_db(db_index)? {
+            Some(mut db) => Ok(db.remove_expired()),
+            None => Ok(Vec::new()),
         }
-        Ok(())
     }
 
     // ========================================================================
@@ -405,35 +1160,107 @@ impl StorageAdapter {
     /// }
     /// ```
     pub fn get_value(&self, db_index: usize, key: &str) -> Result<Option<StoredValue>> {
-        let databases = self
-            .databases
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(db) = databases.get(db_index) {
+        if let Some(db) = self.read_db(db_index)? {
             if let Some(stored) = db.get(key) {
                 if stored.is_expired() {
                     return Ok(None);
                 }
+                stored.touch();
                 return Ok(Some(stored.clone()));
             }
         }
         Ok(None)
     }
 
-    /// Set a value for a key in a specific database.
+    /// Get a stored value without counting it as an access, so that
+    /// introspection (OBJECT, DEBUG OBJECT) doesn't skew LRU or LFU data
+    pub fn peek_value(&self, db_index: usize, key: &str) -> Result<Option<StoredValue>> {
+        if let Some(db) = self.read_db(db_index)? {
+            if let Some(stored) = db.get(key) {
+                if !stored.is_expired() {
+                    return Ok(Some(stored.clone()));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Run a read-only closure against a stored value without cloning it.
     ///
-    /// This method supports all data types (String, List, Hash, Set, ZSet) with
-    /// direct in-memory storage (no serialization needed). The value's expiration
-    /// metadata is preserved.
+    /// The closure runs while the database read lock is held, so it should only
+    /// extract what the caller needs (e.g. a slice of a list) and return quickly.
+    /// Use `get_value()` instead when ownership of the whole value is required.
     ///
     /// # Arguments
     /// * `db_index` - The database index (0-15 by default)
-    /// * `key` - The key to set
-    /// * `value` - The StoredValue to store (can be any supported data type)
+    /// * `key` - The key to read
+    /// * `f` - A closure that inspects the StoredValue
     ///
     /// # Returns
-    /// * `Ok(())` - If the value was successfully stored
+    /// * `Ok(Some(R))` - The closure result if the key exists and is not expired
+    /// * `Ok(None)` - If the key doesn't exist or has expired
+    /// * `Err(AikvError)` - If lock acquisition fails or the closure returns an error
+    ///
+    /// # Example
+    /// ```ignore
+    /// let len = storage.with_value(0, "mylist", |v| Ok(v.as_list()?.len()))?;
+    /// ```
+    pub fn with_value<F, R>(&self, db_index: usize, key: &str, f: F) -> Result<Option<R>>
+    where
+        F: FnOnce(&StoredValue) -> Result<R>,
+    {
+        if let Some(db) = self.read_db(db_index)? {
+            if let Some(stored) = db.get(key) {
+                if stored.is_expired() {
+                    return Ok(None);
+                }
+                stored.touch();
+                return f(stored).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Get the stored values of several keys under a single read lock.
+    ///
+    /// # Arguments
+    /// * `db_index` - The database index (0-15 by default)
+    /// * `keys` - The keys to read
+    ///
+    /// # Returns
+    /// * `Ok(values)` - One entry per key, in order: `None` if the key doesn't
+    ///   exist or has expired
+    /// * `Err(AikvError)` - If lock acquisition fails
+    pub fn read_batch(&self, db_index: usize, keys: &[String]) -> Result<Vec<Option<StoredValue>>> {
+        let Some(db) = self.read_db(db_index)? else {
+            return Ok(vec![None; keys.len()]);
+        };
+        Ok(keys
+            .iter()
+            .map(|key| {
+                db.get(key)
+                    .filter(|stored| !stored.is_expired())
+                    .map(|stored| {
+                        stored.touch();
+                        stored.clone()
+                    })
+            })
+            .collect())
+    }
+
+    /// Set a value for a key in a specific database.
+    ///
+    /// This method supports all data types (String, List, Hash, Set, ZSet) with
+    /// direct in-memory storage (no serialization needed). The value's expiration
+    /// metadata is preserved.
+    ///
+    /// # Arguments
+    /// * `db_index` - The database index (0-15 by default)
+    /// * `key` - The key to set
+    /// * `value` - The StoredValue to store (can be any supported data type)
+    ///
+    /// # Returns
+    /// * `Ok(())` - If the value was successfully stored
     /// * `Err(AikvError)` - If the database index is invalid or lock acquisition fails
     ///
     /// # Example
@@ -444,12 +1271,9 @@ impl StorageAdapter {
     /// storage.set_value(0, "myhash".to_string(), value)?;
     /// ```
     pub fn set_value(&self, db_index: usize, key: String, value: StoredValue) -> Result<()> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        let _reservation = self.reserve(db_index, &key, &value)?;
 
-        if let Some(db) = databases.get_mut(db_index) {
+        if let Some(mut db) = self.write_db(db_index)? {
             db.insert(key, value);
             Ok(())
         } else {
@@ -486,12 +1310,7 @@ impl StorageAdapter {
     /// }
     /// ```
     pub fn delete_and_get(&self, db_index: usize, key: &str) -> Result<Option<StoredValue>> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(db) = databases.get_mut(db_index) {
+        if let Some(mut db) = self.write_db(db_index)? {
             if let Some(stored) = db.remove(key) {
                 if !stored.is_expired() {
                     return Ok(Some(stored));
@@ -530,22 +1349,77 @@ impl StorageAdapter {
     where
         F: FnOnce(&mut StoredValue) -> Result<()>,
     {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        self.upsert_value(db_index, key, None::<fn() -> StoredValue>, f)
+    }
 
-        if let Some(db) = databases.get_mut(db_index) {
-            if let Some(stored) = db.get_mut(key) {
-                if stored.is_expired() {
-                    db.remove(key);
-                    return Ok(false);
-                }
-                f(stored)?;
-                return Ok(true);
+    /// Atomically update a value using a closure, starting from `default()`
+    /// when the key doesn't exist or has expired.
+    ///
+    /// The key is created under the same lock `f` runs under, so concurrent
+    /// upserts of a new key can't overwrite each other.
+    pub fn update_or_insert_value<D, F>(
+        &self,
+        db_index: usize,
+        key: &str,
+        default: D,
+        f: F,
+    ) -> Result<()>
+    where
+        D: FnOnce() -> StoredValue,
+        F: FnOnce(&mut StoredValue) -> Result<()>,
+    {
+        if db_index >= self.databases.len() {
+            return Err(AikvError::Storage(format!(
+                "Invalid database index: {}",
+                db_index
+            )));
+        }
+        self.upsert_value(db_index, key, Some(default), f)
+            .map(|_| ())
+    }
+
+    /// `update_value`, creating a missing key from `default` if there is one
+    fn upsert_value<D, F>(
+        &self,
+        db_index: usize,
+        key: &str,
+        default: Option<D>,
+        f: F,
+    ) -> Result<bool>
+    where
+        D: FnOnce() -> StoredValue,
+        F: FnOnce(&mut StoredValue) -> Result<()>,
+    {
+        let limit = self.memory_limit()?;
+        // Like Redis, a write is refused while memory is over the limit and
+        // nothing can be evicted
+        if limit.maxmemory > 0 {
+            self.make_room(0, Some((db_index, key)))?;
+        }
+
+        let Some(mut db) = self.write_db(db_index)? else {
+            return Ok(false);
+        };
+        let before = db.used_memory;
+        let updated = match default {
+            Some(default) if !db.contains_live_key(key) => {
+                let mut value = default();
+                f(&mut value)?;
+                db.insert(key.to_string(), value);
+                true
             }
+            _ => db.update(key, f)?,
+        };
+        let grew = db.used_memory > before;
+        drop(db);
+
+        // How much the value grows is only known once `f` has run, so room
+        // is made after the fact. If none can be made the growth stands and
+        // the next write is refused instead
+        if limit.maxmemory > 0 && grew {
+            let _ = self.make_room(0, Some((db_index, key)));
         }
-        Ok(false)
+        Ok(updated)
     }
 
     /// Write a batch of operations atomically.
@@ -555,7 +1429,7 @@ impl StorageAdapter {
     ///
     /// # Arguments
     /// * `db_index` - The database index (0-15 by default)
-    /// * `operations` - Vector of (key, operation) pairs where operation is either Set(value) or Delete
+    /// * `operations` - Vector of (key, operation) pairs where operation is Set(value), SetValue(value) or Delete
     ///
     /// # Returns
     /// * `Ok(())` - If all operations succeeded
@@ -577,17 +1451,34 @@ impl StorageAdapter {
             return Ok(());
         }
 
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        let _reservation = if self.memory_limit()?.maxmemory > 0 {
+            let incoming: usize = match self.read_db(db_index)? {
+                Some(db) => operations
+                    .iter()
+                    .map(|(key, op)| match op {
+                        BatchOp::Set(value) => {
+                            db.growth(key, &StoredValue::new_string(value.clone()))
+                        }
+                        BatchOp::SetValue(value) => db.growth(key, value),
+                        BatchOp::Delete => 0,
+                    })
+                    .sum(),
+                None => 0,
+            };
+            Some(self.reserve_bytes(incoming, None)?)
+        } else {
+            None
+        };
 
-        if let Some(db) = databases.get_mut(db_index) {
+        if let Some(mut db) = self.write_db(db_index)? {
             for (key, op) in operations {
                 match op {
                     BatchOp::Set(value) => {
                         db.insert(key, StoredValue::new_string(value));
                     }
+                    BatchOp::SetValue(value) => {
+                        db.insert(key, value);
+                    }
                     BatchOp::Delete => {
                         db.remove(&key);
                     }
@@ -604,18 +1495,14 @@ impl StorageAdapter {
 
     /// Get a value by key from a specific database
     pub fn get_from_db(&self, db_index: usize, key: &str) -> Result<Option<Bytes>> {
-        let databases = self
-            .databases
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(db) = databases.get(db_index) {
+        if let Some(db) = self.read_db(db_index)? {
             if let Some(stored) = db.get(key) {
                 if stored.is_expired() {
                     return Ok(None);
                 }
                 // Only return value if it's a String type
                 if let ValueType::String(data) = &stored.value {
+                    stored.touch();
                     return Ok(Some(data.clone()));
                 } else {
                     return Err(AikvError::WrongType(
@@ -634,13 +1521,11 @@ impl StorageAdapter {
 
     /// Set a value for a key in a specific database
     pub fn set_in_db(&self, db_index: usize, key: String, value: Bytes) -> Result<()> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        let value = StoredValue::new_string(value);
+        let _reservation = self.reserve(db_index, &key, &value)?;
 
-        if let Some(db) = databases.get_mut(db_index) {
-            db.insert(key, StoredValue::new_string(value));
+        if let Some(mut db) = self.write_db(db_index)? {
+            db.insert(key, value);
             Ok(())
         } else {
             Err(AikvError::Storage(format!(
@@ -663,16 +1548,11 @@ impl StorageAdapter {
         value: Bytes,
         expires_at: u64,
     ) -> Result<()> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        let value = StoredValue::with_expiration(ValueType::String(value), expires_at);
+        let _reservation = self.reserve(db_index, &key, &value)?;
 
-        if let Some(db) = databases.get_mut(db_index) {
-            db.insert(
-                key,
-                StoredValue::with_expiration(ValueType::String(value), expires_at),
-            );
+        if let Some(mut db) = self.write_db(db_index)? {
+            db.insert(key, value);
             Ok(())
         } else {
             Err(AikvError::Storage(format!(
@@ -684,18 +1564,13 @@ impl StorageAdapter {
 
     /// Set expiration for a key in milliseconds
     pub fn set_expire_in_db(&self, db_index: usize, key: &str, expire_ms: u64) -> Result<bool> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(db) = databases.get_mut(db_index) {
+        if let Some(mut db) = self.write_db(db_index)? {
             if let Some(stored) = db.get_mut(key) {
                 if stored.is_expired() {
                     db.remove(key);
                     return Ok(false);
                 }
-                stored.expires_at = Some(Self::current_time_ms() + expire_ms);
+                stored.expires_at = Some(current_time_ms() + expire_ms);
                 return Ok(true);
             }
         }
@@ -709,12 +1584,7 @@ impl StorageAdapter {
         key: &str,
         timestamp_ms: u64,
     ) -> Result<bool> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(db) = databases.get_mut(db_index) {
+        if let Some(mut db) = self.write_db(db_index)? {
             if let Some(stored) = db.get_mut(key) {
                 if stored.is_expired() {
                     db.remove(key);
@@ -729,18 +1599,13 @@ impl StorageAdapter {
 
     /// Get TTL in milliseconds
     pub fn get_ttl_in_db(&self, db_index: usize, key: &str) -> Result<i64> {
-        let databases = self
-            .databases
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(db) = databases.get(db_index) {
+        if let Some(db) = self.read_db(db_index)? {
             if let Some(stored) = db.get(key) {
                 if stored.is_expired() {
                     return Ok(-2); // Key doesn't exist (expired)
                 }
                 if let Some(expires_at) = stored.expires_at {
-                    let now = Self::current_time_ms();
+                    let now = current_time_ms();
                     if expires_at > now {
                         return Ok((expires_at - now) as i64);
                     } else {
@@ -756,12 +1621,7 @@ impl StorageAdapter {
 
     /// Get expiration timestamp in milliseconds
     pub fn get_expire_time_in_db(&self, db_index: usize, key: &str) -> Result<i64> {
-        let databases = self
-            .databases
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(db) = databases.get(db_index) {
+        if let Some(db) = self.read_db(db_index)? {
             if let Some(stored) = db.get(key) {
                 if stored.is_expired() {
                     return Ok(-2); // Key doesn't exist (expired)
@@ -778,12 +1638,7 @@ impl StorageAdapter {
 
     /// Remove expiration from a key
     pub fn persist_in_db(&self, db_index: usize, key: &str) -> Result<bool> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(db) = databases.get_mut(db_index) {
+        if let Some(mut db) = self.write_db(db_index)? {
             if let Some(stored) = db.get_mut(key) {
                 if stored.is_expired() {
                     db.remove(key);
@@ -800,13 +1655,9 @@ impl StorageAdapter {
 
     /// Delete a key from a specific database
     pub fn delete_from_db(&self, db_index: usize, key: &str) -> Result<bool> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(db) = databases.get_mut(db_index) {
-            Ok(db.remove(key).is_some())
+        if let Some(mut db) = self.write_db(db_index)? {
+            // An expired key is dropped, but it didn't exist as far as DEL is concerned
+            Ok(db.remove(key).is_some_and(|stored| !stored.is_expired()))
         } else {
             Ok(false)
         }
@@ -819,12 +1670,7 @@ impl StorageAdapter {
 
     /// Check if a key exists in a specific database
     pub fn exists_in_db(&self, db_index: usize, key: &str) -> Result<bool> {
-        let databases = self
-            .databases
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(db) = databases.get(db_index) {
+        if let Some(db) = self.read_db(db_index)? {
             if let Some(stored) = db.get(key) {
                 return Ok(!stored.is_expired());
             }
@@ -839,12 +1685,7 @@ impl StorageAdapter {
 
     /// Get all keys in a database
     pub fn get_all_keys_in_db(&self, db_index: usize) -> Result<Vec<String>> {
-        let databases = self
-            .databases
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(db) = databases.get(db_index) {
+        if let Some(db) = self.read_db(db_index)? {
             let keys: Vec<String> = db
                 .iter()
                 .filter(|(_, v)| !v.is_expired())
@@ -864,15 +1705,11 @@ impl StorageAdapter {
     /// Export all databases as StoredValue format for RDB persistence
     /// This is used by RDB save functionality to persist all data types
     pub fn export_all_databases(&self) -> Result<Vec<HashMap<String, StoredValue>>> {
-        let databases = self
-            .databases
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
         let mut result = Vec::new();
-        for db in databases.iter() {
+        for db in self.databases.iter() {
+            let db = lock_for_read(db);
             let mut exported_db = HashMap::new();
-            for (key, stored_value) in db {
+            for (key, stored_value) in db.iter() {
                 if !stored_value.is_expired() {
                     exported_db.insert(key.clone(), stored_value.clone());
                 }
@@ -884,12 +1721,7 @@ impl StorageAdapter {
 
     /// Clear a specific database
     pub fn flush_db(&self, db_index: usize) -> Result<()> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(db) = databases.get_mut(db_index) {
+        if let Some(mut db) = self.write_db(db_index)? {
             db.clear();
         }
         Ok(())
@@ -897,92 +1729,82 @@ impl StorageAdapter {
 
     /// Clear all databases
     pub fn flush_all(&self) -> Result<()> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        for db in self.databases.iter() {
+            lock_for_write(db).clear();
+        }
+        Ok(())
+    }
 
-        for db in databases.iter_mut() {
-            db.clear();
+    /// Clear a database, freeing its keys in the background (FLUSHDB ASYNC)
+    pub fn flush_db_async(&self, db_index: usize) -> Result<()> {
+        if let Some(mut db) = self.write_db(db_index)? {
+            free_in_background(vec![db.take()]);
         }
         Ok(())
     }
 
-    /// Swap two databases
-    pub fn swap_db(&self, db1: usize, db2: usize) -> Result<()> {
-        let mut databases = self
+    /// Clear all databases, freeing their keys in the background (FLUSHALL ASYNC)
+    pub fn flush_all_async(&self) -> Result<()> {
+        let contents = self
             .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+            .iter()
+            .map(|db| lock_for_write(db).take())
+            .collect();
+        free_in_background(contents);
+        Ok(())
+    }
 
-        if db1 >= databases.len() || db2 >= databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {} or {}",
-                db1, db2
-            )));
+    /// Swap two databases
+    pub fn swap_db(&self, db1: usize, db2: usize) -> Result<()> {
+        if db1 == db2 {
+            if db1 >= self.databases.len() {
+                return Err(AikvError::Storage(format!(
+                    "Invalid database index: {} or {}",
+                    db1, db2
+                )));
+            }
+            return Ok(());
         }
 
-        databases.swap(db1, db2);
+        let (mut first, mut second) = self.write_pair(db1, db2)?;
+        std::mem::swap(&mut *first, &mut *second);
         Ok(())
     }
 
     /// Move a key from one database to another
     pub fn move_key(&self, src_db: usize, dst_db: usize, key: &str) -> Result<bool> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if src_db >= databases.len() || dst_db >= databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {} or {}",
-                src_db, dst_db
-            )));
+        if src_db == dst_db {
+            if src_db >= self.databases.len() {
+                return Err(AikvError::Storage(format!(
+                    "Invalid database index: {} or {}",
+                    src_db, dst_db
+                )));
+            }
+            // The key always "exists" in the destination already
+            return Ok(false);
         }
 
+        let (mut src, mut dst) = self.write_pair(src_db, dst_db)?;
+
         // Check if key exists in source and not expired
-        let value = if let Some(src) = databases.get(src_db) {
-            if let Some(stored) = src.get(key) {
-                if stored.is_expired() {
-                    return Ok(false);
-                }
-                Some(stored.clone())
-            } else {
-                None
-            }
-        } else {
-            None
+        let live = match src.get(key) {
+            Some(stored) => !stored.is_expired(),
+            None => false,
         };
+        if !live || dst.contains_key(key) {
+            return Ok(false);
+        }
 
-        if let Some(stored_value) = value {
-            // Check if key already exists in destination
-            if let Some(dst) = databases.get(dst_db) {
-                if dst.contains_key(key) {
-                    return Ok(false);
-                }
-            }
-
-            // Remove from source and add to destination
-            if let Some(src) = databases.get_mut(src_db) {
-                src.remove(key);
-            }
-            if let Some(dst) = databases.get_mut(dst_db) {
-                dst.insert(key.to_string(), stored_value);
-            }
-            Ok(true)
-        } else {
-            Ok(false)
+        // Remove from source and add to destination
+        if let Some(stored_value) = src.remove(key) {
+            dst.insert(key.to_string(), stored_value);
         }
+        Ok(true)
     }
 
     /// Rename a key
     pub fn rename_in_db(&self, db_index: usize, old_key: &str, new_key: &str) -> Result<bool> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(db) = databases.get_mut(db_index) {
+        if let Some(mut db) = self.write_db(db_index)? {
             if let Some(value) = db.remove(old_key) {
                 if value.is_expired() {
                     return Ok(false);
@@ -996,12 +1818,7 @@ impl StorageAdapter {
 
     /// Rename a key only if new key doesn't exist
     pub fn rename_nx_in_db(&self, db_index: usize, old_key: &str, new_key: &str) -> Result<bool> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(db) = databases.get_mut(db_index) {
+        if let Some(mut db) = self.write_db(db_index)? {
             if db.contains_key(new_key) {
                 return Ok(false);
             }
@@ -1025,72 +1842,64 @@ impl StorageAdapter {
         dst_key: &str,
         replace: bool,
     ) -> Result<bool> {
-        let mut databases = self
-            .databases
-            .write()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
+        let _reservation = match self.memory_limit()?.maxmemory {
+            0 => None,
+            _ => match self.get_value(src_db, src_key)? {
+                Some(value) => Some(self.reserve(dst_db, dst_key, &value)?),
+                None => None,
+            },
+        };
 
-        if src_db >= databases.len() || dst_db >= databases.len() {
-            return Err(AikvError::Storage(format!(
-                "Invalid database index: {} or {}",
-                src_db, dst_db
-            )));
+        if src_db == dst_db {
+            let mut db = match self.write_db(src_db)? {
+                Some(db) => db,
+                None => {
+                    return Err(AikvError::Storage(format!(
+                        "Invalid database index: {} or {}",
+                        src_db, dst_db
+                    )))
+                }
+            };
+            let stored_value = match db.get(src_key) {
+                Some(stored) if !stored.is_expired() => stored.to_copy(),
+                _ => return Ok(false),
+            };
+            if db.contains_live_key(dst_key) && !replace {
+                return Ok(false);
+            }
+            db.insert(dst_key.to_string(), stored_value);
+            return Ok(true);
         }
 
+        let (src, mut dst) = self.write_pair(src_db, dst_db)?;
+
         // Get value from source
-        let value = if let Some(src) = databases.get(src_db) {
-            if let Some(stored) = src.get(src_key) {
-                if stored.is_expired() {
-                    return Ok(false);
-                }
-                Some(stored.clone())
-            } else {
-                None
-            }
-        } else {
-            None
+        let stored_value = match src.get(src_key) {
+            Some(stored) if !stored.is_expired() => stored.to_copy(),
+            _ => return Ok(false),
         };
 
-        if let Some(stored_value) = value {
-            // Check if destination key exists
-            if let Some(dst) = databases.get(dst_db) {
-                if dst.contains_key(dst_key) && !replace {
-                    return Ok(false);
-                }
-            }
-
-            // Copy to destination
-            if let Some(dst) = databases.get_mut(dst_db) {
-                dst.insert(dst_key.to_string(), stored_value);
-            }
-            Ok(true)
-        } else {
-            Ok(false)
+        // Check if destination key exists (an expired key counts as absent)
+        if dst.contains_live_key(dst_key) && !replace {
+            return Ok(false);
         }
+
+        // Copy to destination
+        dst.insert(dst_key.to_string(), stored_value);
+        Ok(true)
     }
 
     /// Get multiple keys from a specific database
     /// Get a random key from a database
     pub fn random_key_in_db(&self, db_index: usize) -> Result<Option<String>> {
-        let databases = self
-            .databases
-            .read()
-            .map_err(|e| AikvError::Storage(format!("Lock error: {}", e)))?;
-
-        if let Some(db) = databases.get(db_index) {
-            let valid_keys: Vec<String> = db
+        if let Some(db) = self.read_db(db_index)? {
+            // One pass of reservoir sampling picks each live key equally often
+            Ok(db
                 .iter()
                 .filter(|(_, v)| !v.is_expired())
-                .map(|(k, _)| k.clone())
-                .collect();
-
-            if valid_keys.is_empty() {
-                return Ok(None);
-            }
-
-            // Simple random selection using current time
-            let idx = (Self::current_time_ms() as usize) % valid_keys.len();
-            Ok(Some(valid_keys[idx].clone()))
+                .map(|(k, _)| k)
+                .choose(&mut rand::thread_rng())
+                .cloned())
         } else {
             Ok(None)
         }
@@ -1174,4 +1983,478 @@ mod tests {
         assert_eq!(value2.unwrap().as_string().unwrap(), &Bytes::from("value2"));
         assert!(value3.is_none());
     }
+
+    #[test]
+    fn test_read_batch_matches_individual_gets() {
+        let storage = StorageAdapter::new();
+        storage
+            .set_value(
+                0,
+                "s".to_string(),
+                StoredValue::new_string(Bytes::from("v")),
+            )
+            .unwrap();
+        storage
+            .set_value(
+                0,
+                "l".to_string(),
+                StoredValue::new_list(VecDeque::from([Bytes::from("a")])),
+            )
+            .unwrap();
+        storage
+            .set_value(
+                0,
+                "gone".to_string(),
+                StoredValue::with_expiration(ValueType::String(Bytes::from("x")), 1),
+            )
+            .unwrap();
+
+        let keys: Vec<String> = ["s", "missing", "l", "gone", "s"]
+            .iter()
+            .map(|k| k.to_string())
+            .collect();
+        let batch = storage.read_batch(0, &keys).unwrap();
+        let individual: Vec<Option<StoredValue>> = keys
+            .iter()
+            .map(|key| storage.get_value(0, key).unwrap())
+            .collect();
+
+        assert_eq!(batch.len(), keys.len());
+        for (batched, single) in batch.iter().zip(&individual) {
+            assert_eq!(
+                batched.as_ref().map(|v| format!("{:?}", v.value())),
+                single.as_ref().map(|v| format!("{:?}", v.value()))
+            );
+        }
+        assert!(batch[0].is_some() && batch[2].is_some());
+        assert!(batch[1].is_none() && batch[3].is_none());
+
+        // An invalid database reads as all missing, like get_value
+        assert!(storage
+            .read_batch(99, &keys)
+            .unwrap()
+            .iter()
+            .all(Option::is_none));
+    }
+
+    #[test]
+    fn test_with_value() {
+        let storage = StorageAdapter::new();
+        let list: VecDeque<Bytes> = (0..100).map(|i| Bytes::from(i.to_string())).collect();
+        storage
+            .set_value(0, "list".to_string(), StoredValue::new_list(list))
+            .unwrap();
+
+        let slice = storage
+            .with_value(0, "list", |v| {
+                Ok(v.as_list()?
+                    .iter()
+                    .skip(10)
+                    .take(3)
+                    .cloned()
+                    .collect::<Vec<_>>())
+            })
+            .unwrap();
+        assert_eq!(
+            slice,
+            Some(vec![
+                Bytes::from("10"),
+                Bytes::from("11"),
+                Bytes::from("12")
+            ])
+        );
+
+        // Missing and expired keys don't invoke the closure
+        assert_eq!(storage.with_value(0, "missing", |_| Ok(())).unwrap(), None);
+        storage
+            .set_with_expiration_in_db(0, "expired".to_string(), Bytes::from("v"), 1)
+            .unwrap();
+        assert_eq!(storage.with_value(0, "expired", |_| Ok(())).unwrap(), None);
+
+        // Closure errors are propagated
+        let result = storage.with_value(0, "list", |v| v.as_hash().map(|h| h.len()));
+        assert!(matches!(result, Err(AikvError::WrongType(_))));
+    }
+
+    #[test]
+    fn test_writes_to_different_databases_do_not_block() {
+        let storage = StorageAdapter::new();
+        let _db0 = storage.write_db(0).unwrap().unwrap();
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let writer = storage.clone();
+        std::thread::spawn(move || {
+            writer
+                .set_in_db(1, "key".to_string(), Bytes::from("value"))
+                .unwrap();
+            tx.send(()).unwrap();
+        });
+
+        // db 1 must stay writable while db 0 is held exclusively
+        rx.recv_timeout(std::time::Duration::from_secs(5))
+            .expect("write to db 1 blocked on db 0 lock");
+    }
+
+    #[test]
+    fn test_swap_db_and_move_key() {
+        let storage = StorageAdapter::new();
+        storage
+            .set_in_db(0, "key0".to_string(), Bytes::from("value0"))
+            .unwrap();
+        storage
+            .set_in_db(1, "key1".to_string(), Bytes::from("value1"))
+            .unwrap();
+
+        storage.swap_db(0, 1).unwrap();
+        assert_eq!(
+            storage.get_from_db(0, "key1").unwrap(),
+            Some(Bytes::from("value1"))
+        );
+        assert_eq!(
+            storage.get_from_db(1, "key0").unwrap(),
+            Some(Bytes::from("value0"))
+        );
+
+        assert!(storage.move_key(1, 0, "key0").unwrap());
+        assert!(!storage.move_key(1, 0, "key0").unwrap());
+        assert!(!storage.move_key(0, 0, "key0").unwrap());
+        assert_eq!(storage.dbsize_in_db(0).unwrap(), 2);
+        assert_eq!(storage.dbsize_in_db(1).unwrap(), 0);
+
+        assert!(storage.swap_db(0, 0).is_ok());
+        assert!(storage.swap_db(0, 16).is_err());
+    }
+
+    #[test]
+    fn test_cross_database_operations_do_not_deadlock() {
+        let storage = StorageAdapter::new();
+        storage
+            .set_in_db(0, "key".to_string(), Bytes::from("value"))
+            .unwrap();
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let storage = storage.clone();
+                std::thread::spawn(move || {
+                    let (a, b) = if i < 2 { (0, 1) } else { (1, 0) };
+                    for _ in 0..1000 {
+                        storage.swap_db(a, b).unwrap();
+                        storage.move_key(a, b, "key").unwrap();
+                        storage.copy_in_db(b, a, "key", "key", true).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    fn limited_storage(entries: usize, policy: EvictionPolicy) -> StorageAdapter {
+        let storage = StorageAdapter::with_db_count(2);
+        let entry = entry_size(
+            "key0",
+            &StoredValue::new_string(Bytes::from(vec![b'x'; 100])),
+        );
+        storage
+            .set_memory_limit(MemoryLimit {
+                maxmemory: entry * entries,
+                policy,
+            })
+            .unwrap();
+        storage
+    }
+
+    #[test]
+    fn test_used_memory_tracking() {
+        let storage = StorageAdapter::new();
+        assert_eq!(storage.used_memory().unwrap(), 0);
+
+        storage
+            .set_in_db(0, "key1".to_string(), Bytes::from("value1"))
+            .unwrap();
+        storage
+            .set_in_db(1, "key2".to_string(), Bytes::from("value2"))
+            .unwrap();
+        let used = storage.used_memory().unwrap();
+        assert_eq!(used, 2 * (ENTRY_OVERHEAD + 4 + 6));
+
+        // Overwriting replaces the old size instead of adding to it
+        storage
+            .set_in_db(0, "key1".to_string(), Bytes::from("v"))
+            .unwrap();
+        assert_eq!(storage.used_memory().unwrap(), used - 5);
+
+        storage.delete_from_db(0, "key1").unwrap();
+        storage.flush_db(1).unwrap();
+        assert_eq!(storage.used_memory().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_panicking_update_does_not_brick_the_database() {
+        let storage = StorageAdapter::new();
+        storage
+            .set_in_db(0, "key".to_string(), Bytes::from("value"))
+            .unwrap();
+
+        // The panic unwinds while the database's write lock is held
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            storage.update_value(0, "key", |_| panic!("command bug"))
+        }));
+        assert!(result.is_err());
+        assert!(storage.databases[0].is_poisoned());
+
+        assert_eq!(
+            storage.get_from_db(0, "key").unwrap(),
+            Some(Bytes::from("value"))
+        );
+        storage
+            .set_in_db(0, "other".to_string(), Bytes::from("v"))
+            .unwrap();
+        assert_eq!(storage.dbsize_in_db(0).unwrap(), 2);
+        storage.flush_all().unwrap();
+        assert_eq!(storage.dbsize_in_db(0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_update_value_tracks_growth() {
+        let storage = StorageAdapter::new();
+        storage
+            .set_value(
+                0,
+                "list".to_string(),
+                StoredValue::new_list(VecDeque::new()),
+            )
+            .unwrap();
+        let before = storage.used_memory().unwrap();
+
+        storage
+            .update_value(0, "list", |v| {
+                v.as_list_mut()?.push_back(Bytes::from("item"));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(
+            storage.used_memory().unwrap(),
+            before + 4 + ELEMENT_OVERHEAD
+        );
+    }
+
+    #[test]
+    fn test_update_value_makes_room_for_its_growth() {
+        let storage = limited_storage(2, EvictionPolicy::AllKeysLru);
+        let value = Bytes::from(vec![b'x'; 100]);
+        let push_two = |v: &mut StoredValue| -> Result<()> {
+            let list = v.as_list_mut()?;
+            list.push_back(value.clone());
+            list.push_back(value.clone());
+            Ok(())
+        };
+
+        storage
+            .set_in_db(1, "old".to_string(), value.clone())
+            .unwrap();
+        storage
+            .set_value(
+                0,
+                "list".to_string(),
+                StoredValue::new_list(VecDeque::new()),
+            )
+            .unwrap();
+        assert!(storage.update_value(0, "list", push_two).unwrap());
+        assert!(!storage.exists_in_db(1, "old").unwrap());
+        assert!(storage.used_memory().unwrap() <= storage.memory_limit().unwrap().maxmemory);
+
+        // The key being updated is never evicted to make room for itself, so
+        // its growth stands and the next write is refused instead
+        assert!(storage.update_value(0, "list", push_two).unwrap());
+        let result = storage.update_value(0, "list", push_two);
+        assert!(matches!(result, Err(AikvError::OutOfMemory)));
+        let list = storage.get_value(0, "list").unwrap().unwrap();
+        assert_eq!(list.as_list().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_concurrent_writes_cannot_share_headroom() {
+        let storage = limited_storage(4, EvictionPolicy::NoEviction);
+        let value = Bytes::from(vec![b'x'; 100]);
+
+        std::thread::scope(|scope| {
+            for thread in 0..8 {
+                let storage = storage.clone();
+                let value = value.clone();
+                scope.spawn(move || {
+                    for i in 0..50 {
+                        let _ = storage.set_in_db(0, format!("{}{:03}", thread, i), value.clone());
+                    }
+                });
+            }
+        });
+
+        assert_eq!(storage.dbsize_in_db(0).unwrap(), 4);
+        assert!(storage.used_memory().unwrap() <= storage.memory_limit().unwrap().maxmemory);
+    }
+
+    #[test]
+    fn test_allkeys_lru_evicts_least_recently_used() {
+        let storage = limited_storage(3, EvictionPolicy::AllKeysLru);
+        let value = Bytes::from(vec![b'x'; 100]);
+
+        for key in ["key1", "key2", "key3"] {
+            storage
+                .set_in_db(0, key.to_string(), value.clone())
+                .unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        // Touch key1 so key2 becomes the least recently used
+        storage.get_from_db(0, "key1").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        storage.set_in_db(0, "key4".to_string(), value).unwrap();
+
+        assert!(storage.exists_in_db(0, "key1").unwrap());
+        assert!(!storage.exists_in_db(0, "key2").unwrap());
+        assert!(storage.exists_in_db(0, "key3").unwrap());
+        assert!(storage.exists_in_db(0, "key4").unwrap());
+    }
+
+    #[test]
+    fn test_allkeys_lfu_evicts_least_frequently_used() {
+        let storage = limited_storage(3, EvictionPolicy::AllKeysLfu);
+        let value = Bytes::from(vec![b'x'; 100]);
+
+        for key in ["key1", "key2", "key3"] {
+            storage
+                .set_in_db(0, key.to_string(), value.clone())
+                .unwrap();
+        }
+
+        // Plenty of reads push the counters of key1 and key3 past key2's
+        for _ in 0..200 {
+            storage.get_from_db(0, "key1").unwrap();
+            storage.get_from_db(0, "key3").unwrap();
+        }
+
+        storage.set_in_db(0, "key4".to_string(), value).unwrap();
+
+        assert!(storage.exists_in_db(0, "key1").unwrap());
+        assert!(!storage.exists_in_db(0, "key2").unwrap());
+        assert!(storage.exists_in_db(0, "key3").unwrap());
+        assert!(storage.exists_in_db(0, "key4").unwrap());
+    }
+
+    #[test]
+    fn test_access_frequency_grows_logarithmically() {
+        let value = StoredValue::new_string(Bytes::from("v"));
+        assert_eq!(value.lfu_frequency(), LFU_INIT_VAL as u8);
+
+        for _ in 0..1000 {
+            value.touch();
+        }
+        // A thousand accesses only buy a handful of increments
+        let frequency = value.lfu_frequency();
+        assert!(
+            frequency > LFU_INIT_VAL as u8 && frequency < 40,
+            "{}",
+            frequency
+        );
+
+        // The counter saturates instead of wrapping
+        value.set_lfu_frequency(255);
+        value.touch();
+        assert!(value.lfu_frequency() >= 254);
+    }
+
+    #[test]
+    fn test_eviction_spans_databases() {
+        let storage = limited_storage(2, EvictionPolicy::AllKeysLru);
+        let value = Bytes::from(vec![b'x'; 100]);
+
+        storage
+            .set_in_db(1, "key1".to_string(), value.clone())
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        storage
+            .set_in_db(0, "key2".to_string(), value.clone())
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        storage.set_in_db(0, "key3".to_string(), value).unwrap();
+
+        assert!(!storage.exists_in_db(1, "key1").unwrap());
+        assert!(storage.exists_in_db(0, "key2").unwrap());
+        assert!(storage.exists_in_db(0, "key3").unwrap());
+    }
+
+    #[test]
+    fn test_volatile_ttl_evicts_nearest_expiration() {
+        let storage = limited_storage(3, EvictionPolicy::VolatileTtl);
+        let value = Bytes::from(vec![b'x'; 100]);
+        let now = current_time_ms();
+
+        storage
+            .set_in_db(0, "key1".to_string(), value.clone())
+            .unwrap();
+        storage
+            .set_with_expiration_in_db(0, "key2".to_string(), value.clone(), now + 60_000)
+            .unwrap();
+        storage
+            .set_with_expiration_in_db(0, "key3".to_string(), value.clone(), now + 10_000)
+            .unwrap();
+        storage.set_in_db(0, "key4".to_string(), value).unwrap();
+
+        assert!(storage.exists_in_db(0, "key1").unwrap());
+        assert!(storage.exists_in_db(0, "key2").unwrap());
+        assert!(!storage.exists_in_db(0, "key3").unwrap());
+        assert!(storage.exists_in_db(0, "key4").unwrap());
+    }
+
+    #[test]
+    fn test_noeviction_returns_oom() {
+        let storage = limited_storage(1, EvictionPolicy::NoEviction);
+        let value = Bytes::from(vec![b'x'; 100]);
+
+        storage
+            .set_in_db(0, "key1".to_string(), value.clone())
+            .unwrap();
+        let result = storage.set_in_db(0, "key2".to_string(), value);
+        assert!(matches!(result, Err(AikvError::OutOfMemory)));
+
+        // Existing data is left untouched and deletes are still allowed
+        assert!(storage.exists_in_db(0, "key1").unwrap());
+        assert!(storage.delete_from_db(0, "key1").unwrap());
+    }
+
+    #[test]
+    fn test_volatile_lru_without_candidates_returns_oom() {
+        let storage = limited_storage(1, EvictionPolicy::VolatileLru);
+        let value = Bytes::from(vec![b'x'; 100]);
+
+        storage
+            .set_in_db(0, "key1".to_string(), value.clone())
+            .unwrap();
+        let result = storage.set_in_db(0, "key2".to_string(), value);
+        assert!(matches!(result, Err(AikvError::OutOfMemory)));
+    }
+
+    #[test]
+    fn test_eviction_policy_parse() {
+        for name in [
+            "noeviction",
+            "allkeys-lru",
+            "allkeys-random",
+            "volatile-lru",
+            "volatile-ttl",
+            "allkeys-lfu",
+            "volatile-lfu",
+        ] {
+            assert_eq!(EvictionPolicy::parse(name).unwrap().as_str(), name);
+        }
+        assert_eq!(
+            EvictionPolicy::parse("ALLKEYS-LRU"),
+            Some(EvictionPolicy::AllKeysLru)
+        );
+        assert_eq!(EvictionPolicy::parse("bogus"), None);
+    }
 }
@@ -9,7 +9,15 @@ pub use memory_adapter::StorageAdapter;
 pub use aidb_adapter::AiDbStorageAdapter;
 
 // Export the core storage types for command implementations
-pub use memory_adapter::{BatchOp, SerializableStoredValue, StoredValue, ValueType};
+pub use memory_adapter::{
+    entry_size, BatchOp, SerializableStoredValue, StoredValue, StreamId, ValueType,
+};
+
+// Export the access-tracking switch behind CLIENT NO-TOUCH
+pub use memory_adapter::without_touching;
+
+// Export memory limit types used by configuration and INFO
+pub use memory_adapter::{EvictionPolicy, MemoryLimit};
 
 use crate::error::Result;
 use bytes::Bytes;
@@ -38,6 +46,44 @@ impl StorageEngine {
         )?))
     }
 
+    // ========================================================================
+    // MEMORY LIMITS
+    // ========================================================================
+
+    /// Set the memory limit and eviction policy.
+    ///
+    /// Only the memory engine enforces a limit; AiDb keeps its data on disk.
+    pub fn set_memory_limit(&self, limit: MemoryLimit) -> Result<()> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.set_memory_limit(limit),
+            StorageEngine::AiDb(_) => Ok(()),
+        }
+    }
+
+    /// Get the memory limit and eviction policy
+    pub fn memory_limit(&self) -> Result<MemoryLimit> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.memory_limit(),
+            StorageEngine::AiDb(_) => Ok(MemoryLimit::default()),
+        }
+    }
+
+    /// Number of logical databases
+    pub fn db_count(&self) -> usize {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.db_count(),
+            StorageEngine::AiDb(adapter) => adapter.db_count(),
+        }
+    }
+
+    /// Approximate number of bytes used by the dataset held in memory
+    pub fn used_memory(&self) -> Result<usize> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.used_memory(),
+            StorageEngine::AiDb(_) => Ok(0),
+        }
+    }
+
     // ========================================================================
     // CORE STORAGE METHODS
     // ========================================================================
@@ -50,6 +96,40 @@ impl StorageEngine {
         }
     }
 
+    /// Get a stored value without counting it as an access (for OBJECT and
+    /// DEBUG OBJECT).
+    pub fn peek_value(&self, db_index: usize, key: &str) -> Result<Option<StoredValue>> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.peek_value(db_index, key),
+            StorageEngine::AiDb(adapter) => adapter.peek_value(db_index, key),
+        }
+    }
+
+    /// Run a read-only closure against a stored value without taking ownership.
+    ///
+    /// Prefer this over `get_value()` on hot read paths: the memory backend
+    /// borrows the value in place instead of deep-cloning it.
+    pub fn with_value<F, R>(&self, db_index: usize, key: &str, f: F) -> Result<Option<R>>
+    where
+        F: FnOnce(&StoredValue) -> Result<R>,
+    {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.with_value(db_index, key, f),
+            StorageEngine::AiDb(adapter) => adapter.with_value(db_index, key, f),
+        }
+    }
+
+    /// Get the stored values of several keys, in key order, `None` for
+    /// missing or expired keys.
+    ///
+    /// The companion of `write_batch()` for multi-key reads such as MGET.
+    pub fn read_batch(&self, db_index: usize, keys: &[String]) -> Result<Vec<Option<StoredValue>>> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.read_batch(db_index, keys),
+            StorageEngine::AiDb(adapter) => adapter.read_batch(db_index, keys),
+        }
+    }
+
     /// Set a value for a key in a specific database.
     pub fn set_value(&self, db_index: usize, key: String, value: StoredValue) -> Result<()> {
         match self {
@@ -77,6 +157,29 @@ impl StorageEngine {
         }
     }
 
+    /// Atomically update a value using a closure, starting from `default()`
+    /// when the key doesn't exist.
+    pub fn update_or_insert_value<D, F>(
+        &self,
+        db_index: usize,
+        key: &str,
+        default: D,
+        f: F,
+    ) -> Result<()>
+    where
+        D: FnOnce() -> StoredValue,
+        F: FnOnce(&mut StoredValue) -> Result<()>,
+    {
+        match self {
+            StorageEngine::Memory(adapter) => {
+                adapter.update_or_insert_value(db_index, key, default, f)
+            }
+            StorageEngine::AiDb(adapter) => {
+                adapter.update_or_insert_value(db_index, key, default, f)
+            }
+        }
+    }
+
     /// Write a batch of operations atomically.
     pub fn write_batch(&self, db_index: usize, operations: Vec<(String, BatchOp)>) -> Result<()> {
         match self {
@@ -236,6 +339,22 @@ impl StorageEngine {
         }
     }
 
+    /// Get database size with a full scan, never using a maintained count
+    pub fn exact_dbsize_in_db(&self, db_index: usize) -> Result<usize> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.dbsize_in_db(db_index),
+            StorageEngine::AiDb(adapter) => adapter.exact_dbsize_in_db(db_index),
+        }
+    }
+
+    /// Delete the expired keys of a database, returning the keys removed
+    pub fn remove_expired_in_db(&self, db_index: usize) -> Result<Vec<String>> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.remove_expired_in_db(db_index),
+            StorageEngine::AiDb(adapter) => adapter.remove_expired_in_db(db_index),
+        }
+    }
+
     /// Clear a specific database
     pub fn flush_db(&self, db_index: usize) -> Result<()> {
         match self {
@@ -252,6 +371,36 @@ impl StorageEngine {
         }
     }
 
+    /// Clear a database, freeing its keys in the background where the
+    /// engine can; the database is empty either way once this returns
+    ///
+    /// AiDb deletes every key before returning, as `flush_db` does.
+    pub fn flush_db_async(&self, db_index: usize) -> Result<()> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.flush_db_async(db_index),
+            StorageEngine::AiDb(adapter) => adapter.flush_db(db_index),
+        }
+    }
+
+    /// Clear all databases, freeing their keys in the background where the
+    /// engine can; see [`StorageEngine::flush_db_async`]
+    pub fn flush_all_async(&self) -> Result<()> {
+        match self {
+            StorageEngine::Memory(adapter) => adapter.flush_all_async(),
+            StorageEngine::AiDb(adapter) => adapter.flush_all(),
+        }
+    }
+
+    /// Make everything written so far durable (FSYNC)
+    ///
+    /// The memory engine keeps nothing on disk, so there is nothing to do.
+    pub fn sync(&self) -> Result<()> {
+        match self {
+            StorageEngine::Memory(_) => Ok(()),
+            StorageEngine::AiDb(adapter) => adapter.sync(),
+        }
+    }
+
     /// Swap two databases
     pub fn swap_db(&self, db1: usize, db2: usize) -> Result<()> {
         match self {
@@ -0,0 +1,221 @@
+//! Tests for ACL users and the permissions they're checked against
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+
+fn is_error(reply: &RespValue, prefix: &str) -> bool {
+    matches!(reply, RespValue::Error(e) if e.starts_with(prefix))
+}
+
+#[tokio::test]
+async fn test_restricted_user_is_limited_to_its_commands_and_keys() {
+    let addr = start_server(|_| {}).await;
+    let mut admin = TestClient::connect(&addr).await;
+    assert_eq!(
+        admin
+            .command(&["ACL", "SETUSER", "reader", "on", ">pw", "+get", "~cached:*"])
+            .await,
+        RespValue::ok()
+    );
+    admin.command(&["SET", "cached:1", "hit"]).await;
+    admin.command(&["SET", "other", "miss"]).await;
+
+    let mut reader = TestClient::connect(&addr).await;
+    let reply = reader.command(&["AUTH", "reader", "wrong"]).await;
+    assert!(is_error(&reply, "WRONGPASS"), "got {:?}", reply);
+    assert_eq!(
+        reader.command(&["AUTH", "reader", "pw"]).await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        reader.command(&["ACL", "WHOAMI"]).await,
+        RespValue::error("NOPERM User reader has no permissions to run the 'acl' command")
+    );
+
+    assert_eq!(
+        reader.command(&["GET", "cached:1"]).await,
+        RespValue::bulk_string("hit")
+    );
+    let reply = reader.command(&["GET", "other"]).await;
+    assert!(is_error(&reply, "NOPERM"), "got {:?}", reply);
+    let reply = reader.command(&["SET", "cached:1", "x"]).await;
+    assert!(is_error(&reply, "NOPERM"), "got {:?}", reply);
+    assert_eq!(
+        admin.command(&["GET", "cached:1"]).await,
+        RespValue::bulk_string("hit")
+    );
+
+    // RESET goes back to the default user
+    reader.command(&["RESET"]).await;
+    assert_eq!(
+        reader.command(&["ACL", "WHOAMI"]).await,
+        RespValue::bulk_string("default")
+    );
+
+    // A disabled user can't authenticate
+    admin.command(&["ACL", "SETUSER", "reader", "off"]).await;
+    let reply = reader.command(&["AUTH", "reader", "pw"]).await;
+    assert!(is_error(&reply, "WRONGPASS"), "got {:?}", reply);
+}
+
+#[tokio::test]
+async fn test_acl_getuser_list_and_deluser() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    assert_eq!(
+        client.command(&["ACL", "WHOAMI"]).await,
+        RespValue::bulk_string("default")
+    );
+    client
+        .command(&[
+            "ACL",
+            "SETUSER",
+            "writer",
+            "on",
+            "nopass",
+            "+@all",
+            "-flushall",
+            "~app:*",
+        ])
+        .await;
+    assert_eq!(
+        client.command(&["ACL", "LIST"]).await,
+        RespValue::array(vec![
+            RespValue::bulk_string("user default on nopass ~* +@all"),
+            RespValue::bulk_string("user writer on nopass ~app:* +@all -flushall"),
+        ])
+    );
+    assert_eq!(
+        client.command(&["ACL", "GETUSER", "writer"]).await,
+        RespValue::array(vec![
+            RespValue::bulk_string("flags"),
+            RespValue::array(vec![
+                RespValue::bulk_string("on"),
+                RespValue::bulk_string("nopass"),
+            ]),
+            RespValue::bulk_string("passwords"),
+            RespValue::array(Vec::new()),
+            RespValue::bulk_string("commands"),
+            RespValue::bulk_string("+@all -flushall"),
+            RespValue::bulk_string("keys"),
+            RespValue::bulk_string("~app:*"),
+            RespValue::bulk_string("channels"),
+            RespValue::bulk_string("&*"),
+        ])
+    );
+    assert_eq!(
+        client.command(&["ACL", "GETUSER", "nobody"]).await,
+        RespValue::null_array()
+    );
+
+    let reply = client
+        .command(&["ACL", "SETUSER", "writer", "+nosuchcommand"])
+        .await;
+    assert!(
+        matches!(&reply, RespValue::Error(e) if e.contains("Error in ACL SETUSER modifier")),
+        "got {:?}",
+        reply
+    );
+    let reply = client.command(&["ACL", "SETUSER", "writer", "bogus"]).await;
+    assert!(
+        matches!(&reply, RespValue::Error(e) if e.contains("Error in ACL SETUSER modifier")),
+        "got {:?}",
+        reply
+    );
+
+    let reply = client.command(&["ACL", "DELUSER", "default"]).await;
+    assert!(
+        matches!(&reply, RespValue::Error(e) if e.contains("cannot be removed")),
+        "got {:?}",
+        reply
+    );
+    assert_eq!(
+        client
+            .command(&["ACL", "DELUSER", "writer", "nobody"])
+            .await,
+        RespValue::integer(1)
+    );
+    let reply = client.command(&["AUTH", "writer", "anything"]).await;
+    assert!(is_error(&reply, "WRONGPASS"), "got {:?}", reply);
+}
+
+#[tokio::test]
+async fn test_restricted_user_is_checked_on_scripts_cluster_and_subscriptions() {
+    let addr = start_server(|_| {}).await;
+    let mut admin = TestClient::connect(&addr).await;
+    admin
+        .command(&[
+            "ACL",
+            "SETUSER",
+            "app",
+            "on",
+            ">pw",
+            "~app:*",
+            "resetchannels",
+            "&news.*",
+            "+get",
+            "+set",
+            "+eval",
+            "+subscribe",
+            "+psubscribe",
+        ])
+        .await;
+    admin.command(&["SET", "secret", "s3cr3t"]).await;
+
+    let mut client = TestClient::connect(&addr).await;
+    assert_eq!(
+        client.command(&["AUTH", "app", "pw"]).await,
+        RespValue::ok()
+    );
+
+    // redis.call runs as the caller, not as the default user
+    assert_eq!(
+        client
+            .command(&[
+                "EVAL",
+                "return redis.call('SET', KEYS[1], 'v')",
+                "1",
+                "app:1"
+            ])
+            .await,
+        RespValue::ok()
+    );
+    for script in [
+        "return redis.call('GET', 'secret')",
+        "return redis.call('DEL', 'app:1')",
+    ] {
+        let reply = client.command(&["EVAL", script, "0"]).await;
+        assert!(
+            matches!(&reply, RespValue::Error(e) if e.contains("permissions")),
+            "got {:?}",
+            reply
+        );
+    }
+    assert_eq!(
+        admin.command(&["GET", "app:1"]).await,
+        RespValue::bulk_string("v")
+    );
+
+    let reply = client
+        .command(&["CLUSTER", "MEET", "127.0.0.1", "7000"])
+        .await;
+    assert!(is_error(&reply, "NOPERM"), "got {:?}", reply);
+    let reply = client.command(&["MONITOR"]).await;
+    assert!(is_error(&reply, "NOPERM"), "got {:?}", reply);
+
+    let reply = client.command(&["SUBSCRIBE", "news.1", "sports"]).await;
+    assert!(is_error(&reply, "NOPERM"), "got {:?}", reply);
+    // A pattern must be one of the user's own, not just match one
+    let reply = client.command(&["PSUBSCRIBE", "news.1*"]).await;
+    assert!(is_error(&reply, "NOPERM"), "got {:?}", reply);
+    let reply = client.command(&["PSUBSCRIBE", "news.*"]).await;
+    assert!(
+        matches!(&reply, RespValue::Array(Some(items)) | RespValue::Push(items)
+            if items.first() == Some(&RespValue::bulk_string("psubscribe"))),
+        "got {:?}",
+        reply
+    );
+}
@@ -4,7 +4,7 @@
 /// with WAL durability guarantees.
 #[cfg(test)]
 mod aidb_writebatch_tests {
-    use aikv::storage::{AiDbStorageAdapter, BatchOp};
+    use aikv::storage::{AiDbStorageAdapter, BatchOp, StoredValue};
     use bytes::Bytes;
     use tempfile::TempDir;
 
@@ -128,4 +128,49 @@ mod aidb_writebatch_tests {
             Some(Bytes::from("new_value"))
         );
     }
+
+    #[test]
+    fn test_aidb_write_batch_applies_every_type_and_ttl_together() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = AiDbStorageAdapter::new(temp_dir.path(), 1).unwrap();
+        storage
+            .set_in_db(0, "gone".to_string(), Bytes::from("old"))
+            .unwrap();
+
+        let expires_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64
+            + 60_000;
+        let mut list = StoredValue::new_list(vec![Bytes::from("a"), Bytes::from("b")].into());
+        list.set_expiration(Some(expires_at));
+        let operations = vec![
+            ("list".to_string(), BatchOp::SetValue(list)),
+            ("plain".to_string(), BatchOp::Set(Bytes::from("v"))),
+            ("gone".to_string(), BatchOp::Delete),
+        ];
+        storage.write_batch(0, operations).unwrap();
+
+        // Reopening replays the WAL: the batch is there in full, TTL included
+        drop(storage);
+        let storage = AiDbStorageAdapter::new(temp_dir.path(), 1).unwrap();
+        let list = storage.get_value(0, "list").unwrap().unwrap();
+        assert_eq!(list.as_list().unwrap().len(), 2);
+        assert_eq!(list.expires_at(), Some(expires_at));
+        assert_eq!(
+            storage.get_from_db(0, "plain").unwrap(),
+            Some(Bytes::from("v"))
+        );
+        assert_eq!(storage.get_from_db(0, "gone").unwrap(), None);
+    }
+
+    #[test]
+    fn test_aidb_write_batch_to_invalid_database_applies_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = AiDbStorageAdapter::new(temp_dir.path(), 1).unwrap();
+
+        let operations = vec![("key".to_string(), BatchOp::Set(Bytes::from("v")))];
+        assert!(storage.write_batch(1, operations).is_err());
+        assert_eq!(storage.get_from_db(0, "key").unwrap(), None);
+    }
 }
@@ -0,0 +1,104 @@
+//! Tests for password authentication (AUTH and requirepass)
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+
+fn is_error(reply: &RespValue, prefix: &str) -> bool {
+    matches!(reply, RespValue::Error(e) if e.starts_with(prefix))
+}
+
+#[tokio::test]
+async fn test_unauthenticated_commands_are_rejected() {
+    let addr = start_server(|server| server.set_requirepass(Some("secret".to_string()))).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    let reply = client.command(&["SET", "key", "value"]).await;
+    assert!(is_error(&reply, "NOAUTH"), "got {:?}", reply);
+    let reply = client.command(&["GET", "key"]).await;
+    assert!(is_error(&reply, "NOAUTH"), "got {:?}", reply);
+
+    // PING is allowed before authenticating
+    assert_eq!(
+        client.command(&["PING"]).await,
+        RespValue::simple_string("PONG")
+    );
+
+    // HELLO without AUTH is rejected, wrong passwords too
+    let reply = client.command(&["HELLO", "3"]).await;
+    assert!(is_error(&reply, "NOAUTH"), "got {:?}", reply);
+    let reply = client.command(&["AUTH", "wrong"]).await;
+    assert!(is_error(&reply, "WRONGPASS"), "got {:?}", reply);
+    let reply = client.command(&["AUTH", "alice", "secret"]).await;
+    assert!(is_error(&reply, "WRONGPASS"), "got {:?}", reply);
+
+    let reply = client.command(&["GET", "key"]).await;
+    assert!(is_error(&reply, "NOAUTH"), "got {:?}", reply);
+}
+
+#[tokio::test]
+async fn test_unauthenticated_monitor_is_rejected() {
+    let addr = start_server(|server| server.set_requirepass(Some("secret".to_string()))).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    let reply = client.command(&["MONITOR"]).await;
+    assert!(is_error(&reply, "NOAUTH"), "got {:?}", reply);
+
+    assert_eq!(client.command(&["AUTH", "secret"]).await, RespValue::ok());
+    assert_eq!(client.command(&["MONITOR"]).await, RespValue::ok());
+}
+
+#[tokio::test]
+async fn test_auth_grants_access() {
+    let addr = start_server(|server| server.set_requirepass(Some("secret".to_string()))).await;
+
+    let mut client = TestClient::connect(&addr).await;
+    assert_eq!(client.command(&["AUTH", "secret"]).await, RespValue::ok());
+    assert_eq!(
+        client.command(&["SET", "key", "value"]).await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        client.command(&["GET", "key"]).await,
+        RespValue::bulk_string("value")
+    );
+
+    // Username form with the default user
+    let mut client = TestClient::connect(&addr).await;
+    assert_eq!(
+        client.command(&["AUTH", "default", "secret"]).await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        client.command(&["GET", "key"]).await,
+        RespValue::bulk_string("value")
+    );
+
+    // HELLO can authenticate and negotiate in one step
+    let mut client = TestClient::connect(&addr).await;
+    let reply = client
+        .command(&["HELLO", "3", "AUTH", "default", "secret"])
+        .await;
+    assert!(matches!(reply, RespValue::Map(_)), "got {:?}", reply);
+    assert_eq!(
+        client.command(&["GET", "key"]).await,
+        RespValue::bulk_string("value")
+    );
+}
+
+#[tokio::test]
+async fn test_no_requirepass() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    // Commands work without AUTH
+    assert_eq!(
+        client.command(&["SET", "key", "value"]).await,
+        RespValue::ok()
+    );
+
+    // AUTH <password> is an error when no password is configured
+    let reply = client.command(&["AUTH", "anything"]).await;
+    assert!(is_error(&reply, "ERR"), "got {:?}", reply);
+}
@@ -1,4 +1,5 @@
-use aikv::command::CommandExecutor;
+use aikv::command::{ClientContext, CommandExecutor};
+use aikv::error::AikvError;
 use aikv::protocol::RespValue;
 use aikv::StorageEngine;
 use bytes::Bytes;
@@ -7,90 +8,191 @@ use bytes::Bytes;
 fn test_database_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Test SELECT
     let result = executor
-        .execute("SELECT", &[Bytes::from("1")], &mut current_db, client_id)
+        .execute("SELECT", &[Bytes::from("1")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::ok());
-    assert_eq!(current_db, 1);
+    assert_eq!(client.db, 1);
 
     // Test SET in database 1
     executor
         .execute(
             "SET",
             &[Bytes::from("key1"), Bytes::from("value1")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
     // Test DBSIZE
-    let result = executor
-        .execute("DBSIZE", &[], &mut current_db, client_id)
-        .unwrap();
+    let result = executor.execute("DBSIZE", &[], &mut client).unwrap();
     assert_eq!(result, RespValue::integer(1));
 
     // Test SELECT back to database 0
     executor
-        .execute("SELECT", &[Bytes::from("0")], &mut current_db, client_id)
+        .execute("SELECT", &[Bytes::from("0")], &mut client)
         .unwrap();
-    assert_eq!(current_db, 0);
+    assert_eq!(client.db, 0);
 
     // Database 0 should be empty
-    let result = executor
-        .execute("DBSIZE", &[], &mut current_db, client_id)
-        .unwrap();
+    let result = executor.execute("DBSIZE", &[], &mut client).unwrap();
     assert_eq!(result, RespValue::integer(0));
 
     // Test MOVE command
     executor
-        .execute("SELECT", &[Bytes::from("1")], &mut current_db, client_id)
+        .execute("SELECT", &[Bytes::from("1")], &mut client)
         .unwrap();
     let result = executor
         .execute(
             "MOVE",
             &[Bytes::from("key1"), Bytes::from("0")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     assert_eq!(result, RespValue::integer(1));
 
     // Now database 1 should be empty
-    let result = executor
-        .execute("DBSIZE", &[], &mut current_db, client_id)
-        .unwrap();
+    let result = executor.execute("DBSIZE", &[], &mut client).unwrap();
     assert_eq!(result, RespValue::integer(0));
 
     // And database 0 should have one key
     executor
-        .execute("SELECT", &[Bytes::from("0")], &mut current_db, client_id)
-        .unwrap();
-    let result = executor
-        .execute("DBSIZE", &[], &mut current_db, client_id)
+        .execute("SELECT", &[Bytes::from("0")], &mut client)
         .unwrap();
+    let result = executor.execute("DBSIZE", &[], &mut client).unwrap();
     assert_eq!(result, RespValue::integer(1));
 
     // Test FLUSHDB
-    let result = executor
-        .execute("FLUSHDB", &[], &mut current_db, client_id)
-        .unwrap();
+    let result = executor.execute("FLUSHDB", &[], &mut client).unwrap();
     assert_eq!(result, RespValue::ok());
-    let result = executor
-        .execute("DBSIZE", &[], &mut current_db, client_id)
-        .unwrap();
+    let result = executor.execute("DBSIZE", &[], &mut client).unwrap();
     assert_eq!(result, RespValue::integer(0));
 }
 
+#[test]
+fn test_flush_sync_and_async() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage.clone());
+    let mut client = ClientContext::new(1);
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    run("SET", &["a", "1"]).unwrap();
+    run("SET", &["b", "2"]).unwrap();
+    assert_eq!(run("FLUSHDB", &["async"]).unwrap(), RespValue::ok());
+    // The keys are gone as soon as the reply is, even if freed later
+    assert_eq!(run("DBSIZE", &[]).unwrap(), RespValue::integer(0));
+    assert_eq!(run("GET", &["a"]).unwrap(), RespValue::null_bulk_string());
+
+    for db in ["0", "5", "15"] {
+        run("SELECT", &[db]).unwrap();
+        run("SET", &["k", db]).unwrap();
+    }
+    assert_eq!(run("FLUSHALL", &["SYNC"]).unwrap(), RespValue::ok());
+    for db in 0..16 {
+        assert_eq!(storage.dbsize_in_db(db).unwrap(), 0, "db{}", db);
+    }
+
+    run("SET", &["k", "v"]).unwrap();
+    assert_eq!(run("FLUSHALL", &["ASYNC"]).unwrap(), RespValue::ok());
+    assert_eq!(run("DBSIZE", &[]).unwrap(), RespValue::integer(0));
+
+    for args in [vec!["LAZY"], vec!["SYNC", "ASYNC"]] {
+        run("SET", &["k", "v"]).unwrap();
+        for cmd in ["FLUSHDB", "FLUSHALL"] {
+            let err = run(cmd, &args).unwrap_err();
+            assert!(err.to_string().contains("syntax error"), "{}", err);
+        }
+        assert_eq!(run("DBSIZE", &[]).unwrap(), RespValue::integer(1));
+    }
+}
+
+#[test]
+fn test_select_validates_index() {
+    let storage = StorageEngine::new_memory(4);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut select = |index: &str| {
+        let result = executor.execute("SELECT", &[Bytes::from(index.to_string())], &mut client);
+        (result, client.db)
+    };
+
+    let (result, db) = select("3");
+    assert_eq!(result.unwrap(), RespValue::ok());
+    assert_eq!(db, 3);
+
+    // The bound is the engine's database count, and a failed SELECT stays put
+    for index in ["4", "16", "-1"] {
+        let (result, db) = select(index);
+        let err = result.unwrap_err();
+        assert!(
+            err.to_string().contains("DB index is out of range"),
+            "{}: {}",
+            index,
+            err
+        );
+        assert_eq!(db, 3);
+    }
+
+    let (result, db) = select("one");
+    let err = result.unwrap_err();
+    assert!(
+        err.to_string().contains("value is not an integer"),
+        "{}",
+        err
+    );
+    assert_eq!(db, 3);
+}
+
+#[test]
+fn test_select_updates_the_client_context() {
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let mut client = ClientContext::new(7);
+    let args = |items: &[&str]| -> Vec<Bytes> {
+        items.iter().map(|i| Bytes::from(i.to_string())).collect()
+    };
+
+    assert_eq!(
+        executor
+            .execute("SELECT", &args(&["5"]), &mut client)
+            .unwrap(),
+        RespValue::ok()
+    );
+    assert_eq!(client.db, 5);
+    assert_eq!(client.client_id, 7);
+
+    // Later commands run against the selected database
+    executor
+        .execute("SET", &args(&["k", "v"]), &mut client)
+        .unwrap();
+    assert_eq!(
+        executor
+            .execute("DBSIZE", &[], &mut ClientContext::new(8))
+            .unwrap(),
+        RespValue::integer(0)
+    );
+
+    // A script's SELECT doesn't leak into the caller's context
+    executor
+        .execute(
+            "EVAL",
+            &args(&["redis.call('SELECT', '2') return 1", "0"]),
+            &mut client,
+        )
+        .unwrap();
+    assert_eq!(client.db, 5);
+}
+
 #[test]
 fn test_key_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Set up test data
     executor
@@ -104,14 +206,13 @@ fn test_key_commands() {
                 Bytes::from("product:1"),
                 Bytes::from("Widget"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
     // Test KEYS
     let result = executor
-        .execute("KEYS", &[Bytes::from("*")], &mut current_db, client_id)
+        .execute("KEYS", &[Bytes::from("*")], &mut client)
         .unwrap();
     if let RespValue::Array(Some(keys)) = result {
         assert_eq!(keys.len(), 3);
@@ -121,7 +222,7 @@ fn test_key_commands() {
 
     // Test KEYS with pattern
     let result = executor
-        .execute("KEYS", &[Bytes::from("user:*")], &mut current_db, client_id)
+        .execute("KEYS", &[Bytes::from("user:*")], &mut client)
         .unwrap();
     if let RespValue::Array(Some(keys)) = result {
         assert_eq!(keys.len(), 2);
@@ -131,7 +232,7 @@ fn test_key_commands() {
 
     // Test SCAN - basic iteration
     let result = executor
-        .execute("SCAN", &[Bytes::from("0")], &mut current_db, client_id)
+        .execute("SCAN", &[Bytes::from("0")], &mut client)
         .unwrap();
     if let RespValue::Array(Some(scan_result)) = result {
         assert_eq!(scan_result.len(), 2); // [cursor, keys]
@@ -152,8 +253,7 @@ fn test_key_commands() {
                 Bytes::from("MATCH"),
                 Bytes::from("user:*"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     if let RespValue::Array(Some(scan_result)) = result {
@@ -172,8 +272,7 @@ fn test_key_commands() {
         .execute(
             "SCAN",
             &[Bytes::from("0"), Bytes::from("COUNT"), Bytes::from("1")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     if let RespValue::Array(Some(scan_result)) = result {
@@ -188,9 +287,7 @@ fn test_key_commands() {
     }
 
     // Test RANDOMKEY
-    let result = executor
-        .execute("RANDOMKEY", &[], &mut current_db, client_id)
-        .unwrap();
+    let result = executor.execute("RANDOMKEY", &[], &mut client).unwrap();
     assert!(matches!(result, RespValue::BulkString(Some(_))));
 
     // Test RENAME
@@ -198,31 +295,20 @@ fn test_key_commands() {
         .execute(
             "RENAME",
             &[Bytes::from("user:1"), Bytes::from("user:100")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     assert_eq!(result, RespValue::ok());
 
     // Verify old key doesn't exist
     let result = executor
-        .execute(
-            "EXISTS",
-            &[Bytes::from("user:1")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("EXISTS", &[Bytes::from("user:1")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::integer(0));
 
     // Verify new key exists
     let result = executor
-        .execute(
-            "EXISTS",
-            &[Bytes::from("user:100")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("EXISTS", &[Bytes::from("user:100")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::integer(1));
 
@@ -231,15 +317,14 @@ fn test_key_commands() {
         .execute(
             "RENAMENX",
             &[Bytes::from("user:100"), Bytes::from("user:2")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     assert_eq!(result, RespValue::integer(0)); // user:2 already exists
 
     // Test TYPE
     let result = executor
-        .execute("TYPE", &[Bytes::from("user:2")], &mut current_db, client_id)
+        .execute("TYPE", &[Bytes::from("user:2")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::simple_string("string"));
 
@@ -248,38 +333,402 @@ fn test_key_commands() {
         .execute(
             "COPY",
             &[Bytes::from("user:2"), Bytes::from("user:2:backup")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     assert_eq!(result, RespValue::integer(1));
 
     // Verify copy exists
     let result = executor
-        .execute(
-            "GET",
-            &[Bytes::from("user:2:backup")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("GET", &[Bytes::from("user:2:backup")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::bulk_string("Bob"));
 }
 
+#[test]
+fn test_type_reports_every_value_type() {
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let mut client = ClientContext::new(1);
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    run("SET", &["string", "v"]).unwrap();
+    run("RPUSH", &["list", "a"]).unwrap();
+    run("HSET", &["hash", "f", "v"]).unwrap();
+    run("SADD", &["set", "a"]).unwrap();
+    run("ZADD", &["zset", "1", "a"]).unwrap();
+    // HyperLogLogs are strings in Redis too; JSON documents are stored as
+    // strings rather than a type of their own
+    run("JSON.SET", &["json", "$", "{\"a\":1}"]).unwrap();
+    run("PFADD", &["hll", "a"]).unwrap();
+    run("SET", &["expired", "v", "PX", "1"]).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(10));
+
+    for (key, type_name) in [
+        ("string", "string"),
+        ("list", "list"),
+        ("hash", "hash"),
+        ("set", "set"),
+        ("zset", "zset"),
+        ("json", "string"),
+        ("hll", "string"),
+        ("expired", "none"),
+        ("missing", "none"),
+    ] {
+        assert_eq!(
+            run("TYPE", &[key]).unwrap(),
+            RespValue::simple_string(type_name),
+            "TYPE {}",
+            key
+        );
+    }
+    assert!(run("TYPE", &[]).is_err());
+}
+
+#[test]
+fn test_randomkey_is_uniform() {
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let mut client = ClientContext::new(1);
+
+    const KEYS: usize = 10;
+    const DRAWS: usize = 10_000;
+    for i in 0..KEYS {
+        executor
+            .execute(
+                "SET",
+                &[Bytes::from(format!("key:{}", i)), Bytes::from("v")],
+                &mut client,
+            )
+            .unwrap();
+    }
+
+    let mut hits = std::collections::HashMap::new();
+    for _ in 0..DRAWS {
+        match executor.execute("RANDOMKEY", &[], &mut client).unwrap() {
+            RespValue::BulkString(Some(key)) => *hits.entry(key).or_insert(0usize) += 1,
+            reply => panic!("expected a key, got {:?}", reply),
+        }
+    }
+
+    // Each key expects 1000 hits with a standard deviation of 30; the bounds
+    // are ten deviations wide so the test doesn't flake
+    assert_eq!(hits.len(), KEYS);
+    for (key, count) in hits {
+        assert!(
+            (700..=1300).contains(&count),
+            "{:?} drawn {} times out of {}",
+            key,
+            count,
+            DRAWS
+        );
+    }
+}
+
+#[test]
+fn test_exists_and_del_count_every_key() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    run("SET", &["a", "1"]).unwrap();
+    run("RPUSH", &["b", "x"]).unwrap();
+    run("SET", &["gone", "1", "PX", "1"]).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(10));
+
+    // EXISTS counts a key once per occurrence; expired keys don't count
+    assert_eq!(
+        run("EXISTS", &["a", "a", "b", "missing", "gone", "a"]).unwrap(),
+        RespValue::integer(4)
+    );
+
+    // DEL counts the keys it actually removed, each once
+    assert_eq!(
+        run("DEL", &["a", "missing", "a", "b", "gone"]).unwrap(),
+        RespValue::integer(2)
+    );
+    assert_eq!(
+        run("EXISTS", &["a", "b", "gone"]).unwrap(),
+        RespValue::integer(0)
+    );
+    assert_eq!(run("DEL", &["a", "b"]).unwrap(), RespValue::integer(0));
+}
+
+#[test]
+fn test_append_creates_key_and_keeps_ttl() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    // A missing key is created with the appended value
+    assert_eq!(
+        run("APPEND", &["k", "Hello"]).unwrap(),
+        RespValue::integer(5)
+    );
+    assert_eq!(run("TTL", &["k"]).unwrap(), RespValue::integer(-1));
+
+    run("EXPIRE", &["k", "100"]).unwrap();
+    assert_eq!(
+        run("APPEND", &["k", " World"]).unwrap(),
+        RespValue::integer(11)
+    );
+    assert_eq!(
+        run("GET", &["k"]).unwrap(),
+        RespValue::bulk_string("Hello World")
+    );
+    assert!(matches!(
+        run("TTL", &["k"]).unwrap(),
+        RespValue::Integer(ttl) if ttl > 0 && ttl <= 100
+    ));
+
+    run("RPUSH", &["list", "a"]).unwrap();
+    assert!(matches!(
+        run("APPEND", &["list", "b"]),
+        Err(AikvError::WrongType(_))
+    ));
+}
+
+#[test]
+fn test_set_overwrite_clears_ttl() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let engines = [
+        StorageEngine::new_memory(16),
+        StorageEngine::new_aidb(dir.path().to_str().unwrap(), 16).unwrap(),
+    ];
+
+    for storage in engines {
+        let executor = CommandExecutor::new(storage);
+        let mut client = ClientContext::new(1);
+
+        let mut run = |cmd: &str, args: &[&str]| {
+            let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+            executor.execute(cmd, &args, &mut client)
+        };
+
+        // A plain SET replaces the TTL along with the value
+        run("SET", &["k", "old", "EX", "100"]).unwrap();
+        run("SET", &["k", "new"]).unwrap();
+        assert_eq!(run("TTL", &["k"]).unwrap(), RespValue::integer(-1));
+        assert_eq!(run("GET", &["k"]).unwrap(), RespValue::bulk_string("new"));
+
+        // Even after the key was rewritten in place with its TTL kept
+        run("EXPIRE", &["k", "100"]).unwrap();
+        run("APPEND", &["k", "er"]).unwrap();
+        run("SET", &["k", "newest"]).unwrap();
+        assert_eq!(run("TTL", &["k"]).unwrap(), RespValue::integer(-1));
+
+        // Unless KEEPTTL asks to keep it
+        run("EXPIRE", &["k", "100"]).unwrap();
+        run("SET", &["k", "kept", "KEEPTTL"]).unwrap();
+        assert!(matches!(
+            run("TTL", &["k"]).unwrap(),
+            RespValue::Integer(ttl) if ttl > 0 && ttl <= 100
+        ));
+        assert!(run("SET", &["k", "v", "KEEPTTL", "EX", "10"]).is_err());
+
+        // A PERSISTed key doesn't get its old TTL back on the next write
+        run("PERSIST", &["k"]).unwrap();
+        run("APPEND", &["k", "!"]).unwrap();
+        assert_eq!(run("TTL", &["k"]).unwrap(), RespValue::integer(-1));
+    }
+}
+
+#[test]
+fn test_fsync_makes_aidb_writes_durable() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().to_str().unwrap();
+
+    let executor = CommandExecutor::new(StorageEngine::new_aidb(path, 16).unwrap());
+    let mut client = ClientContext::new(1);
+    {
+        let mut run = |cmd: &str, args: &[&str]| {
+            let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+            executor.execute(cmd, &args, &mut client)
+        };
+
+        run("SET", &["durable", "yes"]).unwrap();
+        run("RPUSH", &["list", "a", "b"]).unwrap();
+        run("SELECT", &["3"]).unwrap();
+        run("SET", &["other", "db"]).unwrap();
+        assert_eq!(run("FSYNC", &[]).unwrap(), RespValue::ok());
+        assert!(run("FSYNC", &["now"]).is_err());
+    }
+
+    // Never close the adapter, as after a crash: dropping it would flush
+    // the databases itself, leaving FSYNC nothing to prove
+    std::mem::forget(executor);
+
+    // A new adapter over the same directory sees what was synced, in every
+    // database
+    let reopened = StorageEngine::new_aidb(path, 16).unwrap();
+    assert_eq!(
+        reopened.get_from_db(0, "durable").unwrap(),
+        Some(Bytes::from("yes"))
+    );
+    assert!(reopened.exists_in_db(0, "list").unwrap());
+    assert_eq!(
+        reopened.get_from_db(3, "other").unwrap(),
+        Some(Bytes::from("db"))
+    );
+
+    // Nothing to flush for the memory engine, but the command still succeeds
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let mut client = ClientContext::new(1);
+    assert_eq!(
+        executor.execute("FSYNC", &[], &mut client).unwrap(),
+        RespValue::ok()
+    );
+}
+
+#[test]
+fn test_aidb_persists_every_value_type() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().to_str().unwrap();
+
+    {
+        let executor = CommandExecutor::new(StorageEngine::new_aidb(path, 16).unwrap());
+        let mut client = ClientContext::new(1);
+        let mut run = |cmd: &str, args: &[&str]| {
+            let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+            executor.execute(cmd, &args, &mut client)
+        };
+
+        run("RPUSH", &["list", "a", "b", "c"]).unwrap();
+        run("LPOP", &["list"]).unwrap();
+        run("HSET", &["hash", "f", "v"]).unwrap();
+        run("SADD", &["set", "m1", "m2"]).unwrap();
+        run("ZADD", &["zset", "1.5", "z"]).unwrap();
+        run("EXPIRE", &["zset", "1000"]).unwrap();
+        run("FSYNC", &[]).unwrap();
+    }
+
+    let executor = CommandExecutor::new(StorageEngine::new_aidb(path, 16).unwrap());
+    let mut client = ClientContext::new(1);
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    assert_eq!(
+        run("LRANGE", &["list", "0", "-1"]).unwrap(),
+        RespValue::array(vec![
+            RespValue::bulk_string("b"),
+            RespValue::bulk_string("c")
+        ])
+    );
+    assert_eq!(
+        run("HGET", &["hash", "f"]).unwrap(),
+        RespValue::bulk_string("v")
+    );
+    assert_eq!(run("SCARD", &["set"]).unwrap(), RespValue::integer(2));
+    assert_eq!(
+        run("SISMEMBER", &["set", "m2"]).unwrap(),
+        RespValue::integer(1)
+    );
+    assert_eq!(
+        run("ZSCORE", &["zset", "z"]).unwrap(),
+        RespValue::double(1.5)
+    );
+    assert!(matches!(
+        run("TTL", &["zset"]).unwrap(),
+        RespValue::Integer(ttl) if ttl > 0
+    ));
+    assert_eq!(
+        run("TYPE", &["hash"]).unwrap(),
+        RespValue::simple_string("hash")
+    );
+
+    // A reloaded list is still a list, not a missing string
+    assert!(matches!(
+        run("GET", &["list"]),
+        Err(AikvError::WrongType(_))
+    ));
+}
+
+#[test]
+fn test_copy_across_databases() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str], db: usize| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        client.db = db;
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    run("HSET", &["src", "f1", "v1", "f2", "v2"], 0).unwrap();
+    run("PEXPIRE", &["src", "100000"], 0).unwrap();
+    run("SET", &["dst", "old"], 2).unwrap();
+
+    // Existing destination without REPLACE is left alone
+    assert_eq!(
+        run("COPY", &["src", "dst", "DB", "2"], 0).unwrap(),
+        RespValue::integer(0)
+    );
+    assert_eq!(
+        run("TYPE", &["dst"], 2).unwrap(),
+        RespValue::simple_string("string")
+    );
+
+    assert_eq!(
+        run("COPY", &["src", "dst", "DB", "2", "REPLACE"], 0).unwrap(),
+        RespValue::integer(1)
+    );
+    assert_eq!(run("HLEN", &["dst"], 2).unwrap(), RespValue::integer(2));
+
+    // TTL is copied along with the value
+    match run("PTTL", &["dst"], 2).unwrap() {
+        RespValue::Integer(pttl) => assert!(pttl > 0 && pttl <= 100000),
+        other => panic!("Expected integer PTTL, got {:?}", other),
+    }
+
+    // Mutating either copy doesn't affect the other
+    run("HSET", &["src", "f1", "changed", "f3", "v3"], 0).unwrap();
+    run("HDEL", &["dst", "f2"], 2).unwrap();
+    assert_eq!(
+        run("HGET", &["dst", "f1"], 2).unwrap(),
+        RespValue::bulk_string("v1")
+    );
+    assert_eq!(run("HLEN", &["dst"], 2).unwrap(), RespValue::integer(1));
+    assert_eq!(run("HLEN", &["src"], 0).unwrap(), RespValue::integer(3));
+    assert_eq!(
+        run("HGET", &["src", "f2"], 0).unwrap(),
+        RespValue::bulk_string("v2")
+    );
+
+    // Invalid destinations
+    assert!(run("COPY", &["src", "other", "DB", "16"], 0).is_err());
+    assert!(run("COPY", &["src", "src"], 0).is_err());
+    assert_eq!(
+        run("COPY", &["src", "src", "DB", "1"], 0).unwrap(),
+        RespValue::integer(1)
+    );
+}
+
 #[test]
 fn test_expiration_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Set up test data
     executor
         .execute(
             "SET",
             &[Bytes::from("key1"), Bytes::from("value1")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -288,15 +737,14 @@ fn test_expiration_commands() {
         .execute(
             "EXPIRE",
             &[Bytes::from("key1"), Bytes::from("100")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     assert_eq!(result, RespValue::integer(1));
 
     // Test TTL
     let result = executor
-        .execute("TTL", &[Bytes::from("key1")], &mut current_db, client_id)
+        .execute("TTL", &[Bytes::from("key1")], &mut client)
         .unwrap();
     if let RespValue::Integer(ttl) = result {
         assert!(ttl > 0 && ttl <= 100);
@@ -306,7 +754,7 @@ fn test_expiration_commands() {
 
     // Test PTTL
     let result = executor
-        .execute("PTTL", &[Bytes::from("key1")], &mut current_db, client_id)
+        .execute("PTTL", &[Bytes::from("key1")], &mut client)
         .unwrap();
     if let RespValue::Integer(pttl) = result {
         assert!(pttl > 0 && pttl <= 100000);
@@ -316,18 +764,13 @@ fn test_expiration_commands() {
 
     // Test PERSIST
     let result = executor
-        .execute(
-            "PERSIST",
-            &[Bytes::from("key1")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("PERSIST", &[Bytes::from("key1")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::integer(1));
 
     // TTL should now be -1
     let result = executor
-        .execute("TTL", &[Bytes::from("key1")], &mut current_db, client_id)
+        .execute("TTL", &[Bytes::from("key1")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::integer(-1));
 
@@ -336,20 +779,14 @@ fn test_expiration_commands() {
         .execute(
             "PEXPIRE",
             &[Bytes::from("key1"), Bytes::from("50000")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     assert_eq!(result, RespValue::integer(1));
 
     // Test EXPIRETIME
     let result = executor
-        .execute(
-            "EXPIRETIME",
-            &[Bytes::from("key1")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("EXPIRETIME", &[Bytes::from("key1")], &mut client)
         .unwrap();
     if let RespValue::Integer(timestamp) = result {
         assert!(timestamp > 0);
@@ -359,12 +796,7 @@ fn test_expiration_commands() {
 
     // Test PEXPIRETIME
     let result = executor
-        .execute(
-            "PEXPIRETIME",
-            &[Bytes::from("key1")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("PEXPIRETIME", &[Bytes::from("key1")], &mut client)
         .unwrap();
     if let RespValue::Integer(timestamp) = result {
         assert!(timestamp > 0);
@@ -374,49 +806,228 @@ fn test_expiration_commands() {
 
     // Test TTL on non-existent key
     let result = executor
-        .execute(
-            "TTL",
-            &[Bytes::from("nonexistent")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("TTL", &[Bytes::from("nonexistent")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::integer(-2));
 }
 
+#[test]
+fn test_ttl_rounds_to_the_nearest_second() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    // Missing keys and keys without a TTL report the sentinels either way
+    for cmd in ["TTL", "PTTL", "EXPIRETIME", "PEXPIRETIME"] {
+        assert_eq!(run(cmd, &["missing"]).unwrap(), RespValue::integer(-2));
+    }
+    run("SET", &["key", "value"]).unwrap();
+    for cmd in ["TTL", "PTTL", "EXPIRETIME", "PEXPIRETIME"] {
+        assert_eq!(run(cmd, &["key"]).unwrap(), RespValue::integer(-1));
+    }
+
+    // A bit under 1800ms left rounds up to 2 seconds, where truncating
+    // would report 1
+    run("PEXPIRE", &["key", "1800"]).unwrap();
+    assert!(matches!(
+        run("PTTL", &["key"]).unwrap(),
+        RespValue::Integer(pttl) if pttl > 1700 && pttl <= 1800
+    ));
+    assert_eq!(run("TTL", &["key"]).unwrap(), RespValue::integer(2));
+
+    // ... and a bit under 1200ms rounds down to 1
+    run("PEXPIRE", &["key", "1200"]).unwrap();
+    assert_eq!(run("TTL", &["key"]).unwrap(), RespValue::integer(1));
+
+    // The absolute expire time rounds the same way
+    let second = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        + 1000;
+    let at = (second * 1000 + 600).to_string();
+    run("PEXPIREAT", &["key", &at]).unwrap();
+    assert_eq!(
+        run("PEXPIRETIME", &["key"]).unwrap(),
+        RespValue::integer(second as i64 * 1000 + 600)
+    );
+    assert_eq!(
+        run("EXPIRETIME", &["key"]).unwrap(),
+        RespValue::integer(second as i64 + 1)
+    );
+}
+
+#[test]
+fn test_expire_in_the_past_deletes_the_key() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    for (cmd, time) in [
+        ("EXPIRE", "-1"),
+        ("PEXPIRE", "0"),
+        ("EXPIREAT", "1"),
+        ("PEXPIREAT", "1"),
+    ] {
+        run("SET", &["key", "value"]).unwrap();
+        assert_eq!(
+            run(cmd, &["key", time]).unwrap(),
+            RespValue::integer(1),
+            "{} {}",
+            cmd,
+            time
+        );
+        assert_eq!(
+            run("EXISTS", &["key"]).unwrap(),
+            RespValue::integer(0),
+            "{} {}",
+            cmd,
+            time
+        );
+        assert_eq!(run("DBSIZE", &[]).unwrap(), RespValue::integer(0));
+    }
+
+    // Nothing to delete
+    assert_eq!(
+        run("PEXPIREAT", &["key", "1"]).unwrap(),
+        RespValue::integer(0)
+    );
+}
+
+#[test]
+fn test_expire_conditions() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+    let ttl = |reply: RespValue| match reply {
+        RespValue::Integer(ttl) => ttl,
+        other => panic!("expected integer, got {:?}", other),
+    };
+
+    run("SET", &["key", "value"]).unwrap();
+
+    // XX and GT need an existing TTL; NX and LT apply to a key without one
+    assert_eq!(
+        run("EXPIRE", &["key", "100", "XX"]).unwrap(),
+        RespValue::integer(0)
+    );
+    assert_eq!(
+        run("EXPIRE", &["key", "100", "GT"]).unwrap(),
+        RespValue::integer(0)
+    );
+    assert_eq!(run("TTL", &["key"]).unwrap(), RespValue::integer(-1));
+    assert_eq!(
+        run("EXPIRE", &["key", "100", "NX"]).unwrap(),
+        RespValue::integer(1)
+    );
+    assert_eq!(
+        run("EXPIRE", &["key", "200", "NX"]).unwrap(),
+        RespValue::integer(0)
+    );
+    assert!(ttl(run("TTL", &["key"]).unwrap()) <= 100);
+
+    // GT refuses to shorten the TTL but extends it
+    assert_eq!(
+        run("EXPIRE", &["key", "50", "GT"]).unwrap(),
+        RespValue::integer(0)
+    );
+    assert!(ttl(run("TTL", &["key"]).unwrap()) > 50);
+    assert_eq!(
+        run("PEXPIRE", &["key", "300000", "gt"]).unwrap(),
+        RespValue::integer(1)
+    );
+    assert!(ttl(run("TTL", &["key"]).unwrap()) > 200);
+
+    // LT refuses to extend it but shortens it
+    assert_eq!(
+        run("EXPIRE", &["key", "400", "LT"]).unwrap(),
+        RespValue::integer(0)
+    );
+    assert_eq!(
+        run("EXPIRE", &["key", "150", "LT"]).unwrap(),
+        RespValue::integer(1)
+    );
+    assert!(ttl(run("TTL", &["key"]).unwrap()) <= 150);
+
+    // XX replaces an existing TTL; absolute forms take the same flags
+    let at = (std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        + 1000)
+        .to_string();
+    assert_eq!(
+        run("EXPIREAT", &["key", &at, "XX"]).unwrap(),
+        RespValue::integer(1)
+    );
+    assert!(ttl(run("TTL", &["key"]).unwrap()) > 900);
+    assert_eq!(
+        run("PEXPIREAT", &["key", "1", "NX"]).unwrap(),
+        RespValue::integer(0)
+    );
+
+    // Missing keys are never touched
+    assert_eq!(
+        run("EXPIRE", &["missing", "100", "NX"]).unwrap(),
+        RespValue::integer(0)
+    );
+
+    // Mutually exclusive and unknown flags are rejected
+    let err = run("EXPIRE", &["key", "100", "NX", "XX"])
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("not compatible"), "{}", err);
+    let err = run("EXPIRE", &["key", "100", "GT", "LT"])
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("GT and LT"), "{}", err);
+    let err = run("EXPIRE", &["key", "100", "SOMETIMES"])
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("Unsupported option SOMETIMES"), "{}", err);
+    assert!(ttl(run("TTL", &["key"]).unwrap()) > 900);
+}
+
 #[test]
 fn test_ping_command() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Test PING without argument - should return simple string "PONG"
-    let result = executor
-        .execute("PING", &[], &mut current_db, client_id)
-        .unwrap();
+    let result = executor.execute("PING", &[], &mut client).unwrap();
     assert_eq!(result, RespValue::simple_string("PONG"));
 
     // Test PING with message argument - should return bulk string with the message
     let result = executor
-        .execute("PING", &[Bytes::from("hello")], &mut current_db, client_id)
+        .execute("PING", &[Bytes::from("hello")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::bulk_string("hello"));
 
     // Test PING with empty string argument
     let result = executor
-        .execute("PING", &[Bytes::from("")], &mut current_db, client_id)
+        .execute("PING", &[Bytes::from("")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::bulk_string(""));
 
     // Test PING with special characters
     let result = executor
-        .execute(
-            "PING",
-            &[Bytes::from("hello world!")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("PING", &[Bytes::from("hello world!")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::bulk_string("hello world!"));
 
@@ -424,8 +1035,7 @@ fn test_ping_command() {
     let result = executor.execute(
         "PING",
         &[Bytes::from("hello"), Bytes::from("world")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_err());
 }
@@ -434,19 +1044,16 @@ fn test_ping_command() {
 fn test_server_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Register the client first (simulating what Connection does)
     executor
         .server_commands()
-        .register_client(client_id, "127.0.0.1:12345".to_string())
+        .register_client(client.client_id, "127.0.0.1:12345".to_string())
         .unwrap();
 
     // Test INFO
-    let result = executor
-        .execute("INFO", &[], &mut current_db, client_id)
-        .unwrap();
+    let result = executor.execute("INFO", &[], &mut client).unwrap();
     assert!(matches!(result, RespValue::BulkString(Some(_))));
 
     // Test CONFIG GET
@@ -454,22 +1061,23 @@ fn test_server_commands() {
         .execute(
             "CONFIG",
             &[Bytes::from("GET"), Bytes::from("server")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
-    if let RespValue::Array(Some(arr)) = result {
-        assert_eq!(arr.len(), 2);
-        assert_eq!(arr[0], RespValue::bulk_string("server"));
-        assert_eq!(arr[1], RespValue::bulk_string("aikv"));
+    if let RespValue::Map(pairs) = result {
+        assert_eq!(
+            pairs,
+            vec![(
+                RespValue::bulk_string("server"),
+                RespValue::bulk_string("aikv")
+            )]
+        );
     } else {
-        panic!("Expected array");
+        panic!("Expected map");
     }
 
     // Test TIME
-    let result = executor
-        .execute("TIME", &[], &mut current_db, client_id)
-        .unwrap();
+    let result = executor.execute("TIME", &[], &mut client).unwrap();
     if let RespValue::Array(Some(arr)) = result {
         assert_eq!(arr.len(), 2);
         assert!(matches!(&arr[0], RespValue::BulkString(Some(_))));
@@ -483,36 +1091,78 @@ fn test_server_commands() {
         .execute(
             "CLIENT",
             &[Bytes::from("SETNAME"), Bytes::from("test-client")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     assert_eq!(result, RespValue::ok());
 
     // Test CLIENT GETNAME
     let result = executor
-        .execute(
-            "CLIENT",
-            &[Bytes::from("GETNAME")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("CLIENT", &[Bytes::from("GETNAME")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::bulk_string("test-client"));
 
     // Test CLIENT LIST
     let result = executor
-        .execute("CLIENT", &[Bytes::from("LIST")], &mut current_db, client_id)
+        .execute("CLIENT", &[Bytes::from("LIST")], &mut client)
         .unwrap();
     assert!(matches!(result, RespValue::BulkString(Some(_))));
 }
 
+#[test]
+fn test_scan_type_filter() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    run("SET", &["string:1", "v"]).unwrap();
+    run("HSET", &["hash:1", "f", "v"]).unwrap();
+    run("HSET", &["hash:2", "f", "v"]).unwrap();
+    run("RPUSH", &["list:1", "a"]).unwrap();
+    run("SADD", &["set:1", "a"]).unwrap();
+    run("ZADD", &["zset:1", "1", "a"]).unwrap();
+
+    let mut scan = |args: &[&str]| {
+        let reply = run("SCAN", args).unwrap();
+        let RespValue::Array(Some(reply)) = reply else {
+            panic!("expected array, got {:?}", reply);
+        };
+        assert_eq!(reply[0], RespValue::bulk_string("0"));
+        let RespValue::Array(Some(keys)) = &reply[1] else {
+            panic!("expected array of keys, got {:?}", reply[1]);
+        };
+        let mut keys: Vec<String> = keys
+            .iter()
+            .map(|key| match key {
+                RespValue::BulkString(Some(key)) => String::from_utf8_lossy(key).to_string(),
+                other => panic!("expected bulk string, got {:?}", other),
+            })
+            .collect();
+        keys.sort();
+        keys
+    };
+
+    assert_eq!(scan(&["0", "TYPE", "hash"]), ["hash:1", "hash:2"]);
+    assert_eq!(scan(&["0", "TYPE", "LIST"]), ["list:1"]);
+    assert_eq!(scan(&["0", "TYPE", "zset", "COUNT", "100"]), ["zset:1"]);
+    assert_eq!(scan(&["0", "MATCH", "*:2", "TYPE", "hash"]), ["hash:2"]);
+    assert!(scan(&["0", "TYPE", "stream"]).is_empty());
+    assert_eq!(
+        scan(&["0", "COUNT", "100"]),
+        ["hash:1", "hash:2", "list:1", "set:1", "string:1", "zset:1"]
+    );
+}
+
 #[test]
 fn test_scan_iteration() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Set up test data with many keys
     for i in 0..25 {
@@ -523,8 +1173,7 @@ fn test_scan_iteration() {
                     Bytes::from(format!("key:{}", i)),
                     Bytes::from(format!("value:{}", i)),
                 ],
-                &mut current_db,
-                client_id,
+                &mut client,
             )
             .unwrap();
     }
@@ -543,8 +1192,7 @@ fn test_scan_iteration() {
                     Bytes::from("COUNT"),
                     Bytes::from("5"),
                 ],
-                &mut current_db,
-                client_id,
+                &mut client,
             )
             .unwrap();
 
@@ -588,8 +1236,7 @@ fn test_scan_iteration() {
                 Bytes::from("COUNT"),
                 Bytes::from("20"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -612,8 +1259,7 @@ fn test_scan_iteration() {
 fn test_set_with_expire_options() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Test SET with EX option
     let result = executor
@@ -625,15 +1271,14 @@ fn test_set_with_expire_options() {
                 Bytes::from("EX"),
                 Bytes::from("100"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     assert_eq!(result, RespValue::ok());
 
     // Verify TTL was set
     let result = executor
-        .execute("TTL", &[Bytes::from("key1")], &mut current_db, client_id)
+        .execute("TTL", &[Bytes::from("key1")], &mut client)
         .unwrap();
     if let RespValue::Integer(ttl) = result {
         assert!(ttl > 0 && ttl <= 100);
@@ -651,15 +1296,14 @@ fn test_set_with_expire_options() {
                 Bytes::from("PX"),
                 Bytes::from("50000"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     assert_eq!(result, RespValue::ok());
 
     // Verify PTTL was set
     let result = executor
-        .execute("PTTL", &[Bytes::from("key2")], &mut current_db, client_id)
+        .execute("PTTL", &[Bytes::from("key2")], &mut client)
         .unwrap();
     if let RespValue::Integer(pttl) = result {
         assert!(pttl > 0 && pttl <= 50000);
@@ -672,22 +1316,20 @@ fn test_set_with_expire_options() {
 fn test_dump_and_restore_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Set up test data
     executor
         .execute(
             "SET",
             &[Bytes::from("mykey"), Bytes::from("hello world")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
     // Test DUMP
     let result = executor
-        .execute("DUMP", &[Bytes::from("mykey")], &mut current_db, client_id)
+        .execute("DUMP", &[Bytes::from("mykey")], &mut client)
         .unwrap();
     let dump_data = match result {
         RespValue::BulkString(Some(data)) => data,
@@ -696,17 +1338,12 @@ fn test_dump_and_restore_commands() {
 
     // Delete the key
     executor
-        .execute("DEL", &[Bytes::from("mykey")], &mut current_db, client_id)
+        .execute("DEL", &[Bytes::from("mykey")], &mut client)
         .unwrap();
 
     // Verify key is gone
     let result = executor
-        .execute(
-            "EXISTS",
-            &[Bytes::from("mykey")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("EXISTS", &[Bytes::from("mykey")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::integer(0));
 
@@ -715,15 +1352,14 @@ fn test_dump_and_restore_commands() {
         .execute(
             "RESTORE",
             &[Bytes::from("mykey"), Bytes::from("0"), dump_data.clone()],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     assert_eq!(result, RespValue::ok());
 
     // Verify key is restored
     let result = executor
-        .execute("GET", &[Bytes::from("mykey")], &mut current_db, client_id)
+        .execute("GET", &[Bytes::from("mykey")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::bulk_string("hello world"));
 
@@ -732,8 +1368,7 @@ fn test_dump_and_restore_commands() {
         .execute(
             "SET",
             &[Bytes::from("otherkey"), Bytes::from("other value")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -746,20 +1381,14 @@ fn test_dump_and_restore_commands() {
                 dump_data.clone(),
                 Bytes::from("REPLACE"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     assert_eq!(result, RespValue::ok());
 
     // Verify it was replaced
     let result = executor
-        .execute(
-            "GET",
-            &[Bytes::from("otherkey")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("GET", &[Bytes::from("otherkey")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::bulk_string("hello world"));
 
@@ -767,8 +1396,7 @@ fn test_dump_and_restore_commands() {
     let result = executor.execute(
         "RESTORE",
         &[Bytes::from("otherkey"), Bytes::from("0"), dump_data.clone()],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_err());
 
@@ -781,15 +1409,14 @@ fn test_dump_and_restore_commands() {
                 Bytes::from("5000"),
                 dump_data.clone(),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     assert_eq!(result, RespValue::ok());
 
     // Verify TTL was set
     let result = executor
-        .execute("PTTL", &[Bytes::from("ttlkey")], &mut current_db, client_id)
+        .execute("PTTL", &[Bytes::from("ttlkey")], &mut client)
         .unwrap();
     if let RespValue::Integer(pttl) = result {
         assert!(pttl > 0 && pttl <= 5000);
@@ -799,12 +1426,7 @@ fn test_dump_and_restore_commands() {
 
     // Test DUMP on non-existent key
     let result = executor
-        .execute(
-            "DUMP",
-            &[Bytes::from("nonexistent")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("DUMP", &[Bytes::from("nonexistent")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::null_bulk_string());
 }
@@ -813,8 +1435,7 @@ fn test_dump_and_restore_commands() {
 fn test_dump_restore_with_complex_types() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Test with List
     executor
@@ -826,13 +1447,12 @@ fn test_dump_restore_with_complex_types() {
                 Bytes::from("b"),
                 Bytes::from("c"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
     let result = executor
-        .execute("DUMP", &[Bytes::from("mylist")], &mut current_db, client_id)
+        .execute("DUMP", &[Bytes::from("mylist")], &mut client)
         .unwrap();
     let list_dump = match result {
         RespValue::BulkString(Some(data)) => data,
@@ -844,8 +1464,7 @@ fn test_dump_restore_with_complex_types() {
         .execute(
             "RESTORE",
             &[Bytes::from("restoredlist"), Bytes::from("0"), list_dump],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     assert_eq!(result, RespValue::ok());
@@ -859,8 +1478,7 @@ fn test_dump_restore_with_complex_types() {
                 Bytes::from("0"),
                 Bytes::from("-1"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     if let RespValue::Array(Some(items)) = result {
@@ -883,13 +1501,12 @@ fn test_dump_restore_with_complex_types() {
                 Bytes::from("field2"),
                 Bytes::from("value2"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
     let result = executor
-        .execute("DUMP", &[Bytes::from("myhash")], &mut current_db, client_id)
+        .execute("DUMP", &[Bytes::from("myhash")], &mut client)
         .unwrap();
     let hash_dump = match result {
         RespValue::BulkString(Some(data)) => data,
@@ -901,8 +1518,7 @@ fn test_dump_restore_with_complex_types() {
         .execute(
             "RESTORE",
             &[Bytes::from("restoredhash"), Bytes::from("0"), hash_dump],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     assert_eq!(result, RespValue::ok());
@@ -912,289 +1528,151 @@ fn test_dump_restore_with_complex_types() {
         .execute(
             "HGET",
             &[Bytes::from("restoredhash"), Bytes::from("field1")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     assert_eq!(result, RespValue::bulk_string("value1"));
 }
 
 #[test]
-fn test_migrate_command() {
+fn test_dump_restore_all_types() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
-
-    // Set up test data in database 0
-    executor
-        .execute(
-            "SET",
-            &[Bytes::from("migratekey"), Bytes::from("migrate value")],
-            &mut current_db,
-            client_id,
-        )
-        .unwrap();
-
-    // Test basic MIGRATE to database 1
-    let result = executor
-        .execute(
-            "MIGRATE",
-            &[
-                Bytes::from("localhost"),
-                Bytes::from("6379"),
-                Bytes::from("migratekey"),
-                Bytes::from("1"),
-                Bytes::from("1000"),
-            ],
-            &mut current_db,
-            client_id,
-        )
-        .unwrap();
-    assert_eq!(result, RespValue::ok());
-
-    // Verify key is gone from database 0
-    let result = executor
-        .execute(
-            "EXISTS",
-            &[Bytes::from("migratekey")],
-            &mut current_db,
-            client_id,
-        )
-        .unwrap();
-    assert_eq!(result, RespValue::integer(0));
-
-    // Switch to database 1 and verify key exists
-    executor
-        .execute("SELECT", &[Bytes::from("1")], &mut current_db, client_id)
-        .unwrap();
-    let result = executor
-        .execute(
-            "GET",
-            &[Bytes::from("migratekey")],
-            &mut current_db,
-            client_id,
-        )
-        .unwrap();
-    assert_eq!(result, RespValue::bulk_string("migrate value"));
-
-    // Test MIGRATE with COPY option
-    executor
-        .execute(
-            "SET",
-            &[Bytes::from("copykey"), Bytes::from("copy value")],
-            &mut current_db,
-            client_id,
-        )
-        .unwrap();
-
-    let result = executor
-        .execute(
-            "MIGRATE",
-            &[
-                Bytes::from("localhost"),
-                Bytes::from("6379"),
-                Bytes::from("copykey"),
-                Bytes::from("2"),
-                Bytes::from("1000"),
-                Bytes::from("COPY"),
-            ],
-            &mut current_db,
-            client_id,
-        )
-        .unwrap();
-    assert_eq!(result, RespValue::ok());
-
-    // Verify key still exists in database 1
-    let result = executor
-        .execute(
-            "EXISTS",
-            &[Bytes::from("copykey")],
-            &mut current_db,
-            client_id,
-        )
-        .unwrap();
-    assert_eq!(result, RespValue::integer(1));
-
-    // Verify key also exists in database 2
-    executor
-        .execute("SELECT", &[Bytes::from("2")], &mut current_db, client_id)
-        .unwrap();
-    let result = executor
-        .execute("GET", &[Bytes::from("copykey")], &mut current_db, client_id)
-        .unwrap();
-    assert_eq!(result, RespValue::bulk_string("copy value"));
-
-    // Test MIGRATE non-existent key
-    let result = executor
-        .execute(
-            "MIGRATE",
-            &[
-                Bytes::from("localhost"),
-                Bytes::from("6379"),
-                Bytes::from("nonexistent"),
-                Bytes::from("3"),
-                Bytes::from("1000"),
-            ],
-            &mut current_db,
-            client_id,
-        )
-        .unwrap();
-    assert_eq!(result, RespValue::simple_string("NOKEY"));
-
-    // Test MIGRATE with REPLACE option
-    // Set a key in database 3
-    executor
-        .execute("SELECT", &[Bytes::from("3")], &mut current_db, client_id)
-        .unwrap();
-    executor
-        .execute(
-            "SET",
-            &[Bytes::from("replacekey"), Bytes::from("original")],
-            &mut current_db,
-            client_id,
-        )
-        .unwrap();
-
-    // Set a key in database 0
-    executor
-        .execute("SELECT", &[Bytes::from("0")], &mut current_db, client_id)
-        .unwrap();
-    executor
-        .execute(
-            "SET",
-            &[Bytes::from("replacekey"), Bytes::from("new value")],
-            &mut current_db,
-            client_id,
-        )
-        .unwrap();
-
-    // Migrate with REPLACE
-    let result = executor
-        .execute(
-            "MIGRATE",
-            &[
-                Bytes::from("localhost"),
-                Bytes::from("6379"),
-                Bytes::from("replacekey"),
-                Bytes::from("3"),
-                Bytes::from("1000"),
-                Bytes::from("REPLACE"),
-            ],
-            &mut current_db,
-            client_id,
-        )
-        .unwrap();
-    assert_eq!(result, RespValue::ok());
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: Vec<Bytes>| executor.execute(cmd, &args, &mut client);
+    let args =
+        |args: &[&str]| -> Vec<Bytes> { args.iter().map(|a| Bytes::from(a.to_string())).collect() };
+
+    run("SET", args(&["string", "hello"])).unwrap();
+    run("RPUSH", args(&["list", "a", "b", "c"])).unwrap();
+    run("HSET", args(&["hash", "f1", "v1", "f2", "v2"])).unwrap();
+    run("SADD", args(&["set", "x", "y"])).unwrap();
+    run("ZADD", args(&["zset", "1.5", "one", "2", "two"])).unwrap();
+
+    // (key, TYPE reply, length command, expected length, element lookup)
+    let cases = [
+        ("string", "string", "STRLEN", 5, ("GET", None, "hello")),
+        ("list", "list", "LLEN", 3, ("LINDEX", Some("1"), "b")),
+        ("hash", "hash", "HLEN", 2, ("HGET", Some("f2"), "v2")),
+        ("set", "set", "SCARD", 2, ("SISMEMBER", Some("y"), "1")),
+        ("zset", "zset", "ZCARD", 2, ("ZSCORE", Some("one"), "1.5")),
+    ];
+
+    for (key, type_name, len_cmd, len, (lookup_cmd, lookup_arg, expected)) in cases {
+        let payload = match run("DUMP", args(&[key])).unwrap() {
+            RespValue::BulkString(Some(data)) => data,
+            other => panic!("Expected bulk string for DUMP {}, got {:?}", key, other),
+        };
+        let restored = format!("restored:{}", key);
+        let restore_args = |extra: &[&str]| {
+            let mut restore = vec![
+                Bytes::from(restored.clone()),
+                Bytes::from("0"),
+                payload.clone(),
+            ];
+            restore.extend(args(extra));
+            restore
+        };
+
+        // Restore into a fresh key
+        assert_eq!(run("RESTORE", restore_args(&[])).unwrap(), RespValue::ok());
+        assert_eq!(
+            run("TYPE", args(&[restored.as_str()])).unwrap(),
+            RespValue::simple_string(type_name)
+        );
+        assert_eq!(
+            run(len_cmd, args(&[restored.as_str()])).unwrap(),
+            RespValue::integer(len)
+        );
+
+        let mut lookup = vec![restored.as_str()];
+        lookup.extend(lookup_arg);
+        match run(lookup_cmd, args(&lookup)).unwrap() {
+            RespValue::BulkString(Some(data)) => assert_eq!(data, Bytes::from(expected)),
+            RespValue::Integer(n) => assert_eq!(n.to_string(), expected),
+            other => panic!("Unexpected {} reply: {:?}", lookup_cmd, other),
+        }
 
-    // Verify value was replaced in database 3
-    executor
-        .execute("SELECT", &[Bytes::from("3")], &mut current_db, client_id)
-        .unwrap();
-    let result = executor
-        .execute(
-            "GET",
-            &[Bytes::from("replacekey")],
-            &mut current_db,
-            client_id,
-        )
-        .unwrap();
-    assert_eq!(result, RespValue::bulk_string("new value"));
+        // Restoring over the existing key requires REPLACE
+        assert!(run("RESTORE", restore_args(&[])).is_err());
+        assert_eq!(
+            run("RESTORE", restore_args(&["REPLACE"])).unwrap(),
+            RespValue::ok()
+        );
+        assert_eq!(
+            run(len_cmd, args(&[restored.as_str()])).unwrap(),
+            RespValue::integer(len)
+        );
+    }
 }
 
 #[test]
-fn test_migrate_with_keys_option() {
+fn test_restore_rejects_corrupt_payload() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
-    // Set up multiple keys in database 0
     executor
         .execute(
-            "SET",
-            &[Bytes::from("key1"), Bytes::from("value1")],
-            &mut current_db,
-            client_id,
-        )
-        .unwrap();
-    executor
-        .execute(
-            "SET",
-            &[Bytes::from("key2"), Bytes::from("value2")],
-            &mut current_db,
-            client_id,
+            "RPUSH",
+            &[Bytes::from("mylist"), Bytes::from("a"), Bytes::from("b")],
+            &mut client,
         )
         .unwrap();
-    executor
+    let payload = match executor
+        .execute("DUMP", &[Bytes::from("mylist")], &mut client)
+        .unwrap()
+    {
+        RespValue::BulkString(Some(data)) => data,
+        _ => panic!("Expected bulk string for DUMP"),
+    };
+
+    // Flip a byte in the value body
+    let mut corrupt = payload.to_vec();
+    corrupt[0] ^= 0xff;
+    let err = executor
         .execute(
-            "SET",
-            &[Bytes::from("key3"), Bytes::from("value3")],
-            &mut current_db,
-            client_id,
+            "RESTORE",
+            &[Bytes::from("copy"), Bytes::from("0"), Bytes::from(corrupt)],
+            &mut client,
         )
-        .unwrap();
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Bad data format");
 
-    // Migrate multiple keys with KEYS option
-    let result = executor
+    // Truncated payloads are rejected the same way
+    let err = executor
         .execute(
-            "MIGRATE",
+            "RESTORE",
             &[
-                Bytes::from("localhost"),
-                Bytes::from("6379"),
-                Bytes::from(""),
-                Bytes::from("4"),
-                Bytes::from("1000"),
-                Bytes::from("KEYS"),
-                Bytes::from("key1"),
-                Bytes::from("key2"),
+                Bytes::from("copy"),
+                Bytes::from("0"),
+                payload.slice(..payload.len() - 1),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
-        .unwrap();
-    assert_eq!(result, RespValue::ok());
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Bad data format");
 
-    // Verify keys are gone from database 0
-    let result = executor
-        .execute("EXISTS", &[Bytes::from("key1")], &mut current_db, client_id)
-        .unwrap();
-    assert_eq!(result, RespValue::integer(0));
-    let result = executor
-        .execute("EXISTS", &[Bytes::from("key2")], &mut current_db, client_id)
-        .unwrap();
-    assert_eq!(result, RespValue::integer(0));
-    // key3 should still exist
-    let result = executor
-        .execute("EXISTS", &[Bytes::from("key3")], &mut current_db, client_id)
-        .unwrap();
-    assert_eq!(result, RespValue::integer(1));
-
-    // Verify keys exist in database 4
-    executor
-        .execute("SELECT", &[Bytes::from("4")], &mut current_db, client_id)
-        .unwrap();
-    let result = executor
-        .execute("GET", &[Bytes::from("key1")], &mut current_db, client_id)
-        .unwrap();
-    assert_eq!(result, RespValue::bulk_string("value1"));
-    let result = executor
-        .execute("GET", &[Bytes::from("key2")], &mut current_db, client_id)
-        .unwrap();
-    assert_eq!(result, RespValue::bulk_string("value2"));
+    // BUSYKEY when the target exists and REPLACE wasn't given
+    let err = executor
+        .execute(
+            "RESTORE",
+            &[Bytes::from("mylist"), Bytes::from("0"), payload],
+            &mut client,
+        )
+        .unwrap_err();
+    assert!(err.to_string().starts_with("BUSYKEY"));
 }
 
 #[test]
 fn test_command_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Test COMMAND (returns all commands)
-    let result = executor
-        .execute("COMMAND", &[], &mut current_db, client_id)
-        .unwrap();
+    let result = executor.execute("COMMAND", &[], &mut client).unwrap();
     if let RespValue::Array(Some(arr)) = result {
         // Should have a reasonable number of commands
         assert!(arr.len() > 50);
@@ -1204,12 +1682,7 @@ fn test_command_commands() {
 
     // Test COMMAND COUNT
     let result = executor
-        .execute(
-            "COMMAND",
-            &[Bytes::from("COUNT")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("COMMAND", &[Bytes::from("COUNT")], &mut client)
         .unwrap();
     if let RespValue::Integer(count) = result {
         assert!(count > 50);
@@ -1222,8 +1695,7 @@ fn test_command_commands() {
         .execute(
             "COMMAND",
             &[Bytes::from("INFO"), Bytes::from("GET")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     if let RespValue::Array(Some(arr)) = result {
@@ -1245,8 +1717,7 @@ fn test_command_commands() {
         .execute(
             "COMMAND",
             &[Bytes::from("INFO"), Bytes::from("NONEXISTENT")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
     if let RespValue::Array(Some(arr)) = result {
@@ -1257,28 +1728,230 @@ fn test_command_commands() {
     }
 
     // Test COMMAND HELP
+    let result = executor
+        .execute("COMMAND", &[Bytes::from("HELP")], &mut client)
+        .unwrap();
+    assert!(matches!(result, RespValue::Array(Some(_))));
+}
+
+#[test]
+fn test_subcommand_help() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    for command in [
+        "CLIENT", "CONFIG", "SCRIPT", "OBJECT", "SLOWLOG", "DEBUG", "COMMAND",
+    ] {
+        let reply = run(command, &["help"]).unwrap();
+        let RespValue::Array(Some(lines)) = &reply else {
+            panic!("{} HELP: expected array, got {:?}", command, reply);
+        };
+        // A usage line, each subcommand, and HELP itself last
+        assert!(lines.len() > 3, "{} HELP: {:?}", command, lines);
+        assert!(
+            lines
+                .iter()
+                .all(|line| matches!(line, RespValue::SimpleString(_))),
+            "{} HELP: {:?}",
+            command,
+            lines
+        );
+        assert_eq!(
+            lines[0],
+            RespValue::simple_string(format!(
+                "{} <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+                command
+            ))
+        );
+        assert_eq!(
+            lines[lines.len() - 2],
+            RespValue::simple_string("HELP"),
+            "{} HELP",
+            command
+        );
+
+        match run(command, &["NoSuchThing"]) {
+            Err(AikvError::InvalidArgument(e)) => assert!(
+                e.contains(&format!(
+                    "unknown subcommand 'NoSuchThing'. Try {} HELP.",
+                    command
+                )) || (command == "OBJECT" && e.contains("Try OBJECT HELP.")),
+                "{}",
+                e
+            ),
+            other => panic!("{}: expected an error, got {:?}", command, other),
+        }
+    }
+}
+
+#[test]
+fn test_command_table_arity() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    // COMMAND COUNT reports every registered command
+    let result = executor
+        .execute("COMMAND", &[Bytes::from("COUNT")], &mut client)
+        .unwrap();
+    assert_eq!(
+        result,
+        RespValue::integer(aikv::command::server::command_table().len() as i64)
+    );
+
+    // COMMAND INFO GET reports an arity of 2 (GET key)
     let result = executor
         .execute(
-            "COMMAND",
-            &[Bytes::from("HELP")],
-            &mut current_db,
-            client_id,
+            "command",
+            &[Bytes::from("info"), Bytes::from("get")],
+            &mut client,
         )
         .unwrap();
-    assert!(matches!(result, RespValue::Array(Some(_))));
+    match result {
+        RespValue::Array(Some(arr)) => match &arr[0] {
+            RespValue::Array(Some(info)) => assert_eq!(info[1], RespValue::integer(2)),
+            other => panic!("Expected array for command info, got {:?}", other),
+        },
+        other => panic!("Expected array for COMMAND INFO, got {:?}", other),
+    }
+
+    // Arity is enforced from the table before dispatch
+    for (command, args) in [
+        ("GET", vec![]),
+        ("GET", vec![Bytes::from("a"), Bytes::from("b")]),
+        ("incr", vec![]),
+        ("SETEX", vec![Bytes::from("key"), Bytes::from("10")]),
+        ("ZLEXCOUNT", vec![Bytes::from("key")]),
+    ] {
+        let result = executor.execute(command, &args, &mut client);
+        assert!(
+            matches!(result, Err(AikvError::WrongArgCount(_))),
+            "{} {:?}: {:?}",
+            command,
+            args,
+            result
+        );
+    }
+}
+
+#[test]
+fn test_command_getkeys() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+    let mut getkeys = |command: &[&str]| {
+        let mut args = vec![Bytes::from("GETKEYS")];
+        args.extend(command.iter().map(|arg| Bytes::from(arg.to_string())));
+        executor.execute("COMMAND", &args, &mut client)
+    };
+    let keys = |keys: &[&str]| {
+        RespValue::array(
+            keys.iter()
+                .map(|key| RespValue::bulk_string(*key))
+                .collect(),
+        )
+    };
+
+    assert_eq!(
+        getkeys(&["MSET", "a", "1", "b", "2"]).unwrap(),
+        keys(&["a", "b"])
+    );
+    assert_eq!(
+        getkeys(&["EVAL", "return 1", "2", "k1", "k2", "arg"]).unwrap(),
+        keys(&["k1", "k2"])
+    );
+    assert_eq!(
+        getkeys(&["ZADD", "zset", "NX", "1", "m"]).unwrap(),
+        keys(&["zset"])
+    );
+    assert_eq!(
+        getkeys(&["SORT", "list", "BY", "w_*", "GET", "o_*", "STORE", "dest"]).unwrap(),
+        keys(&["list", "dest"])
+    );
+    assert_eq!(
+        getkeys(&["MIGRATE", "host", "6379", "", "0", "5000", "KEYS", "k1", "k2"]).unwrap(),
+        keys(&["k1", "k2"])
+    );
+
+    for (command, error) in [
+        (vec!["PING"], "has no key arguments"),
+        (vec!["EVAL", "return 1", "0"], "Invalid arguments"),
+        (vec!["GET"], "Invalid number of arguments"),
+    ] {
+        let err = getkeys(&command).unwrap_err();
+        assert!(err.to_string().contains(error), "{:?}: {}", command, err);
+    }
+    assert!(getkeys(&["NOSUCHCOMMAND", "key"]).is_err());
+}
+
+#[test]
+fn test_command_docs() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+    let count = match executor
+        .execute("COMMAND", &[Bytes::from("COUNT")], &mut client)
+        .unwrap()
+    {
+        RespValue::Integer(count) => count as usize,
+        other => panic!("Expected integer for COMMAND COUNT, got {:?}", other),
+    };
+    let mut docs = |names: &[&str]| {
+        let mut args = vec![Bytes::from("DOCS")];
+        args.extend(names.iter().map(|name| Bytes::from(name.to_string())));
+        match executor.execute("COMMAND", &args, &mut client).unwrap() {
+            RespValue::Map(docs) => docs,
+            other => panic!("Expected map for COMMAND DOCS, got {:?}", other),
+        }
+    };
+    let field = |fields: &[(RespValue, RespValue)], name: &str| {
+        fields
+            .iter()
+            .find(|(field, _)| *field == RespValue::bulk_string(name.to_string()))
+            .map(|(_, value)| value.clone())
+    };
+
+    let get = docs(&["get"]);
+    assert_eq!(get.len(), 1);
+    assert_eq!(get[0].0, RespValue::bulk_string("get"));
+    let RespValue::Map(get) = &get[0].1 else {
+        panic!("Expected map for the docs of GET");
+    };
+    assert!(matches!(
+        field(get, "summary"),
+        Some(RespValue::BulkString(Some(_)))
+    ));
+    assert_eq!(field(get, "group"), Some(RespValue::bulk_string("string")));
+    let Some(RespValue::Array(Some(arguments))) = field(get, "arguments") else {
+        panic!("Expected an arguments array for GET");
+    };
+    assert_eq!(arguments.len(), 1);
+    let RespValue::Map(key) = &arguments[0] else {
+        panic!("Expected map for the key argument");
+    };
+    assert_eq!(field(key, "name"), Some(RespValue::bulk_string("key")));
+    assert_eq!(field(key, "type"), Some(RespValue::bulk_string("key")));
+    assert_eq!(field(key, "key_spec_index"), Some(RespValue::integer(0)));
+
+    // Unknown commands are left out, and no names means every command
+    assert_eq!(docs(&["get", "nosuchcommand", "set"]).len(), 2);
+    assert_eq!(docs(&[]).len(), count);
 }
 
 #[test]
 fn test_save_lastsave_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Test LASTSAVE - should return a timestamp
-    let result = executor
-        .execute("LASTSAVE", &[], &mut current_db, client_id)
-        .unwrap();
+    let result = executor.execute("LASTSAVE", &[], &mut client).unwrap();
     if let RespValue::Integer(timestamp) = result {
         // Should be a reasonable Unix timestamp
         assert!(timestamp > 0);
@@ -1287,24 +1960,18 @@ fn test_save_lastsave_commands() {
     }
 
     // Test SAVE
-    let result = executor
-        .execute("SAVE", &[], &mut current_db, client_id)
-        .unwrap();
+    let result = executor.execute("SAVE", &[], &mut client).unwrap();
     assert_eq!(result, RespValue::ok());
 
     // Test BGSAVE
-    let result = executor
-        .execute("BGSAVE", &[], &mut current_db, client_id)
-        .unwrap();
+    let result = executor.execute("BGSAVE", &[], &mut client).unwrap();
     assert_eq!(
         result,
         RespValue::simple_string("Background saving started")
     );
 
     // Test LASTSAVE after save - should have updated timestamp
-    let result = executor
-        .execute("LASTSAVE", &[], &mut current_db, client_id)
-        .unwrap();
+    let result = executor.execute("LASTSAVE", &[], &mut client).unwrap();
     if let RespValue::Integer(timestamp) = result {
         assert!(timestamp > 0);
     } else {
@@ -1312,21 +1979,116 @@ fn test_save_lastsave_commands() {
     }
 }
 
+#[test]
+fn test_wait_command_standalone() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    // No replicas in standalone mode, so WAIT returns 0 without blocking
+    let result = executor
+        .execute("WAIT", &[Bytes::from("1"), Bytes::from("0")], &mut client)
+        .unwrap();
+    assert_eq!(result, RespValue::integer(0));
+
+    // Invalid arguments
+    assert!(executor
+        .execute("WAIT", &[Bytes::from("1")], &mut client)
+        .is_err());
+    assert!(executor
+        .execute("WAIT", &[Bytes::from("1"), Bytes::from("-1")], &mut client,)
+        .is_err());
+    assert!(executor
+        .execute("WAIT", &[Bytes::from("one"), Bytes::from("0")], &mut client,)
+        .is_err());
+}
+
+#[test]
+fn test_hello_protocol_negotiation() {
+    use aikv::protocol::ProtocolVersion;
+
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let server = executor.server_commands();
+    server
+        .register_client(7, "127.0.0.1:5000".to_string())
+        .unwrap();
+    let mut client = ClientContext::new(7);
+
+    // HELLO without arguments keeps the default RESP2 and replies with a flat array
+    let reply = server.hello(&[], &mut client).unwrap();
+    assert_eq!(client.protocol, ProtocolVersion::Resp2);
+    let encoded = reply.serialize_for(client.protocol);
+    assert!(encoded.starts_with(b"*14\r\n"));
+    assert!(encoded.windows(7).any(|w| w == b"proto\r\n"));
+
+    // HELLO 3 switches to RESP3 and replies with a map
+    let reply = server.hello(&[Bytes::from("3")], &mut client).unwrap();
+    assert_eq!(client.protocol, ProtocolVersion::Resp3);
+    match &reply {
+        RespValue::Map(pairs) => {
+            assert!(pairs.contains(&(RespValue::bulk_string("proto"), RespValue::integer(3))));
+            assert!(pairs.contains(&(RespValue::bulk_string("id"), RespValue::integer(7))));
+        }
+        other => panic!("Expected map reply, got {:?}", other),
+    }
+    assert!(reply.serialize_for(client.protocol).starts_with(b"%7\r\n"));
+
+    // HELLO without arguments keeps whatever was negotiated
+    server.hello(&[], &mut client).unwrap();
+    assert_eq!(client.protocol, ProtocolVersion::Resp3);
+
+    // AUTH with the default user and SETNAME
+    server
+        .hello(
+            &[
+                Bytes::from("2"),
+                Bytes::from("AUTH"),
+                Bytes::from("default"),
+                Bytes::from("secret"),
+                Bytes::from("SETNAME"),
+                Bytes::from("myclient"),
+            ],
+            &mut client,
+        )
+        .unwrap();
+    assert_eq!(client.protocol, ProtocolVersion::Resp2);
+    assert_eq!(client.name.as_deref(), Some("myclient"));
+    assert_eq!(
+        server.client_getname(&[], &client).unwrap(),
+        RespValue::bulk_string("myclient")
+    );
+
+    // Errors leave the protocol as it was
+    let err = server.hello(&[Bytes::from("4")], &mut client).unwrap_err();
+    assert!(err.to_string().starts_with("NOPROTO"));
+    let err = server
+        .hello(
+            &[
+                Bytes::from("3"),
+                Bytes::from("AUTH"),
+                Bytes::from("alice"),
+                Bytes::from("secret"),
+            ],
+            &mut client,
+        )
+        .unwrap_err();
+    assert!(err.to_string().starts_with("WRONGPASS"));
+    assert!(server
+        .hello(&[Bytes::from("3"), Bytes::from("SETNAME")], &mut client)
+        .is_err());
+    assert_eq!(client.protocol, ProtocolVersion::Resp2);
+}
+
 #[test]
 fn test_config_rewrite_command() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Test CONFIG REWRITE - should return OK (stub implementation)
     let result = executor
-        .execute(
-            "CONFIG",
-            &[Bytes::from("REWRITE")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("CONFIG", &[Bytes::from("REWRITE")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::ok());
 }
@@ -1335,17 +2097,11 @@ fn test_config_rewrite_command() {
 fn test_shutdown_command() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Test SHUTDOWN with ABORT (should not actually shutdown)
     let result = executor
-        .execute(
-            "SHUTDOWN",
-            &[Bytes::from("ABORT")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("SHUTDOWN", &[Bytes::from("ABORT")], &mut client)
         .unwrap();
     assert_eq!(result, RespValue::ok());
 
@@ -1353,18 +2109,140 @@ fn test_shutdown_command() {
     assert!(!executor.server_commands().is_shutdown_requested());
 
     // Test SHUTDOWN with invalid option
-    let result = executor.execute(
-        "SHUTDOWN",
-        &[Bytes::from("INVALID")],
-        &mut current_db,
-        client_id,
-    );
+    let result = executor.execute("SHUTDOWN", &[Bytes::from("INVALID")], &mut client);
     assert!(result.is_err());
 
     // Test basic SHUTDOWN (returns error because server is shutting down)
-    let result = executor.execute("SHUTDOWN", &[], &mut current_db, client_id);
+    let result = executor.execute("SHUTDOWN", &[], &mut client);
     assert!(result.is_err());
 
     // Verify shutdown was requested
     assert!(executor.server_commands().is_shutdown_requested());
 }
+
+#[test]
+fn test_time_command() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let parse = |reply: RespValue| -> (u64, u64) {
+        let RespValue::Array(Some(arr)) = &reply else {
+            panic!("expected array, got {:?}", reply);
+        };
+        assert_eq!(arr.len(), 2);
+        let number = |value: &RespValue| match value {
+            RespValue::BulkString(Some(b)) => String::from_utf8_lossy(b).parse::<u64>().unwrap(),
+            other => panic!("expected bulk string, got {:?}", other),
+        };
+        (number(&arr[0]), number(&arr[1]))
+    };
+
+    // Unix seconds, then the microseconds within that second
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let (seconds, micros) = parse(executor.execute("TIME", &[], &mut client).unwrap());
+    assert!(seconds.abs_diff(now) <= 1, "{} vs {}", seconds, now);
+    assert!((0..1_000_000).contains(&micros));
+
+    // Scripts see the same reply
+    let (script_seconds, script_micros) = parse(
+        executor
+            .execute(
+                "EVAL",
+                &[Bytes::from("return redis.call('TIME')"), Bytes::from("0")],
+                &mut client,
+            )
+            .unwrap(),
+    );
+    assert!(script_seconds.abs_diff(now) <= 1);
+    assert!((0..1_000_000).contains(&script_micros));
+
+    assert!(executor
+        .execute("TIME", &[Bytes::from("extra")], &mut client)
+        .is_err());
+}
+
+#[test]
+fn test_memory_usage_and_stats() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+    let usage = |reply: RespValue| match reply {
+        RespValue::Integer(bytes) => bytes,
+        other => panic!("expected integer, got {:?}", other),
+    };
+
+    run("SET", &["short", "hi"]).unwrap();
+    let items: Vec<String> = (0..1000).map(|i| format!("item:{}", i)).collect();
+    let mut rpush = vec!["list"];
+    rpush.extend(items.iter().map(String::as_str));
+    run("RPUSH", &rpush).unwrap();
+
+    let short = usage(run("MEMORY", &["USAGE", "short"]).unwrap());
+    let list = usage(run("MEMORY", &["USAGE", "list", "SAMPLES", "0"]).unwrap());
+    assert!(short > 0);
+    assert!(list > 100 * short, "list {} vs string {}", list, short);
+
+    assert_eq!(
+        run("MEMORY", &["USAGE", "missing"]).unwrap(),
+        RespValue::null_bulk_string()
+    );
+    assert!(run("MEMORY", &["USAGE", "short", "SAMPLES"]).is_err());
+    assert!(run("MEMORY", &["USAGE", "short", "SAMPLES", "x"]).is_err());
+
+    let RespValue::Map(stats) = run("MEMORY", &["STATS"]).unwrap() else {
+        panic!("expected a map");
+    };
+    assert!(stats.contains(&(RespValue::bulk_string("keys.count"), RespValue::integer(2))));
+    assert!(matches!(
+        run("MEMORY", &["DOCTOR"]).unwrap(),
+        RespValue::BulkString(Some(_))
+    ));
+    assert!(run("MEMORY", &["NOSUCH"]).is_err());
+}
+
+#[test]
+fn test_database_indexes_follow_the_configured_count() {
+    let storage = StorageEngine::new_memory(4);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+    let out_of_range = |reply: Result<RespValue, AikvError>| matches!(reply, Err(e) if e.to_string().contains("DB index is out of range"));
+
+    assert_eq!(run("SELECT", &["3"]).unwrap(), RespValue::ok());
+    assert!(out_of_range(run("SELECT", &["4"])));
+    assert!(out_of_range(run("SELECT", &["16"])));
+
+    run("SET", &["k", "v"]).unwrap();
+    assert!(out_of_range(run("MOVE", &["k", "4"])));
+    assert!(out_of_range(run("COPY", &["k", "k2", "DB", "4"])));
+    assert!(out_of_range(run("SWAPDB", &["0", "4"])));
+    assert_eq!(run("MOVE", &["k", "0"]).unwrap(), RespValue::integer(1));
+    assert_eq!(run("SWAPDB", &["0", "3"]).unwrap(), RespValue::ok());
+    assert_eq!(run("GET", &["k"]).unwrap(), RespValue::bulk_string("v"));
+
+    // FLUSHDB empties the selected database only
+    assert_eq!(run("FLUSHDB", &[]).unwrap(), RespValue::ok());
+    assert_eq!(run("DBSIZE", &[]).unwrap(), RespValue::integer(0));
+
+    // A single database is enough to run
+    let executor = CommandExecutor::new(StorageEngine::new_memory(1));
+    let mut client = ClientContext::new(1);
+    let select = |index: &str, client: &mut ClientContext| {
+        executor.execute("SELECT", &[Bytes::from(index.to_string())], client)
+    };
+    assert_eq!(select("0", &mut client).unwrap(), RespValue::ok());
+    assert!(out_of_range(select("1", &mut client)));
+}
@@ -0,0 +1,153 @@
+//! Tests for the blocking pops BLMPOP and BZMPOP
+
+mod common;
+
+use aikv::command::{ClientContext, CommandExecutor};
+use aikv::protocol::RespValue;
+use aikv::StorageEngine;
+use bytes::Bytes;
+use common::{start_server, TestClient};
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+async fn next_reply(client: &mut TestClient) -> RespValue {
+    timeout(Duration::from_secs(2), client.read_reply())
+        .await
+        .expect("Still blocked")
+        .expect("Connection closed")
+}
+
+fn popped(key: &str, elements: &[&str]) -> RespValue {
+    RespValue::array(vec![
+        RespValue::bulk_string(key.to_string()),
+        RespValue::array(
+            elements
+                .iter()
+                .map(|e| RespValue::bulk_string(e.to_string()))
+                .collect(),
+        ),
+    ])
+}
+
+#[tokio::test]
+async fn test_blmpop_waits_for_a_push_to_any_of_its_keys() {
+    let addr = start_server(|_| {}).await;
+    let mut blocked = TestClient::connect(&addr).await;
+    let mut writer = TestClient::connect(&addr).await;
+
+    // Data already there is popped right away, like LMPOP
+    writer.command(&["RPUSH", "first", "x"]).await;
+    assert_eq!(
+        blocked
+            .command(&["BLMPOP", "0", "2", "first", "second", "LEFT"])
+            .await,
+        popped("first", &["x"])
+    );
+
+    // A command pipelined behind the blocked one waits for it
+    blocked
+        .send(&["BLMPOP", "0", "2", "first", "second", "LEFT", "COUNT", "2"])
+        .await;
+    blocked.send(&["PING"]).await;
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        writer.command(&["RPUSH", "second", "a", "b", "c"]).await,
+        RespValue::integer(3)
+    );
+    assert_eq!(
+        next_reply(&mut blocked).await,
+        popped("second", &["a", "b"])
+    );
+    assert_eq!(
+        next_reply(&mut blocked).await,
+        RespValue::simple_string("PONG")
+    );
+    assert_eq!(
+        writer.command(&["LRANGE", "second", "0", "-1"]).await,
+        RespValue::array(vec![RespValue::bulk_string("c")])
+    );
+}
+
+#[tokio::test]
+async fn test_bzmpop_waits_for_a_write_then_times_out() {
+    let addr = start_server(|_| {}).await;
+    let mut blocked = TestClient::connect(&addr).await;
+    let mut writer = TestClient::connect(&addr).await;
+
+    blocked.send(&["BZMPOP", "5", "1", "zset", "MAX"]).await;
+    sleep(Duration::from_millis(100)).await;
+    writer
+        .command(&["ZADD", "zset", "1", "one", "2", "two"])
+        .await;
+    assert_eq!(
+        next_reply(&mut blocked).await,
+        RespValue::array(vec![
+            RespValue::bulk_string("zset"),
+            RespValue::array(vec![RespValue::array(vec![
+                RespValue::bulk_string("two"),
+                RespValue::bulk_string("2"),
+            ])]),
+        ])
+    );
+
+    // A write to another key doesn't end the wait, the timeout does
+    blocked.send(&["BZMPOP", "0.2", "1", "empty", "MIN"]).await;
+    writer.command(&["ZADD", "other", "1", "one"]).await;
+    assert_eq!(next_reply(&mut blocked).await, RespValue::null_array());
+
+    for args in [
+        &["BZMPOP", "-1", "1", "zset", "MIN"][..],
+        &["BZMPOP", "soon", "1", "zset", "MIN"],
+        &["BLMPOP", "0", "0", "list", "LEFT"],
+    ] {
+        assert!(
+            matches!(blocked.command(args).await, RespValue::Error(_)),
+            "{:?}",
+            args
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_blmpop_gives_up_when_the_client_disconnects() {
+    let addr = start_server(|_| {}).await;
+    let mut blocked = TestClient::connect(&addr).await;
+    let mut writer = TestClient::connect(&addr).await;
+
+    blocked.send(&["BLMPOP", "0", "1", "list", "LEFT"]).await;
+    sleep(Duration::from_millis(100)).await;
+    drop(blocked);
+    sleep(Duration::from_millis(100)).await;
+
+    // Nobody is left to receive the element, so it stays
+    writer.command(&["RPUSH", "list", "a"]).await;
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        writer.command(&["LLEN", "list"]).await,
+        RespValue::integer(1)
+    );
+}
+
+#[test]
+fn test_blocking_pops_dont_block_outside_a_connection() {
+    // As in scripts, where there is no connection to block
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let mut client = ClientContext::new(1);
+    let args =
+        |args: &[&str]| -> Vec<Bytes> { args.iter().map(|a| Bytes::from(a.to_string())).collect() };
+    assert_eq!(
+        executor
+            .execute("BLMPOP", &args(&["0", "1", "list", "LEFT"]), &mut client)
+            .unwrap(),
+        RespValue::null_array()
+    );
+    executor
+        .execute("RPUSH", &args(&["list", "a", "b"]), &mut client)
+        .unwrap();
+    assert_eq!(
+        executor
+            .execute("BLMPOP", &args(&["0", "1", "list", "RIGHT"]), &mut client)
+            .unwrap(),
+        popped("list", &["b"])
+    );
+}
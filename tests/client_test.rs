@@ -0,0 +1,67 @@
+//! Tests for CLIENT subcommands that span connections (ID, LIST, KILL)
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+use std::time::Duration;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_client_kill_by_id_closes_connection() {
+    let addr = start_server(|_| {}).await;
+    let mut victim = TestClient::connect(&addr).await;
+    let mut killer = TestClient::connect(&addr).await;
+
+    let victim_id = match victim.command(&["CLIENT", "ID"]).await {
+        RespValue::Integer(id) => id,
+        other => panic!("expected integer id, got {:?}", other),
+    };
+    assert_eq!(
+        victim.command(&["CLIENT", "SETNAME", "victim"]).await,
+        RespValue::ok()
+    );
+
+    // Names set on one connection are visible from another
+    match killer.command(&["CLIENT", "LIST"]).await {
+        RespValue::BulkString(Some(list)) => {
+            let list = String::from_utf8_lossy(&list).to_string();
+            assert!(
+                list.contains(&format!("id={} ", victim_id)) && list.contains("name=victim"),
+                "unexpected CLIENT LIST: {}",
+                list
+            );
+        }
+        other => panic!("expected bulk string, got {:?}", other),
+    }
+
+    let id = victim_id.to_string();
+    assert_eq!(
+        killer.command(&["CLIENT", "KILL", "ID", &id]).await,
+        RespValue::integer(1)
+    );
+
+    // The killed connection is closed by the server
+    let reply = timeout(Duration::from_secs(2), victim.read_reply())
+        .await
+        .expect("killed connection was not closed");
+    assert!(reply.is_none(), "got {:?}", reply);
+
+    // Killing it again finds nothing, and the killer skips itself by default
+    assert_eq!(
+        killer.command(&["CLIENT", "KILL", "ID", &id]).await,
+        RespValue::integer(0)
+    );
+    let own_id = match killer.command(&["CLIENT", "ID"]).await {
+        RespValue::Integer(id) => id.to_string(),
+        other => panic!("expected integer id, got {:?}", other),
+    };
+    assert_eq!(
+        killer.command(&["CLIENT", "KILL", "ID", &own_id]).await,
+        RespValue::integer(0)
+    );
+    assert_eq!(
+        killer.command(&["PING"]).await,
+        RespValue::simple_string("PONG")
+    );
+}
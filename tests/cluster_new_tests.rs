@@ -526,6 +526,101 @@ mod cluster_tests {
         Ok(())
     }
 
+    /// Test commands for another node's slot are redirected to the owner
+    #[tokio::test]
+    async fn test_executor_redirects_to_slot_owner() -> Result<()> {
+        use aikv::command::{ClientContext, CommandExecutor};
+        use aikv::error::AikvError;
+        use aikv::protocol::RespValue;
+        use aikv::storage::StorageEngine;
+        use bytes::Bytes;
+
+        let _ = tokio::fs::remove_dir_all("/tmp/test_executor_moved").await;
+
+        let raft_config = RaftConfig::default();
+        let mut node = MultiRaftNode::new(1, "/tmp/test_executor_moved", raft_config.clone())
+            .await
+            .map_err(|e| AikvError::Internal(e.to_string()))?;
+        node.init_meta_raft(raft_config.clone())
+            .await
+            .map_err(|e| AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(1, "127.0.0.1:50121".to_string())])
+            .await
+            .map_err(|e| AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+        let meta_raft = node.meta_raft().unwrap();
+        sleep(Duration::from_millis(500)).await;
+
+        // Node 1 serves slots 0-8191, node 2 serves 8192-16383
+        for (id, addr) in [(1, "127.0.0.1:6379"), (2, "127.0.0.1:6380")] {
+            meta_raft
+                .add_node(id, addr.to_string())
+                .await
+                .map_err(|e| AikvError::Internal(e.to_string()))?;
+        }
+        for (group, (start, end)) in [(1, (0, 8192)), (2, (8192, 16384))] {
+            meta_raft
+                .create_group(group, vec![group])
+                .await
+                .map_err(|e| AikvError::Internal(e.to_string()))?;
+            meta_raft
+                .update_group_leader(group, group)
+                .await
+                .map_err(|e| AikvError::Internal(e.to_string()))?;
+            meta_raft
+                .update_slots(start, end, group)
+                .await
+                .map_err(|e| AikvError::Internal(e.to_string()))?;
+        }
+        sleep(Duration::from_millis(300)).await;
+
+        let router = Arc::new(Router::new(meta_raft.get_cluster_meta()));
+        let mut executor = CommandExecutor::new(StorageEngine::new_memory(16));
+        executor.set_cluster_commands(ClusterCommands::new(1, meta_raft.clone(), node, router));
+        let mut client = ClientContext::new(0);
+        let bytes = |args: &[&str]| -> Vec<Bytes> {
+            args.iter().map(|a| Bytes::from(a.to_string())).collect()
+        };
+
+        // "foo" hashes to slot 12182, owned by node 2
+        match executor.execute("GET", &bytes(&["foo"]), &mut client) {
+            Err(AikvError::Moved(slot, addr)) => {
+                assert_eq!(slot, 12182);
+                assert_eq!(addr, "127.0.0.1:6380");
+            }
+            other => panic!("expected MOVED, got {:?}", other),
+        }
+        match executor.execute("EVAL", &bytes(&["return 1", "1", "foo"]), &mut client) {
+            Err(AikvError::Moved(slot, _)) => assert_eq!(slot, 12182),
+            other => panic!("expected MOVED, got {:?}", other),
+        }
+
+        // "bar" hashes to slot 5061 and is served locally
+        assert_eq!(
+            executor.execute("SET", &bytes(&["bar", "1"]), &mut client)?,
+            RespValue::SimpleString("OK".to_string())
+        );
+
+        // SORT's STORE destination is routed along with the source
+        assert!(matches!(
+            executor.execute("SORT", &bytes(&["bar", "STORE", "foo"]), &mut client),
+            Err(AikvError::CrossSlot)
+        ));
+        match executor.execute(
+            "SORT",
+            &bytes(&["foo", "STORE", "{foo}:sorted"]),
+            &mut client,
+        ) {
+            Err(AikvError::Moved(slot, _)) => assert_eq!(slot, 12182),
+            other => panic!("expected MOVED, got {:?}", other),
+        }
+
+        let _ = tokio::fs::remove_dir_all("/tmp/test_executor_moved").await;
+
+        Ok(())
+    }
+
     /// Test check_key_slot calculates slot correctly and checks ownership
     #[tokio::test]
     async fn test_check_key_slot() -> Result<()> {
@@ -742,4 +837,774 @@ mod cluster_tests {
 
         Ok(())
     }
+
+    /// Test WAIT against a single-member raft group: there are no replicas to
+    /// acknowledge, so it returns 0 once the timeout expires
+    #[tokio::test]
+    async fn test_wait_single_member_group() -> Result<()> {
+        let _ = tokio::fs::remove_dir_all("/tmp/test_wait").await;
+
+        let raft_config = RaftConfig::default();
+        let mut node = MultiRaftNode::new(1, "/tmp/test_wait", raft_config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.init_meta_raft(raft_config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(1, "127.0.0.1:50061".to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+        let meta_raft = node.meta_raft().unwrap();
+        let router = Arc::new(Router::new(meta_raft.get_cluster_meta()));
+        let cluster_commands = ClusterCommands::new(1, meta_raft.clone(), node.clone(), router);
+
+        meta_raft
+            .create_group(1, vec![1])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        meta_raft
+            .update_group_leader(1, 1)
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        sleep(Duration::from_millis(100)).await;
+
+        // Zero replicas requested is satisfied immediately
+        let result = cluster_commands.wait(0, 0).await?;
+        assert_eq!(result, aikv::protocol::RespValue::Integer(0));
+
+        // Asking for a replica that doesn't exist blocks until the timeout
+        let start = std::time::Instant::now();
+        let result = cluster_commands.wait(1, 200).await?;
+        assert_eq!(result, aikv::protocol::RespValue::Integer(0));
+        assert!(start.elapsed() >= Duration::from_millis(200));
+
+        let _ = tokio::fs::remove_dir_all("/tmp/test_wait").await;
+
+        Ok(())
+    }
+
+    /// Test WAIT on a 3-node group counts the replicas that caught up, and
+    /// times out with the smaller count once one of them lags behind
+    #[tokio::test]
+    async fn test_wait_counts_replica_acks() -> Result<()> {
+        use aidb::cluster::raft_network::raft_rpc::raft_service_server::RaftServiceServer;
+        use aikv::cluster::raft_service::MultiRaftService;
+        use aikv::protocol::RespValue;
+        use std::collections::BTreeSet;
+
+        let internal = |e: &dyn std::fmt::Display| aikv::error::AikvError::Internal(e.to_string());
+        let addrs = [
+            (1, "127.0.0.1:50151"),
+            (2, "127.0.0.1:50152"),
+            (3, "127.0.0.1:50153"),
+        ];
+
+        // Three nodes, each serving Raft RPCs and knowing the others' addresses
+        let mut nodes = Vec::new();
+        for (node_id, addr) in addrs {
+            let dir = format!("/tmp/test_wait_replicas_node{}", node_id);
+            let _ = tokio::fs::remove_dir_all(&dir).await;
+            let config = RaftConfig::default();
+            let mut node = MultiRaftNode::new(node_id, &dir, config.clone())
+                .await
+                .map_err(|e| internal(&e))?;
+            node.init_meta_raft(config)
+                .await
+                .map_err(|e| internal(&e))?;
+            if node_id == 1 {
+                node.initialize_meta_cluster(vec![(1, addr.to_string())])
+                    .await
+                    .map_err(|e| internal(&e))?;
+            }
+            for (peer, peer_addr) in addrs {
+                node.add_node_address(peer, format!("http://{}", peer_addr));
+            }
+            let node = Arc::new(node);
+
+            let service = RaftServiceServer::new(MultiRaftService::new(node.clone()));
+            let bind_addr = addr.parse().unwrap();
+            tokio::spawn(async move {
+                let _ = tonic::transport::Server::builder()
+                    .add_service(service)
+                    .serve(bind_addr)
+                    .await;
+            });
+            nodes.push(node);
+        }
+        for node in &nodes {
+            node.create_raft_group(1, vec![1, 2, 3])
+                .await
+                .map_err(|e| internal(&e))?;
+        }
+
+        // Wait for the data group to elect a leader
+        let raft = |node_id: u64| nodes[node_id as usize - 1].get_raft_group(1).unwrap();
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+        let leader = loop {
+            if let Some(leader) = raft(1).metrics().borrow().current_leader {
+                break leader;
+            }
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "group 1 elected no leader"
+            );
+            sleep(Duration::from_millis(50)).await;
+        };
+
+        let meta_raft = nodes[0].meta_raft().unwrap().clone();
+        meta_raft
+            .create_group(1, vec![1, 2, 3])
+            .await
+            .map_err(|e| internal(&e))?;
+        meta_raft
+            .update_group_leader(1, leader)
+            .await
+            .map_err(|e| internal(&e))?;
+        sleep(Duration::from_millis(200)).await;
+
+        let router = Arc::new(Router::new(meta_raft.get_cluster_meta()));
+        let cluster_commands = ClusterCommands::new(
+            leader,
+            meta_raft.clone(),
+            nodes[leader as usize - 1].clone(),
+            router,
+        );
+
+        // Both replicas acknowledge the leader's log
+        let result = cluster_commands.wait(2, 5000).await?;
+        assert_eq!(result, RespValue::Integer(2));
+
+        // Stop one replica, then append to the log; a membership change is
+        // an entry the test can make without a client write
+        let replicas: Vec<u64> = (1..=3).filter(|&id| id != leader).collect();
+        let (follower, lagging) = (replicas[0], replicas[1]);
+        raft(lagging).shutdown().await.map_err(|e| internal(&e))?;
+        raft(leader)
+            .change_membership(BTreeSet::from([leader, follower]), true)
+            .await
+            .map_err(|e| internal(&e))?;
+
+        // Only the replica that is still up acknowledges the new entries
+        let result = cluster_commands.wait(1, 5000).await?;
+        assert_eq!(result, RespValue::Integer(1));
+        let start = std::time::Instant::now();
+        let result = cluster_commands.wait(2, 300).await?;
+        assert_eq!(result, RespValue::Integer(1));
+        assert!(start.elapsed() >= Duration::from_millis(300));
+
+        for (node_id, _) in addrs {
+            let _ =
+                tokio::fs::remove_dir_all(format!("/tmp/test_wait_replicas_node{}", node_id)).await;
+        }
+
+        Ok(())
+    }
+
+    /// Test CLUSTER SHARDS groups a master with its replicas
+    #[tokio::test]
+    async fn test_cluster_shards() -> Result<()> {
+        use aikv::protocol::RespValue;
+
+        let _ = tokio::fs::remove_dir_all("/tmp/test_shards").await;
+
+        let config = RaftConfig::default();
+        let mut node = MultiRaftNode::new(1, "/tmp/test_shards", config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.init_meta_raft(config.clone())
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(1, "127.0.0.1:50111".to_string())])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+        let meta = node.meta_raft().ok_or_else(|| {
+            aikv::error::AikvError::Internal("Meta raft not initialized".to_string())
+        })?;
+        sleep(Duration::from_millis(500)).await;
+
+        // Node 1 leads group 1, node 2 replicates it
+        for (id, addr) in [(1, "127.0.0.1:6379"), (2, "127.0.0.1:6380")] {
+            meta.add_node(id, addr.to_string())
+                .await
+                .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        }
+        meta.create_group(1, vec![1, 2])
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        meta.update_group_leader(1, 1)
+            .await
+            .map_err(|e| aikv::error::AikvError::Internal(e.to_string()))?;
+        sleep(Duration::from_millis(300)).await;
+
+        let router = Arc::new(Router::new(meta.get_cluster_meta()));
+        let cmd = ClusterCommands::new(1, meta.clone(), node, router);
+        let slots: Vec<u16> = (0..=100).chain(200..=300).collect();
+        cmd.cluster_addslots(slots).await?;
+        sleep(Duration::from_millis(300)).await;
+
+        let node_entry = |id: u64, port: i64, role: &'static str| {
+            RespValue::map(vec![
+                (
+                    RespValue::bulk_string("id"),
+                    RespValue::bulk_string(format!("{:040x}", id)),
+                ),
+                (RespValue::bulk_string("port"), RespValue::Integer(port)),
+                (
+                    RespValue::bulk_string("ip"),
+                    RespValue::bulk_string("127.0.0.1"),
+                ),
+                (
+                    RespValue::bulk_string("endpoint"),
+                    RespValue::bulk_string("127.0.0.1"),
+                ),
+                (RespValue::bulk_string("role"), RespValue::bulk_string(role)),
+                (
+                    RespValue::bulk_string("replication-offset"),
+                    RespValue::Integer(0),
+                ),
+                (
+                    RespValue::bulk_string("health"),
+                    RespValue::bulk_string("online"),
+                ),
+            ])
+        };
+        let expected = RespValue::array(vec![RespValue::map(vec![
+            (
+                RespValue::bulk_string("slots"),
+                RespValue::array(vec![
+                    RespValue::Integer(0),
+                    RespValue::Integer(100),
+                    RespValue::Integer(200),
+                    RespValue::Integer(300),
+                ]),
+            ),
+            (
+                RespValue::bulk_string("nodes"),
+                RespValue::array(vec![
+                    node_entry(1, 6379, "master"),
+                    node_entry(2, 6380, "replica"),
+                ]),
+            ),
+        ])]);
+        assert_eq!(cmd.cluster_shards()?, expected);
+
+        let _ = tokio::fs::remove_dir_all("/tmp/test_shards").await;
+
+        Ok(())
+    }
+
+    /// Test CLUSTER KEYSLOT against slots computed by Redis
+    #[test]
+    fn test_keyslot_matches_redis() {
+        use aikv::cluster::key_to_slot_with_hash_tag;
+
+        assert_eq!(key_to_slot_with_hash_tag(b"123456789"), 12739);
+        assert_eq!(key_to_slot_with_hash_tag(b"somekey"), 11058);
+        assert_eq!(key_to_slot_with_hash_tag(b"foo{hash_tag}"), 2515);
+        assert_eq!(
+            key_to_slot_with_hash_tag(b"{user}:1"),
+            key_to_slot_with_hash_tag(b"{user}:2")
+        );
+        assert_eq!(
+            key_to_slot_with_hash_tag(b"{user}:1"),
+            key_to_slot_with_hash_tag(b"user")
+        );
+
+        // An empty tag is ignored and the whole key is hashed; only the
+        // first '{' and the first '}' after it count
+        assert_ne!(
+            key_to_slot_with_hash_tag(b"{}:1"),
+            key_to_slot_with_hash_tag(b"{}:2")
+        );
+        assert_eq!(
+            key_to_slot_with_hash_tag(b"foo{}{bar}"),
+            aikv::cluster::Router::key_to_slot(b"foo{}{bar}")
+        );
+        assert_eq!(
+            key_to_slot_with_hash_tag(b"foo{{bar}}zap"),
+            key_to_slot_with_hash_tag(b"{bar")
+        );
+        assert_eq!(
+            key_to_slot_with_hash_tag(b"foo{bar}{zap}"),
+            key_to_slot_with_hash_tag(b"bar")
+        );
+    }
+
+    /// Test CLUSTER COUNTKEYSINSLOT/GETKEYSINSLOT scan the local keyspace
+    #[tokio::test]
+    async fn test_keys_in_slot() -> Result<()> {
+        use aikv::command::{ClientContext, CommandExecutor};
+        use aikv::protocol::RespValue;
+        use aikv::storage::StorageEngine;
+        use bytes::Bytes;
+
+        let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+        let mut client = ClientContext::new(0);
+        let bytes = |args: &[&str]| -> Vec<Bytes> {
+            args.iter().map(|a| Bytes::from(a.to_string())).collect()
+        };
+        for key in ["{user}:1", "{user}:2", "{user}:3", "other"] {
+            executor.execute("SET", &bytes(&[key, "v"]), &mut client)?;
+        }
+
+        let slot = aikv::cluster::key_to_slot_with_hash_tag(b"user").to_string();
+        assert_eq!(
+            executor.execute("CLUSTER", &bytes(&["COUNTKEYSINSLOT", &slot]), &mut client)?,
+            RespValue::Integer(3)
+        );
+        assert_eq!(
+            executor.execute(
+                "CLUSTER",
+                &bytes(&["GETKEYSINSLOT", &slot, "2"]),
+                &mut client
+            )?,
+            RespValue::Array(Some(vec![
+                RespValue::BulkString(Some(Bytes::from("{user}:1"))),
+                RespValue::BulkString(Some(Bytes::from("{user}:2"))),
+            ]))
+        );
+        assert!(executor
+            .execute(
+                "CLUSTER",
+                &bytes(&["COUNTKEYSINSLOT", "16384"]),
+                &mut client
+            )
+            .is_err());
+
+        Ok(())
+    }
+
+    /// Test CLUSTER SETSLOT walking a slot from node 1 to node 2: MIGRATING on
+    /// the source, IMPORTING on the destination, then NODE to hand it over
+    #[tokio::test]
+    async fn test_cluster_setslot_migrates_a_slot() -> Result<()> {
+        use aikv::cluster::SetSlotAction;
+        use aikv::command::{ClientContext, CommandExecutor};
+        use aikv::error::AikvError;
+        use aikv::protocol::RespValue;
+        use aikv::storage::StorageEngine;
+        use bytes::Bytes;
+
+        let _ = tokio::fs::remove_dir_all("/tmp/test_cluster_setslot").await;
+
+        let raft_config = RaftConfig::default();
+        let mut node = MultiRaftNode::new(1, "/tmp/test_cluster_setslot", raft_config.clone())
+            .await
+            .map_err(|e| AikvError::Internal(e.to_string()))?;
+        node.init_meta_raft(raft_config.clone())
+            .await
+            .map_err(|e| AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(1, "127.0.0.1:50131".to_string())])
+            .await
+            .map_err(|e| AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+        let meta_raft = node.meta_raft().unwrap();
+        sleep(Duration::from_millis(500)).await;
+
+        // Node 1 serves slots 0-8191, node 2 serves 8192-16383
+        for (id, addr) in [(1, "127.0.0.1:6379"), (2, "127.0.0.1:6380")] {
+            meta_raft
+                .add_node(id, addr.to_string())
+                .await
+                .map_err(|e| AikvError::Internal(e.to_string()))?;
+        }
+        for (group, (start, end)) in [(1, (0, 8192)), (2, (8192, 16384))] {
+            meta_raft
+                .create_group(group, vec![group])
+                .await
+                .map_err(|e| AikvError::Internal(e.to_string()))?;
+            meta_raft
+                .update_group_leader(group, group)
+                .await
+                .map_err(|e| AikvError::Internal(e.to_string()))?;
+            meta_raft
+                .update_slots(start, end, group)
+                .await
+                .map_err(|e| AikvError::Internal(e.to_string()))?;
+        }
+        sleep(Duration::from_millis(300)).await;
+
+        // One executor per node, each with its own keyspace
+        let executor_for = |node_id| {
+            let router = Arc::new(Router::new(meta_raft.get_cluster_meta()));
+            let mut executor = CommandExecutor::new(StorageEngine::new_memory(16));
+            executor.set_cluster_commands(ClusterCommands::new(
+                node_id,
+                meta_raft.clone(),
+                node.clone(),
+                router,
+            ));
+            executor
+        };
+        let source = executor_for(1);
+        let dest = executor_for(2);
+        let mut client = ClientContext::new(0);
+        let bytes = |args: &[&str]| -> Vec<Bytes> {
+            args.iter().map(|a| Bytes::from(a.to_string())).collect()
+        };
+        let nodes =
+            |executor: &CommandExecutor| match executor.cluster_commands().unwrap().cluster_nodes()
+            {
+                Ok(RespValue::BulkString(Some(b))) => String::from_utf8_lossy(&b).to_string(),
+                other => panic!("expected bulk string, got {:?}", other),
+            };
+
+        // "bar" hashes to slot 5061, owned by node 1, and so does "{bar}:new"
+        let slot = 5061;
+        source.execute("SET", &bytes(&["bar", "1"]), &mut client)?;
+
+        assert_eq!(
+            SetSlotAction::parse(&bytes(&["migrating", "2"]))?,
+            SetSlotAction::Migrating(2)
+        );
+        assert!(SetSlotAction::parse(&bytes(&["STABLE", "2"])).is_err());
+        assert!(SetSlotAction::parse(&bytes(&["NODE"])).is_err());
+
+        // A node only migrates slots it owns, and only imports those it doesn't
+        let source_cluster = source.cluster_commands().unwrap();
+        let dest_cluster = dest.cluster_commands().unwrap();
+        assert!(source_cluster
+            .cluster_setslot(slot, SetSlotAction::Importing(2))
+            .await
+            .is_err());
+        assert!(dest_cluster
+            .cluster_setslot(slot, SetSlotAction::Migrating(1))
+            .await
+            .is_err());
+        assert!(source_cluster
+            .cluster_setslot(slot, SetSlotAction::Migrating(99))
+            .await
+            .is_err());
+
+        source_cluster
+            .cluster_setslot(slot, SetSlotAction::Migrating(2))
+            .await?;
+        dest_cluster
+            .cluster_setslot(slot, SetSlotAction::Importing(1))
+            .await?;
+        assert!(nodes(&source).contains(&format!("[{}->-{:040x}]", slot, 2)));
+        assert!(nodes(&dest).contains(&format!("[{}-<-{:040x}]", slot, 1)));
+
+        // The source still serves keys it has, and sends the rest to the target
+        assert_eq!(
+            source.execute("GET", &bytes(&["bar"]), &mut client)?,
+            RespValue::BulkString(Some(Bytes::from("1")))
+        );
+        match source.execute("GET", &bytes(&["{bar}:new"]), &mut client) {
+            Err(AikvError::Ask(ask_slot, addr)) => {
+                assert_eq!(ask_slot, slot);
+                assert_eq!(addr, "127.0.0.1:6380");
+            }
+            other => panic!("expected ASK, got {:?}", other),
+        }
+
+        // The target only takes the slot's keys right after ASKING
+        assert!(matches!(
+            dest.execute("SET", &bytes(&["{bar}:new", "2"]), &mut client),
+            Err(AikvError::Moved(..))
+        ));
+        assert_eq!(
+            dest.execute("ASKING", &[], &mut client)?,
+            RespValue::SimpleString("OK".to_string())
+        );
+        assert_eq!(
+            dest.execute("SET", &bytes(&["{bar}:new", "2"]), &mut client)?,
+            RespValue::SimpleString("OK".to_string())
+        );
+        assert!(matches!(
+            dest.execute("GET", &bytes(&["{bar}:new"]), &mut client),
+            Err(AikvError::Moved(..))
+        ));
+
+        // Handing the slot over ends the migration on both sides
+        source_cluster
+            .cluster_setslot(slot, SetSlotAction::Node(2))
+            .await?;
+        dest_cluster
+            .cluster_setslot(slot, SetSlotAction::Stable)
+            .await?;
+        sleep(Duration::from_millis(300)).await;
+        assert!(!nodes(&source).contains('['));
+        assert!(!nodes(&dest).contains('['));
+        assert_eq!(
+            dest.execute("GET", &bytes(&["{bar}:new"]), &mut client)?,
+            RespValue::BulkString(Some(Bytes::from("2")))
+        );
+        assert!(matches!(
+            source.execute("GET", &bytes(&["bar"]), &mut client),
+            Err(AikvError::Moved(..))
+        ));
+
+        let _ = tokio::fs::remove_dir_all("/tmp/test_cluster_setslot").await;
+
+        Ok(())
+    }
+
+    /// Test READONLY letting a replica serve reads of the slots it replicates,
+    /// while writes are still redirected to the master
+    #[tokio::test]
+    async fn test_readonly_reads_from_replica() -> Result<()> {
+        use aikv::command::{ClientContext, CommandExecutor};
+        use aikv::error::AikvError;
+        use aikv::protocol::RespValue;
+        use aikv::storage::StorageEngine;
+        use bytes::Bytes;
+
+        let _ = tokio::fs::remove_dir_all("/tmp/test_readonly_replica").await;
+
+        let raft_config = RaftConfig::default();
+        let mut node = MultiRaftNode::new(1, "/tmp/test_readonly_replica", raft_config.clone())
+            .await
+            .map_err(|e| AikvError::Internal(e.to_string()))?;
+        node.init_meta_raft(raft_config.clone())
+            .await
+            .map_err(|e| AikvError::Internal(e.to_string()))?;
+        node.initialize_meta_cluster(vec![(1, "127.0.0.1:50141".to_string())])
+            .await
+            .map_err(|e| AikvError::Internal(e.to_string()))?;
+
+        let node = Arc::new(node);
+        let meta_raft = node.meta_raft().unwrap();
+        sleep(Duration::from_millis(500)).await;
+
+        // Node 1 masters every slot, node 2 replicates them, node 3 holds none
+        for (id, addr) in [
+            (1, "127.0.0.1:6379"),
+            (2, "127.0.0.1:6380"),
+            (3, "127.0.0.1:6381"),
+        ] {
+            meta_raft
+                .add_node(id, addr.to_string())
+                .await
+                .map_err(|e| AikvError::Internal(e.to_string()))?;
+        }
+        meta_raft
+            .create_group(1, vec![1, 2])
+            .await
+            .map_err(|e| AikvError::Internal(e.to_string()))?;
+        meta_raft
+            .update_group_leader(1, 1)
+            .await
+            .map_err(|e| AikvError::Internal(e.to_string()))?;
+        meta_raft
+            .update_slots(0, 16384, 1)
+            .await
+            .map_err(|e| AikvError::Internal(e.to_string()))?;
+        sleep(Duration::from_millis(300)).await;
+
+        let executor_for = |node_id| {
+            let router = Arc::new(Router::new(meta_raft.get_cluster_meta()));
+            let mut executor = CommandExecutor::new(StorageEngine::new_memory(16));
+            executor.set_cluster_commands(ClusterCommands::new(
+                node_id,
+                meta_raft.clone(),
+                node.clone(),
+                router,
+            ));
+            executor
+        };
+        let replica = executor_for(2);
+        let other = executor_for(3);
+        let bytes = |args: &[&str]| -> Vec<Bytes> {
+            args.iter().map(|a| Bytes::from(a.to_string())).collect()
+        };
+        let moved_to_master = |result: Result<RespValue>| match result {
+            Err(AikvError::Moved(_, addr)) => assert_eq!(addr, "127.0.0.1:6379"),
+            other => panic!("expected MOVED, got {:?}", other),
+        };
+
+        // Without READONLY the replica redirects reads as well
+        let mut client = ClientContext::new(0);
+        moved_to_master(replica.execute("GET", &bytes(&["foo"]), &mut client));
+
+        assert_eq!(
+            replica.execute("READONLY", &[], &mut client)?,
+            RespValue::SimpleString("OK".to_string())
+        );
+        assert_eq!(
+            replica.execute("GET", &bytes(&["foo"]), &mut client)?,
+            RespValue::BulkString(None)
+        );
+        assert_eq!(
+            replica.execute("EXISTS", &bytes(&["foo", "{foo}:2"]), &mut client)?,
+            RespValue::Integer(0)
+        );
+        moved_to_master(replica.execute("SET", &bytes(&["foo", "1"]), &mut client));
+        moved_to_master(replica.execute("EVAL", &bytes(&["return 1", "1", "foo"]), &mut client));
+        // A write doesn't end READONLY mode
+        assert_eq!(
+            replica.execute("GET", &bytes(&["foo"]), &mut client)?,
+            RespValue::BulkString(None)
+        );
+
+        // Only replicas serve reads; other nodes still redirect
+        let mut other_client = ClientContext::new(1);
+        other.execute("READONLY", &[], &mut other_client)?;
+        moved_to_master(other.execute("GET", &bytes(&["foo"]), &mut other_client));
+
+        // READWRITE goes back to redirecting everything
+        assert_eq!(
+            replica.execute("READWRITE", &[], &mut client)?,
+            RespValue::SimpleString("OK".to_string())
+        );
+        assert!(!client.readonly);
+        moved_to_master(replica.execute("GET", &bytes(&["foo"]), &mut client));
+
+        let _ = tokio::fs::remove_dir_all("/tmp/test_readonly_replica").await;
+
+        Ok(())
+    }
+
+    /// Test parsing the CLUSTER FAILOVER mode argument
+    #[test]
+    fn test_failover_mode_parse() {
+        use aikv::cluster::FailoverMode;
+        use bytes::Bytes;
+
+        assert_eq!(FailoverMode::parse(&[]).unwrap(), FailoverMode::Default);
+        assert_eq!(
+            FailoverMode::parse(&[Bytes::from("force")]).unwrap(),
+            FailoverMode::Force
+        );
+        assert_eq!(
+            FailoverMode::parse(&[Bytes::from("TAKEOVER")]).unwrap(),
+            FailoverMode::Takeover
+        );
+        assert!(FailoverMode::parse(&[Bytes::from("NOW")]).is_err());
+        assert!(FailoverMode::parse(&[Bytes::from("FORCE"), Bytes::from("FORCE")]).is_err());
+    }
+
+    /// Test CLUSTER FAILOVER on a 3-node group: a replica stands for election,
+    /// wins it, and then becomes the group's master in the cluster metadata
+    #[tokio::test]
+    async fn test_cluster_failover_moves_leadership_to_replica() -> Result<()> {
+        use aidb::cluster::raft_network::raft_rpc::raft_service_server::RaftServiceServer;
+        use aikv::cluster::raft_service::MultiRaftService;
+        use aikv::cluster::FailoverMode;
+
+        let internal = |e: &dyn std::fmt::Display| aikv::error::AikvError::Internal(e.to_string());
+        let addrs = [
+            (1, "127.0.0.1:50091"),
+            (2, "127.0.0.1:50092"),
+            (3, "127.0.0.1:50093"),
+        ];
+
+        // Three nodes, each serving Raft RPCs and knowing the others' addresses
+        let mut nodes = Vec::new();
+        for (node_id, addr) in addrs {
+            let dir = format!("/tmp/test_failover_node{}", node_id);
+            let _ = tokio::fs::remove_dir_all(&dir).await;
+            let config = RaftConfig::default();
+            let mut node = MultiRaftNode::new(node_id, &dir, config.clone())
+                .await
+                .map_err(|e| internal(&e))?;
+            node.init_meta_raft(config)
+                .await
+                .map_err(|e| internal(&e))?;
+            if node_id == 1 {
+                node.initialize_meta_cluster(vec![(1, addr.to_string())])
+                    .await
+                    .map_err(|e| internal(&e))?;
+            }
+            for (peer, peer_addr) in addrs {
+                node.add_node_address(peer, format!("http://{}", peer_addr));
+            }
+            let node = Arc::new(node);
+
+            let service = RaftServiceServer::new(MultiRaftService::new(node.clone()));
+            let bind_addr = addr.parse().unwrap();
+            tokio::spawn(async move {
+                let _ = tonic::transport::Server::builder()
+                    .add_service(service)
+                    .serve(bind_addr)
+                    .await;
+            });
+            nodes.push(node);
+        }
+        for node in &nodes {
+            node.create_raft_group(1, vec![1, 2, 3])
+                .await
+                .map_err(|e| internal(&e))?;
+        }
+
+        // Wait for the data group to elect a leader
+        let raft = |node_id: u64| nodes[node_id as usize - 1].get_raft_group(1).unwrap();
+        let leader_of_group = || raft(1).metrics().borrow().current_leader;
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+        let leader = loop {
+            if let Some(leader) = leader_of_group() {
+                break leader;
+            }
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "group 1 elected no leader"
+            );
+            sleep(Duration::from_millis(50)).await;
+        };
+
+        let meta_raft = nodes[0].meta_raft().unwrap().clone();
+        meta_raft
+            .create_group(1, vec![1, 2, 3])
+            .await
+            .map_err(|e| internal(&e))?;
+        meta_raft
+            .update_group_leader(1, leader)
+            .await
+            .map_err(|e| internal(&e))?;
+        sleep(Duration::from_millis(200)).await;
+
+        // The master itself can't fail over
+        let commands = |node_id: u64| {
+            let router = Arc::new(Router::new(meta_raft.get_cluster_meta()));
+            ClusterCommands::new(
+                node_id,
+                meta_raft.clone(),
+                nodes[node_id as usize - 1].clone(),
+                router,
+            )
+        };
+        assert!(commands(leader)
+            .cluster_failover(FailoverMode::Default)
+            .await
+            .is_err());
+
+        // A replica replies at once and takes over shortly after
+        let replica = if leader == 1 { 2 } else { 1 };
+        let result = commands(replica)
+            .cluster_failover(FailoverMode::Force)
+            .await?;
+        assert_eq!(
+            result,
+            aikv::protocol::RespValue::SimpleString("OK".to_string())
+        );
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+        loop {
+            let elected = raft(replica).metrics().borrow().current_leader == Some(replica);
+            let recorded = meta_raft
+                .get_cluster_meta()
+                .groups
+                .get(&1)
+                .is_some_and(|group| group.leader == Some(replica));
+            if elected && recorded {
+                break;
+            }
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "leadership did not move to node {}",
+                replica
+            );
+            sleep(Duration::from_millis(50)).await;
+        }
+
+        for (node_id, _) in addrs {
+            let _ = tokio::fs::remove_dir_all(format!("/tmp/test_failover_node{}", node_id)).await;
+        }
+
+        Ok(())
+    }
 }
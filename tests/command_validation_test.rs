@@ -1,4 +1,4 @@
-use aikv::command::CommandExecutor;
+use aikv::command::{ClientContext, CommandExecutor};
 use aikv::protocol::RespValue;
 use aikv::StorageEngine;
 use bytes::Bytes;
@@ -23,8 +23,7 @@ enum ValidationStatus {
 
 struct CommandValidator {
     executor: CommandExecutor,
-    current_db: usize,
-    client_id: usize,
+    client: ClientContext,
 }
 
 impl CommandValidator {
@@ -33,16 +32,12 @@ impl CommandValidator {
         let executor = CommandExecutor::new(storage);
         Self {
             executor,
-            current_db: 0,
-            client_id: 1,
+            client: ClientContext::new(1),
         }
     }
 
     fn test_ping(&mut self) -> CommandValidation {
-        match self
-            .executor
-            .execute("PING", &[], &mut self.current_db, self.client_id)
-        {
+        match self.executor.execute("PING", &[], &mut self.client) {
             Ok(resp) => {
                 if matches!(resp, RespValue::SimpleString(_)) {
                     CommandValidation {
@@ -71,12 +66,10 @@ impl CommandValidator {
 
     fn test_echo(&mut self) -> CommandValidation {
         let test_message = "Hello AiKv!";
-        match self.executor.execute(
-            "ECHO",
-            &[Bytes::from(test_message)],
-            &mut self.current_db,
-            self.client_id,
-        ) {
+        match self
+            .executor
+            .execute("ECHO", &[Bytes::from(test_message)], &mut self.client)
+        {
             Ok(resp) => {
                 if matches!(resp, RespValue::BulkString(Some(_))) {
                     CommandValidation {
@@ -111,8 +104,7 @@ impl CommandValidator {
         match self.executor.execute(
             "SET",
             &[Bytes::from(key), Bytes::from(value)],
-            &mut self.current_db,
-            self.client_id,
+            &mut self.client,
         ) {
             Ok(resp) => {
                 if !matches!(resp, RespValue::SimpleString(_)) && resp != RespValue::ok() {
@@ -135,12 +127,10 @@ impl CommandValidator {
         }
 
         // Test GET
-        match self.executor.execute(
-            "GET",
-            &[Bytes::from(key)],
-            &mut self.current_db,
-            self.client_id,
-        ) {
+        match self
+            .executor
+            .execute("GET", &[Bytes::from(key)], &mut self.client)
+        {
             Ok(resp) => {
                 if matches!(resp, RespValue::BulkString(Some(ref data)) if data == value) {
                     CommandValidation {
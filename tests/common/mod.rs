@@ -0,0 +1,117 @@
+//! Shared helpers for tests that talk to a real AiKv server over TCP.
+
+#![allow(dead_code)]
+
+use aikv::protocol::{RespParser, RespValue};
+use aikv::{Server, StorageEngine};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::sleep;
+
+/// Start a server on a free local port and return its address.
+///
+/// `configure` runs before the server starts accepting connections.
+pub async fn start_server(configure: impl FnOnce(&mut Server)) -> String {
+    let addr = free_addr();
+
+    let mut server = Server::new(addr.clone(), StorageEngine::new_memory(16));
+    configure(&mut server);
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+
+    wait_for_listener(&addr).await;
+    addr
+}
+
+/// A local address nothing is listening on yet
+pub fn free_addr() -> String {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("Failed to find a free port")
+        .port();
+    format!("127.0.0.1:{}", port)
+}
+
+/// Wait for a server at `addr` to start accepting connections
+pub async fn wait_for_listener(addr: &str) {
+    for _ in 0..100 {
+        if TcpStream::connect(addr).await.is_ok() {
+            return;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    panic!("Server at {} did not start", addr);
+}
+
+/// Minimal RESP client
+pub struct TestClient {
+    stream: TcpStream,
+    parser: RespParser,
+}
+
+impl TestClient {
+    pub async fn connect(addr: &str) -> Self {
+        Self {
+            stream: TcpStream::connect(addr).await.expect("Failed to connect"),
+            parser: RespParser::new(8192),
+        }
+    }
+
+    /// Send a command and wait for its reply
+    pub async fn command(&mut self, args: &[&str]) -> RespValue {
+        self.send(args).await;
+        self.read_reply()
+            .await
+            .expect("Connection closed before reply")
+    }
+
+    /// Send a command without waiting for the reply
+    pub async fn send(&mut self, args: &[&str]) {
+        let mut data = format!("*{}\r\n", args.len()).into_bytes();
+        for arg in args {
+            data.extend_from_slice(format!("${}\r\n{}\r\n", arg.len(), arg).as_bytes());
+        }
+        self.stream
+            .write_all(&data)
+            .await
+            .expect("Failed to write command");
+    }
+
+    /// Send raw bytes, e.g. a request that isn't valid RESP
+    pub async fn send_raw(&mut self, data: &[u8]) {
+        self.stream
+            .write_all(data)
+            .await
+            .expect("Failed to write request");
+    }
+
+    /// Send several commands in a single write, without waiting for replies
+    pub async fn send_pipeline(&mut self, commands: &[Vec<String>]) {
+        let mut data = Vec::new();
+        for args in commands {
+            data.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+            for arg in args {
+                data.extend_from_slice(format!("${}\r\n{}\r\n", arg.len(), arg).as_bytes());
+            }
+        }
+        self.stream
+            .write_all(&data)
+            .await
+            .expect("Failed to write pipeline");
+    }
+
+    /// Read the next reply, or `None` if the server closed the connection
+    pub async fn read_reply(&mut self) -> Option<RespValue> {
+        loop {
+            if let Some(value) = self.parser.parse().expect("Invalid reply") {
+                return Some(value);
+            }
+            let n = self.stream.read_buf(self.parser.buffer_mut()).await.ok()?;
+            if n == 0 {
+                return None;
+            }
+        }
+    }
+}
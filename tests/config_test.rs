@@ -0,0 +1,190 @@
+//! Tests for CONFIG GET/SET against the configuration shared by all connections
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+
+/// Parameter names and values of a RESP2 CONFIG GET reply
+fn config_pairs(reply: RespValue) -> Vec<(String, String)> {
+    let RespValue::Array(Some(items)) = reply else {
+        panic!("expected array, got {:?}", reply);
+    };
+    items
+        .chunks(2)
+        .map(|pair| match pair {
+            [RespValue::BulkString(Some(name)), RespValue::BulkString(Some(value))] => (
+                String::from_utf8_lossy(name).to_string(),
+                String::from_utf8_lossy(value).to_string(),
+            ),
+            other => panic!("unexpected pair {:?}", other),
+        })
+        .collect()
+}
+
+fn is_error(reply: &RespValue, needle: &str) -> bool {
+    matches!(reply, RespValue::Error(e) if e.contains(needle))
+}
+
+#[tokio::test]
+async fn test_config_set_then_get_from_another_connection() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+    let mut other = TestClient::connect(&addr).await;
+
+    assert_eq!(
+        client
+            .command(&["CONFIG", "SET", "slowlog-log-slower-than", "5000"])
+            .await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        config_pairs(
+            other
+                .command(&["CONFIG", "GET", "slowlog-log-slower-than"])
+                .await
+        ),
+        vec![("slowlog-log-slower-than".to_string(), "5000".to_string())]
+    );
+
+    // Memory values take units and change the running limit
+    assert_eq!(
+        client
+            .command(&["CONFIG", "SET", "maxmemory", "100mb"])
+            .await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        config_pairs(other.command(&["CONFIG", "GET", "maxmemory"]).await),
+        vec![("maxmemory".to_string(), "100000000".to_string())]
+    );
+    let info = match other.command(&["INFO", "memory"]).await {
+        RespValue::BulkString(Some(info)) => String::from_utf8_lossy(&info).to_string(),
+        reply => panic!("expected bulk string, got {:?}", reply),
+    };
+    assert!(info.contains("maxmemory:100000000\r\n"), "{}", info);
+
+    assert_eq!(
+        client
+            .command(&["CONFIG", "SET", "appendfsync", "always"])
+            .await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        config_pairs(other.command(&["CONFIG", "GET", "appendfsync"]).await),
+        vec![("appendfsync".to_string(), "always".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn test_config_get_glob_returns_every_match() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    let names: Vec<String> = config_pairs(client.command(&["CONFIG", "GET", "max*"]).await)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(names, vec!["maxclients", "maxmemory", "maxmemory-policy"]);
+
+    let names: Vec<String> = config_pairs(
+        client
+            .command(&["CONFIG", "GET", "slowlog-*", "port"])
+            .await,
+    )
+    .into_iter()
+    .map(|(name, _)| name)
+    .collect();
+    assert_eq!(
+        names,
+        vec!["slowlog-log-slower-than", "slowlog-max-len", "port"]
+    );
+}
+
+#[tokio::test]
+async fn test_config_set_rejects_immutable_and_unknown_parameters() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    let reply = client.command(&["CONFIG", "SET", "port", "7000"]).await;
+    assert!(
+        is_error(&reply, "can't set immutable config"),
+        "{:?}",
+        reply
+    );
+    let reply = client.command(&["CONFIG", "SET", "databases", "4"]).await;
+    assert!(
+        is_error(&reply, "can't set immutable config"),
+        "{:?}",
+        reply
+    );
+    let reply = client
+        .command(&["CONFIG", "SET", "no-such-option", "1"])
+        .await;
+    assert!(is_error(&reply, "Unknown option"), "{:?}", reply);
+
+    // A rejected value leaves the parameter unchanged
+    let reply = client
+        .command(&["CONFIG", "SET", "maxmemory", "lots"])
+        .await;
+    assert!(is_error(&reply, "memory value"), "{:?}", reply);
+    assert_eq!(
+        config_pairs(client.command(&["CONFIG", "GET", "maxmemory"]).await),
+        vec![("maxmemory".to_string(), "0".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn test_config_names_and_values_ignore_case() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    assert_eq!(
+        client.command(&["CONFIG", "SET", "MaxMemory", "1gb"]).await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        config_pairs(client.command(&["CONFIG", "GET", "MAXMEMORY"]).await),
+        vec![("maxmemory".to_string(), "1000000000".to_string())]
+    );
+    assert_eq!(
+        client.command(&["CONFIG", "SET", "maxmemory", "1G"]).await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        config_pairs(client.command(&["CONFIG", "GET", "maxmemory"]).await),
+        vec![("maxmemory".to_string(), "1073741824".to_string())]
+    );
+
+    assert_eq!(
+        client
+            .command(&["CONFIG", "SET", "maxmemory-policy", "ALLKEYS-LRU"])
+            .await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        config_pairs(client.command(&["CONFIG", "GET", "maxmemory-policy"]).await),
+        vec![("maxmemory-policy".to_string(), "allkeys-lru".to_string())]
+    );
+
+    // Unparseable values name the parameter and change nothing
+    let reply = client
+        .command(&["CONFIG", "SET", "maxmemory", "1gbi"])
+        .await;
+    assert!(
+        is_error(
+            &reply,
+            "CONFIG SET failed (possibly related to argument 'maxmemory') - argument must be a memory value"
+        ),
+        "{:?}",
+        reply
+    );
+    let reply = client
+        .command(&["CONFIG", "SET", "maxmemory-policy", "sometimes"])
+        .await;
+    assert!(is_error(&reply, "'maxmemory-policy'"), "{:?}", reply);
+    assert_eq!(
+        config_pairs(client.command(&["CONFIG", "GET", "maxmemory"]).await),
+        vec![("maxmemory".to_string(), "1073741824".to_string())]
+    );
+}
@@ -1,4 +1,5 @@
-use aikv::command::CommandExecutor;
+use aikv::command::set::SetCommands;
+use aikv::command::{ClientContext, CommandExecutor};
 use aikv::protocol::RespValue;
 use aikv::StorageEngine;
 use bytes::Bytes;
@@ -7,22 +8,21 @@ use bytes::Bytes;
 fn test_list_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // RPUSH
     let args = vec![Bytes::from("mylist"), Bytes::from("world")];
-    let result = executor.execute("RPUSH", &args, &mut current_db, client_id);
+    let result = executor.execute("RPUSH", &args, &mut client);
     assert!(result.is_ok());
 
     // LPUSH
     let args = vec![Bytes::from("mylist"), Bytes::from("hello")];
-    let result = executor.execute("LPUSH", &args, &mut current_db, client_id);
+    let result = executor.execute("LPUSH", &args, &mut client);
     assert!(result.is_ok());
 
     // LRANGE
     let args = vec![Bytes::from("mylist"), Bytes::from("0"), Bytes::from("-1")];
-    let result = executor.execute("LRANGE", &args, &mut current_db, client_id);
+    let result = executor.execute("LRANGE", &args, &mut client);
     assert!(result.is_ok());
     if let Ok(RespValue::Array(Some(items))) = result {
         assert_eq!(items.len(), 2);
@@ -32,7 +32,7 @@ fn test_list_commands() {
 
     // LLEN
     let args = vec![Bytes::from("mylist")];
-    let result = executor.execute("LLEN", &args, &mut current_db, client_id);
+    let result = executor.execute("LLEN", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(2));
 }
@@ -41,14 +41,11 @@ fn test_list_commands() {
 fn test_linsert_command() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Create a list
     let args = vec![Bytes::from("mylist"), Bytes::from("a"), Bytes::from("c")];
-    executor
-        .execute("RPUSH", &args, &mut current_db, client_id)
-        .unwrap();
+    executor.execute("RPUSH", &args, &mut client).unwrap();
 
     // LINSERT BEFORE
     let args = vec![
@@ -57,13 +54,13 @@ fn test_linsert_command() {
         Bytes::from("c"),
         Bytes::from("b"),
     ];
-    let result = executor.execute("LINSERT", &args, &mut current_db, client_id);
+    let result = executor.execute("LINSERT", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(3));
 
     // Verify list order: a, b, c
     let args = vec![Bytes::from("mylist"), Bytes::from("0"), Bytes::from("-1")];
-    let result = executor.execute("LRANGE", &args, &mut current_db, client_id);
+    let result = executor.execute("LRANGE", &args, &mut client);
     if let Ok(RespValue::Array(Some(items))) = result {
         assert_eq!(items.len(), 3);
     } else {
@@ -77,7 +74,7 @@ fn test_linsert_command() {
         Bytes::from("c"),
         Bytes::from("d"),
     ];
-    let result = executor.execute("LINSERT", &args, &mut current_db, client_id);
+    let result = executor.execute("LINSERT", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(4));
 
@@ -88,7 +85,7 @@ fn test_linsert_command() {
         Bytes::from("notexist"),
         Bytes::from("x"),
     ];
-    let result = executor.execute("LINSERT", &args, &mut current_db, client_id);
+    let result = executor.execute("LINSERT", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(-1));
 
@@ -99,17 +96,105 @@ fn test_linsert_command() {
         Bytes::from("a"),
         Bytes::from("x"),
     ];
-    let result = executor.execute("LINSERT", &args, &mut current_db, client_id);
+    let result = executor.execute("LINSERT", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(0));
 }
 
+#[test]
+fn test_lset_lrem_linsert_edit_in_place() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+    let list = |items: &[&str]| {
+        RespValue::array(
+            items
+                .iter()
+                .map(|i| RespValue::bulk_string(i.to_string()))
+                .collect(),
+        )
+    };
+
+    run("RPUSH", &["l", "a", "x", "b", "x", "c", "x", "d", "x"]).unwrap();
+    run("EXPIRE", &["l", "1000"]).unwrap();
+
+    // LREM from the head, from the tail, then every remaining match
+    assert_eq!(
+        run("LREM", &["l", "1", "x"]).unwrap(),
+        RespValue::integer(1)
+    );
+    assert_eq!(
+        run("LRANGE", &["l", "0", "-1"]).unwrap(),
+        list(&["a", "b", "x", "c", "x", "d", "x"])
+    );
+    assert_eq!(
+        run("LREM", &["l", "-2", "x"]).unwrap(),
+        RespValue::integer(2)
+    );
+    assert_eq!(
+        run("LRANGE", &["l", "0", "-1"]).unwrap(),
+        list(&["a", "b", "x", "c", "d"])
+    );
+    assert_eq!(
+        run("LREM", &["l", "0", "x"]).unwrap(),
+        RespValue::integer(1)
+    );
+    assert_eq!(
+        run("LREM", &["l", "0", "x"]).unwrap(),
+        RespValue::integer(0)
+    );
+
+    // LSET with positive and negative indexes
+    assert_eq!(run("LSET", &["l", "0", "A"]).unwrap(), RespValue::ok());
+    assert_eq!(run("LSET", &["l", "-1", "D"]).unwrap(), RespValue::ok());
+    assert!(run("LSET", &["l", "4", "x"]).is_err());
+    assert!(run("LSET", &["l", "-5", "x"]).is_err());
+    assert!(run("LSET", &["missing", "0", "x"]).is_err());
+
+    // LINSERT near either end
+    assert_eq!(
+        run("LINSERT", &["l", "BEFORE", "A", "first"]).unwrap(),
+        RespValue::integer(5)
+    );
+    assert_eq!(
+        run("LINSERT", &["l", "AFTER", "D", "last"]).unwrap(),
+        RespValue::integer(6)
+    );
+    assert_eq!(
+        run("LRANGE", &["l", "0", "-1"]).unwrap(),
+        list(&["first", "A", "b", "c", "D", "last"])
+    );
+
+    // Editing the list in place keeps its TTL
+    match run("TTL", &["l"]).unwrap() {
+        RespValue::Integer(ttl) => assert!(ttl > 900, "ttl {}", ttl),
+        other => panic!("expected integer, got {:?}", other),
+    }
+
+    // Removing the last element deletes the key
+    run("RPUSH", &["single", "x"]).unwrap();
+    assert_eq!(
+        run("LREM", &["single", "0", "x"]).unwrap(),
+        RespValue::integer(1)
+    );
+    assert_eq!(run("EXISTS", &["single"]).unwrap(), RespValue::integer(0));
+
+    run("SET", &["str", "v"]).unwrap();
+    assert!(run("LSET", &["str", "0", "x"]).is_err());
+    assert!(run("LREM", &["str", "0", "x"]).is_err());
+    assert!(run("LINSERT", &["str", "BEFORE", "v", "x"]).is_err());
+}
+
 #[test]
 fn test_lmove_command() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Create source list
     let args = vec![
@@ -118,9 +203,7 @@ fn test_lmove_command() {
         Bytes::from("b"),
         Bytes::from("c"),
     ];
-    executor
-        .execute("RPUSH", &args, &mut current_db, client_id)
-        .unwrap();
+    executor.execute("RPUSH", &args, &mut client).unwrap();
 
     // LMOVE LEFT RIGHT (pop from left of src, push to right of dst)
     let args = vec![
@@ -129,7 +212,7 @@ fn test_lmove_command() {
         Bytes::from("LEFT"),
         Bytes::from("RIGHT"),
     ];
-    let result = executor.execute("LMOVE", &args, &mut current_db, client_id);
+    let result = executor.execute("LMOVE", &args, &mut client);
     assert!(result.is_ok());
     if let RespValue::BulkString(Some(value)) = result.unwrap() {
         assert_eq!(value.as_ref(), b"a");
@@ -139,12 +222,12 @@ fn test_lmove_command() {
 
     // Verify src has 2 elements
     let args = vec![Bytes::from("src")];
-    let result = executor.execute("LLEN", &args, &mut current_db, client_id);
+    let result = executor.execute("LLEN", &args, &mut client);
     assert_eq!(result.unwrap(), RespValue::Integer(2));
 
     // Verify dst has 1 element
     let args = vec![Bytes::from("dst")];
-    let result = executor.execute("LLEN", &args, &mut current_db, client_id);
+    let result = executor.execute("LLEN", &args, &mut client);
     assert_eq!(result.unwrap(), RespValue::Integer(1));
 
     // LMOVE RIGHT LEFT (pop from right of src, push to left of dst)
@@ -154,7 +237,7 @@ fn test_lmove_command() {
         Bytes::from("RIGHT"),
         Bytes::from("LEFT"),
     ];
-    let result = executor.execute("LMOVE", &args, &mut current_db, client_id);
+    let result = executor.execute("LMOVE", &args, &mut client);
     assert!(result.is_ok());
     if let RespValue::BulkString(Some(value)) = result.unwrap() {
         assert_eq!(value.as_ref(), b"c");
@@ -169,17 +252,67 @@ fn test_lmove_command() {
         Bytes::from("LEFT"),
         Bytes::from("RIGHT"),
     ];
-    let result = executor.execute("LMOVE", &args, &mut current_db, client_id);
+    let result = executor.execute("LMOVE", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Null);
 }
 
+#[test]
+fn test_lmpop_pops_from_first_non_empty_list() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+    let bulk = |items: &[&str]| {
+        RespValue::array(
+            items
+                .iter()
+                .map(|item| RespValue::bulk_string(item.to_string()))
+                .collect(),
+        )
+    };
+
+    run("RPUSH", &["second", "a", "b", "c"]).unwrap();
+
+    // The first key is missing, so the second supplies the elements
+    assert_eq!(
+        run("LMPOP", &["2", "first", "second", "LEFT"]).unwrap(),
+        RespValue::array(vec![RespValue::bulk_string("second"), bulk(&["a"])])
+    );
+    assert_eq!(
+        run("LMPOP", &["2", "first", "second", "right", "COUNT", "5"]).unwrap(),
+        RespValue::array(vec![RespValue::bulk_string("second"), bulk(&["c", "b"])])
+    );
+    assert_eq!(run("EXISTS", &["second"]).unwrap(), RespValue::integer(0));
+    assert_eq!(
+        run("LMPOP", &["2", "first", "second", "LEFT"]).unwrap(),
+        RespValue::null_array()
+    );
+
+    for args in [
+        &["0", "k", "LEFT"][..],
+        &["3", "k", "LEFT"],
+        &["1", "k", "UP"],
+        &["1", "k", "LEFT", "COUNT", "0"],
+        &["1", "k", "LEFT", "COUNT"],
+        &["1", "k", "LEFT", "COUNT", "1", "COUNT", "1"],
+    ] {
+        assert!(run("LMPOP", args).is_err(), "{:?}", args);
+    }
+
+    run("SET", &["str", "x"]).unwrap();
+    assert!(run("LMPOP", &["1", "str", "LEFT"]).is_err());
+}
+
 #[test]
 fn test_hash_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // HSET
     let args = vec![
@@ -189,28 +322,28 @@ fn test_hash_commands() {
         Bytes::from("field2"),
         Bytes::from("value2"),
     ];
-    let result = executor.execute("HSET", &args, &mut current_db, client_id);
+    let result = executor.execute("HSET", &args, &mut client);
     assert!(result.is_ok());
 
     // HGET
     let args = vec![Bytes::from("myhash"), Bytes::from("field1")];
-    let result = executor.execute("HGET", &args, &mut current_db, client_id);
+    let result = executor.execute("HGET", &args, &mut client);
     assert!(result.is_ok());
 
     // HLEN
     let args = vec![Bytes::from("myhash")];
-    let result = executor.execute("HLEN", &args, &mut current_db, client_id);
+    let result = executor.execute("HLEN", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(2));
 
     // HGETALL
     let args = vec![Bytes::from("myhash")];
-    let result = executor.execute("HGETALL", &args, &mut current_db, client_id);
+    let result = executor.execute("HGETALL", &args, &mut client);
     assert!(result.is_ok());
-    if let Ok(RespValue::Array(Some(items))) = result {
-        assert_eq!(items.len(), 4); // 2 fields * 2 (field + value)
+    if let Ok(RespValue::Map(pairs)) = result {
+        assert_eq!(pairs.len(), 2); // 2 field-value pairs
     } else {
-        panic!("Expected array result");
+        panic!("Expected map result");
     }
 }
 
@@ -218,8 +351,7 @@ fn test_hash_commands() {
 fn test_hmset_command() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // HMSET - set multiple field-value pairs
     let args = vec![
@@ -231,7 +363,7 @@ fn test_hmset_command() {
         Bytes::from("field3"),
         Bytes::from("value3"),
     ];
-    let result = executor.execute("HMSET", &args, &mut current_db, client_id);
+    let result = executor.execute("HMSET", &args, &mut client);
     assert!(result.is_ok());
     // HMSET should return OK
     if let RespValue::SimpleString(s) = result.unwrap() {
@@ -242,13 +374,13 @@ fn test_hmset_command() {
 
     // Verify with HLEN
     let args = vec![Bytes::from("testhash")];
-    let result = executor.execute("HLEN", &args, &mut current_db, client_id);
+    let result = executor.execute("HLEN", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(3));
 
     // Verify individual fields with HGET
     let args = vec![Bytes::from("testhash"), Bytes::from("field1")];
-    let result = executor.execute("HGET", &args, &mut current_db, client_id);
+    let result = executor.execute("HGET", &args, &mut client);
     assert!(result.is_ok());
     if let RespValue::BulkString(Some(value)) = result.unwrap() {
         assert_eq!(value.as_ref(), b"value1");
@@ -264,12 +396,12 @@ fn test_hmset_command() {
         Bytes::from("field4"),
         Bytes::from("value4"),
     ];
-    let result = executor.execute("HMSET", &args, &mut current_db, client_id);
+    let result = executor.execute("HMSET", &args, &mut client);
     assert!(result.is_ok());
 
     // Verify updated field
     let args = vec![Bytes::from("testhash"), Bytes::from("field1")];
-    let result = executor.execute("HGET", &args, &mut current_db, client_id);
+    let result = executor.execute("HGET", &args, &mut client);
     assert!(result.is_ok());
     if let RespValue::BulkString(Some(value)) = result.unwrap() {
         assert_eq!(value.as_ref(), b"newvalue1");
@@ -279,7 +411,7 @@ fn test_hmset_command() {
 
     // Verify total fields count
     let args = vec![Bytes::from("testhash")];
-    let result = executor.execute("HLEN", &args, &mut current_db, client_id);
+    let result = executor.execute("HLEN", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(4));
 }
@@ -288,8 +420,7 @@ fn test_hmset_command() {
 fn test_hscan_command() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Create a hash with multiple fields
     let args = vec![
@@ -303,13 +434,11 @@ fn test_hscan_command() {
         Bytes::from("anotherfield"),
         Bytes::from("anothervalue"),
     ];
-    executor
-        .execute("HSET", &args, &mut current_db, client_id)
-        .unwrap();
+    executor.execute("HSET", &args, &mut client).unwrap();
 
     // HSCAN with cursor 0 (start of iteration)
     let args = vec![Bytes::from("scanhash"), Bytes::from("0")];
-    let result = executor.execute("HSCAN", &args, &mut current_db, client_id);
+    let result = executor.execute("HSCAN", &args, &mut client);
     assert!(result.is_ok());
     if let RespValue::Array(Some(items)) = result.unwrap() {
         assert_eq!(items.len(), 2); // [cursor, [fields]]
@@ -330,7 +459,7 @@ fn test_hscan_command() {
         Bytes::from("COUNT"),
         Bytes::from("2"),
     ];
-    let result = executor.execute("HSCAN", &args, &mut current_db, client_id);
+    let result = executor.execute("HSCAN", &args, &mut client);
     assert!(result.is_ok());
     if let RespValue::Array(Some(items)) = result.unwrap() {
         assert_eq!(items.len(), 2);
@@ -356,7 +485,7 @@ fn test_hscan_command() {
         Bytes::from("MATCH"),
         Bytes::from("field*"),
     ];
-    let result = executor.execute("HSCAN", &args, &mut current_db, client_id);
+    let result = executor.execute("HSCAN", &args, &mut client);
     assert!(result.is_ok());
     if let RespValue::Array(Some(items)) = result.unwrap() {
         // Check fields array - should only have field1, field2, field3
@@ -371,7 +500,7 @@ fn test_hscan_command() {
 
     // HSCAN on non-existent key should return empty result
     let args = vec![Bytes::from("nonexistent"), Bytes::from("0")];
-    let result = executor.execute("HSCAN", &args, &mut current_db, client_id);
+    let result = executor.execute("HSCAN", &args, &mut client);
     assert!(result.is_ok());
     if let RespValue::Array(Some(items)) = result.unwrap() {
         assert_eq!(items.len(), 2);
@@ -390,8 +519,7 @@ fn test_hscan_command() {
 fn test_set_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // SADD
     let args = vec![
@@ -400,41 +528,159 @@ fn test_set_commands() {
         Bytes::from("member2"),
         Bytes::from("member3"),
     ];
-    let result = executor.execute("SADD", &args, &mut current_db, client_id);
+    let result = executor.execute("SADD", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(3));
 
     // SCARD
     let args = vec![Bytes::from("myset")];
-    let result = executor.execute("SCARD", &args, &mut current_db, client_id);
+    let result = executor.execute("SCARD", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(3));
 
     // SISMEMBER
     let args = vec![Bytes::from("myset"), Bytes::from("member1")];
-    let result = executor.execute("SISMEMBER", &args, &mut current_db, client_id);
+    let result = executor.execute("SISMEMBER", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(1));
 
     // SREM
     let args = vec![Bytes::from("myset"), Bytes::from("member2")];
-    let result = executor.execute("SREM", &args, &mut current_db, client_id);
+    let result = executor.execute("SREM", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(1));
 
     // SCARD after removal
     let args = vec![Bytes::from("myset")];
-    let result = executor.execute("SCARD", &args, &mut current_db, client_id);
+    let result = executor.execute("SCARD", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(2));
 }
 
+#[test]
+fn test_srandmember_counts() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+    let members = |reply: RespValue| match reply {
+        RespValue::Array(Some(items)) => items
+            .into_iter()
+            .map(|item| match item {
+                RespValue::BulkString(Some(b)) => String::from_utf8_lossy(&b).to_string(),
+                other => panic!("expected bulk string, got {:?}", other),
+            })
+            .collect::<Vec<_>>(),
+        other => panic!("expected array, got {:?}", other),
+    };
+    let set: std::collections::HashSet<String> = ["a", "b", "c", "d", "e"]
+        .iter()
+        .map(|m| m.to_string())
+        .collect();
+    run("SADD", &["s", "a", "b", "c", "d", "e"]).unwrap();
+
+    // No count: one member, as a bulk string
+    match run("SRANDMEMBER", &["s"]).unwrap() {
+        RespValue::BulkString(Some(b)) => assert!(set.contains(&*String::from_utf8_lossy(&b))),
+        other => panic!("expected bulk string, got {:?}", other),
+    }
+    assert_eq!(run("SRANDMEMBER", &["missing"]).unwrap(), RespValue::Null);
+
+    // A positive count gives distinct members, never more than the set has
+    let picked = members(run("SRANDMEMBER", &["s", "3"]).unwrap());
+    assert_eq!(picked.len(), 3);
+    let distinct: std::collections::HashSet<String> = picked.iter().cloned().collect();
+    assert_eq!(distinct.len(), 3);
+    assert!(distinct.is_subset(&set));
+    let all: std::collections::HashSet<String> = members(run("SRANDMEMBER", &["s", "10"]).unwrap())
+        .into_iter()
+        .collect();
+    assert_eq!(all, set);
+    assert!(members(run("SRANDMEMBER", &["s", "0"]).unwrap()).is_empty());
+
+    // A negative count gives exactly that many, so members must repeat
+    let picked = members(run("SRANDMEMBER", &["s", "-20"]).unwrap());
+    assert_eq!(picked.len(), 20);
+    assert!(picked.iter().all(|m| set.contains(m)));
+    assert!(members(run("SRANDMEMBER", &["missing", "-3"]).unwrap()).is_empty());
+
+    // Picks come from a real RNG, not the same iteration order every time
+    let firsts: std::collections::HashSet<String> = (0..100)
+        .map(|_| members(run("SRANDMEMBER", &["s", "-1"]).unwrap()).remove(0))
+        .collect();
+    assert!(firsts.len() > 1);
+
+    assert!(run("SRANDMEMBER", &["s", "x"]).is_err());
+    assert!(run("SRANDMEMBER", &["s", "1", "2"]).is_err());
+}
+
+#[test]
+fn test_spop_with_count() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+    let members = |reply: RespValue| match reply {
+        RespValue::Array(Some(items)) => items
+            .into_iter()
+            .map(|item| match item {
+                RespValue::BulkString(Some(b)) => String::from_utf8_lossy(&b).to_string(),
+                other => panic!("expected bulk string, got {:?}", other),
+            })
+            .collect::<std::collections::HashSet<_>>(),
+        other => panic!("expected array, got {:?}", other),
+    };
+    let set: std::collections::HashSet<String> = ["a", "b", "c", "d", "e"]
+        .iter()
+        .map(|m| m.to_string())
+        .collect();
+    run("SADD", &["s", "a", "b", "c", "d", "e"]).unwrap();
+
+    // Three distinct members come out, and only those are removed
+    let popped = members(run("SPOP", &["s", "3"]).unwrap());
+    assert_eq!(popped.len(), 3);
+    assert!(popped.is_subset(&set));
+    assert_eq!(run("SCARD", &["s"]).unwrap(), RespValue::integer(2));
+    for member in &popped {
+        assert_eq!(
+            run("SISMEMBER", &["s", member.as_str()]).unwrap(),
+            RespValue::integer(0)
+        );
+    }
+
+    // Asking for more than is left takes the rest and deletes the key
+    let rest = members(run("SPOP", &["s", "10"]).unwrap());
+    assert_eq!(rest.len(), 2);
+    assert!(rest.is_disjoint(&popped));
+    assert_eq!(run("EXISTS", &["s"]).unwrap(), RespValue::integer(0));
+    assert!(members(run("SPOP", &["s", "3"]).unwrap()).is_empty());
+
+    // Without a count, a single member as a bulk string
+    run("SADD", &["one", "x"]).unwrap();
+    assert_eq!(run("SPOP", &["one"]).unwrap(), RespValue::bulk_string("x"));
+    assert_eq!(run("EXISTS", &["one"]).unwrap(), RespValue::integer(0));
+    assert_eq!(run("SPOP", &["one"]).unwrap(), RespValue::Null);
+
+    run("SADD", &["s", "a"]).unwrap();
+    assert!(members(run("SPOP", &["s", "0"]).unwrap()).is_empty());
+    assert_eq!(run("SCARD", &["s"]).unwrap(), RespValue::integer(1));
+    assert!(run("SPOP", &["s", "-1"]).is_err());
+    assert!(run("SPOP", &["s", "1", "2"]).is_err());
+}
+
 #[test]
 fn test_zset_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // ZADD
     let args = vec![
@@ -446,30 +692,30 @@ fn test_zset_commands() {
         Bytes::from("3"),
         Bytes::from("three"),
     ];
-    let result = executor.execute("ZADD", &args, &mut current_db, client_id);
+    let result = executor.execute("ZADD", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(3));
 
     // ZCARD
     let args = vec![Bytes::from("myzset")];
-    let result = executor.execute("ZCARD", &args, &mut current_db, client_id);
+    let result = executor.execute("ZCARD", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(3));
 
     // ZSCORE
     let args = vec![Bytes::from("myzset"), Bytes::from("two")];
-    let result = executor.execute("ZSCORE", &args, &mut current_db, client_id);
+    let result = executor.execute("ZSCORE", &args, &mut client);
     assert!(result.is_ok());
 
     // ZRANK
     let args = vec![Bytes::from("myzset"), Bytes::from("two")];
-    let result = executor.execute("ZRANK", &args, &mut current_db, client_id);
+    let result = executor.execute("ZRANK", &args, &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(1)); // 0-indexed
 
     // ZRANGE
     let args = vec![Bytes::from("myzset"), Bytes::from("0"), Bytes::from("-1")];
-    let result = executor.execute("ZRANGE", &args, &mut current_db, client_id);
+    let result = executor.execute("ZRANGE", &args, &mut client);
     assert!(result.is_ok());
     if let Ok(RespValue::Array(Some(items))) = result {
         assert_eq!(items.len(), 3);
@@ -482,8 +728,7 @@ fn test_zset_commands() {
 fn test_set_operations() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Create set1
     let args = vec![
@@ -492,9 +737,7 @@ fn test_set_operations() {
         Bytes::from("b"),
         Bytes::from("c"),
     ];
-    executor
-        .execute("SADD", &args, &mut current_db, client_id)
-        .unwrap();
+    executor.execute("SADD", &args, &mut client).unwrap();
 
     // Create set2
     let args = vec![
@@ -503,96 +746,191 @@ fn test_set_operations() {
         Bytes::from("c"),
         Bytes::from("d"),
     ];
-    executor
-        .execute("SADD", &args, &mut current_db, client_id)
-        .unwrap();
+    executor.execute("SADD", &args, &mut client).unwrap();
 
     // SUNION
     let args = vec![Bytes::from("set1"), Bytes::from("set2")];
-    let result = executor.execute("SUNION", &args, &mut current_db, client_id);
+    let result = executor.execute("SUNION", &args, &mut client);
     assert!(result.is_ok());
-    if let Ok(RespValue::Array(Some(items))) = result {
+    if let Ok(RespValue::Set(items)) = result {
         assert_eq!(items.len(), 4); // a, b, c, d
     } else {
-        panic!("Expected array result");
+        panic!("Expected set result");
     }
 
     // SINTER
     let args = vec![Bytes::from("set1"), Bytes::from("set2")];
-    let result = executor.execute("SINTER", &args, &mut current_db, client_id);
+    let result = executor.execute("SINTER", &args, &mut client);
     assert!(result.is_ok());
-    if let Ok(RespValue::Array(Some(items))) = result {
+    if let Ok(RespValue::Set(items)) = result {
         assert_eq!(items.len(), 2); // b, c
     } else {
-        panic!("Expected array result");
+        panic!("Expected set result");
     }
 
     // SDIFF
     let args = vec![Bytes::from("set1"), Bytes::from("set2")];
-    let result = executor.execute("SDIFF", &args, &mut current_db, client_id);
+    let result = executor.execute("SDIFF", &args, &mut client);
     assert!(result.is_ok());
-    if let Ok(RespValue::Array(Some(items))) = result {
+    if let Ok(RespValue::Set(items)) = result {
         assert_eq!(items.len(), 1); // a
     } else {
-        panic!("Expected array result");
+        panic!("Expected set result");
     }
 }
 
 // ================= NEW STRING COMMANDS TESTS =================
 
+#[test]
+fn test_sintercard_counts_the_intersection() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    run("SADD", &["s1", "a", "b", "c", "d"]).unwrap();
+    run("SADD", &["s2", "b", "c", "d", "e"]).unwrap();
+
+    assert_eq!(
+        run("SINTERCARD", &["2", "s1", "s2"]).unwrap(),
+        RespValue::integer(3)
+    );
+    assert_eq!(
+        run("SINTERCARD", &["2", "s1", "s2", "LIMIT", "2"]).unwrap(),
+        RespValue::integer(2)
+    );
+    assert_eq!(
+        run("SINTERCARD", &["2", "s1", "s2", "LIMIT", "0"]).unwrap(),
+        RespValue::integer(3)
+    );
+    assert_eq!(
+        run("SINTERCARD", &["1", "s1"]).unwrap(),
+        RespValue::integer(4)
+    );
+    assert_eq!(
+        run("SINTERCARD", &["2", "s1", "missing"]).unwrap(),
+        RespValue::integer(0)
+    );
+
+    for args in [
+        &["0", "s1"][..],
+        &["3", "s1", "s2"],
+        &["2", "s1", "s2", "LIMIT", "-1"],
+        &["2", "s1", "s2", "LIMIT"],
+    ] {
+        assert!(run("SINTERCARD", args).is_err(), "{:?}", args);
+    }
+}
+
+#[test]
+fn test_set_store_operations_with_empty_result() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    run("SADD", &["s1", "a", "b", "c"]).unwrap();
+    run("SADD", &["s2", "x", "y"]).unwrap();
+    run("SADD", &["s3", "a", "b"]).unwrap();
+    run("SET", &["dest", "old"]).unwrap();
+
+    // An intersection with a missing key is empty no matter the other sets
+    assert_eq!(
+        run("SINTER", &["s1", "missing", "s2"]).unwrap(),
+        RespValue::set(vec![])
+    );
+    assert_eq!(
+        run("SINTER", &["s1", "s3", "s2"]).unwrap(),
+        RespValue::set(vec![])
+    );
+
+    // An empty STORE result deletes the destination, whatever it held
+    assert_eq!(
+        run("SINTERSTORE", &["dest", "s1", "s2"]).unwrap(),
+        RespValue::integer(0)
+    );
+    assert_eq!(run("EXISTS", &["dest"]).unwrap(), RespValue::integer(0));
+
+    assert_eq!(
+        run("SDIFFSTORE", &["dest", "s3", "s1"]).unwrap(),
+        RespValue::integer(0)
+    );
+    assert_eq!(run("EXISTS", &["dest"]).unwrap(), RespValue::integer(0));
+
+    assert_eq!(
+        run("SINTERSTORE", &["dest", "s1", "s3"]).unwrap(),
+        RespValue::integer(2)
+    );
+    assert_eq!(run("SCARD", &["dest"]).unwrap(), RespValue::integer(2));
+    assert_eq!(
+        run("SUNIONSTORE", &["dest", "missing"]).unwrap(),
+        RespValue::integer(0)
+    );
+    assert_eq!(run("EXISTS", &["dest"]).unwrap(), RespValue::integer(0));
+}
+
+#[test]
+fn test_set_operation_result_limit() {
+    let storage = StorageEngine::new_memory(16);
+    let mut sets = SetCommands::new(storage);
+    let args =
+        |args: &[&str]| -> Vec<Bytes> { args.iter().map(|a| Bytes::from(a.to_string())).collect() };
+
+    sets.sadd(&args(&["s1", "a", "b", "c"]), 0).unwrap();
+    sets.sadd(&args(&["s2", "c", "d"]), 0).unwrap();
+    sets.set_result_limit(Some(3));
+
+    assert!(sets
+        .sunion(&args(&["s1", "s2"]), 0)
+        .unwrap_err()
+        .to_string()
+        .contains("exceeds the limit of 3 members"));
+    assert!(sets.sunionstore(&args(&["dest", "s1", "s2"]), 0).is_err());
+    assert_eq!(
+        sets.sinter(&args(&["s1", "s2"]), 0).unwrap(),
+        RespValue::set(vec![RespValue::bulk_string("c")])
+    );
+    assert_eq!(
+        sets.sdiffstore(&args(&["dest", "s1", "s2"]), 0).unwrap(),
+        RespValue::integer(2)
+    );
+}
+
 #[test]
 fn test_incr_decr_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // INCR on non-existent key (starts from 0)
-    let result = executor.execute(
-        "INCR",
-        &[Bytes::from("counter")],
-        &mut current_db,
-        client_id,
-    );
+    let result = executor.execute("INCR", &[Bytes::from("counter")], &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(1));
 
     // INCR again
-    let result = executor.execute(
-        "INCR",
-        &[Bytes::from("counter")],
-        &mut current_db,
-        client_id,
-    );
+    let result = executor.execute("INCR", &[Bytes::from("counter")], &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(2));
 
     // DECR
-    let result = executor.execute(
-        "DECR",
-        &[Bytes::from("counter")],
-        &mut current_db,
-        client_id,
-    );
+    let result = executor.execute("DECR", &[Bytes::from("counter")], &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(1));
 
     // DECR below 0
-    let result = executor.execute(
-        "DECR",
-        &[Bytes::from("counter")],
-        &mut current_db,
-        client_id,
-    );
+    let result = executor.execute("DECR", &[Bytes::from("counter")], &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(0));
 
-    let result = executor.execute(
-        "DECR",
-        &[Bytes::from("counter")],
-        &mut current_db,
-        client_id,
-    );
+    let result = executor.execute("DECR", &[Bytes::from("counter")], &mut client);
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(-1));
 }
@@ -601,15 +939,13 @@ fn test_incr_decr_commands() {
 fn test_incrby_decrby_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // INCRBY
     let result = executor.execute(
         "INCRBY",
         &[Bytes::from("counter"), Bytes::from("10")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(10));
@@ -618,8 +954,7 @@ fn test_incrby_decrby_commands() {
     let result = executor.execute(
         "INCRBY",
         &[Bytes::from("counter"), Bytes::from("5")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(15));
@@ -628,8 +963,7 @@ fn test_incrby_decrby_commands() {
     let result = executor.execute(
         "DECRBY",
         &[Bytes::from("counter"), Bytes::from("3")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(12));
@@ -639,15 +973,13 @@ fn test_incrby_decrby_commands() {
 fn test_incrbyfloat_command() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // INCRBYFLOAT on non-existent key
     let result = executor.execute(
         "INCRBYFLOAT",
         &[Bytes::from("floatkey"), Bytes::from("10.5")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
 
@@ -655,8 +987,7 @@ fn test_incrbyfloat_command() {
     let result = executor.execute(
         "INCRBYFLOAT",
         &[Bytes::from("floatkey"), Bytes::from("0.1")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
 
@@ -664,26 +995,64 @@ fn test_incrbyfloat_command() {
     let result = executor.execute(
         "INCRBYFLOAT",
         &[Bytes::from("floatkey"), Bytes::from("-5.2")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_incrbyfloat_formatting() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    // Exponents are accepted but never written back
+    run("INCRBYFLOAT", &["k", "3.0e3"]).unwrap();
+    assert_eq!(run("GET", &["k"]).unwrap(), RespValue::bulk_string("3000"));
+    run("SET", &["k", "3.0"]).unwrap();
+    run("INCRBYFLOAT", &["k", "0.140"]).unwrap();
+    assert_eq!(run("GET", &["k"]).unwrap(), RespValue::bulk_string("3.14"));
+
+    assert_eq!(
+        run("HINCRBYFLOAT", &["h", "f", "3.0e3"]).unwrap(),
+        RespValue::bulk_string("3000")
+    );
+    assert_eq!(
+        run("HINCRBYFLOAT", &["h", "f", "-2999.5"]).unwrap(),
+        RespValue::bulk_string("0.5")
+    );
+
+    // A non-finite result is refused and leaves the value alone
+    run("SET", &["big", "1.7e308"]).unwrap();
+    let err = run("INCRBYFLOAT", &["big", "1.7e308"]).unwrap_err();
+    assert!(err.to_string().contains("NaN or Infinity"), "{}", err);
+    assert_eq!(
+        run("GET", &["big"]).unwrap(),
+        RespValue::bulk_string("1.7e308")
+    );
+    let err = run("HINCRBYFLOAT", &["h", "f", "inf"]).unwrap_err();
+    assert!(err.to_string().contains("NaN or Infinity"), "{}", err);
+    assert!(run("INCRBYFLOAT", &["k", "nan"]).is_err());
+    assert!(run("HINCRBYFLOAT", &["h", "f", "abc"]).is_err());
+}
+
 #[test]
 fn test_getrange_setrange_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // SET a string
     executor
         .execute(
             "SET",
             &[Bytes::from("mykey"), Bytes::from("Hello World")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -691,8 +1060,7 @@ fn test_getrange_setrange_commands() {
     let result = executor.execute(
         "GETRANGE",
         &[Bytes::from("mykey"), Bytes::from("0"), Bytes::from("4")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     if let RespValue::BulkString(Some(value)) = result.unwrap() {
@@ -705,8 +1073,7 @@ fn test_getrange_setrange_commands() {
     let result = executor.execute(
         "GETRANGE",
         &[Bytes::from("mykey"), Bytes::from("-5"), Bytes::from("-1")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     if let RespValue::BulkString(Some(value)) = result.unwrap() {
@@ -719,14 +1086,13 @@ fn test_getrange_setrange_commands() {
     let result = executor.execute(
         "SETRANGE",
         &[Bytes::from("mykey"), Bytes::from("6"), Bytes::from("Redis")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(11));
 
     // Verify
-    let result = executor.execute("GET", &[Bytes::from("mykey")], &mut current_db, client_id);
+    let result = executor.execute("GET", &[Bytes::from("mykey")], &mut client);
     if let Ok(RespValue::BulkString(Some(value))) = result {
         assert_eq!(value.as_ref(), b"Hello Redis");
     } else {
@@ -738,16 +1104,14 @@ fn test_getrange_setrange_commands() {
 fn test_getex_getdel_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // SET a value
     executor
         .execute(
             "SET",
             &[Bytes::from("mykey"), Bytes::from("Hello")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -755,8 +1119,7 @@ fn test_getex_getdel_commands() {
     let result = executor.execute(
         "GETEX",
         &[Bytes::from("mykey"), Bytes::from("EX"), Bytes::from("100")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     if let RespValue::BulkString(Some(value)) = result.unwrap() {
@@ -766,12 +1129,7 @@ fn test_getex_getdel_commands() {
     }
 
     // GETDEL
-    let result = executor.execute(
-        "GETDEL",
-        &[Bytes::from("mykey")],
-        &mut current_db,
-        client_id,
-    );
+    let result = executor.execute("GETDEL", &[Bytes::from("mykey")], &mut client);
     assert!(result.is_ok());
     if let RespValue::BulkString(Some(value)) = result.unwrap() {
         assert_eq!(value.as_ref(), b"Hello");
@@ -780,7 +1138,7 @@ fn test_getex_getdel_commands() {
     }
 
     // Key should be deleted
-    let result = executor.execute("GET", &[Bytes::from("mykey")], &mut current_db, client_id);
+    let result = executor.execute("GET", &[Bytes::from("mykey")], &mut client);
     assert!(result.is_ok());
     match result.unwrap() {
         RespValue::BulkString(None) | RespValue::Null => {}
@@ -792,15 +1150,13 @@ fn test_getex_getdel_commands() {
 fn test_setnx_setex_psetex_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // SETNX on non-existent key
     let result = executor.execute(
         "SETNX",
         &[Bytes::from("mykey"), Bytes::from("Hello")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(1));
@@ -809,14 +1165,13 @@ fn test_setnx_setex_psetex_commands() {
     let result = executor.execute(
         "SETNX",
         &[Bytes::from("mykey"), Bytes::from("World")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(0));
 
     // Verify value unchanged
-    let result = executor.execute("GET", &[Bytes::from("mykey")], &mut current_db, client_id);
+    let result = executor.execute("GET", &[Bytes::from("mykey")], &mut client);
     if let Ok(RespValue::BulkString(Some(value))) = result {
         assert_eq!(value.as_ref(), b"Hello");
     } else {
@@ -831,8 +1186,7 @@ fn test_setnx_setex_psetex_commands() {
             Bytes::from("10"),
             Bytes::from("value"),
         ],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     if let RespValue::SimpleString(s) = result.unwrap() {
@@ -849,8 +1203,7 @@ fn test_setnx_setex_psetex_commands() {
             Bytes::from("10000"),
             Bytes::from("value"),
         ],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     if let RespValue::SimpleString(s) = result.unwrap() {
@@ -866,8 +1219,7 @@ fn test_setnx_setex_psetex_commands() {
 fn test_lpos_command() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Create a list
     executor
@@ -882,8 +1234,7 @@ fn test_lpos_command() {
                 Bytes::from("d"),
                 Bytes::from("b"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -891,8 +1242,7 @@ fn test_lpos_command() {
     let result = executor.execute(
         "LPOS",
         &[Bytes::from("mylist"), Bytes::from("b")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(1));
@@ -906,8 +1256,7 @@ fn test_lpos_command() {
             Bytes::from("RANK"),
             Bytes::from("2"),
         ],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(3));
@@ -921,8 +1270,7 @@ fn test_lpos_command() {
             Bytes::from("COUNT"),
             Bytes::from("0"),
         ],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     if let RespValue::Array(Some(arr)) = result.unwrap() {
@@ -935,8 +1283,7 @@ fn test_lpos_command() {
     let result = executor.execute(
         "LPOS",
         &[Bytes::from("mylist"), Bytes::from("x")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Null);
@@ -948,8 +1295,7 @@ fn test_lpos_command() {
 fn test_sscan_command() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Create a set
     executor
@@ -962,8 +1308,7 @@ fn test_sscan_command() {
                 Bytes::from("member3"),
                 Bytes::from("other"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -971,8 +1316,7 @@ fn test_sscan_command() {
     let result = executor.execute(
         "SSCAN",
         &[Bytes::from("myset"), Bytes::from("0")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     if let RespValue::Array(Some(items)) = result.unwrap() {
@@ -993,8 +1337,7 @@ fn test_sscan_command() {
             Bytes::from("MATCH"),
             Bytes::from("member*"),
         ],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     if let RespValue::Array(Some(items)) = result.unwrap() {
@@ -1010,8 +1353,7 @@ fn test_sscan_command() {
 fn test_smove_command() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Create source set
     executor
@@ -1023,8 +1365,7 @@ fn test_smove_command() {
                 Bytes::from("b"),
                 Bytes::from("c"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -1033,8 +1374,7 @@ fn test_smove_command() {
         .execute(
             "SADD",
             &[Bytes::from("dst"), Bytes::from("x"), Bytes::from("y")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -1042,26 +1382,24 @@ fn test_smove_command() {
     let result = executor.execute(
         "SMOVE",
         &[Bytes::from("src"), Bytes::from("dst"), Bytes::from("b")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(1));
 
     // Verify source
-    let result = executor.execute("SCARD", &[Bytes::from("src")], &mut current_db, client_id);
+    let result = executor.execute("SCARD", &[Bytes::from("src")], &mut client);
     assert_eq!(result.unwrap(), RespValue::Integer(2));
 
     // Verify destination
-    let result = executor.execute("SCARD", &[Bytes::from("dst")], &mut current_db, client_id);
+    let result = executor.execute("SCARD", &[Bytes::from("dst")], &mut client);
     assert_eq!(result.unwrap(), RespValue::Integer(3));
 
     // SMOVE non-existent member
     let result = executor.execute(
         "SMOVE",
         &[Bytes::from("src"), Bytes::from("dst"), Bytes::from("z")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(0));
@@ -1073,8 +1411,7 @@ fn test_smove_command() {
 fn test_zscan_command() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Create a sorted set
     executor
@@ -1089,8 +1426,7 @@ fn test_zscan_command() {
                 Bytes::from("3"),
                 Bytes::from("three"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -1098,8 +1434,7 @@ fn test_zscan_command() {
     let result = executor.execute(
         "ZSCAN",
         &[Bytes::from("myzset"), Bytes::from("0")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     if let RespValue::Array(Some(items)) = result.unwrap() {
@@ -1116,8 +1451,7 @@ fn test_zscan_command() {
 fn test_zpopmin_zpopmax_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Create a sorted set
     executor
@@ -1132,18 +1466,12 @@ fn test_zpopmin_zpopmax_commands() {
                 Bytes::from("3"),
                 Bytes::from("three"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
     // ZPOPMIN
-    let result = executor.execute(
-        "ZPOPMIN",
-        &[Bytes::from("myzset")],
-        &mut current_db,
-        client_id,
-    );
+    let result = executor.execute("ZPOPMIN", &[Bytes::from("myzset")], &mut client);
     assert!(result.is_ok());
     if let RespValue::Array(Some(items)) = result.unwrap() {
         assert_eq!(items.len(), 2);
@@ -1155,12 +1483,7 @@ fn test_zpopmin_zpopmax_commands() {
     }
 
     // ZPOPMAX
-    let result = executor.execute(
-        "ZPOPMAX",
-        &[Bytes::from("myzset")],
-        &mut current_db,
-        client_id,
-    );
+    let result = executor.execute("ZPOPMAX", &[Bytes::from("myzset")], &mut client);
     assert!(result.is_ok());
     if let RespValue::Array(Some(items)) = result.unwrap() {
         assert_eq!(items.len(), 2);
@@ -1172,21 +1495,57 @@ fn test_zpopmin_zpopmax_commands() {
     }
 
     // Verify only "two" remains
-    let result = executor.execute(
-        "ZCARD",
-        &[Bytes::from("myzset")],
-        &mut current_db,
-        client_id,
-    );
+    let result = executor.execute("ZCARD", &[Bytes::from("myzset")], &mut client);
     assert_eq!(result.unwrap(), RespValue::Integer(1));
 }
 
+#[test]
+fn test_zmpop_pops_from_first_non_empty_sorted_set() {
+    let storage = StorageEngine::new_memory(16);
+    let executor = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+    let pair = |member: &str, score: &str| {
+        RespValue::array(vec![
+            RespValue::bulk_string(member.to_string()),
+            RespValue::bulk_string(score.to_string()),
+        ])
+    };
+
+    run("ZADD", &["second", "1", "one", "2", "two", "3", "three"]).unwrap();
+
+    // The first key is missing, so the second supplies the members
+    assert_eq!(
+        run("ZMPOP", &["2", "first", "second", "MIN"]).unwrap(),
+        RespValue::array(vec![
+            RespValue::bulk_string("second"),
+            RespValue::array(vec![pair("one", "1")]),
+        ])
+    );
+    assert_eq!(
+        run("ZMPOP", &["2", "first", "second", "max", "COUNT", "10"]).unwrap(),
+        RespValue::array(vec![
+            RespValue::bulk_string("second"),
+            RespValue::array(vec![pair("three", "3"), pair("two", "2")]),
+        ])
+    );
+    assert_eq!(run("EXISTS", &["second"]).unwrap(), RespValue::integer(0));
+    assert_eq!(
+        run("ZMPOP", &["2", "first", "second", "MIN"]).unwrap(),
+        RespValue::null_array()
+    );
+    assert!(run("ZMPOP", &["1", "k", "LEFT"]).is_err());
+}
+
 #[test]
 fn test_zrangebylex_zrevrangebylex_zlexcount_commands() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Create a sorted set with same score for lex ordering
     executor
@@ -1205,8 +1564,7 @@ fn test_zrangebylex_zrevrangebylex_zlexcount_commands() {
                 Bytes::from("0"),
                 Bytes::from("e"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -1214,8 +1572,7 @@ fn test_zrangebylex_zrevrangebylex_zlexcount_commands() {
     let result = executor.execute(
         "ZRANGEBYLEX",
         &[Bytes::from("myzset"), Bytes::from("[b"), Bytes::from("[d")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     if let RespValue::Array(Some(items)) = result.unwrap() {
@@ -1228,8 +1585,7 @@ fn test_zrangebylex_zrevrangebylex_zlexcount_commands() {
     let result = executor.execute(
         "ZRANGEBYLEX",
         &[Bytes::from("myzset"), Bytes::from("(a"), Bytes::from("(e")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     if let RespValue::Array(Some(items)) = result.unwrap() {
@@ -1242,8 +1598,7 @@ fn test_zrangebylex_zrevrangebylex_zlexcount_commands() {
     let result = executor.execute(
         "ZREVRANGEBYLEX",
         &[Bytes::from("myzset"), Bytes::from("[d"), Bytes::from("[b")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     if let RespValue::Array(Some(items)) = result.unwrap() {
@@ -1256,8 +1611,7 @@ fn test_zrangebylex_zrevrangebylex_zlexcount_commands() {
     let result = executor.execute(
         "ZLEXCOUNT",
         &[Bytes::from("myzset"), Bytes::from("-"), Bytes::from("+")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(5));
@@ -1266,8 +1620,7 @@ fn test_zrangebylex_zrevrangebylex_zlexcount_commands() {
     let result = executor.execute(
         "ZLEXCOUNT",
         &[Bytes::from("myzset"), Bytes::from("[b"), Bytes::from("[d")],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), RespValue::Integer(3));
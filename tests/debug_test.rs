@@ -0,0 +1,125 @@
+//! Tests for the DEBUG command
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+/// The `expired_keys` counter of INFO stats
+async fn expired_keys(client: &mut TestClient) -> u64 {
+    let info = match client.command(&["INFO", "stats"]).await {
+        RespValue::BulkString(Some(info)) => String::from_utf8_lossy(&info).to_string(),
+        reply => panic!("expected bulk string, got {:?}", reply),
+    };
+    info.lines()
+        .find_map(|line| line.strip_prefix("expired_keys:"))
+        .expect("no expired_keys in INFO stats")
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_debug_sleep_blocks_for_the_duration() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    let start = Instant::now();
+    assert_eq!(
+        client.command(&["DEBUG", "SLEEP", "0.2"]).await,
+        RespValue::ok()
+    );
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    let reply = client.command(&["DEBUG", "SLEEP", "soon"]).await;
+    assert!(
+        matches!(&reply, RespValue::Error(e) if e.contains("not a valid float")),
+        "{:?}",
+        reply
+    );
+}
+
+#[tokio::test]
+async fn test_debug_set_active_expire_pauses_the_sweeper() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    assert_eq!(
+        client.command(&["DEBUG", "SET-ACTIVE-EXPIRE", "0"]).await,
+        RespValue::ok()
+    );
+    client.command(&["SET", "short", "v", "PX", "50"]).await;
+
+    // The key is past its TTL, but nothing has read it or swept it
+    sleep(Duration::from_millis(400)).await;
+    assert_eq!(expired_keys(&mut client).await, 0);
+
+    assert_eq!(
+        client.command(&["DEBUG", "SET-ACTIVE-EXPIRE", "1"]).await,
+        RespValue::ok()
+    );
+    let mut swept = false;
+    for _ in 0..20 {
+        sleep(Duration::from_millis(100)).await;
+        if expired_keys(&mut client).await == 1 {
+            swept = true;
+            break;
+        }
+    }
+    assert!(swept, "sweeper did not remove the expired key");
+    assert_eq!(client.command(&["DBSIZE"]).await, RespValue::integer(0));
+}
+
+#[tokio::test]
+async fn test_debug_object_and_unknown_subcommands() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    client.command(&["SET", "counter", "12345"]).await;
+    client.command(&["RPUSH", "list", "a", "bc"]).await;
+
+    let reply = client.command(&["DEBUG", "OBJECT", "counter"]).await;
+    assert!(
+        matches!(&reply, RespValue::SimpleString(s)
+            if s.contains("encoding:int") && s.contains("serializedlength:")),
+        "{:?}",
+        reply
+    );
+    let reply = client.command(&["DEBUG", "OBJECT", "list"]).await;
+    assert!(
+        matches!(&reply, RespValue::SimpleString(s)
+            if s.contains("encoding:quicklist") && s.contains("ql_uncompressed_size:3")),
+        "{:?}",
+        reply
+    );
+
+    let reply = client.command(&["DEBUG", "OBJECT", "missing"]).await;
+    assert!(
+        matches!(&reply, RespValue::Error(e) if e.contains("no such key")),
+        "{:?}",
+        reply
+    );
+    assert_eq!(client.command(&["DEBUG", "JMAP"]).await, RespValue::ok());
+    let reply = client.command(&["DEBUG", "SEGFAULT"]).await;
+    assert!(
+        matches!(&reply, RespValue::Error(e) if e.contains("unknown subcommand 'SEGFAULT'")),
+        "{:?}",
+        reply
+    );
+}
+
+#[tokio::test]
+async fn test_debug_dbsize_exact_counts_live_keys() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    client.command(&["SET", "a", "1"]).await;
+    client.command(&["SET", "b", "2"]).await;
+    client.command(&["DEL", "a"]).await;
+    assert_eq!(
+        client.command(&["DEBUG", "DBSIZE-EXACT"]).await,
+        RespValue::integer(1)
+    );
+    assert_eq!(client.command(&["DBSIZE"]).await, RespValue::integer(1));
+}
@@ -0,0 +1,61 @@
+//! Tests for the wording of error replies, which clients match on
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+
+#[tokio::test]
+async fn test_error_replies_use_redis_codes_and_wording() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    client.command(&["RPUSH", "list", "a"]).await;
+    assert_eq!(
+        client.command(&["GET", "list"]).await,
+        RespValue::error("WRONGTYPE Operation against a key holding the wrong kind of value")
+    );
+    assert_eq!(
+        client.command(&["SADD", "list", "m"]).await,
+        RespValue::error("WRONGTYPE Operation against a key holding the wrong kind of value")
+    );
+
+    // Arity errors name the command in lower case, however it was sent
+    assert_eq!(
+        client.command(&["GET"]).await,
+        RespValue::error("ERR wrong number of arguments for 'get' command")
+    );
+    assert_eq!(
+        client.command(&["hset", "h", "f"]).await,
+        RespValue::error("ERR wrong number of arguments for 'hset' command")
+    );
+
+    assert_eq!(
+        client.command(&["NoSuchCommand"]).await,
+        RespValue::error("ERR unknown command 'NoSuchCommand', with args beginning with: ")
+    );
+}
+
+#[tokio::test]
+async fn test_unknown_command_echoes_its_first_arguments() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    assert_eq!(
+        client.command(&["foo", "bar", "baz"]).await,
+        RespValue::error("ERR unknown command 'foo', with args beginning with: 'bar' 'baz' ")
+    );
+
+    // Only the first 128 characters or so of the arguments are echoed
+    let long = "x".repeat(100);
+    let reply = client.command(&["foo", &long, &long, "never"]).await;
+    let RespValue::Error(e) = &reply else {
+        panic!("expected an error, got {:?}", reply);
+    };
+    let expected = format!(
+        "ERR unknown command 'foo', with args beginning with: '{}' '{}' ",
+        long,
+        "x".repeat(128 - 103)
+    );
+    assert_eq!(e, &expected);
+}
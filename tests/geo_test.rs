@@ -0,0 +1,208 @@
+//! Tests for the GEO commands
+
+use aikv::command::{ClientContext, CommandExecutor};
+use aikv::protocol::RespValue;
+use aikv::StorageEngine;
+use bytes::Bytes;
+
+fn bulk_strings(items: &[&str]) -> RespValue {
+    RespValue::array(
+        items
+            .iter()
+            .map(|i| RespValue::bulk_string(i.to_string()))
+            .collect(),
+    )
+}
+
+fn sicily() -> CommandExecutor {
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let args: Vec<Bytes> = [
+        "Sicily",
+        "13.361389",
+        "38.115556",
+        "Palermo",
+        "15.087269",
+        "37.502669",
+        "Catania",
+    ]
+    .iter()
+    .map(|a| Bytes::from(a.to_string()))
+    .collect();
+    assert_eq!(
+        executor
+            .execute("GEOADD", &args, &mut ClientContext::new(1))
+            .unwrap(),
+        RespValue::integer(2)
+    );
+    executor
+}
+
+#[test]
+fn test_geoadd_geopos_and_geodist() {
+    let executor = sicily();
+    let mut client = ClientContext::new(1);
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    assert_eq!(
+        run("GEODIST", &["Sicily", "Palermo", "Catania"]).unwrap(),
+        RespValue::bulk_string("166274.1516")
+    );
+    assert_eq!(
+        run("GEODIST", &["Sicily", "Palermo", "Catania", "km"]).unwrap(),
+        RespValue::bulk_string("166.2742")
+    );
+    assert_eq!(
+        run("GEODIST", &["Sicily", "Palermo", "Catania", "mi"]).unwrap(),
+        RespValue::bulk_string("103.3182")
+    );
+    assert_eq!(
+        run("GEODIST", &["Sicily", "Palermo", "Atlantis"]).unwrap(),
+        RespValue::null_bulk_string()
+    );
+    assert!(run("GEODIST", &["Sicily", "Palermo", "Catania", "league"]).is_err());
+
+    // Positions come back from the geohash, accurate to well under a meter
+    let reply = run("GEOPOS", &["Sicily", "Palermo", "Atlantis"]).unwrap();
+    let RespValue::Array(Some(positions)) = &reply else {
+        panic!("expected array, got {:?}", reply);
+    };
+    let RespValue::Array(Some(palermo)) = &positions[0] else {
+        panic!("expected a position, got {:?}", positions[0]);
+    };
+    let coordinate = |value: &RespValue| match value {
+        RespValue::BulkString(Some(b)) => String::from_utf8_lossy(b).parse::<f64>().unwrap(),
+        other => panic!("expected bulk string, got {:?}", other),
+    };
+    assert!((coordinate(&palermo[0]) - 13.361389).abs() < 1e-5);
+    assert!((coordinate(&palermo[1]) - 38.115556).abs() < 1e-5);
+    assert_eq!(positions[1], RespValue::null_array());
+
+    // The points are an ordinary sorted set scored by geohash
+    assert_eq!(
+        run("ZSCORE", &["Sicily", "Palermo"]).unwrap(),
+        RespValue::double(3479099956230698.0)
+    );
+    assert_eq!(
+        run("GEOADD", &["Sicily", "NX", "13.5", "38.1", "Palermo"]).unwrap(),
+        RespValue::integer(0)
+    );
+    assert!(run("GEOADD", &["Sicily", "200", "38", "Nowhere"]).is_err());
+}
+
+#[test]
+fn test_geosearch_by_radius_and_box() {
+    let executor = sicily();
+    let mut client = ClientContext::new(1);
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    assert_eq!(
+        run(
+            "GEOSEARCH",
+            &["Sicily", "FROMLONLAT", "15", "37", "BYRADIUS", "100", "km"]
+        )
+        .unwrap(),
+        bulk_strings(&["Catania"])
+    );
+    assert_eq!(
+        run(
+            "GEOSEARCH",
+            &[
+                "Sicily",
+                "FROMLONLAT",
+                "15",
+                "37",
+                "BYRADIUS",
+                "200",
+                "km",
+                "ASC"
+            ]
+        )
+        .unwrap(),
+        bulk_strings(&["Catania", "Palermo"])
+    );
+    assert_eq!(
+        run(
+            "GEOSEARCH",
+            &[
+                "Sicily",
+                "FROMLONLAT",
+                "15",
+                "37",
+                "BYBOX",
+                "400",
+                "400",
+                "km",
+                "DESC",
+                "WITHDIST"
+            ]
+        )
+        .unwrap(),
+        RespValue::array(vec![
+            bulk_strings(&["Palermo", "190.4424"]),
+            bulk_strings(&["Catania", "56.4413"]),
+        ])
+    );
+
+    // COUNT without ANY returns the nearest
+    assert_eq!(
+        run(
+            "GEOSEARCH",
+            &[
+                "Sicily",
+                "FROMMEMBER",
+                "Palermo",
+                "BYRADIUS",
+                "500",
+                "km",
+                "COUNT",
+                "1"
+            ]
+        )
+        .unwrap(),
+        bulk_strings(&["Palermo"])
+    );
+
+    assert_eq!(
+        run(
+            "GEOSEARCH",
+            &["Nowhere", "FROMLONLAT", "15", "37", "BYRADIUS", "1", "m"]
+        )
+        .unwrap(),
+        RespValue::array(Vec::new())
+    );
+    for args in [
+        vec!["Sicily", "FROMMEMBER", "Atlantis", "BYRADIUS", "1", "m"],
+        vec![
+            "Sicily",
+            "FROMLONLAT",
+            "15",
+            "37",
+            "BYRADIUS",
+            "1",
+            "m",
+            "BYBOX",
+            "1",
+            "1",
+            "m",
+        ],
+        vec!["Sicily", "BYRADIUS", "1", "m", "ASC", "WITHHASH"],
+        vec![
+            "Sicily",
+            "FROMLONLAT",
+            "15",
+            "37",
+            "BYRADIUS",
+            "1",
+            "m",
+            "ANY",
+        ],
+    ] {
+        assert!(run("GEOSEARCH", &args).is_err(), "{:?}", args);
+    }
+}
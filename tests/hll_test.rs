@@ -0,0 +1,116 @@
+//! Tests for the HyperLogLog commands
+
+use aikv::command::{ClientContext, CommandExecutor};
+use aikv::protocol::RespValue;
+use aikv::StorageEngine;
+use bytes::Bytes;
+
+fn count(reply: RespValue) -> f64 {
+    match reply {
+        RespValue::Integer(n) => n as f64,
+        other => panic!("expected integer, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_pfadd_and_pfcount() {
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let mut client = ClientContext::new(1);
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    assert_eq!(
+        run("PFADD", &["hll", "a", "b", "c", "d", "e", "f", "g"]).unwrap(),
+        RespValue::integer(1)
+    );
+    assert_eq!(run("PFCOUNT", &["hll"]).unwrap(), RespValue::integer(7));
+    // Nothing new, so no register moves
+    assert_eq!(
+        run("PFADD", &["hll", "a", "b"]).unwrap(),
+        RespValue::integer(0)
+    );
+    // PFADD with no elements only reports creating the key
+    assert_eq!(run("PFADD", &["empty"]).unwrap(), RespValue::integer(1));
+    assert_eq!(run("PFADD", &["empty"]).unwrap(), RespValue::integer(0));
+    assert_eq!(run("PFCOUNT", &["empty"]).unwrap(), RespValue::integer(0));
+    assert_eq!(run("PFCOUNT", &["missing"]).unwrap(), RespValue::integer(0));
+
+    // The HLL is a dense 12KB string
+    match run("GET", &["hll"]).unwrap() {
+        RespValue::BulkString(Some(bytes)) => {
+            assert_eq!(bytes.len(), 16 + 12288);
+            assert!(bytes.starts_with(b"HYLL"));
+        }
+        other => panic!("expected bulk string, got {:?}", other),
+    }
+
+    let args: Vec<Bytes> = std::iter::once(Bytes::from("big"))
+        .chain((0..10000).map(|i| Bytes::from(format!("element:{}", i))))
+        .collect();
+    assert_eq!(
+        executor
+            .execute("PFADD", &args, &mut ClientContext::new(1))
+            .unwrap(),
+        RespValue::integer(1)
+    );
+    let estimate = count(
+        executor
+            .execute("PFCOUNT", &args[..1], &mut ClientContext::new(1))
+            .unwrap(),
+    );
+    assert!(
+        (estimate - 10000.0).abs() / 10000.0 < 0.02,
+        "estimate {} is off by more than 2%",
+        estimate
+    );
+}
+
+#[test]
+fn test_pfmerge_approximates_the_union() {
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let add = |key: &str, prefix: &str| {
+        let args: Vec<Bytes> = std::iter::once(Bytes::from(key.to_string()))
+            .chain((0..5000).map(|i| Bytes::from(format!("{}:{}", prefix, i))))
+            .collect();
+        executor
+            .execute("PFADD", &args, &mut ClientContext::new(1))
+            .unwrap();
+    };
+    add("left", "a");
+    add("right", "b");
+
+    let mut client = ClientContext::new(1);
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+    assert_eq!(
+        run("PFMERGE", &["union", "left", "right", "missing"]).unwrap(),
+        RespValue::ok()
+    );
+    let union = count(run("PFCOUNT", &["union"]).unwrap());
+    assert!(
+        (union - 10000.0).abs() / 10000.0 < 0.02,
+        "union estimate {} is off by more than 2%",
+        union
+    );
+    // Counting several keys merges them on the fly
+    assert_eq!(count(run("PFCOUNT", &["left", "right"]).unwrap()), union);
+
+    // Merging into an existing destination keeps what it already had
+    run("PFADD", &["dest", "only-in-dest"]).unwrap();
+    run("PFMERGE", &["dest", "dest"]).unwrap();
+    assert_eq!(run("PFCOUNT", &["dest"]).unwrap(), RespValue::integer(1));
+
+    // Other strings and types are rejected
+    run("SET", &["plain", "not an hll"]).unwrap();
+    run("LPUSH", &["list", "x"]).unwrap();
+    for key in ["plain", "list"] {
+        assert!(run("PFADD", &[key, "x"]).is_err());
+        assert!(run("PFCOUNT", &[key]).is_err());
+        assert!(run("PFMERGE", &["union", key]).is_err());
+    }
+    assert!(run("PFMERGE", &["plain", "left"]).is_err());
+}
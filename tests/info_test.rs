@@ -0,0 +1,88 @@
+//! Tests for INFO section filtering and the keyspace section
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+
+async fn info(client: &mut TestClient, sections: &[&str]) -> String {
+    let mut args = vec!["INFO"];
+    args.extend_from_slice(sections);
+    match client.command(&args).await {
+        RespValue::BulkString(Some(info)) => String::from_utf8_lossy(&info).to_string(),
+        reply => panic!("expected bulk string, got {:?}", reply),
+    }
+}
+
+/// Section headers (`# Name`) of an INFO reply, in order
+fn headers(info: &str) -> Vec<&str> {
+    info.lines()
+        .filter_map(|line| line.strip_prefix("# "))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_info_keyspace_counts_keys_and_expires() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    assert_eq!(info(&mut client, &["keyspace"]).await, "# Keyspace");
+
+    client.command(&["SET", "a", "1"]).await;
+    client.command(&["SET", "b", "2", "EX", "100"]).await;
+    client.command(&["SELECT", "3"]).await;
+    client.command(&["RPUSH", "list", "x"]).await;
+
+    let keyspace = info(&mut client, &["keyspace"]).await;
+    let lines: Vec<&str> = keyspace.lines().collect();
+    assert_eq!(lines[0], "# Keyspace");
+    assert!(
+        lines[1].starts_with("db0:keys=2,expires=1,avg_ttl="),
+        "{}",
+        keyspace
+    );
+    let avg_ttl: u64 = lines[1].rsplit('=').next().unwrap().parse().unwrap();
+    assert!(avg_ttl > 90_000 && avg_ttl <= 100_000, "{}", keyspace);
+    assert_eq!(lines[2], "db3:keys=1,expires=0,avg_ttl=0");
+    assert_eq!(lines.len(), 3);
+}
+
+#[tokio::test]
+async fn test_info_returns_only_requested_sections() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    let replication = info(&mut client, &["replication"]).await;
+    assert_eq!(headers(&replication), vec!["Replication"]);
+    assert!(replication.contains("role:master\r\n"), "{}", replication);
+
+    let cpu = info(&mut client, &["CPU"]).await;
+    assert_eq!(headers(&cpu), vec!["CPU"]);
+    assert!(cpu.contains("used_cpu_user:"), "{}", cpu);
+
+    // Several sections come back in the order asked, each once
+    let both = info(&mut client, &["persistence", "clients", "persistence"]).await;
+    assert_eq!(headers(&both), vec!["Persistence", "Clients"]);
+    assert!(both.contains("aof_enabled:0\r\n"), "{}", both);
+    assert!(both.contains("maxclients:10000\r\n"), "{}", both);
+
+    assert_eq!(info(&mut client, &["no-such-section"]).await, "");
+
+    let default = info(&mut client, &[]).await;
+    assert_eq!(
+        headers(&default),
+        vec![
+            "Server",
+            "Clients",
+            "Memory",
+            "Persistence",
+            "Stats",
+            "Replication",
+            "CPU",
+            "Cluster",
+            "Keyspace"
+        ]
+    );
+    let all = info(&mut client, &["all"]).await;
+    assert!(headers(&all).contains(&"Commandstats"), "{}", all);
+}
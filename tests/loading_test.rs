@@ -0,0 +1,62 @@
+//! Tests for answering -LOADING while the dataset is loaded on startup
+
+mod common;
+
+use aikv::protocol::RespValue;
+use aikv::{Server, StorageEngine};
+use bytes::Bytes;
+use common::{free_addr, wait_for_listener, TestClient};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+fn info_text(reply: RespValue) -> String {
+    match reply {
+        RespValue::BulkString(Some(info)) => String::from_utf8_lossy(&info).to_string(),
+        other => panic!("expected bulk string, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_data_commands_get_loading_until_the_dataset_is_loaded() {
+    let addr = free_addr();
+    let storage = StorageEngine::new_memory(16);
+    let server = Server::new(addr.clone(), storage.clone());
+    let loading = server.loading_flag();
+    loading.store(true, Ordering::SeqCst);
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    wait_for_listener(&addr).await;
+
+    // A slow load that finishes while a client is already connected
+    let load = {
+        let loading = loading.clone();
+        tokio::task::spawn_blocking(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            storage
+                .set_in_db(0, "k".to_string(), Bytes::from("v"))
+                .unwrap();
+            loading.store(false, Ordering::SeqCst);
+        })
+    };
+
+    let mut client = TestClient::connect(&addr).await;
+    let loading_error = RespValue::error("LOADING AiKv is loading the dataset in memory");
+    assert_eq!(client.command(&["GET", "k"]).await, loading_error);
+    assert_eq!(client.command(&["SET", "k", "early"]).await, loading_error);
+    assert_eq!(
+        client.command(&["PING"]).await,
+        RespValue::simple_string("PONG")
+    );
+    let info = info_text(client.command(&["INFO", "persistence"]).await);
+    assert!(info.contains("loading:1"), "{}", info);
+
+    load.await.unwrap();
+    assert_eq!(
+        client.command(&["GET", "k"]).await,
+        RespValue::bulk_string("v")
+    );
+    assert_eq!(client.command(&["SET", "k", "late"]).await, RespValue::ok());
+    let info = info_text(client.command(&["INFO", "persistence"]).await);
+    assert!(info.contains("loading:0"), "{}", info);
+}
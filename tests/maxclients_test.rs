@@ -0,0 +1,89 @@
+//! Tests for rejecting connections beyond `maxclients`
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+#[tokio::test]
+async fn test_connection_over_maxclients_is_rejected() {
+    let addr = start_server(|server| server.set_maxclients(2)).await;
+    let mut first = TestClient::connect(&addr).await;
+    let mut second = TestClient::connect(&addr).await;
+    for client in [&mut first, &mut second] {
+        assert_eq!(
+            client.command(&["PING"]).await,
+            RespValue::simple_string("PONG")
+        );
+    }
+
+    // The third client gets the error and is disconnected
+    let mut rejected = TestClient::connect(&addr).await;
+    let reply = timeout(Duration::from_secs(2), rejected.read_reply())
+        .await
+        .expect("no reply from a rejected connection");
+    assert_eq!(
+        reply,
+        Some(RespValue::Error(
+            "ERR max number of clients reached".to_string()
+        ))
+    );
+    assert_eq!(rejected.read_reply().await, None);
+
+    // Clients already connected are unaffected
+    assert_eq!(
+        first.command(&["PING"]).await,
+        RespValue::simple_string("PONG")
+    );
+
+    // A disconnect frees a slot
+    drop(second);
+    let mut accepted = None;
+    for _ in 0..50 {
+        sleep(Duration::from_millis(20)).await;
+        let mut client = TestClient::connect(&addr).await;
+        client.send(&["PING"]).await;
+        if client.read_reply().await == Some(RespValue::simple_string("PONG")) {
+            accepted = Some(client);
+            break;
+        }
+    }
+    assert!(accepted.is_some(), "slot was not freed on disconnect");
+}
+
+#[tokio::test]
+async fn test_maxclients_is_a_runtime_config() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    assert_eq!(
+        client.command(&["CONFIG", "GET", "maxclients"]).await,
+        RespValue::array(vec![
+            RespValue::bulk_string("maxclients"),
+            RespValue::bulk_string("10000")
+        ])
+    );
+    assert_eq!(
+        client.command(&["CONFIG", "SET", "maxclients", "1"]).await,
+        RespValue::ok()
+    );
+    let mut other = TestClient::connect(&addr).await;
+    let reply = timeout(Duration::from_secs(2), other.read_reply())
+        .await
+        .expect("no reply from a rejected connection");
+    assert_eq!(
+        reply,
+        Some(RespValue::Error(
+            "ERR max number of clients reached".to_string()
+        ))
+    );
+
+    let reply = client.command(&["CONFIG", "SET", "maxclients", "0"]).await;
+    assert!(
+        matches!(&reply, RespValue::Error(e) if e.contains("positive integer")),
+        "{:?}",
+        reply
+    );
+}
@@ -0,0 +1,101 @@
+//! Tests for the Prometheus `/metrics` HTTP endpoint
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::sleep;
+
+/// Issue a GET request and return the status line and body
+async fn http_get(addr: &str, path: &str) -> (String, String) {
+    let mut stream = None;
+    for _ in 0..100 {
+        if let Ok(s) = TcpStream::connect(addr).await {
+            stream = Some(s);
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    let mut stream = stream.expect("metrics endpoint did not start");
+
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, addr);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").expect("no header end");
+    (head.lines().next().unwrap().to_string(), body.to_string())
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint_reports_commands() {
+    let metrics_port = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .unwrap()
+        .port();
+    let metrics_addr = format!("127.0.0.1:{}", metrics_port);
+
+    let addr = start_server(|server| server.set_metrics_addr(metrics_addr.clone())).await;
+    let mut client = TestClient::connect(&addr).await;
+    client.command(&["SET", "a", "1"]).await;
+    client.command(&["SET", "b", "2"]).await;
+    client.command(&["GET", "a"]).await;
+
+    let (status, body) = http_get(&metrics_addr, "/metrics").await;
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert!(
+        body.contains("aikv_commands_by_type{command=\"SET\"} 2"),
+        "{}",
+        body
+    );
+    assert!(
+        body.contains("aikv_command_duration_seconds_count{command=\"GET\"} 1"),
+        "{}",
+        body
+    );
+    assert!(body.contains("aikv_db_keys{db=\"0\"} 2"), "{}", body);
+
+    let (status, _) = http_get(&metrics_addr, "/other").await;
+    assert_eq!(status, "HTTP/1.1 404 Not Found");
+}
+
+#[tokio::test]
+async fn test_info_commandstats_reports_calls_and_latency() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+    for i in 0..50 {
+        client.command(&["SET", "key", &i.to_string()]).await;
+    }
+
+    let info = match client.command(&["INFO", "commandstats"]).await {
+        RespValue::BulkString(Some(info)) => String::from_utf8_lossy(&info).to_string(),
+        other => panic!("expected bulk string, got {:?}", other),
+    };
+    assert!(info.starts_with("# Commandstats"), "{}", info);
+    let line = info
+        .lines()
+        .find(|line| line.starts_with("cmdstat_set:"))
+        .unwrap_or_else(|| panic!("no cmdstat_set in {}", info));
+    assert!(line.contains("calls=50,"), "{}", line);
+    let usec_per_call: f64 = line
+        .split(',')
+        .find_map(|field| field.strip_prefix("usec_per_call="))
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(usec_per_call > 0.0, "{}", line);
+    assert!(!info.contains("# Latencystats"), "{}", info);
+
+    let info = match client.command(&["INFO", "latencystats"]).await {
+        RespValue::BulkString(Some(info)) => String::from_utf8_lossy(&info).to_string(),
+        other => panic!("expected bulk string, got {:?}", other),
+    };
+    assert!(
+        info.contains("latency_percentiles_usec_set:p50="),
+        "{}",
+        info
+    );
+}
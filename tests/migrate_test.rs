@@ -0,0 +1,207 @@
+//! Tests for MIGRATE between two servers
+//!
+//! MIGRATE transfers its keys on a blocking thread, so both servers can
+//! share the test's single-threaded runtime.
+
+mod common;
+
+use aikv::persistence::{load_aof, AofSyncPolicy, AofWriter};
+use aikv::protocol::RespValue;
+use common::{free_addr, start_server, TestClient};
+use tempfile::TempDir;
+
+fn error_message(reply: RespValue) -> String {
+    match reply {
+        RespValue::Error(e) => e,
+        other => panic!("expected error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_migrate_moves_a_hash_to_another_server() {
+    let source_addr = start_server(|_| {}).await;
+    let target_addr = start_server(|_| {}).await;
+    let (host, port) = target_addr.split_once(':').unwrap();
+    let mut source = TestClient::connect(&source_addr).await;
+    let mut target = TestClient::connect(&target_addr).await;
+
+    assert_eq!(
+        source
+            .command(&["HSET", "user:1", "name", "ada", "lang", "rust"])
+            .await,
+        RespValue::integer(2)
+    );
+    source.command(&["PEXPIRE", "user:1", "100000"]).await;
+
+    assert_eq!(
+        source
+            .command(&["MIGRATE", host, port, "user:1", "0", "5000"])
+            .await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        source.command(&["EXISTS", "user:1"]).await,
+        RespValue::integer(0)
+    );
+    assert_eq!(
+        target.command(&["HGET", "user:1", "name"]).await,
+        RespValue::bulk_string("ada")
+    );
+    assert_eq!(
+        target.command(&["HGET", "user:1", "lang"]).await,
+        RespValue::bulk_string("rust")
+    );
+    // The TTL travels with the key
+    match target.command(&["PTTL", "user:1"]).await {
+        RespValue::Integer(ttl) => assert!(ttl > 0 && ttl <= 100000, "ttl {}", ttl),
+        other => panic!("expected integer, got {:?}", other),
+    }
+
+    // COPY leaves the source key in place, and the destination db is honoured
+    source.command(&["SET", "kept", "v"]).await;
+    assert_eq!(
+        source
+            .command(&["MIGRATE", host, port, "kept", "2", "5000", "COPY"])
+            .await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        source.command(&["GET", "kept"]).await,
+        RespValue::bulk_string("v")
+    );
+    assert_eq!(
+        target.command(&["GET", "kept"]).await,
+        RespValue::null_bulk_string()
+    );
+    target.command(&["SELECT", "2"]).await;
+    assert_eq!(
+        target.command(&["GET", "kept"]).await,
+        RespValue::bulk_string("v")
+    );
+}
+
+#[tokio::test]
+async fn test_migrate_keys_option_nokey_and_errors() {
+    let source_addr = start_server(|_| {}).await;
+    let target_addr = start_server(|_| {}).await;
+    let (host, port) = target_addr.split_once(':').unwrap();
+    let mut source = TestClient::connect(&source_addr).await;
+    let mut target = TestClient::connect(&target_addr).await;
+
+    source.command(&["SET", "k1", "v1"]).await;
+    source.command(&["SET", "k2", "v2"]).await;
+
+    // Missing keys among KEYS are skipped
+    assert_eq!(
+        source
+            .command(&["MIGRATE", host, port, "", "0", "5000", "KEYS", "k1", "k2", "nope"])
+            .await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        source.command(&["EXISTS", "k1", "k2"]).await,
+        RespValue::integer(0)
+    );
+    assert_eq!(
+        target.command(&["GET", "k1"]).await,
+        RespValue::bulk_string("v1")
+    );
+    assert_eq!(
+        target.command(&["GET", "k2"]).await,
+        RespValue::bulk_string("v2")
+    );
+
+    assert_eq!(
+        source
+            .command(&["MIGRATE", host, port, "nope", "0", "5000"])
+            .await,
+        RespValue::simple_string("NOKEY")
+    );
+
+    // An existing target key needs REPLACE, and the source keeps its key until then
+    source.command(&["SET", "k1", "new"]).await;
+    let error = error_message(
+        source
+            .command(&["MIGRATE", host, port, "k1", "0", "5000"])
+            .await,
+    );
+    assert!(error.contains("BUSYKEY"), "{}", error);
+    assert_eq!(
+        source.command(&["GET", "k1"]).await,
+        RespValue::bulk_string("new")
+    );
+    assert_eq!(
+        source
+            .command(&["MIGRATE", host, port, "k1", "0", "5000", "REPLACE"])
+            .await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        target.command(&["GET", "k1"]).await,
+        RespValue::bulk_string("new")
+    );
+
+    // KEYS needs the single key argument to be empty
+    source.command(&["SET", "k3", "v3"]).await;
+    assert!(error_message(
+        source
+            .command(&["MIGRATE", host, port, "k3", "0", "5000", "KEYS", "k3"])
+            .await
+    )
+    .contains("empty string"));
+
+    // Nothing listening: an IOERR, and the key stays
+    let unreachable = free_addr();
+    let (host, port) = unreachable.split_once(':').unwrap();
+    let error = error_message(
+        source
+            .command(&["MIGRATE", host, port, "k3", "0", "500"])
+            .await,
+    );
+    assert!(error.starts_with("IOERR"), "{}", error);
+    assert_eq!(
+        source.command(&["GET", "k3"]).await,
+        RespValue::bulk_string("v3")
+    );
+}
+
+#[tokio::test]
+async fn test_migrate_logs_keys_moved_before_a_failure() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("appendonly.aof");
+    let source_addr = start_server(|server| {
+        server.set_aof_writer(AofWriter::new(&path, AofSyncPolicy::Always).unwrap())
+    })
+    .await;
+    let target_addr = start_server(|_| {}).await;
+    let (host, port) = target_addr.split_once(':').unwrap();
+    let mut source = TestClient::connect(&source_addr).await;
+    let mut target = TestClient::connect(&target_addr).await;
+
+    source.command(&["SET", "k1", "v1"]).await;
+    source.command(&["SET", "k2", "v2"]).await;
+    target.command(&["SET", "k2", "taken"]).await;
+
+    // k1 moves before k2 is refused, and stays moved
+    let error = error_message(
+        source
+            .command(&["MIGRATE", host, port, "", "0", "5000", "KEYS", "k1", "k2"])
+            .await,
+    );
+    assert!(error.contains("BUSYKEY"), "{}", error);
+    assert_eq!(
+        source.command(&["EXISTS", "k1"]).await,
+        RespValue::integer(0)
+    );
+    assert_eq!(
+        source.command(&["GET", "k2"]).await,
+        RespValue::bulk_string("v2")
+    );
+    assert_eq!(
+        target.command(&["GET", "k1"]).await,
+        RespValue::bulk_string("v1")
+    );
+
+    let commands = load_aof(&path).unwrap();
+    assert_eq!(commands.last().unwrap(), &["DEL", "k1"]);
+}
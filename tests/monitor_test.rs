@@ -1,10 +1,17 @@
 //! Tests for the MONITOR command functionality
 //!
 //! The MONITOR command streams all commands processed by the server in real-time.
-//! These tests verify the MonitorBroadcaster and MonitorMessage functionality.
+//! These tests verify the MonitorBroadcaster and MonitorMessage functionality,
+//! and the feed a MONITOR client gets from a running server.
 
+mod common;
+
+use aikv::protocol::RespValue;
 use aikv::server::monitor::{MonitorBroadcaster, MonitorMessage};
+use common::{start_server, TestClient};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::timeout;
 
 /// Unix timestamp for 2000-01-01 00:00:00 UTC
 const YEAR_2000_UNIX_TIMESTAMP: f64 = 946684800.0;
@@ -228,3 +235,43 @@ fn test_monitor_message_timestamp() {
     let formatted = msg.format();
     assert!(formatted.contains(&format!("{:.6}", msg.timestamp)));
 }
+
+async fn next_line(monitor: &mut TestClient) -> String {
+    match timeout(Duration::from_secs(2), monitor.read_reply()).await {
+        Ok(Some(RespValue::SimpleString(line))) => line,
+        other => panic!("expected a monitor line, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_monitor_sees_commands_from_other_connections() {
+    let addr = start_server(|_| {}).await;
+    let mut monitor = TestClient::connect(&addr).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    assert_eq!(monitor.command(&["MONITOR"]).await, RespValue::ok());
+    assert_eq!(
+        client.command(&["SET", "foo", "bar"]).await,
+        RespValue::ok()
+    );
+    client.command(&["SELECT", "2"]).await;
+    client.command(&["GET", "foo"]).await;
+
+    // <unix.micros> [db addr] "CMD" "arg1" ...
+    let line = next_line(&mut monitor).await;
+    let (timestamp, rest) = line.split_once(' ').unwrap();
+    let (seconds, micros) = timestamp.split_once('.').unwrap();
+    assert!(seconds.parse::<f64>().unwrap() > YEAR_2000_UNIX_TIMESTAMP);
+    assert_eq!(micros.len(), 6);
+    assert!(rest.starts_with("[0 127.0.0.1:"), "{}", line);
+    assert!(rest.ends_with("] \"SET\" \"foo\" \"bar\""), "{}", line);
+    assert!(next_line(&mut monitor).await.ends_with("\"SELECT\" \"2\""));
+    let line = next_line(&mut monitor).await;
+    assert!(line.contains("[2 127.0.0.1:"), "{}", line);
+    assert!(line.ends_with("\"GET\" \"foo\""), "{}", line);
+
+    // The monitor's own commands aren't fed back to it
+    monitor.send(&["PING"]).await;
+    client.command(&["DEL", "foo"]).await;
+    assert!(next_line(&mut monitor).await.ends_with("\"DEL\" \"foo\""));
+}
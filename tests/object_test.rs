@@ -0,0 +1,136 @@
+//! Tests for OBJECT and the access tracking behind it
+
+mod common;
+
+use aikv::command::{ClientContext, CommandExecutor};
+use aikv::protocol::RespValue;
+use aikv::StorageEngine;
+use bytes::Bytes;
+use common::{start_server, TestClient};
+
+async fn freq(client: &mut TestClient, key: &str) -> i64 {
+    match client.command(&["OBJECT", "FREQ", key]).await {
+        RespValue::Integer(freq) => freq,
+        reply => panic!("expected integer, got {:?}", reply),
+    }
+}
+
+#[tokio::test]
+async fn test_object_freq_tracks_accesses_under_lfu() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    client.command(&["SET", "hot", "1"]).await;
+    client.command(&["SET", "cold", "1"]).await;
+
+    // FREQ means nothing unless an LFU policy is selected
+    let reply = client.command(&["OBJECT", "FREQ", "hot"]).await;
+    assert!(
+        matches!(&reply, RespValue::Error(e) if e.contains("An LFU maxmemory policy is not selected")),
+        "{:?}",
+        reply
+    );
+
+    assert_eq!(
+        client
+            .command(&["CONFIG", "SET", "maxmemory-policy", "allkeys-lfu"])
+            .await,
+        RespValue::ok()
+    );
+    let untouched = freq(&mut client, "cold").await;
+    for _ in 0..300 {
+        client.command(&["GET", "hot"]).await;
+    }
+
+    let hot = freq(&mut client, "hot").await;
+    assert!(hot > untouched, "hot {} vs cold {}", hot, untouched);
+    // Looking at the counter doesn't count as an access
+    assert_eq!(freq(&mut client, "cold").await, untouched);
+
+    // IDLETIME is the one that's meaningless under LFU
+    let reply = client.command(&["OBJECT", "IDLETIME", "hot"]).await;
+    assert!(
+        matches!(&reply, RespValue::Error(e) if e.contains("An LRU maxmemory policy is not selected")),
+        "{:?}",
+        reply
+    );
+}
+
+#[tokio::test]
+async fn test_object_subcommands() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    client.command(&["SET", "num", "12345"]).await;
+    client.command(&["RPUSH", "list", "a"]).await;
+
+    assert_eq!(
+        client.command(&["OBJECT", "ENCODING", "num"]).await,
+        RespValue::bulk_string("int")
+    );
+    assert_eq!(
+        client.command(&["OBJECT", "ENCODING", "list"]).await,
+        RespValue::bulk_string("quicklist")
+    );
+    assert_eq!(
+        client.command(&["OBJECT", "REFCOUNT", "num"]).await,
+        RespValue::integer(1)
+    );
+    assert_eq!(
+        client.command(&["OBJECT", "IDLETIME", "num"]).await,
+        RespValue::integer(0)
+    );
+    assert_eq!(
+        client.command(&["OBJECT", "ENCODING", "missing"]).await,
+        RespValue::BulkString(None)
+    );
+    assert!(matches!(
+        client.command(&["OBJECT", "HELP"]).await,
+        RespValue::Array(Some(lines)) if !lines.is_empty()
+    ));
+    assert!(matches!(
+        client.command(&["OBJECT", "NOPE", "num"]).await,
+        RespValue::Error(e) if e.contains("Try OBJECT HELP")
+    ));
+}
+
+#[test]
+fn test_client_no_touch_leaves_idle_time_alone() {
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let mut client = ClientContext::new(1);
+    let mut run = |cmd: &str, args: &[&[u8]]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::copy_from_slice(a)).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+    let idle_time = |reply: RespValue| match reply {
+        RespValue::Integer(seconds) => seconds,
+        other => panic!("expected integer, got {:?}", other),
+    };
+
+    // A key last accessed 100 seconds ago
+    run("SET", &[b"k", b"v"]).unwrap();
+    let RespValue::BulkString(Some(payload)) = run("DUMP", &[b"k"]).unwrap() else {
+        panic!("DUMP returned no payload");
+    };
+    let restore: [&[u8]; 6] = [b"k", b"0", &payload, b"REPLACE", b"IDLETIME", b"100"];
+    run("RESTORE", &restore).unwrap();
+
+    assert_eq!(
+        run("CLIENT", &[b"NO-TOUCH", b"on"]).unwrap(),
+        RespValue::ok()
+    );
+    for _ in 0..3 {
+        assert_eq!(run("GET", &[b"k"]).unwrap(), RespValue::bulk_string("v"));
+    }
+    assert!(idle_time(run("OBJECT", &[b"IDLETIME", b"k"]).unwrap()) >= 100);
+
+    assert_eq!(
+        run("CLIENT", &[b"NO-TOUCH", b"OFF"]).unwrap(),
+        RespValue::ok()
+    );
+    run("GET", &[b"k"]).unwrap();
+    assert_eq!(idle_time(run("OBJECT", &[b"IDLETIME", b"k"]).unwrap()), 0);
+
+    assert!(run("CLIENT", &[b"NO-TOUCH", b"maybe"]).is_err());
+    assert!(run("CLIENT", &[b"NO-TOUCH"]).is_err());
+}
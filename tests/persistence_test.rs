@@ -0,0 +1,400 @@
+//! Tests for AOF and RDB persistence through the command executor
+
+use aikv::command::server::RdbState;
+use aikv::command::{ClientContext, CommandExecutor};
+use aikv::persistence::{load_aof, load_rdb_into, load_stored_value_rdb, AofSyncPolicy, AofWriter};
+use aikv::protocol::RespValue;
+use aikv::StorageEngine;
+use bytes::Bytes;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+fn run(executor: &CommandExecutor, client: &mut ClientContext, args: &[&str]) -> RespValue {
+    let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+    executor
+        .execute(&String::from_utf8_lossy(&args[0]), &args[1..], client)
+        .unwrap()
+}
+
+#[test]
+fn test_aof_replay_restores_writes() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("appendonly.aof");
+
+    {
+        let mut executor = CommandExecutor::new(StorageEngine::new_memory(16));
+        executor.set_aof_writer(AofWriter::new(&path, AofSyncPolicy::Always).unwrap());
+        let mut client = ClientContext::new(1);
+
+        run(&executor, &mut client, &["SET", "greeting", "hello"]);
+        run(&executor, &mut client, &["SET", "doomed", "x"]);
+        run(&executor, &mut client, &["DEL", "doomed"]);
+        run(&executor, &mut client, &["RPUSH", "list", "a", "b", "c"]);
+        run(&executor, &mut client, &["HSET", "hash", "field", "value"]);
+        run(&executor, &mut client, &["EXPIRE", "greeting", "1000"]);
+        run(&executor, &mut client, &["SELECT", "3"]);
+        run(&executor, &mut client, &["SET", "other", "db3"]);
+
+        // Failed and read-only commands are not logged
+        assert!(executor
+            .execute("INCR", &[Bytes::from("list")], &mut client)
+            .is_err());
+        run(&executor, &mut client, &["GET", "other"]);
+    }
+
+    // Reads, SELECTs only where the database changes, and absolute expiry times
+    let commands = load_aof(&path).unwrap();
+    let names: Vec<&str> = commands.iter().map(|c| c[0].as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "SELECT",
+            "SET",
+            "SET",
+            "DEL",
+            "RPUSH",
+            "HSET",
+            "PEXPIREAT",
+            "SELECT",
+            "SET"
+        ]
+    );
+
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    assert_eq!(executor.replay_aof(&path).unwrap(), commands.len());
+
+    let mut client = ClientContext::new(1);
+    assert_eq!(
+        run(&executor, &mut client, &["GET", "greeting"]),
+        RespValue::bulk_string("hello")
+    );
+    assert_eq!(
+        run(&executor, &mut client, &["EXISTS", "doomed"]),
+        RespValue::integer(0)
+    );
+    assert_eq!(
+        run(&executor, &mut client, &["LRANGE", "list", "0", "-1"]),
+        RespValue::array(vec![
+            RespValue::bulk_string("a"),
+            RespValue::bulk_string("b"),
+            RespValue::bulk_string("c"),
+        ])
+    );
+    assert_eq!(
+        run(&executor, &mut client, &["HGET", "hash", "field"]),
+        RespValue::bulk_string("value")
+    );
+    match run(&executor, &mut client, &["TTL", "greeting"]) {
+        RespValue::Integer(ttl) => assert!((990..=1000).contains(&ttl), "ttl {}", ttl),
+        other => panic!("unexpected TTL reply {:?}", other),
+    }
+
+    let mut client = ClientContext {
+        db: 3,
+        ..ClientContext::new(1)
+    };
+    assert_eq!(
+        run(&executor, &mut client, &["GET", "other"]),
+        RespValue::bulk_string("db3")
+    );
+}
+
+#[test]
+fn test_aof_logs_conditional_set_ttls_only_when_the_set_happened() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("appendonly.aof");
+
+    {
+        let mut executor = CommandExecutor::new(StorageEngine::new_memory(16));
+        executor.set_aof_writer(AofWriter::new(&path, AofSyncPolicy::Always).unwrap());
+        let mut client = ClientContext::new(1);
+
+        run(&executor, &mut client, &["SET", "kept", "old"]);
+        // GET replies with the old value although NX stopped the write
+        assert_eq!(
+            run(
+                &executor,
+                &mut client,
+                &["SET", "kept", "new", "EX", "100", "NX", "GET"]
+            ),
+            RespValue::bulk_string("old")
+        );
+        assert_eq!(
+            run(
+                &executor,
+                &mut client,
+                &["SET", "fresh", "v", "EX", "100", "NX", "GET"]
+            ),
+            RespValue::null_bulk_string()
+        );
+        assert_eq!(
+            run(
+                &executor,
+                &mut client,
+                &["SET", "missing", "v", "EX", "100", "XX", "GET"]
+            ),
+            RespValue::null_bulk_string()
+        );
+    }
+
+    let commands = load_aof(&path).unwrap();
+    let names: Vec<&str> = commands.iter().map(|c| c[0].as_str()).collect();
+    assert_eq!(names, ["SELECT", "SET", "SET", "SET", "PEXPIREAT", "SET"]);
+    assert_eq!(commands[4][1], "fresh");
+
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    executor.replay_aof(&path).unwrap();
+    let mut client = ClientContext::new(1);
+    assert_eq!(
+        run(&executor, &mut client, &["GET", "kept"]),
+        RespValue::bulk_string("old")
+    );
+    assert_eq!(
+        run(&executor, &mut client, &["TTL", "kept"]),
+        RespValue::integer(-1)
+    );
+    assert_eq!(
+        run(&executor, &mut client, &["EXISTS", "missing"]),
+        RespValue::integer(0)
+    );
+}
+
+#[test]
+fn test_aof_logs_concurrent_writes_in_the_order_they_were_applied() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("appendonly.aof");
+    let storage = StorageEngine::new_memory(16);
+    let aof = AofWriter::new(&path, AofSyncPolicy::No).unwrap();
+
+    // One executor per connection, sharing the dataset and the log
+    std::thread::scope(|scope| {
+        for thread in 0..4 {
+            let mut executor = CommandExecutor::new(storage.clone());
+            executor.set_aof_writer(aof.clone());
+            scope.spawn(move || {
+                let mut client = ClientContext::new(thread);
+                for i in 0..200 {
+                    let value = format!("{}:{}", thread, i);
+                    run(&executor, &mut client, &["SET", "contended", &value]);
+                    run(&executor, &mut client, &["APPEND", "log", &value]);
+                }
+            });
+        }
+    });
+    aof.flush().unwrap();
+
+    let live = CommandExecutor::new(storage);
+    let restored = CommandExecutor::new(StorageEngine::new_memory(16));
+    restored.replay_aof(&path).unwrap();
+    let mut client = ClientContext::new(1);
+    for key in ["contended", "log"] {
+        assert_eq!(
+            run(&restored, &mut client, &["GET", key]),
+            run(&live, &mut client, &["GET", key])
+        );
+    }
+}
+
+#[test]
+fn test_aof_replay_stops_at_truncated_tail() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("appendonly.aof");
+    std::fs::write(
+        &path,
+        b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n*3\r\n$3\r\nSET\r\n$1\r\nj",
+    )
+    .unwrap();
+
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    assert_eq!(executor.replay_aof(&path).unwrap(), 1);
+
+    let mut client = ClientContext::new(1);
+    assert_eq!(
+        run(&executor, &mut client, &["GET", "k"]),
+        RespValue::bulk_string("v")
+    );
+}
+
+#[test]
+fn test_save_then_load_rdb_into_new_engine() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("dump.rdb");
+    let rdb = Arc::new(RdbState::new(&path));
+
+    let mut executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    executor.set_rdb_state(Arc::clone(&rdb));
+    let mut client = ClientContext::new(1);
+    run(&executor, &mut client, &["SET", "greeting", "hello"]);
+    run(&executor, &mut client, &["RPUSH", "list", "a", "b", "c"]);
+    run(&executor, &mut client, &["HSET", "hash", "field", "value"]);
+    run(&executor, &mut client, &["EXPIRE", "greeting", "1000"]);
+    run(&executor, &mut client, &["SELECT", "2"]);
+    run(&executor, &mut client, &["SADD", "set", "x", "y"]);
+
+    assert_eq!(run(&executor, &mut client, &["SAVE"]), RespValue::ok());
+    assert_eq!(
+        run(&executor, &mut client, &["LASTSAVE"]),
+        RespValue::integer(rdb.last_save_time() as i64)
+    );
+
+    // Load the snapshot into a fresh engine and read everything back
+    let storage = StorageEngine::new_memory(16);
+    assert_eq!(load_rdb_into(&path, &storage).unwrap(), 4);
+    let restored = CommandExecutor::new(storage);
+    let mut client = ClientContext::new(1);
+    assert_eq!(
+        run(&restored, &mut client, &["GET", "greeting"]),
+        RespValue::bulk_string("hello")
+    );
+    assert!(matches!(
+        run(&restored, &mut client, &["TTL", "greeting"]),
+        RespValue::Integer(ttl) if ttl > 990
+    ));
+    assert_eq!(
+        run(&restored, &mut client, &["LRANGE", "list", "0", "-1"]),
+        RespValue::array(vec![
+            RespValue::bulk_string("a"),
+            RespValue::bulk_string("b"),
+            RespValue::bulk_string("c")
+        ])
+    );
+    assert_eq!(
+        run(&restored, &mut client, &["HGET", "hash", "field"]),
+        RespValue::bulk_string("value")
+    );
+    run(&restored, &mut client, &["SELECT", "2"]);
+    assert_eq!(
+        run(&restored, &mut client, &["SCARD", "set"]),
+        RespValue::integer(2)
+    );
+}
+
+#[test]
+fn test_bgsave_writes_in_background() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("dump.rdb");
+    let rdb = Arc::new(RdbState::new(&path));
+
+    let mut executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    executor.set_rdb_state(Arc::clone(&rdb));
+    let mut client = ClientContext::new(1);
+    run(&executor, &mut client, &["SET", "key", "value"]);
+
+    assert_eq!(
+        run(&executor, &mut client, &["BGSAVE"]),
+        RespValue::simple_string("Background saving started")
+    );
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while rdb.bgsave_in_progress() {
+        assert!(Instant::now() < deadline, "BGSAVE did not finish");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let databases = load_stored_value_rdb(&path).unwrap();
+    assert!(databases[0].contains_key("key"));
+}
+
+#[test]
+fn test_load_rdb_into_rejects_corrupt_file() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("dump.rdb");
+    std::fs::write(&path, b"not an rdb file").unwrap();
+
+    let storage = StorageEngine::new_memory(16);
+    assert!(load_rdb_into(&path, &storage).is_err());
+    assert!(load_rdb_into(dir.path().join("missing.rdb"), &storage).is_err());
+}
+
+#[test]
+fn test_bgrewriteaof_compacts_log() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("appendonly.aof");
+    let aof = AofWriter::new(&path, AofSyncPolicy::Always).unwrap();
+
+    let mut executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    executor.set_aof_writer(aof.clone());
+    let mut client = ClientContext::new(1);
+    for i in 0..100 {
+        run(&executor, &mut client, &["SET", "counter", &i.to_string()]);
+    }
+    run(&executor, &mut client, &["RPUSH", "list", "a", "b"]);
+    run(&executor, &mut client, &["SELECT", "1"]);
+    run(&executor, &mut client, &["HSET", "hash", "f", "v"]);
+    assert_eq!(load_aof(&path).unwrap().len(), 104);
+
+    assert_eq!(
+        run(&executor, &mut client, &["BGREWRITEAOF"]),
+        RespValue::simple_string("Background append only file rewriting started")
+    );
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while aof.rewrite_in_progress() {
+        assert!(Instant::now() < deadline, "BGREWRITEAOF did not finish");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    // One entry per key plus a SELECT per database; later writes still append
+    assert_eq!(load_aof(&path).unwrap().len(), 5);
+    run(&executor, &mut client, &["SET", "after", "rewrite"]);
+    drop(executor);
+    drop(aof);
+
+    let restored = CommandExecutor::new(StorageEngine::new_memory(16));
+    assert_eq!(restored.replay_aof(&path).unwrap(), 7);
+    let mut client = ClientContext::new(1);
+    assert_eq!(
+        run(&restored, &mut client, &["GET", "counter"]),
+        RespValue::bulk_string("99")
+    );
+    assert_eq!(
+        run(&restored, &mut client, &["LLEN", "list"]),
+        RespValue::integer(2)
+    );
+    run(&restored, &mut client, &["SELECT", "1"]);
+    assert_eq!(
+        run(&restored, &mut client, &["HGET", "hash", "f"]),
+        RespValue::bulk_string("v")
+    );
+    assert_eq!(
+        run(&restored, &mut client, &["GET", "after"]),
+        RespValue::bulk_string("rewrite")
+    );
+}
+
+#[test]
+fn test_waitaof_fsyncs_the_log_whatever_the_policy() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("appendonly.aof");
+
+    let mut executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    executor.set_aof_writer(AofWriter::new(&path, AofSyncPolicy::No).unwrap());
+    let mut client = ClientContext::new(1);
+    run(&executor, &mut client, &["SET", "order:1", "paid"]);
+
+    // Standalone: the local AOF acknowledges, no replica does
+    assert_eq!(
+        run(&executor, &mut client, &["WAITAOF", "1", "0", "0"]),
+        RespValue::array(vec![RespValue::integer(1), RespValue::integer(0)])
+    );
+    let commands = load_aof(&path).unwrap();
+    assert_eq!(commands.last().unwrap(), &["SET", "order:1", "paid"]);
+
+    let args =
+        |args: &[&str]| -> Vec<Bytes> { args.iter().map(|a| Bytes::from(a.to_string())).collect() };
+    assert!(executor
+        .execute("WAITAOF", &args(&["1", "0", "-1"]), &mut client)
+        .is_err());
+
+    // Without an AOF there is nothing local to wait for
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let reply = executor.execute("WAITAOF", &args(&["1", "0", "0"]), &mut client);
+    assert!(
+        matches!(&reply, Err(e) if e.to_string().contains("appendonly is disabled")),
+        "{:?}",
+        reply
+    );
+    assert_eq!(
+        run(&executor, &mut client, &["WAITAOF", "0", "0", "0"]),
+        RespValue::array(vec![RespValue::integer(0), RespValue::integer(0)])
+    );
+}
@@ -0,0 +1,75 @@
+//! Tests for commands pipelined without waiting for replies
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+
+#[tokio::test]
+async fn test_pipelined_commands_reply_in_order() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    let mut commands = Vec::new();
+    for i in 0..1000 {
+        commands.push(vec![
+            "SET".to_string(),
+            format!("key:{}", i),
+            format!("value:{}", i),
+        ]);
+        commands.push(vec!["GET".to_string(), format!("key:{}", i)]);
+    }
+    client.send_pipeline(&commands).await;
+
+    for i in 0..1000 {
+        assert_eq!(
+            client.read_reply().await,
+            Some(RespValue::ok()),
+            "SET {}",
+            i
+        );
+        assert_eq!(
+            client.read_reply().await,
+            Some(RespValue::bulk_string(format!("value:{}", i))),
+            "GET {}",
+            i
+        );
+    }
+
+    // The connection is still in step afterwards
+    assert_eq!(client.command(&["DBSIZE"]).await, RespValue::integer(1000));
+}
+
+#[tokio::test]
+async fn test_pipeline_switching_protocol_midway() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    let command = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+    client
+        .send_pipeline(&[
+            command(&["HSET", "h", "f", "v"]),
+            command(&["HGETALL", "h"]),
+            command(&["HELLO", "3"]),
+            command(&["HGETALL", "h"]),
+        ])
+        .await;
+
+    assert_eq!(client.read_reply().await, Some(RespValue::integer(1)));
+    // Replies before HELLO use RESP2, the ones after it RESP3
+    assert_eq!(
+        client.read_reply().await,
+        Some(RespValue::array(vec![
+            RespValue::bulk_string("f"),
+            RespValue::bulk_string("v")
+        ]))
+    );
+    assert!(matches!(client.read_reply().await, Some(RespValue::Map(_))));
+    assert_eq!(
+        client.read_reply().await,
+        Some(RespValue::map(vec![(
+            RespValue::bulk_string("f"),
+            RespValue::bulk_string("v")
+        )]))
+    );
+}
@@ -0,0 +1,128 @@
+//! Tests for capping the strings APPEND, SETRANGE and SETBIT build at
+//! proto-max-bulk-len
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+
+fn too_big() -> RespValue {
+    RespValue::error("ERR string exceeds maximum allowed size (proto-max-bulk-len)")
+}
+
+#[tokio::test]
+async fn test_growing_past_the_default_limit_is_rejected() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    // 512mb is the default, and none of these allocate anything
+    assert_eq!(
+        client.command(&["SETRANGE", "k", "536870912", "x"]).await,
+        too_big()
+    );
+    assert_eq!(
+        client.command(&["SETRANGE", "k", "536870911", "xx"]).await,
+        too_big()
+    );
+    assert_eq!(
+        client
+            .command(&["SETRANGE", "k", "18446744073709551615", "x"])
+            .await,
+        too_big()
+    );
+    assert_eq!(
+        client.command(&["SETBIT", "k", "4294967296", "1"]).await,
+        too_big()
+    );
+    assert_eq!(
+        client.command(&["EXISTS", "k"]).await,
+        RespValue::integer(0)
+    );
+
+    // Writing nothing never grows the string
+    assert_eq!(
+        client.command(&["SETRANGE", "k", "536870912", ""]).await,
+        RespValue::integer(0)
+    );
+    assert_eq!(
+        client.command(&["EXISTS", "k"]).await,
+        RespValue::integer(0)
+    );
+}
+
+#[tokio::test]
+async fn test_append_past_a_configured_limit_is_rejected() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    assert!(matches!(
+        client
+            .command(&["CONFIG", "SET", "proto-max-bulk-len", "1000"])
+            .await,
+        RespValue::Error(_)
+    ));
+    assert_eq!(
+        client
+            .command(&["CONFIG", "SET", "proto-max-bulk-len", "1048576"])
+            .await,
+        RespValue::ok()
+    );
+
+    // Exactly at the limit is still fine
+    assert_eq!(
+        client.command(&["SETRANGE", "k", "1048575", "x"]).await,
+        RespValue::integer(1048576)
+    );
+    assert_eq!(client.command(&["APPEND", "k", "y"]).await, too_big());
+    assert_eq!(
+        client.command(&["SETRANGE", "k", "1048576", "y"]).await,
+        too_big()
+    );
+    assert_eq!(
+        client.command(&["SETBIT", "k", "8388608", "1"]).await,
+        too_big()
+    );
+    assert_eq!(
+        client.command(&["STRLEN", "k"]).await,
+        RespValue::integer(1048576)
+    );
+
+    // Raising the limit again lets the string grow
+    client
+        .command(&["CONFIG", "SET", "proto-max-bulk-len", "2097152"])
+        .await;
+    assert_eq!(
+        client.command(&["APPEND", "k", "y"]).await,
+        RespValue::integer(1048577)
+    );
+}
+
+#[tokio::test]
+async fn test_bulk_strings_past_a_configured_limit_are_refused_by_the_parser() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    let at_limit = "x".repeat(1048576);
+    assert_eq!(
+        client
+            .command(&["CONFIG", "SET", "proto-max-bulk-len", "1048576"])
+            .await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        client.command(&["SET", "k", &at_limit]).await,
+        RespValue::ok()
+    );
+
+    // The length alone is refused, and the connection closed
+    client
+        .send_raw(b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1048577\r\n")
+        .await;
+    let reply = client.read_reply().await;
+    assert!(
+        matches!(&reply, Some(RespValue::Error(e)) if e.contains("invalid bulk length")),
+        "{:?}",
+        reply
+    );
+    assert_eq!(client.read_reply().await, None);
+}
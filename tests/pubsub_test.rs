@@ -0,0 +1,399 @@
+//! Tests for Pub/Sub and keyspace notifications
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+use std::time::Duration;
+use tokio::time::timeout;
+
+fn push(items: &[&str], count: Option<i64>) -> RespValue {
+    let mut items: Vec<RespValue> = items.iter().map(|i| RespValue::bulk_string(*i)).collect();
+    if let Some(count) = count {
+        items.push(RespValue::integer(count));
+    }
+    RespValue::array(items)
+}
+
+async fn next_message(client: &mut TestClient) -> RespValue {
+    timeout(Duration::from_secs(2), client.read_reply())
+        .await
+        .expect("No message published")
+        .expect("Connection closed")
+}
+
+#[tokio::test]
+async fn test_publish_reaches_channel_and_pattern_subscribers() {
+    let addr = start_server(|_| {}).await;
+    let mut subscriber = TestClient::connect(&addr).await;
+    let mut publisher = TestClient::connect(&addr).await;
+
+    subscriber.send(&["SUBSCRIBE", "news", "sport"]).await;
+    assert_eq!(
+        next_message(&mut subscriber).await,
+        push(&["subscribe", "news"], Some(1))
+    );
+    assert_eq!(
+        next_message(&mut subscriber).await,
+        push(&["subscribe", "sport"], Some(2))
+    );
+    assert_eq!(
+        subscriber.command(&["PSUBSCRIBE", "n*"]).await,
+        push(&["psubscribe", "n*"], Some(3))
+    );
+
+    assert_eq!(
+        publisher.command(&["PUBLISH", "news", "hello"]).await,
+        RespValue::integer(2)
+    );
+    assert_eq!(
+        next_message(&mut subscriber).await,
+        push(&["message", "news", "hello"], None)
+    );
+    assert_eq!(
+        next_message(&mut subscriber).await,
+        push(&["pmessage", "n*", "news", "hello"], None)
+    );
+
+    assert_eq!(
+        subscriber.command(&["PUNSUBSCRIBE"]).await,
+        push(&["punsubscribe", "n*"], Some(2))
+    );
+    assert_eq!(
+        publisher.command(&["PUBLISH", "nothing", "x"]).await,
+        RespValue::integer(0)
+    );
+}
+
+#[tokio::test]
+async fn test_set_publishes_keyevent() {
+    let addr = start_server(|_| {}).await;
+    let mut subscriber = TestClient::connect(&addr).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    assert_eq!(
+        client
+            .command(&["CONFIG", "SET", "notify-keyspace-events", "KEA"])
+            .await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        client
+            .command(&["CONFIG", "GET", "notify-keyspace-events"])
+            .await,
+        push(&["notify-keyspace-events", "AKE"], None)
+    );
+
+    assert_eq!(
+        subscriber
+            .command(&["SUBSCRIBE", "__keyevent@0__:set"])
+            .await,
+        push(&["subscribe", "__keyevent@0__:set"], Some(1))
+    );
+    assert_eq!(
+        subscriber
+            .command(&["PSUBSCRIBE", "__keyspace@0__:*"])
+            .await,
+        push(&["psubscribe", "__keyspace@0__:*"], Some(2))
+    );
+
+    assert_eq!(
+        client.command(&["SET", "greeting", "hi"]).await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        next_message(&mut subscriber).await,
+        push(
+            &[
+                "pmessage",
+                "__keyspace@0__:*",
+                "__keyspace@0__:greeting",
+                "set"
+            ],
+            None
+        )
+    );
+    assert_eq!(
+        next_message(&mut subscriber).await,
+        push(&["message", "__keyevent@0__:set", "greeting"], None)
+    );
+
+    // A DEL of a missing key changes nothing, so only the real one is published
+    assert_eq!(
+        client.command(&["DEL", "missing", "greeting"]).await,
+        RespValue::integer(1)
+    );
+    assert_eq!(
+        next_message(&mut subscriber).await,
+        push(
+            &[
+                "pmessage",
+                "__keyspace@0__:*",
+                "__keyspace@0__:greeting",
+                "del"
+            ],
+            None
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_expire_in_the_past_publishes_del() {
+    let addr = start_server(|_| {}).await;
+    let mut subscriber = TestClient::connect(&addr).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    assert_eq!(
+        client
+            .command(&["CONFIG", "SET", "notify-keyspace-events", "Eg"])
+            .await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        subscriber
+            .command(&["PSUBSCRIBE", "__keyevent@0__:*"])
+            .await,
+        push(&["psubscribe", "__keyevent@0__:*"], Some(1))
+    );
+
+    client.command(&["SET", "key", "v"]).await;
+    client.command(&["EXPIRE", "key", "100"]).await;
+    assert_eq!(
+        next_message(&mut subscriber).await,
+        push(
+            &[
+                "pmessage",
+                "__keyevent@0__:*",
+                "__keyevent@0__:expire",
+                "key"
+            ],
+            None
+        )
+    );
+    assert_eq!(
+        client.command(&["PEXPIREAT", "key", "1"]).await,
+        RespValue::integer(1)
+    );
+    assert_eq!(
+        next_message(&mut subscriber).await,
+        push(
+            &["pmessage", "__keyevent@0__:*", "__keyevent@0__:del", "key"],
+            None
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_only_enabled_event_classes_are_published() {
+    let addr = start_server(|_| {}).await;
+    let mut subscriber = TestClient::connect(&addr).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    // Expired events only, on keyevent channels
+    assert_eq!(
+        client
+            .command(&["CONFIG", "SET", "notify-keyspace-events", "Ex"])
+            .await,
+        RespValue::ok()
+    );
+    assert!(matches!(
+        client
+            .command(&["CONFIG", "SET", "notify-keyspace-events", "KE?"])
+            .await,
+        RespValue::Error(e) if e.contains("Invalid event class character")
+    ));
+
+    assert_eq!(
+        subscriber.command(&["PSUBSCRIBE", "__key*__:*"]).await,
+        push(&["psubscribe", "__key*__:*"], Some(1))
+    );
+
+    client.command(&["SET", "plain", "v"]).await;
+    client.command(&["LPUSH", "list", "a"]).await;
+    client
+        .command(&["SET", "short-lived", "v", "PX", "50"])
+        .await;
+
+    // The sweeper's expired event is the first thing published
+    assert_eq!(
+        next_message(&mut subscriber).await,
+        push(
+            &[
+                "pmessage",
+                "__key*__:*",
+                "__keyevent@0__:expired",
+                "short-lived"
+            ],
+            None
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_resp2_subscribe_mode_only_allows_pubsub_commands() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    assert_eq!(
+        client.command(&["SUBSCRIBE", "news"]).await,
+        push(&["subscribe", "news"], Some(1))
+    );
+    assert_eq!(
+        client.command(&["SET", "k", "v"]).await,
+        RespValue::error(
+            "ERR Can't execute 'set': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context"
+        )
+    );
+    // PING answers in the shape of a pushed message
+    assert_eq!(client.command(&["PING"]).await, push(&["pong", ""], None));
+    assert_eq!(
+        client.command(&["PING", "hi"]).await,
+        push(&["pong", "hi"], None)
+    );
+
+    // Each reply carries the subscriptions left
+    client.send(&["SUBSCRIBE", "sport", "weather"]).await;
+    assert_eq!(
+        next_message(&mut client).await,
+        push(&["subscribe", "sport"], Some(2))
+    );
+    assert_eq!(
+        next_message(&mut client).await,
+        push(&["subscribe", "weather"], Some(3))
+    );
+    assert_eq!(
+        client.command(&["UNSUBSCRIBE", "sport"]).await,
+        push(&["unsubscribe", "sport"], Some(2))
+    );
+    client.send(&["UNSUBSCRIBE"]).await;
+    assert_eq!(
+        next_message(&mut client).await,
+        push(&["unsubscribe", "news"], Some(1))
+    );
+    assert_eq!(
+        next_message(&mut client).await,
+        push(&["unsubscribe", "weather"], Some(0))
+    );
+
+    // Out of subscribe mode, anything goes again
+    assert_eq!(client.command(&["SET", "k", "v"]).await, RespValue::ok());
+}
+
+#[tokio::test]
+async fn test_resp3_subscribe_mode_allows_any_command() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+    assert!(matches!(
+        client.command(&["HELLO", "3"]).await,
+        RespValue::Map(_)
+    ));
+
+    assert_eq!(
+        client.command(&["SUBSCRIBE", "news"]).await,
+        RespValue::push(vec![
+            RespValue::bulk_string("subscribe"),
+            RespValue::bulk_string("news"),
+            RespValue::integer(1),
+        ])
+    );
+    assert_eq!(client.command(&["SET", "k", "v"]).await, RespValue::ok());
+    assert_eq!(
+        client.command(&["GET", "k"]).await,
+        RespValue::bulk_string("v")
+    );
+    assert_eq!(
+        client.command(&["PING"]).await,
+        RespValue::simple_string("PONG")
+    );
+}
+
+/// Keep publishing `payload` to `firehose` until no subscriber receives it,
+/// returning false if they are still receiving it after `within`
+async fn publish_until_nobody_listens(
+    publisher: &mut TestClient,
+    payload: &str,
+    within: Duration,
+) -> bool {
+    let start = std::time::Instant::now();
+    while start.elapsed() < within {
+        if publisher.command(&["PUBLISH", "firehose", payload]).await == RespValue::integer(0) {
+            return true;
+        }
+    }
+    false
+}
+
+#[tokio::test]
+async fn test_subscriber_over_the_hard_output_limit_is_dropped() {
+    let addr = start_server(|_| {}).await;
+    let mut publisher = TestClient::connect(&addr).await;
+    assert_eq!(
+        publisher
+            .command(&[
+                "CONFIG",
+                "SET",
+                "client-output-buffer-limit",
+                "pubsub 1mb 0 0"
+            ])
+            .await,
+        RespValue::ok()
+    );
+
+    let mut subscriber = TestClient::connect(&addr).await;
+    assert_eq!(
+        subscriber.command(&["SUBSCRIBE", "firehose"]).await,
+        push(&["subscribe", "firehose"], Some(1))
+    );
+
+    // The subscriber never reads again, so once the socket buffers fill its
+    // messages queue up on the server until the limit closes it
+    let payload = "x".repeat(64 * 1024);
+    assert!(
+        publish_until_nobody_listens(&mut publisher, &payload, Duration::from_secs(10)).await,
+        "subscriber was never dropped"
+    );
+
+    // The connection is gone, not just unsubscribed
+    let closed = timeout(Duration::from_secs(5), async {
+        while subscriber.read_reply().await.is_some() {}
+    })
+    .await;
+    assert!(closed.is_ok(), "subscriber connection was left open");
+}
+
+#[tokio::test]
+async fn test_subscriber_over_the_soft_output_limit_is_dropped() {
+    let addr = start_server(|_| {}).await;
+    let mut publisher = TestClient::connect(&addr).await;
+    assert_eq!(
+        publisher
+            .command(&[
+                "CONFIG",
+                "SET",
+                "client-output-buffer-limit",
+                "pubsub 0 256kb 1"
+            ])
+            .await,
+        RespValue::ok()
+    );
+    let reply = publisher
+        .command(&["CONFIG", "SET", "client-output-buffer-limit", "pubsub 1mb"])
+        .await;
+    assert!(
+        matches!(&reply, RespValue::Error(e) if e.contains("Wrong number of arguments")),
+        "{:?}",
+        reply
+    );
+
+    let mut subscriber = TestClient::connect(&addr).await;
+    assert_eq!(
+        subscriber.command(&["SUBSCRIBE", "firehose"]).await,
+        push(&["subscribe", "firehose"], Some(1))
+    );
+
+    let payload = "x".repeat(16 * 1024);
+    assert!(
+        publish_until_nobody_listens(&mut publisher, &payload, Duration::from_secs(20)).await,
+        "subscriber was never dropped"
+    );
+}
@@ -0,0 +1,80 @@
+//! Tests for RESET putting a connection back into its initial state
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+
+#[tokio::test]
+async fn test_reset_restores_pristine_connection() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    assert!(matches!(
+        client.command(&["HELLO", "3"]).await,
+        RespValue::Map(_)
+    ));
+    assert_eq!(client.command(&["SELECT", "3"]).await, RespValue::ok());
+    assert_eq!(
+        client.command(&["SET", "k", "in-db-3"]).await,
+        RespValue::ok()
+    );
+    assert_eq!(
+        client.command(&["CLIENT", "SETNAME", "pooled"]).await,
+        RespValue::ok()
+    );
+
+    assert_eq!(
+        client.command(&["RESET"]).await,
+        RespValue::simple_string("RESET")
+    );
+
+    // Back on database 0, with no name and RESP2 replies
+    assert_eq!(
+        client.command(&["GET", "k"]).await,
+        RespValue::BulkString(None)
+    );
+    assert_eq!(
+        client.command(&["CLIENT", "GETNAME"]).await,
+        RespValue::BulkString(None)
+    );
+    client.command(&["HSET", "h", "f", "v"]).await;
+    assert_eq!(
+        client.command(&["HGETALL", "h"]).await,
+        RespValue::array(vec![
+            RespValue::bulk_string("f"),
+            RespValue::bulk_string("v")
+        ])
+    );
+
+    // The data itself is untouched
+    assert_eq!(client.command(&["SELECT", "3"]).await, RespValue::ok());
+    assert_eq!(
+        client.command(&["GET", "k"]).await,
+        RespValue::bulk_string("in-db-3")
+    );
+}
+
+#[tokio::test]
+async fn test_reset_deauthenticates() {
+    let addr = start_server(|server| server.set_requirepass(Some("secret".to_string()))).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    assert_eq!(client.command(&["AUTH", "secret"]).await, RespValue::ok());
+    assert_eq!(client.command(&["SET", "k", "v"]).await, RespValue::ok());
+
+    assert_eq!(
+        client.command(&["RESET"]).await,
+        RespValue::simple_string("RESET")
+    );
+    assert!(matches!(
+        client.command(&["GET", "k"]).await,
+        RespValue::Error(e) if e.starts_with("NOAUTH")
+    ));
+
+    // RESET itself doesn't need authentication
+    assert_eq!(
+        client.command(&["RESET"]).await,
+        RespValue::simple_string("RESET")
+    );
+}
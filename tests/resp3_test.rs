@@ -0,0 +1,145 @@
+//! Tests that replies use RESP3 types after HELLO 3 and stay RESP2 otherwise
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+
+async fn populate(client: &mut TestClient) {
+    client.command(&["ZADD", "zs", "1.5", "m"]).await;
+    client.command(&["SADD", "s", "a"]).await;
+    client.command(&["HSET", "h", "f", "v"]).await;
+}
+
+#[tokio::test]
+async fn test_resp2_replies_unchanged() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+    populate(&mut client).await;
+
+    assert_eq!(
+        client.command(&["ZSCORE", "zs", "m"]).await,
+        RespValue::bulk_string("1.5")
+    );
+    assert_eq!(
+        client.command(&["INCRBYFLOAT", "f", "2.5"]).await,
+        RespValue::bulk_string("2.5")
+    );
+    assert_eq!(
+        client.command(&["SMEMBERS", "s"]).await,
+        RespValue::array(vec![RespValue::bulk_string("a")])
+    );
+    assert_eq!(
+        client.command(&["HGETALL", "h"]).await,
+        RespValue::array(vec![
+            RespValue::bulk_string("f"),
+            RespValue::bulk_string("v")
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_resp3_replies_use_native_types() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+    assert!(matches!(
+        client.command(&["HELLO", "3"]).await,
+        RespValue::Map(_)
+    ));
+    populate(&mut client).await;
+
+    assert_eq!(
+        client.command(&["ZSCORE", "zs", "m"]).await,
+        RespValue::double(1.5)
+    );
+    assert_eq!(
+        client.command(&["INCRBYFLOAT", "f", "2.5"]).await,
+        RespValue::double(2.5)
+    );
+    assert_eq!(
+        client.command(&["SMEMBERS", "s"]).await,
+        RespValue::set(vec![RespValue::bulk_string("a")])
+    );
+    assert_eq!(
+        client.command(&["HGETALL", "h"]).await,
+        RespValue::map(vec![(
+            RespValue::bulk_string("f"),
+            RespValue::bulk_string("v")
+        )])
+    );
+    assert_eq!(
+        client.command(&["CONFIG", "GET", "server"]).await,
+        RespValue::map(vec![(
+            RespValue::bulk_string("server"),
+            RespValue::bulk_string("aikv")
+        )])
+    );
+}
+
+#[tokio::test]
+async fn test_missing_keys_read_as_empty_aggregates_or_null() {
+    let addr = start_server(|_| {}).await;
+    for protocol in ["2", "3"] {
+        let mut client = TestClient::connect(&addr).await;
+        assert!(matches!(
+            client.command(&["HELLO", protocol]).await,
+            RespValue::Map(_) | RespValue::Array(Some(_))
+        ));
+        let resp3 = protocol == "3";
+
+        // Collection reads of a missing key are empty, not null
+        assert_eq!(
+            client.command(&["LRANGE", "missing", "0", "-1"]).await,
+            RespValue::array(Vec::new())
+        );
+        assert_eq!(
+            client.command(&["HGETALL", "missing"]).await,
+            if resp3 {
+                RespValue::map(Vec::new())
+            } else {
+                RespValue::array(Vec::new())
+            }
+        );
+        assert_eq!(
+            client.command(&["SMEMBERS", "missing"]).await,
+            if resp3 {
+                RespValue::set(Vec::new())
+            } else {
+                RespValue::array(Vec::new())
+            }
+        );
+
+        // Single-element reads are null
+        assert_eq!(
+            client.command(&["GET", "missing"]).await,
+            RespValue::null_bulk_string()
+        );
+        assert_eq!(
+            client.command(&["LPOP", "missing"]).await,
+            if resp3 {
+                RespValue::Null
+            } else {
+                RespValue::null_bulk_string()
+            }
+        );
+        // With a count, a missing key is a null array but an existing one
+        // gives an array even for a single element
+        assert_eq!(
+            client.command(&["LPOP", "missing", "2"]).await,
+            RespValue::null_array()
+        );
+        client.command(&["RPUSH", "list", "a", "b"]).await;
+        assert_eq!(
+            client.command(&["LPOP", "list", "1"]).await,
+            RespValue::array(vec![RespValue::bulk_string("a")])
+        );
+        assert_eq!(
+            client.command(&["RPOP", "list", "0"]).await,
+            RespValue::array(Vec::new())
+        );
+        assert_eq!(
+            client.command(&["RPOP", "list"]).await,
+            RespValue::bulk_string("b")
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use aikv::command::CommandExecutor;
+use aikv::command::{ClientContext, CommandExecutor};
 use aikv::protocol::RespValue;
 use aikv::StorageEngine;
 use bytes::Bytes;
@@ -7,8 +7,7 @@ use bytes::Bytes;
 fn test_script_load_and_exists() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Test SCRIPT LOAD
     let script = "return 'hello world'";
@@ -16,8 +15,7 @@ fn test_script_load_and_exists() {
         .execute(
             "SCRIPT",
             &[Bytes::from("LOAD"), Bytes::from(script)],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -33,8 +31,7 @@ fn test_script_load_and_exists() {
         .execute(
             "SCRIPT",
             &[Bytes::from("EXISTS"), sha1.clone()],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -53,8 +50,7 @@ fn test_script_load_and_exists() {
                 Bytes::from("EXISTS"),
                 Bytes::from("0000000000000000000000000000000000000000"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -67,8 +63,7 @@ fn test_script_load_and_exists() {
 fn test_eval_simple_script() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Test EVAL with simple return value
     let script = "return 42";
@@ -76,8 +71,7 @@ fn test_eval_simple_script() {
         .execute(
             "EVAL",
             &[Bytes::from(script), Bytes::from("0")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -89,8 +83,7 @@ fn test_eval_simple_script() {
         .execute(
             "EVAL",
             &[Bytes::from(script), Bytes::from("0")],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -105,8 +98,7 @@ fn test_eval_simple_script() {
 fn test_eval_with_keys_and_argv() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Test EVAL with KEYS
     let script = "return KEYS[1]";
@@ -118,8 +110,7 @@ fn test_eval_with_keys_and_argv() {
                 Bytes::from("1"),
                 Bytes::from("testkey"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -139,8 +130,7 @@ fn test_eval_with_keys_and_argv() {
                 Bytes::from("0"),
                 Bytes::from("testarg"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -155,8 +145,7 @@ fn test_eval_with_keys_and_argv() {
 fn test_eval_redis_call() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Test EVAL with redis.call to SET and GET
     let script = r#"
@@ -173,8 +162,7 @@ fn test_eval_redis_call() {
                 Bytes::from("mykey"),
                 Bytes::from("myvalue"),
             ],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -186,7 +174,7 @@ fn test_eval_redis_call() {
 
     // Verify the value was actually stored
     let result = executor
-        .execute("GET", &[Bytes::from("mykey")], &mut current_db, client_id)
+        .execute("GET", &[Bytes::from("mykey")], &mut client)
         .unwrap();
 
     if let RespValue::BulkString(Some(value)) = result {
@@ -198,8 +186,7 @@ fn test_eval_redis_call() {
 fn test_evalsha() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Load a script
     let script = "return 'cached script result'";
@@ -207,8 +194,7 @@ fn test_evalsha() {
         .execute(
             "SCRIPT",
             &[Bytes::from("LOAD"), Bytes::from(script)],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -220,12 +206,7 @@ fn test_evalsha() {
 
     // Execute the cached script using EVALSHA
     let result = executor
-        .execute(
-            "EVALSHA",
-            &[sha1, Bytes::from("0")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("EVALSHA", &[sha1, Bytes::from("0")], &mut client)
         .unwrap();
 
     if let RespValue::BulkString(Some(value)) = result {
@@ -239,8 +220,7 @@ fn test_evalsha() {
 fn test_evalsha_not_found() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Try to execute a non-existent script
     let result = executor.execute(
@@ -249,8 +229,7 @@ fn test_evalsha_not_found() {
             Bytes::from("0000000000000000000000000000000000000000"),
             Bytes::from("0"),
         ],
-        &mut current_db,
-        client_id,
+        &mut client,
     );
 
     assert!(result.is_err());
@@ -260,8 +239,7 @@ fn test_evalsha_not_found() {
 fn test_script_flush() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Load a script
     let script = "return 1";
@@ -269,8 +247,7 @@ fn test_script_flush() {
         .execute(
             "SCRIPT",
             &[Bytes::from("LOAD"), Bytes::from(script)],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -285,8 +262,7 @@ fn test_script_flush() {
         .execute(
             "SCRIPT",
             &[Bytes::from("EXISTS"), sha1.clone()],
-            &mut current_db,
-            client_id,
+            &mut client,
         )
         .unwrap();
 
@@ -296,24 +272,14 @@ fn test_script_flush() {
 
     // Flush all scripts
     let result = executor
-        .execute(
-            "SCRIPT",
-            &[Bytes::from("FLUSH")],
-            &mut current_db,
-            client_id,
-        )
+        .execute("SCRIPT", &[Bytes::from("FLUSH")], &mut client)
         .unwrap();
 
     assert_eq!(result, RespValue::simple_string("OK"));
 
     // Verify script no longer exists
     let result = executor
-        .execute(
-            "SCRIPT",
-            &[Bytes::from("EXISTS"), sha1],
-            &mut current_db,
-            client_id,
-        )
+        .execute("SCRIPT", &[Bytes::from("EXISTS"), sha1], &mut client)
         .unwrap();
 
     if let RespValue::Array(Some(arr)) = result {
@@ -325,11 +291,10 @@ fn test_script_flush() {
 fn test_script_kill() {
     let storage = StorageEngine::new_memory(16);
     let executor = CommandExecutor::new(storage);
-    let mut current_db = 0;
-    let client_id = 1;
+    let mut client = ClientContext::new(1);
 
     // Test SCRIPT KILL (should return NOTBUSY since no script is running)
-    let result = executor.execute("SCRIPT", &[Bytes::from("KILL")], &mut current_db, client_id);
+    let result = executor.execute("SCRIPT", &[Bytes::from("KILL")], &mut client);
 
     assert!(result.is_err());
 }
@@ -0,0 +1,66 @@
+//! Tests for SCRIPT KILL and lua-time-limit across connections
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+use std::time::Duration;
+use tokio::time::sleep;
+
+fn is_error(reply: &RespValue, needle: &str) -> bool {
+    matches!(reply, RespValue::Error(e) if e.contains(needle))
+}
+
+// A running script occupies its connection's worker thread
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_script_kill_stops_script_on_another_connection() {
+    let addr = start_server(|_| {}).await;
+    let mut runner = TestClient::connect(&addr).await;
+    let mut killer = TestClient::connect(&addr).await;
+
+    let reply = killer.command(&["SCRIPT", "KILL"]).await;
+    assert!(is_error(&reply, "NOTBUSY"), "{:?}", reply);
+
+    runner
+        .send(&[
+            "EVAL",
+            "redis.call('SET', KEYS[1], 'written') while true do end",
+            "1",
+            "killed_key",
+        ])
+        .await;
+    sleep(Duration::from_millis(200)).await;
+
+    assert_eq!(killer.command(&["SCRIPT", "KILL"]).await, RespValue::ok());
+    let reply = runner.read_reply().await.expect("connection closed");
+    assert!(is_error(&reply, "killed"), "{:?}", reply);
+
+    // The killed script's buffered write was discarded
+    assert_eq!(
+        killer.command(&["GET", "killed_key"]).await,
+        RespValue::BulkString(None)
+    );
+    let reply = killer.command(&["SCRIPT", "KILL"]).await;
+    assert!(is_error(&reply, "NOTBUSY"), "{:?}", reply);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_lua_time_limit_aborts_long_scripts() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    assert_eq!(
+        client
+            .command(&["CONFIG", "SET", "lua-time-limit", "50"])
+            .await,
+        RespValue::ok()
+    );
+    let reply = client.command(&["EVAL", "while true do end", "0"]).await;
+    assert!(is_error(&reply, "lua-time-limit"), "{:?}", reply);
+
+    // Short scripts still run
+    assert_eq!(
+        client.command(&["EVAL", "return 1", "0"]).await,
+        RespValue::integer(1)
+    );
+}
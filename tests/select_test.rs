@@ -0,0 +1,53 @@
+//! Tests that a connection's selected database tracks changes made to the
+//! shared storage from other connections (SWAPDB, MOVE, FLUSHALL)
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+
+#[tokio::test]
+async fn test_swapdb_is_seen_by_other_connections() {
+    let addr = start_server(|_| {}).await;
+    let mut a = TestClient::connect(&addr).await;
+    let mut b = TestClient::connect(&addr).await;
+
+    assert_eq!(a.command(&["SELECT", "1"]).await, RespValue::ok());
+    assert_eq!(a.command(&["SET", "k", "v"]).await, RespValue::ok());
+
+    // B swaps the databases under A, which stays on db 1
+    assert_eq!(b.command(&["SWAPDB", "0", "1"]).await, RespValue::ok());
+    assert_eq!(
+        a.command(&["GET", "k"]).await,
+        RespValue::null_bulk_string()
+    );
+    assert_eq!(b.command(&["GET", "k"]).await, RespValue::bulk_string("v"));
+
+    // Swapping back brings the key back to A
+    assert_eq!(b.command(&["SWAPDB", "1", "0"]).await, RespValue::ok());
+    assert_eq!(a.command(&["GET", "k"]).await, RespValue::bulk_string("v"));
+    assert_eq!(
+        b.command(&["GET", "k"]).await,
+        RespValue::null_bulk_string()
+    );
+}
+
+#[tokio::test]
+async fn test_move_and_flushall_are_seen_by_other_connections() {
+    let addr = start_server(|_| {}).await;
+    let mut a = TestClient::connect(&addr).await;
+    let mut b = TestClient::connect(&addr).await;
+
+    assert_eq!(a.command(&["SELECT", "3"]).await, RespValue::ok());
+    assert_eq!(b.command(&["SET", "k", "v"]).await, RespValue::ok());
+    assert_eq!(b.command(&["MOVE", "k", "3"]).await, RespValue::integer(1));
+    assert_eq!(a.command(&["GET", "k"]).await, RespValue::bulk_string("v"));
+    assert_eq!(b.command(&["EXISTS", "k"]).await, RespValue::integer(0));
+
+    assert_eq!(b.command(&["FLUSHALL"]).await, RespValue::ok());
+    assert_eq!(a.command(&["DBSIZE"]).await, RespValue::integer(0));
+    assert_eq!(
+        a.command(&["GET", "k"]).await,
+        RespValue::null_bulk_string()
+    );
+}
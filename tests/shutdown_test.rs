@@ -0,0 +1,157 @@
+//! Tests for shutting a running server down gracefully
+
+mod common;
+
+use aikv::persistence::{load_rdb_into, AofSyncPolicy, AofWriter};
+use aikv::protocol::RespValue;
+use aikv::{Server, StorageEngine};
+use common::{free_addr, wait_for_listener, TestClient};
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_shutdown_signal_saves_and_returns_from_run() {
+    let dir = TempDir::new().unwrap();
+    let rdb_path = dir.path().join("dump.rdb");
+    let addr = free_addr();
+
+    let mut server = Server::new(addr.clone(), StorageEngine::new_memory(16));
+    server.set_rdb_path(&rdb_path);
+    server.set_save_on_shutdown(true);
+    let shutdown = server.shutdown_signal();
+    let running = tokio::spawn(async move { server.run().await });
+    wait_for_listener(&addr).await;
+
+    let mut client = TestClient::connect(&addr).await;
+    assert_eq!(client.command(&["SET", "k", "v"]).await, RespValue::ok());
+    assert_eq!(
+        client.command(&["RPUSH", "list", "a", "b"]).await,
+        RespValue::integer(2)
+    );
+    assert!(!rdb_path.exists());
+
+    shutdown.trigger();
+    let result = timeout(Duration::from_secs(5), running)
+        .await
+        .expect("run did not return after shutdown")
+        .unwrap();
+    assert!(result.is_ok(), "{:?}", result);
+
+    // The idle client was disconnected and nobody else gets in
+    assert_eq!(client.read_reply().await, None);
+    assert!(TcpStream::connect(&addr).await.is_err());
+
+    // Everything written before the shutdown made it into the snapshot
+    let storage = StorageEngine::new_memory(16);
+    assert_eq!(load_rdb_into(&rdb_path, &storage).unwrap(), 2);
+    assert_eq!(
+        storage.get_from_db(0, "k").unwrap(),
+        Some(bytes::Bytes::from("v"))
+    );
+}
+
+#[tokio::test]
+async fn test_shutdown_without_save_writes_no_snapshot() {
+    let dir = TempDir::new().unwrap();
+    let rdb_path = dir.path().join("dump.rdb");
+    let addr = free_addr();
+
+    let mut server = Server::new(addr.clone(), StorageEngine::new_memory(16));
+    server.set_rdb_path(&rdb_path);
+    let shutdown = server.shutdown_signal();
+    // Triggering before `run` starts is not lost
+    shutdown.trigger();
+
+    let result = timeout(Duration::from_secs(5), server.run())
+        .await
+        .expect("run did not return after shutdown");
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(!rdb_path.exists());
+}
+
+#[tokio::test]
+async fn test_shutdown_nosave_command_stops_without_snapshot() {
+    let dir = TempDir::new().unwrap();
+    let rdb_path = dir.path().join("dump.rdb");
+    let addr = free_addr();
+
+    let mut server = Server::new(addr.clone(), StorageEngine::new_memory(16));
+    server.set_rdb_path(&rdb_path);
+    server.set_save_on_shutdown(true);
+    let shutdown = server.shutdown_signal();
+    let running = tokio::spawn(async move { server.run().await });
+    wait_for_listener(&addr).await;
+
+    let mut client = TestClient::connect(&addr).await;
+    assert_eq!(client.command(&["SET", "k", "v"]).await, RespValue::ok());
+    assert!(matches!(
+        client.command(&["SHUTDOWN", "SAVE", "NOSAVE"]).await,
+        RespValue::Error(e) if e.contains("syntax error")
+    ));
+    assert!(!shutdown.is_triggered());
+
+    client.send(&["SHUTDOWN", "NOSAVE"]).await;
+    let result = timeout(Duration::from_secs(5), running)
+        .await
+        .expect("run did not return after SHUTDOWN")
+        .unwrap();
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(shutdown.is_triggered());
+
+    assert!(TcpStream::connect(&addr).await.is_err());
+    assert!(!rdb_path.exists());
+}
+
+#[tokio::test]
+async fn test_shutdown_save_command_forces_snapshot() {
+    let dir = TempDir::new().unwrap();
+    let rdb_path = dir.path().join("dump.rdb");
+    let addr = free_addr();
+
+    // AOF on, no snapshot by default: SAVE still writes one
+    let mut server = Server::new(addr.clone(), StorageEngine::new_memory(16));
+    server.set_rdb_path(&rdb_path);
+    server.set_aof_writer(
+        AofWriter::new(dir.path().join("appendonly.aof"), AofSyncPolicy::No).unwrap(),
+    );
+    let running = tokio::spawn(async move { server.run().await });
+    wait_for_listener(&addr).await;
+
+    let mut client = TestClient::connect(&addr).await;
+    assert_eq!(client.command(&["SET", "k", "v"]).await, RespValue::ok());
+    client.send(&["SHUTDOWN", "SAVE"]).await;
+    let result = timeout(Duration::from_secs(5), running)
+        .await
+        .expect("run did not return after SHUTDOWN")
+        .unwrap();
+    assert!(result.is_ok(), "{:?}", result);
+
+    let storage = StorageEngine::new_memory(16);
+    assert_eq!(load_rdb_into(&rdb_path, &storage).unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_shutdown_save_without_persistence_is_refused() {
+    let addr = free_addr();
+    let server = Server::new(addr.clone(), StorageEngine::new_memory(16));
+    let shutdown = server.shutdown_signal();
+    tokio::spawn(async move { server.run().await });
+    wait_for_listener(&addr).await;
+
+    let mut client = TestClient::connect(&addr).await;
+    let reply = client.command(&["SHUTDOWN", "SAVE"]).await;
+    assert!(
+        matches!(&reply, RespValue::Error(e) if e.contains("persistence is disabled")),
+        "{:?}",
+        reply
+    );
+
+    // The server keeps running
+    assert!(!shutdown.is_triggered());
+    assert_eq!(
+        client.command(&["PING"]).await,
+        RespValue::simple_string("PONG")
+    );
+}
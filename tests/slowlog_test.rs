@@ -0,0 +1,70 @@
+//! Tests that the connection loop feeds SLOWLOG, shared across connections
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+
+#[tokio::test]
+async fn test_slowlog_captures_commands_over_threshold() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+    let mut other = TestClient::connect(&addr).await;
+
+    // Nothing is slow enough at the default 10ms threshold
+    client.command(&["SET", "fast", "1"]).await;
+    assert_eq!(
+        other.command(&["SLOWLOG", "LEN"]).await,
+        RespValue::integer(0)
+    );
+
+    assert_eq!(
+        client
+            .command(&["CONFIG", "SET", "slowlog-log-slower-than", "0"])
+            .await,
+        RespValue::ok()
+    );
+    client.command(&["SET", "slow", "value"]).await;
+
+    // The entry is visible from another connection, most recent first
+    let entries = match other.command(&["SLOWLOG", "GET", "1"]).await {
+        RespValue::Array(Some(entries)) => entries,
+        other => panic!("expected array, got {:?}", other),
+    };
+    assert_eq!(entries.len(), 1);
+    let entry = match &entries[0] {
+        RespValue::Array(Some(entry)) => entry,
+        other => panic!("expected entry array, got {:?}", other),
+    };
+    assert!(matches!(entry[2], RespValue::Integer(us) if us >= 0));
+    assert_eq!(
+        entry[3],
+        RespValue::array(vec![
+            RespValue::bulk_string("SET"),
+            RespValue::bulk_string("slow"),
+            RespValue::bulk_string("value")
+        ])
+    );
+    assert!(
+        matches!(&entry[4], RespValue::BulkString(Some(addr)) if addr.starts_with(b"127.0.0.1:")),
+        "{:?}",
+        entry[4]
+    );
+
+    // CONFIG SET, SET and the SLOWLOG GET itself were logged
+    assert_eq!(
+        other.command(&["SLOWLOG", "LEN"]).await,
+        RespValue::integer(3)
+    );
+
+    // A negative threshold disables logging
+    client
+        .command(&["CONFIG", "SET", "slowlog-log-slower-than", "-1"])
+        .await;
+    assert_eq!(other.command(&["SLOWLOG", "RESET"]).await, RespValue::ok());
+    client.command(&["SET", "slow", "again"]).await;
+    assert_eq!(
+        other.command(&["SLOWLOG", "LEN"]).await,
+        RespValue::integer(0)
+    );
+}
@@ -0,0 +1,149 @@
+//! Tests for SORT over lists, sets and sorted sets
+
+use aikv::command::{ClientContext, CommandExecutor};
+use aikv::protocol::RespValue;
+use aikv::StorageEngine;
+use bytes::Bytes;
+
+fn bulk_strings(items: &[&str]) -> RespValue {
+    RespValue::array(
+        items
+            .iter()
+            .map(|i| RespValue::bulk_string(i.to_string()))
+            .collect(),
+    )
+}
+
+#[test]
+fn test_sort_numeric_and_alpha() {
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let mut client = ClientContext::new(1);
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    run("RPUSH", &["nums", "10", "2", "-1.5", "33"]).unwrap();
+    assert_eq!(
+        run("SORT", &["nums"]).unwrap(),
+        bulk_strings(&["-1.5", "2", "10", "33"])
+    );
+    assert_eq!(
+        run("SORT", &["nums", "DESC", "LIMIT", "1", "2"]).unwrap(),
+        bulk_strings(&["10", "2"])
+    );
+    // ALPHA compares the bytes, so "10" comes before "2"
+    assert_eq!(
+        run("SORT", &["nums", "ALPHA"]).unwrap(),
+        bulk_strings(&["-1.5", "10", "2", "33"])
+    );
+
+    run("SADD", &["words", "banana", "apple", "cherry"]).unwrap();
+    assert!(matches!(
+        run("SORT", &["words"]),
+        Err(e) if e.to_string().contains("can't be converted into double")
+    ));
+    assert_eq!(
+        run("SORT", &["words", "ALPHA", "DESC"]).unwrap(),
+        bulk_strings(&["cherry", "banana", "apple"])
+    );
+
+    run("ZADD", &["z", "3", "a", "1", "b", "2", "c"]).unwrap();
+    assert_eq!(
+        run("SORT", &["z", "BY", "nosort"]).unwrap(),
+        bulk_strings(&["b", "c", "a"])
+    );
+
+    assert_eq!(run("SORT", &["missing"]).unwrap(), bulk_strings(&[]));
+    run("SET", &["str", "1"]).unwrap();
+    assert!(run("SORT", &["str"]).is_err());
+    assert!(run("SORT", &["nums", "LIMIT", "1"]).is_err());
+}
+
+#[test]
+fn test_sort_by_and_get_patterns() {
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let mut client = ClientContext::new(1);
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    run("RPUSH", &["users", "1", "2", "3"]).unwrap();
+    run("HSET", &["user:1", "name", "carol", "age", "41"]).unwrap();
+    run("HSET", &["user:2", "name", "alice", "age", "29"]).unwrap();
+    run("HSET", &["user:3", "name", "bob", "age", "35"]).unwrap();
+    run("SET", &["weight_1", "3"]).unwrap();
+    run("SET", &["weight_2", "1"]).unwrap();
+    run("SET", &["weight_3", "2"]).unwrap();
+
+    assert_eq!(
+        run("SORT", &["users", "BY", "user:*->age"]).unwrap(),
+        bulk_strings(&["2", "3", "1"])
+    );
+    assert_eq!(
+        run("SORT", &["users", "BY", "weight_*", "DESC"]).unwrap(),
+        bulk_strings(&["1", "3", "2"])
+    );
+    assert_eq!(
+        run(
+            "SORT",
+            &[
+                "users",
+                "BY",
+                "user:*->name",
+                "ALPHA",
+                "GET",
+                "#",
+                "GET",
+                "user:*->name"
+            ]
+        )
+        .unwrap(),
+        bulk_strings(&["2", "alice", "3", "bob", "1", "carol"])
+    );
+
+    // Missing GET targets are nil
+    run("RPUSH", &["users", "4"]).unwrap();
+    assert_eq!(
+        run("SORT", &["users", "GET", "user:*->name"]).unwrap(),
+        RespValue::array(vec![
+            RespValue::bulk_string("carol"),
+            RespValue::bulk_string("alice"),
+            RespValue::bulk_string("bob"),
+            RespValue::null_bulk_string(),
+        ])
+    );
+}
+
+#[test]
+fn test_sort_store_produces_list() {
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let mut client = ClientContext::new(1);
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    run("SADD", &["s", "3", "1", "2"]).unwrap();
+    run("SET", &["dest", "old"]).unwrap();
+    assert_eq!(
+        run("SORT", &["s", "STORE", "dest"]).unwrap(),
+        RespValue::integer(3)
+    );
+    assert_eq!(
+        run("TYPE", &["dest"]).unwrap(),
+        RespValue::simple_string("list")
+    );
+    assert_eq!(
+        run("LRANGE", &["dest", "0", "-1"]).unwrap(),
+        bulk_strings(&["1", "2", "3"])
+    );
+
+    // An empty result removes the destination
+    assert_eq!(
+        run("SORT", &["missing", "STORE", "dest"]).unwrap(),
+        RespValue::integer(0)
+    );
+    assert_eq!(run("EXISTS", &["dest"]).unwrap(), RespValue::integer(0));
+}
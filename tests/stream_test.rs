@@ -0,0 +1,180 @@
+//! Tests for the stream commands
+
+use aikv::command::{ClientContext, CommandExecutor};
+use aikv::protocol::RespValue;
+use aikv::StorageEngine;
+use bytes::Bytes;
+
+fn id(reply: RespValue) -> (u64, u64) {
+    match reply {
+        RespValue::BulkString(Some(id)) => {
+            let id = String::from_utf8_lossy(&id).to_string();
+            let (ms, seq) = id.split_once('-').expect("ms-seq");
+            (ms.parse().unwrap(), seq.parse().unwrap())
+        }
+        other => panic!("expected bulk string, got {:?}", other),
+    }
+}
+
+fn entry(id: &str, fields: &[&str]) -> RespValue {
+    RespValue::array(vec![
+        RespValue::bulk_string(id.to_string()),
+        RespValue::array(
+            fields
+                .iter()
+                .map(|f| RespValue::bulk_string(f.to_string()))
+                .collect(),
+        ),
+    ])
+}
+
+#[test]
+fn test_xadd_generates_increasing_ids() {
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let mut client = ClientContext::new(1);
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    // Many entries in the same millisecond still get increasing IDs
+    let mut last = (0, 0);
+    for i in 0..1000 {
+        let next = id(run("XADD", &["events", "*", "n", &i.to_string()]).unwrap());
+        assert!(next > last, "{:?} after {:?}", next, last);
+        last = next;
+    }
+    assert_eq!(run("XLEN", &["events"]).unwrap(), RespValue::integer(1000));
+    assert_eq!(run("XLEN", &["missing"]).unwrap(), RespValue::integer(0));
+
+    // An explicit ID ahead of the clock makes `*` continue from it
+    let future = last.0 + 1_000_000;
+    let explicit = format!("{}-5", future);
+    assert_eq!(
+        id(run("XADD", &["events", &explicit, "n", "x"]).unwrap()),
+        (future, 5)
+    );
+    assert_eq!(
+        id(run("XADD", &["events", "*", "n", "y"]).unwrap()),
+        (future, 6)
+    );
+    let auto_seq = format!("{}-*", future);
+    assert_eq!(
+        id(run("XADD", &["events", &auto_seq, "n", "z"]).unwrap()),
+        (future, 7)
+    );
+
+    // IDs that don't move forward are rejected
+    for stale in [explicit.as_str(), "1-1", "1-*", "0-0"] {
+        assert!(
+            run("XADD", &["events", stale, "n", "v"]).is_err(),
+            "{}",
+            stale
+        );
+    }
+    assert!(run("XADD", &["fresh", "0-0", "n", "v"]).is_err());
+    assert_eq!(
+        id(run("XADD", &["fresh", "0-*", "n", "v"]).unwrap()),
+        (0, 1)
+    );
+    assert_eq!(id(run("XADD", &["fresh", "7", "n", "v"]).unwrap()), (7, 0));
+    for bad in ["abc", "1-x", "-1", "1-2-3"] {
+        assert!(run("XADD", &["fresh", bad, "n", "v"]).is_err(), "{}", bad);
+    }
+    assert!(run("XADD", &["fresh", "*", "field-without-value"]).is_err());
+
+    run("SET", &["plain", "v"]).unwrap();
+    assert!(run("XADD", &["plain", "*", "n", "v"]).is_err());
+    assert!(run("XLEN", &["plain"]).is_err());
+    assert_eq!(
+        run("TYPE", &["events"]).unwrap(),
+        RespValue::simple_string("stream")
+    );
+}
+
+#[test]
+fn test_xrange_bounds_and_xread() {
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let mut client = ClientContext::new(1);
+    let mut run = |cmd: &str, args: &[&str]| {
+        let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+        executor.execute(cmd, &args, &mut client)
+    };
+
+    for (id, value) in [("1-0", "a"), ("1-1", "b"), ("2-0", "c"), ("3-5", "d")] {
+        run("XADD", &["s", id, "v", value]).unwrap();
+    }
+    let a = entry("1-0", &["v", "a"]);
+    let b = entry("1-1", &["v", "b"]);
+    let c = entry("2-0", &["v", "c"]);
+    let d = entry("3-5", &["v", "d"]);
+
+    assert_eq!(
+        run("XRANGE", &["s", "-", "+"]).unwrap(),
+        RespValue::array(vec![a.clone(), b.clone(), c.clone(), d.clone()])
+    );
+    // Both ends are inclusive, and a bare millisecond covers all its entries
+    assert_eq!(
+        run("XRANGE", &["s", "1-1", "2-0"]).unwrap(),
+        RespValue::array(vec![b.clone(), c.clone()])
+    );
+    assert_eq!(
+        run("XRANGE", &["s", "1", "1"]).unwrap(),
+        RespValue::array(vec![a.clone(), b.clone()])
+    );
+    // `(` makes a bound exclusive
+    assert_eq!(
+        run("XRANGE", &["s", "(1-0", "(3-5"]).unwrap(),
+        RespValue::array(vec![b.clone(), c.clone()])
+    );
+    assert_eq!(
+        run("XRANGE", &["s", "-", "+", "COUNT", "2"]).unwrap(),
+        RespValue::array(vec![a.clone(), b.clone()])
+    );
+    assert_eq!(
+        run("XRANGE", &["s", "-", "+", "COUNT", "0"]).unwrap(),
+        RespValue::array(Vec::new())
+    );
+    assert_eq!(
+        run("XRANGE", &["s", "3", "1"]).unwrap(),
+        RespValue::array(Vec::new())
+    );
+    assert_eq!(
+        run("XRANGE", &["missing", "-", "+"]).unwrap(),
+        RespValue::array(Vec::new())
+    );
+    assert!(run("XRANGE", &["s", "x", "+"]).is_err());
+    assert!(run("XRANGE", &["s", "-", "+", "LIMIT", "1"]).is_err());
+
+    // XREAD returns what comes strictly after the given ID
+    run("XADD", &["t", "5-0", "k", "v"]).unwrap();
+    assert_eq!(
+        run("XREAD", &["COUNT", "1", "STREAMS", "s", "1-0"]).unwrap(),
+        RespValue::array(vec![RespValue::array(vec![
+            RespValue::bulk_string("s"),
+            RespValue::array(vec![b.clone()]),
+        ])])
+    );
+    assert_eq!(
+        run("XREAD", &["STREAMS", "s", "t", "2", "0"]).unwrap(),
+        RespValue::array(vec![
+            RespValue::array(vec![
+                RespValue::bulk_string("s"),
+                RespValue::array(vec![d.clone()]),
+            ]),
+            RespValue::array(vec![
+                RespValue::bulk_string("t"),
+                RespValue::array(vec![entry("5-0", &["k", "v"])]),
+            ]),
+        ])
+    );
+    assert_eq!(
+        run("XREAD", &["STREAMS", "s", "3-5"]).unwrap(),
+        RespValue::null_array()
+    );
+    assert_eq!(
+        run("XREAD", &["STREAMS", "s", "$"]).unwrap(),
+        RespValue::null_array()
+    );
+    assert!(run("XREAD", &["STREAMS", "s", "t", "0"]).is_err());
+}
@@ -0,0 +1,113 @@
+//! Tests for streaming large aggregate replies instead of building them whole
+
+mod common;
+
+use aikv::command::{ClientContext, CommandExecutor};
+use aikv::protocol::{ProtocolVersion, RespValue, STREAMING_THRESHOLD};
+use aikv::StorageEngine;
+use bytes::Bytes;
+use common::{start_server, TestClient};
+
+const LARGE: usize = STREAMING_THRESHOLD * 3;
+
+#[test]
+fn test_large_hgetall_streams_the_materialized_bytes() {
+    let executor = CommandExecutor::new(StorageEngine::new_memory(16));
+    let mut client = ClientContext::new(1);
+
+    let mut args = vec![Bytes::from("small"), Bytes::from("f"), Bytes::from("v")];
+    executor.execute("HSET", &args, &mut client).unwrap();
+    let reply = executor
+        .execute("HGETALL", &args[..1], &mut client)
+        .unwrap();
+    assert_eq!(
+        reply,
+        RespValue::map(vec![(
+            RespValue::bulk_string("f"),
+            RespValue::bulk_string("v")
+        )])
+    );
+
+    args = vec![Bytes::from("big")];
+    for i in 0..LARGE {
+        args.push(Bytes::from(format!("field:{}", i)));
+        args.push(Bytes::from(format!("value:{}", i)));
+    }
+    executor.execute("HSET", &args, &mut client).unwrap();
+
+    let reply = executor
+        .execute("HGETALL", &args[..1], &mut client)
+        .unwrap();
+    let RespValue::Streaming(streaming) = &reply else {
+        panic!("expected a streaming reply, got {:?}", reply);
+    };
+    assert_eq!(streaming.len(), LARGE);
+
+    for protocol in [ProtocolVersion::Resp2, ProtocolVersion::Resp3] {
+        let mut streamed = streaming.header(protocol).to_vec();
+        for index in 0..streaming.len() {
+            streaming.write_item(index, protocol, &mut streamed);
+        }
+        assert_eq!(
+            Bytes::from(streamed),
+            streaming.materialize().serialize_for(protocol)
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_large_replies_arrive_whole() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    let elements: Vec<String> = (0..LARGE).map(|i| format!("e{}", i)).collect();
+    let mut rpush = vec!["RPUSH", "list"];
+    rpush.extend(elements.iter().map(String::as_str));
+    client.command(&rpush).await;
+
+    let mut zadd = vec!["ZADD".to_string(), "zset".to_string()];
+    for (i, element) in elements.iter().enumerate() {
+        zadd.push(i.to_string());
+        zadd.push(element.clone());
+    }
+    let zadd: Vec<&str> = zadd.iter().map(String::as_str).collect();
+    client.command(&zadd).await;
+
+    let expected: Vec<RespValue> = elements
+        .iter()
+        .map(|e| RespValue::bulk_string(e.clone()))
+        .collect();
+    assert_eq!(
+        client.command(&["LRANGE", "list", "0", "-1"]).await,
+        RespValue::array(expected)
+    );
+
+    // A streamed reply followed by an ordinary one in the same pipeline
+    client
+        .send(&["ZRANGE", "zset", "0", "-1", "WITHSCORES"])
+        .await;
+    client.send(&["PING"]).await;
+    let RespValue::Array(Some(items)) = client.read_reply().await.unwrap() else {
+        panic!("expected an array");
+    };
+    assert_eq!(items.len(), LARGE * 2);
+    assert_eq!(items[0], RespValue::bulk_string("e0"));
+    assert_eq!(
+        items[LARGE * 2 - 1],
+        RespValue::bulk_string((LARGE - 1).to_string())
+    );
+    assert_eq!(
+        client.read_reply().await.unwrap(),
+        RespValue::simple_string("PONG")
+    );
+
+    // RESP3 clients get the set type, streamed the same way
+    let mut sadd = vec!["SADD", "set"];
+    sadd.extend(elements.iter().map(String::as_str));
+    client.command(&sadd).await;
+    client.command(&["HELLO", "3"]).await;
+    let RespValue::Set(members) = client.command(&["SMEMBERS", "set"]).await else {
+        panic!("expected a set");
+    };
+    assert_eq!(members.len(), LARGE);
+}
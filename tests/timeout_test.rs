@@ -0,0 +1,50 @@
+//! Tests for closing idle connections after `timeout` seconds
+
+mod common;
+
+use aikv::protocol::RespValue;
+use common::{start_server, TestClient};
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+#[tokio::test]
+async fn test_idle_connection_is_closed_after_timeout() {
+    let addr = start_server(|server| server.set_idle_timeout(1)).await;
+    let mut idle = TestClient::connect(&addr).await;
+    let mut active = TestClient::connect(&addr).await;
+
+    // Pinging within the window keeps resetting the idle timer
+    for _ in 0..4 {
+        sleep(Duration::from_millis(400)).await;
+        assert_eq!(
+            active.command(&["PING"]).await,
+            RespValue::simple_string("PONG")
+        );
+    }
+
+    // By now the idle client has been silent for over a second
+    let reply = timeout(Duration::from_secs(2), idle.read_reply())
+        .await
+        .expect("idle connection was not closed");
+    assert_eq!(reply, None);
+}
+
+#[tokio::test]
+async fn test_timeout_is_a_runtime_config() {
+    let addr = start_server(|_| {}).await;
+    let mut client = TestClient::connect(&addr).await;
+
+    assert_eq!(
+        client.command(&["CONFIG", "GET", "timeout"]).await,
+        RespValue::array(vec![
+            RespValue::bulk_string("timeout"),
+            RespValue::bulk_string("0")
+        ])
+    );
+    assert_eq!(
+        client.command(&["CONFIG", "SET", "timeout", "300"]).await,
+        RespValue::ok()
+    );
+    let reply = client.command(&["CONFIG", "SET", "timeout", "-1"]).await;
+    assert!(matches!(reply, RespValue::Error(_)), "{:?}", reply);
+}
@@ -0,0 +1,74 @@
+//! Tests for TLS client connections
+
+mod common;
+
+use aikv::server::TlsConfig;
+use common::start_server;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls");
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("Failed to find a free port")
+        .port()
+}
+
+/// A connector trusting the CA that signed the fixture server certificate
+fn connector() -> TlsConnector {
+    let ca = std::fs::read(format!("{}/ca.crt", FIXTURES)).expect("Failed to read CA");
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut ca.as_slice()) {
+        roots.add(cert.expect("Invalid CA")).expect("Invalid CA");
+    }
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    TlsConnector::from(Arc::new(config))
+}
+
+#[tokio::test]
+async fn test_tls_ping_pong() {
+    let tls_addr = format!("127.0.0.1:{}", free_port());
+    let tls_config = TlsConfig::new(
+        format!("{}/server.crt", FIXTURES),
+        format!("{}/server.key", FIXTURES),
+    );
+    let server_tls_addr = tls_addr.clone();
+    let addr = start_server(move |server| {
+        server
+            .set_tls(server_tls_addr, &tls_config)
+            .expect("Failed to load TLS fixtures");
+    })
+    .await;
+
+    let stream = TcpStream::connect(&tls_addr).await.unwrap();
+    let mut stream = connector()
+        .connect(ServerName::try_from("localhost").unwrap(), stream)
+        .await
+        .expect("TLS handshake failed");
+
+    stream.write_all(b"*1\r\n$4\r\nPING\r\n").await.unwrap();
+    let mut reply = [0u8; 7];
+    stream.read_exact(&mut reply).await.unwrap();
+    assert_eq!(&reply, b"+PONG\r\n");
+
+    // The plaintext port keeps working alongside the TLS one
+    let mut plain = common::TestClient::connect(&addr).await;
+    assert_eq!(
+        plain.command(&["PING"]).await,
+        aikv::protocol::RespValue::simple_string("PONG")
+    );
+}
+
+#[test]
+fn test_tls_config_rejects_missing_files() {
+    let config = TlsConfig::new("/nonexistent/server.crt", "/nonexistent/server.key");
+    assert!(config.acceptor().is_err());
+}
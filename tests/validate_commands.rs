@@ -1,4 +1,4 @@
-use aikv::command::CommandExecutor;
+use aikv::command::{ClientContext, CommandExecutor};
 use aikv::protocol::RespValue;
 use aikv::StorageEngine;
 
@@ -20,8 +20,7 @@ enum ValidationStatus {
 
 struct CommandValidator {
     executor: CommandExecutor,
-    current_db: usize,
-    client_id: usize,
+    client: ClientContext,
 }
 
 impl CommandValidator {
@@ -30,8 +29,7 @@ impl CommandValidator {
         let executor = CommandExecutor::new(storage);
         Self {
             executor,
-            current_db: 0,
-            client_id: 1,
+            client: ClientContext::new(1),
         }
     }
 
@@ -80,10 +78,7 @@ impl CommandValidator {
     }
 
     fn test_ping(&mut self) -> CommandValidation {
-        match self
-            .executor
-            .execute("PING", &[], &mut self.current_db, self.client_id)
-        {
+        match self.executor.execute("PING", &[], &mut self.client) {
             Ok(resp) => {
                 if matches!(resp, RespValue::SimpleString(_)) {
                     CommandValidation {